@@ -0,0 +1,349 @@
+use std::io::{self, Cursor, Read, Seek};
+
+use crate::ttf_reader::{FromTTFReader, TableTag, TrueTypeFontReader, TrueTypeFontReaderError};
+
+/// The graphic type of PNG images, which every CBDT image format this reads holds.
+pub const PNG_GRAPHIC_TYPE: [u8; 4] = *b"png ";
+
+/// The graphic type of sbix glyphs that stand for another glyph's graphic in the same strike.
+const DUPLICATE_GRAPHIC_TYPE: [u8; 4] = *b"dupe";
+
+/// The size and placement of a bitmap, in pixels, with bearings from the glyph origin to the left and top edges.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BitmapMetrics {
+	pub height: u8,
+	pub width: u8,
+	pub bearing_x: i8,
+	pub bearing_y: i8,
+	pub advance: u8,
+}
+
+impl FromTTFReader for BitmapMetrics {
+	type Input = bool; // Whether these are big metrics, which have vertical metrics after the horizontal ones
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, big: bool) -> Result<BitmapMetrics, TrueTypeFontReaderError> {
+		let metrics = BitmapMetrics {
+			height: ttf_reader.read_bytes()?,
+			width: ttf_reader.read_bytes()?,
+			bearing_x: ttf_reader.read_bytes()?,
+			bearing_y: ttf_reader.read_bytes()?,
+			advance: ttf_reader.read_bytes()?,
+		};
+		if big {
+			ttf_reader.skip(3)?; // Vertical bearings and advance
+		}
+		Ok(metrics)
+	}
+}
+
+/// Where in CBDT the images of a run of glyphs are, by the index format they're stored in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GlyphOffsets {
+	Offsets(Vec<u32>), // Formats 1 and 3, one per glyph in the run and one more for the end of the last
+	Constant { image_size: u32, metrics: BitmapMetrics }, // Format 2, images all the same size one after another
+	Sparse(Vec<(u16, u32)>), // Format 4, glyph ids and offsets, sorted, with one more for the end of the last
+	SparseConstant { image_size: u32, metrics: BitmapMetrics, glyph_ids: Vec<u16> }, // Format 5
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexSubtable {
+	pub first_glyph_id: u16,
+	pub last_glyph_id: u16,
+	pub image_format: u16,
+	pub image_data_offset: u32, // From the start of CBDT, with the glyph offsets from here
+	pub glyph_offsets: GlyphOffsets,
+}
+
+/// The bitmaps of one size, or strike.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitmapSize {
+	pub start_glyph_id: u16,
+	pub end_glyph_id: u16,
+	pub pixels_per_em_x: u8,
+	pub pixels_per_em_y: u8,
+	pub bit_depth: u8, // 32 for colour bitmaps
+	pub index_subtables: Vec<IndexSubtable>,
+}
+
+pub struct ColourBitmapLocationTable {
+	pub strikes: Vec<BitmapSize>,
+}
+
+impl FromTTFReader for ColourBitmapLocationTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<ColourBitmapLocationTable, TrueTypeFontReaderError> {
+		let offset = offset as u64;
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+
+		// Version 2 is the layout of EBLC, which CBLC only extends with the colour image formats.
+		let major_version: u16 = ttf_reader.read_bytes()?;
+		let minor_version: u16 = ttf_reader.read_bytes()?;
+		if major_version != 2 && major_version != 3 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::ColourBitmapLocation, major_version, minor_version));
+		}
+		let number_of_sizes: u32 = ttf_reader.read_bytes()?;
+
+		let mut size_headers: Vec<(u32, u32, u16, u16, u8, u8, u8)> = Vec::new();
+		for _ in 0..number_of_sizes {
+			let index_subtable_array_offset: u32 = ttf_reader.read_bytes()?;
+			let _index_tables_size: u32 = ttf_reader.read_bytes()?;
+			let number_of_index_subtables: u32 = ttf_reader.read_bytes()?;
+			let _colour_ref: u32 = ttf_reader.read_bytes()?;
+			ttf_reader.skip(24)?; // Horizontal and vertical line metrics
+			let start_glyph_id: u16 = ttf_reader.read_bytes()?;
+			let end_glyph_id: u16 = ttf_reader.read_bytes()?;
+			let pixels_per_em_x: u8 = ttf_reader.read_bytes()?;
+			let pixels_per_em_y: u8 = ttf_reader.read_bytes()?;
+			let bit_depth: u8 = ttf_reader.read_bytes()?;
+			let _flags: i8 = ttf_reader.read_bytes()?;
+			size_headers.push((index_subtable_array_offset, number_of_index_subtables, start_glyph_id, end_glyph_id, pixels_per_em_x, pixels_per_em_y, bit_depth));
+		}
+
+		let mut strikes: Vec<BitmapSize> = Vec::with_capacity(size_headers.len());
+		for (index_subtable_array_offset, number_of_index_subtables, start_glyph_id, end_glyph_id, pixels_per_em_x, pixels_per_em_y, bit_depth) in size_headers {
+			let array_start = offset + index_subtable_array_offset as u64;
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(array_start))?;
+			let mut index_subtable_records: Vec<(u16, u16, u32)> = Vec::new();
+			for _ in 0..number_of_index_subtables {
+				index_subtable_records.push((ttf_reader.read_bytes()?, ttf_reader.read_bytes()?, ttf_reader.read_bytes()?));
+			}
+
+			let mut index_subtables: Vec<IndexSubtable> = Vec::with_capacity(index_subtable_records.len());
+			for (first_glyph_id, last_glyph_id, additional_offset) in index_subtable_records {
+				if last_glyph_id < first_glyph_id {
+					return Err(TrueTypeFontReaderError::InvalidData(TableTag::ColourBitmapLocation, "index subtable should end on or after the glyph it starts with"));
+				}
+				ttf_reader.buffer_reader.seek(io::SeekFrom::Start(array_start + additional_offset as u64))?;
+				let index_format: u16 = ttf_reader.read_bytes()?;
+				let image_format: u16 = ttf_reader.read_bytes()?;
+				let image_data_offset: u32 = ttf_reader.read_bytes()?;
+				let number_of_offsets = (last_glyph_id - first_glyph_id) as usize + 2;
+				let glyph_offsets = match index_format {
+					1 => {
+						let mut offsets: Vec<u32> = Vec::with_capacity(number_of_offsets);
+						for _ in 0..number_of_offsets {
+							offsets.push(ttf_reader.read_bytes()?);
+						}
+						GlyphOffsets::Offsets(offsets)
+					},
+					2 => GlyphOffsets::Constant { image_size: ttf_reader.read_bytes()?, metrics: ttf_reader.read(true)? },
+					3 => {
+						let mut offsets: Vec<u32> = Vec::with_capacity(number_of_offsets);
+						for _ in 0..number_of_offsets {
+							offsets.push(ttf_reader.read_bytes::<u16>()? as u32);
+						}
+						GlyphOffsets::Offsets(offsets)
+					},
+					4 => {
+						let number_of_glyphs: u32 = ttf_reader.read_bytes()?;
+						let mut glyphs: Vec<(u16, u32)> = Vec::new();
+						for _ in 0..=number_of_glyphs {
+							glyphs.push((ttf_reader.read_bytes()?, ttf_reader.read_bytes::<u16>()? as u32));
+						}
+						GlyphOffsets::Sparse(glyphs)
+					},
+					5 => {
+						let image_size: u32 = ttf_reader.read_bytes()?;
+						let metrics: BitmapMetrics = ttf_reader.read(true)?;
+						let number_of_glyphs: u32 = ttf_reader.read_bytes()?;
+						let mut glyph_ids: Vec<u16> = Vec::new();
+						for _ in 0..number_of_glyphs {
+							glyph_ids.push(ttf_reader.read_bytes()?);
+						}
+						GlyphOffsets::SparseConstant { image_size, metrics, glyph_ids }
+					},
+					_ => return Err(TrueTypeFontReaderError::InvalidData(TableTag::ColourBitmapLocation, "index subtable should be format 1, 2, 3, 4 or 5")),
+				};
+				index_subtables.push(IndexSubtable { first_glyph_id, last_glyph_id, image_format, image_data_offset, glyph_offsets });
+			}
+			strikes.push(BitmapSize { start_glyph_id, end_glyph_id, pixels_per_em_x, pixels_per_em_y, bit_depth, index_subtables });
+		}
+
+		Ok(ColourBitmapLocationTable {
+			strikes,
+		})
+	}
+}
+
+impl IndexSubtable {
+	/// Where a glyph's image is in CBDT and how long it is, along with its metrics when they're kept here rather than with the image.
+	pub fn image_location(&self, glyph_id: u16) -> Option<(usize, usize, Option<BitmapMetrics>)> {
+		if glyph_id < self.first_glyph_id || glyph_id > self.last_glyph_id {
+			return None;
+		}
+		let index = (glyph_id - self.first_glyph_id) as usize;
+		let (start, end, metrics) = match &self.glyph_offsets {
+			GlyphOffsets::Offsets(offsets) => (*offsets.get(index)?, *offsets.get(index + 1)?, None),
+			GlyphOffsets::Constant { image_size, metrics } => (index as u32 * image_size, (index as u32 + 1) * image_size, Some(*metrics)),
+			GlyphOffsets::Sparse(glyphs) => {
+				let position = glyphs[..glyphs.len().saturating_sub(1)].binary_search_by_key(&glyph_id, |&(glyph_id, _)| glyph_id).ok()?;
+				(glyphs[position].1, glyphs[position + 1].1, None)
+			},
+			GlyphOffsets::SparseConstant { image_size, metrics, glyph_ids } => {
+				let position = glyph_ids.binary_search(&glyph_id).ok()? as u32;
+				(position * image_size, (position + 1) * image_size, Some(*metrics))
+			},
+		};
+		// Glyphs without an image have the same offset as the glyph after them.
+		if end <= start {
+			return None;
+		}
+		Some((self.image_data_offset as usize + start as usize, (end - start) as usize, metrics))
+	}
+}
+
+pub struct ColourBitmapDataTable {
+	pub data: Vec<u8>, // The whole table, as CBLC gives offsets from its start
+}
+
+impl FromTTFReader for ColourBitmapDataTable {
+	type Input = (u32, u32); // offset, length
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (offset, length): (u32, u32)) -> Result<ColourBitmapDataTable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+
+		let major_version: u16 = ttf_reader.read_bytes()?;
+		let minor_version: u16 = ttf_reader.read_bytes()?;
+		if major_version != 2 && major_version != 3 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::ColourBitmapData, major_version, minor_version));
+		}
+
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+		let mut data = vec![0u8; length as usize];
+		ttf_reader.buffer_reader.read_exact(&mut data)?;
+
+		Ok(ColourBitmapDataTable {
+			data,
+		})
+	}
+}
+
+/// One size of sbix graphics, with where each glyph's graphic is from the start of the strike.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SbixStrike {
+	pub pixels_per_em: u16,
+	pub pixels_per_inch: u16,
+	pub offset: u32, // From the start of sbix
+	pub glyph_data_offsets: Vec<u32>, // One per glyph and one more for the end of the last
+}
+
+pub struct StandardBitmapGraphicsTable {
+	pub flags: u16, // Bit 1 asks for the outlines to be drawn as well as the graphics
+	pub strikes: Vec<SbixStrike>,
+	pub data: Vec<u8>, // The whole table
+}
+
+impl FromTTFReader for StandardBitmapGraphicsTable {
+	type Input = (u32, u32, u16); // offset, length, number of glyphs
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (offset, length, number_of_glyphs): (u32, u32, u16)) -> Result<StandardBitmapGraphicsTable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+
+		let version: u16 = ttf_reader.read_bytes()?;
+		if version != 1 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::StandardBitmapGraphics, version, 0));
+		}
+		let flags: u16 = ttf_reader.read_bytes()?;
+		let number_of_strikes: u32 = ttf_reader.read_bytes()?;
+		let mut strike_offsets: Vec<u32> = Vec::new();
+		for _ in 0..number_of_strikes {
+			strike_offsets.push(ttf_reader.read_bytes()?);
+		}
+
+		let mut strikes: Vec<SbixStrike> = Vec::with_capacity(strike_offsets.len());
+		for strike_offset in strike_offsets {
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64 + strike_offset as u64))?;
+			let pixels_per_em: u16 = ttf_reader.read_bytes()?;
+			let pixels_per_inch: u16 = ttf_reader.read_bytes()?;
+			let mut glyph_data_offsets: Vec<u32> = Vec::with_capacity(number_of_glyphs as usize + 1);
+			for _ in 0..=number_of_glyphs {
+				glyph_data_offsets.push(ttf_reader.read_bytes()?);
+			}
+			strikes.push(SbixStrike { pixels_per_em, pixels_per_inch, offset: strike_offset, glyph_data_offsets });
+		}
+
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+		let mut data = vec![0u8; length as usize];
+		ttf_reader.buffer_reader.read_exact(&mut data)?;
+
+		Ok(StandardBitmapGraphicsTable {
+			flags,
+			strikes,
+			data,
+		})
+	}
+}
+
+impl StandardBitmapGraphicsTable {
+	/// A glyph's origin offset, graphic type and graphic in one strike, following a 'dupe' to the glyph it stands for.
+	fn glyph_data(&self, strike: &SbixStrike, glyph_id: u16, follow_duplicate: bool) -> Option<(i16, i16, [u8; 4], &[u8])> {
+		let start = *strike.glyph_data_offsets.get(glyph_id as usize)? as usize;
+		let end = *strike.glyph_data_offsets.get(glyph_id as usize + 1)? as usize;
+		if end < start + 8 {
+			return None; // No graphic for this glyph
+		}
+		let glyph_data = self.data.get(strike.offset as usize + start..strike.offset as usize + end)?;
+		let left = i16::from_be_bytes([glyph_data[0], glyph_data[1]]);
+		let bottom = i16::from_be_bytes([glyph_data[2], glyph_data[3]]);
+		let graphic_type = [glyph_data[4], glyph_data[5], glyph_data[6], glyph_data[7]];
+		let data = &glyph_data[8..];
+		match graphic_type {
+			DUPLICATE_GRAPHIC_TYPE if follow_duplicate && data.len() >= 2 => self.glyph_data(strike, u16::from_be_bytes([data[0], data[1]]), false),
+			DUPLICATE_GRAPHIC_TYPE => None,
+			_ => Some((left, bottom, graphic_type, data)),
+		}
+	}
+}
+
+/// A glyph's image in one strike, placed in pixels of that strike from the glyph origin to its bottom left corner.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitmapImage<'a> {
+	pub pixels_per_em: u16,
+	pub left: i16,
+	pub bottom: i16,
+	pub graphic_type: [u8; 4], // An sbix graphic type, such as 'png ', 'jpg ' or 'tiff'
+	pub data: &'a [u8],
+}
+
+/// The tables colour bitmap glyphs come from: Google's CBLC and CBDT, or Apple's sbix.
+pub enum BitmapGlyphs {
+	ColourBitmaps(ColourBitmapLocationTable, ColourBitmapDataTable),
+	StandardBitmapGraphics(StandardBitmapGraphicsTable),
+}
+
+impl BitmapGlyphs {
+	/// A glyph's image in every strike that has one.
+	pub fn images(&self, glyph_id: u16) -> Vec<BitmapImage<'_>> {
+		match self {
+			BitmapGlyphs::ColourBitmaps(location_table, data_table) => location_table.strikes.iter().filter(|strike| strike.start_glyph_id <= glyph_id && glyph_id <= strike.end_glyph_id).filter_map(|strike| {
+				let (subtable, (start, length, metrics)) = strike.index_subtables.iter().find_map(|subtable| Some((subtable, subtable.image_location(glyph_id)?)))?;
+				let image = data_table.data.get(start..start + length)?;
+				// Formats 17 and 18 start with their metrics, small or big, and 19 uses the metrics from CBLC. All three then have the PNG's length.
+				let (metrics, data) = match (subtable.image_format, metrics) {
+					(17, _) => (read_metrics(image, false)?, image.get(5..)?),
+					(18, _) => (read_metrics(image, true)?, image.get(8..)?),
+					(19, Some(metrics)) => (metrics, image),
+					_ => return None,
+				};
+				let png_length = u32::from_be_bytes(data.get(..4)?.try_into().ok()?) as usize;
+				Some(BitmapImage {
+					pixels_per_em: strike.pixels_per_em_y as u16,
+					left: metrics.bearing_x as i16,
+					bottom: metrics.bearing_y as i16 - metrics.height as i16,
+					graphic_type: PNG_GRAPHIC_TYPE,
+					data: data.get(4..4 + png_length)?,
+				})
+			}).collect(),
+			BitmapGlyphs::StandardBitmapGraphics(table) => table.strikes.iter().filter_map(|strike| {
+				let (left, bottom, graphic_type, data) = table.glyph_data(strike, glyph_id, true)?;
+				Some(BitmapImage { pixels_per_em: strike.pixels_per_em, left, bottom, graphic_type, data })
+			}).collect(),
+		}
+	}
+}
+
+fn read_metrics(data: &[u8], big: bool) -> Option<BitmapMetrics> {
+	let mut ttf_reader = TrueTypeFontReader::from_source(Cursor::new(data));
+	ttf_reader.read(big).ok()
+}
@@ -0,0 +1,32 @@
+struct VertexInput {
+	@location(0) position: vec2<f32>,
+	@location(1) texture_coordinates: vec2<f32>, // In texels of the atlas
+};
+
+struct VertexOutput {
+	@builtin(position) clip_position: vec4<f32>,
+	@location(0) texture_coordinates: vec2<f32>,
+};
+
+@vertex
+fn vs_main(
+	model: VertexInput,
+) -> VertexOutput {
+	var out: VertexOutput;
+	out.clip_position = vec4<f32>(model.position, 0.0, 1.0);
+	out.texture_coordinates = model.texture_coordinates;
+	return out;
+}
+
+// Every bitmap glyph's image, packed together.
+@group(0) @binding(0)
+var atlas: texture_2d<f32>;
+
+@group(0) @binding(1)
+var atlas_sampler: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+	let size = vec2<f32>(textureDimensions(atlas));
+	return textureSample(atlas, atlas_sampler, in.texture_coordinates / size);
+}
@@ -0,0 +1,688 @@
+//! Decompression of Brotli (RFC 7932) streams, as used by WOFF2 files.
+
+use crate::inflate::{BitReader, Huffman, InflateError};
+
+// The static dictionary from appendix A of the RFC, words grouped by length and sorted within each length.
+const DICTIONARY: &[u8] = include_bytes!("brotli_dictionary.bin");
+// The number of dictionary words of each length is 2 to the power of these, lengths other than 4 to 24 have no words.
+const DICTIONARY_SIZE_BITS: [u8; 25] = [0, 0, 0, 0, 10, 10, 11, 11, 10, 10, 10, 10, 10, 9, 9, 8, 7, 7, 8, 7, 7, 6, 6, 5, 5];
+
+const NUMBER_OF_LITERALS: usize = 256;
+const NUMBER_OF_INSERT_AND_COPY_CODES: usize = 704;
+const NUMBER_OF_BLOCK_COUNT_CODES: usize = 26;
+const LITERAL_CONTEXTS: usize = 64;
+const DISTANCE_CONTEXTS: usize = 4;
+
+// The order code length code lengths are stored in, for complex prefix codes.
+const CODE_LENGTH_ORDER: [usize; 18] = [1, 2, 3, 4, 0, 5, 17, 6, 16, 7, 8, 9, 10, 11, 12, 13, 14, 15];
+// Code length code lengths are themselves stored with this fixed code, as the code lengths of the values 0 to 5.
+const CODE_LENGTH_CODE_LENGTHS: [u8; 6] = [2, 4, 3, 2, 2, 4];
+const REPEAT_PREVIOUS_CODE_LENGTH: u16 = 16;
+const INITIAL_REPEATED_CODE_LENGTH: u8 = 8;
+
+const BLOCK_COUNT_BASES: [u32; 26] = [1, 5, 9, 13, 17, 25, 33, 41, 49, 65, 81, 97, 113, 145, 177, 209, 241, 305, 369, 497, 753, 1265, 2289, 4337, 8433, 16625];
+const BLOCK_COUNT_EXTRA_BITS: [u8; 26] = [2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 6, 6, 7, 8, 9, 10, 11, 12, 13, 24];
+const INSERT_LENGTH_BASES: [u32; 24] = [0, 1, 2, 3, 4, 5, 6, 8, 10, 14, 18, 26, 34, 50, 66, 98, 130, 194, 322, 578, 1090, 2114, 6210, 22594];
+const INSERT_LENGTH_EXTRA_BITS: [u8; 24] = [0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 7, 8, 9, 10, 12, 14, 24];
+const COPY_LENGTH_BASES: [u32; 24] = [2, 3, 4, 5, 6, 7, 8, 9, 10, 12, 14, 18, 22, 30, 38, 54, 70, 102, 134, 198, 326, 582, 1094, 2118];
+const COPY_LENGTH_EXTRA_BITS: [u8; 24] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 7, 8, 9, 10, 24];
+// Insert and copy codes come in cells of 64: the first insert length code and copy length code of each cell, and whether it reuses the last distance.
+const INSERT_AND_COPY_CELLS: [(usize, usize, bool); 11] = [(0, 0, true), (0, 8, true), (0, 0, false), (0, 8, false), (8, 0, false), (8, 8, false), (0, 16, false), (16, 0, false), (8, 16, false), (16, 8, false), (16, 16, false)];
+// Distance codes below 16 refer to recent distances: how far back in the recent distances, and what to add to it.
+const RECENT_DISTANCE_CODES: [(usize, i64); 16] = [(0, 0), (1, 0), (2, 0), (3, 0), (0, -1), (0, 1), (0, -2), (0, 2), (0, -3), (0, 3), (1, -1), (1, 1), (1, -2), (1, 2), (1, -3), (1, 3)];
+const NUMBER_OF_RECENT_DISTANCE_CODES: usize = 16;
+const INITIAL_RECENT_DISTANCES: [usize; 4] = [4, 11, 15, 16];
+
+// The UTF-8 context of a literal comes from its two preceding bytes, looked up in these and added together.
+const UTF8_CONTEXT_PREVIOUS: [u8; 256] = [
+	0, 0, 0, 0, 0, 0, 0, 0, 0, 4, 4, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+	8, 12, 16, 12, 12, 20, 12, 16, 24, 28, 12, 12, 32, 12, 36, 12, 44, 44, 44, 44, 44, 44, 44, 44, 44, 44, 32, 32, 24, 40, 28, 12,
+	12, 48, 52, 52, 52, 48, 52, 52, 52, 48, 52, 52, 52, 52, 52, 48, 52, 52, 52, 52, 52, 48, 52, 52, 52, 52, 52, 24, 12, 28, 12, 12,
+	12, 56, 60, 60, 60, 56, 60, 60, 60, 56, 60, 60, 60, 60, 60, 56, 60, 60, 60, 60, 60, 56, 60, 60, 60, 60, 60, 24, 12, 28, 12, 0,
+	0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1,
+	0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1,
+	2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3,
+	2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3, 2, 3,
+];
+const UTF8_CONTEXT_BEFORE: [u8; 256] = [
+	0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+	0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1,
+	1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1,
+	1, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 1, 1, 1, 1, 0,
+	0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+	0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+	0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+	2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+];
+
+#[derive(Debug, PartialEq)]
+pub enum BrotliError {
+	UnexpectedEnd,
+	InvalidWindowSize,
+	InvalidMetaBlockHeader,
+	InvalidPrefixCode,
+	InvalidContextMap,
+	MetaBlockLengthExceeded,
+	InvalidDistance(usize),
+	InvalidDictionaryReference(usize, usize), // copy length, word id
+	OutputTooLong,
+}
+
+impl From<InflateError> for BrotliError {
+	fn from(value: InflateError) -> Self {
+		match value {
+			InflateError::UnexpectedEnd => BrotliError::UnexpectedEnd,
+			_ => BrotliError::InvalidPrefixCode,
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+enum WordTransform {
+	Identity,
+	OmitLast(usize),
+	OmitFirst(usize),
+	UppercaseFirst,
+	UppercaseAll,
+}
+
+// The transforms that can be applied to dictionary words: prefix, transform, suffix.
+const TRANSFORMS: [(&[u8], WordTransform, &[u8]); 121] = [
+	(b"", WordTransform::Identity, b""),
+	(b"", WordTransform::Identity, b" "),
+	(b" ", WordTransform::Identity, b" "),
+	(b"", WordTransform::OmitFirst(1), b""),
+	(b"", WordTransform::UppercaseFirst, b" "),
+	(b"", WordTransform::Identity, b" the "),
+	(b" ", WordTransform::Identity, b""),
+	(b"s ", WordTransform::Identity, b" "),
+	(b"", WordTransform::Identity, b" of "),
+	(b"", WordTransform::UppercaseFirst, b""),
+	(b"", WordTransform::Identity, b" and "),
+	(b"", WordTransform::OmitFirst(2), b""),
+	(b"", WordTransform::OmitLast(1), b""),
+	(b", ", WordTransform::Identity, b" "),
+	(b"", WordTransform::Identity, b", "),
+	(b" ", WordTransform::UppercaseFirst, b" "),
+	(b"", WordTransform::Identity, b" in "),
+	(b"", WordTransform::Identity, b" to "),
+	(b"e ", WordTransform::Identity, b" "),
+	(b"", WordTransform::Identity, b"\""),
+	(b"", WordTransform::Identity, b"."),
+	(b"", WordTransform::Identity, b"\">"),
+	(b"", WordTransform::Identity, b"\x0a"),
+	(b"", WordTransform::OmitLast(3), b""),
+	(b"", WordTransform::Identity, b"]"),
+	(b"", WordTransform::Identity, b" for "),
+	(b"", WordTransform::OmitFirst(3), b""),
+	(b"", WordTransform::OmitLast(2), b""),
+	(b"", WordTransform::Identity, b" a "),
+	(b"", WordTransform::Identity, b" that "),
+	(b" ", WordTransform::UppercaseFirst, b""),
+	(b"", WordTransform::Identity, b". "),
+	(b".", WordTransform::Identity, b""),
+	(b" ", WordTransform::Identity, b", "),
+	(b"", WordTransform::OmitFirst(4), b""),
+	(b"", WordTransform::Identity, b" with "),
+	(b"", WordTransform::Identity, b"'"),
+	(b"", WordTransform::Identity, b" from "),
+	(b"", WordTransform::Identity, b" by "),
+	(b"", WordTransform::OmitFirst(5), b""),
+	(b"", WordTransform::OmitFirst(6), b""),
+	(b" the ", WordTransform::Identity, b""),
+	(b"", WordTransform::OmitLast(4), b""),
+	(b"", WordTransform::Identity, b". The "),
+	(b"", WordTransform::UppercaseAll, b""),
+	(b"", WordTransform::Identity, b" on "),
+	(b"", WordTransform::Identity, b" as "),
+	(b"", WordTransform::Identity, b" is "),
+	(b"", WordTransform::OmitLast(7), b""),
+	(b"", WordTransform::OmitLast(1), b"ing "),
+	(b"", WordTransform::Identity, b"\x0a\x09"),
+	(b"", WordTransform::Identity, b":"),
+	(b" ", WordTransform::Identity, b". "),
+	(b"", WordTransform::Identity, b"ed "),
+	(b"", WordTransform::OmitFirst(9), b""),
+	(b"", WordTransform::OmitFirst(7), b""),
+	(b"", WordTransform::OmitLast(6), b""),
+	(b"", WordTransform::Identity, b"("),
+	(b"", WordTransform::UppercaseFirst, b", "),
+	(b"", WordTransform::OmitLast(8), b""),
+	(b"", WordTransform::Identity, b" at "),
+	(b"", WordTransform::Identity, b"ly "),
+	(b" the ", WordTransform::Identity, b" of "),
+	(b"", WordTransform::OmitLast(5), b""),
+	(b"", WordTransform::OmitLast(9), b""),
+	(b" ", WordTransform::UppercaseFirst, b", "),
+	(b"", WordTransform::UppercaseFirst, b"\""),
+	(b".", WordTransform::Identity, b"("),
+	(b"", WordTransform::UppercaseAll, b" "),
+	(b"", WordTransform::UppercaseFirst, b"\">"),
+	(b"", WordTransform::Identity, b"=\""),
+	(b" ", WordTransform::Identity, b"."),
+	(b".com/", WordTransform::Identity, b""),
+	(b" the ", WordTransform::Identity, b" of the "),
+	(b"", WordTransform::UppercaseFirst, b"'"),
+	(b"", WordTransform::Identity, b". This "),
+	(b"", WordTransform::Identity, b","),
+	(b".", WordTransform::Identity, b" "),
+	(b"", WordTransform::UppercaseFirst, b"("),
+	(b"", WordTransform::UppercaseFirst, b"."),
+	(b"", WordTransform::Identity, b" not "),
+	(b" ", WordTransform::Identity, b"=\""),
+	(b"", WordTransform::Identity, b"er "),
+	(b" ", WordTransform::UppercaseAll, b" "),
+	(b"", WordTransform::Identity, b"al "),
+	(b" ", WordTransform::UppercaseAll, b""),
+	(b"", WordTransform::Identity, b"='"),
+	(b"", WordTransform::UppercaseAll, b"\""),
+	(b"", WordTransform::UppercaseFirst, b". "),
+	(b" ", WordTransform::Identity, b"("),
+	(b"", WordTransform::Identity, b"ful "),
+	(b" ", WordTransform::UppercaseFirst, b". "),
+	(b"", WordTransform::Identity, b"ive "),
+	(b"", WordTransform::Identity, b"less "),
+	(b"", WordTransform::UppercaseAll, b"'"),
+	(b"", WordTransform::Identity, b"est "),
+	(b" ", WordTransform::UppercaseFirst, b"."),
+	(b"", WordTransform::UppercaseAll, b"\">"),
+	(b" ", WordTransform::Identity, b"='"),
+	(b"", WordTransform::UppercaseFirst, b","),
+	(b"", WordTransform::Identity, b"ize "),
+	(b"", WordTransform::UppercaseAll, b"."),
+	(b"\xc2\xa0", WordTransform::Identity, b""),
+	(b" ", WordTransform::Identity, b","),
+	(b"", WordTransform::UppercaseFirst, b"=\""),
+	(b"", WordTransform::UppercaseAll, b"=\""),
+	(b"", WordTransform::Identity, b"ous "),
+	(b"", WordTransform::UppercaseAll, b", "),
+	(b"", WordTransform::UppercaseFirst, b"='"),
+	(b" ", WordTransform::UppercaseFirst, b","),
+	(b" ", WordTransform::UppercaseAll, b"=\""),
+	(b" ", WordTransform::UppercaseAll, b", "),
+	(b"", WordTransform::UppercaseAll, b","),
+	(b"", WordTransform::UppercaseAll, b"("),
+	(b"", WordTransform::UppercaseAll, b". "),
+	(b" ", WordTransform::UppercaseAll, b"."),
+	(b"", WordTransform::UppercaseAll, b"='"),
+	(b" ", WordTransform::UppercaseAll, b". "),
+	(b" ", WordTransform::UppercaseFirst, b"=\""),
+	(b" ", WordTransform::UppercaseAll, b"='"),
+	(b" ", WordTransform::UppercaseFirst, b"='"),
+];
+
+#[derive(Debug, Clone, Copy)]
+enum ContextMode {
+	LeastSignificantBits,
+	MostSignificantBits,
+	Utf8,
+	Signed,
+}
+
+impl ContextMode {
+	fn from_bits(bits: u32) -> Self {
+		match bits {
+			0 => ContextMode::LeastSignificantBits,
+			1 => ContextMode::MostSignificantBits,
+			2 => ContextMode::Utf8,
+			_ => ContextMode::Signed,
+		}
+	}
+
+	/// Picks one of 64 contexts for a literal, from the two bytes before it.
+	fn literal_context(self, previous: u8, before_previous: u8) -> usize {
+		match self {
+			ContextMode::LeastSignificantBits => (previous & 0x3F) as usize,
+			ContextMode::MostSignificantBits => (previous >> 2) as usize,
+			ContextMode::Utf8 => (UTF8_CONTEXT_PREVIOUS[previous as usize] | UTF8_CONTEXT_BEFORE[before_previous as usize]) as usize,
+			ContextMode::Signed => (signed_context(previous) << 3) | signed_context(before_previous),
+		}
+	}
+}
+
+fn signed_context(byte: u8) -> usize {
+	match byte {
+		0 => 0,
+		1..=15 => 1,
+		16..=63 => 2,
+		64..=127 => 3,
+		128..=191 => 4,
+		192..=239 => 5,
+		240..=254 => 6,
+		255 => 7,
+	}
+}
+
+/// A prefix code over some alphabet. A code with only one symbol takes up no bits at all.
+enum PrefixCode {
+	Single(u16),
+	Huffman(Huffman),
+}
+
+impl PrefixCode {
+	fn read(bit_reader: &mut BitReader, alphabet_size: usize) -> Result<Self, BrotliError> {
+		match bit_reader.bits(2)? {
+			1 => Self::read_simple(bit_reader, alphabet_size),
+			skipped_code_lengths => Self::read_complex(bit_reader, alphabet_size, skipped_code_lengths as usize),
+		}
+	}
+
+	/// Simple prefix codes list up to four symbols, with their code lengths implied by how many there are.
+	fn read_simple(bit_reader: &mut BitReader, alphabet_size: usize) -> Result<Self, BrotliError> {
+		let number_of_symbols = bit_reader.bits(2)? as usize + 1;
+		let symbol_bits = (usize::BITS - (alphabet_size - 1).leading_zeros()) as u8;
+		let mut symbols: Vec<usize> = Vec::with_capacity(number_of_symbols);
+		for _ in 0..number_of_symbols {
+			let symbol = bit_reader.bits(symbol_bits)? as usize;
+			if symbol >= alphabet_size || symbols.contains(&symbol) {
+				return Err(BrotliError::InvalidPrefixCode);
+			}
+			symbols.push(symbol);
+		}
+
+		let lengths: &[u8] = match number_of_symbols {
+			1 => return Ok(PrefixCode::Single(symbols[0] as u16)),
+			2 => &[1, 1],
+			3 => &[1, 2, 2],
+			_ => if bit_reader.bit()? == 1 { &[1, 2, 3, 3] } else { &[2, 2, 2, 2] },
+		};
+		let mut code_lengths = vec![0u8; alphabet_size];
+		for (symbol, length) in symbols.iter().zip(lengths) {
+			code_lengths[*symbol] = *length;
+		}
+		Ok(PrefixCode::Huffman(Huffman::new(&code_lengths)?))
+	}
+
+	/// Complex prefix codes store a code length for every symbol, compressed with a code length code and run lengths.
+	fn read_complex(bit_reader: &mut BitReader, alphabet_size: usize, skipped_code_lengths: usize) -> Result<Self, BrotliError> {
+		let code_length_code_code = Huffman::new(&CODE_LENGTH_CODE_LENGTHS).expect("fixed code is complete");
+		let mut code_length_code_lengths = [0u8; 18];
+		let mut space: i32 = 32;
+		let mut number_of_codes = 0;
+		for symbol in CODE_LENGTH_ORDER.iter().skip(skipped_code_lengths) {
+			let length = code_length_code_code.decode(bit_reader)? as u8;
+			code_length_code_lengths[*symbol] = length;
+			if length != 0 {
+				space -= 32 >> length;
+				number_of_codes += 1;
+				if space <= 0 {
+					break;
+				}
+			}
+		}
+		if number_of_codes != 1 && space != 0 {
+			return Err(BrotliError::InvalidPrefixCode);
+		}
+		let code_length_code = match number_of_codes {
+			1 => PrefixCode::Single(code_length_code_lengths.iter().position(|length| *length != 0).expect("one code length is set") as u16),
+			_ => PrefixCode::Huffman(Huffman::new(&code_length_code_lengths)?),
+		};
+
+		let mut code_lengths = vec![0u8; alphabet_size];
+		let mut symbol = 0;
+		let mut space: i32 = 1 << 15;
+		let mut previous_length = INITIAL_REPEATED_CODE_LENGTH;
+		let mut repeat = 0;
+		let mut repeated_length = 0;
+		while symbol < alphabet_size && space > 0 {
+			let code = code_length_code.decode(bit_reader)?;
+			if code < REPEAT_PREVIOUS_CODE_LENGTH {
+				repeat = 0;
+				code_lengths[symbol] = code as u8;
+				if code != 0 {
+					previous_length = code as u8;
+					space -= (1 << 15) >> code;
+				}
+				symbol += 1;
+				continue;
+			}
+
+			// Consecutive repeat codes build on each other, each one multiplying the previous repeat count.
+			let (extra_bits, length) = if code == REPEAT_PREVIOUS_CODE_LENGTH { (2, previous_length) } else { (3, 0) };
+			if repeated_length != length {
+				repeat = 0;
+				repeated_length = length;
+			}
+			let previous_repeat = repeat;
+			if repeat > 0 {
+				repeat = (repeat - 2) << extra_bits;
+			}
+			repeat += bit_reader.bits(extra_bits)? as usize + 3;
+			let repeat_delta = repeat - previous_repeat;
+			if symbol + repeat_delta > alphabet_size {
+				return Err(BrotliError::InvalidPrefixCode);
+			}
+			code_lengths[symbol..symbol + repeat_delta].fill(repeated_length);
+			symbol += repeat_delta;
+			if repeated_length != 0 {
+				space -= (repeat_delta << (15 - repeated_length)) as i32;
+			}
+		}
+		if space != 0 {
+			return Err(BrotliError::InvalidPrefixCode);
+		}
+		Ok(PrefixCode::Huffman(Huffman::new(&code_lengths)?))
+	}
+
+	fn decode(&self, bit_reader: &mut BitReader) -> Result<u16, BrotliError> {
+		match self {
+			PrefixCode::Single(symbol) => Ok(*symbol),
+			PrefixCode::Huffman(huffman) => Ok(huffman.decode(bit_reader)?),
+		}
+	}
+}
+
+/// The block types of one category (literals, insert and copy commands or distances), and which block the decoder is in.
+struct BlockTypes {
+	count: usize,
+	codes: Option<(PrefixCode, PrefixCode)>, // block type code, block count code
+	current: usize,
+	previous: usize,
+	remaining: u32,
+}
+
+impl BlockTypes {
+	fn read(bit_reader: &mut BitReader) -> Result<Self, BrotliError> {
+		let count = read_variable_length(bit_reader)? + 1;
+		if count < 2 {
+			return Ok(BlockTypes { count, codes: None, current: 0, previous: 1, remaining: u32::MAX });
+		}
+
+		let type_code = PrefixCode::read(bit_reader, count + 2)?;
+		let count_code = PrefixCode::read(bit_reader, NUMBER_OF_BLOCK_COUNT_CODES)?;
+		let remaining = read_block_count(bit_reader, &count_code)?;
+		Ok(BlockTypes { count, codes: Some((type_code, count_code)), current: 0, previous: 1, remaining })
+	}
+
+	/// Moves one symbol through the current block, switching block when it has run out, and gives the block type.
+	fn next(&mut self, bit_reader: &mut BitReader) -> Result<usize, BrotliError> {
+		if let (0, Some((type_code, count_code))) = (self.remaining, &self.codes) {
+			let block_type = match type_code.decode(bit_reader)? as usize {
+				0 => self.previous,
+				1 => self.current + 1,
+				code => code - 2,
+			} % self.count;
+			self.previous = self.current;
+			self.current = block_type;
+			self.remaining = read_block_count(bit_reader, count_code)?;
+		}
+		self.remaining = self.remaining.saturating_sub(1);
+		Ok(self.current)
+	}
+}
+
+fn read_block_count(bit_reader: &mut BitReader, count_code: &PrefixCode) -> Result<u32, BrotliError> {
+	let code = count_code.decode(bit_reader)? as usize;
+	Ok(BLOCK_COUNT_BASES[code] + bit_reader.bits(BLOCK_COUNT_EXTRA_BITS[code])?)
+}
+
+/// Reads a number from 0 to 255, stored in as few bits as its magnitude allows.
+fn read_variable_length(bit_reader: &mut BitReader) -> Result<usize, BrotliError> {
+	if bit_reader.bit()? == 0 {
+		return Ok(0);
+	}
+	let bits = bit_reader.bits(3)? as u8;
+	if bits == 0 {
+		return Ok(1);
+	}
+	Ok((1 << bits) + bit_reader.bits(bits)? as usize)
+}
+
+fn read_window_bits(bit_reader: &mut BitReader) -> Result<u8, BrotliError> {
+	if bit_reader.bit()? == 0 {
+		return Ok(16);
+	}
+	match bit_reader.bits(3)? {
+		0 => match bit_reader.bits(3)? {
+			0 => Ok(17),
+			1 => Err(BrotliError::InvalidWindowSize),
+			bits => Ok(8 + bits as u8),
+		},
+		bits => Ok(17 + bits as u8),
+	}
+}
+
+/// Context maps pick the prefix code for each context of each block type, stored run length encoded and optionally move-to-front transformed.
+fn read_context_map(bit_reader: &mut BitReader, number_of_trees: usize, size: usize) -> Result<Vec<u8>, BrotliError> {
+	if number_of_trees < 2 {
+		return Ok(vec![0; size]);
+	}
+
+	let maximum_run_length_prefix = if bit_reader.bit()? == 1 { bit_reader.bits(4)? as usize + 1 } else { 0 };
+	let code = PrefixCode::read(bit_reader, number_of_trees + maximum_run_length_prefix)?;
+	let mut context_map: Vec<u8> = Vec::with_capacity(size);
+	while context_map.len() < size {
+		let symbol = code.decode(bit_reader)? as usize;
+		if symbol == 0 {
+			context_map.push(0);
+		} else if symbol <= maximum_run_length_prefix {
+			let run_length = (1 << symbol) + bit_reader.bits(symbol as u8)? as usize;
+			if context_map.len() + run_length > size {
+				return Err(BrotliError::InvalidContextMap);
+			}
+			context_map.resize(context_map.len() + run_length, 0);
+		} else {
+			context_map.push((symbol - maximum_run_length_prefix) as u8);
+		}
+	}
+
+	if bit_reader.bit()? == 1 {
+		let mut recent_values: Vec<u8> = (0..=255).collect();
+		for value in context_map.iter_mut() {
+			let index = *value as usize;
+			*value = recent_values.remove(index);
+			recent_values.insert(0, *value);
+		}
+	}
+	if context_map.iter().any(|tree| *tree as usize >= number_of_trees) {
+		return Err(BrotliError::InvalidContextMap);
+	}
+	Ok(context_map)
+}
+
+/// Works out a distance from its code, giving whether it should be remembered as a recent distance.
+fn read_distance(bit_reader: &mut BitReader, distance_code: usize, postfix_bits: usize, direct_distances: usize, recent_distances: &[usize; 4]) -> Result<(usize, bool), BrotliError> {
+	if distance_code < NUMBER_OF_RECENT_DISTANCE_CODES {
+		let (index, adjustment) = RECENT_DISTANCE_CODES[distance_code];
+		let distance = recent_distances[index] as i64 + adjustment;
+		if distance <= 0 {
+			return Err(BrotliError::InvalidDistance(0));
+		}
+		return Ok((distance as usize, distance_code != 0));
+	}
+	if distance_code < NUMBER_OF_RECENT_DISTANCE_CODES + direct_distances {
+		return Ok((distance_code - NUMBER_OF_RECENT_DISTANCE_CODES + 1, true));
+	}
+
+	let code = distance_code - NUMBER_OF_RECENT_DISTANCE_CODES - direct_distances;
+	let extra_bits = 1 + (code >> (postfix_bits + 1));
+	let extra = bit_reader.bits(extra_bits as u8)? as usize;
+	let high = code >> postfix_bits;
+	let low = code & ((1 << postfix_bits) - 1);
+	let offset = ((2 + (high & 1)) << extra_bits) - 4;
+	Ok((((offset + extra) << postfix_bits) + low + direct_distances + 1, true))
+}
+
+/// Looks up a word from the static dictionary, with one of the transforms applied to it.
+fn dictionary_word(length: usize, word_id: usize) -> Result<Vec<u8>, BrotliError> {
+	let size_bits = DICTIONARY_SIZE_BITS.get(length).copied().unwrap_or(0);
+	if size_bits == 0 {
+		return Err(BrotliError::InvalidDictionaryReference(length, word_id));
+	}
+	let (prefix, transform, suffix) = TRANSFORMS.get(word_id >> size_bits).ok_or(BrotliError::InvalidDictionaryReference(length, word_id))?;
+	let offset: usize = (4..length).map(|shorter_length| shorter_length << DICTIONARY_SIZE_BITS[shorter_length]).sum::<usize>() + (word_id & ((1 << size_bits) - 1)) * length;
+	let word = &DICTIONARY[offset..offset + length];
+
+	let mut transformed = match transform {
+		WordTransform::OmitLast(count) => word[..length.saturating_sub(*count)].to_vec(),
+		WordTransform::OmitFirst(count) => word[(*count).min(length)..].to_vec(),
+		_ => word.to_vec(),
+	};
+	match transform {
+		WordTransform::UppercaseFirst => {
+			uppercase(&mut transformed, 0);
+		},
+		WordTransform::UppercaseAll => {
+			let mut index = 0;
+			while index < transformed.len() {
+				index += uppercase(&mut transformed, index);
+			}
+		},
+		_ => {},
+	}
+
+	let mut output = prefix.to_vec();
+	output.extend(transformed);
+	output.extend_from_slice(suffix);
+	Ok(output)
+}
+
+/// Brotli's approximation of uppercasing the UTF-8 character at `index`, giving the character's length.
+fn uppercase(word: &mut [u8], index: usize) -> usize {
+	let byte = word[index];
+	if byte < 0xC0 {
+		if byte.is_ascii_lowercase() {
+			word[index] ^= 0x20;
+		}
+		return 1;
+	}
+	if byte < 0xE0 {
+		if let Some(next) = word.get_mut(index + 1) {
+			*next ^= 0x20;
+		}
+		return 2;
+	}
+	if let Some(next) = word.get_mut(index + 2) {
+		*next ^= 0x05;
+	}
+	3
+}
+
+fn decompress_meta_block(bit_reader: &mut BitReader, output: &mut Vec<u8>, length: usize, maximum_backward_distance: usize, recent_distances: &mut [usize; 4]) -> Result<(), BrotliError> {
+	let mut literal_blocks = BlockTypes::read(bit_reader)?;
+	let mut command_blocks = BlockTypes::read(bit_reader)?;
+	let mut distance_blocks = BlockTypes::read(bit_reader)?;
+	let postfix_bits = bit_reader.bits(2)? as usize;
+	let direct_distances = (bit_reader.bits(4)? as usize) << postfix_bits;
+	let mut context_modes: Vec<ContextMode> = Vec::with_capacity(literal_blocks.count);
+	for _ in 0..literal_blocks.count {
+		context_modes.push(ContextMode::from_bits(bit_reader.bits(2)?));
+	}
+
+	let number_of_literal_trees = read_variable_length(bit_reader)? + 1;
+	let literal_context_map = read_context_map(bit_reader, number_of_literal_trees, LITERAL_CONTEXTS * literal_blocks.count)?;
+	let number_of_distance_trees = read_variable_length(bit_reader)? + 1;
+	let distance_context_map = read_context_map(bit_reader, number_of_distance_trees, DISTANCE_CONTEXTS * distance_blocks.count)?;
+
+	let literal_codes = (0..number_of_literal_trees).map(|_| PrefixCode::read(bit_reader, NUMBER_OF_LITERALS)).collect::<Result<Vec<PrefixCode>, BrotliError>>()?;
+	let command_codes = (0..command_blocks.count).map(|_| PrefixCode::read(bit_reader, NUMBER_OF_INSERT_AND_COPY_CODES)).collect::<Result<Vec<PrefixCode>, BrotliError>>()?;
+	let distance_alphabet_size = NUMBER_OF_RECENT_DISTANCE_CODES + direct_distances + (48 << postfix_bits);
+	let distance_codes = (0..number_of_distance_trees).map(|_| PrefixCode::read(bit_reader, distance_alphabet_size)).collect::<Result<Vec<PrefixCode>, BrotliError>>()?;
+
+	let end = output.len() + length;
+	while output.len() < end {
+		let command_block_type = command_blocks.next(bit_reader)?;
+		let command = command_codes[command_block_type].decode(bit_reader)? as usize;
+		let (insert_length_base, copy_length_base, reuses_last_distance) = INSERT_AND_COPY_CELLS[command >> 6];
+		let insert_length_code = insert_length_base + ((command >> 3) & 7);
+		let copy_length_code = copy_length_base + (command & 7);
+		let insert_length = (INSERT_LENGTH_BASES[insert_length_code] + bit_reader.bits(INSERT_LENGTH_EXTRA_BITS[insert_length_code])?) as usize;
+		let copy_length = (COPY_LENGTH_BASES[copy_length_code] + bit_reader.bits(COPY_LENGTH_EXTRA_BITS[copy_length_code])?) as usize;
+
+		if output.len() + insert_length > end {
+			return Err(BrotliError::MetaBlockLengthExceeded);
+		}
+		for _ in 0..insert_length {
+			let literal_block_type = literal_blocks.next(bit_reader)?;
+			let previous = output.last().copied().unwrap_or(0);
+			let before_previous = output.len().checked_sub(2).map_or(0, |index| output[index]);
+			let context = context_modes[literal_block_type].literal_context(previous, before_previous);
+			let tree = literal_context_map[literal_block_type * LITERAL_CONTEXTS + context] as usize;
+			output.push(literal_codes[tree].decode(bit_reader)? as u8);
+		}
+		// The last command of a meta-block may end after its literals, leaving its copy unused.
+		if output.len() == end {
+			break;
+		}
+
+		let (distance, remember_distance) = if reuses_last_distance {
+			(recent_distances[0], false)
+		} else {
+			let distance_block_type = distance_blocks.next(bit_reader)?;
+			let context = copy_length.min(5) - 2;
+			let tree = distance_context_map[distance_block_type * DISTANCE_CONTEXTS + context] as usize;
+			let distance_code = distance_codes[tree].decode(bit_reader)? as usize;
+			read_distance(bit_reader, distance_code, postfix_bits, direct_distances, recent_distances)?
+		};
+
+		// Distances reaching back past the start of the window refer to the static dictionary instead.
+		let maximum_distance = maximum_backward_distance.min(output.len());
+		if distance > maximum_distance {
+			let word = dictionary_word(copy_length, distance - maximum_distance - 1)?;
+			if output.len() + word.len() > end {
+				return Err(BrotliError::MetaBlockLengthExceeded);
+			}
+			output.extend(word);
+		} else {
+			if output.len() + copy_length > end {
+				return Err(BrotliError::MetaBlockLengthExceeded);
+			}
+			if remember_distance {
+				recent_distances.rotate_right(1);
+				recent_distances[0] = distance;
+			}
+			// The copy may overlap what it is writing, so it has to go a byte at a time.
+			let start = output.len() - distance;
+			for offset in 0..copy_length {
+				output.push(output[start + offset]);
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Decompresses a Brotli stream, failing if it would produce more than `maximum_length` bytes.
+pub fn decompress(data: &[u8], maximum_length: usize) -> Result<Vec<u8>, BrotliError> {
+	let mut bit_reader = BitReader::new(data);
+	let window_bits = read_window_bits(&mut bit_reader)?;
+	let maximum_backward_distance = (1usize << window_bits) - 16;
+	let mut recent_distances = INITIAL_RECENT_DISTANCES;
+	let mut output: Vec<u8> = Vec::new();
+
+	loop {
+		let last_meta_block = bit_reader.bit()? == 1;
+		if last_meta_block && bit_reader.bit()? == 1 {
+			return Ok(output);
+		}
+
+		let nibbles = match bit_reader.bits(2)? {
+			3 => 0,
+			nibbles => nibbles as u8 + 4,
+		};
+		if nibbles == 0 {
+			// Metadata blocks hold nothing the output needs, so are skipped over.
+			if bit_reader.bit()? != 0 {
+				return Err(BrotliError::InvalidMetaBlockHeader);
+			}
+			let skip_bytes = bit_reader.bits(2)? as u8;
+			let skip_length = if skip_bytes == 0 { 0 } else { bit_reader.bits(skip_bytes * 8)? as usize + 1 };
+			bit_reader.align_to_byte();
+			bit_reader.bytes(skip_length)?;
+		} else {
+			let length = bit_reader.bits(nibbles * 4)? as usize + 1;
+			if output.len() + length > maximum_length {
+				return Err(BrotliError::OutputTooLong);
+			}
+			if !last_meta_block && bit_reader.bit()? == 1 {
+				bit_reader.align_to_byte();
+				output.extend_from_slice(bit_reader.bytes(length)?);
+			} else {
+				decompress_meta_block(&mut bit_reader, &mut output, length, maximum_backward_distance, &mut recent_distances)?;
+			}
+		}
+
+		if last_meta_block {
+			return Ok(output);
+		}
+	}
+}
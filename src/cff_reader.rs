@@ -0,0 +1,853 @@
+use std::io::{self, Read, Seek};
+
+use crate::ttf_reader::{FromTTFReader, TableTag, TrueTypeFontReader, TrueTypeFontReaderError};
+use crate::variation_reader::ItemVariationStore;
+
+const CHAR_STRINGS_OPERATOR: u16 = 17;
+const PRIVATE_OPERATOR: u16 = 18;
+const SUBROUTINES_OPERATOR: u16 = 19;
+const DEFAULT_WIDTH_X_OPERATOR: u16 = 20;
+const NOMINAL_WIDTH_X_OPERATOR: u16 = 21;
+const VARIATION_STORE_INDEX_OPERATOR: u16 = 22;
+const VARIATION_STORE_OPERATOR: u16 = 24;
+const CHAR_STRING_TYPE_OPERATOR: u16 = 12 << 8 | 6;
+const FONT_DICT_ARRAY_OPERATOR: u16 = 12 << 8 | 36;
+const FONT_DICT_SELECT_OPERATOR: u16 = 12 << 8 | 37;
+
+const MAXIMUM_STACK_SIZE: usize = 48;
+const MAXIMUM_STACK_SIZE_CFF2: usize = 513;
+const MAXIMUM_SUBROUTINE_DEPTH: usize = 10;
+
+/// CFF2 widened INDEX counts to 32 bits, otherwise the INDEX and DICT structures are shared with CFF.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompactFontFormatVersion {
+	One,
+	Two,
+}
+
+pub struct CompactFontFormatIndex {
+	pub items: Vec<Vec<u8>>,
+}
+
+/// A DICT as a list of (operator, operands). Two byte operators are stored as `12 << 8 | second byte`.
+pub struct CompactFontFormatDict {
+	pub entries: Vec<(u16, Vec<f64>)>,
+}
+
+pub struct CompactFontFormatPrivate {
+	pub local_subroutines: Vec<Vec<u8>>,
+	pub default_width_x: f32,
+	pub nominal_width_x: f32,
+	pub variation_store_index: u16, // CFF2 only, the item variation data used by blends unless a charstring picks another
+}
+
+pub struct CompactFontFormatTable {
+	pub char_strings: Vec<Vec<u8>>,
+	pub global_subroutines: Vec<Vec<u8>>,
+	pub private_dicts: Vec<CompactFontFormatPrivate>,
+	pub font_dict_select: Vec<u16>, // Private DICT used by each glyph, empty if the font isn't CID-keyed.
+}
+
+pub struct CompactFontFormat2Table {
+	pub char_strings: Vec<Vec<u8>>,
+	pub global_subroutines: Vec<Vec<u8>>,
+	pub private_dicts: Vec<CompactFontFormatPrivate>,
+	pub font_dict_select: Vec<u16>, // Private DICT used by each glyph, empty if every glyph uses the first.
+	pub variation_store: Option<ItemVariationStore>,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CharStringError {
+	StackOverflow,
+	StackUnderflow,
+	SubroutineOutOfRange(i32),
+	SubroutinesNestedTooDeep,
+	UnsupportedOperator(u16),
+	UnexpectedEnd,
+	VariationStoreIndexOutOfRange(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PathSegment {
+	Line(f32, f32),
+	Cubic((f32, f32), (f32, f32), (f32, f32)), // first control point, second control point, end point
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CharStringContour {
+	pub start: (f32, f32),
+	pub segments: Vec<PathSegment>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct CharStringOutline {
+	pub contours: Vec<CharStringContour>,
+	pub width: Option<f32>,
+}
+
+impl CompactFontFormatDict {
+	pub fn get(&self, operator: u16) -> Option<&[f64]> {
+		self.entries.iter().find(|(entry_operator, _)| *entry_operator == operator).map(|(_, operands)| operands.as_slice())
+	}
+
+	pub fn parse(data: &[u8]) -> Result<Self, TrueTypeFontReaderError> {
+		let mut entries: Vec<(u16, Vec<f64>)> = Vec::new();
+		let mut operands: Vec<f64> = Vec::new();
+		let mut position = 0;
+		let invalid = || TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat, "DICT data ends in the middle of an operand");
+
+		while position < data.len() {
+			let b0 = data[position];
+			position += 1;
+			match b0 {
+				0..=11 | 13..=24 => { // 22 to 24 are CFF2's vsindex, blend and vstore. A blend keeps its operands, so they aren't taken by the next operator.
+					entries.push((b0 as u16, std::mem::take(&mut operands)));
+				},
+				12 => {
+					let b1 = *data.get(position).ok_or_else(invalid)?;
+					position += 1;
+					entries.push((12 << 8 | b1 as u16, std::mem::take(&mut operands)));
+				},
+				28 => {
+					let bytes = data.get(position..position + 2).ok_or_else(invalid)?;
+					operands.push(i16::from_be_bytes([bytes[0], bytes[1]]) as f64);
+					position += 2;
+				},
+				29 => {
+					let bytes = data.get(position..position + 4).ok_or_else(invalid)?;
+					operands.push(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f64);
+					position += 4;
+				},
+				30 => {
+					let mut number = String::new();
+					'nibbles: loop {
+						let byte = *data.get(position).ok_or_else(invalid)?;
+						position += 1;
+						for nibble in [byte >> 4, byte & 0x0F] {
+							match nibble {
+								0..=9 => number.push((b'0' + nibble) as char),
+								0xA => number.push('.'),
+								0xB => number.push('E'),
+								0xC => number.push_str("E-"),
+								0xE => number.push('-'),
+								0xF => break 'nibbles,
+								_ => {},
+							}
+						}
+					}
+					operands.push(number.parse().unwrap_or(0.0));
+				},
+				32..=246 => operands.push(b0 as f64 - 139.0),
+				247..=250 => {
+					let b1 = *data.get(position).ok_or_else(invalid)?;
+					position += 1;
+					operands.push((b0 as f64 - 247.0) * 256.0 + b1 as f64 + 108.0);
+				},
+				251..=254 => {
+					let b1 = *data.get(position).ok_or_else(invalid)?;
+					position += 1;
+					operands.push(-(b0 as f64 - 251.0) * 256.0 - b1 as f64 - 108.0);
+				},
+				_ => return Err(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat, "reserved byte in DICT data")),
+			}
+		}
+
+		Ok(CompactFontFormatDict { entries })
+	}
+}
+
+impl FromTTFReader for CompactFontFormatIndex {
+	type Input = (u64, CompactFontFormatVersion); // offset, version of the table containing the INDEX
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (offset, version): (u64, CompactFontFormatVersion)) -> Result<CompactFontFormatIndex, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+
+		let count: u32 = match version {
+			CompactFontFormatVersion::One => ttf_reader.read_bytes::<u16>()? as u32,
+			CompactFontFormatVersion::Two => ttf_reader.read_bytes()?,
+		};
+		if count == 0 {
+			return Ok(CompactFontFormatIndex { items: Vec::new() });
+		}
+
+		let offset_size: u8 = ttf_reader.read_bytes()?;
+		if offset_size == 0 || offset_size > 4 {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat, "INDEX offset size should be between 1 and 4"));
+		}
+
+		// CFF2 counts can be far larger than the data, so don't trust them for the allocation.
+		let mut offsets: Vec<u32> = Vec::with_capacity((count as usize).min(u16::MAX as usize) + 1);
+		for _ in 0..=count {
+			let mut item_offset: u32 = 0;
+			for _ in 0..offset_size {
+				let byte: u8 = ttf_reader.read_bytes()?;
+				item_offset = item_offset << 8 | byte as u32;
+			}
+			offsets.push(item_offset);
+		}
+
+		// Offsets are relative to the byte before the data, so the first one is always 1.
+		let data_length = offsets[count as usize].checked_sub(1).ok_or(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat, "INDEX offsets should start at 1"))?;
+		if data_length as u64 > ttf_reader.remaining()? {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat, "INDEX data should fit in the font"));
+		}
+		let mut data = vec![0u8; data_length as usize];
+		ttf_reader.buffer_reader.read_exact(&mut data)?;
+
+		let mut items: Vec<Vec<u8>> = Vec::with_capacity(offsets.len() - 1);
+		for window in offsets.windows(2) {
+			let (start, end) = (window[0] as usize, window[1] as usize);
+			if start == 0 || start > end || end - 1 > data.len() {
+				return Err(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat, "INDEX offsets should be in ascending order"));
+			}
+			items.push(data[start - 1..end - 1].to_vec());
+		}
+
+		Ok(CompactFontFormatIndex { items })
+	}
+}
+
+impl FromTTFReader for CompactFontFormatPrivate {
+	type Input = (u64, u32, CompactFontFormatVersion); // offset, size, version of the table containing the DICT
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (offset, size, version): (u64, u32, CompactFontFormatVersion)) -> Result<CompactFontFormatPrivate, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+		if size as u64 > ttf_reader.remaining()? {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat, "Private DICT should fit in the font"));
+		}
+		let mut data = vec![0u8; size as usize];
+		ttf_reader.buffer_reader.read_exact(&mut data)?;
+		let private_dict = CompactFontFormatDict::parse(&data)?;
+
+		// The Subrs offset is relative to the start of the Private DICT.
+		let local_subroutines = match private_dict.get(SUBROUTINES_OPERATOR) {
+			Some([subroutines_offset]) => {
+				let subroutines: CompactFontFormatIndex = ttf_reader.read((dict_offset(offset, *subroutines_offset)?, version))?;
+				subroutines.items
+			},
+			_ => Vec::new(),
+		};
+
+		Ok(CompactFontFormatPrivate {
+			local_subroutines,
+			default_width_x: private_dict.get(DEFAULT_WIDTH_X_OPERATOR).and_then(|operands| operands.first()).copied().unwrap_or(0.0) as f32,
+			nominal_width_x: private_dict.get(NOMINAL_WIDTH_X_OPERATOR).and_then(|operands| operands.first()).copied().unwrap_or(0.0) as f32,
+			variation_store_index: private_dict.get(VARIATION_STORE_INDEX_OPERATOR).and_then(|operands| operands.first()).copied().unwrap_or(0.0) as u16,
+		})
+	}
+}
+
+impl FromTTFReader for CompactFontFormatTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<CompactFontFormatTable, TrueTypeFontReaderError> {
+		let table_start = offset as u64;
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(table_start))?;
+
+		let major_version: u8 = ttf_reader.read_bytes()?;
+		let minor_version: u8 = ttf_reader.read_bytes()?;
+		if major_version != 1 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::CompactFontFormat, major_version as u16, minor_version as u16));
+		}
+		let header_size: u8 = ttf_reader.read_bytes()?;
+
+		let name_index: CompactFontFormatIndex = ttf_reader.read((table_start + header_size as u64, CompactFontFormatVersion::One))?;
+		let name_index_end = ttf_reader.buffer_reader.stream_position()?;
+		let top_dict_index: CompactFontFormatIndex = ttf_reader.read((name_index_end, CompactFontFormatVersion::One))?;
+		let top_dict_index_end = ttf_reader.buffer_reader.stream_position()?;
+		let _string_index: CompactFontFormatIndex = ttf_reader.read((top_dict_index_end, CompactFontFormatVersion::One))?;
+		let string_index_end = ttf_reader.buffer_reader.stream_position()?;
+		let global_subroutines: CompactFontFormatIndex = ttf_reader.read((string_index_end, CompactFontFormatVersion::One))?;
+
+		if name_index.items.len() != 1 || top_dict_index.items.len() != 1 {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat, "an OpenType CFF table should contain exactly one font"));
+		}
+		let top_dict = CompactFontFormatDict::parse(&top_dict_index.items[0])?;
+
+		if let Some(char_string_type) = top_dict.get(CHAR_STRING_TYPE_OPERATOR) && char_string_type != [2.0] {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat, "only Type 2 charstrings are supported"));
+		}
+
+		let char_strings: CompactFontFormatIndex = match top_dict.get(CHAR_STRINGS_OPERATOR) {
+			Some([char_strings_offset]) => ttf_reader.read((dict_offset(table_start, *char_strings_offset)?, CompactFontFormatVersion::One))?,
+			_ => return Err(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat, "Top DICT should have a CharStrings offset")),
+		};
+
+		let mut private_dicts: Vec<CompactFontFormatPrivate> = Vec::new();
+		let mut font_dict_select: Vec<u16> = Vec::new();
+		match (top_dict.get(FONT_DICT_ARRAY_OPERATOR), top_dict.get(FONT_DICT_SELECT_OPERATOR)) {
+			(Some([font_dict_array_offset]), Some([font_dict_select_offset])) => {
+				// CID-keyed font, every glyph picks one of the Font DICTs and with it a Private DICT.
+				let font_dict_array: CompactFontFormatIndex = ttf_reader.read((dict_offset(table_start, *font_dict_array_offset)?, CompactFontFormatVersion::One))?;
+				for font_dict in font_dict_array.items.iter() {
+					let font_dict = CompactFontFormatDict::parse(font_dict)?;
+					private_dicts.push(read_private_dict(ttf_reader, &font_dict, table_start, CompactFontFormatVersion::One)?);
+				}
+				font_dict_select = read_font_dict_select(ttf_reader, dict_offset(table_start, *font_dict_select_offset)?, char_strings.items.len())?;
+				if font_dict_select.iter().any(|font_dict_index| *font_dict_index as usize >= private_dicts.len()) {
+					return Err(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat, "FDSelect should only reference Font DICTs in the FDArray"));
+				}
+			},
+			_ => {
+				private_dicts.push(read_private_dict(ttf_reader, &top_dict, table_start, CompactFontFormatVersion::One)?);
+			},
+		}
+
+		Ok(CompactFontFormatTable {
+			char_strings: char_strings.items,
+			global_subroutines: global_subroutines.items,
+			private_dicts,
+			font_dict_select,
+		})
+	}
+}
+
+impl FromTTFReader for CompactFontFormat2Table {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<CompactFontFormat2Table, TrueTypeFontReaderError> {
+		let table_start = offset as u64;
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(table_start))?;
+
+		let major_version: u8 = ttf_reader.read_bytes()?;
+		let minor_version: u8 = ttf_reader.read_bytes()?;
+		if major_version != 2 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::CompactFontFormat2, major_version as u16, minor_version as u16));
+		}
+		let header_size: u8 = ttf_reader.read_bytes()?;
+		let top_dict_length: u16 = ttf_reader.read_bytes()?;
+
+		// CFF2 has no Name or String INDEX, and the Top DICT is stored directly rather than in an INDEX.
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(table_start + header_size as u64))?;
+		let mut top_dict_data = vec![0u8; top_dict_length as usize];
+		ttf_reader.buffer_reader.read_exact(&mut top_dict_data)?;
+		let top_dict = CompactFontFormatDict::parse(&top_dict_data)?;
+		let global_subroutines: CompactFontFormatIndex = ttf_reader.read((table_start + header_size as u64 + top_dict_length as u64, CompactFontFormatVersion::Two))?;
+
+		let char_strings: CompactFontFormatIndex = match top_dict.get(CHAR_STRINGS_OPERATOR) {
+			Some([char_strings_offset]) => ttf_reader.read((dict_offset(table_start, *char_strings_offset)?, CompactFontFormatVersion::Two))?,
+			_ => return Err(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat2, "Top DICT should have a CharStrings offset")),
+		};
+
+		let font_dict_array: CompactFontFormatIndex = match top_dict.get(FONT_DICT_ARRAY_OPERATOR) {
+			Some([font_dict_array_offset]) => ttf_reader.read((dict_offset(table_start, *font_dict_array_offset)?, CompactFontFormatVersion::Two))?,
+			_ => return Err(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat2, "Top DICT should have an FDArray offset")),
+		};
+		let mut private_dicts: Vec<CompactFontFormatPrivate> = Vec::with_capacity(font_dict_array.items.len());
+		for font_dict in font_dict_array.items.iter() {
+			let font_dict = CompactFontFormatDict::parse(font_dict)?;
+			private_dicts.push(read_private_dict(ttf_reader, &font_dict, table_start, CompactFontFormatVersion::Two)?);
+		}
+		if private_dicts.is_empty() {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat2, "FDArray should have at least one Font DICT"));
+		}
+
+		let font_dict_select = match top_dict.get(FONT_DICT_SELECT_OPERATOR) {
+			Some([font_dict_select_offset]) => read_font_dict_select(ttf_reader, dict_offset(table_start, *font_dict_select_offset)?, char_strings.items.len())?,
+			_ => Vec::new(),
+		};
+		if font_dict_select.iter().any(|font_dict_index| *font_dict_index as usize >= private_dicts.len()) {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat2, "FDSelect should only reference Font DICTs in the FDArray"));
+		}
+
+		// The variation store is preceded by its length, which isn't needed.
+		let variation_store: Option<ItemVariationStore> = match top_dict.get(VARIATION_STORE_OPERATOR) {
+			Some([variation_store_offset]) => Some(ttf_reader.read((dict_offset(table_start + 2, *variation_store_offset)?, TableTag::CompactFontFormat2))?),
+			_ => None,
+		};
+
+		Ok(CompactFontFormat2Table {
+			char_strings: char_strings.items,
+			global_subroutines: global_subroutines.items,
+			private_dicts,
+			font_dict_select,
+			variation_store,
+		})
+	}
+}
+
+/// Where a DICT offset operand points, from what it's relative to. Operands can be real numbers far beyond the font.
+fn dict_offset(start: u64, offset: f64) -> Result<u64, TrueTypeFontReaderError> {
+	start.checked_add(offset as u64).ok_or(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat, "DICT offsets should be in the font"))
+}
+
+fn read_private_dict<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, dict: &CompactFontFormatDict, table_start: u64, version: CompactFontFormatVersion) -> Result<CompactFontFormatPrivate, TrueTypeFontReaderError> {
+	match dict.get(PRIVATE_OPERATOR) {
+		Some([size, private_offset]) => ttf_reader.read((dict_offset(table_start, *private_offset)?, *size as u32, version)),
+		_ => Ok(CompactFontFormatPrivate { local_subroutines: Vec::new(), default_width_x: 0.0, nominal_width_x: 0.0, variation_store_index: 0 }),
+	}
+}
+
+fn read_font_dict_select<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64, number_of_glyphs: usize) -> Result<Vec<u16>, TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+
+	let format: u8 = ttf_reader.read_bytes()?;
+	let mut font_dict_select: Vec<u16> = Vec::with_capacity(number_of_glyphs);
+	match format {
+		0 => {
+			for _ in 0..number_of_glyphs {
+				let font_dict_index: u8 = ttf_reader.read_bytes()?;
+				font_dict_select.push(font_dict_index as u16);
+			}
+		},
+		3 | 4 => {
+			let number_of_ranges: u32 = if format == 3 { ttf_reader.read_bytes::<u16>()? as u32 } else { ttf_reader.read_bytes()? };
+			let mut first: u32 = if format == 3 { ttf_reader.read_bytes::<u16>()? as u32 } else { ttf_reader.read_bytes()? };
+			for _ in 0..number_of_ranges {
+				let font_dict_index: u16 = if format == 3 { ttf_reader.read_bytes::<u8>()? as u16 } else { ttf_reader.read_bytes()? };
+				let next: u32 = if format == 3 { ttf_reader.read_bytes::<u16>()? as u32 } else { ttf_reader.read_bytes()? };
+				for _ in first..next.min(number_of_glyphs as u32) {
+					font_dict_select.push(font_dict_index);
+				}
+				first = next;
+			}
+		},
+		_ => return Err(TrueTypeFontReaderError::InvalidData(TableTag::CompactFontFormat, "FDSelect format should be 0, 3 or 4")),
+	}
+
+	font_dict_select.resize(number_of_glyphs, 0);
+	Ok(font_dict_select)
+}
+
+impl CompactFontFormatTable {
+	pub fn outline(&self, glyph_index: usize) -> Result<CharStringOutline, CharStringError> {
+		let char_string = self.char_strings.get(glyph_index).ok_or(CharStringError::UnexpectedEnd)?;
+		let private_dict = &self.private_dicts[self.font_dict_select.get(glyph_index).copied().unwrap_or(0) as usize];
+
+		let mut interpreter = CharStringInterpreter::new(&self.global_subroutines, &private_dict.local_subroutines);
+		interpreter.run(char_string, 0)?;
+		let mut outline = interpreter.finish();
+		outline.width = Some(match outline.width {
+			Some(width) => private_dict.nominal_width_x + width,
+			None => private_dict.default_width_x,
+		});
+		Ok(outline)
+	}
+}
+
+impl CompactFontFormat2Table {
+	/// The outline of a glyph at the given normalised variation coordinates. CFF2 charstrings have no width, that comes from hmtx.
+	pub fn outline(&self, glyph_index: usize, coordinates: &[f32]) -> Result<CharStringOutline, CharStringError> {
+		let char_string = self.char_strings.get(glyph_index).ok_or(CharStringError::UnexpectedEnd)?;
+		let private_dict = &self.private_dicts[self.font_dict_select.get(glyph_index).copied().unwrap_or(0) as usize];
+
+		let variation_scalars: Vec<Vec<f32>> = match &self.variation_store {
+			Some(variation_store) => (0..variation_store.item_variation_data.len()).filter_map(|data_index| variation_store.region_scalars(data_index, coordinates)).collect(),
+			None => Vec::new(),
+		};
+
+		let mut interpreter = CharStringInterpreter::new_variable(&self.global_subroutines, &private_dict.local_subroutines, &variation_scalars, private_dict.variation_store_index as usize);
+		interpreter.run(char_string, 0)?;
+		Ok(interpreter.finish())
+	}
+}
+
+fn subroutine_bias(number_of_subroutines: usize) -> i32 {
+	if number_of_subroutines < 1240 {
+		107
+	} else if number_of_subroutines < 33900 {
+		1131
+	} else {
+		32768
+	}
+}
+
+/// Runs a Type 2 or CFF2 charstring, collecting its contours. Hints are counted, so hint masks can be skipped, but otherwise ignored.
+pub struct CharStringInterpreter<'a> {
+	global_subroutines: &'a [Vec<u8>],
+	local_subroutines: &'a [Vec<u8>],
+	variation_scalars: &'a [Vec<f32>], // CFF2 only, the region scalars of each item variation data
+	variation_store_index: Option<usize>, // None for Type 2 charstrings, which have no blend or vsindex operators
+	maximum_stack_size: usize,
+	stack: Vec<f32>,
+	x: f32,
+	y: f32,
+	number_of_stems: usize,
+	width_parsed: bool,
+	finished: bool,
+	current_contour: Option<CharStringContour>,
+	outline: CharStringOutline,
+}
+
+impl<'a> CharStringInterpreter<'a> {
+	pub fn new(global_subroutines: &'a [Vec<u8>], local_subroutines: &'a [Vec<u8>]) -> Self {
+		Self {
+			global_subroutines,
+			local_subroutines,
+			variation_scalars: &[],
+			variation_store_index: None,
+			maximum_stack_size: MAXIMUM_STACK_SIZE,
+			stack: Vec::with_capacity(MAXIMUM_STACK_SIZE),
+			x: 0.0,
+			y: 0.0,
+			number_of_stems: 0,
+			width_parsed: false,
+			finished: false,
+			current_contour: None,
+			outline: CharStringOutline::default(),
+		}
+	}
+
+	/// An interpreter for CFF2 charstrings, which never include a width and can blend their operands between regions.
+	pub fn new_variable(global_subroutines: &'a [Vec<u8>], local_subroutines: &'a [Vec<u8>], variation_scalars: &'a [Vec<f32>], variation_store_index: usize) -> Self {
+		Self {
+			variation_scalars,
+			variation_store_index: Some(variation_store_index),
+			maximum_stack_size: MAXIMUM_STACK_SIZE_CFF2,
+			width_parsed: true,
+			..Self::new(global_subroutines, local_subroutines)
+		}
+	}
+
+	pub fn finish(mut self) -> CharStringOutline {
+		self.close_contour();
+		self.outline
+	}
+
+	fn push(&mut self, value: f32) -> Result<(), CharStringError> {
+		if self.stack.len() >= self.maximum_stack_size {
+			return Err(CharStringError::StackOverflow);
+		}
+		self.stack.push(value);
+		Ok(())
+	}
+
+	fn pop(&mut self) -> Result<f32, CharStringError> {
+		self.stack.pop().ok_or(CharStringError::StackUnderflow)
+	}
+
+	/// The first stack clearing operator may have the advance width as an extra leading argument.
+	fn parse_width(&mut self, has_extra_argument: bool) {
+		if !self.width_parsed && has_extra_argument && !self.stack.is_empty() {
+			self.outline.width = Some(self.stack.remove(0));
+		}
+		self.width_parsed = true;
+	}
+
+	fn close_contour(&mut self) {
+		if let Some(contour) = self.current_contour.take() && !contour.segments.is_empty() {
+			self.outline.contours.push(contour);
+		}
+	}
+
+	fn move_to(&mut self, dx: f32, dy: f32) {
+		self.close_contour();
+		self.x += dx;
+		self.y += dy;
+		self.current_contour = Some(CharStringContour { start: (self.x, self.y), segments: Vec::new() });
+	}
+
+	fn line_to(&mut self, dx: f32, dy: f32) {
+		self.x += dx;
+		self.y += dy;
+		let (x, y) = (self.x, self.y);
+		self.current_contour.get_or_insert_with(|| CharStringContour { start: (0.0, 0.0), segments: Vec::new() }).segments.push(PathSegment::Line(x, y));
+	}
+
+	fn curve_to(&mut self, dxa: f32, dya: f32, dxb: f32, dyb: f32, dxc: f32, dyc: f32) {
+		let first_control = (self.x + dxa, self.y + dya);
+		let second_control = (first_control.0 + dxb, first_control.1 + dyb);
+		let end = (second_control.0 + dxc, second_control.1 + dyc);
+		self.x = end.0;
+		self.y = end.1;
+		self.current_contour.get_or_insert_with(|| CharStringContour { start: (0.0, 0.0), segments: Vec::new() }).segments.push(PathSegment::Cubic(first_control, second_control, end));
+	}
+
+	fn add_stems(&mut self) {
+		let has_extra_argument = self.stack.len() % 2 == 1;
+		self.parse_width(has_extra_argument);
+		self.number_of_stems += self.stack.len() / 2;
+		self.stack.clear();
+	}
+
+	pub fn run(&mut self, char_string: &[u8], depth: usize) -> Result<(), CharStringError> {
+		if depth > MAXIMUM_SUBROUTINE_DEPTH {
+			return Err(CharStringError::SubroutinesNestedTooDeep);
+		}
+
+		let mut position = 0;
+		while position < char_string.len() && !self.finished {
+			let b0 = char_string[position];
+			position += 1;
+			match b0 {
+				1 | 3 | 18 | 23 => { // hstem, vstem, hstemhm, vstemhm
+					self.add_stems();
+				},
+				19 | 20 => { // hintmask, cntrmask
+					self.add_stems();
+					position += self.number_of_stems.div_ceil(8);
+				},
+				21 => { // rmoveto
+					let has_extra_argument = self.stack.len() > 2;
+					self.parse_width(has_extra_argument);
+					let dy = self.pop()?;
+					let dx = self.pop()?;
+					self.move_to(dx, dy);
+					self.stack.clear();
+				},
+				22 => { // hmoveto
+					let has_extra_argument = self.stack.len() > 1;
+					self.parse_width(has_extra_argument);
+					let dx = self.pop()?;
+					self.move_to(dx, 0.0);
+					self.stack.clear();
+				},
+				4 => { // vmoveto
+					let has_extra_argument = self.stack.len() > 1;
+					self.parse_width(has_extra_argument);
+					let dy = self.pop()?;
+					self.move_to(0.0, dy);
+					self.stack.clear();
+				},
+				5 => { // rlineto
+					let arguments = std::mem::take(&mut self.stack);
+					for pair in arguments.chunks_exact(2) {
+						self.line_to(pair[0], pair[1]);
+					}
+				},
+				6 | 7 => { // hlineto, vlineto
+					let arguments = std::mem::take(&mut self.stack);
+					let mut horizontal = b0 == 6;
+					for argument in arguments {
+						if horizontal {
+							self.line_to(argument, 0.0);
+						} else {
+							self.line_to(0.0, argument);
+						}
+						horizontal = !horizontal;
+					}
+				},
+				8 => { // rrcurveto
+					let arguments = std::mem::take(&mut self.stack);
+					for curve in arguments.chunks_exact(6) {
+						self.curve_to(curve[0], curve[1], curve[2], curve[3], curve[4], curve[5]);
+					}
+				},
+				24 => { // rcurveline
+					let arguments = std::mem::take(&mut self.stack);
+					if arguments.len() < 8 {
+						return Err(CharStringError::StackUnderflow);
+					}
+					let (curves, line) = arguments.split_at(arguments.len() - 2);
+					for curve in curves.chunks_exact(6) {
+						self.curve_to(curve[0], curve[1], curve[2], curve[3], curve[4], curve[5]);
+					}
+					self.line_to(line[0], line[1]);
+				},
+				25 => { // rlinecurve
+					let arguments = std::mem::take(&mut self.stack);
+					if arguments.len() < 8 {
+						return Err(CharStringError::StackUnderflow);
+					}
+					let (lines, curve) = arguments.split_at(arguments.len() - 6);
+					for line in lines.chunks_exact(2) {
+						self.line_to(line[0], line[1]);
+					}
+					self.curve_to(curve[0], curve[1], curve[2], curve[3], curve[4], curve[5]);
+				},
+				26 => { // vvcurveto
+					let mut arguments: &[f32] = &std::mem::take(&mut self.stack);
+					let mut dx1 = 0.0;
+					if arguments.len() % 2 == 1 {
+						dx1 = arguments[0];
+						arguments = &arguments[1..];
+					}
+					for curve in arguments.chunks_exact(4) {
+						self.curve_to(dx1, curve[0], curve[1], curve[2], 0.0, curve[3]);
+						dx1 = 0.0;
+					}
+				},
+				27 => { // hhcurveto
+					let mut arguments: &[f32] = &std::mem::take(&mut self.stack);
+					let mut dy1 = 0.0;
+					if arguments.len() % 2 == 1 {
+						dy1 = arguments[0];
+						arguments = &arguments[1..];
+					}
+					for curve in arguments.chunks_exact(4) {
+						self.curve_to(curve[0], dy1, curve[1], curve[2], curve[3], 0.0);
+						dy1 = 0.0;
+					}
+				},
+				30 | 31 => { // vhcurveto, hvcurveto
+					let arguments = std::mem::take(&mut self.stack);
+					let mut horizontal = b0 == 31;
+					let number_of_curves = arguments.len() / 4;
+					for (curve_index, curve) in arguments.chunks_exact(4).enumerate() {
+						let last_argument = if curve_index == number_of_curves - 1 && arguments.len() % 4 == 1 { arguments[arguments.len() - 1] } else { 0.0 };
+						if horizontal {
+							self.curve_to(curve[0], 0.0, curve[1], curve[2], last_argument, curve[3]);
+						} else {
+							self.curve_to(0.0, curve[0], curve[1], curve[2], curve[3], last_argument);
+						}
+						horizontal = !horizontal;
+					}
+				},
+				15 if self.variation_store_index.is_some() => { // vsindex
+					let variation_store_index = self.pop()? as i32;
+					if usize::try_from(variation_store_index).ok().filter(|index| *index < self.variation_scalars.len()).is_none() {
+						return Err(CharStringError::VariationStoreIndexOutOfRange(variation_store_index));
+					}
+					self.variation_store_index = Some(variation_store_index as usize);
+					self.stack.clear();
+				},
+				16 if self.variation_store_index.is_some() => { // blend
+					self.blend()?;
+				},
+				10 | 29 => { // callsubr, callgsubr
+					let subroutines = if b0 == 10 { self.local_subroutines } else { self.global_subroutines };
+					let operand = self.pop()? as i32;
+					let subroutine_index = operand.checked_add(subroutine_bias(subroutines.len())).ok_or(CharStringError::SubroutineOutOfRange(operand))?;
+					let subroutine = usize::try_from(subroutine_index).ok().and_then(|index| subroutines.get(index)).ok_or(CharStringError::SubroutineOutOfRange(subroutine_index))?;
+					self.run(subroutine, depth + 1)?;
+				},
+				11 => { // return
+					return Ok(());
+				},
+				14 => { // endchar
+					let has_extra_argument = self.stack.len() == 1 || self.stack.len() == 5;
+					self.parse_width(has_extra_argument);
+					// Four remaining arguments would be the deprecated seac accent composition, which isn't supported.
+					self.stack.clear();
+					self.close_contour();
+					self.finished = true;
+				},
+				12 => {
+					let b1 = *char_string.get(position).ok_or(CharStringError::UnexpectedEnd)?;
+					position += 1;
+					self.run_escaped_operator(b1)?;
+				},
+				28 => { // shortint
+					let bytes = char_string.get(position..position + 2).ok_or(CharStringError::UnexpectedEnd)?;
+					position += 2;
+					self.push(i16::from_be_bytes([bytes[0], bytes[1]]) as f32)?;
+				},
+				32..=246 => self.push(b0 as f32 - 139.0)?,
+				247..=250 => {
+					let b1 = *char_string.get(position).ok_or(CharStringError::UnexpectedEnd)?;
+					position += 1;
+					self.push((b0 as f32 - 247.0) * 256.0 + b1 as f32 + 108.0)?;
+				},
+				251..=254 => {
+					let b1 = *char_string.get(position).ok_or(CharStringError::UnexpectedEnd)?;
+					position += 1;
+					self.push(-(b0 as f32 - 251.0) * 256.0 - b1 as f32 - 108.0)?;
+				},
+				255 => { // 16.16 fixed point
+					let bytes = char_string.get(position..position + 4).ok_or(CharStringError::UnexpectedEnd)?;
+					position += 4;
+					self.push(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 65536.0)?;
+				},
+				_ => return Err(CharStringError::UnsupportedOperator(b0 as u16)),
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Replaces n default values and their deltas for each region with the n blended values.
+	fn blend(&mut self) -> Result<(), CharStringError> {
+		let number_of_values = self.pop()?.max(0.0) as usize;
+		let variation_store_index = self.variation_store_index.unwrap_or(0);
+		let scalars: &[f32] = match self.variation_scalars.get(variation_store_index) {
+			Some(scalars) => scalars,
+			None if self.variation_scalars.is_empty() => &[],
+			None => return Err(CharStringError::VariationStoreIndexOutOfRange(variation_store_index as i32)),
+		};
+
+		let number_of_operands = number_of_values.checked_mul(scalars.len() + 1).filter(|number_of_operands| *number_of_operands <= self.stack.len()).ok_or(CharStringError::StackUnderflow)?;
+		let first_operand = self.stack.len() - number_of_operands;
+		for value_index in 0..number_of_values {
+			let deltas_start = first_operand + number_of_values + value_index * scalars.len();
+			let delta: f32 = self.stack[deltas_start..deltas_start + scalars.len()].iter().zip(scalars).map(|(delta, scalar)| delta * scalar).sum();
+			self.stack[first_operand + value_index] += delta;
+		}
+		self.stack.truncate(first_operand + number_of_values);
+		Ok(())
+	}
+
+	fn run_escaped_operator(&mut self, b1: u8) -> Result<(), CharStringError> {
+		match b1 {
+			35 => { // flex
+				if self.stack.len() < 13 {
+					return Err(CharStringError::StackUnderflow);
+				}
+				let arguments = std::mem::take(&mut self.stack);
+				self.curve_to(arguments[0], arguments[1], arguments[2], arguments[3], arguments[4], arguments[5]);
+				self.curve_to(arguments[6], arguments[7], arguments[8], arguments[9], arguments[10], arguments[11]);
+			},
+			34 => { // hflex
+				if self.stack.len() < 7 {
+					return Err(CharStringError::StackUnderflow);
+				}
+				let arguments = std::mem::take(&mut self.stack);
+				self.curve_to(arguments[0], 0.0, arguments[1], arguments[2], arguments[3], 0.0);
+				self.curve_to(arguments[4], 0.0, arguments[5], -arguments[2], arguments[6], 0.0);
+			},
+			36 => { // hflex1
+				if self.stack.len() < 9 {
+					return Err(CharStringError::StackUnderflow);
+				}
+				let arguments = std::mem::take(&mut self.stack);
+				let start_y = self.y;
+				self.curve_to(arguments[0], arguments[1], arguments[2], arguments[3], arguments[4], 0.0);
+				let dy6 = start_y - (self.y + arguments[7]);
+				self.curve_to(arguments[5], 0.0, arguments[6], arguments[7], arguments[8], dy6);
+			},
+			37 => { // flex1
+				if self.stack.len() < 11 {
+					return Err(CharStringError::StackUnderflow);
+				}
+				let arguments = std::mem::take(&mut self.stack);
+				let (start_x, start_y) = (self.x, self.y);
+				let dx: f32 = arguments[0..10].iter().step_by(2).sum();
+				let dy: f32 = arguments[1..10].iter().step_by(2).sum();
+				self.curve_to(arguments[0], arguments[1], arguments[2], arguments[3], arguments[4], arguments[5]);
+				let (control_x, control_y) = (self.x + arguments[6] + arguments[8], self.y + arguments[7] + arguments[9]);
+				if dx.abs() > dy.abs() {
+					self.curve_to(arguments[6], arguments[7], arguments[8], arguments[9], arguments[10], start_y - control_y);
+				} else {
+					self.curve_to(arguments[6], arguments[7], arguments[8], arguments[9], start_x - control_x, arguments[10]);
+				}
+			},
+			9 => { // abs
+				let a = self.pop()?;
+				self.push(a.abs())?;
+			},
+			10 | 11 | 12 | 24 => { // add, sub, div, mul
+				let b = self.pop()?;
+				let a = self.pop()?;
+				self.push(match b1 {
+					10 => a + b,
+					11 => a - b,
+					12 => if b == 0.0 { 0.0 } else { a / b },
+					_ => a * b,
+				})?;
+			},
+			14 => { // neg
+				let a = self.pop()?;
+				self.push(-a)?;
+			},
+			26 => { // sqrt
+				let a = self.pop()?;
+				self.push(a.max(0.0).sqrt())?;
+			},
+			18 => { // drop
+				self.pop()?;
+			},
+			27 => { // dup
+				let a = self.pop()?;
+				self.push(a)?;
+				self.push(a)?;
+			},
+			28 => { // exch
+				let b = self.pop()?;
+				let a = self.pop()?;
+				self.push(b)?;
+				self.push(a)?;
+			},
+			_ => return Err(CharStringError::UnsupportedOperator(12 << 8 | b1 as u16)),
+		}
+		Ok(())
+	}
+}
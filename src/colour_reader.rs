@@ -0,0 +1,899 @@
+use std::{cell::Cell, collections::HashMap, f32::consts::PI, io::{self, Read, Seek}};
+
+use crate::ttf_reader::{read_uint24, FromTTFReader, TableTag, TrueTypeFontReader, TrueTypeFontReaderError};
+use crate::variation_reader::{DeltaSetIndexMap, ItemVariationStore};
+
+/// The palette entry index that stands for the colour of the text, rather than a colour in the palette.
+pub const FOREGROUND_PALETTE_ENTRY: u16 = 0xFFFF;
+
+/// The variation index of values that don't vary.
+pub const NO_VARIATION_INDEX: u32 = 0xFFFF_FFFF;
+
+/// How deep paints may nest, so that paint graphs which loop back on themselves can't recurse forever.
+const MAX_PAINT_DEPTH: usize = 64;
+
+/// How many paints one glyph may visit, as paints shared many times over could otherwise take forever to evaluate.
+const MAX_PAINT_VISITS: usize = 65536;
+
+const USABLE_WITH_LIGHT_BACKGROUND: u32 = 0x0001;
+const USABLE_WITH_DARK_BACKGROUND: u32 = 0x0002;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BaseGlyphRecord {
+	pub glyph_id: u16,
+	pub first_layer_index: u16, // Into the layer records
+	pub number_of_layers: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayerRecord {
+	pub glyph_id: u16, // The outline drawn for this layer
+	pub palette_entry_index: u16, // Or FOREGROUND_PALETTE_ENTRY for the text colour
+}
+
+pub struct ColourTable {
+	pub base_glyphs: Vec<BaseGlyphRecord>, // Sorted by glyph id
+	pub layers: Vec<LayerRecord>,
+	pub base_glyph_paints: Vec<(u16, usize)>, // Version 1 glyph ids, sorted, and the index of their paint
+	pub layer_paints: Vec<usize>, // Indices of the paints in the layer list
+	pub clips: Vec<ClipRecord>,
+	pub paints: Vec<Paint>, // Every paint in the graph, each read once however many paints share it
+	pub variation_index_map: Option<DeltaSetIndexMap>,
+	pub item_variation_store: Option<ItemVariationStore>,
+}
+
+/// How a gradient carries on past its first and last colour stops.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extend {
+	Pad, // With the colour of the nearest stop
+	Repeat,
+	Reflect, // Repeating, with every other repeat backwards
+}
+
+/// Values of a paint, scaled from how they're stored, that variable fonts can move with deltas from the item variation store.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VariableValues {
+	pub values: Vec<f32>,
+	pub units: Vec<f32>, // What a delta of 1 is for each value: 1/16384 for F2DOT14, 1/65536 for Fixed and 1 for FWORD
+	pub variation_index_base: u32, // Of the first value, with the rest following on, or NO_VARIATION_INDEX
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColourStop {
+	pub palette_entry_index: u16,
+	pub values: VariableValues, // Stop offset and alpha
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColourLine {
+	pub extend: Extend,
+	pub stops: Vec<ColourStop>,
+}
+
+/// Which transform a transform paint applies, which decides what its values are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransformKind {
+	Affine, // xx, yx, xy, yy, dx, dy
+	Translate, // dx, dy
+	Scale, // scale x, scale y
+	ScaleAroundCentre, // scale x, scale y, centre x, centre y
+	ScaleUniform, // scale
+	ScaleUniformAroundCentre, // scale, centre x, centre y
+	Rotate, // angle
+	RotateAroundCentre, // angle, centre x, centre y
+	Skew, // x skew angle, y skew angle
+	SkewAroundCentre, // x skew angle, y skew angle, centre x, centre y
+}
+
+/// How a paint is combined with the paint behind it, as in the W3C compositing and blending spec.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompositeMode {
+	Clear,
+	Source,
+	Destination,
+	SourceOver,
+	DestinationOver,
+	SourceIn,
+	DestinationIn,
+	SourceOut,
+	DestinationOut,
+	SourceAtop,
+	DestinationAtop,
+	Xor,
+	Plus,
+	Screen,
+	Overlay,
+	Darken,
+	Lighten,
+	ColourDodge,
+	ColourBurn,
+	HardLight,
+	SoftLight,
+	Difference,
+	Exclusion,
+	Multiply,
+	Hue,
+	Saturation,
+	Colour,
+	Luminosity,
+}
+
+/// A node of the version 1 paint graph. Other paints are referred to by their index in `ColourTable::paints`.
+/// Angles are stored as multiples of 180°, counter-clockwise.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Paint {
+	Layers { first_layer_index: u32, number_of_layers: u8 }, // Format 1, a run of the layer list, bottom first
+	Solid { palette_entry_index: u16, alpha: VariableValues }, // Formats 2 and 3
+	LinearGradient { colour_line: ColourLine, points: VariableValues }, // Formats 4 and 5: x0, y0, x1, y1, x2, y2
+	RadialGradient { colour_line: ColourLine, circles: VariableValues }, // Formats 6 and 7: x0, y0, radius 0, x1, y1, radius 1
+	SweepGradient { colour_line: ColourLine, centre_and_angles: VariableValues }, // Formats 8 and 9: centre x, centre y, start angle, end angle
+	Glyph { glyph_id: u16, paint: usize }, // Format 10, clipping its paint to the glyph's outline
+	ColourGlyph { glyph_id: u16 }, // Format 11, the paint of another version 1 glyph
+	Transform { kind: TransformKind, values: VariableValues, paint: usize }, // Formats 12 to 31
+	Composite { source: usize, mode: CompositeMode, backdrop: usize }, // Format 32
+	Unsupported(u8), // A format that isn't defined, which paints nothing
+}
+
+/// A range of glyphs that share a clip box, its x min, y min, x max and y max, outside which nothing of them is painted.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipRecord {
+	pub start_glyph_id: u16,
+	pub end_glyph_id: u16,
+	pub clip_box: VariableValues,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipBox {
+	pub x_min: f32,
+	pub y_min: f32,
+	pub x_max: f32,
+	pub y_max: f32,
+}
+
+/// A 2×3 affine transform, taking x and y to xx·x + xy·y + dx and yx·x + yy·y + dy.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Affine {
+	pub xx: f32,
+	pub yx: f32,
+	pub xy: f32,
+	pub yy: f32,
+	pub dx: f32,
+	pub dy: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GradientStop {
+	pub offset: f32,
+	pub colour: [f32; 4], // Premultiplied RGBA, from 0 to 1
+}
+
+/// A colour line with its colours looked up and its variations applied. Stops are sorted by offset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gradient {
+	pub extend: Extend,
+	pub stops: Vec<GradientStop>,
+}
+
+/// What fills an area, in the coordinates of the paint that made it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Brush {
+	Solid([f32; 4]), // Premultiplied RGBA, from 0 to 1
+	LinearGradient { start: (f32, f32), end: (f32, f32), gradient: Gradient }, // Colours are the same along lines at right angles to start to end
+	RadialGradient { start_centre: (f32, f32), start_radius: f32, end_centre: (f32, f32), end_radius: f32, gradient: Gradient },
+	SweepGradient { centre: (f32, f32), start_angle: f32, end_angle: f32, gradient: Gradient }, // In radians, counter-clockwise from the positive x axis
+}
+
+/// One step of drawing a version 1 colour glyph, in font units.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PaintCommand {
+	/// Fills where every clip glyph's outline, transformed, overlaps, or everywhere if there aren't any, with a brush drawn through its transform.
+	Fill { clips: Vec<(u16, Affine)>, brush: Brush, brush_transform: Affine },
+	/// Draws the source and backdrop commands separately then combines them, drawing the result over whatever is underneath.
+	Composite { mode: CompositeMode, source: Vec<PaintCommand>, backdrop: Vec<PaintCommand> },
+}
+
+/// What evaluating a glyph's paints needs besides the table.
+struct PaintContext<'a> {
+	palette: &'a [PaletteColour],
+	foreground: PaletteColour,
+	coordinates: &'a [f32],
+	remaining_visits: Cell<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaletteColour {
+	pub red: u8,
+	pub green: u8,
+	pub blue: u8,
+	pub alpha: u8,
+}
+
+pub struct ColourPaletteTable {
+	pub number_of_palette_entries: u16,
+	pub palettes: Vec<Vec<PaletteColour>>,
+	pub palette_types: Vec<u32>, // Flags for which backgrounds each palette suits, empty for version 0 tables
+	pub palette_labels: Vec<Option<u16>>, // Name ids, empty for version 0 tables
+}
+
+impl Affine {
+	pub const IDENTITY: Affine = Affine { xx: 1.0, yx: 0.0, xy: 0.0, yy: 1.0, dx: 0.0, dy: 0.0 };
+
+	pub fn translate(dx: f32, dy: f32) -> Affine {
+		Affine { dx, dy, ..Affine::IDENTITY }
+	}
+
+	pub fn scale(scale_x: f32, scale_y: f32) -> Affine {
+		Affine { xx: scale_x, yy: scale_y, ..Affine::IDENTITY }
+	}
+
+	/// Counter-clockwise, in radians.
+	pub fn rotate(angle: f32) -> Affine {
+		let (sin, cos) = angle.sin_cos();
+		Affine { xx: cos, yx: sin, xy: -sin, yy: cos, dx: 0.0, dy: 0.0 }
+	}
+
+	/// Counter-clockwise, in radians, so a positive x skew angle leans the top of a glyph to the left.
+	pub fn skew(x_skew_angle: f32, y_skew_angle: f32) -> Affine {
+		Affine { xx: 1.0, yx: y_skew_angle.tan(), xy: -x_skew_angle.tan(), yy: 1.0, dx: 0.0, dy: 0.0 }
+	}
+
+	/// This transform applied after `other`.
+	pub fn multiply(&self, other: &Affine) -> Affine {
+		Affine {
+			xx: self.xx * other.xx + self.xy * other.yx,
+			yx: self.yx * other.xx + self.yy * other.yx,
+			xy: self.xx * other.xy + self.xy * other.yy,
+			yy: self.yx * other.xy + self.yy * other.yy,
+			dx: self.xx * other.dx + self.xy * other.dy + self.dx,
+			dy: self.yx * other.dx + self.yy * other.dy + self.dy,
+		}
+	}
+
+	pub fn apply(&self, x: f32, y: f32) -> (f32, f32) {
+		(self.xx * x + self.xy * y + self.dx, self.yx * x + self.yy * y + self.dy)
+	}
+
+	/// Negative for transforms that mirror, which turns clockwise outlines anticlockwise.
+	pub fn determinant(&self) -> f32 {
+		self.xx * self.yy - self.xy * self.yx
+	}
+
+	/// None for transforms that flatten everything onto a line or a point.
+	pub fn invert(&self) -> Option<Affine> {
+		let determinant = self.determinant();
+		if determinant.abs() < f32::EPSILON {
+			return None;
+		}
+		let (xx, yx, xy, yy) = (self.yy / determinant, -self.yx / determinant, -self.xy / determinant, self.xx / determinant);
+		Some(Affine { xx, yx, xy, yy, dx: -(xx * self.dx + xy * self.dy), dy: -(yx * self.dx + yy * self.dy) })
+	}
+}
+
+impl TransformKind {
+	/// The transform for this kind's values, after any variations.
+	fn affine(&self, values: &[f32]) -> Affine {
+		let value = |index: usize| values.get(index).copied().unwrap_or(0.0);
+		let around_centre = |transform: Affine, centre_index: usize| {
+			let (centre_x, centre_y) = (value(centre_index), value(centre_index + 1));
+			Affine::translate(centre_x, centre_y).multiply(&transform).multiply(&Affine::translate(-centre_x, -centre_y))
+		};
+		match self {
+			TransformKind::Affine => Affine { xx: value(0), yx: value(1), xy: value(2), yy: value(3), dx: value(4), dy: value(5) },
+			TransformKind::Translate => Affine::translate(value(0), value(1)),
+			TransformKind::Scale => Affine::scale(value(0), value(1)),
+			TransformKind::ScaleAroundCentre => around_centre(Affine::scale(value(0), value(1)), 2),
+			TransformKind::ScaleUniform => Affine::scale(value(0), value(0)),
+			TransformKind::ScaleUniformAroundCentre => around_centre(Affine::scale(value(0), value(0)), 1),
+			TransformKind::Rotate => Affine::rotate(value(0) * PI),
+			TransformKind::RotateAroundCentre => around_centre(Affine::rotate(value(0) * PI), 1),
+			TransformKind::Skew => Affine::skew(value(0) * PI, value(1) * PI),
+			TransformKind::SkewAroundCentre => around_centre(Affine::skew(value(0) * PI, value(1) * PI), 2),
+		}
+	}
+}
+
+impl CompositeMode {
+	fn from_u8(mode: u8) -> Option<CompositeMode> {
+		const MODES: [CompositeMode; 28] = [
+			CompositeMode::Clear, CompositeMode::Source, CompositeMode::Destination, CompositeMode::SourceOver, CompositeMode::DestinationOver,
+			CompositeMode::SourceIn, CompositeMode::DestinationIn, CompositeMode::SourceOut, CompositeMode::DestinationOut, CompositeMode::SourceAtop,
+			CompositeMode::DestinationAtop, CompositeMode::Xor, CompositeMode::Plus, CompositeMode::Screen, CompositeMode::Overlay,
+			CompositeMode::Darken, CompositeMode::Lighten, CompositeMode::ColourDodge, CompositeMode::ColourBurn, CompositeMode::HardLight,
+			CompositeMode::SoftLight, CompositeMode::Difference, CompositeMode::Exclusion, CompositeMode::Multiply, CompositeMode::Hue,
+			CompositeMode::Saturation, CompositeMode::Colour, CompositeMode::Luminosity,
+		];
+		MODES.get(mode as usize).copied()
+	}
+}
+
+impl Gradient {
+	/// The colour at a position along the gradient, measured in the same units as the stop offsets.
+	/// The extend mode repeats the stretch from the first stop to the last.
+	pub fn colour_at(&self, position: f32) -> [f32; 4] {
+		let (Some(first), Some(last)) = (self.stops.first(), self.stops.last()) else {
+			return [0.0; 4];
+		};
+		let span = last.offset - first.offset;
+		let position = match self.extend {
+			_ if span <= 0.0 || !position.is_finite() => position,
+			Extend::Pad => position,
+			Extend::Repeat => first.offset + (position - first.offset).rem_euclid(span),
+			Extend::Reflect => {
+				let position_in_pair = (position - first.offset).rem_euclid(2.0 * span);
+				first.offset + if position_in_pair > span { 2.0 * span - position_in_pair } else { position_in_pair }
+			},
+		};
+		if position <= first.offset {
+			return first.colour;
+		}
+		for pair in self.stops.windows(2) {
+			let (start, end) = (pair[0], pair[1]);
+			if position < end.offset {
+				let amount = (position - start.offset) / (end.offset - start.offset);
+				return std::array::from_fn(|channel| start.colour[channel] + (end.colour[channel] - start.colour[channel]) * amount);
+			}
+		}
+		last.colour
+	}
+}
+
+impl Brush {
+	/// The brush's colour at a point in its own coordinates, premultiplied. Radial gradients are clear where neither circle reaches.
+	pub fn colour_at(&self, x: f32, y: f32) -> [f32; 4] {
+		match self {
+			Brush::Solid(colour) => *colour,
+			Brush::LinearGradient { start, end, gradient } => {
+				let (direction_x, direction_y) = (end.0 - start.0, end.1 - start.1);
+				let length_squared = direction_x * direction_x + direction_y * direction_y;
+				if length_squared == 0.0 {
+					return gradient.colour_at(0.0);
+				}
+				gradient.colour_at(((x - start.0) * direction_x + (y - start.1) * direction_y) / length_squared)
+			},
+			Brush::RadialGradient { start_centre, start_radius, end_centre, end_radius, gradient } => {
+				// The largest t where the circle interpolated between the two, with a radius that isn't negative, passes through the point.
+				let (centre_x, centre_y, radius) = (end_centre.0 - start_centre.0, end_centre.1 - start_centre.1, end_radius - start_radius);
+				let (point_x, point_y) = (x - start_centre.0, y - start_centre.1);
+				let a = centre_x * centre_x + centre_y * centre_y - radius * radius;
+				let b = point_x * centre_x + point_y * centre_y + start_radius * radius;
+				let c = point_x * point_x + point_y * point_y - start_radius * start_radius;
+				let solutions = if a.abs() < 1e-6 {
+					if b == 0.0 {
+						return [0.0; 4];
+					}
+					[c / (2.0 * b), f32::NEG_INFINITY]
+				} else {
+					let discriminant = b * b - a * c;
+					if discriminant < 0.0 {
+						return [0.0; 4];
+					}
+					let (first, second) = ((b + discriminant.sqrt()) / a, (b - discriminant.sqrt()) / a);
+					[first.max(second), first.min(second)]
+				};
+				match solutions.into_iter().find(|t| t.is_finite() && start_radius + t * radius >= 0.0) {
+					Some(t) => gradient.colour_at(t),
+					None => [0.0; 4],
+				}
+			},
+			Brush::SweepGradient { centre, start_angle, end_angle, gradient } => {
+				let angle = (y - centre.1).atan2(x - centre.0).rem_euclid(2.0 * PI);
+				let sweep = end_angle - start_angle;
+				if sweep.abs() < f32::EPSILON {
+					return gradient.colour_at(if angle < *start_angle { f32::NEG_INFINITY } else { f32::INFINITY });
+				}
+				gradient.colour_at((angle - start_angle) / sweep)
+			},
+		}
+	}
+}
+
+impl PaintContext<'_> {
+	/// A premultiplied palette colour, with the foreground entry, and any the palette doesn't have, in the text colour.
+	fn colour(&self, palette_entry_index: u16, alpha: f32) -> [f32; 4] {
+		let colour = match palette_entry_index {
+			FOREGROUND_PALETTE_ENTRY => self.foreground,
+			_ => self.palette.get(palette_entry_index as usize).copied().unwrap_or(self.foreground),
+		};
+		let alpha = (colour.alpha as f32 / 255.0 * alpha).clamp(0.0, 1.0);
+		[colour.red as f32 / 255.0 * alpha, colour.green as f32 / 255.0 * alpha, colour.blue as f32 / 255.0 * alpha, alpha]
+	}
+}
+
+impl ColourTable {
+	/// The layers a colour glyph is drawn with, bottom first. Glyphs that aren't colour glyphs have none.
+	pub fn layers(&self, glyph_id: u16) -> &[LayerRecord] {
+		let Ok(index) = self.base_glyphs.binary_search_by_key(&glyph_id, |base_glyph| base_glyph.glyph_id) else {
+			return &[];
+		};
+		let base_glyph = self.base_glyphs[index];
+		let first_layer_index = base_glyph.first_layer_index as usize;
+		self.layers.get(first_layer_index..first_layer_index + base_glyph.number_of_layers as usize).unwrap_or(&[])
+	}
+
+	/// The index of a glyph's version 1 paint, which renderers that understand it use in place of its layers.
+	pub fn base_paint(&self, glyph_id: u16) -> Option<usize> {
+		let index = self.base_glyph_paints.binary_search_by_key(&glyph_id, |(base_glyph_id, _)| *base_glyph_id).ok()?;
+		Some(self.base_glyph_paints[index].1)
+	}
+
+	pub fn clip_box(&self, glyph_id: u16, coordinates: &[f32]) -> Option<ClipBox> {
+		let clip = self.clips.iter().find(|clip| (clip.start_glyph_id..=clip.end_glyph_id).contains(&glyph_id))?;
+		let values = self.resolve(&clip.clip_box, coordinates);
+		Some(ClipBox { x_min: values[0], y_min: values[1], x_max: values[2], y_max: values[3] })
+	}
+
+	/// Evaluates a glyph's version 1 paint graph at the given normalised coordinates into what to draw, bottom first.
+	/// Colours come from the palette, with entries it doesn't have in the foreground colour.
+	pub fn paint(&self, glyph_id: u16, palette: &[PaletteColour], foreground: PaletteColour, coordinates: &[f32]) -> Option<Vec<PaintCommand>> {
+		let paint_index = self.base_paint(glyph_id)?;
+		let context = PaintContext { palette, foreground, coordinates, remaining_visits: Cell::new(MAX_PAINT_VISITS) };
+		let mut commands = Vec::new();
+		self.evaluate(paint_index, &context, &mut Vec::new(), Affine::IDENTITY, 0, &mut commands);
+		Some(commands)
+	}
+
+	fn evaluate(&self, paint_index: usize, context: &PaintContext, clips: &mut Vec<(u16, Affine)>, transform: Affine, depth: usize, commands: &mut Vec<PaintCommand>) {
+		if depth > MAX_PAINT_DEPTH || context.remaining_visits.get() == 0 {
+			return;
+		}
+		context.remaining_visits.set(context.remaining_visits.get() - 1);
+		let Some(paint) = self.paints.get(paint_index) else {
+			return;
+		};
+		let mut fill = |brush: Brush| commands.push(PaintCommand::Fill { clips: clips.clone(), brush, brush_transform: transform });
+		match paint {
+			Paint::Layers { first_layer_index, number_of_layers } => {
+				for &layer in self.layer_paints.iter().skip(*first_layer_index as usize).take(*number_of_layers as usize) {
+					self.evaluate(layer, context, clips, transform, depth + 1, commands);
+				}
+			},
+			Paint::Solid { palette_entry_index, alpha } => fill(Brush::Solid(context.colour(*palette_entry_index, self.resolve(alpha, context.coordinates)[0]))),
+			Paint::LinearGradient { colour_line, points } => {
+				let points = self.resolve(points, context.coordinates);
+				let (start, end, rotation) = ((points[0], points[1]), (points[2], points[3]), (points[4], points[5]));
+				// The gradient runs at right angles to the line from the start to the rotation point, so the end is moved onto the line through the start that way.
+				let (normal_x, normal_y) = (start.1 - rotation.1, rotation.0 - start.0);
+				let normal_length_squared = normal_x * normal_x + normal_y * normal_y;
+				let end = if normal_length_squared == 0.0 {
+					end
+				} else {
+					let distance = ((end.0 - start.0) * normal_x + (end.1 - start.1) * normal_y) / normal_length_squared;
+					(start.0 + normal_x * distance, start.1 + normal_y * distance)
+				};
+				fill(Brush::LinearGradient { start, end, gradient: self.gradient(colour_line, context) });
+			},
+			Paint::RadialGradient { colour_line, circles } => {
+				let circles = self.resolve(circles, context.coordinates);
+				fill(Brush::RadialGradient {
+					start_centre: (circles[0], circles[1]),
+					start_radius: circles[2],
+					end_centre: (circles[3], circles[4]),
+					end_radius: circles[5],
+					gradient: self.gradient(colour_line, context),
+				});
+			},
+			Paint::SweepGradient { colour_line, centre_and_angles } => {
+				let values = self.resolve(centre_and_angles, context.coordinates);
+				// Sweep angles are stored less 180°, so that a full turn from 0° to 360° fits in an F2DOT14.
+				fill(Brush::SweepGradient {
+					centre: (values[0], values[1]),
+					start_angle: (values[2] + 1.0) * PI,
+					end_angle: (values[3] + 1.0) * PI,
+					gradient: self.gradient(colour_line, context),
+				});
+			},
+			Paint::Glyph { glyph_id, paint } => {
+				clips.push((*glyph_id, transform));
+				self.evaluate(*paint, context, clips, transform, depth + 1, commands);
+				clips.pop();
+			},
+			Paint::ColourGlyph { glyph_id } => {
+				if let Some(paint) = self.base_paint(*glyph_id) {
+					self.evaluate(paint, context, clips, transform, depth + 1, commands);
+				}
+			},
+			Paint::Transform { kind, values, paint } => {
+				let transform = transform.multiply(&kind.affine(&self.resolve(values, context.coordinates)));
+				self.evaluate(*paint, context, clips, transform, depth + 1, commands);
+			},
+			Paint::Composite { source, mode, backdrop } => {
+				let (mut source_commands, mut backdrop_commands) = (Vec::new(), Vec::new());
+				self.evaluate(*source, context, clips, transform, depth + 1, &mut source_commands);
+				self.evaluate(*backdrop, context, clips, transform, depth + 1, &mut backdrop_commands);
+				commands.push(PaintCommand::Composite { mode: *mode, source: source_commands, backdrop: backdrop_commands });
+			},
+			Paint::Unsupported(_) => {},
+		}
+	}
+
+	fn gradient(&self, colour_line: &ColourLine, context: &PaintContext) -> Gradient {
+		let mut stops: Vec<GradientStop> = colour_line.stops.iter().map(|stop| {
+			let values = self.resolve(&stop.values, context.coordinates);
+			GradientStop { offset: values[0], colour: context.colour(stop.palette_entry_index, values[1]) }
+		}).collect();
+		stops.sort_by(|first, second| first.offset.total_cmp(&second.offset));
+		Gradient { extend: colour_line.extend, stops }
+	}
+
+	/// The values with their deltas at the given normalised coordinates added.
+	fn resolve(&self, values: &VariableValues, coordinates: &[f32]) -> Vec<f32> {
+		if values.variation_index_base == NO_VARIATION_INDEX || self.item_variation_store.is_none() || coordinates.iter().all(|coordinate| *coordinate == 0.0) {
+			return values.values.clone();
+		}
+		values.values.iter().zip(&values.units).enumerate().map(|(index, (value, unit))| {
+			value + self.delta(values.variation_index_base.saturating_add(index as u32), coordinates) * unit
+		}).collect()
+	}
+
+	/// Without a variation index map, the variation index holds the outer index in its high 16 bits and the inner index in its low 16 bits.
+	fn delta(&self, variation_index: u32, coordinates: &[f32]) -> f32 {
+		let Some(item_variation_store) = &self.item_variation_store else {
+			return 0.0;
+		};
+		let (outer_index, inner_index) = match &self.variation_index_map {
+			Some(variation_index_map) => match variation_index_map.index(variation_index as usize) {
+				Some(index) => index,
+				None => return 0.0,
+			},
+			None => ((variation_index >> 16) as u16, variation_index as u16),
+		};
+		item_variation_store.delta(outer_index, inner_index, coordinates)
+	}
+}
+
+impl ColourPaletteTable {
+	/// A colour from a palette, or None for the foreground entry and entries the palette doesn't have.
+	pub fn colour(&self, palette_index: usize, palette_entry_index: u16) -> Option<PaletteColour> {
+		if palette_entry_index == FOREGROUND_PALETTE_ENTRY {
+			return None;
+		}
+		self.palettes.get(palette_index)?.get(palette_entry_index as usize).copied()
+	}
+
+	/// The first palette the font marks as suited to a dark or light background, if it marks any.
+	pub fn palette_for_background(&self, dark_background: bool) -> Option<usize> {
+		let flag = if dark_background { USABLE_WITH_DARK_BACKGROUND } else { USABLE_WITH_LIGHT_BACKGROUND };
+		self.palette_types.iter().position(|palette_type| palette_type & flag != 0)
+	}
+}
+
+impl FromTTFReader for ColourTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<ColourTable, TrueTypeFontReaderError> {
+		let offset = offset as u64;
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+
+		let version: u16 = ttf_reader.read_bytes()?;
+		if version > 1 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::Colour, version, 0));
+		}
+		let number_of_base_glyph_records: u16 = ttf_reader.read_bytes()?;
+		let base_glyph_records_offset: u32 = ttf_reader.read_bytes()?;
+		let layer_records_offset: u32 = ttf_reader.read_bytes()?;
+		let number_of_layer_records: u16 = ttf_reader.read_bytes()?;
+		// Version 1 adds a graph of paints after the same header.
+		let (base_glyph_list_offset, layer_list_offset, clip_list_offset, variation_index_map_offset, item_variation_store_offset): (u32, u32, u32, u32, u32) = match version {
+			0 => (0, 0, 0, 0, 0),
+			_ => (ttf_reader.read_bytes()?, ttf_reader.read_bytes()?, ttf_reader.read_bytes()?, ttf_reader.read_bytes()?, ttf_reader.read_bytes()?),
+		};
+
+		let mut base_glyphs: Vec<BaseGlyphRecord> = Vec::with_capacity(number_of_base_glyph_records as usize);
+		if number_of_base_glyph_records > 0 {
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset + base_glyph_records_offset as u64))?;
+			for _ in 0..number_of_base_glyph_records {
+				base_glyphs.push(BaseGlyphRecord {
+					glyph_id: ttf_reader.read_bytes()?,
+					first_layer_index: ttf_reader.read_bytes()?,
+					number_of_layers: ttf_reader.read_bytes()?,
+				});
+			}
+		}
+		if base_glyphs.windows(2).any(|pair| pair[0].glyph_id >= pair[1].glyph_id) {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::Colour, "base glyph records should be sorted by glyph id"));
+		}
+
+		let mut layers: Vec<LayerRecord> = Vec::with_capacity(number_of_layer_records as usize);
+		if number_of_layer_records > 0 {
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset + layer_records_offset as u64))?;
+			for _ in 0..number_of_layer_records {
+				layers.push(LayerRecord {
+					glyph_id: ttf_reader.read_bytes()?,
+					palette_entry_index: ttf_reader.read_bytes()?,
+				});
+			}
+		}
+
+		let mut paints: Vec<Paint> = Vec::new();
+		let mut paint_indices: HashMap<u64, usize> = HashMap::new();
+		let mut base_glyph_paints: Vec<(u16, usize)> = Vec::new();
+		if base_glyph_list_offset != 0 {
+			let base_glyph_list_offset = offset + base_glyph_list_offset as u64;
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(base_glyph_list_offset))?;
+			let base_glyph_paint_count: u32 = ttf_reader.read_bytes()?;
+			if base_glyph_paint_count as u64 * 6 > ttf_reader.remaining()? {
+				return Err(TrueTypeFontReaderError::InvalidData(TableTag::Colour, "base glyph paint records should fit in the table"));
+			}
+			let mut paint_offsets: Vec<(u16, u32)> = Vec::with_capacity(base_glyph_paint_count as usize);
+			for _ in 0..base_glyph_paint_count {
+				paint_offsets.push((ttf_reader.read_bytes()?, ttf_reader.read_bytes()?));
+			}
+			for (glyph_id, paint_offset) in paint_offsets {
+				base_glyph_paints.push((glyph_id, read_paint(ttf_reader, base_glyph_list_offset + paint_offset as u64, &mut paints, &mut paint_indices, 0)?));
+			}
+		}
+		if base_glyph_paints.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::Colour, "base glyph paint records should be sorted by glyph id"));
+		}
+
+		let mut layer_paints: Vec<usize> = Vec::new();
+		if layer_list_offset != 0 {
+			let layer_list_offset = offset + layer_list_offset as u64;
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(layer_list_offset))?;
+			let layer_count: u32 = ttf_reader.read_bytes()?;
+			if layer_count as u64 * 4 > ttf_reader.remaining()? {
+				return Err(TrueTypeFontReaderError::InvalidData(TableTag::Colour, "layer paint offsets should fit in the table"));
+			}
+			let mut paint_offsets: Vec<u32> = Vec::with_capacity(layer_count as usize);
+			for _ in 0..layer_count {
+				paint_offsets.push(ttf_reader.read_bytes()?);
+			}
+			for paint_offset in paint_offsets {
+				layer_paints.push(read_paint(ttf_reader, layer_list_offset + paint_offset as u64, &mut paints, &mut paint_indices, 0)?);
+			}
+		}
+
+		let clips = match clip_list_offset {
+			0 => Vec::new(),
+			clip_list_offset => read_clips(ttf_reader, offset + clip_list_offset as u64)?,
+		};
+		let variation_index_map = match variation_index_map_offset {
+			0 => None,
+			variation_index_map_offset => Some(ttf_reader.read((offset + variation_index_map_offset as u64, TableTag::Colour))?),
+		};
+		let item_variation_store = match item_variation_store_offset {
+			0 => None,
+			item_variation_store_offset => Some(ttf_reader.read((offset + item_variation_store_offset as u64, TableTag::Colour))?),
+		};
+
+		Ok(ColourTable {
+			base_glyphs,
+			layers,
+			base_glyph_paints,
+			layer_paints,
+			clips,
+			paints,
+			variation_index_map,
+			item_variation_store,
+		})
+	}
+}
+
+/// How a paint's value is stored, which decides how it's scaled.
+#[derive(Clone, Copy)]
+enum ValueFormat {
+	FWord,
+	UFWord,
+	F2Dot14,
+	Fixed,
+}
+
+/// Reads values stored one after the other, followed by the variation index of the first for the variable formats.
+fn read_values<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, formats: &[ValueFormat], variable: bool) -> Result<VariableValues, TrueTypeFontReaderError> {
+	let mut values = Vec::with_capacity(formats.len());
+	let mut units = Vec::with_capacity(formats.len());
+	for format in formats {
+		let (value, unit) = match format {
+			ValueFormat::FWord => (ttf_reader.read_bytes::<i16>()? as f32, 1.0),
+			ValueFormat::UFWord => (ttf_reader.read_bytes::<u16>()? as f32, 1.0),
+			ValueFormat::F2Dot14 => (ttf_reader.read_bytes::<i16>()? as f32 / 16384.0, 1.0 / 16384.0),
+			ValueFormat::Fixed => (ttf_reader.read_bytes::<u32>()? as i32 as f32 / 65536.0, 1.0 / 65536.0),
+		};
+		values.push(value);
+		units.push(unit);
+	}
+	let variation_index_base = if variable { ttf_reader.read_bytes()? } else { NO_VARIATION_INDEX };
+	Ok(VariableValues { values, units, variation_index_base })
+}
+
+fn read_colour_line<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64, variable: bool) -> Result<ColourLine, TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	let extend = match ttf_reader.read_bytes::<u8>()? {
+		1 => Extend::Repeat,
+		2 => Extend::Reflect,
+		_ => Extend::Pad, // Including modes that aren't defined yet
+	};
+	let stop_count: u16 = ttf_reader.read_bytes()?;
+	let mut stops = Vec::with_capacity(stop_count as usize);
+	for _ in 0..stop_count {
+		let stop_offset: i16 = ttf_reader.read_bytes()?;
+		let palette_entry_index: u16 = ttf_reader.read_bytes()?;
+		let alpha: i16 = ttf_reader.read_bytes()?;
+		let variation_index_base = if variable { ttf_reader.read_bytes()? } else { NO_VARIATION_INDEX };
+		stops.push(ColourStop {
+			palette_entry_index,
+			values: VariableValues { values: vec![stop_offset as f32 / 16384.0, alpha as f32 / 16384.0], units: vec![1.0 / 16384.0; 2], variation_index_base },
+		});
+	}
+	Ok(ColourLine { extend, stops })
+}
+
+/// Reads the paint at an offset into `paints`, along with every paint under it, giving its index there.
+/// Paints shared by several others are only read once, which also stops paints that refer back to themselves being read forever.
+fn read_paint<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64, paints: &mut Vec<Paint>, paint_indices: &mut HashMap<u64, usize>, depth: usize) -> Result<usize, TrueTypeFontReaderError> {
+	if let Some(&index) = paint_indices.get(&offset) {
+		return Ok(index);
+	}
+	if depth > MAX_PAINT_DEPTH {
+		return Err(TrueTypeFontReaderError::InvalidData(TableTag::Colour, "paints should not nest so deeply"));
+	}
+	let index = paints.len();
+	paints.push(Paint::Unsupported(0));
+	paint_indices.insert(offset, index);
+
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	let format: u8 = ttf_reader.read_bytes()?;
+	let variable = format % 2 == 1; // Of the formats that have a variable version
+	// Everything of this paint is read before the paints under it, which move the reader.
+	let mut read_child = |ttf_reader: &mut TrueTypeFontReader<Source>, child_offset: u32| read_paint(ttf_reader, offset + child_offset as u64, paints, paint_indices, depth + 1);
+	let paint = match format {
+		1 => {
+			let number_of_layers: u8 = ttf_reader.read_bytes()?;
+			Paint::Layers { first_layer_index: ttf_reader.read_bytes()?, number_of_layers }
+		},
+		2 | 3 => {
+			let palette_entry_index: u16 = ttf_reader.read_bytes()?;
+			Paint::Solid { palette_entry_index, alpha: read_values(ttf_reader, &[ValueFormat::F2Dot14], format == 3)? }
+		},
+		4..=9 => {
+			let colour_line_offset = read_uint24(ttf_reader)?;
+			let formats: &[ValueFormat] = match format {
+				4 | 5 => &[ValueFormat::FWord; 6],
+				6 | 7 => &[ValueFormat::FWord, ValueFormat::FWord, ValueFormat::UFWord, ValueFormat::FWord, ValueFormat::FWord, ValueFormat::UFWord],
+				_ => &[ValueFormat::FWord, ValueFormat::FWord, ValueFormat::F2Dot14, ValueFormat::F2Dot14],
+			};
+			let values = read_values(ttf_reader, formats, variable)?;
+			let colour_line = read_colour_line(ttf_reader, offset + colour_line_offset as u64, variable)?;
+			match format {
+				4 | 5 => Paint::LinearGradient { colour_line, points: values },
+				6 | 7 => Paint::RadialGradient { colour_line, circles: values },
+				_ => Paint::SweepGradient { colour_line, centre_and_angles: values },
+			}
+		},
+		10 => {
+			let paint_offset = read_uint24(ttf_reader)?;
+			let glyph_id: u16 = ttf_reader.read_bytes()?;
+			Paint::Glyph { glyph_id, paint: read_child(ttf_reader, paint_offset)? }
+		},
+		11 => Paint::ColourGlyph { glyph_id: ttf_reader.read_bytes()? },
+		12 | 13 => {
+			let paint_offset = read_uint24(ttf_reader)?;
+			let transform_offset = read_uint24(ttf_reader)?;
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset + transform_offset as u64))?;
+			let values = read_values(ttf_reader, &[ValueFormat::Fixed; 6], variable)?;
+			Paint::Transform { kind: TransformKind::Affine, values, paint: read_child(ttf_reader, paint_offset)? }
+		},
+		14..=31 => {
+			let paint_offset = read_uint24(ttf_reader)?;
+			let (kind, formats): (TransformKind, &[ValueFormat]) = match format {
+				14 | 15 => (TransformKind::Translate, &[ValueFormat::FWord; 2]),
+				16 | 17 => (TransformKind::Scale, &[ValueFormat::F2Dot14; 2]),
+				18 | 19 => (TransformKind::ScaleAroundCentre, &[ValueFormat::F2Dot14, ValueFormat::F2Dot14, ValueFormat::FWord, ValueFormat::FWord]),
+				20 | 21 => (TransformKind::ScaleUniform, &[ValueFormat::F2Dot14]),
+				22 | 23 => (TransformKind::ScaleUniformAroundCentre, &[ValueFormat::F2Dot14, ValueFormat::FWord, ValueFormat::FWord]),
+				24 | 25 => (TransformKind::Rotate, &[ValueFormat::F2Dot14]),
+				26 | 27 => (TransformKind::RotateAroundCentre, &[ValueFormat::F2Dot14, ValueFormat::FWord, ValueFormat::FWord]),
+				28 | 29 => (TransformKind::Skew, &[ValueFormat::F2Dot14; 2]),
+				_ => (TransformKind::SkewAroundCentre, &[ValueFormat::F2Dot14, ValueFormat::F2Dot14, ValueFormat::FWord, ValueFormat::FWord]),
+			};
+			let values = read_values(ttf_reader, formats, variable)?;
+			Paint::Transform { kind, values, paint: read_child(ttf_reader, paint_offset)? }
+		},
+		32 => {
+			let source_offset = read_uint24(ttf_reader)?;
+			let mode: u8 = ttf_reader.read_bytes()?;
+			let backdrop_offset = read_uint24(ttf_reader)?;
+			match CompositeMode::from_u8(mode) {
+				Some(mode) => Paint::Composite { source: read_child(ttf_reader, source_offset)?, mode, backdrop: read_child(ttf_reader, backdrop_offset)? },
+				None => Paint::Unsupported(format),
+			}
+		},
+		_ => Paint::Unsupported(format),
+	};
+	paints[index] = paint;
+	Ok(index)
+}
+
+fn read_clips<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64) -> Result<Vec<ClipRecord>, TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	let format: u8 = ttf_reader.read_bytes()?;
+	if format != 1 {
+		return Err(TrueTypeFontReaderError::InvalidData(TableTag::Colour, "clip list should be format 1"));
+	}
+	let clip_count: u32 = ttf_reader.read_bytes()?;
+	if clip_count as u64 * 7 > ttf_reader.remaining()? {
+		return Err(TrueTypeFontReaderError::InvalidData(TableTag::Colour, "clip records should fit in the table"));
+	}
+	let mut clip_box_offsets: Vec<(u16, u16, u32)> = Vec::with_capacity(clip_count as usize);
+	for _ in 0..clip_count {
+		clip_box_offsets.push((ttf_reader.read_bytes()?, ttf_reader.read_bytes()?, read_uint24(ttf_reader)?));
+	}
+
+	let mut clips = Vec::with_capacity(clip_box_offsets.len());
+	for (start_glyph_id, end_glyph_id, clip_box_offset) in clip_box_offsets {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset + clip_box_offset as u64))?;
+		let clip_box_format: u8 = ttf_reader.read_bytes()?;
+		if clip_box_format != 1 && clip_box_format != 2 {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::Colour, "clip box should be format 1 or 2"));
+		}
+		clips.push(ClipRecord { start_glyph_id, end_glyph_id, clip_box: read_values(ttf_reader, &[ValueFormat::FWord; 4], clip_box_format == 2)? });
+	}
+	Ok(clips)
+}
+
+impl FromTTFReader for ColourPaletteTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<ColourPaletteTable, TrueTypeFontReaderError> {
+		let offset = offset as u64;
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+
+		let version: u16 = ttf_reader.read_bytes()?;
+		if version > 1 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::ColourPalette, version, 0));
+		}
+		let number_of_palette_entries: u16 = ttf_reader.read_bytes()?;
+		let number_of_palettes: u16 = ttf_reader.read_bytes()?;
+		let number_of_colour_records: u16 = ttf_reader.read_bytes()?;
+		let colour_records_offset: u32 = ttf_reader.read_bytes()?;
+		let mut first_colour_record_indices: Vec<u16> = Vec::with_capacity(number_of_palettes as usize);
+		for _ in 0..number_of_palettes {
+			first_colour_record_indices.push(ttf_reader.read_bytes()?);
+		}
+		let (palette_types_offset, palette_labels_offset): (u32, u32) = match version {
+			0 => (0, 0),
+			_ => (ttf_reader.read_bytes()?, ttf_reader.read_bytes()?),
+		};
+
+		// Palettes are runs of the same colour records, which they may share.
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset + colour_records_offset as u64))?;
+		let mut colour_records: Vec<PaletteColour> = Vec::with_capacity(number_of_colour_records as usize);
+		for _ in 0..number_of_colour_records {
+			let blue: u8 = ttf_reader.read_bytes()?;
+			let green: u8 = ttf_reader.read_bytes()?;
+			let red: u8 = ttf_reader.read_bytes()?;
+			let alpha: u8 = ttf_reader.read_bytes()?;
+			colour_records.push(PaletteColour { red, green, blue, alpha });
+		}
+		let mut palettes: Vec<Vec<PaletteColour>> = Vec::with_capacity(number_of_palettes as usize);
+		for first_colour_record_index in first_colour_record_indices {
+			let first_colour_record_index = first_colour_record_index as usize;
+			match colour_records.get(first_colour_record_index..first_colour_record_index + number_of_palette_entries as usize) {
+				Some(palette) => palettes.push(palette.to_vec()),
+				None => return Err(TrueTypeFontReaderError::InvalidData(TableTag::ColourPalette, "palette should fit within the colour records")),
+			}
+		}
+
+		let mut palette_types: Vec<u32> = Vec::new();
+		if palette_types_offset != 0 {
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset + palette_types_offset as u64))?;
+			for _ in 0..number_of_palettes {
+				palette_types.push(ttf_reader.read_bytes()?);
+			}
+		}
+		let mut palette_labels: Vec<Option<u16>> = Vec::new();
+		if palette_labels_offset != 0 {
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset + palette_labels_offset as u64))?;
+			for _ in 0..number_of_palettes {
+				palette_labels.push(Some(ttf_reader.read_bytes()?).filter(|&name_id| name_id != 0xFFFF));
+			}
+		}
+
+		Ok(ColourPaletteTable {
+			number_of_palette_entries,
+			palettes,
+			palette_types,
+			palette_labels,
+		})
+	}
+}
@@ -1,52 +1,523 @@
-use std::{fmt::Display, sync::{Arc, Mutex}};
+use std::{borrow::Cow, collections::HashMap, fmt::Display, io::{self, Read, Seek}, sync::{Arc, Mutex}};
 
 use mircalla_types::{units::Pixels, vectors::{Colour, Position, Size}};
 use winit::dpi::PhysicalSize;
 
-use crate::{ttf_parser::GlyphIntermediate, ttf_reader::{self, HorizontalMetric}};
+use crate::{bitmap_reader::{BitmapGlyphs, PNG_GRAPHIC_TYPE}, font::{autohinter::AutoHinter, hinting::Hinting}, cff_reader::{CharStringError, CompactFontFormat2Table}, brotli::BrotliError, colour_reader::{Affine, ClipBox, ColourPaletteTable, ColourTable, LayerRecord, PaintCommand, PaletteColour}, glyph_definition_reader::{CaretValue, GlyphClass, GlyphDefinitionTable}, glyph_positioning_reader::{GlyphPosition, GlyphPositioningTable, DEFAULT_POSITIONING_FEATURES, VERTICAL_POSITIONING_FEATURES}, glyph_substitution_reader::{GlyphSubstitutionTable, DEFAULT_SUBSTITUTION_FEATURES, VERTICAL_SUBSTITUTION_FEATURES}, inflate::InflateError, kerning_reader::KerningTable, naming_reader::{self, NamingTable}, png, ttf_parser::{match_component_points, GlyphDataIntermediate, GlyphIntermediate}, ttf_reader::{self, CollectionHeader, GlyphDataRaw, GlyphOffset, GlyphRaw, HorizontalMetric, SimpleGlyphRaw, TableDirectory, TableTag, TrueTypeFontReader, TrueTypeFontReaderError, VerticalMetric, VerticalOriginTable}, variation_reader::{AxisVariationsTable, GlyphVariationsTable, HorizontalMetricsVariationsTable, InstanceRecord, MetricsVariationsTable, VariationAxisRecord}};
 
+pub mod colour_rasterizer;
 pub mod font_renderer;
+pub mod autohinter;
+pub mod hinting;
 
 pub struct Font {
-	pub glyphs: Mutex<Vec<LazyGlyph>>,
-	pub mappings: Vec<Mapping>,
+	pub glyphs: Arc<Mutex<Vec<LazyGlyph>>>,
+	pub mappings: Arc<Vec<Mapping>>,
+	pub selected_mapping: Option<usize>, // Index into mappings of the subtable used to look up characters
+	pub names: Arc<Vec<Name>>,
 	pub units_per_em: FontUnits<u16>,
 	pub typographic_descender: FontUnits<i16>,
 	pub typographic_ascender: FontUnits<i16>,
 	pub line_spacing: FontUnits<i16>,
+	pub italic_angle: f32, // Degrees counter-clockwise from vertical
+	pub underline_position: FontUnits<i16>, // Top of the underline, relative to the baseline
+	pub underline_thickness: FontUnits<i16>,
+	pub is_fixed_pitch: bool,
+	pub glyph_names: Arc<Vec<String>>, // Empty for fonts that don't name their glyphs
+	pub kerning: Arc<HashMap<(u16, u16), i16>>, // Adjustments to the advance between pairs of glyphs, from the kern table
+	pub glyph_positioning: Option<Arc<GlyphPositioningTable>>,
+	pub glyph_substitution: Option<Arc<GlyphSubstitutionTable>>,
+	pub glyph_definition: Option<Arc<GlyphDefinitionTable>>,
+	pub vertical_metrics: Option<Arc<VerticalMetrics>>, // None for fonts without a vhea table
+	pub colour_glyphs: Option<Arc<ColourTable>>,
+	pub colour_palettes: Option<Arc<ColourPaletteTable>>,
+	pub bitmap_glyphs: Option<Arc<BitmapGlyphs>>, // From CBLC and CBDT, or sbix
+	pub hinting: Option<Arc<Hinting>>, // None for fonts without glyf outlines or instructions for them
+	pub auto_hinter: Option<Arc<AutoHinter>>, // For glyf and CFF outlines without instructions, when the font isn't variable
 	pub number_of_glyphs: usize,
+	pub variations: Option<Arc<FontVariations>>, // None for fonts without variable outlines
+	pub variation_coordinates: Vec<f32>, // Normalised, one per variation axis
 }
 
 impl Font {
 	pub fn get_index(&self, character: char) -> Option<usize> {
-		match self.mappings[0].get_glyph_id(character as u64) {
+		match self.get_glyph_id(character) {
 			Some(index) => Some(index as usize),
 			None => None,
 		}
 	}
 
+	fn get_glyph_id(&self, character: char) -> Option<u16> {
+		self.mapping()?.get_glyph_id(character as u64)
+	}
+
 	pub fn get_character_codes(&self, glyph_index: u16) -> Vec<char> {
-		self.mappings[0].get_character_codes(glyph_index)
+		match self.mapping() {
+			Some(mapping) => mapping.get_character_codes(glyph_index),
+			None => Vec::new(),
+		}
+	}
+
+	/// The cmap subtable characters are looked up in, chosen when the font is loaded unless changed with `select_mapping`.
+	pub fn mapping(&self) -> Option<&Mapping> {
+		self.mappings.get(self.selected_mapping?)
+	}
+
+	/// Looks characters up in the subtable for the given platform and encoding instead, such as `(1, 0)` for Mac Roman.
+	/// Returns None and keeps the current subtable if the font doesn't have one for them.
+	pub fn select_mapping(&mut self, platform_id: u16, encoding_id: u16) -> Option<&Mapping> {
+		let index = self.mappings.iter().position(|mapping| mapping.platform_id == platform_id && mapping.encoding_id == encoding_id && !matches!(mapping.format, MappingFormat::TrueTypeFormat14(_)))?;
+		self.selected_mapping = Some(index);
+		self.mapping()
+	}
+
+	/// The glyph for a character followed by a variation selector, such as U+FE0F for emoji presentation or an ideographic variation selector.
+	/// Sequences the font doesn't give a glyph of their own use the character's usual glyph.
+	pub fn get_index_with_variation_selector(&self, character: char, variation_selector: char) -> Option<usize> {
+		let variation_glyph_id = self.mappings.iter().find_map(|mapping| match mapping {
+			Mapping { format: MappingFormat::TrueTypeFormat14(mapping), .. } => mapping.get_glyph_id(character as u64, variation_selector as u64),
+			_ => None,
+		});
+		match variation_glyph_id {
+			Some(glyph_id) => Some(glyph_id as usize),
+			None => self.get_index(character),
+		}
+	}
+
+	/// A string from the naming table in the given language, a BCP 47 tag such as `"de"` or `"zh-Hant"`.
+	/// Falls back to the same language in another region, then to English, then to whichever language the font has.
+	pub fn name(&self, name_id: u16, language: &str) -> Option<&str> {
+		let primary_language = language.split('-').next().unwrap_or(language);
+		let mut best: Option<(&Name, u8)> = None;
+		for name in self.names.iter().filter(|name| name.name_id == name_id) {
+			let score = match &name.language {
+				Some(tag) if tag.eq_ignore_ascii_case(language) => 3,
+				Some(tag) if tag.split('-').next().is_some_and(|primary| primary.eq_ignore_ascii_case(primary_language)) => 2,
+				Some(tag) if tag.split('-').next() == Some("en") => 1,
+				_ => 0,
+			};
+			if best.is_none_or(|(_, best_score)| score > best_score) {
+				best = Some((name, score));
+			}
+		}
+		best.map(|(name, _)| name.value.as_str())
+	}
+
+	/// The family the font belongs to, such as "Source Sans 3", using the typographic family where the font gives one.
+	pub fn family_name(&self, language: &str) -> Option<&str> {
+		self.name(naming_reader::TYPOGRAPHIC_FAMILY_NAME_ID, language).or_else(|| self.name(naming_reader::FAMILY_NAME_ID, language))
+	}
+
+	/// The style within its family, such as "Semibold Italic".
+	pub fn style_name(&self, language: &str) -> Option<&str> {
+		self.name(naming_reader::TYPOGRAPHIC_SUBFAMILY_NAME_ID, language).or_else(|| self.name(naming_reader::SUBFAMILY_NAME_ID, language))
+	}
+
+	pub fn full_name(&self, language: &str) -> Option<&str> {
+		self.name(naming_reader::FULL_NAME_ID, language)
+	}
+
+	/// The PostScript name, which is always ASCII and shouldn't vary with language.
+	pub fn postscript_name(&self) -> Option<&str> {
+		self.name(naming_reader::POSTSCRIPT_NAME_ID, "en")
+	}
+
+	/// The PostScript name of a glyph, such as "Adieresis" or "uni0410", if the font names its glyphs.
+	pub fn glyph_name(&self, index: usize) -> Option<&str> {
+		self.glyph_names.get(index).map(|name| name.as_str())
+	}
+
+	pub fn glyph_by_name(&self, name: &str) -> Option<usize> {
+		self.glyph_names.iter().position(|glyph_name| glyph_name == name)
+	}
+
+	/// Whether a glyph is a base, ligature, mark or part of a character, if the font says.
+	pub fn glyph_class(&self, index: usize) -> Option<GlyphClass> {
+		self.glyph_definition.as_ref()?.glyph_class(u16::try_from(index).ok()?)
+	}
+
+	/// Where the caret can go between the components of a ligature glyph, in order. Other glyphs have none.
+	pub fn ligature_carets(&self, index: usize) -> &[CaretValue] {
+		match (&self.glyph_definition, u16::try_from(index)) {
+			(Some(glyph_definition), Ok(index)) => glyph_definition.ligature_carets(index),
+			_ => &[],
+		}
+	}
+
+	/// How much closer together, when negative, or further apart two glyphs should be than their advance widths alone would put them.
+	pub fn get_kerning(&self, left_glyph_id: u16, right_glyph_id: u16) -> FontUnits<i16> {
+		self.kerning.get(&(left_glyph_id, right_glyph_id)).copied().unwrap_or(0).into()
+	}
+
+	/// Maps text to glyphs, substitutes them with the font's GSUB lookups and positions them with its GPOS lookups,
+	/// for a script, such as `*b"latn"`, and the features turned on, such as those from `default_features`.
+	/// Fonts without GPOS kerning are kerned with their kern table instead.
+	pub fn layout_glyphs(&self, text: &str, script_tag: [u8; 4], feature_tags: &[[u8; 4]]) -> Vec<PositionedGlyph> {
+		let glyph_ids = self.substitute_glyphs(text, script_tag, feature_tags);
+		let advances: Vec<i32> = glyph_ids.iter().map(|&glyph_id| self.get_glyph(glyph_id as usize).map_or(0, |glyph| glyph.advance_width.value as i32)).collect();
+
+		let mut kerned = !feature_tags.contains(b"kern");
+		let mut positions = vec![GlyphPosition::default(); glyph_ids.len()];
+		if let Some(glyph_positioning) = &self.glyph_positioning {
+			kerned |= !glyph_positioning.lookup_indices(script_tag, None, &[*b"kern"]).is_empty();
+			let lookup_indices = glyph_positioning.lookup_indices(script_tag, None, feature_tags);
+			positions = glyph_positioning.position(&glyph_ids, &advances, &lookup_indices, self.glyph_definition.as_deref());
+		}
+		if !kerned {
+			for (index, pair) in glyph_ids.windows(2).enumerate() {
+				positions[index].x_advance += self.get_kerning(pair[0], pair[1]).value as i32;
+			}
+		}
+
+		glyph_ids.iter().zip(advances).zip(positions).map(|((&glyph_id, advance), position)| PositionedGlyph {
+			glyph_id,
+			advance: (advance + position.x_advance).into(),
+			offset: (FontUnits::from(position.x_placement), FontUnits::from(position.y_placement)).into(),
+		}).collect()
+	}
+
+	/// Lays text out from top to bottom for a vertical column, like `layout_glyphs`, along with the `vert` and `vrt2` alternates and `vkrn` kerning.
+	/// Each glyph's offset puts the centre of the top of its em box on the pen, and its advance is downward.
+	pub fn layout_vertical_glyphs(&self, text: &str, script_tag: [u8; 4], feature_tags: &[[u8; 4]]) -> Vec<PositionedGlyph> {
+		// Horizontal kerning would move glyphs across the column rather than along it.
+		let feature_tags: Vec<[u8; 4]> = feature_tags.iter().filter(|&feature_tag| feature_tag != b"kern")
+			.chain(VERTICAL_SUBSTITUTION_FEATURES.iter())
+			.chain(VERTICAL_POSITIONING_FEATURES.iter())
+			.copied().collect();
+		let glyph_ids = self.substitute_glyphs(text, script_tag, &feature_tags);
+		// Glyph ids past the end of the font, which a broken GSUB can give, take up no room.
+		let metrics: Vec<(i32, i32, i32)> = glyph_ids.iter().map(|&glyph_id| self.get_glyph(glyph_id as usize).map_or((0, 0, 0), |glyph| {
+			(glyph.advance_height.value as i32, glyph.advance_width.value as i32, glyph.vertical_origin_y().value as i32)
+		})).collect();
+
+		// GPOS adds to advances but doesn't need them, and vertical lookups move the advance with y values, which are up, so the advance shrinks.
+		let mut positions = vec![GlyphPosition::default(); glyph_ids.len()];
+		if let Some(glyph_positioning) = &self.glyph_positioning {
+			let lookup_indices = glyph_positioning.lookup_indices(script_tag, None, &feature_tags);
+			positions = glyph_positioning.position(&glyph_ids, &vec![0; glyph_ids.len()], &lookup_indices, self.glyph_definition.as_deref());
+		}
+
+		glyph_ids.iter().zip(metrics).zip(positions).map(|((&glyph_id, (advance_height, advance_width, vertical_origin_y)), position)| PositionedGlyph {
+			glyph_id,
+			advance: (advance_height - position.y_advance).into(),
+			offset: (FontUnits::from(position.x_placement - advance_width / 2), FontUnits::from(position.y_placement - vertical_origin_y)).into(),
+		}).collect()
+	}
+
+	/// Maps text to glyphs and runs the GSUB lookups for the features over them.
+	/// Variation selectors pick the glyph for the character before them and aren't given a glyph of their own.
+	fn substitute_glyphs(&self, text: &str, script_tag: [u8; 4], feature_tags: &[[u8; 4]]) -> Vec<u16> {
+		let mut glyph_ids: Vec<u16> = Vec::with_capacity(text.len());
+		let mut characters = text.chars().peekable();
+		while let Some(character) = characters.next() {
+			if is_variation_selector(character) {
+				continue;
+			}
+			let glyph_id = match characters.next_if(|&next| is_variation_selector(next)) {
+				Some(variation_selector) => self.get_index_with_variation_selector(character, variation_selector).map(|glyph_id| glyph_id as u16),
+				None => self.get_glyph_id(character),
+			};
+			glyph_ids.push(glyph_id.unwrap_or(0));
+		}
+		if let Some(glyph_substitution) = &self.glyph_substitution {
+			glyph_substitution.substitute(&mut glyph_ids, &glyph_substitution.lookup_indices(script_tag, None, feature_tags), self.glyph_definition.as_deref());
+		}
+		glyph_ids
+	}
+
+	/// How far apart columns of vertical text are, from vhea, or the line spacing when the font doesn't say.
+	pub fn column_spacing(&self) -> FontUnits<i16> {
+		match &self.vertical_metrics {
+			Some(vertical_metrics) if vertical_metrics.ascender.value - vertical_metrics.descender.value > 0 => {
+				vertical_metrics.ascender - vertical_metrics.descender + vertical_metrics.line_gap
+			},
+			_ => self.line_spacing,
+		}
+	}
+
+	/// The vertical metrics of a glyph whose outline reaches up to `y_max`.
+	/// Fonts without vmtx have every glyph's em box run from the ascender to the descender, and VORG moves the top of the box for CFF fonts.
+	fn vertical_metric(&self, glyph_id: usize, y_max: i16) -> VerticalMetric {
+		let vertical_metrics = self.vertical_metrics.as_deref();
+		let metric = vertical_metrics.and_then(|vertical_metrics| vertical_metrics.metrics.get(glyph_id).copied());
+		let vertical_origin_y = vertical_metrics.and_then(|vertical_metrics| vertical_metrics.vertical_origins.as_ref()).map(|vertical_origins| vertical_origins.vertical_origin_y(glyph_id as u16));
+		match (metric, vertical_origin_y) {
+			(Some(metric), None) => metric,
+			(Some(metric), Some(vertical_origin_y)) => VerticalMetric { advance_height: metric.advance_height, top_side_bearing: vertical_origin_y.saturating_sub(y_max) },
+			(None, vertical_origin_y) => VerticalMetric {
+				advance_height: (self.typographic_ascender + self.typographic_descender).value.max(0) as u16,
+				top_side_bearing: vertical_origin_y.unwrap_or(self.typographic_ascender.value).saturating_sub(y_max),
+			},
+		}
 	}
 
-	pub fn get_glyph(&self, index: usize) -> Arc<Glyph> {
+	pub fn get_glyph(&self, index: usize) -> Result<Arc<Glyph>, GlyphLookupError> {
+		// Glyphs still in glyf are read without holding the lock on the rest, so a face being loaded from the same data isn't held up.
+		let unread = match self.glyphs.lock().unwrap().get(index) {
+			None => return Err(GlyphLookupError::GlyphNotFound(index, self.number_of_glyphs)),
+			Some(LazyGlyph::GlyphComplete(glyph)) => return Ok(glyph.clone()),
+			Some(LazyGlyph::GlyphUnread(glyph_locations, horizontal_metric)) => Some((glyph_locations.clone(), **horizontal_metric)),
+			Some(LazyGlyph::GlyphIncomplete(..)) => None,
+		};
+		let mut read = unread.map(|(glyph_locations, horizontal_metric)| self.read_glyph(&glyph_locations, index, horizontal_metric));
+
+		// The lock is held until the glyph is made, so nothing sees the empty glyph left in its place meanwhile.
 		let mut glyphs = self.glyphs.lock().unwrap();
-		match &(*glyphs)[index] {
-			LazyGlyph::GlyphComplete(glyph) => glyph.clone(),
-			LazyGlyph::GlyphIncomplete(glyph_intermediate, horizontal_metric) => {
-				let glyph_intermediate = glyph_intermediate.clone();
-				let horizontal_metric = horizontal_metric.clone();
-				(*glyphs)[index] = LazyGlyph::Processing;
-				let mut glyph: Glyph = Arc::into_inner(glyph_intermediate).expect("this should only have one reference").into();
-				glyph.set_horizontal_metrics(Arc::into_inner(horizontal_metric).expect("this should only have one reference"));
-				let glyph = Arc::new(glyph);
+		let (glyph_intermediate, horizontal_metric) = match std::mem::replace(&mut (*glyphs)[index], LazyGlyph::GlyphComplete(Arc::new(Glyph::new_empty([0; 4].into())))) {
+			// Made by another call while the lock was let go.
+			LazyGlyph::GlyphComplete(glyph) => {
 				(*glyphs)[index] = LazyGlyph::GlyphComplete(glyph.clone());
-				glyph
+				return Ok(glyph);
+			},
+			LazyGlyph::GlyphIncomplete(glyph_intermediate, horizontal_metric) => (Arc::into_inner(glyph_intermediate).expect("this should only have one reference"), Arc::into_inner(horizontal_metric).expect("this should only have one reference")),
+			LazyGlyph::GlyphUnread(glyph_locations, horizontal_metric) => read.take().unwrap_or_else(|| self.read_glyph(&glyph_locations, index, *horizontal_metric)),
+		};
+		let mut glyph: Glyph = glyph_intermediate.into();
+		glyph.set_horizontal_metrics(horizontal_metric);
+		glyph.set_vertical_metrics(self.vertical_metric(index, glyph.bounds.y_max));
+		// Colour glyphs are drawn with their layers in place of their own outline, which is there for renderers without colour.
+		// Glyphs with a version 1 paint are drawn from that instead, by `colour_paint`, so keep their outline.
+		let layers = self.colour_glyphs.as_ref().filter(|colour_glyphs| colour_glyphs.base_paint(index as u16).is_none()).map_or(&[][..], |colour_glyphs| colour_glyphs.layers(index as u16));
+		if !layers.is_empty() {
+			glyph.data = GlyphData::Layered(LayeredGlyph { layers: layers.to_vec() });
+		} else if let Some(bitmap) = self.bitmap_glyph(index as u16).filter(|_| self.colour_glyphs.as_ref().is_none_or(|colour_glyphs| colour_glyphs.base_paint(index as u16).is_none())) {
+			// Glyphs without an outline of their own take their bounds from the largest image, so they're laid out and clipped like any other.
+			if matches!(glyph.data, GlyphData::None) {
+				glyph.bounds = bitmap.strikes.iter().max_by_key(|strike| strike.pixels_per_em).map_or(glyph.bounds, |strike| strike.bounds(self.units_per_em.value));
+			}
+			glyph.data = GlyphData::Bitmap(bitmap);
+		}
+		let glyph = Arc::new(glyph);
+		(*glyphs)[index] = LazyGlyph::GlyphComplete(glyph.clone());
+		Ok(glyph)
+	}
+
+	/// A glyph's outline from glyf, with any components placed by matching points put in place, or a failed parse if it can't be read.
+	/// Variable fonts have the outline and horizontal metrics varied to the font's coordinates.
+	fn read_glyph(&self, glyph_locations: &GlyphLocations, index: usize, horizontal_metric: HorizontalMetric) -> (GlyphIntermediate, HorizontalMetric) {
+		if let Some(varied) = self.variations.as_ref().and_then(|variations| variations.vary_glyph(index, horizontal_metric, &self.variation_coordinates)) {
+			return varied;
+		}
+		let glyph_intermediate = match glyph_locations.read_glyph(index as u16) {
+			Some(Ok(glyph)) => match_component_points(&|glyph_id| glyph_locations.read_glyph(glyph_id)?.ok().map(Cow::Owned), glyph),
+			Some(Err(_)) | None => unreadable_glyph(),
+		};
+		(glyph_intermediate, horizontal_metric)
+	}
+
+	/// A glyph's outline grid-fitted at a size, by its TrueType instructions or by the autohinter for fonts without any. None if
+	/// the font can't be hinted, gasp doesn't ask for grid-fitting at that size, or hinting fails, in which case the glyph should
+	/// be drawn from its usual outline.
+	pub fn hinted_glyph(&self, glyph_id: u16, pixels_per_em: f32) -> Option<Arc<Glyph>> {
+		if glyph_id as usize >= self.number_of_glyphs {
+			return None;
+		}
+		if let Some(hinting) = &self.hinting {
+			if !hinting.grid_fits(pixels_per_em) {
+				return None;
+			}
+			return hinting.cached_glyph(glyph_id, pixels_per_em, || {
+				let unhinted = self.get_glyph(glyph_id as usize).ok()?;
+				if !matches!(unhinted.data, GlyphData::SimpleGlyph(_) | GlyphData::CompositeGlyph(_)) {
+					return None;
+				}
+				let outline = hinting.hint_glyph(glyph_id, pixels_per_em).ok().flatten()?;
+				if outline.contour_ends.is_empty() {
+					return None;
+				}
+
+				// The hinted points are turned back into font units, so the glyph is drawn like any other.
+				let font_units_per_pixel = self.units_per_em.value as f64 / (64.0 * pixels_per_em as f64);
+				let to_font_units = |value: i32| (value as f64 * font_units_per_pixel).round().clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+				let outline = SimpleGlyphRaw {
+					end_points_of_contours: outline.contour_ends.iter().map(|end| *end as u16).collect(),
+					instruction_length: 0,
+					instructions: Vec::new(),
+					flags: outline.on_curve.iter().map(|on_curve| *on_curve as u8).collect(),
+					x_coordinates: outline.points.iter().map(|(x, _)| to_font_units(*x)).collect(),
+					y_coordinates: outline.points.iter().map(|(_, y)| to_font_units(*y)).collect(),
+				};
+				Self::hinted_outline_glyph(outline, &unhinted)
+			});
+		}
+		let auto_hinter = self.auto_hinter.as_ref()?;
+		auto_hinter.cached_glyph(glyph_id, pixels_per_em, || {
+			let unhinted = self.get_glyph(glyph_id as usize).ok()?;
+			if !matches!(unhinted.data, GlyphData::SimpleGlyph(_) | GlyphData::CompositeGlyph(_)) {
+				return None;
 			}
-			LazyGlyph::Processing => todo!("Should do some sort of lock or something")
+			Self::hinted_outline_glyph(auto_hinter.hint_glyph(glyph_id, pixels_per_em)?, &unhinted)
+		})
+	}
+
+	/// A grid-fitted outline in font units made into a glyph with the unhinted glyph's metrics, or None if it can't be triangulated.
+	fn hinted_outline_glyph(outline: SimpleGlyphRaw, unhinted: &Glyph) -> Option<Arc<Glyph>> {
+		let raw = GlyphRaw {
+			number_of_contours: outline.end_points_of_contours.len() as i16,
+			x_min: outline.x_coordinates.iter().copied().min().unwrap_or(0),
+			y_min: outline.y_coordinates.iter().copied().min().unwrap_or(0),
+			x_max: outline.x_coordinates.iter().copied().max().unwrap_or(0),
+			y_max: outline.y_coordinates.iter().copied().max().unwrap_or(0),
+			glyph_data: GlyphDataRaw::SimpleGlyphRaw(outline),
+		};
+		let mut glyph: Glyph = GlyphIntermediate::from(raw).into();
+		if matches!(glyph.data, GlyphData::FailedParse(_)) {
+			return None;
+		}
+		glyph.left_side_bearing = unhinted.left_side_bearing;
+		glyph.advance_width = unhinted.advance_width;
+		glyph.top_side_bearing = unhinted.top_side_bearing;
+		glyph.advance_height = unhinted.advance_height;
+		Some(Arc::new(glyph))
+	}
+
+	/// A glyph's images from the font's bitmap tables, in every strike that has a PNG of it which decodes.
+	/// JPEG and TIFF graphics in sbix aren't decoded, so are left out.
+	fn bitmap_glyph(&self, glyph_id: u16) -> Option<BitmapGlyph> {
+		let strikes: Vec<Bitmap> = self.bitmap_glyphs.as_ref()?.images(glyph_id).into_iter()
+			.filter(|image| image.graphic_type == PNG_GRAPHIC_TYPE && image.pixels_per_em > 0)
+			.filter_map(|image| Some(Bitmap { pixels_per_em: image.pixels_per_em, left: image.left, bottom: image.bottom, image: png::decode(image.data).ok()? }))
+			.collect();
+		match strikes.is_empty() {
+			true => None,
+			false => Some(BitmapGlyph { strikes }),
+		}
+	}
+
+	/// How many colour palettes the font has, any of which can be picked with `TextBox::palette`.
+	pub fn palette_count(&self) -> usize {
+		self.colour_palettes.as_ref().map_or(0, |colour_palettes| colour_palettes.palettes.len())
+	}
+
+	/// The first palette the font marks as suited to a dark or light background, otherwise the first palette.
+	pub fn palette_for_background(&self, dark_background: bool) -> usize {
+		self.colour_palettes.as_ref().and_then(|colour_palettes| colour_palettes.palette_for_background(dark_background)).unwrap_or(0)
+	}
+
+	/// The name the font gives a palette, such as "Dark", if it names them.
+	pub fn palette_name(&self, palette_index: usize, language: &str) -> Option<&str> {
+		let name_id = (*self.colour_palettes.as_ref()?.palette_labels.get(palette_index)?)?;
+		self.name(name_id, language)
+	}
+
+	/// The colour of a palette entry, with the foreground entry, and any the palette doesn't have, in the text colour.
+	/// Palettes past the last use the first. Vertices only carry RGB, so the palette's alpha is dropped.
+	pub fn palette_colour(&self, palette_index: usize, palette_entry_index: u16, foreground: Colour) -> Colour {
+		let Some(colour_palettes) = &self.colour_palettes else {
+			return foreground;
+		};
+		let palette_index = if palette_index < colour_palettes.palettes.len() { palette_index } else { 0 };
+		match colour_palettes.colour(palette_index, palette_entry_index) {
+			Some(colour) => Colour { r: colour.red, g: colour.green, b: colour.blue },
+			None => foreground,
+		}
+	}
+
+	/// The glyphs drawn for a glyph, bottom first, with their colours:
+	/// a colour glyph's layers in the palette's colours, or the glyph itself in the text colour.
+	pub fn glyph_layers(&self, glyph: &Arc<Glyph>, palette_index: usize, foreground: Colour) -> Vec<(Arc<Glyph>, Colour)> {
+		match &glyph.data {
+			GlyphData::Layered(data) => self.colour_layers(data, palette_index, foreground),
+			_ => vec![(glyph.clone(), foreground)],
 		}
 	}
 
+	/// A glyph's COLR version 1 paint graph, evaluated at the font's variation coordinates, or None if it doesn't have one.
+	/// Colours come from the palette, as with `palette_colour`, with the foreground entry in the text colour.
+	pub fn colour_paint(&self, glyph_id: u16, palette_index: usize, foreground: Colour) -> Option<ColourPaint> {
+		let colour_glyphs = self.colour_glyphs.as_ref()?;
+		let palette = self.colour_palettes.as_ref()
+			.and_then(|colour_palettes| colour_palettes.palettes.get(palette_index).or(colour_palettes.palettes.first()))
+			.map_or(&[][..], |palette| palette.as_slice());
+		let foreground = PaletteColour { red: foreground.r, green: foreground.g, blue: foreground.b, alpha: 255 };
+		Some(ColourPaint {
+			commands: colour_glyphs.paint(glyph_id, palette, foreground, &self.variation_coordinates)?,
+			clip_box: colour_glyphs.clip_box(glyph_id, &self.variation_coordinates),
+		})
+	}
+
+	/// Layers should be plain outlines, so any that are colour glyphs themselves are left out rather than followed round in circles.
+	fn colour_layers(&self, data: &LayeredGlyph, palette_index: usize, foreground: Colour) -> Vec<(Arc<Glyph>, Colour)> {
+		data.layers.iter()
+			.filter_map(|layer| Some((self.get_glyph(layer.glyph_id as usize).ok()?, self.palette_colour(palette_index, layer.palette_entry_index, foreground))))
+			.filter(|(layer_glyph, _)| !matches!(layer_glyph.data, GlyphData::Layered(_)))
+			.collect()
+	}
+
+	/// This font at another instance, given user space values such as `("wght", 650.0)`. Axes that aren't given use their default value.
+	/// Glyf glyphs aren't read until the instance is asked for them, so this costs little however many glyphs the font has.
+	pub fn with_variation(&self, user_coordinates: &[(&str, f32)]) -> Font {
+		let (glyphs, variation_coordinates) = match &self.variations {
+			Some(variations) => {
+				let coordinates = variations.normalised_coordinates(user_coordinates);
+				(Arc::new(Mutex::new(variations.glyphs(&coordinates))), coordinates)
+			},
+			None => (self.glyphs.clone(), self.variation_coordinates.clone()),
+		};
+		// Font-wide metrics follow MVAR, keeping their current values in fonts without variations.
+		let metric = |value_tag: &[u8; 4]| self.variations.as_ref().and_then(|variations| variations.metric(*value_tag, &variation_coordinates));
+		let line_spacing = match (metric(b"hasc"), metric(b"hdsc"), metric(b"hlgp")) {
+			(Some(ascender), Some(descender), Some(line_gap)) => (ascender - descender + line_gap).into(),
+			_ => self.line_spacing,
+		};
+		let vertical_metrics = match (&self.vertical_metrics, metric(b"vasc"), metric(b"vdsc"), metric(b"vlgp")) {
+			(Some(vertical_metrics), Some(ascender), Some(descender), Some(line_gap)) => Some(Arc::new(VerticalMetrics {
+				ascender: ascender.into(),
+				descender: descender.into(),
+				line_gap: line_gap.into(),
+				metrics: vertical_metrics.metrics.clone(),
+				vertical_origins: vertical_metrics.vertical_origins.clone(),
+			})),
+			(vertical_metrics, ..) => vertical_metrics.clone(),
+		};
+
+		Font {
+			glyphs,
+			mappings: self.mappings.clone(),
+			selected_mapping: self.selected_mapping,
+			names: self.names.clone(),
+			units_per_em: self.units_per_em,
+			typographic_descender: metric(b"hcld").map_or(self.typographic_descender, FontUnits::from),
+			typographic_ascender: metric(b"hcla").map_or(self.typographic_ascender, FontUnits::from),
+			line_spacing,
+			italic_angle: self.italic_angle,
+			underline_position: metric(b"undo").map_or(self.underline_position, FontUnits::from),
+			underline_thickness: metric(b"unds").map_or(self.underline_thickness, FontUnits::from),
+			is_fixed_pitch: self.is_fixed_pitch,
+			glyph_names: self.glyph_names.clone(),
+			kerning: self.kerning.clone(),
+			glyph_positioning: self.glyph_positioning.clone(),
+			glyph_substitution: self.glyph_substitution.clone(),
+			glyph_definition: self.glyph_definition.clone(),
+			vertical_metrics,
+			colour_glyphs: self.colour_glyphs.clone(),
+			colour_palettes: self.colour_palettes.clone(),
+			bitmap_glyphs: self.bitmap_glyphs.clone(),
+			// The instructions were written for the default outlines, which other instances don't have.
+			hinting: self.hinting.clone().filter(|_| variation_coordinates.iter().all(|coordinate| *coordinate == 0.0)),
+			auto_hinter: self.auto_hinter.clone().filter(|_| variation_coordinates.iter().all(|coordinate| *coordinate == 0.0)),
+			number_of_glyphs: self.number_of_glyphs,
+			variations: self.variations.clone(),
+			variation_coordinates,
+		}
+	}
+
+	/// The names of the font's named instances, such as "Bold" or "Condensed Light", in the given language.
+	pub fn instance_names(&self, language: &str) -> Vec<&str> {
+		match &self.variations {
+			Some(variations) => variations.instances.iter().filter_map(|instance| self.name(instance.subfamily_name_id, language)).collect(),
+			None => Vec::new(),
+		}
+	}
+
+	/// This font at one of its named instances, found by its name in any language. Returns None if the font doesn't have one by that name.
+	pub fn with_named_instance(&self, instance_name: &str) -> Option<Font> {
+		let variations = self.variations.as_ref()?;
+		let instance = variations.instances.iter().find(|instance| self.names.iter().any(|name| name.name_id == instance.subfamily_name_id && name.value == instance_name))?;
+		let user_coordinates: Vec<(&str, f32)> = variations.axes.iter().zip(&instance.coordinates).filter_map(|(axis, &value)| Some((std::str::from_utf8(&axis.axis_tag).ok()?, value))).collect();
+		Some(self.with_variation(&user_coordinates))
+	}
+
 	/*pub fn number_of_failed_parse_of_type(&self, type_of_parse_error: GlyphParseError) -> usize {
 		let mut count = 0;
 		for glyph in &self.glyphs {
@@ -70,10 +541,261 @@ impl Font {
 	}*/
 }
 
+/// The GSUB and GPOS features applied to horizontal text unless asked for otherwise.
+pub fn default_features() -> Vec<[u8; 4]> {
+	DEFAULT_SUBSTITUTION_FEATURES.iter().chain(DEFAULT_POSITIONING_FEATURES.iter()).copied().collect()
+}
+
+/// The metrics for laying glyphs out in columns, from the vhea, vmtx and VORG tables.
+pub struct VerticalMetrics {
+	pub ascender: FontUnits<i16>, // From the centre line of a column to its right edge
+	pub descender: FontUnits<i16>, // From the centre line of a column to its left edge, usually negative
+	pub line_gap: FontUnits<i16>,
+	pub metrics: Vec<VerticalMetric>, // One per glyph, empty for fonts without a vmtx table
+	pub vertical_origins: Option<VerticalOriginTable>,
+}
+
+/// A glyph laid out in a line of text, in font units from the pen position.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PositionedGlyph {
+	pub glyph_id: u16,
+	pub advance: FontUnits<i32>, // How far the pen moves after the glyph, including any kerning
+	pub offset: Position<FontUnits<i32>>, // Where the glyph is drawn relative to the pen, such as a mark moved onto its base
+}
+
+/// The horizontal kerning pairs of a kern table, summing the adjustments from each subtable unless a later one overrides them.
+pub(crate) fn kerning_pairs(kerning_table: &KerningTable) -> HashMap<(u16, u16), i16> {
+	let mut kerning_pairs: HashMap<(u16, u16), i16> = HashMap::new();
+	for subtable in kerning_table.subtables.iter().filter(|subtable| subtable.is_horizontal_kerning()) {
+		for pair in subtable.pairs.iter() {
+			let value = kerning_pairs.entry((pair.left, pair.right)).or_insert(0);
+			*value = if subtable.override_accumulated { pair.value } else { value.saturating_add(pair.value) };
+		}
+	}
+	kerning_pairs
+}
+
+/// A string from the naming table, decoded from whichever encoding it was stored in.
+#[derive(Debug, Clone)]
+pub struct Name {
+	pub name_id: u16,
+	pub language: Option<String>, // BCP 47 tag, None for Unicode platform names which don't give one
+	pub value: String,
+}
+
+impl From<NamingTable> for Vec<Name> {
+	fn from(naming_table: NamingTable) -> Self {
+		naming_table.name_records.iter().filter_map(|name_record| {
+			Some(Name {
+				name_id: name_record.name_id,
+				language: naming_table.language_tag(name_record),
+				value: name_record.decode()?,
+			})
+		}).collect()
+	}
+}
+
+/// A TrueType or OpenType collection (`.ttc` / `.otc`). Faces loaded from the same collection share any tables they have in common.
+pub struct FontCollection<Source: Read + Seek> {
+	pub ttf_reader: Arc<Mutex<TrueTypeFontReader<Source>>>,
+	pub glyph_source: Option<Arc<Mutex<dyn ttf_reader::GlyphSource>>>, // The same font data, if the faces loaded from it can keep it to read their glyphs from
+	pub header: CollectionHeader,
+	pub faces: Vec<TableDirectory>,
+	pub shared_tables: SharedTables,
+}
+
+/// Tables already loaded by an earlier face, keyed by their offsets in the font data.
+#[derive(Default)]
+pub struct SharedTables {
+	pub glyphs: HashMap<SharedGlyphsKey, Arc<Mutex<Vec<LazyGlyph>>>>,
+	pub mappings: HashMap<u32, Arc<Vec<Mapping>>>, // cmap offset
+}
+
+/// Everything a face's glyphs are made from, by table offsets where it comes from a table of its own.
+/// Faces that agree on all of it can share their glyphs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SharedGlyphsKey {
+	pub outlines: u32, // glyf, CFF or CFF2
+	pub horizontal_metrics: u32, // hmtx
+	pub vertical_metrics: Option<u32>, // vmtx
+	pub vertical_origins: Option<u32>, // VORG
+	pub colour_glyphs: Option<u32>, // COLR
+	pub bitmap_glyphs: [Option<u32>; 3], // CBLC, CBDT and sbix
+	pub em_box: (u16, i16, i16), // Units per em, ascender and descender, which size bitmap glyphs and glyphs without vmtx
+}
+
+/// The outlines of a variable font, kept so its glyphs can be rebuilt at another point in its design space.
+pub struct FontVariations {
+	pub axes: Vec<VariationAxisRecord>,
+	pub instances: Vec<InstanceRecord>,
+	pub axis_variations: Option<AxisVariationsTable>,
+	pub outlines: VariableOutlines,
+	pub horizontal_metrics: Vec<HorizontalMetric>,
+	pub horizontal_metrics_variations: Option<HorizontalMetricsVariationsTable>,
+	pub metrics_variations: Option<MetricsVariationsTable>,
+	pub default_metrics: HashMap<[u8; 4], i16>, // Font-wide metrics at the default instance, by their MVAR tags such as `*b"hasc"`
+}
+
+pub enum VariableOutlines {
+	CompactFontFormat2(CompactFontFormat2Table),
+	TrueType(RawGlyphs, GlyphVariationsTable), // glyf outlines at the default instance, and the deltas that move their points
+}
+
+impl FontVariations {
+	/// Normalised coordinates for each axis, using the default value for any axis not given.
+	pub fn normalised_coordinates(&self, user_coordinates: &[(&str, f32)]) -> Vec<f32> {
+		self.axes.iter().enumerate().map(|(axis_index, axis)| {
+			let coordinate = match user_coordinates.iter().find(|(tag, _)| tag.as_bytes() == axis.axis_tag) {
+				Some((_, value)) => axis.normalise(*value),
+				None => 0.0,
+			};
+			match &self.axis_variations {
+				Some(axis_variations) => axis_variations.map_coordinate(axis_index, coordinate),
+				None => coordinate,
+			}
+		}).collect()
+	}
+
+	/// A font-wide metric, by its MVAR tag such as `*b"hasc"`, at the given normalised coordinates.
+	/// None for metrics the font doesn't have, such as the vertical ones in fonts without vhea.
+	pub fn metric(&self, value_tag: [u8; 4], coordinates: &[f32]) -> Option<i16> {
+		let default_value = *self.default_metrics.get(&value_tag)?;
+		let delta = self.metrics_variations.as_ref().map_or(0.0, |metrics_variations| metrics_variations.delta(value_tag, coordinates));
+		Some((default_value as f32 + delta).round() as i16)
+	}
+
+	/// Moves a glyph's horizontal metrics with HVAR when the font has it, which takes the place of the deltas for gvar's phantom points.
+	fn vary_horizontal_metric(&self, glyph_index: usize, horizontal_metric: HorizontalMetric, varied_horizontal_metric: HorizontalMetric, coordinates: &[f32]) -> HorizontalMetric {
+		let Some(horizontal_metrics_variations) = &self.horizontal_metrics_variations else {
+			return varied_horizontal_metric;
+		};
+		HorizontalMetric {
+			advance_width: (horizontal_metric.advance_width as f32 + horizontal_metrics_variations.advance_width_delta(glyph_index, coordinates)).round().max(0.0) as u16,
+			left_side_bearing: match horizontal_metrics_variations.left_side_bearing_delta(glyph_index, coordinates) {
+				Some(delta) => (horizontal_metric.left_side_bearing as f32 + delta).round() as i16,
+				None => varied_horizontal_metric.left_side_bearing,
+			},
+		}
+	}
+
+	/// The glyphs of an instance. CFF2 outlines are all varied here, while glyf outlines are left in glyf to be read and
+	/// varied by `vary_glyph` the first time each is asked for.
+	pub fn glyphs(&self, coordinates: &[f32]) -> Vec<LazyGlyph> {
+		match &self.outlines {
+			VariableOutlines::CompactFontFormat2(compact_font_format_2_table) => {
+				compact_font_format_2_table.char_strings.iter().enumerate().zip(self.horizontal_metrics.iter()).map(|((glyph_index, _), horizontal_metric)| {
+					let intermediate: GlyphIntermediate = compact_font_format_2_table.outline(glyph_index, coordinates).into();
+					let horizontal_metric = self.vary_horizontal_metric(glyph_index, *horizontal_metric, *horizontal_metric, coordinates);
+					LazyGlyph::GlyphIncomplete(Arc::new(intermediate), Arc::new(horizontal_metric))
+				}).collect()
+			},
+			VariableOutlines::TrueType(RawGlyphs::Unread(glyph_locations), _) => {
+				self.horizontal_metrics.iter().take(glyph_locations.len()).map(|horizontal_metric| LazyGlyph::GlyphUnread(glyph_locations.clone(), Arc::new(*horizontal_metric))).collect()
+			},
+			VariableOutlines::TrueType(RawGlyphs::Read(glyphs), _) => {
+				self.horizontal_metrics.iter().take(glyphs.len()).enumerate().map(|(glyph_index, horizontal_metric)| {
+					let (intermediate, horizontal_metric) = self.vary_glyph(glyph_index, *horizontal_metric, coordinates).unwrap_or_else(|| (unreadable_glyph(), *horizontal_metric));
+					LazyGlyph::GlyphIncomplete(Arc::new(intermediate), Arc::new(horizontal_metric))
+				}).collect()
+			},
+		}
+	}
+
+	/// A glyf outline moved by gvar, with its horizontal metrics moved by HVAR or gvar's phantom points. Components placed by
+	/// matching points are lined up on the varied outlines of the glyphs they're matched with. None for CFF2 outlines.
+	pub fn vary_glyph(&self, glyph_index: usize, horizontal_metric: HorizontalMetric, coordinates: &[f32]) -> Option<(GlyphIntermediate, HorizontalMetric)> {
+		let VariableOutlines::TrueType(glyphs, glyph_variations_table) = &self.outlines else {
+			return None;
+		};
+		let varied = |glyph_id: u16| {
+			let glyph = glyphs.glyph(glyph_id)?;
+			let horizontal_metric = *self.horizontal_metrics.get(glyph_id as usize)?;
+			Some(glyph_variations_table.vary_glyph(glyph_id as usize, &glyph, horizontal_metric, coordinates))
+		};
+		Some(match varied(glyph_index as u16) {
+			Some((glyph, varied_horizontal_metric)) => (
+				match_component_points(&|glyph_id| varied(glyph_id).map(|(glyph, _)| Cow::Owned(glyph)), glyph),
+				self.vary_horizontal_metric(glyph_index, horizontal_metric, varied_horizontal_metric, coordinates),
+			),
+			None => (unreadable_glyph(), horizontal_metric),
+		})
+	}
+}
+
+/// Whether a character is one of the variation selectors, U+FE00 to U+FE0F and U+E0100 to U+E01EF.
+fn is_variation_selector(character: char) -> bool {
+	matches!(character, '\u{FE00}'..='\u{FE0F}' | '\u{E0100}'..='\u{E01EF}')
+}
+
+/// Stands in for a glyph whose outline couldn't be read from glyf, which is drawn as empty.
+fn unreadable_glyph() -> GlyphIntermediate {
+	GlyphIntermediate { number_of_contours: None, bounds: [0; 4].into(), glyph_data: GlyphDataIntermediate::FailedParse(GlyphParseError::UnreadableOutline) }
+}
+
 pub enum LazyGlyph {
 	GlyphComplete(Arc<Glyph>),
 	GlyphIncomplete(Arc<GlyphIntermediate>, Arc<HorizontalMetric>),
-	Processing,
+	GlyphUnread(Arc<GlyphLocations>, Arc<HorizontalMetric>), // Still in glyf, read the first time it's asked for
+}
+
+/// Where each glyph's outline is in glyf, from loca, and the font data to read it from.
+pub struct GlyphLocations {
+	source: Arc<Mutex<dyn ttf_reader::GlyphSource>>,
+	glyph_offsets: Vec<GlyphOffset>,
+	glyph_table_start: u64,
+}
+
+impl GlyphLocations {
+	pub fn new(source: Arc<Mutex<dyn ttf_reader::GlyphSource>>, glyph_offsets: Vec<GlyphOffset>, glyph_table_start: u64) -> Self {
+		Self { source, glyph_offsets, glyph_table_start }
+	}
+
+	pub fn len(&self) -> usize {
+		self.glyph_offsets.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.glyph_offsets.is_empty()
+	}
+
+	/// A glyph's outline as it's stored, or None if the glyph id is out of range.
+	pub fn read_glyph(&self, glyph_id: u16) -> Option<Result<GlyphRaw, TrueTypeFontReaderError>> {
+		let glyph_offset = *self.glyph_offsets.get(glyph_id as usize)?;
+		Some(self.source.lock().unwrap().read_glyph(glyph_offset, self.glyph_table_start))
+	}
+}
+
+/// glyf outlines as they're stored, either all read already or read from the font data as each one is needed.
+pub enum RawGlyphs {
+	Read(Vec<GlyphRaw>),
+	Unread(Arc<GlyphLocations>),
+}
+
+impl RawGlyphs {
+	pub fn len(&self) -> usize {
+		match self {
+			RawGlyphs::Read(glyphs) => glyphs.len(),
+			RawGlyphs::Unread(glyph_locations) => glyph_locations.len(),
+		}
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+
+	/// A glyph's outline, or None if the glyph id is out of range or it can't be read.
+	pub fn glyph(&self, glyph_id: u16) -> Option<Cow<'_, GlyphRaw>> {
+		match self {
+			RawGlyphs::Read(glyphs) => glyphs.get(glyph_id as usize).map(Cow::Borrowed),
+			RawGlyphs::Unread(glyph_locations) => glyph_locations.read_glyph(glyph_id)?.ok().map(Cow::Owned),
+		}
+	}
+}
+
+impl From<Vec<GlyphRaw>> for RawGlyphs {
+	fn from(value: Vec<GlyphRaw>) -> Self {
+		RawGlyphs::Read(value)
+	}
 }
 
 pub struct GlyphIndex(pub u16);
@@ -83,6 +805,41 @@ pub struct Glyph {
 	pub data: GlyphData,
 	pub left_side_bearing: FontUnits<i16>, // In font units
 	pub advance_width: FontUnits<u16>, // In font units
+	pub top_side_bearing: FontUnits<i16>, // From the top of the em box to the top of the outline, in vertical text
+	pub advance_height: FontUnits<u16>, // In vertical text
+}
+
+#[derive(Debug)]
+pub enum FontLoadError {
+	MissingTable(TableTag),
+	UnsupportedSfntVersion(u32),
+	TruncatedTable(TableTag),
+	OffsetOutOfRange(TableTag, u64), // table, offset that points outside of the font data or table
+	FaceIndexOutOfRange(usize, usize), // requested face, number of faces in the collection
+	DecompressionFailed(TableTag, InflateError), // table of a WOFF file that couldn't be inflated
+	BrotliDecompressionFailed(BrotliError), // tables of a WOFF2 file, which are compressed together
+	ReaderError(TrueTypeFontReaderError),
+}
+
+impl FontLoadError {
+	/// Attributes an error from reading a table to that table, so running off the end of the data becomes a truncated table.
+	pub fn from_table_error(error: TrueTypeFontReaderError, table_tag: TableTag) -> Self {
+		match error {
+			TrueTypeFontReaderError::IOError(io_error) if io_error.kind() == io::ErrorKind::UnexpectedEof => FontLoadError::TruncatedTable(table_tag),
+			error => FontLoadError::ReaderError(error),
+		}
+	}
+}
+
+impl From<TrueTypeFontReaderError> for FontLoadError {
+	fn from(value: TrueTypeFontReaderError) -> Self {
+		FontLoadError::ReaderError(value)
+	}
+}
+
+#[derive(Debug, PartialEq)]
+pub enum GlyphLookupError {
+	GlyphNotFound(usize, usize), // requested glyph id, number of glyphs in the font
 }
 
 #[derive(Debug, PartialEq)]
@@ -90,11 +847,16 @@ pub enum GlyphParseError {
 	StuckInTriangulisationLoop,
 	HoleDoesNotHaveParent,
 	NoValidChannel,
+	InvalidCharString(CharStringError),
+	UnreadableOutline, // The glyph's outline couldn't be read from glyf when it was first asked for
+	UnmatchedComponentPoint, // A composite's component is placed by matching a point that isn't there
 }
 
 pub enum GlyphData {
 	SimpleGlyph(SimpleGlyph),
 	CompositeGlyph(CompositeGlyph),
+	Layered(LayeredGlyph), // A colour glyph, from COLR
+	Bitmap(BitmapGlyph), // A colour glyph, from CBDT or sbix, drawn in place of any outline
 	FailedParse(GlyphParseError),
 	None,
 }
@@ -104,36 +866,86 @@ pub struct SimpleGlyph {
 	indices: Vec<u32>,
 	convex_bezier_indices: Vec<u32>,
 	concave_bezier_indices: Vec<u32>,
+	cubic_bezier_indices: Vec<u32>,
 }
 
 pub struct CompositeGlyph {
 	children: Vec<ComponentGlyph>,
 }
 
+pub struct LayeredGlyph {
+	pub layers: Vec<LayerRecord>, // Bottom first
+}
+
+pub struct BitmapGlyph {
+	pub strikes: Vec<Bitmap>, // One per size the font has an image of the glyph at
+}
+
+/// A glyph's image at one size, placed in its pixels from the glyph origin to its bottom left corner.
+pub struct Bitmap {
+	pub pixels_per_em: u16,
+	pub left: i16,
+	pub bottom: i16,
+	pub image: png::Image,
+}
+
+impl BitmapGlyph {
+	/// The index of the strike with the size closest to the one the glyph is drawn at, and that strike.
+	/// Of two equally close, the larger is picked, as scaling down looks better than scaling up.
+	pub fn nearest_strike(&self, pixels_per_em: f32) -> Option<(usize, &Bitmap)> {
+		self.strikes.iter().enumerate().min_by(|(_, a), (_, b)| {
+			let distance = |strike: &Bitmap| (strike.pixels_per_em as f32 - pixels_per_em).abs();
+			distance(a).total_cmp(&distance(b)).then(b.pixels_per_em.cmp(&a.pixels_per_em))
+		})
+	}
+}
+
+impl Bitmap {
+	/// The edges of the image in font units.
+	pub fn bounds(&self, units_per_em: u16) -> Bounds {
+		let scale = units_per_em as f32 / self.pixels_per_em as f32;
+		let to_font_units = |pixels: i32| (pixels as f32 * scale).round().clamp(i16::MIN as f32, i16::MAX as f32) as i16;
+		Bounds {
+			x_min: to_font_units(self.left as i32),
+			x_max: to_font_units(self.left as i32 + self.image.width as i32),
+			y_min: to_font_units(self.bottom as i32),
+			y_max: to_font_units(self.bottom as i32 + self.image.height as i32),
+		}
+	}
+}
+
+/// What to draw for a COLR version 1 glyph, bottom first, in font units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColourPaint {
+	pub commands: Vec<PaintCommand>,
+	pub clip_box: Option<ClipBox>, // Nothing is drawn outside this, if the font gives one
+}
+
 pub struct ComponentGlyph {
 	pub child_index: usize,
 	pub offset: Position<FontUnits<i32>>,
+	pub transform: Affine, // Scales, flips or rotates the component before it's offset
 }
 
 impl Glyph {
-	pub fn new_simple(vertices: Vec<Vertex>, indices: Vec<u32>, convex_bezier_indices: Vec<u32>, concave_bezier_indices: Vec<u32>, bounds: Bounds) -> Self {
-		let data = GlyphData::SimpleGlyph(SimpleGlyph { vertices, indices, convex_bezier_indices, concave_bezier_indices, });
-		Self { bounds, data, left_side_bearing: 0.into(), advance_width: 0.into()}
+	pub fn new_simple(vertices: Vec<Vertex>, indices: Vec<u32>, convex_bezier_indices: Vec<u32>, concave_bezier_indices: Vec<u32>, cubic_bezier_indices: Vec<u32>, bounds: Bounds) -> Self {
+		let data = GlyphData::SimpleGlyph(SimpleGlyph { vertices, indices, convex_bezier_indices, concave_bezier_indices, cubic_bezier_indices, });
+		Self { bounds, data, left_side_bearing: 0.into(), advance_width: 0.into(), top_side_bearing: 0.into(), advance_height: 0.into() }
 	}
 
 	pub fn new_composite(children: Vec<ComponentGlyph>, bounds: Bounds) -> Self {
 		let data = GlyphData::CompositeGlyph(CompositeGlyph{ children, });
-		Self { bounds, data, left_side_bearing: 0.into(), advance_width: 0.into()}
+		Self { bounds, data, left_side_bearing: 0.into(), advance_width: 0.into(), top_side_bearing: 0.into(), advance_height: 0.into() }
 	}
 
 	pub fn new_empty(bounds: Bounds) -> Self {
 		let data = GlyphData::None;
-		Self { bounds, data, left_side_bearing: 0.into(), advance_width: 0.into()}
+		Self { bounds, data, left_side_bearing: 0.into(), advance_width: 0.into(), top_side_bearing: 0.into(), advance_height: 0.into() }
 	}
 
 	pub fn new_failed_parse(error: GlyphParseError, bounds: Bounds) -> Self {
 		let data = GlyphData::FailedParse(error);
-		Self { bounds, data, left_side_bearing: 0.into(), advance_width: 0.into()}
+		Self { bounds, data, left_side_bearing: 0.into(), advance_width: 0.into(), top_side_bearing: 0.into(), advance_height: 0.into() }
 	}
 
 	pub fn set_horizontal_metrics(&mut self, horizontal_metric: HorizontalMetric) {
@@ -141,47 +953,101 @@ impl Glyph {
 		self.advance_width = horizontal_metric.advance_width.into();
 	}
 
-	pub fn to_raw(&self, font: &Font, pixels_per_font_unit: f32, offset: Position<FontUnits<i32>>, screen_size: Size<Pixels<i32>>, position: Position<Pixels<f32>>, vertices_start: usize, colour: Colour, bounds: (Position<Pixels<i32>>, Position<Pixels<i32>>)) -> (Vec<font_renderer::VertexRaw>, Vec<u32>, Vec<u32>, Vec<u32>) {
+	pub fn set_vertical_metrics(&mut self, vertical_metric: VerticalMetric) {
+		self.top_side_bearing = vertical_metric.top_side_bearing.into();
+		self.advance_height = vertical_metric.advance_height.into();
+	}
+
+	/// The y coordinate of the top of the glyph's em box in vertical text, which the pen sits on.
+	pub fn vertical_origin_y(&self) -> FontUnits<i16> {
+		(self.top_side_bearing.value.saturating_add(self.bounds.y_max)).into()
+	}
+
+	pub fn to_raw(&self, font: &Font, target: &font_renderer::VertexTarget, offset: Position<FontUnits<i32>>, vertices_start: usize, colour: Colour) -> (Vec<font_renderer::VertexRaw>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>) {
+		let transform = Affine::translate(offset.x.value as f32, offset.y.value as f32);
+		self.to_raw_transformed(font, target, &transform, vertices_start, &font_renderer::VertexPaint::solid(colour))
+	}
+
+	/// As `to_raw`, with the outline moved by a transform in font units rather than an offset, and filled with the given paint.
+	/// Transforms that mirror the outline turn its triangles round, so they still face the front.
+	pub fn to_raw_transformed(&self, font: &Font, target: &font_renderer::VertexTarget, transform: &Affine, vertices_start: usize, paint: &font_renderer::VertexPaint) -> (Vec<font_renderer::VertexRaw>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>) {
+		let colour = paint.colour;
 		match &self.data {
 			GlyphData::SimpleGlyph(data) => {
-				let vertices_raw = data.vertices.iter().map(|v| v.to_raw(pixels_per_font_unit, offset, screen_size, position, colour, bounds)).collect();
-				let indices: Vec<u32> = data.indices.iter().map(|index| index + vertices_start as u32).collect();
-				let convex_bezier_indices: Vec<u32> = data.convex_bezier_indices.iter().map(|index| index + vertices_start as u32).collect();
-				let concave_bezier_indices: Vec<u32> = data.concave_bezier_indices.iter().map(|index| index + vertices_start as u32).collect();
+				let vertices_raw = data.vertices.iter().map(|v| v.to_raw(target, transform, paint)).collect();
+				let offset_indices = |indices: &Vec<u32>| -> Vec<u32> {
+					let mut indices: Vec<u32> = indices.iter().map(|index| index + vertices_start as u32).collect();
+					if transform.determinant() < 0.0 {
+						for triangle in indices.chunks_exact_mut(3) {
+							triangle.swap(1, 2);
+						}
+					}
+					indices
+				};
+				let indices: Vec<u32> = offset_indices(&data.indices);
+				let convex_bezier_indices: Vec<u32> = offset_indices(&data.convex_bezier_indices);
+				let concave_bezier_indices: Vec<u32> = offset_indices(&data.concave_bezier_indices);
+				let cubic_bezier_indices: Vec<u32> = offset_indices(&data.cubic_bezier_indices);
 
-				(vertices_raw, indices, convex_bezier_indices, concave_bezier_indices)
+				(vertices_raw, indices, convex_bezier_indices, concave_bezier_indices, cubic_bezier_indices)
 			},
 			GlyphData::CompositeGlyph(data) => {
 				let mut vertices_raw: Vec<font_renderer::VertexRaw> = Vec::new();
 				let mut indices: Vec<u32> = Vec::new();
 				let mut convex_bezier_indices: Vec<u32> = Vec::new();
 				let mut concave_bezier_indices: Vec<u32> = Vec::new();
+				let mut cubic_bezier_indices: Vec<u32> = Vec::new();
 				// println!("Composite Glyph with Child glyph ids:");
 				// for child in data.children.iter() {
 				// 	println!("	{}", child.child_index);
 				// }
 				for child in data.children.iter() {
+					let Ok(child_glyph) = font.get_glyph(child.child_index) else {
+						continue;
+					};
 					let updated_vertices_start = vertices_raw.len() + vertices_start;
-					let offset = offset + child.offset;
-					let (extra_vertices_raw, extra_indices, extra_convex_bezier_indices, extra_concave_bezier_indices) = font.get_glyph(child.child_index).to_raw(font, pixels_per_font_unit, offset, screen_size, position, updated_vertices_start, colour, bounds);
+					let child_transform = Affine::translate(child.offset.x.value as f32, child.offset.y.value as f32).multiply(&child.transform);
+					let child_paint = font_renderer::VertexPaint { transform: paint.transform.multiply(&child_transform), ..*paint };
+					let (extra_vertices_raw, extra_indices, extra_convex_bezier_indices, extra_concave_bezier_indices, extra_cubic_bezier_indices) = child_glyph.to_raw_transformed(font, target, &transform.multiply(&child_transform), updated_vertices_start, &child_paint);
 					vertices_raw.extend(extra_vertices_raw);
 					indices.extend(extra_indices);
 					convex_bezier_indices.extend(extra_convex_bezier_indices);
 					concave_bezier_indices.extend(extra_concave_bezier_indices);
+					cubic_bezier_indices.extend(extra_cubic_bezier_indices);
 				};
 
-				(vertices_raw, indices, convex_bezier_indices, concave_bezier_indices)
+				(vertices_raw, indices, convex_bezier_indices, concave_bezier_indices, cubic_bezier_indices)
 			},
-			GlyphData::FailedParse(error) => {
-				panic!("Parsing glyph failed with error: {error:?}");
-			}
+			GlyphData::Layered(data) => {
+				// Layers of one glyph come out together here, so where their curves overlap they might not stack in order. TextBox draws each layer in turn instead.
+				let mut vertices_raw: Vec<font_renderer::VertexRaw> = Vec::new();
+				let mut indices: Vec<u32> = Vec::new();
+				let mut convex_bezier_indices: Vec<u32> = Vec::new();
+				let mut concave_bezier_indices: Vec<u32> = Vec::new();
+				let mut cubic_bezier_indices: Vec<u32> = Vec::new();
+				for (layer_glyph, colour) in font.colour_layers(data, 0, colour) {
+					let updated_vertices_start = vertices_raw.len() + vertices_start;
+					let layer_paint = font_renderer::VertexPaint { colour, ..*paint };
+					let (extra_vertices_raw, extra_indices, extra_convex_bezier_indices, extra_concave_bezier_indices, extra_cubic_bezier_indices) = layer_glyph.to_raw_transformed(font, target, transform, updated_vertices_start, &layer_paint);
+					vertices_raw.extend(extra_vertices_raw);
+					indices.extend(extra_indices);
+					convex_bezier_indices.extend(extra_convex_bezier_indices);
+					concave_bezier_indices.extend(extra_concave_bezier_indices);
+					cubic_bezier_indices.extend(extra_cubic_bezier_indices);
+				}
+
+				(vertices_raw, indices, convex_bezier_indices, concave_bezier_indices, cubic_bezier_indices)
+			},
+			// Bitmaps aren't triangles, so TextBox draws them as textured quads instead.
+			// A glyph that failed to parse has no outline to draw, so one bad glyph leaves a gap rather than stopping the text.
+			GlyphData::Bitmap(_) | GlyphData::FailedParse(_) => (Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new()),
 			GlyphData::None => {
-				let vertices = vec![font_renderer::VertexRaw {position: [0.0, 0.0], uv_coords: [0.0, 0.0], colour: colour.into()},
-					font_renderer::VertexRaw {position: [0.0, 0.0], uv_coords: [0.0, 0.0], colour: colour.into()},
-					font_renderer::VertexRaw {position: [0.0, 0.0], uv_coords: [0.0, 0.0], colour: colour.into()}
+				let vertices = vec![font_renderer::VertexRaw {position: [0.0, 0.0], uv_coords: [0.0, 0.0, 0.0], colour: colour.into(), paint_coords: [0.0, 0.0], paint_index: 0},
+					font_renderer::VertexRaw {position: [0.0, 0.0], uv_coords: [0.0, 0.0, 0.0], colour: colour.into(), paint_coords: [0.0, 0.0], paint_index: 0},
+					font_renderer::VertexRaw {position: [0.0, 0.0], uv_coords: [0.0, 0.0, 0.0], colour: colour.into(), paint_coords: [0.0, 0.0], paint_index: 0}
 				];
 				let indices = vec![0, 1, 2];
-				(vertices, indices, Vec::new(), Vec::new())
+				(vertices, indices, Vec::new(), Vec::new(), Vec::new())
 			},
 		}
 	}
@@ -312,7 +1178,8 @@ pub struct Vertex {
 	pub x: FontUnits<i16>,
 	pub y: FontUnits<i16>,
 	pub on_curve: bool,
-	pub uv_coords: [f32; 2],
+	pub cubic: bool, // Off curve point of a cubic curve, rather than a quadratic one
+	pub uv_coords: [f32; 3], // u, v, 0 for quadratic curves and k, l, m for cubic curves
 }
 
 impl Vertex {
@@ -322,10 +1189,10 @@ impl Vertex {
 }
 
 impl Vertex {
-	fn to_raw(&self, pixels_per_font_unit: f32, offset: Position<FontUnits<i32>>, screen_size: Size<Pixels<i32>>, position: Position<Pixels<f32>>, colour: Colour, bounds: (Position<Pixels<i32>>, Position<Pixels<i32>>)) -> font_renderer::VertexRaw {
-		let x = offset.x + self.x;
-		let y = offset.y + self.y;
-		let mut x_pixels = x.to_pixels(pixels_per_font_unit) + position.x;
+	fn to_raw(&self, target: &font_renderer::VertexTarget, transform: &Affine, paint: &font_renderer::VertexPaint) -> font_renderer::VertexRaw {
+		let font_renderer::VertexTarget { pixels_per_font_unit, screen_size, position, bounds } = *target;
+		let (x, y) = transform.apply(self.x.value as f32, self.y.value as f32);
+		let mut x_pixels = Pixels::from(x * pixels_per_font_unit) + position.x;
 		if x_pixels < bounds.0.x.into() {
 			x_pixels = bounds.0.x.into();
 		}
@@ -333,7 +1200,7 @@ impl Vertex {
 			x_pixels = bounds.1.x.into();
 		}
 
-		let mut y_pixels = y.to_pixels(pixels_per_font_unit) + position.y;
+		let mut y_pixels = Pixels::from(y * pixels_per_font_unit) + position.y;
 
 		if y_pixels < bounds.0.y.into() {
 			y_pixels = bounds.0.y.into();
@@ -344,26 +1211,32 @@ impl Vertex {
 
 		let transformed_x = x_pixels.to_screen_space(screen_size.width);
 		let transformed_y = y_pixels.to_screen_space(screen_size.height);
-		font_renderer::VertexRaw{ position: [transformed_x.value, transformed_y.value], uv_coords: self.uv_coords, colour: colour.into() }
+		let (paint_x, paint_y) = paint.transform.apply(self.x.value as f32, self.y.value as f32);
+		font_renderer::VertexRaw{ position: [transformed_x.value, transformed_y.value], uv_coords: self.uv_coords, colour: paint.colour.into(), paint_coords: [paint_x, paint_y], paint_index: paint.index }
 	}
 }
 
 impl Vertex {
 	pub fn new(x: i16, y: i16) -> Self {
-		Self { x: x.into(), y: y.into(), on_curve: true, uv_coords: [0.0, 0.0]}
+		Self { x: x.into(), y: y.into(), on_curve: true, cubic: false, uv_coords: [0.0, 0.0, 0.0]}
 	}
 
-	pub fn with_changed_uv_coord(&self, uv_coords: [f32; 2]) -> Self {
+	pub fn with_changed_uv_coord(&self, uv_coords: [f32; 3]) -> Self {
 		let x = self.x;
 		let y = self.y;
 		let on_curve = self.on_curve;
-		Self { x, y, on_curve, uv_coords, }
+		let cubic = self.cubic;
+		Self { x, y, on_curve, cubic, uv_coords, }
+	}
+
+	pub fn position(&self) -> (f64, f64) {
+		(self.x.value as f64, self.y.value as f64)
 	}
 }
 
 impl From<(i16, i16)> for Vertex {
 	fn from(value: (i16, i16)) -> Self {
-		Vertex { x: value.0.into(), y: value.1.into(), on_curve: true, uv_coords: [0.0, 0.0] }
+		Vertex { x: value.0.into(), y: value.1.into(), on_curve: true, cubic: false, uv_coords: [0.0, 0.0, 0.0] }
 	}
 } 
 
@@ -374,45 +1247,184 @@ impl Display for Vertex {
 }
 
 pub trait ToTriangles {
-	fn to_triangles(self, debug_mode: bool) -> Result<(Vec<Vertex>, Vec<u32>, Vec<u32>, Vec<u32>), GlyphParseError>; //vertices, indices, convex_bezier_indices, concave_bezier_indices
+	fn to_triangles(self, debug_mode: bool) -> Result<(Vec<Vertex>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>), GlyphParseError>; //vertices, indices, convex_bezier_indices, concave_bezier_indices, cubic_bezier_indices
+}
+
+/// A cmap subtable along with the platform and encoding it was given for, which say how its character codes should be read.
+pub struct Mapping {
+	pub platform_id: u16,
+	pub encoding_id: u16,
+	pub format: MappingFormat,
+}
+
+impl Mapping {
+	/// Whether this subtable maps Unicode code points, rather than a legacy encoding such as Mac Roman or Shift JIS.
+	pub fn is_unicode(&self) -> bool {
+		matches!((self.platform_id, self.encoding_id), (0, 0..=4 | 6) | (3, 1 | 10))
+	}
+
+	fn get_glyph_id(&self, character_code: u64) -> Option<u16> {
+		self.format.get_glyph_id(character_code)
+	}
+
+	fn get_character_codes(&self, glyph_index: u16) -> Vec<char> {
+		self.format.get_character_codes(glyph_index)
+	}
+}
+
+/// The subtable that best maps Unicode, preferring full Unicode over the Basic Multilingual Plane and Windows over Unicode platform subtables.
+/// Fonts with none of those fall back to any other Unicode subtable, then to the first subtable that maps characters at all.
+pub(crate) fn preferred_mapping(mappings: &[Mapping]) -> Option<usize> {
+	const PREFERRED_ENCODINGS: [(u16, u16); 4] = [(3, 10), (0, 4), (3, 1), (0, 3)];
+	let maps_characters = |mapping: &Mapping| !matches!(mapping.format, MappingFormat::TrueTypeFormat14(_) | MappingFormat::InvalidFormat(_));
+
+	PREFERRED_ENCODINGS.iter().find_map(|&(platform_id, encoding_id)| {
+		mappings.iter().position(|mapping| mapping.platform_id == platform_id && mapping.encoding_id == encoding_id && maps_characters(mapping))
+	})
+		.or_else(|| mappings.iter().position(|mapping| mapping.is_unicode() && maps_characters(mapping)))
+		.or_else(|| mappings.iter().position(maps_characters))
 }
 
-pub enum Mapping {
+impl From<(&ttf_reader::EncodingRecord, ttf_reader::CharacterToGlyphIndexSubtable)> for Mapping {
+	fn from((encoding_record, subtable): (&ttf_reader::EncodingRecord, ttf_reader::CharacterToGlyphIndexSubtable)) -> Self {
+		Mapping {
+			platform_id: encoding_record.platform_id,
+			encoding_id: encoding_record.encoding_id,
+			format: subtable.into(),
+		}
+	}
+}
+
+pub enum MappingFormat {
+	TrueTypeFormat0(MappingTrueTypeFormat0),
+	TrueTypeFormat2(MappingTrueTypeFormat2),
 	TrueTypeFormat4(MappingTrueTypeFormat4),
+	TrueTypeFormat6(MappingTrueTypeFormat6),
+	TrueTypeFormat10(MappingTrueTypeFormat10),
 	TrueTypeFormat12(MappingTrueTypeFormat12),
+	TrueTypeFormat13(MappingTrueTypeFormat13),
+	TrueTypeFormat14(MappingTrueTypeFormat14),
 	InvalidFormat(usize),
 }
 
-impl Mapping {
+impl MappingFormat {
 	fn get_glyph_id(&self, character_code: u64) -> Option<u16> {
 		match self {
-			Mapping::TrueTypeFormat4(mapping) => mapping.get_glyph_id(character_code),
-			Mapping::TrueTypeFormat12(mapping) => mapping.get_glyph_id(character_code),
-			Mapping::InvalidFormat(format) => {
-				println!("Error Attempting To index with invalid cmap subtable format: {format}");
-				None
-			}
+			MappingFormat::TrueTypeFormat0(mapping) => mapping.get_glyph_id(character_code),
+			MappingFormat::TrueTypeFormat2(mapping) => mapping.get_glyph_id(character_code),
+			MappingFormat::TrueTypeFormat4(mapping) => mapping.get_glyph_id(character_code),
+			MappingFormat::TrueTypeFormat6(mapping) => mapping.get_glyph_id(character_code),
+			MappingFormat::TrueTypeFormat10(mapping) => mapping.get_glyph_id(character_code),
+			MappingFormat::TrueTypeFormat12(mapping) => mapping.get_glyph_id(character_code),
+			MappingFormat::TrueTypeFormat13(mapping) => mapping.get_glyph_id(character_code),
+			MappingFormat::TrueTypeFormat14(_) => None, // only maps variation sequences, see Font::get_index_with_variation_selector
+			MappingFormat::InvalidFormat(_) => None, // a format that isn't supported maps nothing
 		}
 	}
 
 	fn get_character_codes(&self, glyph_index: u16) -> Vec<char> {
 		match self {
-			Mapping::TrueTypeFormat4(mapping) => mapping.get_character_codes(glyph_index),
-			Mapping::TrueTypeFormat12(mapping) => mapping.get_character_codes(glyph_index),
-			Mapping::InvalidFormat(format) => {
-				println!("Error Attempting To get character codes with invalid cmap subtable format: {format}");
-				Vec::new()
-			}
+			MappingFormat::TrueTypeFormat0(mapping) => mapping.get_character_codes(glyph_index),
+			MappingFormat::TrueTypeFormat2(mapping) => mapping.get_character_codes(glyph_index),
+			MappingFormat::TrueTypeFormat4(mapping) => mapping.get_character_codes(glyph_index),
+			MappingFormat::TrueTypeFormat6(mapping) => mapping.get_character_codes(glyph_index),
+			MappingFormat::TrueTypeFormat10(mapping) => mapping.get_character_codes(glyph_index),
+			MappingFormat::TrueTypeFormat12(mapping) => mapping.get_character_codes(glyph_index),
+			MappingFormat::TrueTypeFormat13(mapping) => mapping.get_character_codes(glyph_index),
+			MappingFormat::TrueTypeFormat14(mapping) => mapping.get_character_codes(glyph_index),
+			MappingFormat::InvalidFormat(_) => Vec::new(),
 		}
 	}
 }
 
-impl From<ttf_reader::CharacterToGlyphIndexSubtable> for Mapping {
+impl From<ttf_reader::CharacterToGlyphIndexSubtable> for MappingFormat {
 	fn from(value: ttf_reader::CharacterToGlyphIndexSubtable) -> Self {
 		match value {
-			ttf_reader::CharacterToGlyphIndexSubtable::Format4(subtable) => Mapping::TrueTypeFormat4(subtable.into()),
-			ttf_reader::CharacterToGlyphIndexSubtable::Format12(subtable) => Mapping::TrueTypeFormat12(subtable.into()),
-			ttf_reader::CharacterToGlyphIndexSubtable::InvalidFormat(format) => Mapping::InvalidFormat(format),
+			ttf_reader::CharacterToGlyphIndexSubtable::Format0(subtable) => MappingFormat::TrueTypeFormat0(subtable.into()),
+			ttf_reader::CharacterToGlyphIndexSubtable::Format2(subtable) => MappingFormat::TrueTypeFormat2(subtable.into()),
+			ttf_reader::CharacterToGlyphIndexSubtable::Format4(subtable) => MappingFormat::TrueTypeFormat4(subtable.into()),
+			ttf_reader::CharacterToGlyphIndexSubtable::Format6(subtable) => MappingFormat::TrueTypeFormat6(subtable.into()),
+			ttf_reader::CharacterToGlyphIndexSubtable::Format10(subtable) => MappingFormat::TrueTypeFormat10(subtable.into()),
+			ttf_reader::CharacterToGlyphIndexSubtable::Format12(subtable) => MappingFormat::TrueTypeFormat12(subtable.into()),
+			ttf_reader::CharacterToGlyphIndexSubtable::Format13(subtable) => MappingFormat::TrueTypeFormat13(subtable.into()),
+			ttf_reader::CharacterToGlyphIndexSubtable::Format14(subtable) => MappingFormat::TrueTypeFormat14(subtable.into()),
+			ttf_reader::CharacterToGlyphIndexSubtable::InvalidFormat(format) => MappingFormat::InvalidFormat(format),
+		}
+	}
+}
+
+pub struct MappingTrueTypeFormat0 {
+	glyph_id_array: Vec<u8>,
+}
+
+impl MappingTrueTypeFormat0 {
+	fn get_glyph_id(&self, character_code: u64) -> Option<u16> {
+		match self.glyph_id_array.get(character_code as usize) {
+			Some(0) | None => None,
+			Some(glyph_id) => Some(*glyph_id as u16),
+		}
+	}
+
+	fn get_character_codes(&self, glyph_id: u16) -> Vec<char> {
+		self.glyph_id_array.iter().enumerate().filter(|(_, calculated_glyph_id)| **calculated_glyph_id as u16 == glyph_id).filter_map(|(character_code, _)| char::from_u32(character_code as u32)).collect()
+	}
+}
+
+impl From<ttf_reader::CharacterToGlyphIndexSubtableFormat0> for MappingTrueTypeFormat0 {
+	fn from(value: ttf_reader::CharacterToGlyphIndexSubtableFormat0) -> Self {
+		Self {
+			glyph_id_array: value.glyph_id_array,
+		}
+	}
+}
+
+pub struct MappingTrueTypeFormat2 {
+	sub_header_keys: Vec<u16>,
+	sub_headers: Vec<ttf_reader::SubHeader>,
+	glyph_id_array: Vec<u16>,
+}
+
+impl MappingTrueTypeFormat2 {
+	fn get_glyph_id(&self, character_code: u64) -> Option<u16> {
+		if character_code > 0xFFFF {
+			return None;
+		}
+		let (high_byte, low_byte) = ((character_code >> 8) as usize, (character_code & 0xFF) as u16);
+		// Sub header 0 maps single byte characters, any other high byte key marks the first byte of a two byte character.
+		let sub_header_index = match high_byte {
+			0 if self.sub_header_keys[low_byte as usize] == 0 => 0,
+			0 => return None,
+			_ => match self.sub_header_keys[high_byte] / 8 {
+				0 => return None,
+				sub_header_index => sub_header_index as usize,
+			},
+		};
+		let sub_header = self.sub_headers.get(sub_header_index)?;
+		if low_byte < sub_header.first_code || low_byte as u32 >= sub_header.first_code as u32 + sub_header.entry_count as u32 {
+			return None;
+		}
+
+		// idRangeOffset counts bytes from itself, the last field of its sub header, to the sub header's first glyph.
+		let id_range_offset_position = sub_header_index * 8 + 6;
+		let glyph_id_array_position = self.sub_headers.len() * 8;
+		let glyph_id_index = (id_range_offset_position + sub_header.id_range_offset as usize).checked_sub(glyph_id_array_position)? / 2 + (low_byte - sub_header.first_code) as usize;
+		match self.glyph_id_array.get(glyph_id_index) {
+			Some(0) | None => None,
+			Some(glyph_id) => Some((*glyph_id as i32 + sub_header.id_delta as i32).rem_euclid(65536) as u16),
+		}
+	}
+
+	fn get_character_codes(&self, glyph_id: u16) -> Vec<char> {
+		(0..=0xFFFF).filter(|character_code| self.get_glyph_id(*character_code) == Some(glyph_id)).filter_map(|character_code| char::from_u32(character_code as u32)).collect()
+	}
+}
+
+impl From<ttf_reader::CharacterToGlyphIndexSubtableFormat2> for MappingTrueTypeFormat2 {
+	fn from(value: ttf_reader::CharacterToGlyphIndexSubtableFormat2) -> Self {
+		Self {
+			sub_header_keys: value.sub_header_keys,
+			sub_headers: value.sub_headers,
+			glyph_id_array: value.glyph_id_array,
 		}
 	}
 }
@@ -438,8 +1450,7 @@ impl MappingTrueTypeFormat4 {
 				if self.id_range_offsets[i] == 0 {
 					return Some(((character_code as i128 + self.id_deltas[i] as i128) % 65536) as u16);
 				} else {
-					let glyph_id_index = (character_code - self.start_codes[i] as u64) + (self.id_range_offsets[i] as u64 / 2) + i as u64 - self.segment_count as u64;
-					let glyph_id = self.glyph_id_array[glyph_id_index as usize];
+					let glyph_id = self.glyph_id_array_entry(i, character_code)?;
 					if glyph_id == 0 {
 						return None;
 					} else {
@@ -460,21 +1471,24 @@ impl MappingTrueTypeFormat4 {
 				if self.id_range_offsets[i] == 0 {
 					calculated_glyph_id = ((character_code as i128 + self.id_deltas[i] as i128) % 65536) as u16;
 				} else {
-					let glyph_id_index = (character_code - self.start_codes[i] as u64) + (self.id_range_offsets[i] as u64 / 2) + i as u64 - self.segment_count as u64;
-					let glyph_id = self.glyph_id_array[glyph_id_index as usize];
-					calculated_glyph_id = if glyph_id == 0 {
-						0
-					} else {
-						((glyph_id as i32 + self.id_deltas[i] as i32) % 65536) as u16
-					}
+					calculated_glyph_id = match self.glyph_id_array_entry(i, character_code) {
+						Some(0) | None => 0,
+						Some(glyph_id) => ((glyph_id as i32 + self.id_deltas[i] as i32) % 65536) as u16,
+					};
 				}
-				if calculated_glyph_id == glyph_id {
-					character_codes.push(char::from_u32(character_code as u32).unwrap());
+				if calculated_glyph_id == glyph_id && let Some(character) = char::from_u32(character_code as u32) {
+					character_codes.push(character);
 				}
 			}
 		}
 		character_codes
 	}
+
+	/// The glyphIdArray entry for a character in segment `i`, found by its idRangeOffset, or None if that points outside the array.
+	fn glyph_id_array_entry(&self, i: usize, character_code: u64) -> Option<u16> {
+		let glyph_id_index = ((character_code - self.start_codes[i] as u64) + (self.id_range_offsets[i] as u64 / 2) + i as u64).checked_sub(self.segment_count as u64)?;
+		self.glyph_id_array.get(glyph_id_index as usize).copied()
+	}
 }
 
 impl From<ttf_reader::CharacterToGlyphIndexSubtableFormat4> for MappingTrueTypeFormat4 {
@@ -535,4 +1549,108 @@ impl From<ttf_reader::CharacterToGlyphIndexSubtableFormat12> for MappingTrueType
 			groups: value.groups,
 		}
 	}
+}
+
+pub struct MappingTrueTypeFormat6 {
+	first_code: u16,
+	glyph_id_array: Vec<u16>,
+}
+
+impl MappingTrueTypeFormat6 {
+	fn get_glyph_id(&self, character_code: u64) -> Option<u16> {
+		let index = character_code.checked_sub(self.first_code as u64)?;
+		match self.glyph_id_array.get(index as usize) {
+			Some(0) | None => None,
+			Some(glyph_id) => Some(*glyph_id),
+		}
+	}
+
+	fn get_character_codes(&self, glyph_id: u16) -> Vec<char> {
+		self.glyph_id_array.iter().enumerate().filter(|(_, calculated_glyph_id)| **calculated_glyph_id == glyph_id).filter_map(|(index, _)| char::from_u32(self.first_code as u32 + index as u32)).collect()
+	}
+}
+
+impl From<ttf_reader::CharacterToGlyphIndexSubtableFormat6> for MappingTrueTypeFormat6 {
+	fn from(value: ttf_reader::CharacterToGlyphIndexSubtableFormat6) -> Self {
+		Self {
+			first_code: value.first_code,
+			glyph_id_array: value.glyph_id_array,
+		}
+	}
+}
+
+pub struct MappingTrueTypeFormat10 {
+	start_char_code: u32,
+	glyph_id_array: Vec<u16>,
+}
+
+impl MappingTrueTypeFormat10 {
+	fn get_glyph_id(&self, character_code: u64) -> Option<u16> {
+		let index = character_code.checked_sub(self.start_char_code as u64)?;
+		match self.glyph_id_array.get(index as usize) {
+			Some(0) | None => None,
+			Some(glyph_id) => Some(*glyph_id),
+		}
+	}
+
+	fn get_character_codes(&self, glyph_id: u16) -> Vec<char> {
+		self.glyph_id_array.iter().enumerate().filter(|(_, calculated_glyph_id)| **calculated_glyph_id == glyph_id).filter_map(|(index, _)| char::from_u32(self.start_char_code.wrapping_add(index as u32))).collect()
+	}
+}
+
+impl From<ttf_reader::CharacterToGlyphIndexSubtableFormat10> for MappingTrueTypeFormat10 {
+	fn from(value: ttf_reader::CharacterToGlyphIndexSubtableFormat10) -> Self {
+		Self {
+			start_char_code: value.start_char_code,
+			glyph_id_array: value.glyph_id_array,
+		}
+	}
+}
+
+pub struct MappingTrueTypeFormat13 {
+	groups: Vec<(u32, u32, u32)>, // start character code, end character code, glyph id
+}
+
+impl MappingTrueTypeFormat13 {
+	fn get_glyph_id(&self, character_code: u64) -> Option<u16> {
+		self.groups.iter().find(|(start_code, end_code, _)| character_code >= *start_code as u64 && character_code <= *end_code as u64).map(|(_, _, glyph_id)| *glyph_id as u16)
+	}
+
+	/// The first character code of each group mapping to the glyph. A group can cover the whole of Unicode, as in last resort fonts,
+	/// so the rest of it isn't listed.
+	fn get_character_codes(&self, glyph_id: u16) -> Vec<char> {
+		self.groups.iter().filter(|(_, _, group_glyph_id)| *group_glyph_id as u16 == glyph_id).filter_map(|(start_code, _, _)| char::from_u32(*start_code)).collect()
+	}
+}
+
+impl From<ttf_reader::CharacterToGlyphIndexSubtableFormat13> for MappingTrueTypeFormat13 {
+	fn from(value: ttf_reader::CharacterToGlyphIndexSubtableFormat13) -> Self {
+		Self {
+			groups: value.groups,
+		}
+	}
+}
+
+pub struct MappingTrueTypeFormat14 {
+	variation_selector_records: Vec<ttf_reader::VariationSelectorRecord>,
+}
+
+impl MappingTrueTypeFormat14 {
+	/// The glyph a variation sequence maps to. Sequences using the character's default glyph and sequences that aren't listed both give None.
+	fn get_glyph_id(&self, character_code: u64, variation_selector: u64) -> Option<u16> {
+		let record = self.variation_selector_records.iter().find(|record| record.variation_selector as u64 == variation_selector)?;
+		record.non_default_uvs.iter().find(|(unicode_value, _)| *unicode_value as u64 == character_code).map(|(_, glyph_id)| *glyph_id)
+	}
+
+	fn get_character_codes(&self, glyph_id: u16) -> Vec<char> {
+		self.variation_selector_records.iter().flat_map(|record| record.non_default_uvs.iter()).filter(|(_, uvs_glyph_id)| *uvs_glyph_id == glyph_id).filter_map(|(unicode_value, _)| char::from_u32(*unicode_value)).collect()
+	}
+}
+
+impl From<ttf_reader::CharacterToGlyphIndexSubtableFormat14> for MappingTrueTypeFormat14 {
+	fn from(value: ttf_reader::CharacterToGlyphIndexSubtableFormat14) -> Self {
+		Self {
+			variation_selector_records: value.variation_selector_records,
+		}
+	}
 }
\ No newline at end of file
@@ -0,0 +1,397 @@
+use std::sync::{Arc, OnceLock};
+
+use crate::{cff_reader::CompactFontFormatTable, font::{hinting::HintedGlyphCache, preferred_mapping, Glyph, Mapping, RawGlyphs}, ttf_parser::{flatten_glyph, GlyphDataIntermediate, GlyphIntermediate}, ttf_reader::SimpleGlyphRaw};
+
+const FLAT_RATIO: f64 = 12.0; // How many times longer than it is steep a piece of outline must be to count as flat
+
+/// Characters whose tops or bottoms line up across a font, and which edge of theirs to measure. Round letters such as
+/// O overshoot the flat ones such as H a little, so their extremes are kept apart as the overshoot.
+const BLUE_CHARACTERS: [(&str, BlueEdge); 8] = [
+	("THEZOCQS", BlueEdge::Top), // Capital height
+	("HEZLOCUS", BlueEdge::Bottom), // Baseline
+	("xzroesc", BlueEdge::Top), // x-height
+	("xzroesc", BlueEdge::Bottom), // Baseline of lowercase letters
+	("bdhkl", BlueEdge::Top), // Ascender
+	("pqgjy", BlueEdge::Bottom), // Descender
+	("他们你来到和地对就我时会为能说这", BlueEdge::Top), // Top of the ideographic em box
+	("他们你来到和地对就我时会为能说这", BlueEdge::Bottom),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlueEdge {
+	Top,
+	Bottom,
+}
+
+/// A height that edges of glyphs across the font line up on, in font units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlueZone {
+	pub reference: f64, // Where the flat edges are
+	pub overshoot: f64, // Where the round edges are, a little past the flat ones
+	pub edge: BlueEdge,
+}
+
+/// The outlines the autohinter works from, which are kept as they were read.
+pub enum AutoHintOutlines {
+	TrueType(RawGlyphs),
+	CompactFontFormat(CompactFontFormatTable),
+}
+
+/// Grid-fits outlines without any instructions from the font, in the manner of FreeType's autofit: it finds the font's
+/// blue zones from the outlines of reference characters, then the stems of each glyph from its contours, and snaps them to the pixels.
+pub struct AutoHinter {
+	outlines: AutoHintOutlines,
+	units_per_em: u16,
+	mappings: Arc<Vec<Mapping>>,
+	blue_zones: OnceLock<Vec<BlueZone>>, // Measured when the first glyph is hinted, as the outlines can't be read while the font is loading
+	hinted_glyphs: HintedGlyphCache,
+}
+
+/// A run of outline that goes nearly straight along one axis, as the top of an x or one side of an l does.
+#[derive(Debug, Clone)]
+struct Segment {
+	position: f64, // Across the axis
+	min: f64, // Along the axis
+	max: f64,
+	direction: i8, // Which way the outline goes along the axis
+	points: Vec<usize>,
+}
+
+/// Segments at the same place going the same way, such as the tops of both arches of an m, moved onto the grid together.
+#[derive(Debug, Clone)]
+struct Edge {
+	position: f64,
+	min: f64,
+	max: f64,
+	length: f64,
+	direction: i8,
+	points: Vec<usize>,
+	ink_above: bool, // Whether the outline is filled on the side of greater coordinates
+	hinted: Option<f64>, // In pixels
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+	X, // Moving x coordinates, to fit vertical stems
+	Y, // Moving y coordinates, to fit horizontal stems and blue zones
+}
+
+impl AutoHinter {
+	/// Blue zones are found from the reference characters through the font's character map.
+	pub fn new(outlines: AutoHintOutlines, units_per_em: u16, mappings: Arc<Vec<Mapping>>) -> AutoHinter {
+		AutoHinter { outlines, units_per_em, mappings, blue_zones: OnceLock::new(), hinted_glyphs: HintedGlyphCache::default() }
+	}
+
+	/// The font's blue zones, measured from the glyphs for their reference characters. Zones none of whose characters the font
+	/// has are left out.
+	pub fn blue_zones(&self) -> &[BlueZone] {
+		self.blue_zones.get_or_init(|| {
+			let mapping = preferred_mapping(&self.mappings).map(|index| &self.mappings[index]);
+			let glyph_id = |character: char| mapping?.get_glyph_id(character as u64);
+			BLUE_CHARACTERS.iter().filter_map(|(characters, edge)| self.blue_zone(characters, *edge, &glyph_id)).collect()
+		})
+	}
+
+	/// A glyph's outline grid-fitted at a size, in font units, or None if it has no outline.
+	pub fn hint_glyph(&self, glyph_id: u16, pixels_per_em: f32) -> Option<SimpleGlyphRaw> {
+		let mut outline = self.outline(glyph_id)?;
+		if outline.end_points_of_contours.is_empty() {
+			return None;
+		}
+		let scale = pixels_per_em as f64 / self.units_per_em.max(1) as f64;
+		for axis in [Axis::X, Axis::Y] {
+			self.hint_axis(&mut outline, axis, scale);
+		}
+		Some(outline)
+	}
+
+	/// The hinted glyph at a size from the cache, made with `make` the first time it's asked for.
+	pub(crate) fn cached_glyph(&self, glyph_id: u16, pixels_per_em: f32, make: impl FnOnce() -> Option<Arc<Glyph>>) -> Option<Arc<Glyph>> {
+		self.hinted_glyphs.get_or_insert_with(glyph_id, pixels_per_em, make)
+	}
+
+	/// A glyph's points and contours in font units, with composite glyphs' components put in place and CFF's cubic control points
+	/// flagged as the rest of the crate flags them.
+	fn outline(&self, glyph_id: u16) -> Option<SimpleGlyphRaw> {
+		match &self.outlines {
+			AutoHintOutlines::TrueType(glyphs) => {
+				let mut outline = SimpleGlyphRaw::default();
+				flatten_glyph(&|glyph_id| glyphs.glyph(glyph_id), &*glyphs.glyph(glyph_id)?, &mut outline, (1.0, 0.0, 0.0, 1.0), (0.0, 0.0), 0)?;
+				Some(outline)
+			},
+			AutoHintOutlines::CompactFontFormat(compact_font_format_table) => {
+				let GlyphDataIntermediate::SimpleGlyph(glyph) = GlyphIntermediate::from(compact_font_format_table.outline(glyph_id as usize).ok()?).glyph_data else {
+					return None;
+				};
+				let mut end_points_of_contours: Vec<u16> = Vec::with_capacity(glyph.contours.len());
+				for contour in glyph.contours.iter() {
+					end_points_of_contours.push(contour.indices.iter().flatten().max().copied()? as u16);
+				}
+				Some(SimpleGlyphRaw {
+					end_points_of_contours,
+					instruction_length: 0,
+					instructions: Vec::new(),
+					flags: glyph.points.iter().map(|point| point.flag).collect(),
+					x_coordinates: glyph.points.iter().map(|point| point.x).collect(),
+					y_coordinates: glyph.points.iter().map(|point| point.y).collect(),
+				})
+			},
+		}
+	}
+
+	/// A zone from the extremes of its characters: flat extremes give where it is, and round ones its overshoot.
+	fn blue_zone(&self, characters: &str, edge: BlueEdge, glyph_id: &impl Fn(char) -> Option<u16>) -> Option<BlueZone> {
+		let flat_tolerance = self.units_per_em as f64 / 250.0;
+		let mut flats: Vec<f64> = Vec::new();
+		let mut rounds: Vec<f64> = Vec::new();
+		for character in characters.chars() {
+			let Some(outline) = glyph_id(character).and_then(|glyph_id| self.outline(glyph_id)) else {
+				continue;
+			};
+			let extreme = match edge {
+				BlueEdge::Top => (0..outline.y_coordinates.len()).max_by_key(|point| outline.y_coordinates[*point]),
+				BlueEdge::Bottom => (0..outline.y_coordinates.len()).min_by_key(|point| outline.y_coordinates[*point]),
+			};
+			let Some(extreme) = extreme else {
+				continue;
+			};
+			// The extreme is flat if it's on the curve and the outline carries on straight from it to another point on the curve.
+			let (previous, next) = contour_neighbours(&outline.end_points_of_contours, extreme);
+			let y = outline.y_coordinates[extreme] as f64;
+			let is_flat = outline.flags[extreme] & 0x01 != 0 && [previous, next].iter().any(|neighbour| outline.flags[*neighbour] & 0x01 != 0 && (outline.y_coordinates[*neighbour] as f64 - y).abs() <= flat_tolerance && outline.x_coordinates[*neighbour] != outline.x_coordinates[extreme]);
+			match is_flat {
+				true => flats.push(y),
+				false => rounds.push(y),
+			}
+		}
+		let average = |values: &[f64]| match values.is_empty() {
+			true => None,
+			false => Some(values.iter().sum::<f64>() / values.len() as f64),
+		};
+		let reference = average(&flats).or(average(&rounds))?;
+		Some(BlueZone { reference, overshoot: average(&rounds).unwrap_or(reference), edge })
+	}
+
+	/// Moves the outline's coordinates on one axis so its edges fall on pixel boundaries, and the points between them along with them.
+	fn hint_axis(&self, outline: &mut SimpleGlyphRaw, axis: Axis, scale: f64) {
+		let (along, across): (&[i16], &[i16]) = match axis {
+			Axis::X => (&outline.y_coordinates, &outline.x_coordinates),
+			Axis::Y => (&outline.x_coordinates, &outline.y_coordinates),
+		};
+		let units_per_em = self.units_per_em as f64;
+		let segments = find_segments(&outline.end_points_of_contours, along, across);
+		// Within a quarter of a pixel, segments are the same edge.
+		let mut edges = merge_segments(segments, (0.25 / scale).min(units_per_em / 40.0));
+
+		// Outer contours go clockwise in TrueType outlines and anticlockwise in CFF ones, which decides which side of an edge is filled.
+		let clockwise = signed_area(&outline.end_points_of_contours, &outline.x_coordinates, &outline.y_coordinates) < 0.0;
+		for edge in edges.iter_mut() {
+			edge.ink_above = match axis {
+				Axis::X => (edge.direction > 0) == clockwise,
+				Axis::Y => (edge.direction < 0) == clockwise,
+			};
+		}
+
+		if axis == Axis::Y {
+			self.align_blue_edges(&mut edges, scale);
+		}
+		align_stems(&mut edges, scale, units_per_em * 0.4);
+
+		// Points on an edge move with it, and the rest are spaced out between the edges either side of them.
+		let mut anchors: Vec<(f64, f64)> = edges.iter().filter_map(|edge| Some((edge.position, edge.hinted?))).collect();
+		anchors.sort_by(|a, b| a.0.total_cmp(&b.0));
+		let mut edge_of_point: Vec<Option<usize>> = vec![None; across.len()];
+		for (edge_index, edge) in edges.iter().enumerate().filter(|(_, edge)| edge.hinted.is_some()) {
+			for point in edge.points.iter() {
+				edge_of_point[*point].get_or_insert(edge_index);
+			}
+		}
+		let hinted: Vec<i16> = across.iter().enumerate().map(|(point, coordinate)| {
+			let coordinate = *coordinate as f64;
+			let pixels = match edge_of_point[point] {
+				Some(edge_index) => edges[edge_index].hinted.unwrap_or(0.0) + (coordinate - edges[edge_index].position) * scale,
+				None => interpolate(&anchors, coordinate, scale),
+			};
+			(pixels / scale).round().clamp(i16::MIN as f64, i16::MAX as f64) as i16
+		}).collect();
+		match axis {
+			Axis::X => outline.x_coordinates = hinted,
+			Axis::Y => outline.y_coordinates = hinted,
+		}
+	}
+
+	/// Snaps the edges in a blue zone to its height rounded to the pixel, keeping round edges' overshoot only once it's half a pixel or more.
+	fn align_blue_edges(&self, edges: &mut [Edge], scale: f64) {
+		let fuzz = self.units_per_em as f64 / 40.0;
+		for edge in edges.iter_mut() {
+			let zone = self.blue_zones().iter()
+				.filter(|zone| (zone.edge == BlueEdge::Top) != edge.ink_above)
+				.filter(|zone| edge.position >= zone.reference.min(zone.overshoot) - fuzz && edge.position <= zone.reference.max(zone.overshoot) + fuzz)
+				.min_by(|a, b| (a.reference - edge.position).abs().total_cmp(&(b.reference - edge.position).abs()));
+			if let Some(zone) = zone {
+				let overshoot = (edge.position - zone.reference) * scale;
+				let overshoot = if overshoot.abs() < 0.5 { 0.0 } else { overshoot.round() };
+				edge.hinted = Some((zone.reference * scale).round() + overshoot);
+			}
+		}
+	}
+}
+
+/// The points either side of a point on its contour.
+fn contour_neighbours(end_points_of_contours: &[u16], point: usize) -> (usize, usize) {
+	let mut start = 0;
+	for end in end_points_of_contours.iter().map(|end| *end as usize) {
+		if point <= end {
+			let previous = if point == start { end } else { point - 1 };
+			let next = if point == end { start } else { point + 1 };
+			return (previous, next);
+		}
+		start = end + 1;
+	}
+	(point, point)
+}
+
+/// Twice the area the contours enclose, positive when they go anticlockwise.
+fn signed_area(end_points_of_contours: &[u16], x_coordinates: &[i16], y_coordinates: &[i16]) -> f64 {
+	let mut area = 0.0;
+	let mut start = 0;
+	for end in end_points_of_contours.iter().map(|end| *end as usize) {
+		for point in start..=end.min(x_coordinates.len().saturating_sub(1)) {
+			let next = if point == end { start } else { point + 1 };
+			area += x_coordinates[point] as f64 * y_coordinates[next] as f64 - x_coordinates[next] as f64 * y_coordinates[point] as f64;
+		}
+		start = end + 1;
+	}
+	area
+}
+
+/// The runs of each contour that go flat along the axis, from one point to the next around it.
+fn find_segments(end_points_of_contours: &[u16], along: &[i16], across: &[i16]) -> Vec<Segment> {
+	let mut segments: Vec<Segment> = Vec::new();
+	let mut start = 0;
+	for end in end_points_of_contours.iter().map(|end| *end as usize) {
+		if end >= along.len() || end < start {
+			break;
+		}
+		let points: Vec<usize> = (start..=end).collect();
+		start = end + 1;
+		let directions: Vec<Option<i8>> = (0..points.len()).map(|index| {
+			let (a, b) = (points[index], points[(index + 1) % points.len()]);
+			let along_distance = (along[b] - along[a]) as f64;
+			let across_distance = (across[b] - across[a]) as f64;
+			match along_distance != 0.0 && across_distance.abs() * FLAT_RATIO <= along_distance.abs() {
+				true => Some(along_distance.signum() as i8),
+				false => None,
+			}
+		}).collect();
+		// Start from a piece that begins a run, so a run across the contour's first point isn't split in two.
+		let Some(first) = (0..directions.len()).find(|index| directions[*index] != directions[(index + directions.len() - 1) % directions.len()]) else {
+			continue;
+		};
+		let mut current: Option<Segment> = None;
+		for offset in 0..directions.len() {
+			let index = (first + offset) % directions.len();
+			let (a, b) = (points[index], points[(index + 1) % points.len()]);
+			match (directions[index], &mut current) {
+				(Some(direction), Some(segment)) if segment.direction == direction => segment.points.push(b),
+				(direction, _) => {
+					segments.extend(current.take());
+					current = direction.map(|direction| Segment { position: 0.0, min: 0.0, max: 0.0, direction, points: vec![a, b] });
+				},
+			}
+		}
+		segments.extend(current);
+	}
+	for segment in segments.iter_mut() {
+		segment.position = segment.points.iter().map(|point| across[*point] as f64).sum::<f64>() / segment.points.len() as f64;
+		segment.min = segment.points.iter().map(|point| along[*point] as f64).fold(f64::INFINITY, f64::min);
+		segment.max = segment.points.iter().map(|point| along[*point] as f64).fold(f64::NEG_INFINITY, f64::max);
+	}
+	segments
+}
+
+/// Gathers segments going the same way within `threshold` of each other into edges, placed at the average of their positions by length.
+fn merge_segments(mut segments: Vec<Segment>, threshold: f64) -> Vec<Edge> {
+	segments.sort_by(|a, b| a.position.total_cmp(&b.position));
+	let mut edges: Vec<Edge> = Vec::new();
+	for segment in segments {
+		let length = (segment.max - segment.min).max(1.0);
+		match edges.iter_mut().rev().take_while(|edge| segment.position - edge.position <= threshold).find(|edge| edge.direction == segment.direction) {
+			Some(edge) => {
+				edge.position = (edge.position * edge.length + segment.position * length) / (edge.length + length);
+				edge.length += length;
+				edge.min = edge.min.min(segment.min);
+				edge.max = edge.max.max(segment.max);
+				edge.points.extend(segment.points);
+			},
+			None => edges.push(Edge { position: segment.position, min: segment.min, max: segment.max, length, direction: segment.direction, points: segment.points, ink_above: false, hinted: None }),
+		}
+	}
+	edges
+}
+
+/// Pairs each edge with the nearest facing edge across filled outline to make stems, and rounds their widths to whole pixels.
+/// Stems with an edge already in a blue zone grow from it, and the rest are centred where they were.
+fn align_stems(edges: &mut [Edge], scale: f64, max_width: f64) {
+	let mut candidates: Vec<(f64, usize, usize)> = Vec::new();
+	for (lower_index, lower) in edges.iter().enumerate().filter(|(_, edge)| edge.ink_above) {
+		for (upper_index, upper) in edges.iter().enumerate().filter(|(_, edge)| !edge.ink_above) {
+			let width = upper.position - lower.position;
+			let overlap = lower.max.min(upper.max) - lower.min.max(upper.min);
+			if width > 0.0 && width <= max_width && overlap > 0.0 {
+				// Edges that only overlap a little make poorer stems than a slightly wider pair that overlap fully.
+				let shorter = (lower.max - lower.min).min(upper.max - upper.min).max(1.0);
+				candidates.push((width * (1.0 + shorter / overlap), lower_index, upper_index));
+			}
+		}
+	}
+	candidates.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+	let mut paired = vec![false; edges.len()];
+	let mut stems: Vec<(usize, usize)> = Vec::new();
+	for (_, lower, upper) in candidates {
+		if !paired[lower] && !paired[upper] {
+			paired[lower] = true;
+			paired[upper] = true;
+			stems.push((lower, upper));
+		}
+	}
+	// Stems with a fixed edge go first, so the others don't take the space they need.
+	stems.sort_by_key(|(lower, upper)| edges[*lower].hinted.is_none() && edges[*upper].hinted.is_none());
+
+	for (lower, upper) in stems {
+		let width = (edges[upper].position - edges[lower].position) * scale;
+		let hinted_width = width.round().max(1.0);
+		match (edges[lower].hinted, edges[upper].hinted) {
+			(Some(_), Some(_)) => {},
+			(Some(lower_position), None) => edges[upper].hinted = Some(lower_position + hinted_width),
+			(None, Some(upper_position)) => edges[lower].hinted = Some(upper_position - hinted_width),
+			(None, None) => {
+				let centre = (edges[lower].position + edges[upper].position) / 2.0 * scale;
+				let lower_position = (centre - hinted_width / 2.0).round();
+				edges[lower].hinted = Some(lower_position);
+				edges[upper].hinted = Some(lower_position + hinted_width);
+			},
+		}
+	}
+}
+
+/// A coordinate in pixels, moved in proportion between the edges either side of it, or with the nearest one past the ends.
+fn interpolate(anchors: &[(f64, f64)], coordinate: f64, scale: f64) -> f64 {
+	let scaled = coordinate * scale;
+	let Some((first, last)) = anchors.first().zip(anchors.last()) else {
+		return scaled;
+	};
+	if coordinate <= first.0 {
+		return scaled + first.1 - first.0 * scale;
+	}
+	if coordinate >= last.0 {
+		return scaled + last.1 - last.0 * scale;
+	}
+	let index = anchors.partition_point(|anchor| anchor.0 <= coordinate);
+	let ((low_original, low_hinted), (high_original, high_hinted)) = (anchors[index - 1], anchors[index]);
+	match high_original - low_original > 0.0 {
+		true => low_hinted + (coordinate - low_original) / (high_original - low_original) * (high_hinted - low_hinted),
+		false => low_hinted,
+	}
+}
@@ -0,0 +1,359 @@
+use std::sync::Arc;
+
+use mircalla_types::vectors::Colour;
+
+use crate::colour_reader::{Affine, ClipBox, CompositeMode, PaintCommand};
+
+use super::{ColourPaint, Font, Glyph, GlyphData};
+
+/// How many samples are taken across and down each pixel, which smooths the edges of outlines.
+const SAMPLES_PER_AXIS: usize = 4;
+
+/// How deep composite glyphs may nest, so that glyphs made of themselves can't recurse forever.
+const MAX_COMPONENT_DEPTH: usize = 8;
+
+/// A colour glyph drawn on the CPU, in rows from the top, with RGBA pixels that aren't premultiplied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColourImage {
+	pub width: usize,
+	pub height: usize,
+	pub origin: (f32, f32), // The top left corner, in font units
+	pub pixels: Vec<[u8; 4]>,
+}
+
+impl ColourImage {
+	pub fn pixel(&self, x: usize, y: usize) -> [u8; 4] {
+		self.pixels[y * self.width + x]
+	}
+}
+
+/// One of a glyph's triangles, filled by the same rules as the shader modes: solid, convex and concave quadratic curves, and cubic curves.
+struct Triangle {
+	points: [(f32, f32); 3],
+	uv_coords: [[f32; 3]; 3],
+	mode: u8,
+}
+
+/// Where the samples of each pixel are, in font units.
+struct Grid {
+	origin: (f32, f32),
+	font_units_per_pixel: f32,
+	width: usize,
+	height: usize,
+}
+
+impl Grid {
+	fn sample(&self, x: usize, y: usize, sample: usize) -> (f32, f32) {
+		let (sample_x, sample_y) = (sample % SAMPLES_PER_AXIS, sample / SAMPLES_PER_AXIS);
+		(
+			self.origin.0 + (x as f32 + (sample_x as f32 + 0.5) / SAMPLES_PER_AXIS as f32) * self.font_units_per_pixel,
+			self.origin.1 - (y as f32 + (sample_y as f32 + 0.5) / SAMPLES_PER_AXIS as f32) * self.font_units_per_pixel,
+		)
+	}
+
+	fn centre(&self, x: usize, y: usize) -> (f32, f32) {
+		(self.origin.0 + (x as f32 + 0.5) * self.font_units_per_pixel, self.origin.1 - (y as f32 + 0.5) * self.font_units_per_pixel)
+	}
+}
+
+/// Draws a COLR version 1 glyph at the given size, over its clip box, or over the glyphs it's clipped to if the font doesn't give one.
+pub fn rasterize_colour_glyph(font: &Font, glyph_id: u16, palette_index: usize, foreground: Colour, pixels_per_em: f32) -> Option<ColourImage> {
+	let colour_paint = font.colour_paint(glyph_id, palette_index, foreground)?;
+	let glyph = |index: usize| font.get_glyph(index).ok();
+	Some(rasterize_paint(&colour_paint, &glyph, pixels_per_em / font.units_per_em.value as f32))
+}
+
+/// Draws evaluated paint commands, looking the glyphs they're clipped to up with `glyph`.
+/// Unlike the shader, fills are clipped to every one of their clip glyphs, and composites use their mode.
+pub fn rasterize_paint(colour_paint: &ColourPaint, glyph: &dyn Fn(usize) -> Option<Arc<Glyph>>, pixels_per_font_unit: f32) -> ColourImage {
+	let area = colour_paint.clip_box.or_else(|| paint_area(&colour_paint.commands, glyph));
+	let Some(area) = area.filter(|area| area.x_max > area.x_min && area.y_max > area.y_min && pixels_per_font_unit > 0.0) else {
+		return ColourImage { width: 0, height: 0, origin: (0.0, 0.0), pixels: Vec::new() };
+	};
+	let grid = Grid {
+		origin: (area.x_min, area.y_max),
+		font_units_per_pixel: 1.0 / pixels_per_font_unit,
+		width: ((area.x_max - area.x_min) * pixels_per_font_unit).ceil() as usize,
+		height: ((area.y_max - area.y_min) * pixels_per_font_unit).ceil() as usize,
+	};
+	// Samples past the far edges of the clip box, in the last column and row, are left out.
+	let area_mask: Vec<u16> = (0..grid.width * grid.height).map(|pixel| {
+		(0..SAMPLES_PER_AXIS * SAMPLES_PER_AXIS).fold(0, |mask, sample| {
+			let (x, y) = grid.sample(pixel % grid.width, pixel / grid.width, sample);
+			if x <= area.x_max && y >= area.y_min { mask | 1 << sample } else { mask }
+		})
+	}).collect();
+
+	let mut layer = vec![[0.0; 4]; grid.width * grid.height];
+	draw_commands(&colour_paint.commands, glyph, &grid, &area_mask, &mut layer);
+
+	let pixels = layer.iter().map(|colour| {
+		let alpha = colour[3].clamp(0.0, 1.0);
+		let channel = |value: f32| if alpha > 0.0 { (value / alpha * 255.0).round().clamp(0.0, 255.0) as u8 } else { 0 };
+		[channel(colour[0]), channel(colour[1]), channel(colour[2]), (alpha * 255.0).round() as u8]
+	}).collect();
+	ColourImage { width: grid.width, height: grid.height, origin: grid.origin, pixels }
+}
+
+fn draw_commands(commands: &[PaintCommand], glyph: &dyn Fn(usize) -> Option<Arc<Glyph>>, grid: &Grid, area_mask: &[u16], layer: &mut [[f32; 4]]) {
+	let full_coverage = (SAMPLES_PER_AXIS * SAMPLES_PER_AXIS) as f32;
+	for command in commands {
+		match command {
+			PaintCommand::Fill { clips, brush, brush_transform } => {
+				let Some(brush_inverse) = brush_transform.invert() else {
+					continue;
+				};
+				let mut mask = area_mask.to_vec();
+				for (clip_glyph_id, clip_transform) in clips {
+					let triangles = clip_triangles(glyph, *clip_glyph_id, clip_transform);
+					for (pixel_mask, clip_mask) in mask.iter_mut().zip(coverage(&triangles, grid)) {
+						*pixel_mask &= clip_mask;
+					}
+				}
+				for (pixel, pixel_mask) in mask.into_iter().enumerate().filter(|(_, pixel_mask)| *pixel_mask != 0) {
+					let (x, y) = grid.centre(pixel % grid.width, pixel / grid.width);
+					let (brush_x, brush_y) = brush_inverse.apply(x, y);
+					let colour = brush.colour_at(brush_x, brush_y);
+					let covered = pixel_mask.count_ones() as f32 / full_coverage;
+					layer[pixel] = source_over(colour.map(|channel| channel * covered), layer[pixel]);
+				}
+			},
+			PaintCommand::Composite { mode, source, backdrop } => {
+				let mut source_layer = vec![[0.0; 4]; layer.len()];
+				let mut backdrop_layer = vec![[0.0; 4]; layer.len()];
+				draw_commands(source, glyph, grid, area_mask, &mut source_layer);
+				draw_commands(backdrop, glyph, grid, area_mask, &mut backdrop_layer);
+				for (pixel, colour) in layer.iter_mut().enumerate() {
+					*colour = source_over(composite(*mode, source_layer[pixel], backdrop_layer[pixel]), *colour);
+				}
+			},
+		}
+	}
+}
+
+/// The bounds of everything the commands fill, in font units, for paints without a clip box. Fills that aren't clipped to a glyph have none.
+fn paint_area(commands: &[PaintCommand], glyph: &dyn Fn(usize) -> Option<Arc<Glyph>>) -> Option<ClipBox> {
+	let union = |first: Option<ClipBox>, second: Option<ClipBox>| match (first, second) {
+		(Some(first), Some(second)) => Some(ClipBox { x_min: first.x_min.min(second.x_min), y_min: first.y_min.min(second.y_min), x_max: first.x_max.max(second.x_max), y_max: first.y_max.max(second.y_max) }),
+		(first, second) => first.or(second),
+	};
+	commands.iter().fold(None, |area, command| match command {
+		PaintCommand::Fill { clips, .. } => {
+			// A fill only covers where all its clip glyphs overlap, so it's within the bounds of each.
+			let fill_area = clips.iter().map(|(clip_glyph_id, clip_transform)| triangles_bounds(&clip_triangles(glyph, *clip_glyph_id, clip_transform)))
+				.reduce(|first, second| match (first, second) {
+					(Some(first), Some(second)) => Some(ClipBox { x_min: first.x_min.max(second.x_min), y_min: first.y_min.max(second.y_min), x_max: first.x_max.min(second.x_max), y_max: first.y_max.min(second.y_max) }),
+					_ => None,
+				})
+				.flatten();
+			union(area, fill_area)
+		},
+		PaintCommand::Composite { source, backdrop, .. } => union(area, union(paint_area(source, glyph), paint_area(backdrop, glyph))),
+	})
+}
+
+fn triangles_bounds(triangles: &[Triangle]) -> Option<ClipBox> {
+	let mut points = triangles.iter().flat_map(|triangle| triangle.points);
+	let (x, y) = points.next()?;
+	Some(points.fold(ClipBox { x_min: x, y_min: y, x_max: x, y_max: y }, |bounds, (x, y)| ClipBox {
+		x_min: bounds.x_min.min(x),
+		y_min: bounds.y_min.min(y),
+		x_max: bounds.x_max.max(x),
+		y_max: bounds.y_max.max(y),
+	}))
+}
+
+fn clip_triangles(glyph: &dyn Fn(usize) -> Option<Arc<Glyph>>, glyph_id: u16, transform: &Affine) -> Vec<Triangle> {
+	let mut triangles = Vec::new();
+	if let Some(clip_glyph) = glyph(glyph_id as usize) {
+		glyph_triangles(glyph, &clip_glyph, transform, 0, &mut triangles);
+	}
+	triangles
+}
+
+/// Collects a glyph's triangles, transformed. Clockwise triangles are left out, as the renderer culls them.
+fn glyph_triangles(glyph: &dyn Fn(usize) -> Option<Arc<Glyph>>, outline: &Glyph, transform: &Affine, depth: usize, triangles: &mut Vec<Triangle>) {
+	match &outline.data {
+		GlyphData::SimpleGlyph(data) => {
+			for (mode, indices) in [(0, &data.indices), (1, &data.convex_bezier_indices), (2, &data.concave_bezier_indices), (3, &data.cubic_bezier_indices)] {
+				for triangle in indices.chunks_exact(3) {
+					let Some(vertices) = triangle.iter().map(|index| data.vertices.get(*index as usize)).collect::<Option<Vec<_>>>() else {
+						continue;
+					};
+					let points: [(f32, f32); 3] = std::array::from_fn(|corner| (vertices[corner].x.value as f32, vertices[corner].y.value as f32));
+					if cross(points[0], points[1], points[2]) <= 0.0 {
+						continue;
+					}
+					triangles.push(Triangle {
+						points: points.map(|(x, y)| transform.apply(x, y)),
+						uv_coords: std::array::from_fn(|corner| vertices[corner].uv_coords),
+						mode,
+					});
+				}
+			}
+		},
+		GlyphData::CompositeGlyph(data) if depth < MAX_COMPONENT_DEPTH => {
+			for child in data.children.iter() {
+				if let Some(child_glyph) = glyph(child.child_index) {
+					let child_transform = transform.multiply(&Affine::translate(child.offset.x.value as f32, child.offset.y.value as f32).multiply(&child.transform));
+					glyph_triangles(glyph, &child_glyph, &child_transform, depth + 1, triangles);
+				}
+			}
+		},
+		_ => {},
+	}
+}
+
+/// Twice the signed area of a triangle, positive when its corners go anticlockwise.
+fn cross(first: (f32, f32), second: (f32, f32), third: (f32, f32)) -> f32 {
+	(second.0 - first.0) * (third.1 - first.1) - (second.1 - first.1) * (third.0 - first.0)
+}
+
+/// Which samples of each pixel the triangles cover, a bit for each sample.
+fn coverage(triangles: &[Triangle], grid: &Grid) -> Vec<u16> {
+	let mut masks = vec![0u16; grid.width * grid.height];
+	for triangle in triangles {
+		let [first, second, third] = triangle.points;
+		let area = cross(first, second, third);
+		if area == 0.0 {
+			continue;
+		}
+		let to_column = |x: f32| ((x - grid.origin.0) / grid.font_units_per_pixel).floor();
+		let to_row = |y: f32| ((grid.origin.1 - y) / grid.font_units_per_pixel).floor();
+		let (x_min, x_max) = (first.0.min(second.0).min(third.0), first.0.max(second.0).max(third.0));
+		let (y_min, y_max) = (first.1.min(second.1).min(third.1), first.1.max(second.1).max(third.1));
+		let columns = to_column(x_min).max(0.0) as usize..(to_column(x_max) + 1.0).clamp(0.0, grid.width as f32) as usize;
+		let rows = to_row(y_max).max(0.0) as usize..(to_row(y_min) + 1.0).clamp(0.0, grid.height as f32) as usize;
+		for y in rows {
+			for x in columns.clone() {
+				for sample in 0..SAMPLES_PER_AXIS * SAMPLES_PER_AXIS {
+					let point = grid.sample(x, y, sample);
+					// Barycentric weights of each corner, which are all positive inside the triangle whichever way round it goes.
+					let weights = [cross(point, second, third) / area, cross(first, point, third) / area, cross(first, second, point) / area];
+					if weights.iter().any(|weight| *weight < 0.0) {
+						continue;
+					}
+					let uv: [f32; 3] = std::array::from_fn(|axis| (0..3).map(|corner| weights[corner] * triangle.uv_coords[corner][axis]).sum());
+					let filled = match triangle.mode {
+						1 => uv[0] * uv[0] <= uv[1],
+						2 => uv[0] * uv[0] >= uv[1],
+						3 => uv[0] * uv[0] * uv[0] - uv[1] * uv[2] <= 0.0,
+						_ => true,
+					};
+					if filled {
+						masks[y * grid.width + x] |= 1 << sample;
+					}
+				}
+			}
+		}
+	}
+	masks
+}
+
+fn source_over(source: [f32; 4], backdrop: [f32; 4]) -> [f32; 4] {
+	std::array::from_fn(|channel| source[channel] + backdrop[channel] * (1.0 - source[3]))
+}
+
+/// Combines premultiplied colours, with the Porter-Duff operators and the blend modes of the W3C compositing and blending spec.
+fn composite(mode: CompositeMode, source: [f32; 4], backdrop: [f32; 4]) -> [f32; 4] {
+	let (source_alpha, backdrop_alpha) = (source[3], backdrop[3]);
+	let porter_duff = |source_factor: f32, backdrop_factor: f32| std::array::from_fn(|channel| source[channel] * source_factor + backdrop[channel] * backdrop_factor);
+	match mode {
+		CompositeMode::Clear => [0.0; 4],
+		CompositeMode::Source => source,
+		CompositeMode::Destination => backdrop,
+		CompositeMode::SourceOver => porter_duff(1.0, 1.0 - source_alpha),
+		CompositeMode::DestinationOver => porter_duff(1.0 - backdrop_alpha, 1.0),
+		CompositeMode::SourceIn => porter_duff(backdrop_alpha, 0.0),
+		CompositeMode::DestinationIn => porter_duff(0.0, source_alpha),
+		CompositeMode::SourceOut => porter_duff(1.0 - backdrop_alpha, 0.0),
+		CompositeMode::DestinationOut => porter_duff(0.0, 1.0 - source_alpha),
+		CompositeMode::SourceAtop => porter_duff(backdrop_alpha, 1.0 - source_alpha),
+		CompositeMode::DestinationAtop => porter_duff(1.0 - backdrop_alpha, source_alpha),
+		CompositeMode::Xor => porter_duff(1.0 - backdrop_alpha, 1.0 - source_alpha),
+		CompositeMode::Plus => std::array::from_fn(|channel| (source[channel] + backdrop[channel]).min(1.0)),
+		_ => blend(mode, source, backdrop),
+	}
+}
+
+/// Blends where both colours are, and keeps each as it is where only it is.
+fn blend(mode: CompositeMode, source: [f32; 4], backdrop: [f32; 4]) -> [f32; 4] {
+	let unpremultiply = |colour: [f32; 4]| if colour[3] > 0.0 { [colour[0] / colour[3], colour[1] / colour[3], colour[2] / colour[3]] } else { [0.0; 3] };
+	let (source_colour, backdrop_colour) = (unpremultiply(source), unpremultiply(backdrop));
+	let blended = match mode {
+		CompositeMode::Hue => set_luminosity(set_saturation(source_colour, saturation(backdrop_colour)), luminosity(backdrop_colour)),
+		CompositeMode::Saturation => set_luminosity(set_saturation(backdrop_colour, saturation(source_colour)), luminosity(backdrop_colour)),
+		CompositeMode::Colour => set_luminosity(source_colour, luminosity(backdrop_colour)),
+		CompositeMode::Luminosity => set_luminosity(backdrop_colour, luminosity(source_colour)),
+		_ => std::array::from_fn(|channel| blend_channel(mode, source_colour[channel], backdrop_colour[channel])),
+	};
+	let (source_alpha, backdrop_alpha) = (source[3], backdrop[3]);
+	let mut result: [f32; 4] = std::array::from_fn(|channel| source[channel] * (1.0 - backdrop_alpha) + backdrop[channel] * (1.0 - source_alpha));
+	for channel in 0..3 {
+		result[channel] += source_alpha * backdrop_alpha * blended[channel];
+	}
+	result[3] = source_alpha + backdrop_alpha - source_alpha * backdrop_alpha;
+	result
+}
+
+fn blend_channel(mode: CompositeMode, source: f32, backdrop: f32) -> f32 {
+	let hard_light = |source: f32, backdrop: f32| if source <= 0.5 {
+		backdrop * 2.0 * source
+	} else {
+		let source = 2.0 * source - 1.0;
+		backdrop + source - backdrop * source
+	};
+	match mode {
+		CompositeMode::Multiply => source * backdrop,
+		CompositeMode::Screen => source + backdrop - source * backdrop,
+		CompositeMode::Overlay => hard_light(backdrop, source),
+		CompositeMode::Darken => source.min(backdrop),
+		CompositeMode::Lighten => source.max(backdrop),
+		CompositeMode::ColourDodge => if backdrop <= 0.0 { 0.0 } else if source >= 1.0 { 1.0 } else { (backdrop / (1.0 - source)).min(1.0) },
+		CompositeMode::ColourBurn => if backdrop >= 1.0 { 1.0 } else if source <= 0.0 { 0.0 } else { 1.0 - ((1.0 - backdrop) / source).min(1.0) },
+		CompositeMode::HardLight => hard_light(source, backdrop),
+		CompositeMode::SoftLight => if source <= 0.5 {
+			backdrop - (1.0 - 2.0 * source) * backdrop * (1.0 - backdrop)
+		} else {
+			let darkened = if backdrop <= 0.25 { ((16.0 * backdrop - 12.0) * backdrop + 4.0) * backdrop } else { backdrop.sqrt() };
+			backdrop + (2.0 * source - 1.0) * (darkened - backdrop)
+		},
+		CompositeMode::Difference => (source - backdrop).abs(),
+		CompositeMode::Exclusion => source + backdrop - 2.0 * source * backdrop,
+		_ => source,
+	}
+}
+
+fn luminosity(colour: [f32; 3]) -> f32 {
+	0.3 * colour[0] + 0.59 * colour[1] + 0.11 * colour[2]
+}
+
+fn set_luminosity(colour: [f32; 3], target: f32) -> [f32; 3] {
+	let difference = target - luminosity(colour);
+	let colour = colour.map(|channel| channel + difference);
+	// Brings channels pushed out of range back in, keeping the luminosity.
+	let luminosity = luminosity(colour);
+	let (minimum, maximum) = (colour[0].min(colour[1]).min(colour[2]), colour[0].max(colour[1]).max(colour[2]));
+	colour.map(|channel| {
+		let mut channel = channel;
+		if minimum < 0.0 {
+			channel = luminosity + (channel - luminosity) * luminosity / (luminosity - minimum);
+		}
+		if maximum > 1.0 {
+			channel = luminosity + (channel - luminosity) * (1.0 - luminosity) / (maximum - luminosity);
+		}
+		channel
+	})
+}
+
+fn saturation(colour: [f32; 3]) -> f32 {
+	colour[0].max(colour[1]).max(colour[2]) - colour[0].min(colour[1]).min(colour[2])
+}
+
+fn set_saturation(colour: [f32; 3], target: f32) -> [f32; 3] {
+	let (minimum, maximum) = (colour[0].min(colour[1]).min(colour[2]), colour[0].max(colour[1]).max(colour[2]));
+	if maximum <= minimum {
+		return [0.0; 3];
+	}
+	colour.map(|channel| (channel - minimum) * target / (maximum - minimum))
+}
@@ -1,19 +1,23 @@
 use mircalla_types::{units::{Pixels}, vectors::{Alignment, Alignments, Colour, Position, Size}};
 use wgpu::util::DeviceExt;
 use winit::window::Window;
-use std::{sync::{Arc, Mutex}};
+use std::{collections::HashMap, ops::Range, sync::{Arc, Mutex}};
 
-use crate::font::{ToPixelsSize};
+use crate::colour_reader::{Affine, Brush, ClipBox, Extend, PaintCommand};
+use crate::font::{self, ToPixelsSize};
+use crate::png;
 
-use super::{Font, FontUnits};
+use super::{Font, FontUnits, GlyphData, PositionedGlyph, Vertex};
 
 
 #[repr(C)]
 #[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct VertexRaw {
 	pub position: [f32; 2],
-	pub uv_coords: [f32; 2],
+	pub uv_coords: [f32; 3],
 	pub colour: [f32; 3],
+	pub paint_coords: [f32; 2], // Where the vertex is in its paint's coordinates
+	pub paint_index: u32, // Into the paint buffer, with 0 for the vertex colour
 }
 
 impl VertexRaw {
@@ -30,26 +34,107 @@ impl VertexRaw {
 				wgpu::VertexAttribute {
 					offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
 					shader_location: 1,
-					format: wgpu::VertexFormat::Float32x2,
+					format: wgpu::VertexFormat::Float32x3,
 				},
 				wgpu::VertexAttribute {
-					offset: std::mem::size_of::<[f32; 4]>() as wgpu::BufferAddress,
+					offset: std::mem::size_of::<[f32; 5]>() as wgpu::BufferAddress,
 					shader_location: 2,
 					format: wgpu::VertexFormat::Float32x3,
 				},
+				wgpu::VertexAttribute {
+					offset: std::mem::size_of::<[f32; 8]>() as wgpu::BufferAddress,
+					shader_location: 3,
+					format: wgpu::VertexFormat::Float32x2,
+				},
+				wgpu::VertexAttribute {
+					offset: std::mem::size_of::<[f32; 10]>() as wgpu::BufferAddress,
+					shader_location: 4,
+					format: wgpu::VertexFormat::Uint32,
+				},
 			]
 		}
 	}
 }
 
+/// A corner of a bitmap glyph's quad, with where it is in the bitmap atlas.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct TexturedVertexRaw {
+	pub position: [f32; 2],
+	pub texture_coords: [f32; 2], // In texels, which the shader divides by the size of the atlas
+}
+
+impl TexturedVertexRaw {
+	pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+		wgpu::VertexBufferLayout {
+			array_stride: std::mem::size_of::<TexturedVertexRaw>() as wgpu::BufferAddress,
+			step_mode: wgpu::VertexStepMode::Vertex,
+			attributes: &[
+				wgpu::VertexAttribute {
+					offset: 0,
+					shader_location: 0,
+					format: wgpu::VertexFormat::Float32x2,
+				},
+				wgpu::VertexAttribute {
+					offset: std::mem::size_of::<[f32; 2]>() as wgpu::BufferAddress,
+					shader_location: 1,
+					format: wgpu::VertexFormat::Float32x2,
+				},
+			]
+		}
+	}
+}
+
+/// How a glyph's vertices are filled: with their colour, or with one of the paints in the paint buffer, which they carry coordinates into.
+#[derive(Clone, Copy)]
+pub struct VertexPaint {
+	pub colour: Colour,
+	pub index: u32, // Into the paint buffer, with 0 for the colour
+	pub transform: Affine, // From the glyph's font units to the paint's coordinates
+}
+
+impl VertexPaint {
+	pub fn solid(colour: Colour) -> VertexPaint {
+		VertexPaint { colour, index: 0, transform: Affine::IDENTITY }
+	}
+}
+
+/// Where a glyph's vertices are put: how many pixels a font unit covers, the screen they're drawn on, the pen position on it, and the box they're cut off at.
+#[derive(Clone, Copy)]
+pub struct VertexTarget {
+	pub pixels_per_font_unit: f32,
+	pub screen_size: Size<Pixels<i32>>,
+	pub position: Position<Pixels<f32>>,
+	pub bounds: (Position<Pixels<i32>>, Position<Pixels<i32>>),
+}
+
+/// A colour glyph's brush as the shader reads it.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct PaintRaw {
+	kind: u32, // 0 for solid colours, then 1, 2 and 3 for linear, radial and sweep gradients
+	extend: u32, // 0 to pad, 1 to repeat and 2 to reflect
+	first_stop: u32, // Into the gradient stop buffer, where solid colours keep their colour too
+	stop_count: u32,
+	geometry: [f32; 8], // Start and end points, the two centres then the two radii, or the centre then the start and end angles
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GradientStopRaw {
+	colour: [f32; 4], // Premultiplied
+	offset: f32,
+	padding: [f32; 3],
+}
+
 pub trait ToRawTriangles {
-	fn to_raw(&self, font: &Font, pixels_per_font_unit: f32, screen_size: Size<Pixels<f32>>, position: Position<Pixels<f32>>, vertices_start: usize, colour: Colour) -> (Vec<VertexRaw>, Vec<u32>, Vec<u32>, Vec<u32>);
+	fn to_raw(&self, font: &Font, pixels_per_font_unit: f32, screen_size: Size<Pixels<f32>>, position: Position<Pixels<f32>>, vertices_start: usize, colour: Colour) -> (Vec<VertexRaw>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>);
 }
 
 /* 
 impl ToRawTriangles for char {
 	fn to_raw(&self, font: &Font, pixels_per_font_unit: f32, offset_x: i32, offset_y: i32, screen_width: f32, screen_height: f32, vertices_start: usize) -> (Vec<VertexRaw>, Vec<u32>, Vec<u32>, Vec<u32>) {
-		let character_glyph_id = font.mappings[0].get_glyph_id(self.clone() as u64);
+		let character_glyph_id = font.get_glyph_id(self.clone());
 		match character_glyph_id {
 			Some(glyph_index) => GlyphIndex(glyph_index).to_raw(font, pixels_per_font_unit, offset_x, offset_y, screen_width, screen_height, vertices_start),
 			None => GlyphIndex(0).to_raw(font, pixels_per_font_unit, offset_x, offset_y, screen_width, screen_height, vertices_start),
@@ -76,7 +161,7 @@ impl ToRawTriangles for Mutex<String> {
 		let mut concave_bezier_indices: Vec<u32> = Vec::new();
 		let string = self.lock().unwrap();
 		for character in string.chars() {
-			let character_glyph_id = font.mappings[0].get_glyph_id(character as u64).unwrap_or(0);
+			let character_glyph_id = font.get_glyph_id(character).unwrap_or(0);
 			let glyph = &font.glyphs[character_glyph_id as usize];
 			let (mut vertices_raw_character, mut indices_character, mut convex_bezier_indices_character, mut concave_bezier_indices_character) = glyph.to_raw(font, pixels_per_font_unit, (advance_offset, 0.into()).into(), screen_size, position, vertices_raw.len() + vertices_start, colour);
 			advance_offset += glyph.advance_width;
@@ -90,15 +175,6 @@ impl ToRawTriangles for Mutex<String> {
 	}
 } */
 
-fn to_linear_rgb(color_chanel: u8) -> f32 {
-	let value = color_chanel as f32 / 255.0;
-	if value > 0.04045 {
-		((value + 0.055) / 1.055).powf(2.4)
-	} else {
-		value / 12.92
-	}
-}
-
 #[derive(Clone, Debug)]
 pub enum NewRendererStateError {
 	RequestAdapterError(wgpu::RequestAdapterError),
@@ -125,17 +201,23 @@ pub struct FontRenderer {
 	index_buffer: wgpu::Buffer,
 	pub window: Arc<Window>,
 	number_of_indices: usize,
-	convex_bezier_indices_start: usize,
-	concave_bezier_indices_start: usize,
+	draws: Vec<(u32, Range<u32>)>, // Shader mode and the indices drawn with it, in the order they're drawn
 	mode_bind_group_layout: wgpu::BindGroupLayout,
+	paint_bind_group_layout: wgpu::BindGroupLayout,
+	paint_bind_group: wgpu::BindGroup, // The paints and gradient stops of colour glyphs
+	bitmap_pipeline: wgpu::RenderPipeline,
+	bitmap_vertex_buffer: wgpu::Buffer,
+	bitmap_index_buffer: wgpu::Buffer,
+	number_of_bitmap_indices: usize,
+	bitmap_bind_group_layout: wgpu::BindGroupLayout,
+	bitmap_sampler: wgpu::Sampler,
+	bitmap_bind_group: Option<wgpu::BindGroup>, // The atlas of bitmap glyphs, once there are any
+	bitmap_atlas_upload: Mutex<Option<(wgpu::Texture, Vec<[u8; 4]>)>>, // Made in `update`, which has no queue, so written in `draw_text`
 	pub text_boxes: Vec<TextBox>,
 }
 
 impl FontRenderer {
 	pub async fn new(window: Arc<Window>, device: Arc<wgpu::Device>, config: &wgpu::SurfaceConfiguration) -> Result<Self, NewRendererStateError> {
-		let convex_bezier_indices_start = 0;
-		let concave_bezier_indices_start = 0;
-
 		let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
 			label: Some("Tapestry Shader"),
 			source: wgpu::ShaderSource::Wgsl(include_str!("../triangle_shader.wgsl").into()),
@@ -183,10 +265,30 @@ impl FontRenderer {
 			],
 		});
 
+		let paint_buffer_entry = |binding: u32| wgpu::BindGroupLayoutEntry {
+			binding,
+			visibility: wgpu::ShaderStages::FRAGMENT,
+			ty: wgpu::BindingType::Buffer {
+				ty: wgpu::BufferBindingType::Storage { read_only: true },
+				has_dynamic_offset: false,
+				min_binding_size: None,
+			},
+			count: None,
+		};
+		let paint_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			label: Some("paint_bind_group_layout"),
+			entries: &[
+				paint_buffer_entry(0),
+				paint_buffer_entry(1),
+			],
+		});
+		let paint_bind_group = create_paint_bind_group(&device, &paint_bind_group_layout, &TextMesh::new(MAXIMUM_ATLAS_HEIGHT));
+
 		let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
 			label: Some("Render Pipeline Layout"),
 			bind_group_layouts: &[
-				&mode_bind_group_layout
+				&mode_bind_group_layout,
+				&paint_bind_group_layout,
 			],
 			push_constant_ranges: &[],
 		});
@@ -232,6 +334,103 @@ impl FontRenderer {
 			cache: None,
 		});
 
+		// Bitmap glyphs are quads textured from an atlas, which are never mirrored, so aren't culled.
+		let bitmap_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+			label: Some("Tapestry Bitmap Shader"),
+			source: wgpu::ShaderSource::Wgsl(include_str!("../bitmap_shader.wgsl").into()),
+		});
+
+		let bitmap_bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+			label: Some("bitmap_bind_group_layout"),
+			entries: &[
+				wgpu::BindGroupLayoutEntry {
+					binding: 0,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Texture {
+						sample_type: wgpu::TextureSampleType::Float { filterable: true },
+						view_dimension: wgpu::TextureViewDimension::D2,
+						multisampled: false,
+					},
+					count: None,
+				},
+				wgpu::BindGroupLayoutEntry {
+					binding: 1,
+					visibility: wgpu::ShaderStages::FRAGMENT,
+					ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+					count: None,
+				},
+			],
+		});
+
+		let bitmap_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+			label: Some("Bitmap Sampler"),
+			mag_filter: wgpu::FilterMode::Linear,
+			min_filter: wgpu::FilterMode::Linear,
+			..Default::default()
+		});
+
+		let bitmap_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+			label: Some("Bitmap Pipeline Layout"),
+			bind_group_layouts: &[
+				&bitmap_bind_group_layout,
+			],
+			push_constant_ranges: &[],
+		});
+
+		let bitmap_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+			label: Some("Bitmap Pipeline"),
+			layout: Some(&bitmap_pipeline_layout),
+			vertex: wgpu::VertexState {
+				module: &bitmap_shader,
+				entry_point: Some("vs_main"),
+				buffers: &[
+					TexturedVertexRaw::desc(),
+				],
+				compilation_options: wgpu::PipelineCompilationOptions::default(),
+			},
+			fragment: Some(wgpu::FragmentState {
+				module: &bitmap_shader,
+				entry_point: Some("fs_main"),
+				targets: &[Some(wgpu::ColorTargetState {
+					format: config.format,
+					blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+					write_mask: wgpu::ColorWrites::ALL,
+				})],
+				compilation_options: wgpu::PipelineCompilationOptions::default(),
+			}),
+			primitive: wgpu::PrimitiveState {
+				topology: wgpu::PrimitiveTopology::TriangleList,
+				strip_index_format: None,
+				front_face: wgpu::FrontFace::Ccw,
+				cull_mode: None,
+				polygon_mode: wgpu::PolygonMode::Fill,
+				unclipped_depth: false,
+				conservative: false,
+			},
+			depth_stencil: None,
+			multisample: wgpu::MultisampleState {
+				count: 1,
+				mask: !0,
+				alpha_to_coverage_enabled: false,
+			},
+			multiview: None,
+			cache: None,
+		});
+
+		let bitmap_vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Bitmap Vertex Buffer"),
+			size: 0,
+			usage: wgpu::BufferUsages::VERTEX,
+			mapped_at_creation: false,
+		});
+
+		let bitmap_index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+			label: Some("Bitmap Index Buffer"),
+			size: 0,
+			usage: wgpu::BufferUsages::INDEX,
+			mapped_at_creation: false,
+		});
+
 		let text_boxes: Vec<TextBox> = Vec::new();
 
 		Ok(Self {
@@ -242,9 +441,18 @@ impl FontRenderer {
 			index_buffer,
 			window,
 			number_of_indices: 0,
-			convex_bezier_indices_start,
-			concave_bezier_indices_start,
+			draws: Vec::new(),
 			mode_bind_group_layout,
+			paint_bind_group_layout,
+			paint_bind_group,
+			bitmap_pipeline,
+			bitmap_vertex_buffer,
+			bitmap_index_buffer,
+			number_of_bitmap_indices: 0,
+			bitmap_bind_group_layout,
+			bitmap_sampler,
+			bitmap_bind_group: None,
+			bitmap_atlas_upload: Mutex::new(None),
 			text_boxes,
 		})
 	}
@@ -258,23 +466,26 @@ impl FontRenderer {
 	pub fn update(&mut self) {
 		let size = self.window.inner_size().to_pixels_size();
 
-		let mut vertices: Vec<VertexRaw> = Vec::new();
-		let mut indices: Vec<u32> = Vec::new();
-		let mut convex_bezier_indices: Vec<u32> = Vec::new();
-		let mut concave_bezier_indices: Vec<u32> = Vec::new();
-
+		let mut mesh = TextMesh::new(MAXIMUM_ATLAS_HEIGHT.min(self.device.limits().max_texture_dimension_2d));
 		for text_box in self.text_boxes.iter() {
-			let (mut vertices_text_box, mut indices_text_box, mut convex_bezier_indices_text_box, mut concave_bezier_indices_text_box) = text_box.to_raw(size.into(), vertices.len());
-			vertices.append(&mut vertices_text_box);
-			indices.append(&mut indices_text_box);
-			convex_bezier_indices.append(&mut convex_bezier_indices_text_box);
-			concave_bezier_indices.append(&mut concave_bezier_indices_text_box);
+			text_box.to_raw(size.into(), &mut mesh);
 		}
+		self.paint_bind_group = create_paint_bind_group(&self.device, &self.paint_bind_group_layout, &mesh);
+		let vertices = mesh.vertices;
 
-		let convex_bezier_indices_start = indices.len();
-		indices.extend(convex_bezier_indices);
-		let concave_bezier_indices_start = indices.len();
-		indices.extend(concave_bezier_indices);
+		// Each layer is drawn in full before the next, so the layers of colour glyphs stack in order.
+		let mut indices: Vec<u32> = Vec::new();
+		let mut draws: Vec<(u32, Range<u32>)> = Vec::new();
+		for layer in mesh.layers {
+			for (mode, layer_indices) in [(0, layer.indices), (1, layer.convex_bezier_indices), (2, layer.concave_bezier_indices), (3, layer.cubic_bezier_indices)] {
+				if layer_indices.is_empty() {
+					continue;
+				}
+				let start = indices.len() as u32;
+				indices.extend(layer_indices);
+				draws.push((mode, start..indices.len() as u32));
+			}
+		}
 
 		let vertex_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
 			label: Some("Vertex Buffer"),
@@ -291,8 +502,51 @@ impl FontRenderer {
 		self.number_of_indices = indices.len();
 		self.vertex_buffer = vertex_buffer;
 		self.index_buffer = index_buffer;
-		self.convex_bezier_indices_start = convex_bezier_indices_start;
-		self.concave_bezier_indices_start = concave_bezier_indices_start;
+		self.draws = draws;
+
+		self.bitmap_vertex_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+			label: Some("Bitmap Vertex Buffer"),
+			contents: bytemuck::cast_slice(&mesh.bitmap_vertices),
+			usage: wgpu::BufferUsages::VERTEX,
+		});
+		self.bitmap_index_buffer = self.device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+			label: Some("Bitmap Index Buffer"),
+			contents: bytemuck::cast_slice(&mesh.bitmap_indices),
+			usage: wgpu::BufferUsages::INDEX,
+		});
+		self.number_of_bitmap_indices = mesh.bitmap_indices.len();
+		if mesh.bitmap_indices.is_empty() {
+			self.bitmap_bind_group = None;
+			*self.bitmap_atlas_upload.lock().unwrap() = None;
+			return;
+		}
+
+		let atlas_texture = self.device.create_texture(&wgpu::TextureDescriptor {
+			label: Some("Bitmap Atlas"),
+			size: wgpu::Extent3d { width: ATLAS_WIDTH, height: mesh.bitmap_atlas.height, depth_or_array_layers: 1 },
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: wgpu::TextureDimension::D2,
+			format: wgpu::TextureFormat::Rgba8UnormSrgb,
+			usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+			view_formats: &[],
+		});
+		let atlas_view = atlas_texture.create_view(&wgpu::TextureViewDescriptor::default());
+		self.bitmap_bind_group = Some(self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+			layout: &self.bitmap_bind_group_layout,
+			entries: &[
+				wgpu::BindGroupEntry {
+					binding: 0,
+					resource: wgpu::BindingResource::TextureView(&atlas_view),
+				},
+				wgpu::BindGroupEntry {
+					binding: 1,
+					resource: wgpu::BindingResource::Sampler(&self.bitmap_sampler),
+				},
+			],
+			label: Some("bitmap_bind_group"),
+		}));
+		*self.bitmap_atlas_upload.lock().unwrap() = Some((atlas_texture, mesh.bitmap_atlas.pixels));
 	}
 
 
@@ -457,10 +711,29 @@ impl FontRenderer {
 			..Default::default()
 		}); */
 
-		if self.number_of_indices == 0 {
+		if self.number_of_indices == 0 && self.number_of_bitmap_indices == 0 {
 			return
 		}
 
+		if let Some((atlas_texture, atlas_pixels)) = self.bitmap_atlas_upload.lock().unwrap().take() {
+			let size = atlas_texture.size();
+			queue.write_texture(
+				wgpu::TexelCopyTextureInfo {
+					texture: &atlas_texture,
+					mip_level: 0,
+					origin: wgpu::Origin3d::ZERO,
+					aspect: wgpu::TextureAspect::All,
+				},
+				bytemuck::cast_slice(&atlas_pixels),
+				wgpu::TexelCopyBufferLayout {
+					offset: 0,
+					bytes_per_row: Some(4 * size.width),
+					rows_per_image: Some(size.height),
+				},
+				size,
+			);
+		}
+
 		let size = self.window.inner_size();
 
 		let multisample_texture = self.device.create_texture(&wgpu::TextureDescriptor{
@@ -507,82 +780,46 @@ impl FontRenderer {
 				timestamp_writes: None,
 			});
 
-			render_pass.set_pipeline(&self.render_pipeline);
-			render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-			render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
-			let convex_bezier_indices_start = self.convex_bezier_indices_start;
-			let concave_bezier_indices_start = self.concave_bezier_indices_start;
-			let number_of_indices = self.number_of_indices;
-			if convex_bezier_indices_start - 0 > 0 {
-				let mode: u32 = 0;
-				let mode_buffer = self.device.create_buffer_init(
-					&wgpu::util::BufferInitDescriptor {
-						label: Some("Mode Buffer"),
-						contents: bytemuck::cast_slice(&[mode]),
-						usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-					}
-				);
-				let mode_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-					layout: &self.mode_bind_group_layout,
-					entries: &[
-						wgpu::BindGroupEntry {
-							binding: 0,
-							resource: mode_buffer.as_entire_binding(),
+			// Empty buffers can't be bound, which they are when all the text is bitmap glyphs.
+			if self.number_of_indices > 0 {
+				render_pass.set_pipeline(&self.render_pipeline);
+				render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+				render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+				// One bind group for each shader mode: solid triangles, convex and concave quadratic curves, and cubic curves.
+				let mode_bind_groups: Vec<wgpu::BindGroup> = (0..4u32).map(|mode| {
+					let mode_buffer = self.device.create_buffer_init(
+						&wgpu::util::BufferInitDescriptor {
+							label: Some("Mode Buffer"),
+							contents: bytemuck::cast_slice(&[mode]),
+							usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
 						}
-						],
-						label: Some("mode_bind_group"),
-					}
-				);
-				render_pass.set_bind_group(0, &mode_bind_group, &[]);
-				render_pass.draw_indexed(0..convex_bezier_indices_start as _, 0, 0..1 as _);
-			}
-
-			if concave_bezier_indices_start - convex_bezier_indices_start > 0 {
-				let mode: u32 = 1;
-				let mode_buffer = self.device.create_buffer_init(
-					&wgpu::util::BufferInitDescriptor {
-						label: Some("Camera Buffer"),
-						contents: bytemuck::cast_slice(&[mode]),
-						usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-					}
-				);
-				let mode_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-					layout: &self.mode_bind_group_layout,
-					entries: &[
-						wgpu::BindGroupEntry {
-							binding: 0,
-							resource: mode_buffer.as_entire_binding(),
+					);
+					self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+						layout: &self.mode_bind_group_layout,
+						entries: &[
+							wgpu::BindGroupEntry {
+								binding: 0,
+								resource: mode_buffer.as_entire_binding(),
+							}
+							],
+							label: Some("mode_bind_group"),
 						}
-						],
-						label: Some("mode_bind_group"),
-					}
-				);
-				render_pass.set_bind_group(0, &mode_bind_group, &[]);
-				render_pass.draw_indexed(convex_bezier_indices_start as _..concave_bezier_indices_start as _, 0, 0..1 as _);
+					)
+				}).collect();
+				render_pass.set_bind_group(1, &self.paint_bind_group, &[]);
+				for (mode, range) in self.draws.iter() {
+					render_pass.set_bind_group(0, &mode_bind_groups[*mode as usize], &[]);
+					render_pass.draw_indexed(range.clone(), 0, 0..1 as _);
+				}
 			}
 
-			if number_of_indices - concave_bezier_indices_start > 0 {
-				let mode: u32 = 2;
-				let mode_buffer = self.device.create_buffer_init(
-					&wgpu::util::BufferInitDescriptor {
-						label: Some("Camera Buffer"),
-						contents: bytemuck::cast_slice(&[mode]),
-						usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
-					}
-				);
-				let mode_bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
-					layout: &self.mode_bind_group_layout,
-					entries: &[
-						wgpu::BindGroupEntry {
-							binding: 0,
-							resource: mode_buffer.as_entire_binding(),
-						}
-						],
-						label: Some("mode_bind_group"),
-					}
-				);
-				render_pass.set_bind_group(0, &mode_bind_group, &[]);
-				render_pass.draw_indexed(concave_bezier_indices_start as _..number_of_indices as _, 0, 0..1 as _);
+			// Bitmap glyphs go over the outlines, as they're drawn with a pipeline of their own.
+			if let Some(bitmap_bind_group) = &self.bitmap_bind_group {
+				render_pass.set_pipeline(&self.bitmap_pipeline);
+				render_pass.set_bind_group(0, bitmap_bind_group, &[]);
+				render_pass.set_vertex_buffer(0, self.bitmap_vertex_buffer.slice(..));
+				render_pass.set_index_buffer(self.bitmap_index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+				render_pass.draw_indexed(0..self.number_of_bitmap_indices as u32, 0, 0..1);
 			}
 		}
 
@@ -601,6 +838,12 @@ pub enum WrapOn {
 	Whitespace,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WritingMode {
+	Horizontal,
+	Vertical, // Top to bottom, in columns from right to left, as Chinese, Japanese and Korean can be written
+}
+
 #[derive(Clone)]
 pub struct TextBox {
 	pub font: Arc<Font>,
@@ -612,19 +855,22 @@ pub struct TextBox {
 	pub colour: Colour,
 	pub wrap_options: WrapOptions,
 	pub alignment: Alignment,
+	pub script: [u8; 4], // OpenType script tag used to pick the font's substitution and positioning features
+	pub features: Vec<[u8; 4]>,
+	pub writing_mode: WritingMode,
+	pub palette: usize, // Which of the font's colour palettes colour glyphs are drawn with
 }
 
 impl TextBox {
 	pub fn get_ideal_width(&self) -> Pixels<i32> { // Change when type are unified.
-		let mut width: FontUnits<u32> = 0.into();
+		if self.writing_mode == WritingMode::Vertical {
+			let (_, text_size) = self.layout_columns(self.text_box_size.height);
+			return (text_size.width.to_pixels_em(self.pixels_per_em, self.font.units_per_em).value.ceil() as i32).into();
+		}
+		let mut width: FontUnits<i32> = 0.into();
 		let text_lock = self.text.lock().unwrap();
 		for line in text_lock.lines() {
-			let mut line_width: FontUnits<u32> = 0.into();
-			for character in line.chars() {
-				let character_glyph_id = self.font.mappings[0].get_glyph_id(character as u64).unwrap_or(0);
-				let glyph = &self.font.get_glyph(character_glyph_id as usize);
-				line_width += glyph.advance_width;
-			}
+			let line_width = get_advance_width(&self.font.layout_glyphs(line, self.script, &self.features));
 			if line_width > width {
 				width = line_width;
 			}
@@ -642,7 +888,11 @@ impl TextBox {
 		self.pixels_per_em.value / self.font.units_per_em.value as f32
 	}
 
+	/// In vertical mode, columns wrap at the height of the box instead, and `width` isn't used.
 	pub fn get_height(&self, width: Pixels<i32>) -> Pixels<i32> {
+		if self.writing_mode == WritingMode::Vertical {
+			return self.get_text_size(width).height;
+		}
 		let mut advance_offset: FontUnits<i32> = 0.into();
 		let mut vertical_offset: FontUnits<i32> = 0.into();
 		let string = self.text.lock().unwrap();
@@ -656,33 +906,28 @@ impl TextBox {
 			}
 			match self.wrap_options.wrap_on {
 				WrapOn::Character => {
-					for character in line.chars() {
-						let character_glyph_id = self.font.mappings[0].get_glyph_id(character as u64).unwrap_or(0);
-						let glyph = &self.font.get_glyph(character_glyph_id as usize);
-
-						let future_advance_offset = (advance_offset + glyph.advance_width).to_pixels(self.get_pixels_per_font_unit());
+					for glyph in self.font.layout_glyphs(line, self.script, &self.features) {
+						let future_advance_offset = (advance_offset + glyph.advance).to_pixels(self.get_pixels_per_font_unit());
 						if future_advance_offset > width.into() {
 
 							advance_offset = 0.into();
 							vertical_offset += self.font.line_spacing;
 						}
 
-						advance_offset += glyph.advance_width;
+						advance_offset += glyph.advance;
 					}
 				},
 				WrapOn::Whitespace => {
-					let space_glyph_id = self.font.mappings[0].get_glyph_id(' ' as u64).unwrap_or(0);
-					let space_advance_width =  self.font.get_glyph(space_glyph_id as usize).advance_width;
+					let space_advance_width = get_advance_width(&self.font.layout_glyphs(" ", self.script, &self.features));
 					let mut add_space = false;
 					let mut first_word = true;
 					for word in line.split_whitespace() {
-						let mut word_advance_width: FontUnits<i32> = if add_space {space_advance_width.into()} else {0.into()};
-						for character in word.chars() {
-							let character_glyph_id = self.font.mappings[0].get_glyph_id(character as u64).unwrap_or(0);
-							let glyph = &self.font.get_glyph(character_glyph_id as usize);
-
-							word_advance_width += glyph.advance_width;
-						}
+						let word_glyphs = self.font.layout_glyphs(word, self.script, &self.features);
+						let word_advance_width: FontUnits<i32> = if add_space {
+							get_advance_width(&word_glyphs) + space_advance_width
+						} else {
+							get_advance_width(&word_glyphs)
+						};
 
 						let future_advance_offset = (advance_offset + word_advance_width).to_pixels(self.get_pixels_per_font_unit());
 						if future_advance_offset > width.into() {
@@ -694,18 +939,10 @@ impl TextBox {
 						}
 
 						if add_space {
-							let character_glyph_id = self.font.mappings[0].get_glyph_id(' ' as u64).unwrap_or(0);
-							let glyph = &self.font.get_glyph(character_glyph_id as usize);
-
-							advance_offset += glyph.advance_width;
+							advance_offset += space_advance_width;
 						}
 
-						for character in word.chars() {
-							let character_glyph_id = self.font.mappings[0].get_glyph_id(character as u64).unwrap_or(0);
-							let glyph = &self.font.get_glyph(character_glyph_id as usize);
-
-							advance_offset += glyph.advance_width;
-						}
+						advance_offset += get_advance_width(&word_glyphs);
 
 						first_word = false;
 
@@ -720,7 +957,12 @@ impl TextBox {
 		((vertical_offset + self.font.typographic_ascender + self.font.typographic_descender).to_pixels_em(self.pixels_per_em, self.font.units_per_em).value.ceil() as i32).into()
 	}
 
+	/// In vertical mode, columns wrap at the height of the box instead, and `width` isn't used.
 	pub fn get_text_size(&self, width: Pixels<i32>) -> Size<Pixels<i32>> {
+		if self.writing_mode == WritingMode::Vertical {
+			let (_, text_size) = self.layout_columns(self.text_box_size.height);
+			return (text_size.width.to_pixels_em(self.pixels_per_em, self.font.units_per_em).value.ceil() as i32, text_size.height.to_pixels_em(self.pixels_per_em, self.font.units_per_em).value.ceil() as i32).into();
+		}
 		let mut advance_offset: FontUnits<i32> = 0.into();
 		let mut vertical_offset: FontUnits<i32> = 0.into();
 		let mut max_advance_offset: FontUnits<i32> = 0.into();
@@ -735,36 +977,31 @@ impl TextBox {
 			}
 			match self.wrap_options.wrap_on {
 				WrapOn::Character => {
-					for character in line.chars() {
-						let character_glyph_id = self.font.mappings[0].get_glyph_id(character as u64).unwrap_or(0);
-						let glyph = &self.font.get_glyph(character_glyph_id as usize);
-
-						let future_advance_offset = (advance_offset + glyph.advance_width).to_pixels(self.get_pixels_per_font_unit());
+					for glyph in self.font.layout_glyphs(line, self.script, &self.features) {
+						let future_advance_offset = (advance_offset + glyph.advance).to_pixels(self.get_pixels_per_font_unit());
 						if future_advance_offset > width.into() {
 
 							advance_offset = 0.into();
 							vertical_offset += self.font.line_spacing;
 						}
 
-						advance_offset += glyph.advance_width;
+						advance_offset += glyph.advance;
 						if advance_offset > max_advance_offset {
 							max_advance_offset = advance_offset;
 						}
 					}
 				},
 				WrapOn::Whitespace => {
-					let space_glyph_id = self.font.mappings[0].get_glyph_id(' ' as u64).unwrap_or(0);
-					let space_advance_width =  self.font.get_glyph(space_glyph_id as usize).advance_width;
+					let space_advance_width = get_advance_width(&self.font.layout_glyphs(" ", self.script, &self.features));
 					let mut add_space = false;
 					let mut first_word = true;
 					for word in line.split_whitespace() {
-						let mut word_advance_width: FontUnits<i32> = if add_space {space_advance_width.into()} else {0.into()};
-						for character in word.chars() {
-							let character_glyph_id = self.font.mappings[0].get_glyph_id(character as u64).unwrap_or(0);
-							let glyph = &self.font.get_glyph(character_glyph_id as usize);
-
-							word_advance_width += glyph.advance_width;
-						}
+						let word_glyphs = self.font.layout_glyphs(word, self.script, &self.features);
+						let word_advance_width: FontUnits<i32> = if add_space {
+							get_advance_width(&word_glyphs) + space_advance_width
+						} else {
+							get_advance_width(&word_glyphs)
+						};
 
 						let future_advance_offset = (advance_offset + word_advance_width).to_pixels(self.get_pixels_per_font_unit());
 						if future_advance_offset > width.into() {
@@ -776,17 +1013,11 @@ impl TextBox {
 						}
 
 						if add_space {
-							let character_glyph_id = self.font.mappings[0].get_glyph_id(' ' as u64).unwrap_or(0);
-							let glyph = &self.font.get_glyph(character_glyph_id as usize);
-
-							advance_offset += glyph.advance_width;
+							advance_offset += space_advance_width;
 						}
 
-						for character in word.chars() {
-							let character_glyph_id = self.font.mappings[0].get_glyph_id(character as u64).unwrap_or(0);
-							let glyph = &self.font.get_glyph(character_glyph_id as usize);
-
-							advance_offset += glyph.advance_width;
+						for glyph in word_glyphs {
+							advance_offset += glyph.advance;
 							if advance_offset > max_advance_offset {
 								max_advance_offset = advance_offset;
 							}
@@ -804,16 +1035,353 @@ impl TextBox {
 
 		(max_advance_offset.to_pixels_em(self.pixels_per_em, self.font.units_per_em).value.ceil() as i32, (vertical_offset + self.font.typographic_ascender + self.font.typographic_descender).to_pixels_em(self.pixels_per_em, self.font.units_per_em).value.ceil() as i32).into()
 	}
+
+	/// Lays the text out in columns for vertical mode, starting a new column for each line and wherever the text would run past `height`.
+	/// Gives each glyph with where it's drawn, in font units from the top right corner of the text, along with the size of the text.
+	fn layout_columns(&self, height: Pixels<i32>) -> (Vec<(u16, Position<FontUnits<i32>>)>, Size<FontUnits<i32>>) {
+		let column_spacing = self.font.column_spacing().value as i32;
+		let space_glyph_id = self.font.get_glyph_id(' ');
+		let mut placed_glyphs: Vec<(u16, Position<FontUnits<i32>>)> = Vec::new();
+		let mut columns = 0;
+		let mut advance_offset: FontUnits<i32>;
+		let mut max_advance_offset: FontUnits<i32> = 0.into();
+		let string = self.text.lock().unwrap();
+
+		for line in string.lines() {
+			columns += 1;
+			advance_offset = 0.into();
+			let glyphs = self.font.layout_vertical_glyphs(line, self.script, &self.features);
+			// Glyphs that stay in one column together if they fit, either each glyph or each word with the space after it.
+			let runs: Vec<&[PositionedGlyph]> = match self.wrap_options.wrap_on {
+				WrapOn::Character => glyphs.chunks(1).collect(),
+				WrapOn::Whitespace => glyphs.split_inclusive(|glyph| Some(glyph.glyph_id) == space_glyph_id).collect(),
+			};
+
+			for run in runs {
+				let word = match run.split_last() {
+					Some((last, word)) if Some(last.glyph_id) == space_glyph_id => word,
+					_ => run,
+				};
+				let future_advance_offset = (advance_offset + get_advance_width(word)).to_pixels(self.get_pixels_per_font_unit());
+				if advance_offset.value > 0 && future_advance_offset > height.into() {
+					columns += 1;
+					advance_offset = 0.into();
+				}
+
+				let column_centre: FontUnits<i32> = (-(2 * columns - 1) * column_spacing / 2).into();
+				for (index, glyph) in run.iter().enumerate() {
+					placed_glyphs.push((glyph.glyph_id, (column_centre + glyph.offset.x, glyph.offset.y - advance_offset).into()));
+					advance_offset += glyph.advance;
+					// A trailing space doesn't make the column any longer.
+					if index < word.len() && advance_offset > max_advance_offset {
+						max_advance_offset = advance_offset;
+					}
+				}
+			}
+		}
+		drop(string);
+
+		(placed_glyphs, (FontUnits::from(columns * column_spacing), max_advance_offset).into())
+	}
+}
+
+/// The distance from the start of the first glyph to the start of whatever follows, including any positioning adjustments.
+fn get_advance_width(glyphs: &[PositionedGlyph]) -> FontUnits<i32> {
+	glyphs.iter().fold(0.into(), |advance_width: FontUnits<i32>, glyph| advance_width + glyph.advance)
+}
+
+/// The triangles of one layer of text, split by how the shader fills them.
+#[derive(Default)]
+struct LayerIndices {
+	indices: Vec<u32>,
+	convex_bezier_indices: Vec<u32>,
+	concave_bezier_indices: Vec<u32>,
+	cubic_bezier_indices: Vec<u32>,
+}
+
+impl LayerIndices {
+	fn extend(&mut self, other: LayerIndices) {
+		self.indices.extend(other.indices);
+		self.convex_bezier_indices.extend(other.convex_bezier_indices);
+		self.concave_bezier_indices.extend(other.concave_bezier_indices);
+		self.cubic_bezier_indices.extend(other.cubic_bezier_indices);
+	}
+}
+
+/// How wide the bitmap atlas is, in texels. It grows downwards as more images are packed into it.
+const ATLAS_WIDTH: u32 = 2048;
+
+/// How tall the bitmap atlas may grow, if the device can sample a texture that tall. Images that don't fit aren't drawn.
+const MAXIMUM_ATLAS_HEIGHT: u32 = 8192;
+
+/// The images of bitmap glyphs, packed in rows, or shelves, as tall as the tallest image on them.
+/// Each image has a transparent texel round it, so neighbouring images don't bleed into it when it's sampled.
+struct BitmapAtlas {
+	pixels: Vec<[u8; 4]>, // ATLAS_WIDTH texels a row
+	height: u32,
+	maximum_height: u32, // MAXIMUM_ATLAS_HEIGHT, or less if the device's textures can't be that tall
+	shelf: (u32, u32, u32), // Where the next image goes on the last shelf, and how tall that shelf is
+	placements: HashMap<(usize, usize), (u32, u32)>, // The top left of each image, by its glyph's address and the index of its strike
+}
+
+impl BitmapAtlas {
+	fn new(maximum_height: u32) -> BitmapAtlas {
+		BitmapAtlas { pixels: Vec::new(), height: 0, maximum_height, shelf: (0, 0, 0), placements: HashMap::new() }
+	}
+
+	/// Where an image is in the atlas, packing it in first if it isn't there yet.
+	fn place(&mut self, key: (usize, usize), image: &png::Image) -> Option<(u32, u32)> {
+		if let Some(&placement) = self.placements.get(&key) {
+			return Some(placement);
+		}
+		let (width, height) = (image.width as u32 + 2, image.height as u32 + 2);
+		if width > ATLAS_WIDTH {
+			return None;
+		}
+		let (mut x, mut y, mut shelf_height) = self.shelf;
+		if x + width > ATLAS_WIDTH {
+			(x, y, shelf_height) = (0, y + shelf_height, 0);
+		}
+		let bottom = y + height.max(shelf_height);
+		if bottom > self.maximum_height {
+			return None;
+		}
+		if bottom > self.height {
+			self.pixels.resize((ATLAS_WIDTH * bottom) as usize, [0; 4]);
+			self.height = bottom;
+		}
+		for (row_index, row) in image.pixels.chunks_exact(image.width.max(1)).enumerate() {
+			let start = ((y + 1 + row_index as u32) * ATLAS_WIDTH + x + 1) as usize;
+			self.pixels[start..start + row.len()].copy_from_slice(row);
+		}
+		self.shelf = (x + width, y, height.max(shelf_height));
+		let placement = (x + 1, y + 1);
+		self.placements.insert(key, placement);
+		Some(placement)
+	}
+}
+
+/// The vertices of every text box, with their triangles in layers, and the paints of any colour glyphs.
+/// Plain glyphs are all in the first layer, and colour glyphs put each of theirs in the next.
+/// Bitmap glyphs are quads of their own, textured from the atlas.
+struct TextMesh {
+	vertices: Vec<VertexRaw>,
+	layers: Vec<LayerIndices>,
+	paints: Vec<PaintRaw>,
+	gradient_stops: Vec<GradientStopRaw>,
+	bitmap_vertices: Vec<TexturedVertexRaw>,
+	bitmap_indices: Vec<u32>,
+	bitmap_atlas: BitmapAtlas,
+}
+
+impl TextMesh {
+	/// Paint 0 stands for the vertex colour, so the paint buffers start with a placeholder, which also keeps them from being empty.
+	fn new(maximum_atlas_height: u32) -> TextMesh {
+		TextMesh {
+			vertices: Vec::new(),
+			layers: Vec::new(),
+			paints: vec![PaintRaw { kind: 0, extend: 0, first_stop: 0, stop_count: 1, geometry: [0.0; 8] }],
+			gradient_stops: vec![GradientStopRaw { colour: [0.0; 4], offset: 0.0, padding: [0.0; 3] }],
+			bitmap_vertices: Vec::new(),
+			bitmap_indices: Vec::new(),
+			bitmap_atlas: BitmapAtlas::new(maximum_atlas_height),
+		}
+	}
+
+	fn add_layer(&mut self, layer_index: usize, layer: LayerIndices) {
+		match self.layers.get_mut(layer_index) {
+			Some(layer_indices) => layer_indices.extend(layer),
+			None => self.layers.push(layer),
+		}
+	}
+
+	/// Adds a brush to the paint buffers, giving the index vertices fill with it by.
+	fn add_paint(&mut self, brush: &Brush) -> u32 {
+		let (kind, geometry, gradient) = match brush {
+			Brush::Solid(colour) => {
+				let first_stop = self.gradient_stops.len() as u32;
+				self.gradient_stops.push(GradientStopRaw { colour: *colour, offset: 0.0, padding: [0.0; 3] });
+				self.paints.push(PaintRaw { kind: 0, extend: 0, first_stop, stop_count: 1, geometry: [0.0; 8] });
+				return self.paints.len() as u32 - 1;
+			},
+			Brush::LinearGradient { start, end, gradient } => (1, [start.0, start.1, end.0, end.1, 0.0, 0.0, 0.0, 0.0], gradient),
+			Brush::RadialGradient { start_centre, start_radius, end_centre, end_radius, gradient } => (2, [start_centre.0, start_centre.1, end_centre.0, end_centre.1, *start_radius, *end_radius, 0.0, 0.0], gradient),
+			Brush::SweepGradient { centre, start_angle, end_angle, gradient } => (3, [centre.0, centre.1, *start_angle, *end_angle, 0.0, 0.0, 0.0, 0.0], gradient),
+		};
+		let extend = match gradient.extend {
+			Extend::Pad => 0,
+			Extend::Repeat => 1,
+			Extend::Reflect => 2,
+		};
+		let first_stop = self.gradient_stops.len() as u32;
+		self.gradient_stops.extend(gradient.stops.iter().map(|stop| GradientStopRaw { colour: stop.colour, offset: stop.offset, padding: [0.0; 3] }));
+		self.paints.push(PaintRaw { kind, extend, first_stop, stop_count: gradient.stops.len() as u32, geometry });
+		self.paints.len() as u32 - 1
+	}
+}
+
+fn create_paint_bind_group(device: &wgpu::Device, paint_bind_group_layout: &wgpu::BindGroupLayout, mesh: &TextMesh) -> wgpu::BindGroup {
+	let paint_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+		label: Some("Paint Buffer"),
+		contents: bytemuck::cast_slice(&mesh.paints),
+		usage: wgpu::BufferUsages::STORAGE,
+	});
+	let gradient_stop_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+		label: Some("Gradient Stop Buffer"),
+		contents: bytemuck::cast_slice(&mesh.gradient_stops),
+		usage: wgpu::BufferUsages::STORAGE,
+	});
+	device.create_bind_group(&wgpu::BindGroupDescriptor {
+		layout: paint_bind_group_layout,
+		entries: &[
+			wgpu::BindGroupEntry {
+				binding: 0,
+				resource: paint_buffer.as_entire_binding(),
+			},
+			wgpu::BindGroupEntry {
+				binding: 1,
+				resource: gradient_stop_buffer.as_entire_binding(),
+			},
+		],
+		label: Some("paint_bind_group"),
+	})
 }
 
 impl TextBox {
-	fn to_raw(&self, screen_size: Size<Pixels<i32>>, vertices_start: usize) -> (Vec<VertexRaw>, Vec<u32>, Vec<u32>, Vec<u32>) {
+	/// Adds a glyph's triangles to the mesh, a layer at a time for colour glyphs.
+	fn append_glyph(&self, mesh: &mut TextMesh, glyph_id: u16, offset: Position<FontUnits<i32>>, position: Position<Pixels<f32>>, screen_size: Size<Pixels<i32>>) {
+		if let Some(colour_paint) = self.font.colour_paint(glyph_id, self.palette, self.colour) {
+			let target = VertexTarget { pixels_per_font_unit: self.get_pixels_per_font_unit(), screen_size, position, bounds: self.bounds };
+			self.append_paint_commands(mesh, &colour_paint.commands, colour_paint.clip_box.as_ref(), offset, &target, &mut 0);
+			return;
+		}
+		let Ok(glyph) = self.font.get_glyph(glyph_id as usize) else {
+			return;
+		};
+		if let GlyphData::Bitmap(bitmap_glyph) = &glyph.data {
+			if let Some((strike_index, bitmap)) = bitmap_glyph.nearest_strike(self.pixels_per_em.value) {
+				let key = (Arc::as_ptr(&glyph) as usize, strike_index);
+				self.append_bitmap(mesh, key, bitmap, offset, position, screen_size);
+			}
+			return;
+		}
+		// A grid-fitted outline only lines up with the pixels if the glyph's origin does too.
+		let (glyph, position) = match self.font.hinted_glyph(glyph_id, self.pixels_per_em.value) {
+			Some(hinted_glyph) => (hinted_glyph, self.pixel_aligned(offset, position)),
+			None => (glyph, position),
+		};
+		let target = VertexTarget { pixels_per_font_unit: self.get_pixels_per_font_unit(), screen_size, position, bounds: self.bounds };
+		for (layer_index, (layer_glyph, colour)) in self.font.glyph_layers(&glyph, self.palette, self.colour).into_iter().enumerate() {
+			let (vertices_raw, indices, convex_bezier_indices, concave_bezier_indices, cubic_bezier_indices) = layer_glyph.to_raw(&self.font, &target, offset, mesh.vertices.len(), colour);
+			mesh.vertices.extend(vertices_raw);
+			mesh.add_layer(layer_index, LayerIndices { indices, convex_bezier_indices, concave_bezier_indices, cubic_bezier_indices });
+		}
+	}
+
+	/// Adds the fills of a COLR version 1 glyph, each in a layer of its own so they stack in order.
+	/// The shader can't overlap outlines, so fills are only clipped to the innermost of their clip glyphs,
+	/// and composites draw their source over their backdrop whatever their mode. `colour_rasterizer` draws them exactly.
+	fn append_paint_commands(&self, mesh: &mut TextMesh, commands: &[PaintCommand], clip_box: Option<&ClipBox>, offset: Position<FontUnits<i32>>, target: &VertexTarget, layer_index: &mut usize) {
+		let clipped_target = match clip_box {
+			Some(clip_box) => VertexTarget { bounds: self.clip_bounds(clip_box, offset, target.position), ..*target },
+			None => *target,
+		};
+		let glyph_offset = Affine::translate(offset.x.value as f32, offset.y.value as f32);
+		for command in commands {
+			match command {
+				PaintCommand::Fill { clips, brush, brush_transform } => {
+					let Some(brush_inverse) = brush_transform.invert() else {
+						continue;
+					};
+					let paint_index = mesh.add_paint(brush);
+					let vertices_start = mesh.vertices.len();
+					let (vertices_raw, indices, convex_bezier_indices, concave_bezier_indices, cubic_bezier_indices) = match clips.last().map(|&(clip_glyph_id, clip_transform)| (self.font.get_glyph(clip_glyph_id as usize), clip_transform)) {
+						Some((Ok(clip_glyph), clip_transform)) => {
+							let paint = VertexPaint { colour: self.colour, index: paint_index, transform: brush_inverse.multiply(&clip_transform) };
+							clip_glyph.to_raw_transformed(&self.font, &clipped_target, &glyph_offset.multiply(&clip_transform), vertices_start, &paint)
+						},
+						Some(_) => continue,
+						None => {
+							// Fills without a clip glyph cover the whole clip box, and can't be drawn without one.
+							let Some(clip_box) = clip_box else {
+								continue;
+							};
+							let paint = VertexPaint { colour: self.colour, index: paint_index, transform: brush_inverse };
+							let corners = [(clip_box.x_min, clip_box.y_min), (clip_box.x_max, clip_box.y_min), (clip_box.x_max, clip_box.y_max), (clip_box.x_min, clip_box.y_max)];
+							let vertices_raw = corners.iter().map(|&(x, y)| Vertex::new(x as i16, y as i16).to_raw(&clipped_target, &glyph_offset, &paint)).collect();
+							let indices = [0, 1, 2, 0, 2, 3].iter().map(|index| index + vertices_start as u32).collect();
+							(vertices_raw, indices, Vec::new(), Vec::new(), Vec::new())
+						},
+					};
+					mesh.vertices.extend(vertices_raw);
+					mesh.add_layer(*layer_index, LayerIndices { indices, convex_bezier_indices, concave_bezier_indices, cubic_bezier_indices });
+					*layer_index += 1;
+				},
+				PaintCommand::Composite { source, backdrop, .. } => {
+					self.append_paint_commands(mesh, backdrop, clip_box, offset, target, layer_index);
+					self.append_paint_commands(mesh, source, clip_box, offset, target, layer_index);
+				},
+			}
+		}
+	}
+
+	/// Adds a quad for a bitmap glyph's image at one size, scaled to the text, cut off at the text box's bounds.
+	fn append_bitmap(&self, mesh: &mut TextMesh, key: (usize, usize), bitmap: &font::Bitmap, offset: Position<FontUnits<i32>>, position: Position<Pixels<f32>>, screen_size: Size<Pixels<i32>>) {
+		let Some((atlas_x, atlas_y)) = mesh.bitmap_atlas.place(key, &bitmap.image) else {
+			return;
+		};
+		let (width, height) = (bitmap.image.width as f32, bitmap.image.height as f32);
+		let scale = self.pixels_per_em.value / bitmap.pixels_per_em as f32;
+		let pixels_per_font_unit = self.get_pixels_per_font_unit();
+		let left = offset.x.value as f32 * pixels_per_font_unit + position.x.value + bitmap.left as f32 * scale;
+		let bottom = offset.y.value as f32 * pixels_per_font_unit + position.y.value + bitmap.bottom as f32 * scale;
+		let (right, top) = (left + width * scale, bottom + height * scale);
+
+		let x_min = left.max(self.bounds.0.x.value as f32);
+		let x_max = right.min(self.bounds.1.x.value as f32);
+		let y_min = bottom.max(self.bounds.0.y.value as f32);
+		let y_max = top.min(self.bounds.1.y.value as f32);
+		if x_min >= x_max || y_min >= y_max {
+			return;
+		}
+
+		// Images are stored from their top row, so texels go down as the quad goes up. Cutting off the quad cuts off as much of the image.
+		let texel = |x: f32, y: f32| [atlas_x as f32 + (x - left) / scale, atlas_y as f32 + (top - y) / scale];
+		let to_screen = |x: f32, y: f32| [Pixels::from(x).to_screen_space(screen_size.width).value, Pixels::from(y).to_screen_space(screen_size.height).value];
+		let vertices_start = mesh.bitmap_vertices.len() as u32;
+		for (x, y) in [(x_min, y_min), (x_max, y_min), (x_max, y_max), (x_min, y_max)] {
+			mesh.bitmap_vertices.push(TexturedVertexRaw { position: to_screen(x, y), texture_coords: texel(x, y) });
+		}
+		mesh.bitmap_indices.extend([0, 1, 2, 0, 2, 3].iter().map(|index| index + vertices_start));
+	}
+
+	/// The position moved by less than a pixel each way, so that a glyph at the offset from it starts on a whole pixel.
+	fn pixel_aligned(&self, offset: Position<FontUnits<i32>>, position: Position<Pixels<f32>>) -> Position<Pixels<f32>> {
+		let pixels_per_font_unit = self.get_pixels_per_font_unit();
+		let origin_x = offset.x.value as f32 * pixels_per_font_unit + position.x.value;
+		let origin_y = offset.y.value as f32 * pixels_per_font_unit + position.y.value;
+		(position.x.value + origin_x.round() - origin_x, position.y.value + origin_y.round() - origin_y).into()
+	}
+
+	/// The text box's bounds, in pixels, narrowed to a colour glyph's clip box.
+	fn clip_bounds(&self, clip_box: &ClipBox, offset: Position<FontUnits<i32>>, position: Position<Pixels<f32>>) -> (Position<Pixels<i32>>, Position<Pixels<i32>>) {
+		let pixels_per_font_unit = self.get_pixels_per_font_unit();
+		let to_pixels = |x: f32, y: f32| ((offset.x.value as f32 + x) * pixels_per_font_unit + position.x.value, (offset.y.value as f32 + y) * pixels_per_font_unit + position.y.value);
+		let (x_min, y_min) = to_pixels(clip_box.x_min, clip_box.y_min);
+		let (x_max, y_max) = to_pixels(clip_box.x_max, clip_box.y_max);
+		(
+			(self.bounds.0.x.value.max(x_min.floor() as i32), self.bounds.0.y.value.max(y_min.floor() as i32)).into(),
+			(self.bounds.1.x.value.min(x_max.ceil() as i32), self.bounds.1.y.value.min(y_max.ceil() as i32)).into(),
+		)
+	}
+
+	fn to_raw(&self, screen_size: Size<Pixels<i32>>, mesh: &mut TextMesh) {
+		if self.writing_mode == WritingMode::Vertical {
+			return self.to_raw_vertical(screen_size, mesh);
+		}
+
 		let mut advance_offset: FontUnits<i32> = 0.into();
 		let mut vertical_offset: FontUnits<i32> = 0.into();
-		let mut vertices_raw: Vec<VertexRaw> = Vec::new();
-		let mut indices: Vec<u32> = Vec::new();
-		let mut convex_bezier_indices: Vec<u32> = Vec::new();
-		let mut concave_bezier_indices: Vec<u32> = Vec::new();
 
 		let text_size = self.get_text_size(self.text_box_size.width);
 
@@ -850,37 +1418,29 @@ impl TextBox {
 			}
 			match self.wrap_options.wrap_on {
 				WrapOn::Character => {
-					for character in line.chars() {
-						let character_glyph_id = self.font.mappings[0].get_glyph_id(character as u64).unwrap_or(0);
-						let glyph = &self.font.get_glyph(character_glyph_id as usize);
-
-						let future_advance_offset = (advance_offset + glyph.advance_width).to_pixels(self.get_pixels_per_font_unit());
+					for positioned_glyph in self.font.layout_glyphs(line, self.script, &self.features) {
+						let future_advance_offset = (advance_offset + positioned_glyph.advance).to_pixels(self.get_pixels_per_font_unit());
 						if future_advance_offset > self.text_box_size.width.into() {
 							advance_offset = 0.into();
 							vertical_offset -= self.font.line_spacing;
 						}
 
-						let (mut vertices_raw_character, mut indices_character, mut convex_bezier_indices_character, mut concave_bezier_indices_character) = glyph.to_raw(&*self.font, self.get_pixels_per_font_unit(), (advance_offset, vertical_offset).into(), screen_size, position.into(), vertices_raw.len() + vertices_start, self.colour, self.bounds);
-						advance_offset += glyph.advance_width;
-						vertices_raw.append(&mut vertices_raw_character);
-						indices.append(&mut indices_character);
-						convex_bezier_indices.append(&mut convex_bezier_indices_character);
-						concave_bezier_indices.append(&mut concave_bezier_indices_character);
+						self.append_glyph(mesh, positioned_glyph.glyph_id, (advance_offset + positioned_glyph.offset.x, vertical_offset + positioned_glyph.offset.y).into(), position.into(), screen_size);
+						advance_offset += positioned_glyph.advance;
 					}
 				},
 				WrapOn::Whitespace => {
-					let space_glyph_id = self.font.mappings[0].get_glyph_id(' ' as u64).unwrap_or(0);
-					let space_advance_width =  self.font.get_glyph(space_glyph_id as usize).advance_width;
+					let space_glyphs = self.font.layout_glyphs(" ", self.script, &self.features);
+					let space_advance_width = get_advance_width(&space_glyphs);
 					let mut add_space = false;
 					let mut first_word = true;
 					for word in line.split_whitespace() {
-						let mut word_advance_width: FontUnits<i32> = if add_space {space_advance_width.into()} else {0.into()};
-						for character in word.chars() {
-							let character_glyph_id = self.font.mappings[0].get_glyph_id(character as u64).unwrap_or(0);
-							let glyph = &self.font.get_glyph(character_glyph_id as usize);
-
-							word_advance_width += glyph.advance_width;
-						}
+						let word_glyphs = self.font.layout_glyphs(word, self.script, &self.features);
+						let word_advance_width: FontUnits<i32> = if add_space {
+							get_advance_width(&word_glyphs) + space_advance_width
+						} else {
+							get_advance_width(&word_glyphs)
+						};
 
 						let future_advance_offset = (advance_offset + word_advance_width).to_pixels(self.get_pixels_per_font_unit());
 						if future_advance_offset > self.text_box_size.width.into() {
@@ -891,28 +1451,10 @@ impl TextBox {
 							add_space = true;
 						}
 
-						if add_space {
-							let character_glyph_id = self.font.mappings[0].get_glyph_id(' ' as u64).unwrap_or(0);
-							let glyph = &self.font.get_glyph(character_glyph_id as usize);
-
-							let (mut vertices_raw_character, mut indices_character, mut convex_bezier_indices_character, mut concave_bezier_indices_character) = glyph.to_raw(&*self.font, self.get_pixels_per_font_unit(), (advance_offset, vertical_offset).into(), screen_size, position.into(), vertices_raw.len() + vertices_start, self.colour, self.bounds);
-							advance_offset += glyph.advance_width;
-							vertices_raw.append(&mut vertices_raw_character);
-							indices.append(&mut indices_character);
-							convex_bezier_indices.append(&mut convex_bezier_indices_character);
-							concave_bezier_indices.append(&mut concave_bezier_indices_character);
-						}
-
-						for character in word.chars() {
-							let character_glyph_id = self.font.mappings[0].get_glyph_id(character as u64).unwrap_or(0);
-							let glyph = &self.font.get_glyph(character_glyph_id as usize);
-
-							let (mut vertices_raw_character, mut indices_character, mut convex_bezier_indices_character, mut concave_bezier_indices_character) = glyph.to_raw(&*self.font, self.get_pixels_per_font_unit(), (advance_offset, vertical_offset).into(), screen_size, position.into(), vertices_raw.len() + vertices_start, self.colour, self.bounds);
-							advance_offset += glyph.advance_width;
-							vertices_raw.append(&mut vertices_raw_character);
-							indices.append(&mut indices_character);
-							convex_bezier_indices.append(&mut convex_bezier_indices_character);
-							concave_bezier_indices.append(&mut concave_bezier_indices_character);
+						let glyphs = if add_space { space_glyphs.iter().chain(word_glyphs.iter()).collect::<Vec<_>>() } else { word_glyphs.iter().collect() };
+						for positioned_glyph in glyphs {
+							self.append_glyph(mesh, positioned_glyph.glyph_id, (advance_offset + positioned_glyph.offset.x, vertical_offset + positioned_glyph.offset.y).into(), position.into(), screen_size);
+							advance_offset += positioned_glyph.advance;
 						}
 
 						first_word = false;
@@ -922,7 +1464,37 @@ impl TextBox {
 			first_line = false;
 		}
 		drop(string);
-		(vertices_raw, indices, convex_bezier_indices, concave_bezier_indices)
+	}
+
+	/// Columns start at the right of the box, with their tops where the top of the first line of horizontal text would be.
+	fn to_raw_vertical(&self, screen_size: Size<Pixels<i32>>, mesh: &mut TextMesh) {
+		let (placed_glyphs, _) = self.layout_columns(self.text_box_size.height);
+		let text_size = self.get_text_size(self.text_box_size.width);
+
+		let mut position: Position<Pixels<i32>> = (0, 0).into();
+
+		position.x = match self.alignment.x {
+			Alignments::Start => self.position.x + self.text_box_size.width,
+			Alignments::Centre => {
+				self.position.x + self.text_box_size.width - ((self.text_box_size.width - text_size.width) / 2)
+			},
+			Alignments::End => self.position.x + text_size.width,
+		};
+
+		let top = self.position.y + self.font.typographic_ascender.to_pixels_rounded(self.get_pixels_per_font_unit());
+		position.y = match self.alignment.y {
+			Alignments::Start => top,
+			Alignments::Centre => {
+				top - ((self.text_box_size.height - text_size.height) / 2)
+			},
+			Alignments::End => {
+				top - (self.text_box_size.height - text_size.height)
+			},
+		};
+
+		for (glyph_id, offset) in placed_glyphs {
+			self.append_glyph(mesh, glyph_id, offset, position.into(), screen_size);
+		}
 	}
 }
 
@@ -937,7 +1509,11 @@ impl TextBox {
 			bounds: ((0, 0).into(), (i32::MAX, i32::MAX).into()),
 			colour,
 			wrap_options,
-			alignment: Alignment { x: mircalla_types::vectors::Alignments::Start, y: mircalla_types::vectors::Alignments::Start }
+			alignment: Alignment { x: mircalla_types::vectors::Alignments::Start, y: mircalla_types::vectors::Alignments::Start },
+			script: *b"latn",
+			features: font::default_features(),
+			writing_mode: WritingMode::Horizontal,
+			palette: 0,
 		}
 	}
 
@@ -945,4 +1521,29 @@ impl TextBox {
 		self.alignment = alignment;
 		self
 	}
+
+	pub fn script(mut self, script: [u8; 4]) -> TextBox {
+		self.script = script;
+		self
+	}
+
+	pub fn writing_mode(mut self, writing_mode: WritingMode) -> TextBox {
+		self.writing_mode = writing_mode;
+		self
+	}
+
+	/// Draws colour glyphs with another of the font's palettes, such as the one from `Font::palette_for_background`.
+	pub fn palette(mut self, palette_index: usize) -> TextBox {
+		self.palette = palette_index;
+		self
+	}
+
+	/// Turns an OpenType feature on or off, such as `*b"liga"` for ligatures or `*b"tnum"` for tabular figures.
+	pub fn feature(mut self, feature_tag: [u8; 4], enabled: bool) -> TextBox {
+		self.features.retain(|&tag| tag != feature_tag);
+		if enabled {
+			self.features.push(feature_tag);
+		}
+		self
+	}
 }
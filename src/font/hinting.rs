@@ -0,0 +1,1565 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}};
+
+use crate::{font::{Glyph, RawGlyphs}, hinting_reader::GridFittingTable, ttf_parser::flatten_glyph, ttf_reader::{ComponentGlyphRaw, GlyphDataRaw, HorizontalMetric, MaximumProfileTable, SimpleGlyphRaw}};
+
+const TWILIGHT_ZONE: usize = 0;
+const GLYPH_ZONE: usize = 1;
+const PHANTOM_POINTS: usize = 4; // The left and right side bearing points, and the top and bottom ones, after each glyph's own points
+const TOUCHED_X: u8 = 0x01;
+const TOUCHED_Y: u8 = 0x02;
+const MAX_CALL_DEPTH: usize = 64;
+const MAX_COMPONENT_DEPTH: usize = 8;
+const MAX_INSTRUCTIONS: usize = 1_000_000; // Per program, so a font with a loop that never ends can't hang the renderer
+const EXTRA_STACK_ELEMENTS: usize = 32; // Fonts often claim a smaller stack in maxp than their programs use
+const X_AXIS: (i32, i32) = (0x4000, 0);
+const Y_AXIS: (i32, i32) = (0, 0x4000);
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HintingError {
+	StackUnderflow,
+	StackOverflow,
+	InvalidOpcode(u8),
+	InvalidZone(i32),
+	PointOutOfRange(i32),
+	ContourOutOfRange(i32),
+	FunctionNotDefined(i32),
+	CallsNestedTooDeep,
+	ComponentsNestedTooDeep,
+	TooManyInstructions,
+	UnexpectedEnd, // An instruction's data, an IF's EIF or an FDEF's ENDF run past the end of the program
+	DivideByZero,
+	DistortedOutline, // The instructions flattened a contour or turned it inside out
+}
+
+/// The programs from fpgm, prep and glyf, the control values they work with, and the sizes they've been run for.
+pub struct Hinting {
+	font_program: Vec<u8>,
+	control_value_program: Vec<u8>,
+	control_values: Vec<i16>, // In font units
+	grid_fitting: Option<GridFittingTable>,
+	glyphs: RawGlyphs,
+	horizontal_metrics: Vec<HorizontalMetric>,
+	units_per_em: u16,
+	max_twilight_points: usize,
+	max_storage: usize,
+	max_stack_elements: usize,
+	sizes: Mutex<HashMap<u32, Result<Arc<SizeState>, HintingError>>>, // By pixels per em in 26.6
+	hinted_glyphs: HintedGlyphCache,
+}
+
+/// The tables that drive hinting: fpgm, prep, cvt and gasp.
+pub struct HintingTables {
+	pub font_program: Vec<u8>,
+	pub control_value_program: Vec<u8>,
+	pub control_values: Vec<i16>, // In font units
+	pub grid_fitting: Option<GridFittingTable>,
+}
+
+type HintedGlyphs = HashMap<(u32, u16), Option<Arc<Glyph>>>; // By size in 26.6 and glyph id
+
+/// Glyphs grid-fitted at each size they've been drawn at, or None where they couldn't be.
+#[derive(Default)]
+pub(crate) struct HintedGlyphCache(Mutex<HintedGlyphs>);
+
+impl HintedGlyphCache {
+	pub(crate) fn get_or_insert_with(&self, glyph_id: u16, pixels_per_em: f32, make: impl FnOnce() -> Option<Arc<Glyph>>) -> Option<Arc<Glyph>> {
+		let key = (size_key(pixels_per_em), glyph_id);
+		if let Some(glyph) = self.0.lock().unwrap().get(&key) {
+			return glyph.clone();
+		}
+		let glyph = make();
+		self.0.lock().unwrap().insert(key, glyph.clone());
+		glyph
+	}
+}
+
+/// A glyph's outline moved onto the pixel grid, in 26.6 fixed point pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HintedOutline {
+	pub points: Vec<(i32, i32)>,
+	pub on_curve: Vec<bool>,
+	pub contour_ends: Vec<usize>,
+	pub phantom_points: [(i32, i32); PHANTOM_POINTS],
+}
+
+/// What fpgm and prep leave behind at a size, which each glyph's program starts from.
+struct SizeState {
+	graphics_state: GraphicsState,
+	control_values: Vec<i32>, // Scaled to 26.6 pixels
+	storage: Vec<i32>,
+	twilight_zone: Zone,
+	functions: HashMap<i32, Definition>,
+	instruction_definitions: HashMap<u8, Definition>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Program {
+	Font,
+	ControlValue,
+	Glyph,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Definition {
+	program: Program,
+	start: usize, // The instruction after the FDEF or IDEF
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RoundState {
+	Grid,
+	HalfGrid,
+	DoubleGrid,
+	DownToGrid,
+	UpToGrid,
+	Off,
+	Super { period: i32, phase: i32, threshold: i32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct GraphicsState {
+	zone_pointers: [usize; 3],
+	reference_points: [usize; 3],
+	projection_vector: (i32, i32), // 2.14
+	freedom_vector: (i32, i32),
+	dual_projection_vector: (i32, i32), // The projection vector, measured on original positions, set apart from it only by SDPVTL
+	round_state: RoundState,
+	loop_count: i32,
+	minimum_distance: i32,
+	control_value_cut_in: i32,
+	single_width_cut_in: i32,
+	single_width_value: i32,
+	delta_base: i32,
+	delta_shift: i32,
+	auto_flip: bool,
+	instruct_control: i32,
+}
+
+impl Default for GraphicsState {
+	fn default() -> Self {
+		GraphicsState {
+			zone_pointers: [GLYPH_ZONE; 3],
+			reference_points: [0; 3],
+			projection_vector: X_AXIS,
+			freedom_vector: X_AXIS,
+			dual_projection_vector: X_AXIS,
+			round_state: RoundState::Grid,
+			loop_count: 1,
+			minimum_distance: 64,
+			control_value_cut_in: 68, // 17/16 of a pixel
+			single_width_cut_in: 0,
+			single_width_value: 0,
+			delta_base: 9,
+			delta_shift: 3,
+			auto_flip: true,
+			instruct_control: 0,
+		}
+	}
+}
+
+impl GraphicsState {
+	/// The state a glyph's program starts in. Only the values prep sets that aren't reset for each glyph carry over.
+	fn for_glyph(&self) -> GraphicsState {
+		GraphicsState {
+			zone_pointers: [GLYPH_ZONE; 3],
+			reference_points: [0; 3],
+			projection_vector: X_AXIS,
+			freedom_vector: X_AXIS,
+			dual_projection_vector: X_AXIS,
+			round_state: RoundState::Grid,
+			loop_count: 1,
+			..*self
+		}
+	}
+}
+
+#[derive(Debug, Clone, Default)]
+struct Zone {
+	original: Vec<(i32, i32)>,
+	current: Vec<(i32, i32)>,
+	touched: Vec<u8>,
+	on_curve: Vec<bool>,
+	contour_ends: Vec<usize>,
+}
+
+impl Zone {
+	fn new(points: Vec<(i32, i32)>, on_curve: Vec<bool>, contour_ends: Vec<usize>) -> Zone {
+		Zone {
+			original: points.clone(),
+			touched: vec![0; points.len()],
+			current: points,
+			on_curve,
+			contour_ends,
+		}
+	}
+
+	fn twilight(number_of_points: usize) -> Zone {
+		Zone::new(vec![(0, 0); number_of_points], vec![false; number_of_points], Vec::new())
+	}
+}
+
+impl Hinting {
+	pub fn new(tables: HintingTables, glyphs: RawGlyphs, horizontal_metrics: Vec<HorizontalMetric>, units_per_em: u16, maximum_profile: &MaximumProfileTable) -> Hinting {
+		let HintingTables { font_program, control_value_program, control_values, grid_fitting } = tables;
+		Hinting {
+			font_program,
+			control_value_program,
+			control_values,
+			grid_fitting,
+			glyphs,
+			horizontal_metrics,
+			units_per_em,
+			max_twilight_points: maximum_profile.max_twilight_points as usize,
+			max_storage: maximum_profile.max_storage as usize,
+			max_stack_elements: maximum_profile.max_stack_elements as usize + EXTRA_STACK_ELEMENTS,
+			sizes: Mutex::new(HashMap::new()),
+			hinted_glyphs: HintedGlyphCache::default(),
+		}
+	}
+
+	/// Whether gasp asks for outlines to be grid-fitted at a size. Fonts without a gasp table are grid-fitted at every size.
+	pub fn grid_fits(&self, pixels_per_em: f32) -> bool {
+		self.grid_fitting.as_ref().is_none_or(|grid_fitting| grid_fitting.grid_fits(pixels_per_em.round().clamp(0.0, u16::MAX as f32) as u16))
+	}
+
+	/// A glyph's outline after its instructions have run at a size, or None if prep turned grid-fitting off there with INSTCTRL.
+	pub fn hint_glyph(&self, glyph_id: u16, pixels_per_em: f32) -> Result<Option<HintedOutline>, HintingError> {
+		let size = self.size(pixels_per_em)?;
+		if size.graphics_state.instruct_control & 1 != 0 {
+			return Ok(None);
+		}
+		let outline = self.hint_component(&size, glyph_id, pixels_per_em, 0)?;
+		// An outline with a contour flattened or turned inside out can't be triangulated, so it's given up on like any other error.
+		if !self.keeps_contour_directions(glyph_id, &outline) {
+			return Err(HintingError::DistortedOutline);
+		}
+		Ok(Some(outline))
+	}
+
+	/// Whether every contour of a hinted outline goes the same way round as the glyph's own, and still encloses some area.
+	/// Contours that had none to begin with are left alone.
+	fn keeps_contour_directions(&self, glyph_id: u16, hinted: &HintedOutline) -> bool {
+		let mut unhinted = SimpleGlyphRaw::default();
+		let Some(()) = self.glyphs.glyph(glyph_id).and_then(|glyph| flatten_glyph(&|glyph_id| self.glyphs.glyph(glyph_id), &glyph, &mut unhinted, (1.0, 0.0, 0.0, 1.0), (0.0, 0.0), 0)) else {
+			return true;
+		};
+		let unhinted_points: Vec<(i32, i32)> = unhinted.x_coordinates.iter().zip(&unhinted.y_coordinates).map(|(x, y)| (*x as i32, *y as i32)).collect();
+		if unhinted_points.len() != hinted.points.len() || !unhinted.end_points_of_contours.iter().map(|end| *end as usize).eq(hinted.contour_ends.iter().copied()) {
+			return false;
+		}
+		let mut start = 0;
+		for &end in hinted.contour_ends.iter() {
+			let (Some(hinted_contour), Some(unhinted_contour)) = (hinted.points.get(start..=end), unhinted_points.get(start..=end)) else {
+				return false;
+			};
+			let unhinted_area = signed_area(unhinted_contour);
+			if unhinted_area != 0 && signed_area(hinted_contour).signum() != unhinted_area.signum() {
+				return false;
+			}
+			start = end + 1;
+		}
+		true
+	}
+
+	/// The hinted glyph at a size from the cache, made with `make` the first time it's asked for.
+	pub(crate) fn cached_glyph(&self, glyph_id: u16, pixels_per_em: f32, make: impl FnOnce() -> Option<Arc<Glyph>>) -> Option<Arc<Glyph>> {
+		self.hinted_glyphs.get_or_insert_with(glyph_id, pixels_per_em, make)
+	}
+
+	/// Runs fpgm then prep at a size the first time it's used.
+	fn size(&self, pixels_per_em: f32) -> Result<Arc<SizeState>, HintingError> {
+		let key = size_key(pixels_per_em);
+		if let Some(size) = self.sizes.lock().unwrap().get(&key) {
+			return size.clone();
+		}
+		let size = self.prepare_size(pixels_per_em).map(Arc::new);
+		self.sizes.lock().unwrap().insert(key, size.clone());
+		size
+	}
+
+	fn prepare_size(&self, pixels_per_em: f32) -> Result<SizeState, HintingError> {
+		let scale = self.scale(pixels_per_em);
+		let size = SizeState {
+			graphics_state: GraphicsState::default(),
+			control_values: self.control_values.iter().map(|value| scale_value(*value as i32, scale)).collect(),
+			storage: vec![0; self.max_storage],
+			twilight_zone: Zone::twilight(self.max_twilight_points),
+			functions: HashMap::new(),
+			instruction_definitions: HashMap::new(),
+		};
+		let mut interpreter = Interpreter::new(self, &size, pixels_per_em, &[], Zone::default());
+		interpreter.run(Program::Font)?;
+		interpreter.state = GraphicsState::default();
+		interpreter.run(Program::ControlValue)?;
+
+		// With bit 2 of INSTCTRL set, glyphs start from the default state rather than the one prep leaves.
+		let graphics_state = match interpreter.state.instruct_control & 2 != 0 {
+			true => GraphicsState { instruct_control: interpreter.state.instruct_control, ..GraphicsState::default() },
+			false => interpreter.state,
+		};
+		Ok(SizeState {
+			graphics_state,
+			control_values: interpreter.control_values,
+			storage: interpreter.storage,
+			twilight_zone: std::mem::take(&mut interpreter.zones[TWILIGHT_ZONE]),
+			functions: interpreter.functions,
+			instruction_definitions: interpreter.instruction_definitions,
+		})
+	}
+
+	/// Scales a glyph's points to the size and runs its program on them. Composite glyphs hint each component first,
+	/// then run their own program on the components' points together.
+	fn hint_component(&self, size: &SizeState, glyph_id: u16, pixels_per_em: f32, depth: usize) -> Result<HintedOutline, HintingError> {
+		if depth > MAX_COMPONENT_DEPTH {
+			return Err(HintingError::ComponentsNestedTooDeep);
+		}
+		let scale = self.scale(pixels_per_em);
+		let Some(glyph) = self.glyphs.glyph(glyph_id) else {
+			return Ok(HintedOutline { points: Vec::new(), on_curve: Vec::new(), contour_ends: Vec::new(), phantom_points: [(0, 0); PHANTOM_POINTS] });
+		};
+		let horizontal_metric = self.horizontal_metrics.get(glyph_id as usize).or(self.horizontal_metrics.last()).copied().unwrap_or(HorizontalMetric { advance_width: 0, left_side_bearing: 0 });
+		let left = glyph.x_min as i32 - horizontal_metric.left_side_bearing as i32;
+		let phantom_points = [
+			(scale_value(left, scale), 0),
+			(scale_value(left + horizontal_metric.advance_width as i32, scale), 0),
+			(0, scale_value(glyph.y_max as i32, scale)),
+			(0, scale_value(glyph.y_min as i32, scale)),
+		];
+
+		let (points, on_curve, contour_ends, phantom_points, instructions) = match &glyph.glyph_data {
+			GlyphDataRaw::SimpleGlyphRaw(simple) => {
+				let points = simple.x_coordinates.iter().zip(&simple.y_coordinates).map(|(x, y)| (scale_value(*x as i32, scale), scale_value(*y as i32, scale))).collect();
+				let on_curve = simple.flags.iter().map(|flag| flag & 0x01 != 0).collect();
+				let contour_ends = simple.end_points_of_contours.iter().map(|end| *end as usize).collect();
+				(points, on_curve, contour_ends, phantom_points, &simple.instructions[..])
+			},
+			GlyphDataRaw::CompositeGlyphRaw(composite) => {
+				let mut outline = HintedOutline { points: Vec::new(), on_curve: Vec::new(), contour_ends: Vec::new(), phantom_points };
+				for child in composite.children.iter() {
+					let component = self.hint_component(size, child.glyph_index, pixels_per_em, depth + 1)?;
+					place_component(&mut outline, component, child, scale)?;
+				}
+				(outline.points, outline.on_curve, outline.contour_ends, outline.phantom_points, &composite.instructions[..])
+			},
+			GlyphDataRaw::None => (Vec::new(), Vec::new(), Vec::new(), phantom_points, &[][..]),
+		};
+
+		// The side bearing points are put on the grid before the program runs, so the advance stays a whole number of pixels.
+		let mut zone_points: Vec<(i32, i32)> = points;
+		zone_points.extend(phantom_points);
+		let mut zone = Zone::new(zone_points, on_curve, contour_ends);
+		let number_of_points = zone.current.len() - PHANTOM_POINTS;
+		for point in zone.current[number_of_points..].iter_mut() {
+			*point = (round_to_grid(point.0), round_to_grid(point.1));
+		}
+
+		if !instructions.is_empty() {
+			let mut interpreter = Interpreter::new(self, size, pixels_per_em, instructions, zone);
+			interpreter.state = size.graphics_state.for_glyph();
+			interpreter.run(Program::Glyph)?;
+			zone = std::mem::take(&mut interpreter.zones[GLYPH_ZONE]);
+		}
+
+		let phantom_points = [zone.current[number_of_points], zone.current[number_of_points + 1], zone.current[number_of_points + 2], zone.current[number_of_points + 3]];
+		zone.current.truncate(number_of_points);
+		Ok(HintedOutline { points: zone.current, on_curve: zone.on_curve, contour_ends: zone.contour_ends, phantom_points })
+	}
+
+	/// 26.6 pixels per font unit, as 16.16.
+	fn scale(&self, pixels_per_em: f32) -> f64 {
+		pixels_per_em as f64 * 64.0 / self.units_per_em.max(1) as f64
+	}
+}
+
+/// Twice the area a contour encloses, positive when it goes anticlockwise.
+fn signed_area(points: &[(i32, i32)]) -> i64 {
+	points.iter().zip(points.iter().cycle().skip(1)).map(|(point, next)| point.0 as i64 * next.1 as i64 - next.0 as i64 * point.1 as i64).sum()
+}
+
+/// Adds a hinted component to a composite's outline, transformed, and moved by its offset or so that its matching point lands on the composite's.
+fn place_component(outline: &mut HintedOutline, mut component: HintedOutline, child: &ComponentGlyphRaw, scale: f64) -> Result<(), HintingError> {
+	let f2dot14 = |value: Option<u16>| value.map(|value| value as i16 as f64 / 16384.0);
+	let (xx, xy, yx, yy) = match (f2dot14(child.transform_0), f2dot14(child.transform_1), f2dot14(child.transform_2), f2dot14(child.transform_3)) {
+		(Some(xx), Some(xy), Some(yx), Some(yy)) => (xx, xy, yx, yy),
+		(Some(x_scale), Some(y_scale), None, None) => (x_scale, 0.0, 0.0, y_scale),
+		(Some(scale), None, None, None) => (scale, 0.0, 0.0, scale),
+		_ => (1.0, 0.0, 0.0, 1.0),
+	};
+	if (xx, xy, yx, yy) != (1.0, 0.0, 0.0, 1.0) {
+		for point in component.points.iter_mut() {
+			let (x, y) = (point.0 as f64, point.1 as f64);
+			*point = ((x * xx + y * yx).round() as i32, (x * xy + y * yy).round() as i32);
+		}
+	}
+
+	let offset = match child.flag & 0x0002 != 0 {
+		true => {
+			let offset = (scale_value(child.x_offset_point, scale), scale_value(child.y_offset_point, scale));
+			match child.flag & 0x0004 != 0 {
+				true => (round_to_grid(offset.0), round_to_grid(offset.1)),
+				false => offset,
+			}
+		},
+		// The offsets are point numbers, the first in the composite's points so far and the second in the component's.
+		false => {
+			let parent_point = outline.points.get(child.x_offset_point as usize).ok_or(HintingError::PointOutOfRange(child.x_offset_point))?;
+			let child_point = component.points.get(child.y_offset_point as usize).ok_or(HintingError::PointOutOfRange(child.y_offset_point))?;
+			(parent_point.0 - child_point.0, parent_point.1 - child_point.1)
+		},
+	};
+
+	let base = outline.points.len();
+	outline.points.extend(component.points.iter().map(|(x, y)| (x + offset.0, y + offset.1)));
+	outline.on_curve.extend(component.on_curve);
+	outline.contour_ends.extend(component.contour_ends.iter().map(|end| end + base));
+	// A component flagged USE_MY_METRICS gives the composite its advance.
+	if child.flag & 0x0200 != 0 {
+		outline.phantom_points = component.phantom_points;
+	}
+	Ok(())
+}
+
+fn size_key(pixels_per_em: f32) -> u32 {
+	(pixels_per_em * 64.0).round().max(0.0) as u32
+}
+
+/// Font units to 26.6 pixels.
+fn scale_value(value: i32, scale: f64) -> i32 {
+	(value as f64 * scale).round() as i32
+}
+
+fn round_to_grid(value: i32) -> i32 {
+	(value + 32) & !63
+}
+
+/// a * b / c, rounded, without overflowing in between.
+fn mul_div(a: i32, b: i32, c: i32) -> i32 {
+	if c == 0 {
+		return if (a < 0) != (b < 0) { -0x7FFFFFFF } else { 0x7FFFFFFF };
+	}
+	let (a, b, c) = (a as i64, b as i64, c as i64);
+	let sign = a.signum() * b.signum() * c.signum();
+	let value = (a.abs() * b.abs() + c.abs() / 2) / c.abs();
+	(sign * value).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+}
+
+/// A 26.6 distance times a 2.14 vector component.
+fn mul_2dot14(value: i32, component: i32) -> i32 {
+	((value as i64 * component as i64 + 0x2000) >> 14) as i32
+}
+
+fn dot(vector: (i32, i32), unit: (i32, i32)) -> i32 {
+	((vector.0 as i64 * unit.0 as i64 + vector.1 as i64 * unit.1 as i64 + 0x2000) >> 14) as i32
+}
+
+/// A vector scaled to unit length in 2.14, or the x axis for a vector of no length.
+fn normalise(x: i32, y: i32) -> (i32, i32) {
+	let length = ((x as f64).powi(2) + (y as f64).powi(2)).sqrt();
+	match length == 0.0 {
+		true => X_AXIS,
+		false => ((x as f64 * 16384.0 / length).round() as i32, (y as f64 * 16384.0 / length).round() as i32),
+	}
+}
+
+/// How many bytes an instruction takes up, with the data of the pushes.
+fn instruction_length(code: &[u8], pc: usize) -> Result<usize, HintingError> {
+	let length = match code[pc] {
+		0x40 => 2 + *code.get(pc + 1).ok_or(HintingError::UnexpectedEnd)? as usize,
+		0x41 => 2 + 2 * *code.get(pc + 1).ok_or(HintingError::UnexpectedEnd)? as usize,
+		opcode @ 0xB0..=0xB7 => 2 + (opcode - 0xB0) as usize,
+		opcode @ 0xB8..=0xBF => 3 + 2 * (opcode - 0xB8) as usize,
+		_ => 1,
+	};
+	match pc + length <= code.len() {
+		true => Ok(length),
+		false => Err(HintingError::UnexpectedEnd),
+	}
+}
+
+/// Where to carry on after a false IF, which is after its ELSE if `stop_at_else`, or otherwise after its EIF.
+fn skip_conditional(code: &[u8], mut pc: usize, stop_at_else: bool) -> Result<usize, HintingError> {
+	let mut depth = 0;
+	while pc < code.len() {
+		match code[pc] {
+			0x58 => depth += 1,
+			0x1B if depth == 0 && stop_at_else => return Ok(pc + 1),
+			0x59 if depth == 0 => return Ok(pc + 1),
+			0x59 => depth -= 1,
+			_ => {},
+		}
+		pc += instruction_length(code, pc)?;
+	}
+	Err(HintingError::UnexpectedEnd)
+}
+
+/// Where to carry on after a definition, just past its ENDF.
+fn skip_definition(code: &[u8], mut pc: usize) -> Result<usize, HintingError> {
+	while pc < code.len() {
+		match code[pc] {
+			0x2D => return Ok(pc + 1),
+			opcode @ (0x2C | 0x89) => return Err(HintingError::InvalidOpcode(opcode)),
+			_ => {},
+		}
+		pc += instruction_length(code, pc)?;
+	}
+	Err(HintingError::UnexpectedEnd)
+}
+
+fn jump(pc: usize, offset: i32, length: usize) -> Result<usize, HintingError> {
+	let target = pc as i64 + offset as i64;
+	match offset != 0 && (0..=length as i64).contains(&target) {
+		true => Ok(target as usize),
+		false => Err(HintingError::UnexpectedEnd),
+	}
+}
+
+struct Interpreter<'a> {
+	hinting: &'a Hinting,
+	glyph_program: &'a [u8],
+	pixels_per_em: i32, // Rounded, as MPPEM and the delta instructions see it
+	scale: f64,
+	stack: Vec<i32>,
+	state: GraphicsState,
+	control_values: Vec<i32>,
+	storage: Vec<i32>,
+	zones: [Zone; 2],
+	functions: HashMap<i32, Definition>,
+	instruction_definitions: HashMap<u8, Definition>,
+	instructions_left: usize,
+	program: Program,
+}
+
+impl<'a> Interpreter<'a> {
+	fn new(hinting: &'a Hinting, size: &SizeState, pixels_per_em: f32, glyph_program: &'a [u8], glyph_zone: Zone) -> Interpreter<'a> {
+		Interpreter {
+			hinting,
+			glyph_program,
+			pixels_per_em: pixels_per_em.round() as i32,
+			scale: hinting.scale(pixels_per_em),
+			stack: Vec::new(),
+			state: size.graphics_state,
+			control_values: size.control_values.clone(),
+			storage: size.storage.clone(),
+			zones: [size.twilight_zone.clone(), glyph_zone],
+			functions: size.functions.clone(),
+			instruction_definitions: size.instruction_definitions.clone(),
+			instructions_left: MAX_INSTRUCTIONS,
+			program: Program::Font,
+		}
+	}
+
+	fn run(&mut self, program: Program) -> Result<(), HintingError> {
+		self.program = program;
+		self.stack.clear();
+		self.instructions_left = MAX_INSTRUCTIONS;
+		self.execute(program, 0, 0)
+	}
+
+	fn code(&self, program: Program) -> &'a [u8] {
+		match program {
+			Program::Font => &self.hinting.font_program,
+			Program::ControlValue => &self.hinting.control_value_program,
+			Program::Glyph => self.glyph_program,
+		}
+	}
+
+	fn pop(&mut self) -> Result<i32, HintingError> {
+		self.stack.pop().ok_or(HintingError::StackUnderflow)
+	}
+
+	fn push(&mut self, value: i32) -> Result<(), HintingError> {
+		if self.stack.len() >= self.hinting.max_stack_elements {
+			return Err(HintingError::StackOverflow);
+		}
+		self.stack.push(value);
+		Ok(())
+	}
+
+	fn zone_pointer(&self, index: usize) -> usize {
+		self.state.zone_pointers[index]
+	}
+
+	/// Checks a point number against the zone a zone pointer points to.
+	fn point(&self, zone_pointer: usize, point: i32) -> Result<usize, HintingError> {
+		match point >= 0 && (point as usize) < self.zones[self.zone_pointer(zone_pointer)].current.len() {
+			true => Ok(point as usize),
+			false => Err(HintingError::PointOutOfRange(point)),
+		}
+	}
+
+	fn reference_point(&self, reference: usize, zone_pointer: usize) -> Result<usize, HintingError> {
+		self.point(zone_pointer, self.state.reference_points[reference] as i32)
+	}
+
+	fn pop_point(&mut self, zone_pointer: usize) -> Result<usize, HintingError> {
+		let point = self.pop()?;
+		self.point(zone_pointer, point)
+	}
+
+	fn current(&self, zone_pointer: usize, point: usize) -> (i32, i32) {
+		self.zones[self.zone_pointer(zone_pointer)].current[point]
+	}
+
+	fn original(&self, zone_pointer: usize, point: usize) -> (i32, i32) {
+		self.zones[self.zone_pointer(zone_pointer)].original[point]
+	}
+
+	fn project(&self, vector: (i32, i32)) -> i32 {
+		dot(vector, self.state.projection_vector)
+	}
+
+	fn dual_project(&self, vector: (i32, i32)) -> i32 {
+		dot(vector, self.state.dual_projection_vector)
+	}
+
+	fn freedom_dot_projection(&self) -> i32 {
+		let value = dot(self.state.freedom_vector, self.state.projection_vector);
+		match value.abs() < 0x400 {
+			true => 0x4000,
+			false => value,
+		}
+	}
+
+	/// Moves a point along the freedom vector so that its projection changes by `distance`.
+	fn move_point(&mut self, zone_pointer: usize, point: usize, distance: i32, touch: bool) {
+		let (freedom_x, freedom_y) = self.state.freedom_vector;
+		let freedom_dot_projection = self.freedom_dot_projection();
+		let zone = &mut self.zones[self.state.zone_pointers[zone_pointer]];
+		if freedom_x != 0 {
+			zone.current[point].0 += mul_div(distance, freedom_x, freedom_dot_projection);
+			if touch {
+				zone.touched[point] |= TOUCHED_X;
+			}
+		}
+		if freedom_y != 0 {
+			zone.current[point].1 += mul_div(distance, freedom_y, freedom_dot_projection);
+			if touch {
+				zone.touched[point] |= TOUCHED_Y;
+			}
+		}
+	}
+
+	fn move_original(&mut self, zone_pointer: usize, point: usize, distance: i32) {
+		let (freedom_x, freedom_y) = self.state.freedom_vector;
+		let freedom_dot_projection = self.freedom_dot_projection();
+		let zone = &mut self.zones[self.state.zone_pointers[zone_pointer]];
+		zone.original[point].0 += mul_div(distance, freedom_x, freedom_dot_projection);
+		zone.original[point].1 += mul_div(distance, freedom_y, freedom_dot_projection);
+	}
+
+	fn shift_point(&mut self, zone: usize, point: usize, (dx, dy): (i32, i32), touch: bool) {
+		let (freedom_x, freedom_y) = self.state.freedom_vector;
+		let zone = &mut self.zones[zone];
+		zone.current[point].0 += dx;
+		zone.current[point].1 += dy;
+		if touch {
+			if freedom_x != 0 {
+				zone.touched[point] |= TOUCHED_X;
+			}
+			if freedom_y != 0 {
+				zone.touched[point] |= TOUCHED_Y;
+			}
+		}
+	}
+
+	fn round(&self, value: i32) -> i32 {
+		let magnitude = |round: fn(i32) -> i32| match value >= 0 {
+			true => round(value),
+			false => -round(value.wrapping_neg()),
+		};
+		match self.state.round_state {
+			RoundState::Grid => magnitude(|value| (value + 32) & !63),
+			RoundState::HalfGrid => magnitude(|value| (value & !63) + 32),
+			RoundState::DoubleGrid => magnitude(|value| (value + 16) & !31),
+			RoundState::DownToGrid => magnitude(|value| value & !63),
+			RoundState::UpToGrid => magnitude(|value| (value + 63) & !63),
+			RoundState::Off => value,
+			RoundState::Super { period, phase, threshold } => match value >= 0 {
+				true => {
+					let rounded = (value - phase + threshold).div_euclid(period) * period + phase;
+					if rounded < 0 { phase } else { rounded }
+				},
+				false => {
+					let rounded = -((phase - value + threshold).div_euclid(period) * period) - phase;
+					if rounded > 0 { -phase } else { rounded }
+				},
+			},
+		}
+	}
+
+	/// The period, phase and threshold packed into SROUND's and S45ROUND's argument, with a grid of 64 or 45 for √2/2 pixels.
+	fn super_round(selector: i32, grid_period: i32) -> RoundState {
+		let period = match (selector >> 6) & 3 {
+			0 => grid_period / 2,
+			2 => grid_period * 2,
+			_ => grid_period,
+		};
+		let phase = match (selector >> 4) & 3 {
+			0 => 0,
+			1 => period / 4,
+			2 => period / 2,
+			_ => period * 3 / 4,
+		};
+		let threshold = match selector & 15 {
+			0 => period - 1,
+			threshold => (threshold - 4) * period / 8,
+		};
+		RoundState::Super { period: period.max(1), phase, threshold }
+	}
+
+	fn control_value(&self, index: i32) -> i32 {
+		usize::try_from(index).ok().and_then(|index| self.control_values.get(index)).copied().unwrap_or(0)
+	}
+
+	fn set_control_value(&mut self, index: i32, value: i32) {
+		if let Some(control_value) = usize::try_from(index).ok().and_then(|index| self.control_values.get_mut(index)) {
+			*control_value = value;
+		}
+	}
+
+	/// The two points SPVTL and the like set vectors from, the first in zone pointer 1 and the second in zone pointer 2.
+	fn pop_line(&mut self) -> Result<(usize, usize), HintingError> {
+		let second = self.pop_point(2)?;
+		let first = self.pop_point(1)?;
+		Ok((first, second))
+	}
+
+	/// The vector from the second point of a line to the first, between their current positions or their original ones.
+	/// The odd opcodes give the vector perpendicular to the line, turned anticlockwise.
+	fn line_vector(&self, opcode: u8, (first, second): (usize, usize), original: bool) -> (i32, i32) {
+		let (first, second) = match original {
+			true => (self.original(1, first), self.original(2, second)),
+			false => (self.current(1, first), self.current(2, second)),
+		};
+		let (dx, dy) = (first.0 - second.0, first.1 - second.1);
+		match opcode & 1 != 0 {
+			true => normalise(-dy, dx),
+			false => normalise(dx, dy),
+		}
+	}
+
+	/// How far SHP, SHC and SHZ shift points: as far as reference point 1 or 2 has been moved from where it started.
+	fn shift_displacement(&self, opcode: u8) -> Result<(usize, usize, (i32, i32)), HintingError> {
+		let (zone_pointer, reference) = match opcode & 1 != 0 {
+			true => (0, 1),
+			false => (1, 2),
+		};
+		let point = self.reference_point(reference, zone_pointer)?;
+		let current = self.current(zone_pointer, point);
+		let original = self.original(zone_pointer, point);
+		let distance = self.project((current.0 - original.0, current.1 - original.1));
+		let freedom_dot_projection = self.freedom_dot_projection();
+		let (freedom_x, freedom_y) = self.state.freedom_vector;
+		Ok((self.zone_pointer(zone_pointer), point, (mul_div(distance, freedom_x, freedom_dot_projection), mul_div(distance, freedom_y, freedom_dot_projection))))
+	}
+
+	/// How many points an instruction that SLOOP repeats should move, resetting the count. Like FreeType, the instruction is
+	/// skipped when there are fewer points on the stack, as a few fonts end a glyph's program with one that has none left.
+	fn take_loop_count(&mut self) -> i32 {
+		let loop_count = std::mem::replace(&mut self.state.loop_count, 1);
+		match loop_count as usize > self.stack.len() {
+			true => 0,
+			false => loop_count,
+		}
+	}
+
+	fn execute(&mut self, program: Program, start: usize, depth: usize) -> Result<(), HintingError> {
+		if depth > MAX_CALL_DEPTH {
+			return Err(HintingError::CallsNestedTooDeep);
+		}
+		let code = self.code(program);
+		let mut pc = start;
+		while pc < code.len() {
+			if self.instructions_left == 0 {
+				return Err(HintingError::TooManyInstructions);
+			}
+			self.instructions_left -= 1;
+			let opcode = code[pc];
+			let mut next = pc + instruction_length(code, pc)?;
+			match opcode {
+				// SVTCA, SPVTCA and SFVTCA, with the odd opcodes for the x axis
+				0x00..=0x05 => {
+					let axis = if opcode & 1 != 0 { X_AXIS } else { Y_AXIS };
+					if opcode < 0x04 {
+						self.state.projection_vector = axis;
+						self.state.dual_projection_vector = axis;
+					}
+					if !(0x02..0x04).contains(&opcode) {
+						self.state.freedom_vector = axis;
+					}
+				},
+				// SPVTL
+				0x06 | 0x07 => {
+					let line = self.pop_line()?;
+					let vector = self.line_vector(opcode, line, false);
+					self.state.projection_vector = vector;
+					self.state.dual_projection_vector = vector;
+				},
+				// SFVTL
+				0x08 | 0x09 => {
+					let line = self.pop_line()?;
+					self.state.freedom_vector = self.line_vector(opcode, line, false);
+				},
+				// SPVFS and SFVFS
+				0x0A | 0x0B => {
+					let y = self.pop()?;
+					let x = self.pop()?;
+					let vector = normalise(x, y);
+					if opcode == 0x0A {
+						self.state.projection_vector = vector;
+						self.state.dual_projection_vector = vector;
+					} else {
+						self.state.freedom_vector = vector;
+					}
+				},
+				// GPV and GFV
+				0x0C | 0x0D => {
+					let (x, y) = if opcode == 0x0C { self.state.projection_vector } else { self.state.freedom_vector };
+					self.push(x)?;
+					self.push(y)?;
+				},
+				// SFVTPV
+				0x0E => self.state.freedom_vector = self.state.projection_vector,
+				// ISECT: moves a point to where the lines a0 to a1 and b0 to b1 cross
+				0x0F => {
+					let b1 = self.pop_point(0)?;
+					let b0 = self.pop_point(0)?;
+					let a1 = self.pop_point(1)?;
+					let a0 = self.pop_point(1)?;
+					let point = self.pop_point(2)?;
+					let (a0, a1, b0, b1) = (self.current(1, a0), self.current(1, a1), self.current(0, b0), self.current(0, b1));
+					let (a_x, a_y) = ((a1.0 - a0.0) as i64, (a1.1 - a0.1) as i64);
+					let (b_x, b_y) = ((b1.0 - b0.0) as i64, (b1.1 - b0.1) as i64);
+					let denominator = a_x * b_y - a_y * b_x;
+					// Parallel lines put the point halfway between their middles.
+					let intersection = match denominator == 0 {
+						true => ((a0.0 + a1.0 + b0.0 + b1.0) / 4, (a0.1 + a1.1 + b0.1 + b1.1) / 4),
+						false => {
+							let numerator = (b0.0 - a0.0) as i64 * b_y - (b0.1 - a0.1) as i64 * b_x;
+							(a0.0 + (a_x * numerator / denominator) as i32, a0.1 + (a_y * numerator / denominator) as i32)
+						},
+					};
+					let zone = &mut self.zones[self.state.zone_pointers[2]];
+					zone.current[point] = intersection;
+					zone.touched[point] |= TOUCHED_X | TOUCHED_Y;
+				},
+				// SRP0, SRP1 and SRP2
+				0x10..=0x12 => self.state.reference_points[(opcode - 0x10) as usize] = self.pop()?.max(0) as usize,
+				// SZP0, SZP1, SZP2 and SZPS
+				0x13..=0x16 => {
+					let zone = self.pop()?;
+					if zone != 0 && zone != 1 {
+						return Err(HintingError::InvalidZone(zone));
+					}
+					match opcode {
+						0x16 => self.state.zone_pointers = [zone as usize; 3],
+						_ => self.state.zone_pointers[(opcode - 0x13) as usize] = zone as usize,
+					}
+				},
+				// SLOOP
+				0x17 => self.state.loop_count = self.pop()?.clamp(0, 0xFFFF),
+				// RTG
+				0x18 => self.state.round_state = RoundState::Grid,
+				// RTHG
+				0x19 => self.state.round_state = RoundState::HalfGrid,
+				// SMD
+				0x1A => self.state.minimum_distance = self.pop()?,
+				// ELSE, reached at the end of a true IF's instructions
+				0x1B => next = skip_conditional(code, pc + 1, false)?,
+				// JMPR
+				0x1C => {
+					let offset = self.pop()?;
+					next = jump(pc, offset, code.len())?;
+				},
+				// SCVTCI
+				0x1D => self.state.control_value_cut_in = self.pop()?,
+				// SSWCI
+				0x1E => self.state.single_width_cut_in = self.pop()?,
+				// SSW, in font units
+				0x1F => self.state.single_width_value = scale_value(self.pop()?, self.scale),
+				// DUP
+				0x20 => {
+					let value = *self.stack.last().ok_or(HintingError::StackUnderflow)?;
+					self.push(value)?;
+				},
+				// POP
+				0x21 => {
+					self.pop()?;
+				},
+				// CLEAR
+				0x22 => self.stack.clear(),
+				// SWAP
+				0x23 => {
+					let first = self.pop()?;
+					let second = self.pop()?;
+					self.push(first)?;
+					self.push(second)?;
+				},
+				// DEPTH
+				0x24 => self.push(self.stack.len() as i32)?,
+				// CINDEX and MINDEX
+				0x25 | 0x26 => {
+					let index = self.pop()?;
+					if index <= 0 || index as usize > self.stack.len() {
+						return Err(HintingError::StackUnderflow);
+					}
+					let position = self.stack.len() - index as usize;
+					let value = match opcode == 0x25 {
+						true => self.stack[position],
+						false => self.stack.remove(position),
+					};
+					self.push(value)?;
+				},
+				// ALIGNPTS
+				0x27 => {
+					let second = self.pop_point(0)?;
+					let first = self.pop_point(1)?;
+					let (a, b) = (self.current(0, second), self.current(1, first));
+					let distance = self.project((a.0 - b.0, a.1 - b.1)) / 2;
+					self.move_point(1, first, distance, true);
+					self.move_point(0, second, -distance, true);
+				},
+				// UTP
+				0x29 => {
+					let point = self.pop_point(0)?;
+					let (freedom_x, freedom_y) = self.state.freedom_vector;
+					let zone = &mut self.zones[self.state.zone_pointers[0]];
+					if freedom_x != 0 {
+						zone.touched[point] &= !TOUCHED_X;
+					}
+					if freedom_y != 0 {
+						zone.touched[point] &= !TOUCHED_Y;
+					}
+				},
+				// LOOPCALL and CALL
+				0x2A | 0x2B => {
+					let function = self.pop()?;
+					let count = if opcode == 0x2A { self.pop()? } else { 1 };
+					let definition = *self.functions.get(&function).ok_or(HintingError::FunctionNotDefined(function))?;
+					for _ in 0..count {
+						self.execute(definition.program, definition.start, depth + 1)?;
+					}
+				},
+				// FDEF, which only fpgm and prep may use
+				0x2C => {
+					if program == Program::Glyph {
+						return Err(HintingError::InvalidOpcode(opcode));
+					}
+					let function = self.pop()?;
+					self.functions.insert(function, Definition { program, start: pc + 1 });
+					next = skip_definition(code, pc + 1)?;
+				},
+				// ENDF, which returns from a function
+				0x2D => {
+					if depth == 0 {
+						return Err(HintingError::InvalidOpcode(opcode));
+					}
+					return Ok(());
+				},
+				// MDAP, with the odd opcode rounding
+				0x2E | 0x2F => {
+					let point = self.pop_point(0)?;
+					let distance = match opcode & 1 != 0 {
+						true => {
+							let position = self.project(self.current(0, point));
+							self.round(position) - position
+						},
+						false => 0,
+					};
+					self.move_point(0, point, distance, true);
+					self.state.reference_points[0] = point;
+					self.state.reference_points[1] = point;
+				},
+				// IUP, with the odd opcode for the x axis
+				0x30 | 0x31 => self.interpolate_untouched(opcode & 1 != 0),
+				// SHP
+				0x32 | 0x33 => {
+					let (_, _, displacement) = self.shift_displacement(opcode)?;
+					for _ in 0..self.take_loop_count() {
+						let point = self.pop_point(2)?;
+						self.shift_point(self.zone_pointer(2), point, displacement, true);
+					}
+				},
+				// SHC
+				0x34 | 0x35 => {
+					let (reference_zone, reference_point, displacement) = self.shift_displacement(opcode)?;
+					let contour = self.pop()?;
+					let zone = self.zone_pointer(2);
+					let contour_ends = &self.zones[zone].contour_ends;
+					let end = *usize::try_from(contour).ok().and_then(|contour| contour_ends.get(contour)).ok_or(HintingError::ContourOutOfRange(contour))?;
+					let start = if contour == 0 { 0 } else { contour_ends[contour as usize - 1] + 1 };
+					for point in start..=end.min(self.zones[zone].current.len().saturating_sub(1)) {
+						if zone != reference_zone || point != reference_point {
+							self.shift_point(zone, point, displacement, true);
+						}
+					}
+				},
+				// SHZ, which doesn't move the phantom points
+				0x36 | 0x37 => {
+					let (reference_zone, reference_point, displacement) = self.shift_displacement(opcode)?;
+					let zone = self.pop()?;
+					if zone != 0 && zone != 1 {
+						return Err(HintingError::InvalidZone(zone));
+					}
+					let zone = zone as usize;
+					let limit = match zone {
+						GLYPH_ZONE => self.zones[zone].current.len().saturating_sub(PHANTOM_POINTS),
+						_ => self.zones[zone].current.len(),
+					};
+					for point in 0..limit {
+						if zone != reference_zone || point != reference_point {
+							self.shift_point(zone, point, displacement, false);
+						}
+					}
+				},
+				// SHPIX
+				0x38 => {
+					let distance = self.pop()?;
+					let displacement = (mul_2dot14(distance, self.state.freedom_vector.0), mul_2dot14(distance, self.state.freedom_vector.1));
+					for _ in 0..self.take_loop_count() {
+						let point = self.pop_point(2)?;
+						self.shift_point(self.zone_pointer(2), point, displacement, true);
+					}
+				},
+				// IP
+				0x39 => self.interpolate_points()?,
+				// MSIRP, with the odd opcode setting reference point 0 to the point
+				0x3A | 0x3B => {
+					let distance = self.pop()?;
+					let point = self.pop_point(1)?;
+					let reference = self.reference_point(0, 0)?;
+					if self.zone_pointer(1) == TWILIGHT_ZONE {
+						let original = self.original(0, reference);
+						self.zones[TWILIGHT_ZONE].original[point] = original;
+						self.move_original(1, point, distance);
+						self.zones[TWILIGHT_ZONE].current[point] = self.zones[TWILIGHT_ZONE].original[point];
+					}
+					let (current, reference_current) = (self.current(1, point), self.current(0, reference));
+					let current_distance = self.project((current.0 - reference_current.0, current.1 - reference_current.1));
+					self.move_point(1, point, distance - current_distance, true);
+					self.state.reference_points[1] = reference;
+					self.state.reference_points[2] = point;
+					if opcode & 1 != 0 {
+						self.state.reference_points[0] = point;
+					}
+				},
+				// ALIGNRP
+				0x3C => {
+					let reference = self.reference_point(0, 0)?;
+					for _ in 0..self.take_loop_count() {
+						let point = self.pop_point(1)?;
+						let (current, reference_current) = (self.current(1, point), self.current(0, reference));
+						let distance = self.project((current.0 - reference_current.0, current.1 - reference_current.1));
+						self.move_point(1, point, -distance, true);
+					}
+				},
+				// RTDG
+				0x3D => self.state.round_state = RoundState::DoubleGrid,
+				// MIAP, with the odd opcode rounding and applying the cut-in
+				0x3E | 0x3F => {
+					let control_value_index = self.pop()?;
+					let point = self.pop_point(0)?;
+					let mut distance = self.control_value(control_value_index);
+					if self.zone_pointer(0) == TWILIGHT_ZONE {
+						let (freedom_x, freedom_y) = self.state.freedom_vector;
+						let position = (mul_2dot14(distance, freedom_x), mul_2dot14(distance, freedom_y));
+						self.zones[TWILIGHT_ZONE].original[point] = position;
+						self.zones[TWILIGHT_ZONE].current[point] = position;
+					}
+					let current = self.project(self.current(0, point));
+					if opcode & 1 != 0 {
+						if (distance - current).abs() > self.state.control_value_cut_in {
+							distance = current;
+						}
+						distance = self.round(distance);
+					}
+					self.move_point(0, point, distance - current, true);
+					self.state.reference_points[0] = point;
+					self.state.reference_points[1] = point;
+				},
+				// NPUSHB and NPUSHW
+				0x40 | 0x41 => {
+					let count = code[pc + 1] as usize;
+					for index in 0..count {
+						let value = match opcode == 0x40 {
+							true => code[pc + 2 + index] as i32,
+							false => i16::from_be_bytes([code[pc + 2 + index * 2], code[pc + 3 + index * 2]]) as i32,
+						};
+						self.push(value)?;
+					}
+				},
+				// WS
+				0x42 => {
+					let value = self.pop()?;
+					let location = self.pop()?;
+					if let Some(stored) = usize::try_from(location).ok().and_then(|location| self.storage.get_mut(location)) {
+						*stored = value;
+					}
+				},
+				// RS
+				0x43 => {
+					let location = self.pop()?;
+					let value = usize::try_from(location).ok().and_then(|location| self.storage.get(location)).copied().unwrap_or(0);
+					self.push(value)?;
+				},
+				// WCVTP
+				0x44 => {
+					let value = self.pop()?;
+					let index = self.pop()?;
+					self.set_control_value(index, value);
+				},
+				// RCVT
+				0x45 => {
+					let index = self.pop()?;
+					self.push(self.control_value(index))?;
+				},
+				// GC, on the current position or, for the odd opcode, the original one
+				0x46 | 0x47 => {
+					let point = self.pop_point(2)?;
+					let value = match opcode == 0x46 {
+						true => self.project(self.current(2, point)),
+						false => self.dual_project(self.original(2, point)),
+					};
+					self.push(value)?;
+				},
+				// SCFS
+				0x48 => {
+					let value = self.pop()?;
+					let point = self.pop_point(2)?;
+					let current = self.project(self.current(2, point));
+					self.move_point(2, point, value - current, true);
+					if self.zone_pointer(2) == TWILIGHT_ZONE {
+						self.zones[TWILIGHT_ZONE].original[point] = self.zones[TWILIGHT_ZONE].current[point];
+					}
+				},
+				// MD, on the current positions or, for 0x4A, the original ones
+				0x49 | 0x4A => {
+					let second = self.pop_point(1)?;
+					let first = self.pop_point(0)?;
+					let distance = match opcode == 0x49 {
+						true => {
+							let (a, b) = (self.current(0, first), self.current(1, second));
+							self.project((a.0 - b.0, a.1 - b.1))
+						},
+						false => {
+							let (a, b) = (self.original(0, first), self.original(1, second));
+							self.dual_project((a.0 - b.0, a.1 - b.1))
+						},
+					};
+					self.push(distance)?;
+				},
+				// MPPEM and MPS
+				0x4B | 0x4C => self.push(self.pixels_per_em)?,
+				// FLIPON and FLIPOFF
+				0x4D => self.state.auto_flip = true,
+				0x4E => self.state.auto_flip = false,
+				// DEBUG
+				0x4F => {
+					self.pop()?;
+				},
+				// LT, LTEQ, GT, GTEQ, EQ and NEQ
+				0x50..=0x55 => {
+					let second = self.pop()?;
+					let first = self.pop()?;
+					let result = match opcode {
+						0x50 => first < second,
+						0x51 => first <= second,
+						0x52 => first > second,
+						0x53 => first >= second,
+						0x54 => first == second,
+						_ => first != second,
+					};
+					self.push(result as i32)?;
+				},
+				// ODD and EVEN, after rounding
+				0x56 | 0x57 => {
+					let value = self.pop()?;
+					let rounded = self.round(value) & 127;
+					self.push((rounded == if opcode == 0x56 { 64 } else { 0 }) as i32)?;
+				},
+				// IF
+				0x58 => {
+					if self.pop()? == 0 {
+						next = skip_conditional(code, pc + 1, true)?;
+					}
+				},
+				// EIF
+				0x59 => {},
+				// AND and OR
+				0x5A | 0x5B => {
+					let second = self.pop()? != 0;
+					let first = self.pop()? != 0;
+					self.push(if opcode == 0x5A { first && second } else { first || second } as i32)?;
+				},
+				// NOT
+				0x5C => {
+					let value = self.pop()?;
+					self.push((value == 0) as i32)?;
+				},
+				// DELTAP1, DELTAP2 and DELTAP3
+				0x5D | 0x71 | 0x72 => {
+					let ppem_offset = match opcode { 0x5D => 0, 0x71 => 16, _ => 32 };
+					let count = self.pop()?;
+					// Some fonts give a higher count than they push pairs for, which FreeType stops at, so the same glyphs draw here.
+					for _ in 0..count.min(self.stack.len() as i32 / 2) {
+						let point = self.pop()?;
+						let argument = self.pop()?;
+						// Points past the end are skipped rather than failing the glyph, as some fonts have deltas for other versions of it.
+						let Ok(point) = self.point(0, point) else {
+							continue;
+						};
+						if let Some(step) = self.delta_step(argument, ppem_offset) {
+							self.move_point(0, point, step, true);
+						}
+					}
+				},
+				// SDB and SDS
+				0x5E => self.state.delta_base = self.pop()?,
+				0x5F => self.state.delta_shift = self.pop()?.clamp(0, 6),
+				// ADD, SUB, DIV and MUL
+				0x60..=0x63 => {
+					let second = self.pop()?;
+					let first = self.pop()?;
+					let result = match opcode {
+						0x60 => first.wrapping_add(second),
+						0x61 => first.wrapping_sub(second),
+						0x62 => {
+							if second == 0 {
+								return Err(HintingError::DivideByZero);
+							}
+							((first as i64 * 64) / second as i64) as i32
+						},
+						_ => mul_div(first, second, 64),
+					};
+					self.push(result)?;
+				},
+				// ABS, NEG, FLOOR and CEILING
+				0x64..=0x67 => {
+					let value = self.pop()?;
+					let result = match opcode {
+						0x64 => value.wrapping_abs(),
+						0x65 => value.wrapping_neg(),
+						0x66 => value & !63,
+						_ => value.wrapping_add(63) & !63,
+					};
+					self.push(result)?;
+				},
+				// ROUND, whose distance type is about engine compensation, which is nothing here
+				0x68..=0x6B => {
+					let value = self.pop()?;
+					self.push(self.round(value))?;
+				},
+				// NROUND
+				0x6C..=0x6F => {},
+				// WCVTF, in font units
+				0x70 => {
+					let value = self.pop()?;
+					let index = self.pop()?;
+					self.set_control_value(index, scale_value(value, self.scale));
+				},
+				// DELTAC1, DELTAC2 and DELTAC3
+				0x73..=0x75 => {
+					let ppem_offset = (opcode - 0x73) as i32 * 16;
+					let count = self.pop()?;
+					for _ in 0..count.min(self.stack.len() as i32 / 2) {
+						let index = self.pop()?;
+						let argument = self.pop()?;
+						if let Some(step) = self.delta_step(argument, ppem_offset) {
+							self.set_control_value(index, self.control_value(index) + step);
+						}
+					}
+				},
+				// SROUND and S45ROUND
+				0x76 => self.state.round_state = Self::super_round(self.pop()?, 64),
+				0x77 => self.state.round_state = Self::super_round(self.pop()?, 45),
+				// JROT and JROF
+				0x78 | 0x79 => {
+					let condition = self.pop()? != 0;
+					let offset = self.pop()?;
+					if condition == (opcode == 0x78) {
+						next = jump(pc, offset, code.len())?;
+					}
+				},
+				// ROFF, RUTG and RDTG
+				0x7A => self.state.round_state = RoundState::Off,
+				0x7C => self.state.round_state = RoundState::UpToGrid,
+				0x7D => self.state.round_state = RoundState::DownToGrid,
+				// SANGW and AA, which are obsolete, and SCANCTRL and SCANTYPE, as there's no dropout control to set up
+				0x7E | 0x7F | 0x85 | 0x8D => {
+					self.pop()?;
+				},
+				// FLIPPT
+				0x80 => {
+					for _ in 0..self.take_loop_count() {
+						let point = self.pop_point(0)?;
+						let on_curve = &mut self.zones[self.state.zone_pointers[0]].on_curve;
+						if let Some(on_curve) = on_curve.get_mut(point) {
+							*on_curve = !*on_curve;
+						}
+					}
+				},
+				// FLIPRGON and FLIPRGOFF
+				0x81 | 0x82 => {
+					let high = self.pop_point(0)?;
+					let low = self.pop_point(0)?;
+					let on_curve = &mut self.zones[self.state.zone_pointers[0]].on_curve;
+					for point in low..=high.min(on_curve.len().saturating_sub(1)) {
+						on_curve[point] = opcode == 0x81;
+					}
+				},
+				// SDPVTL, which also sets the dual projection vector from the points' original positions
+				0x86 | 0x87 => {
+					let line = self.pop_line()?;
+					self.state.projection_vector = self.line_vector(opcode, line, false);
+					self.state.dual_projection_vector = self.line_vector(opcode, line, true);
+				},
+				// GETINFO: the version of the rasteriser this acts like, and that it draws in greyscale
+				0x88 => {
+					let selector = self.pop()?;
+					let mut result = 0;
+					if selector & 1 != 0 {
+						result |= 35;
+					}
+					if selector & 32 != 0 {
+						result |= 1 << 12;
+					}
+					self.push(result)?;
+				},
+				// IDEF, which only fpgm and prep may use
+				0x89 => {
+					if program == Program::Glyph {
+						return Err(HintingError::InvalidOpcode(opcode));
+					}
+					let defined_opcode = self.pop()?;
+					self.instruction_definitions.insert(defined_opcode as u8, Definition { program, start: pc + 1 });
+					next = skip_definition(code, pc + 1)?;
+				},
+				// ROLL, which brings the third element to the top
+				0x8A => {
+					let first = self.pop()?;
+					let second = self.pop()?;
+					let third = self.pop()?;
+					self.push(second)?;
+					self.push(first)?;
+					self.push(third)?;
+				},
+				// MAX and MIN
+				0x8B | 0x8C => {
+					let second = self.pop()?;
+					let first = self.pop()?;
+					self.push(if opcode == 0x8B { first.max(second) } else { first.min(second) })?;
+				},
+				// INSTCTRL, which only prep may use
+				0x8E => {
+					let selector = self.pop()?;
+					let value = self.pop()?;
+					if self.program == Program::ControlValue && (1..=3).contains(&selector) {
+						let flag = 1 << (selector - 1);
+						self.state.instruct_control = match value != 0 {
+							true => self.state.instruct_control | flag,
+							false => self.state.instruct_control & !flag,
+						};
+					}
+				},
+				// PUSHB and PUSHW
+				0xB0..=0xB7 => {
+					for index in 0..=(opcode - 0xB0) as usize {
+						self.push(code[pc + 1 + index] as i32)?;
+					}
+				},
+				0xB8..=0xBF => {
+					for index in 0..=(opcode - 0xB8) as usize {
+						self.push(i16::from_be_bytes([code[pc + 1 + index * 2], code[pc + 2 + index * 2]]) as i32)?;
+					}
+				},
+				// MDRP
+				0xC0..=0xDF => self.move_direct_relative_point(opcode)?,
+				// MIRP
+				0xE0..=0xFF => self.move_indirect_relative_point(opcode)?,
+				// Anything else is only valid if the font defined it with IDEF.
+				_ => {
+					let definition = *self.instruction_definitions.get(&opcode).ok_or(HintingError::InvalidOpcode(opcode))?;
+					self.execute(definition.program, definition.start, depth + 1)?;
+				},
+			}
+			pc = next;
+		}
+		// Only the end of a program, not of a function, can be reached without an ENDF.
+		match depth {
+			0 => Ok(()),
+			_ => Err(HintingError::UnexpectedEnd),
+		}
+	}
+
+	/// How far a DELTAP or DELTAC argument moves by, if it applies at this size.
+	fn delta_step(&self, argument: i32, ppem_offset: i32) -> Option<i32> {
+		let pixels_per_em = ((argument >> 4) & 15) + self.state.delta_base + ppem_offset;
+		if pixels_per_em != self.pixels_per_em {
+			return None;
+		}
+		let mut step = (argument & 15) - 8;
+		if step >= 0 {
+			step += 1;
+		}
+		Some(step * 64 / (1 << self.state.delta_shift))
+	}
+
+	/// MDRP: keeps a point the same distance from reference point 0 as it was in the outline, with the flags in the opcode's
+	/// low bits choosing whether to set reference point 0 to it, keep the minimum distance and round, and the distance type.
+	fn move_direct_relative_point(&mut self, opcode: u8) -> Result<(), HintingError> {
+		let point = self.pop_point(1)?;
+		let reference = self.reference_point(0, 0)?;
+		let (original, reference_original) = (self.original(1, point), self.original(0, reference));
+		let mut original_distance = self.dual_project((original.0 - reference_original.0, original.1 - reference_original.1));
+		if (original_distance - self.state.single_width_value).abs() < self.state.single_width_cut_in {
+			original_distance = if original_distance >= 0 { self.state.single_width_value } else { -self.state.single_width_value };
+		}
+		let mut distance = match opcode & 0x04 != 0 {
+			true => self.round(original_distance),
+			false => original_distance,
+		};
+		if opcode & 0x08 != 0 {
+			distance = self.keep_minimum_distance(original_distance, distance);
+		}
+		let (current, reference_current) = (self.current(1, point), self.current(0, reference));
+		let current_distance = self.project((current.0 - reference_current.0, current.1 - reference_current.1));
+		self.move_point(1, point, distance - current_distance, true);
+		self.finish_relative_move(opcode, reference, point);
+		Ok(())
+	}
+
+	/// MIRP: as MDRP, but with the distance from a control value, flipped to the outline's direction if auto flip is on,
+	/// and the outline's own distance used instead when the two differ by more than the cut-in.
+	fn move_indirect_relative_point(&mut self, opcode: u8) -> Result<(), HintingError> {
+		let control_value_index = self.pop()?;
+		let point = self.pop_point(1)?;
+		let reference = self.reference_point(0, 0)?;
+		let mut control_value = self.control_value(control_value_index);
+		if (control_value - self.state.single_width_value).abs() < self.state.single_width_cut_in {
+			control_value = if control_value >= 0 { self.state.single_width_value } else { -self.state.single_width_value };
+		}
+		// Points in the twilight zone are put at the control value's distance, as they have no position of their own.
+		if self.zone_pointer(1) == TWILIGHT_ZONE {
+			let reference_original = self.original(0, reference);
+			let (freedom_x, freedom_y) = self.state.freedom_vector;
+			let position = (reference_original.0 + mul_2dot14(control_value, freedom_x), reference_original.1 + mul_2dot14(control_value, freedom_y));
+			self.zones[TWILIGHT_ZONE].original[point] = position;
+			self.zones[TWILIGHT_ZONE].current[point] = position;
+		}
+		let (original, reference_original) = (self.original(1, point), self.original(0, reference));
+		let original_distance = self.dual_project((original.0 - reference_original.0, original.1 - reference_original.1));
+		let (current, reference_current) = (self.current(1, point), self.current(0, reference));
+		let current_distance = self.project((current.0 - reference_current.0, current.1 - reference_current.1));
+		if self.state.auto_flip && (original_distance ^ control_value) < 0 {
+			control_value = -control_value;
+		}
+		let mut distance = match opcode & 0x04 != 0 {
+			true => {
+				if self.zone_pointer(0) == self.zone_pointer(1) && (control_value - original_distance).abs() > self.state.control_value_cut_in {
+					control_value = original_distance;
+				}
+				self.round(control_value)
+			},
+			false => control_value,
+		};
+		if opcode & 0x08 != 0 {
+			distance = self.keep_minimum_distance(original_distance, distance);
+		}
+		self.move_point(1, point, distance - current_distance, true);
+		self.finish_relative_move(opcode, reference, point);
+		Ok(())
+	}
+
+	fn keep_minimum_distance(&self, original_distance: i32, distance: i32) -> i32 {
+		let minimum_distance = self.state.minimum_distance;
+		match original_distance >= 0 {
+			true => distance.max(minimum_distance),
+			false => distance.min(-minimum_distance),
+		}
+	}
+
+	fn finish_relative_move(&mut self, opcode: u8, reference: usize, point: usize) {
+		self.state.reference_points[1] = reference;
+		self.state.reference_points[2] = point;
+		if opcode & 0x10 != 0 {
+			self.state.reference_points[0] = point;
+		}
+	}
+
+	/// IP: keeps points in the same place relative to reference points 1 and 2 as they were in the outline.
+	fn interpolate_points(&mut self) -> Result<(), HintingError> {
+		let first = self.reference_point(1, 0)?;
+		let second = self.reference_point(2, 1)?;
+		let (first_original, second_original) = (self.original(0, first), self.original(1, second));
+		let (first_current, second_current) = (self.current(0, first), self.current(1, second));
+		let original_range = self.dual_project((second_original.0 - first_original.0, second_original.1 - first_original.1));
+		let current_range = self.project((second_current.0 - first_current.0, second_current.1 - first_current.1));
+		for _ in 0..self.take_loop_count() {
+			let point = self.pop_point(2)?;
+			let (original, current) = (self.original(2, point), self.current(2, point));
+			let original_distance = self.dual_project((original.0 - first_original.0, original.1 - first_original.1));
+			let current_distance = self.project((current.0 - first_current.0, current.1 - first_current.1));
+			let new_distance = match original_range {
+				0 => original_distance,
+				_ => mul_div(original_distance, current_range, original_range),
+			};
+			self.move_point(2, point, new_distance - current_distance, true);
+		}
+		Ok(())
+	}
+
+	/// IUP: moves the points no instruction touched on an axis along with the touched points either side of them on their contour,
+	/// interpolating between them, or shifting with the nearer one where the point is outside them.
+	fn interpolate_untouched(&mut self, x_axis: bool) {
+		let flag = if x_axis { TOUCHED_X } else { TOUCHED_Y };
+		let coordinate = |point: (i32, i32)| if x_axis { point.0 } else { point.1 };
+		let zone = &mut self.zones[GLYPH_ZONE];
+		let contour_ends = zone.contour_ends.clone();
+		let mut start = 0;
+		for end in contour_ends {
+			if end >= zone.current.len() || end < start {
+				break;
+			}
+			let touched: Vec<usize> = (start..=end).filter(|point| zone.touched[*point] & flag != 0).collect();
+			let set = |zone: &mut Zone, point: usize, value: i32| match x_axis {
+				true => zone.current[point].0 = value,
+				false => zone.current[point].1 = value,
+			};
+			match touched.len() {
+				0 => {},
+				1 => {
+					let shift = coordinate(zone.current[touched[0]]) - coordinate(zone.original[touched[0]]);
+					for point in (start..=end).filter(|point| *point != touched[0]) {
+						let value = coordinate(zone.original[point]) + shift;
+						set(zone, point, value);
+					}
+				},
+				_ => {
+					for (index, &first) in touched.iter().enumerate() {
+						let second = touched[(index + 1) % touched.len()];
+						let (first_original, second_original) = (coordinate(zone.original[first]), coordinate(zone.original[second]));
+						let (first_current, second_current) = (coordinate(zone.current[first]), coordinate(zone.current[second]));
+						let ((low_original, low_current), (high_original, high_current)) = match first_original <= second_original {
+							true => ((first_original, first_current), (second_original, second_current)),
+							false => ((second_original, second_current), (first_original, first_current)),
+						};
+						// The points between the two, going round the contour.
+						let mut point = if first == end { start } else { first + 1 };
+						while point != second {
+							let original = coordinate(zone.original[point]);
+							let value = match original {
+								original if original <= low_original => original + low_current - low_original,
+								original if original >= high_original => original + high_current - high_original,
+								original => low_current + mul_div(original - low_original, high_current - low_current, high_original - low_original),
+							};
+							set(zone, point, value);
+							point = if point == end { start } else { point + 1 };
+						}
+					}
+				},
+			}
+			start = end + 1;
+		}
+	}
+}
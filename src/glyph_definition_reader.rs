@@ -0,0 +1,146 @@
+use std::io::{self, Read, Seek};
+
+use crate::layout_reader::{read_optional_class_definition, read_u16_array, ClassDefinition, Coverage};
+use crate::ttf_reader::{FromTTFReader, TableTag, TrueTypeFontReader, TrueTypeFontReaderError};
+
+/// What part a glyph plays in text, which layout lookups can choose to skip over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlyphClass {
+	Base,
+	Ligature, // Several characters joined into one glyph
+	Mark, // Such as an accent, drawn on top of another glyph
+	Component, // Part of a character made of several glyphs
+}
+
+/// Where the caret can go inside a ligature, between two of its components.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CaretValue {
+	Coordinate(i16), // Formats 1 and 3, in font units along the baseline
+	ContourPoint(u16), // Format 2, at the point with this index in the glyph's outline, as hinted
+}
+
+pub struct GlyphDefinitionTable {
+	pub glyph_classes: Option<ClassDefinition>,
+	pub ligature_caret_coverage: Coverage,
+	pub ligature_carets: Vec<Vec<CaretValue>>,
+	pub mark_attachment_classes: ClassDefinition,
+	pub mark_glyph_sets: Vec<Coverage>,
+}
+
+impl GlyphDefinitionTable {
+	pub fn glyph_class(&self, glyph_id: u16) -> Option<GlyphClass> {
+		match self.glyph_classes.as_ref()?.class(glyph_id) {
+			1 => Some(GlyphClass::Base),
+			2 => Some(GlyphClass::Ligature),
+			3 => Some(GlyphClass::Mark),
+			4 => Some(GlyphClass::Component),
+			_ => None,
+		}
+	}
+
+	pub fn ligature_carets(&self, glyph_id: u16) -> &[CaretValue] {
+		self.ligature_caret_coverage.index(glyph_id)
+			.and_then(|index| self.ligature_carets.get(index as usize))
+			.map_or(&[], |carets| carets.as_slice())
+	}
+
+	/// The mark attachment class of a mark, which lookups can use to only see marks of that class. Other glyphs are in class 0.
+	pub fn mark_attachment_class(&self, glyph_id: u16) -> u16 {
+		self.mark_attachment_classes.class(glyph_id)
+	}
+
+	pub fn in_mark_glyph_set(&self, mark_glyph_set: u16, glyph_id: u16) -> bool {
+		self.mark_glyph_sets.get(mark_glyph_set as usize).is_some_and(|coverage| coverage.contains(glyph_id))
+	}
+}
+
+fn read_ligature_carets<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64) -> Result<(Coverage, Vec<Vec<CaretValue>>), TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	let coverage_offset: u16 = ttf_reader.read_bytes()?;
+	let ligature_glyph_offsets = read_u16_array(ttf_reader)?;
+
+	let mut ligature_carets = Vec::with_capacity(ligature_glyph_offsets.len());
+	for ligature_glyph_offset in ligature_glyph_offsets {
+		let ligature_glyph_offset = offset + ligature_glyph_offset as u64;
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(ligature_glyph_offset))?;
+		let caret_value_offsets = read_u16_array(ttf_reader)?;
+		let mut carets = Vec::with_capacity(caret_value_offsets.len());
+		for caret_value_offset in caret_value_offsets {
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(ligature_glyph_offset + caret_value_offset as u64))?;
+			let format: u16 = ttf_reader.read_bytes()?;
+			carets.push(match format {
+				// Format 3 also has a device table to fine tune the coordinate at particular sizes, which isn't done.
+				1 | 3 => CaretValue::Coordinate(ttf_reader.read_bytes()?),
+				2 => CaretValue::ContourPoint(ttf_reader.read_bytes()?),
+				_ => return Err(TrueTypeFontReaderError::InvalidData(TableTag::GlyphDefinition, "caret value should be format 1, 2 or 3")),
+			});
+		}
+		ligature_carets.push(carets);
+	}
+
+	Ok((ttf_reader.read((offset + coverage_offset as u64, TableTag::GlyphDefinition))?, ligature_carets))
+}
+
+fn read_mark_glyph_sets<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64) -> Result<Vec<Coverage>, TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	let format: u16 = ttf_reader.read_bytes()?;
+	if format != 1 {
+		return Err(TrueTypeFontReaderError::InvalidData(TableTag::GlyphDefinition, "mark glyph sets should be format 1"));
+	}
+	let mark_glyph_set_count: u16 = ttf_reader.read_bytes()?;
+	let mut coverage_offsets: Vec<u32> = Vec::with_capacity(mark_glyph_set_count as usize);
+	for _ in 0..mark_glyph_set_count {
+		coverage_offsets.push(ttf_reader.read_bytes()?);
+	}
+
+	let mut mark_glyph_sets = Vec::with_capacity(coverage_offsets.len());
+	for coverage_offset in coverage_offsets {
+		mark_glyph_sets.push(ttf_reader.read((offset + coverage_offset as u64, TableTag::GlyphDefinition))?);
+	}
+	Ok(mark_glyph_sets)
+}
+
+impl FromTTFReader for GlyphDefinitionTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<GlyphDefinitionTable, TrueTypeFontReaderError> {
+		let offset = offset as u64;
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+
+		let major_version: u16 = ttf_reader.read_bytes()?;
+		let minor_version: u16 = ttf_reader.read_bytes()?;
+		if major_version != 1 || ![0, 2, 3].contains(&minor_version) {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::GlyphDefinition, major_version, minor_version));
+		}
+		let glyph_class_definition_offset: u16 = ttf_reader.read_bytes()?;
+		ttf_reader.skip(2)?; // attachment point list offset, which is only needed for hinting
+		let ligature_caret_list_offset: u16 = ttf_reader.read_bytes()?;
+		let mark_attachment_class_definition_offset: u16 = ttf_reader.read_bytes()?;
+		let mark_glyph_sets_offset: u16 = match minor_version {
+			0 => 0,
+			_ => ttf_reader.read_bytes()?,
+		};
+		// Version 1.3 adds an item variation store, for carets in variable fonts, which isn't applied yet.
+
+		let glyph_classes = match glyph_class_definition_offset {
+			0 => None,
+			glyph_class_definition_offset => Some(ttf_reader.read((offset + glyph_class_definition_offset as u64, TableTag::GlyphDefinition))?),
+		};
+		let (ligature_caret_coverage, ligature_carets) = match ligature_caret_list_offset {
+			0 => (Coverage::Glyphs(Vec::new()), Vec::new()),
+			ligature_caret_list_offset => read_ligature_carets(ttf_reader, offset + ligature_caret_list_offset as u64)?,
+		};
+		let mark_glyph_sets = match mark_glyph_sets_offset {
+			0 => Vec::new(),
+			mark_glyph_sets_offset => read_mark_glyph_sets(ttf_reader, offset + mark_glyph_sets_offset as u64)?,
+		};
+
+		Ok(GlyphDefinitionTable {
+			glyph_classes,
+			ligature_caret_coverage,
+			ligature_carets,
+			mark_attachment_classes: read_optional_class_definition(ttf_reader, offset, mark_attachment_class_definition_offset, TableTag::GlyphDefinition)?,
+			mark_glyph_sets,
+		})
+	}
+}
@@ -0,0 +1,412 @@
+use std::io::{self, Read, Seek};
+
+use crate::glyph_definition_reader::GlyphDefinitionTable;
+use crate::layout_reader::{read_u16_array, ClassDefinition, Coverage, GlyphFilter, LayoutTable, LookupSubtable, read_optional_class_definition};
+use crate::ttf_reader::{TableTag, TrueTypeFontReader, TrueTypeFontReaderError};
+
+/// The features applied when laying out horizontal text, unless asked for otherwise.
+pub const DEFAULT_POSITIONING_FEATURES: [[u8; 4]; 7] = [*b"kern", *b"mark", *b"mkmk", *b"curs", *b"dist", *b"abvm", *b"blwm"];
+
+/// The features added when laying out vertical text, which kern glyphs along the column instead.
+pub const VERTICAL_POSITIONING_FEATURES: [[u8; 4]; 1] = [*b"vkrn"];
+
+pub type GlyphPositioningTable = LayoutTable<PositioningSubtable>;
+
+/// Adjustments to where a glyph is drawn and how far it moves the pen, in font units.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ValueRecord {
+	pub x_placement: i16,
+	pub y_placement: i16,
+	pub x_advance: i16,
+	pub y_advance: i16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Anchor {
+	pub x: i16,
+	pub y: i16,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PairValueRecord {
+	pub second_glyph_id: u16,
+	pub first_value: ValueRecord,
+	pub second_value: ValueRecord,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct MarkRecord {
+	pub mark_class: u16,
+	pub mark_anchor: Anchor,
+}
+
+/// Attaches marks to the glyph before them, either a base glyph or another mark.
+#[derive(Debug)]
+pub struct MarkAttachment {
+	pub mark_coverage: Coverage,
+	pub base_coverage: Coverage,
+	pub marks: Vec<MarkRecord>,
+	pub bases: Vec<Vec<Option<Anchor>>>, // For each base glyph, an anchor for each mark class
+}
+
+#[derive(Debug)]
+pub struct MarkToLigatureAttachment {
+	pub mark_coverage: Coverage,
+	pub ligature_coverage: Coverage,
+	pub marks: Vec<MarkRecord>,
+	pub ligatures: Vec<Vec<Vec<Option<Anchor>>>>, // For each ligature, for each of its components, an anchor for each mark class
+}
+
+#[derive(Debug)]
+pub enum PositioningSubtable {
+	SingleAdjustment { coverage: Coverage, value: ValueRecord }, // Type 1 format 1, the same adjustment for every glyph
+	SingleAdjustmentList { coverage: Coverage, values: Vec<ValueRecord> }, // Type 1 format 2
+	PairAdjustmentGlyphs { coverage: Coverage, pair_sets: Vec<Vec<PairValueRecord>>, second_has_value: bool }, // Type 2 format 1
+	PairAdjustmentClasses { coverage: Coverage, first_classes: ClassDefinition, second_classes: ClassDefinition, second_class_count: u16, values: Vec<(ValueRecord, ValueRecord)>, second_has_value: bool }, // Type 2 format 2
+	CursiveAttachment { coverage: Coverage, entry_exits: Vec<(Option<Anchor>, Option<Anchor>)> }, // Type 3
+	MarkToBase(MarkAttachment), // Type 4
+	MarkToLigature(MarkToLigatureAttachment), // Type 5
+	MarkToMark(MarkAttachment), // Type 6
+	Unsupported(u16), // Lookup type
+}
+
+/// How a glyph is moved from where its advance alone would put it, in font units.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GlyphPosition {
+	pub x_placement: i32,
+	pub y_placement: i32,
+	pub x_advance: i32,
+	pub y_advance: i32,
+}
+
+impl GlyphPosition {
+	fn adjust(&mut self, value: ValueRecord) {
+		self.x_placement += value.x_placement as i32;
+		self.y_placement += value.y_placement as i32;
+		self.x_advance += value.x_advance as i32;
+		self.y_advance += value.y_advance as i32;
+	}
+}
+
+/// Moves a mark so its anchor sits on the base glyph's, and stops it moving the pen.
+fn attach_mark(positions: &mut [GlyphPosition], advances: &[i32], base_index: usize, mark_index: usize, base_anchor: Anchor, mark_anchor: Anchor) {
+	let advance_from_base: i32 = (base_index..mark_index).map(|index| advances[index] + positions[index].x_advance).sum();
+	let base_position = positions[base_index];
+	positions[mark_index] = GlyphPosition {
+		x_placement: base_position.x_placement + base_anchor.x as i32 - mark_anchor.x as i32 - advance_from_base,
+		y_placement: base_position.y_placement + base_anchor.y as i32 - mark_anchor.y as i32,
+		x_advance: -advances[mark_index],
+		y_advance: 0,
+	};
+}
+
+impl PositioningSubtable {
+	/// Applies the subtable to the glyph at `index` if it covers it, returning how many glyphs to move on by.
+	fn apply(&self, glyph_ids: &[u16], advances: &[i32], positions: &mut [GlyphPosition], index: usize, filter: &GlyphFilter) -> Option<usize> {
+		let glyph_id = glyph_ids[index];
+		match self {
+			PositioningSubtable::SingleAdjustment { coverage, value } => {
+				coverage.index(glyph_id)?;
+				positions[index].adjust(*value);
+				Some(1)
+			},
+			PositioningSubtable::SingleAdjustmentList { coverage, values } => {
+				let value = values.get(coverage.index(glyph_id)? as usize)?;
+				positions[index].adjust(*value);
+				Some(1)
+			},
+			PositioningSubtable::PairAdjustmentGlyphs { coverage, pair_sets, second_has_value } => {
+				let pair_set = pair_sets.get(coverage.index(glyph_id)? as usize)?;
+				let second_index = filter.next(glyph_ids, index)?;
+				let pair = pair_set.iter().find(|pair| pair.second_glyph_id == glyph_ids[second_index])?;
+				positions[index].adjust(pair.first_value);
+				positions[second_index].adjust(pair.second_value);
+				// The second glyph can start another pair, unless it was adjusted itself.
+				Some(second_index - index + *second_has_value as usize)
+			},
+			PositioningSubtable::PairAdjustmentClasses { coverage, first_classes, second_classes, second_class_count, values, second_has_value } => {
+				coverage.index(glyph_id)?;
+				let second_index = filter.next(glyph_ids, index)?;
+				let value_index = first_classes.class(glyph_id) as usize * *second_class_count as usize + second_classes.class(glyph_ids[second_index]) as usize;
+				let (first_value, second_value) = values.get(value_index)?;
+				positions[index].adjust(*first_value);
+				positions[second_index].adjust(*second_value);
+				Some(second_index - index + *second_has_value as usize)
+			},
+			PositioningSubtable::CursiveAttachment { coverage, entry_exits } => {
+				let (_, exit_anchor) = entry_exits.get(coverage.index(glyph_id)? as usize)?;
+				let exit_anchor = (*exit_anchor)?;
+				let next_index = filter.next(glyph_ids, index)?;
+				let (entry_anchor, _) = entry_exits.get(coverage.index(*glyph_ids.get(next_index)?)? as usize)?;
+				let entry_anchor = (*entry_anchor)?;
+
+				// The pen stops at this glyph's exit, and the next glyph is moved so its entry lines up with it.
+				positions[index].x_advance = exit_anchor.x as i32 + positions[index].x_placement - advances[index];
+				let entry_offset = entry_anchor.x as i32 + positions[next_index].x_placement;
+				positions[next_index].x_advance -= entry_offset;
+				positions[next_index].x_placement -= entry_offset;
+				positions[next_index].y_placement = positions[index].y_placement + exit_anchor.y as i32 - entry_anchor.y as i32;
+				Some(1)
+			},
+			PositioningSubtable::MarkToBase(attachment) => {
+				let mark = attachment.marks.get(attachment.mark_coverage.index(glyph_id)? as usize)?;
+				// Other marks already attached to the base sit between it and this mark.
+				let base_index = (0..index).rev().find(|&base_index| !filter.is_mark(glyph_ids[base_index], &attachment.mark_coverage))?;
+				let base_anchors = attachment.bases.get(attachment.base_coverage.index(glyph_ids[base_index])? as usize)?;
+				let base_anchor = (*base_anchors.get(mark.mark_class as usize)?)?;
+				attach_mark(positions, advances, base_index, index, base_anchor, mark.mark_anchor);
+				Some(1)
+			},
+			PositioningSubtable::MarkToLigature(attachment) => {
+				let mark = attachment.marks.get(attachment.mark_coverage.index(glyph_id)? as usize)?;
+				let ligature_index = (0..index).rev().find(|&ligature_index| !filter.is_mark(glyph_ids[ligature_index], &attachment.mark_coverage))?;
+				let components = attachment.ligatures.get(attachment.ligature_coverage.index(glyph_ids[ligature_index])? as usize)?;
+				// Which component a mark belongs to isn't tracked, so marks go on the last one, as they follow it in the text.
+				let ligature_anchor = (*components.last()?.get(mark.mark_class as usize)?)?;
+				attach_mark(positions, advances, ligature_index, index, ligature_anchor, mark.mark_anchor);
+				Some(1)
+			},
+			PositioningSubtable::MarkToMark(attachment) => {
+				let mark = attachment.marks.get(attachment.mark_coverage.index(glyph_id)? as usize)?;
+				let base_index = filter.previous(glyph_ids, index)?;
+				let base_anchors = attachment.bases.get(attachment.base_coverage.index(glyph_ids[base_index])? as usize)?;
+				let base_anchor = (*base_anchors.get(mark.mark_class as usize)?)?;
+				attach_mark(positions, advances, base_index, index, base_anchor, mark.mark_anchor);
+				Some(1)
+			},
+			PositioningSubtable::Unsupported(_) => None,
+		}
+	}
+}
+
+impl GlyphPositioningTable {
+	/// Runs the given lookups over a line of glyphs with the given advance widths, giving each glyph's adjustments.
+	/// The glyph definitions let lookups skip over glyphs, such as marks between a kerned pair.
+	pub fn position(&self, glyph_ids: &[u16], advances: &[i32], lookup_indices: &[u16], glyph_definition: Option<&GlyphDefinitionTable>) -> Vec<GlyphPosition> {
+		let mut positions = vec![GlyphPosition::default(); glyph_ids.len()];
+		for &lookup_index in lookup_indices {
+			let Some(lookup) = self.lookups.get(lookup_index as usize) else {
+				continue;
+			};
+			let filter = lookup.glyph_filter(glyph_definition);
+			let mut index = 0;
+			while index < glyph_ids.len() {
+				if filter.skips(glyph_ids[index]) {
+					index += 1;
+					continue;
+				}
+				let consumed = lookup.subtables.iter().find_map(|subtable| subtable.apply(glyph_ids, advances, &mut positions, index, &filter));
+				index += consumed.unwrap_or(1);
+			}
+		}
+		positions
+	}
+}
+
+fn read_value_record<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, value_format: u16) -> Result<ValueRecord, TrueTypeFontReaderError> {
+	let mut fields = [0i16; 4];
+	for (bit, field) in fields.iter_mut().enumerate() {
+		if value_format & (1 << bit) != 0 {
+			*field = ttf_reader.read_bytes()?;
+		}
+	}
+	// Device tables fine tune the adjustments at particular sizes, which isn't done.
+	ttf_reader.skip(2 * (value_format & 0x00F0).count_ones() as usize)?;
+	Ok(ValueRecord {
+		x_placement: fields[0],
+		y_placement: fields[1],
+		x_advance: fields[2],
+		y_advance: fields[3],
+	})
+}
+
+fn read_anchor<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, base_offset: u64, offset: u16) -> Result<Option<Anchor>, TrueTypeFontReaderError> {
+	if offset == 0 {
+		return Ok(None);
+	}
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(base_offset + offset as u64))?;
+	let format: u16 = ttf_reader.read_bytes()?;
+	if !(1..=3).contains(&format) {
+		return Err(TrueTypeFontReaderError::InvalidData(TableTag::GlyphPositioning, "anchor table should be format 1, 2 or 3"));
+	}
+	// Formats 2 and 3 add a contour point and device tables for hinting, which aren't needed for the design position.
+	Ok(Some(Anchor {
+		x: ttf_reader.read_bytes()?,
+		y: ttf_reader.read_bytes()?,
+	}))
+}
+
+fn read_mark_array<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64) -> Result<Vec<MarkRecord>, TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	let mark_count: u16 = ttf_reader.read_bytes()?;
+	let mut records: Vec<(u16, u16)> = Vec::with_capacity(mark_count as usize);
+	for _ in 0..mark_count {
+		records.push((ttf_reader.read_bytes()?, ttf_reader.read_bytes()?));
+	}
+
+	let mut marks = Vec::with_capacity(records.len());
+	for (mark_class, mark_anchor_offset) in records {
+		let Some(mark_anchor) = read_anchor(ttf_reader, offset, mark_anchor_offset)? else {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::GlyphPositioning, "mark record should have an anchor"));
+		};
+		marks.push(MarkRecord { mark_class, mark_anchor });
+	}
+	Ok(marks)
+}
+
+/// Reads rows of anchor offsets, one anchor for each mark class, as used by base arrays and ligature components.
+fn read_anchor_matrix<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64, mark_class_count: u16) -> Result<Vec<Vec<Option<Anchor>>>, TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	let row_count: u16 = ttf_reader.read_bytes()?;
+	let mut anchor_offsets: Vec<u16> = Vec::with_capacity(row_count as usize * mark_class_count as usize);
+	for _ in 0..row_count as usize * mark_class_count as usize {
+		anchor_offsets.push(ttf_reader.read_bytes()?);
+	}
+
+	let mut rows = Vec::with_capacity(row_count as usize);
+	for row_offsets in anchor_offsets.chunks(mark_class_count.max(1) as usize) {
+		let mut row = Vec::with_capacity(row_offsets.len());
+		for &anchor_offset in row_offsets {
+			row.push(read_anchor(ttf_reader, offset, anchor_offset)?);
+		}
+		rows.push(row);
+	}
+	Ok(rows)
+}
+
+fn read_mark_attachment<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64) -> Result<MarkAttachment, TrueTypeFontReaderError> {
+	let mark_coverage_offset: u16 = ttf_reader.read_bytes()?;
+	let base_coverage_offset: u16 = ttf_reader.read_bytes()?;
+	let mark_class_count: u16 = ttf_reader.read_bytes()?;
+	let mark_array_offset: u16 = ttf_reader.read_bytes()?;
+	let base_array_offset: u16 = ttf_reader.read_bytes()?;
+
+	Ok(MarkAttachment {
+		mark_coverage: ttf_reader.read((offset + mark_coverage_offset as u64, TableTag::GlyphPositioning))?,
+		base_coverage: ttf_reader.read((offset + base_coverage_offset as u64, TableTag::GlyphPositioning))?,
+		marks: read_mark_array(ttf_reader, offset + mark_array_offset as u64)?,
+		bases: read_anchor_matrix(ttf_reader, offset + base_array_offset as u64, mark_class_count)?,
+	})
+}
+
+impl LookupSubtable for PositioningSubtable {
+	const TABLE_TAG: TableTag = TableTag::GlyphPositioning;
+	const EXTENSION_LOOKUP_TYPE: u16 = 9;
+
+	fn read_subtable<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, lookup_type: u16, offset: u64) -> Result<PositioningSubtable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+		let format: u16 = ttf_reader.read_bytes()?;
+
+		match (lookup_type, format) {
+			(1, 1) => {
+				let coverage_offset: u16 = ttf_reader.read_bytes()?;
+				let value_format: u16 = ttf_reader.read_bytes()?;
+				let value = read_value_record(ttf_reader, value_format)?;
+				Ok(PositioningSubtable::SingleAdjustment {
+					coverage: ttf_reader.read((offset + coverage_offset as u64, TableTag::GlyphPositioning))?,
+					value,
+				})
+			},
+			(1, 2) => {
+				let coverage_offset: u16 = ttf_reader.read_bytes()?;
+				let value_format: u16 = ttf_reader.read_bytes()?;
+				let value_count: u16 = ttf_reader.read_bytes()?;
+				let mut values = Vec::with_capacity(value_count as usize);
+				for _ in 0..value_count {
+					values.push(read_value_record(ttf_reader, value_format)?);
+				}
+				Ok(PositioningSubtable::SingleAdjustmentList {
+					coverage: ttf_reader.read((offset + coverage_offset as u64, TableTag::GlyphPositioning))?,
+					values,
+				})
+			},
+			(2, 1) => {
+				let coverage_offset: u16 = ttf_reader.read_bytes()?;
+				let first_value_format: u16 = ttf_reader.read_bytes()?;
+				let second_value_format: u16 = ttf_reader.read_bytes()?;
+				let pair_set_offsets = read_u16_array(ttf_reader)?;
+
+				let mut pair_sets = Vec::with_capacity(pair_set_offsets.len());
+				for pair_set_offset in pair_set_offsets {
+					ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset + pair_set_offset as u64))?;
+					let pair_value_count: u16 = ttf_reader.read_bytes()?;
+					let mut pair_set = Vec::with_capacity(pair_value_count as usize);
+					for _ in 0..pair_value_count {
+						pair_set.push(PairValueRecord {
+							second_glyph_id: ttf_reader.read_bytes()?,
+							first_value: read_value_record(ttf_reader, first_value_format)?,
+							second_value: read_value_record(ttf_reader, second_value_format)?,
+						});
+					}
+					pair_sets.push(pair_set);
+				}
+				Ok(PositioningSubtable::PairAdjustmentGlyphs {
+					coverage: ttf_reader.read((offset + coverage_offset as u64, TableTag::GlyphPositioning))?,
+					pair_sets,
+					second_has_value: second_value_format != 0,
+				})
+			},
+			(2, 2) => {
+				let coverage_offset: u16 = ttf_reader.read_bytes()?;
+				let first_value_format: u16 = ttf_reader.read_bytes()?;
+				let second_value_format: u16 = ttf_reader.read_bytes()?;
+				let first_class_definition_offset: u16 = ttf_reader.read_bytes()?;
+				let second_class_definition_offset: u16 = ttf_reader.read_bytes()?;
+				let first_class_count: u16 = ttf_reader.read_bytes()?;
+				let second_class_count: u16 = ttf_reader.read_bytes()?;
+				let mut values = Vec::with_capacity(first_class_count as usize * second_class_count as usize);
+				for _ in 0..first_class_count as usize * second_class_count as usize {
+					values.push((read_value_record(ttf_reader, first_value_format)?, read_value_record(ttf_reader, second_value_format)?));
+				}
+				Ok(PositioningSubtable::PairAdjustmentClasses {
+					coverage: ttf_reader.read((offset + coverage_offset as u64, TableTag::GlyphPositioning))?,
+					first_classes: read_optional_class_definition(ttf_reader, offset, first_class_definition_offset, TableTag::GlyphPositioning)?,
+					second_classes: read_optional_class_definition(ttf_reader, offset, second_class_definition_offset, TableTag::GlyphPositioning)?,
+					second_class_count,
+					values,
+					second_has_value: second_value_format != 0,
+				})
+			},
+			(3, 1) => {
+				let coverage_offset: u16 = ttf_reader.read_bytes()?;
+				let entry_exit_count: u16 = ttf_reader.read_bytes()?;
+				let mut anchor_offsets: Vec<(u16, u16)> = Vec::with_capacity(entry_exit_count as usize);
+				for _ in 0..entry_exit_count {
+					anchor_offsets.push((ttf_reader.read_bytes()?, ttf_reader.read_bytes()?));
+				}
+				let mut entry_exits = Vec::with_capacity(anchor_offsets.len());
+				for (entry_anchor_offset, exit_anchor_offset) in anchor_offsets {
+					entry_exits.push((read_anchor(ttf_reader, offset, entry_anchor_offset)?, read_anchor(ttf_reader, offset, exit_anchor_offset)?));
+				}
+				Ok(PositioningSubtable::CursiveAttachment {
+					coverage: ttf_reader.read((offset + coverage_offset as u64, TableTag::GlyphPositioning))?,
+					entry_exits,
+				})
+			},
+			(4, 1) => Ok(PositioningSubtable::MarkToBase(read_mark_attachment(ttf_reader, offset)?)),
+			(5, 1) => {
+				let mark_coverage_offset: u16 = ttf_reader.read_bytes()?;
+				let ligature_coverage_offset: u16 = ttf_reader.read_bytes()?;
+				let mark_class_count: u16 = ttf_reader.read_bytes()?;
+				let mark_array_offset: u16 = ttf_reader.read_bytes()?;
+				let ligature_array_offset = offset + ttf_reader.read_bytes::<u16>()? as u64;
+
+				ttf_reader.buffer_reader.seek(io::SeekFrom::Start(ligature_array_offset))?;
+				let ligature_attach_offsets = read_u16_array(ttf_reader)?;
+				let mut ligatures = Vec::with_capacity(ligature_attach_offsets.len());
+				for ligature_attach_offset in ligature_attach_offsets {
+					ligatures.push(read_anchor_matrix(ttf_reader, ligature_array_offset + ligature_attach_offset as u64, mark_class_count)?);
+				}
+				Ok(PositioningSubtable::MarkToLigature(MarkToLigatureAttachment {
+					mark_coverage: ttf_reader.read((offset + mark_coverage_offset as u64, TableTag::GlyphPositioning))?,
+					ligature_coverage: ttf_reader.read((offset + ligature_coverage_offset as u64, TableTag::GlyphPositioning))?,
+					marks: read_mark_array(ttf_reader, offset + mark_array_offset as u64)?,
+					ligatures,
+				}))
+			},
+			(6, 1) => Ok(PositioningSubtable::MarkToMark(read_mark_attachment(ttf_reader, offset)?)),
+			(1..=6, _) => Err(TrueTypeFontReaderError::InvalidData(TableTag::GlyphPositioning, "lookup subtable format isn't one defined for its type")),
+			(lookup_type, _) => Ok(PositioningSubtable::Unsupported(lookup_type)),
+		}
+	}
+}
@@ -0,0 +1,213 @@
+use std::io::{self, Read, Seek};
+
+use crate::glyph_definition_reader::GlyphDefinitionTable;
+use crate::layout_reader::{read_sequence_context, read_u16_array, Coverage, GlyphFilter, LayoutTable, LookupSubtable, SequenceContext};
+use crate::ttf_reader::{TableTag, TrueTypeFontReader, TrueTypeFontReaderError};
+
+/// The features applied when laying out horizontal text, unless asked for otherwise.
+pub const DEFAULT_SUBSTITUTION_FEATURES: [[u8; 4]; 6] = [*b"ccmp", *b"locl", *b"rlig", *b"liga", *b"clig", *b"calt"];
+
+/// The features added when laying out vertical text, for glyphs such as brackets and punctuation turned to suit columns.
+pub const VERTICAL_SUBSTITUTION_FEATURES: [[u8; 4]; 2] = [*b"vert", *b"vrt2"];
+
+/// How deep contextual lookups may call other lookups, so that fonts which call themselves can't loop forever.
+const MAX_NESTING_LEVEL: usize = 16;
+
+pub type GlyphSubstitutionTable = LayoutTable<SubstitutionSubtable>;
+
+#[derive(Debug, Clone)]
+pub struct Ligature {
+	pub ligature_glyph_id: u16,
+	pub components: Vec<u16>, // Excluding the first glyph, which picked the ligature set
+}
+
+#[derive(Debug, Clone)]
+pub enum SubstitutionSubtable {
+	SingleDelta { coverage: Coverage, delta: i16 }, // Type 1 format 1, added to the glyph id
+	SingleList { coverage: Coverage, substitutes: Vec<u16> }, // Type 1 format 2
+	Multiple { coverage: Coverage, sequences: Vec<Vec<u16>> }, // Type 2
+	Alternate { coverage: Coverage, alternate_sets: Vec<Vec<u16>> }, // Type 3
+	Ligature { coverage: Coverage, ligature_sets: Vec<Vec<Ligature>> }, // Type 4
+	Context(SequenceContext), // Types 5 and 6
+	Unsupported(u16),
+}
+
+impl GlyphSubstitutionTable {
+	/// Runs the given lookups over a line of glyphs, replacing, splitting and joining them as the font asks.
+	/// The glyph definitions let lookups skip over glyphs, such as marks between the letters of a ligature.
+	pub fn substitute(&self, glyph_ids: &mut Vec<u16>, lookup_indices: &[u16], glyph_definition: Option<&GlyphDefinitionTable>) {
+		for &lookup_index in lookup_indices {
+			let mut index = 0;
+			while index < glyph_ids.len() {
+				index += self.apply_lookup(lookup_index, glyph_ids, index, glyph_definition, 0).unwrap_or(1);
+			}
+		}
+	}
+
+	/// Applies the first subtable of a lookup that covers the glyph at `index`, returning how many glyphs to move on by.
+	fn apply_lookup(&self, lookup_index: u16, glyph_ids: &mut Vec<u16>, index: usize, glyph_definition: Option<&GlyphDefinitionTable>, nesting_level: usize) -> Option<usize> {
+		let lookup = self.lookups.get(lookup_index as usize)?;
+		let filter = lookup.glyph_filter(glyph_definition);
+		if filter.skips(*glyph_ids.get(index)?) {
+			return None;
+		}
+		lookup.subtables.iter().find_map(|subtable| self.apply_subtable(subtable, glyph_ids, index, &filter, nesting_level))
+	}
+
+	fn apply_subtable(&self, subtable: &SubstitutionSubtable, glyph_ids: &mut Vec<u16>, index: usize, filter: &GlyphFilter, nesting_level: usize) -> Option<usize> {
+		let glyph_id = *glyph_ids.get(index)?;
+		match subtable {
+			SubstitutionSubtable::SingleDelta { coverage, delta } => {
+				coverage.index(glyph_id)?;
+				glyph_ids[index] = glyph_id.wrapping_add(*delta as u16);
+				Some(1)
+			},
+			SubstitutionSubtable::SingleList { coverage, substitutes } => {
+				glyph_ids[index] = *substitutes.get(coverage.index(glyph_id)? as usize)?;
+				Some(1)
+			},
+			SubstitutionSubtable::Multiple { coverage, sequences } => {
+				let sequence = sequences.get(coverage.index(glyph_id)? as usize)?;
+				glyph_ids.splice(index..index + 1, sequence.iter().copied());
+				Some(sequence.len())
+			},
+			SubstitutionSubtable::Alternate { coverage, alternate_sets } => {
+				// There isn't a way to pick an alternate yet, so the first is used.
+				glyph_ids[index] = *alternate_sets.get(coverage.index(glyph_id)? as usize)?.first()?;
+				Some(1)
+			},
+			SubstitutionSubtable::Ligature { coverage, ligature_sets } => {
+				let ligature_set = ligature_sets.get(coverage.index(glyph_id)? as usize)?;
+				// Ligatures are in order of preference, so the first that matches is used.
+				let (ligature, component_indices) = ligature_set.iter().find_map(|ligature| {
+					let mut component_indices = Vec::with_capacity(ligature.components.len());
+					let mut position = index;
+					for &component in &ligature.components {
+						position = filter.next(glyph_ids, position)?;
+						if glyph_ids[position] != component {
+							return None;
+						}
+						component_indices.push(position);
+					}
+					Some((ligature, component_indices))
+				})?;
+				// Glyphs that were skipped over, such as marks, are kept and end up after the ligature.
+				for &component_index in component_indices.iter().rev() {
+					glyph_ids.remove(component_index);
+				}
+				glyph_ids[index] = ligature.ligature_glyph_id;
+				Some(1)
+			},
+			SubstitutionSubtable::Context(context) => {
+				if nesting_level >= MAX_NESTING_LEVEL {
+					return None;
+				}
+				let (mut input_indices, lookup_records) = context.matches(glyph_ids, index, filter)?;
+				for lookup_record in lookup_records {
+					let Some(&position) = input_indices.get(lookup_record.sequence_index as usize) else {
+						continue;
+					};
+					// Nested lookups can change how many glyphs there are, which moves the input glyphs after them.
+					let length = glyph_ids.len();
+					self.apply_lookup(lookup_record.lookup_index, glyph_ids, position, filter.glyph_definition, nesting_level + 1);
+					let change = glyph_ids.len() as isize - length as isize;
+					for input_index in input_indices.iter_mut().filter(|input_index| **input_index > position) {
+						*input_index = input_index.saturating_add_signed(change);
+					}
+				}
+				Some((input_indices.last()? + 1).saturating_sub(index).max(1))
+			},
+			SubstitutionSubtable::Unsupported(_) => None,
+		}
+	}
+}
+
+/// Reads the offsets that follow, each to a list of glyph ids, such as a sequence or alternate set.
+fn read_glyph_id_arrays<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64) -> Result<Vec<Vec<u16>>, TrueTypeFontReaderError> {
+	let array_offsets = read_u16_array(ttf_reader)?;
+	let mut arrays = Vec::with_capacity(array_offsets.len());
+	for array_offset in array_offsets {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset + array_offset as u64))?;
+		arrays.push(read_u16_array(ttf_reader)?);
+	}
+	Ok(arrays)
+}
+
+fn read_ligature_set<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64) -> Result<Vec<Ligature>, TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	let ligature_offsets = read_u16_array(ttf_reader)?;
+	let mut ligatures = Vec::with_capacity(ligature_offsets.len());
+	for ligature_offset in ligature_offsets {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset + ligature_offset as u64))?;
+		let ligature_glyph_id: u16 = ttf_reader.read_bytes()?;
+		let component_count: u16 = ttf_reader.read_bytes()?;
+		let mut components = Vec::with_capacity(component_count.saturating_sub(1) as usize);
+		for _ in 1..component_count {
+			components.push(ttf_reader.read_bytes()?);
+		}
+		ligatures.push(Ligature { ligature_glyph_id, components });
+	}
+	Ok(ligatures)
+}
+
+impl LookupSubtable for SubstitutionSubtable {
+	const TABLE_TAG: TableTag = TableTag::GlyphSubstitution;
+	const EXTENSION_LOOKUP_TYPE: u16 = 7;
+
+	fn read_subtable<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, lookup_type: u16, offset: u64) -> Result<SubstitutionSubtable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+		let format: u16 = ttf_reader.read_bytes()?;
+
+		match (lookup_type, format) {
+			(1, 1) => {
+				let coverage_offset: u16 = ttf_reader.read_bytes()?;
+				let delta: i16 = ttf_reader.read_bytes()?;
+				Ok(SubstitutionSubtable::SingleDelta {
+					coverage: ttf_reader.read((offset + coverage_offset as u64, TableTag::GlyphSubstitution))?,
+					delta,
+				})
+			},
+			(1, 2) => {
+				let coverage_offset: u16 = ttf_reader.read_bytes()?;
+				let substitutes = read_u16_array(ttf_reader)?;
+				Ok(SubstitutionSubtable::SingleList {
+					coverage: ttf_reader.read((offset + coverage_offset as u64, TableTag::GlyphSubstitution))?,
+					substitutes,
+				})
+			},
+			(2, 1) => {
+				let coverage_offset: u16 = ttf_reader.read_bytes()?;
+				let sequences = read_glyph_id_arrays(ttf_reader, offset)?;
+				Ok(SubstitutionSubtable::Multiple {
+					coverage: ttf_reader.read((offset + coverage_offset as u64, TableTag::GlyphSubstitution))?,
+					sequences,
+				})
+			},
+			(3, 1) => {
+				let coverage_offset: u16 = ttf_reader.read_bytes()?;
+				let alternate_sets = read_glyph_id_arrays(ttf_reader, offset)?;
+				Ok(SubstitutionSubtable::Alternate {
+					coverage: ttf_reader.read((offset + coverage_offset as u64, TableTag::GlyphSubstitution))?,
+					alternate_sets,
+				})
+			},
+			(4, 1) => {
+				let coverage_offset: u16 = ttf_reader.read_bytes()?;
+				let ligature_set_offsets = read_u16_array(ttf_reader)?;
+				let mut ligature_sets = Vec::with_capacity(ligature_set_offsets.len());
+				for ligature_set_offset in ligature_set_offsets {
+					ligature_sets.push(read_ligature_set(ttf_reader, offset + ligature_set_offset as u64)?);
+				}
+				Ok(SubstitutionSubtable::Ligature {
+					coverage: ttf_reader.read((offset + coverage_offset as u64, TableTag::GlyphSubstitution))?,
+					ligature_sets,
+				})
+			},
+			(5, _) => Ok(SubstitutionSubtable::Context(read_sequence_context(ttf_reader, offset, false, TableTag::GlyphSubstitution)?)),
+			(6, _) => Ok(SubstitutionSubtable::Context(read_sequence_context(ttf_reader, offset, true, TableTag::GlyphSubstitution)?)),
+			(1..=4, _) => Err(TrueTypeFontReaderError::InvalidData(TableTag::GlyphSubstitution, "lookup subtable format isn't one defined for its type")),
+			// Type 8, reverse chaining, is applied from the end of the text backwards, which isn't done.
+			(lookup_type, _) => Ok(SubstitutionSubtable::Unsupported(lookup_type)),
+		}
+	}
+}
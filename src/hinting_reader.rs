@@ -0,0 +1,98 @@
+use std::io::{self, Read, Seek};
+
+use crate::ttf_reader::{FromTTFReader, TableTag, TrueTypeFontReader, TrueTypeFontReaderError};
+
+/// gasp behaviour flags: whether to grid-fit at a size, with bilevel or ClearType style rendering, and whether to smooth it.
+pub const GRID_FIT: u16 = 0x0001;
+pub const DO_GREY: u16 = 0x0002;
+pub const SYMMETRIC_GRID_FIT: u16 = 0x0004;
+pub const SYMMETRIC_SMOOTHING: u16 = 0x0008;
+
+/// The values instructions move points by, such as stem widths and the heights of the x-height and cap height, in font units.
+pub struct ControlValueTable {
+	pub control_values: Vec<i16>,
+}
+
+impl FromTTFReader for ControlValueTable {
+	type Input = (u32, u32); // offset, length
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (offset, length): (u32, u32)) -> Result<ControlValueTable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+
+		let mut control_values: Vec<i16> = Vec::with_capacity(length as usize / 2);
+		for _ in 0..length / 2 {
+			control_values.push(ttf_reader.read_bytes()?);
+		}
+
+		Ok(ControlValueTable {
+			control_values,
+		})
+	}
+}
+
+/// The instructions of fpgm, run once to define the functions other programs call, or of prep, run for each size before any glyph.
+pub struct ProgramTable {
+	pub instructions: Vec<u8>,
+}
+
+impl FromTTFReader for ProgramTable {
+	type Input = (u32, u32); // offset, length
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (offset, length): (u32, u32)) -> Result<ProgramTable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+
+		let mut instructions = vec![0u8; length as usize];
+		ttf_reader.buffer_reader.read_exact(&mut instructions)?;
+
+		Ok(ProgramTable {
+			instructions,
+		})
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GridFittingRange {
+	pub max_pixels_per_em: u16, // The largest size this range covers, from just above the size of the range before it
+	pub behaviour: u16,
+}
+
+pub struct GridFittingTable {
+	pub ranges: Vec<GridFittingRange>, // Sorted by size, with the last covering every size up to 0xFFFF
+}
+
+impl FromTTFReader for GridFittingTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<GridFittingTable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+
+		let version: u16 = ttf_reader.read_bytes()?;
+		if version > 1 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::GridFitting, version, 0));
+		}
+		let number_of_ranges: u16 = ttf_reader.read_bytes()?;
+		let mut ranges: Vec<GridFittingRange> = Vec::with_capacity(number_of_ranges as usize);
+		for _ in 0..number_of_ranges {
+			ranges.push(GridFittingRange { max_pixels_per_em: ttf_reader.read_bytes()?, behaviour: ttf_reader.read_bytes()? });
+		}
+		if ranges.windows(2).any(|pair| pair[0].max_pixels_per_em >= pair[1].max_pixels_per_em) {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::GridFitting, "ranges should be sorted by size"));
+		}
+
+		Ok(GridFittingTable {
+			ranges,
+		})
+	}
+}
+
+impl GridFittingTable {
+	/// The behaviour flags of the range a size is in, or none for sizes past the last range.
+	pub fn behaviour(&self, pixels_per_em: u16) -> u16 {
+		self.ranges.iter().find(|range| pixels_per_em <= range.max_pixels_per_em).map_or(0, |range| range.behaviour)
+	}
+
+	/// Whether glyphs should be grid-fitted at a size, in either the bilevel or the ClearType style.
+	pub fn grid_fits(&self, pixels_per_em: u16) -> bool {
+		self.behaviour(pixels_per_em) & (GRID_FIT | SYMMETRIC_GRID_FIT) != 0
+	}
+}
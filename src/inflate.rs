@@ -0,0 +1,274 @@
+//! Decompression of zlib (RFC 1950) wrapped DEFLATE (RFC 1951) data, as used by the tables of WOFF files.
+
+const MAXIMUM_CODE_LENGTH: usize = 15;
+const END_OF_BLOCK: u16 = 256;
+
+const LENGTH_BASES: [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA_BITS: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASES: [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA_BITS: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+// The order code length code lengths are stored in, for dynamic Huffman blocks.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+#[derive(Debug, PartialEq)]
+pub enum InflateError {
+	UnexpectedEnd,
+	InvalidZlibHeader,
+	PresetDictionaryNotSupported,
+	InvalidBlockType,
+	StoredLengthMismatch,
+	InvalidCodeLengths,
+	InvalidSymbol(u16),
+	DistanceTooFarBack(usize),
+	ChecksumMismatch,
+	OutputTooLong,
+}
+
+pub(crate) struct BitReader<'a> {
+	data: &'a [u8],
+	position: usize, // in bits
+}
+
+impl<'a> BitReader<'a> {
+	pub(crate) fn new(data: &'a [u8]) -> Self {
+		Self { data, position: 0 }
+	}
+
+	pub(crate) fn bit(&mut self) -> Result<u32, InflateError> {
+		let byte = *self.data.get(self.position / 8).ok_or(InflateError::UnexpectedEnd)?;
+		let bit = (byte >> (self.position % 8)) & 1;
+		self.position += 1;
+		Ok(bit as u32)
+	}
+
+	/// Reads a value stored least significant bit first, as everything other than Huffman codes is.
+	pub(crate) fn bits(&mut self, count: u8) -> Result<u32, InflateError> {
+		let mut value = 0;
+		for bit_index in 0..count {
+			value |= self.bit()? << bit_index;
+		}
+		Ok(value)
+	}
+
+	pub(crate) fn align_to_byte(&mut self) {
+		self.position = self.position.div_ceil(8) * 8;
+	}
+
+	pub(crate) fn bytes(&mut self, count: usize) -> Result<&'a [u8], InflateError> {
+		let start = self.position / 8;
+		let bytes = self.data.get(start..start + count).ok_or(InflateError::UnexpectedEnd)?;
+		self.position += count * 8;
+		Ok(bytes)
+	}
+}
+
+/// A canonical Huffman code, stored as the number of codes of each length and the symbols in code order.
+pub(crate) struct Huffman {
+	counts: [u16; MAXIMUM_CODE_LENGTH + 1],
+	symbols: Vec<u16>,
+}
+
+impl Huffman {
+	pub(crate) fn new(code_lengths: &[u8]) -> Result<Self, InflateError> {
+		let mut counts = [0u16; MAXIMUM_CODE_LENGTH + 1];
+		for length in code_lengths {
+			counts[*length as usize] += 1;
+		}
+		counts[0] = 0;
+
+		// Over subscribed codes can't be decoded, incomplete ones are allowed as long as the missing codes aren't used.
+		let mut left: i32 = 1;
+		for count in counts.iter().skip(1) {
+			left = left * 2 - *count as i32;
+			if left < 0 {
+				return Err(InflateError::InvalidCodeLengths);
+			}
+		}
+
+		let mut offsets = [0u16; MAXIMUM_CODE_LENGTH + 2];
+		for length in 1..=MAXIMUM_CODE_LENGTH {
+			offsets[length + 1] = offsets[length] + counts[length];
+		}
+		let mut symbols = vec![0u16; offsets[MAXIMUM_CODE_LENGTH + 1] as usize];
+		for (symbol, length) in code_lengths.iter().enumerate() {
+			if *length != 0 {
+				symbols[offsets[*length as usize] as usize] = symbol as u16;
+				offsets[*length as usize] += 1;
+			}
+		}
+
+		Ok(Self { counts, symbols })
+	}
+
+	/// Huffman codes are stored most significant bit first, so they are read one bit at a time.
+	pub(crate) fn decode(&self, bit_reader: &mut BitReader) -> Result<u16, InflateError> {
+		let mut code: i32 = 0;
+		let mut first: i32 = 0;
+		let mut index: i32 = 0;
+		for length in 1..=MAXIMUM_CODE_LENGTH {
+			code |= bit_reader.bit()? as i32;
+			let count = self.counts[length] as i32;
+			if code - first < count {
+				return Ok(self.symbols[(index + code - first) as usize]);
+			}
+			index += count;
+			first = (first + count) << 1;
+			code <<= 1;
+		}
+		Err(InflateError::InvalidCodeLengths)
+	}
+}
+
+fn fixed_huffman() -> (Huffman, Huffman) {
+	let mut literal_lengths = [0u8; 288];
+	literal_lengths[0..144].fill(8);
+	literal_lengths[144..256].fill(9);
+	literal_lengths[256..280].fill(7);
+	literal_lengths[280..288].fill(8);
+	let distance_lengths = [5u8; 30];
+	(Huffman::new(&literal_lengths).expect("fixed code is complete"), Huffman::new(&distance_lengths).expect("fixed code is complete"))
+}
+
+fn dynamic_huffman(bit_reader: &mut BitReader) -> Result<(Huffman, Huffman), InflateError> {
+	let number_of_literal_codes = bit_reader.bits(5)? as usize + 257;
+	let number_of_distance_codes = bit_reader.bits(5)? as usize + 1;
+	let number_of_code_length_codes = bit_reader.bits(4)? as usize + 4;
+	if number_of_literal_codes > 286 || number_of_distance_codes > 30 {
+		return Err(InflateError::InvalidCodeLengths);
+	}
+
+	let mut code_length_lengths = [0u8; 19];
+	for symbol in CODE_LENGTH_ORDER.iter().take(number_of_code_length_codes) {
+		code_length_lengths[*symbol] = bit_reader.bits(3)? as u8;
+	}
+	let code_length_huffman = Huffman::new(&code_length_lengths)?;
+
+	let mut lengths: Vec<u8> = Vec::with_capacity(number_of_literal_codes + number_of_distance_codes);
+	while lengths.len() < number_of_literal_codes + number_of_distance_codes {
+		let symbol = code_length_huffman.decode(bit_reader)?;
+		let (length, repeat) = match symbol {
+			0..=15 => (symbol as u8, 1),
+			16 => (*lengths.last().ok_or(InflateError::InvalidCodeLengths)?, 3 + bit_reader.bits(2)?),
+			17 => (0, 3 + bit_reader.bits(3)?),
+			18 => (0, 11 + bit_reader.bits(7)?),
+			_ => return Err(InflateError::InvalidSymbol(symbol)),
+		};
+		if lengths.len() + repeat as usize > number_of_literal_codes + number_of_distance_codes {
+			return Err(InflateError::InvalidCodeLengths);
+		}
+		lengths.extend(std::iter::repeat_n(length, repeat as usize));
+	}
+
+	if lengths[END_OF_BLOCK as usize] == 0 {
+		return Err(InflateError::InvalidCodeLengths);
+	}
+	let (literal_lengths, distance_lengths) = lengths.split_at(number_of_literal_codes);
+	Ok((Huffman::new(literal_lengths)?, Huffman::new(distance_lengths)?))
+}
+
+fn inflate_block(bit_reader: &mut BitReader, output: &mut Vec<u8>, (literals, distances): &(Huffman, Huffman), maximum_length: usize) -> Result<(), InflateError> {
+	loop {
+		let symbol = literals.decode(bit_reader)?;
+		match symbol {
+			0..=255 => output.push(symbol as u8),
+			END_OF_BLOCK => return Ok(()),
+			257..=285 => {
+				let length_index = (symbol - 257) as usize;
+				let length = LENGTH_BASES[length_index] as usize + bit_reader.bits(LENGTH_EXTRA_BITS[length_index])? as usize;
+				let distance_index = distances.decode(bit_reader)? as usize;
+				if distance_index >= DISTANCE_BASES.len() {
+					return Err(InflateError::InvalidSymbol(distance_index as u16));
+				}
+				let distance = DISTANCE_BASES[distance_index] as usize + bit_reader.bits(DISTANCE_EXTRA_BITS[distance_index])? as usize;
+				if distance > output.len() {
+					return Err(InflateError::DistanceTooFarBack(distance));
+				}
+				// The copy may overlap what it is writing, so it has to go a byte at a time.
+				let start = output.len() - distance;
+				for offset in 0..length {
+					output.push(output[start + offset]);
+				}
+			},
+			_ => return Err(InflateError::InvalidSymbol(symbol)),
+		}
+		if output.len() > maximum_length {
+			return Err(InflateError::OutputTooLong);
+		}
+	}
+}
+
+/// Decompresses raw DEFLATE data, failing if it would produce more than `maximum_length` bytes.
+pub fn inflate(data: &[u8], maximum_length: usize) -> Result<Vec<u8>, InflateError> {
+	inflate_stream(data, maximum_length).map(|(output, _)| output)
+}
+
+/// Decompresses DEFLATE data, also returning the number of bytes it took up, as the data doesn't record its own length.
+fn inflate_stream(data: &[u8], maximum_length: usize) -> Result<(Vec<u8>, usize), InflateError> {
+	let mut bit_reader = BitReader::new(data);
+	let mut output: Vec<u8> = Vec::new();
+
+	loop {
+		let last_block = bit_reader.bit()? == 1;
+		match bit_reader.bits(2)? {
+			0 => {
+				bit_reader.align_to_byte();
+				let header = bit_reader.bytes(4)?;
+				let length = u16::from_le_bytes([header[0], header[1]]);
+				let length_complement = u16::from_le_bytes([header[2], header[3]]);
+				if length != !length_complement {
+					return Err(InflateError::StoredLengthMismatch);
+				}
+				output.extend_from_slice(bit_reader.bytes(length as usize)?);
+				if output.len() > maximum_length {
+					return Err(InflateError::OutputTooLong);
+				}
+			},
+			1 => inflate_block(&mut bit_reader, &mut output, &fixed_huffman(), maximum_length)?,
+			2 => {
+				let huffman = dynamic_huffman(&mut bit_reader)?;
+				inflate_block(&mut bit_reader, &mut output, &huffman, maximum_length)?;
+			},
+			_ => return Err(InflateError::InvalidBlockType),
+		}
+		if last_block {
+			return Ok((output, bit_reader.position.div_ceil(8)));
+		}
+	}
+}
+
+fn adler32(data: &[u8]) -> u32 {
+	let (mut a, mut b) = (1u32, 0u32);
+	for chunk in data.chunks(5552) {
+		for byte in chunk {
+			a += *byte as u32;
+			b += a;
+		}
+		a %= 65521;
+		b %= 65521;
+	}
+	b << 16 | a
+}
+
+/// Decompresses a zlib stream, checking its header and Adler-32 checksum.
+pub fn zlib_decompress(data: &[u8], maximum_length: usize) -> Result<Vec<u8>, InflateError> {
+	let (compression_method, flags) = match data {
+		[compression_method, flags, ..] => (*compression_method, *flags),
+		_ => return Err(InflateError::UnexpectedEnd),
+	};
+	if compression_method & 0x0F != 8 || compression_method >> 4 > 7 || !(compression_method as u16 * 256 + flags as u16).is_multiple_of(31) {
+		return Err(InflateError::InvalidZlibHeader);
+	}
+	if flags & 0x20 != 0 {
+		return Err(InflateError::PresetDictionaryNotSupported);
+	}
+
+	let (output, deflate_length) = inflate_stream(&data[2..], maximum_length)?;
+	let checksum = match data.get(2 + deflate_length..2 + deflate_length + 4) {
+		Some(bytes) => u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+		None => return Err(InflateError::UnexpectedEnd),
+	};
+	if checksum != adler32(&output) {
+		return Err(InflateError::ChecksumMismatch);
+	}
+	Ok(output)
+}
@@ -0,0 +1,126 @@
+use std::io::{self, Read, Seek};
+
+use crate::ttf_reader::{FromTTFReader, TableTag, TrueTypeFontReader, TrueTypeFontReaderError};
+
+const MICROSOFT_HORIZONTAL_FLAG: u16 = 0x0001;
+const MICROSOFT_MINIMUM_FLAG: u16 = 0x0002;
+const MICROSOFT_CROSS_STREAM_FLAG: u16 = 0x0004;
+const MICROSOFT_OVERRIDE_FLAG: u16 = 0x0008;
+const APPLE_VERTICAL_FLAG: u16 = 0x8000;
+const APPLE_CROSS_STREAM_FLAG: u16 = 0x4000;
+const APPLE_VARIATION_FLAG: u16 = 0x2000;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KerningPair {
+	pub left: u16,
+	pub right: u16,
+	pub value: i16,
+}
+
+#[derive(Debug)]
+pub struct KerningSubtable {
+	pub format: u8,
+	pub horizontal: bool,
+	pub minimum: bool, // Values are limits rather than adjustments
+	pub cross_stream: bool, // Values move glyphs perpendicular to the direction of the text
+	pub variation: bool,
+	pub override_accumulated: bool, // Values replace those from earlier subtables, rather than adding to them
+	pub pairs: Vec<KerningPair>, // Only read for format 0 subtables
+}
+
+/// Both the Microsoft table, with a 16 bit version of 0, and Apple's, with a 32 bit version of 1.0.
+pub struct KerningTable {
+	pub subtables: Vec<KerningSubtable>,
+}
+
+impl KerningSubtable {
+	/// Whether the pairs are plain horizontal kerning, to be added to the advance between two glyphs.
+	pub fn is_horizontal_kerning(&self) -> bool {
+		self.format == 0 && self.horizontal && !self.minimum && !self.cross_stream && !self.variation
+	}
+}
+
+fn read_pairs<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>) -> Result<Vec<KerningPair>, TrueTypeFontReaderError> {
+	let number_of_pairs: u16 = ttf_reader.read_bytes()?;
+	ttf_reader.skip(6)?; // search range, entry selector and range shift
+	let mut pairs = Vec::with_capacity(number_of_pairs as usize);
+	for _ in 0..number_of_pairs {
+		pairs.push(KerningPair {
+			left: ttf_reader.read_bytes()?,
+			right: ttf_reader.read_bytes()?,
+			value: ttf_reader.read_bytes()?,
+		});
+	}
+	Ok(pairs)
+}
+
+impl FromTTFReader for KerningTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<KerningTable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+
+		let version: u16 = ttf_reader.read_bytes()?;
+		let mut subtables = Vec::new();
+		match version {
+			0 => {
+				let number_of_subtables: u16 = ttf_reader.read_bytes()?;
+				let mut subtable_offset = offset as u64 + 4;
+				for _ in 0..number_of_subtables {
+					ttf_reader.buffer_reader.seek(io::SeekFrom::Start(subtable_offset))?;
+					ttf_reader.skip(2)?; // version
+					let length: u16 = ttf_reader.read_bytes()?;
+					let coverage: u16 = ttf_reader.read_bytes()?;
+					let format = (coverage >> 8) as u8;
+					subtables.push(KerningSubtable {
+						format,
+						horizontal: coverage & MICROSOFT_HORIZONTAL_FLAG != 0,
+						minimum: coverage & MICROSOFT_MINIMUM_FLAG != 0,
+						cross_stream: coverage & MICROSOFT_CROSS_STREAM_FLAG != 0,
+						variation: false,
+						override_accumulated: coverage & MICROSOFT_OVERRIDE_FLAG != 0,
+						pairs: if format == 0 { read_pairs(ttf_reader)? } else { Vec::new() },
+					});
+					// Fonts with more than 10920 pairs overflow the 16 bit length, so trust the number of pairs over it.
+					subtable_offset += match format {
+						0 => 14 + 6 * subtables.last().map_or(0, |subtable| subtable.pairs.len() as u64),
+						_ => length as u64,
+					};
+				}
+			},
+			1 => {
+				let minor_version: u16 = ttf_reader.read_bytes()?;
+				if minor_version != 0 {
+					return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::Kerning, version, minor_version));
+				}
+				let number_of_subtables: u32 = ttf_reader.read_bytes()?;
+				let mut subtable_offset = offset as u64 + 8;
+				for _ in 0..number_of_subtables {
+					ttf_reader.buffer_reader.seek(io::SeekFrom::Start(subtable_offset))?;
+					let length: u32 = ttf_reader.read_bytes()?;
+					let coverage: u16 = ttf_reader.read_bytes()?;
+					ttf_reader.skip(2)?; // tuple index
+					let format = (coverage & 0xFF) as u8;
+					subtables.push(KerningSubtable {
+						format,
+						horizontal: coverage & APPLE_VERTICAL_FLAG == 0,
+						minimum: false,
+						cross_stream: coverage & APPLE_CROSS_STREAM_FLAG != 0,
+						variation: coverage & APPLE_VARIATION_FLAG != 0,
+						override_accumulated: false,
+						pairs: if format == 0 { read_pairs(ttf_reader)? } else { Vec::new() },
+					});
+					if length < 8 {
+						return Err(TrueTypeFontReaderError::InvalidData(TableTag::Kerning, "subtable should be at least as long as its header"));
+					}
+					subtable_offset += length as u64;
+				}
+			},
+			_ => return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::Kerning, version, 0)),
+		}
+
+		Ok(KerningTable {
+			subtables,
+		})
+	}
+}
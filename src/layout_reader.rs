@@ -0,0 +1,614 @@
+use std::io::{self, Read, Seek};
+
+use crate::glyph_definition_reader::{GlyphClass, GlyphDefinitionTable};
+use crate::ttf_reader::{FromTTFReader, TableTag, TrueTypeFontReader, TrueTypeFontReaderError};
+
+pub const DEFAULT_SCRIPT_TAG: [u8; 4] = *b"DFLT";
+
+const IGNORE_BASE_GLYPHS_FLAG: u16 = 0x0002;
+const IGNORE_LIGATURES_FLAG: u16 = 0x0004;
+const IGNORE_MARKS_FLAG: u16 = 0x0008;
+const USE_MARK_FILTERING_SET_FLAG: u16 = 0x0010;
+const MARK_ATTACHMENT_CLASS_MASK: u16 = 0xFF00;
+const NO_REQUIRED_FEATURE: u16 = 0xFFFF;
+
+/// A range of glyphs, with the coverage index of its first glyph or the class of all of them.
+#[derive(Debug, Clone, Copy)]
+pub struct RangeRecord {
+	pub start_glyph_id: u16,
+	pub end_glyph_id: u16,
+	pub value: u16,
+}
+
+/// The glyphs a subtable applies to, each given an index into the subtable's arrays.
+#[derive(Debug, Clone)]
+pub enum Coverage {
+	Glyphs(Vec<u16>), // Format 1, sorted by glyph id
+	Ranges(Vec<RangeRecord>), // Format 2
+}
+
+#[derive(Debug, Clone)]
+pub enum ClassDefinition {
+	Array { start_glyph_id: u16, classes: Vec<u16> }, // Format 1
+	Ranges(Vec<RangeRecord>), // Format 2
+}
+
+#[derive(Debug, Clone)]
+pub struct LanguageSystem {
+	pub required_feature_index: Option<u16>,
+	pub feature_indices: Vec<u16>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Script {
+	pub default_language_system: Option<LanguageSystem>,
+	pub language_systems: Vec<([u8; 4], LanguageSystem)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Feature {
+	pub feature_tag: [u8; 4],
+	pub lookup_indices: Vec<u16>,
+}
+
+#[derive(Debug)]
+pub struct Lookup<Subtable> {
+	pub lookup_type: u16, // The type of the subtables, rather than the extension type for lookups that are stored as extensions
+	pub lookup_flag: u16,
+	pub mark_filtering_set: Option<u16>,
+	pub subtables: Vec<Subtable>,
+}
+
+/// Decides which glyphs a lookup skips over, from its flags and the font's glyph definitions.
+#[derive(Clone, Copy)]
+pub struct GlyphFilter<'a> {
+	pub glyph_definition: Option<&'a GlyphDefinitionTable>,
+	pub lookup_flag: u16,
+	pub mark_filtering_set: Option<u16>,
+}
+
+/// A lookup to apply at a position within a matched input sequence.
+#[derive(Debug, Clone, Copy)]
+pub struct SequenceLookupRecord {
+	pub sequence_index: u16,
+	pub lookup_index: u16,
+}
+
+/// A sequence of glyph ids or classes to match around the glyph a rule is tried at.
+#[derive(Debug, Clone)]
+pub struct SequenceRule {
+	pub backtrack: Vec<u16>, // Closest glyph first
+	pub input: Vec<u16>, // Excluding the first glyph, which picked the rule set
+	pub lookahead: Vec<u16>,
+	pub lookup_records: Vec<SequenceLookupRecord>,
+}
+
+/// A contextual or chained contextual subtable, which applies other lookups where a sequence of glyphs matches.
+/// Contextual subtables are read as chained ones without a backtrack or lookahead.
+#[derive(Debug, Clone)]
+pub enum SequenceContext {
+	Glyphs { coverage: Coverage, rule_sets: Vec<Vec<SequenceRule>> }, // Format 1
+	Classes { coverage: Coverage, backtrack_classes: ClassDefinition, input_classes: ClassDefinition, lookahead_classes: ClassDefinition, rule_sets: Vec<Vec<SequenceRule>> }, // Format 2
+	Coverages { backtrack: Vec<Coverage>, input: Vec<Coverage>, lookahead: Vec<Coverage>, lookup_records: Vec<SequenceLookupRecord> }, // Format 3
+}
+
+/// The script list, feature list and lookup list shared by the GPOS and GSUB tables.
+pub struct LayoutTable<Subtable> {
+	pub scripts: Vec<([u8; 4], Script)>,
+	pub features: Vec<Feature>,
+	pub lookups: Vec<Lookup<Subtable>>,
+}
+
+/// The lookup subtables of a particular layout table.
+pub trait LookupSubtable: Sized {
+	const TABLE_TAG: TableTag;
+	const EXTENSION_LOOKUP_TYPE: u16;
+
+	fn read_subtable<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, lookup_type: u16, offset: u64) -> Result<Self, TrueTypeFontReaderError>;
+}
+
+impl<Subtable> Lookup<Subtable> {
+	pub fn glyph_filter<'a>(&self, glyph_definition: Option<&'a GlyphDefinitionTable>) -> GlyphFilter<'a> {
+		GlyphFilter {
+			glyph_definition,
+			lookup_flag: self.lookup_flag,
+			mark_filtering_set: self.mark_filtering_set,
+		}
+	}
+}
+
+impl GlyphFilter<'_> {
+	/// Whether the lookup should act as if the glyph wasn't there. Without glyph definitions nothing is skipped.
+	pub fn skips(&self, glyph_id: u16) -> bool {
+		let Some(glyph_definition) = self.glyph_definition else {
+			return false;
+		};
+		match glyph_definition.glyph_class(glyph_id) {
+			Some(GlyphClass::Base) => self.lookup_flag & IGNORE_BASE_GLYPHS_FLAG != 0,
+			Some(GlyphClass::Ligature) => self.lookup_flag & IGNORE_LIGATURES_FLAG != 0,
+			Some(GlyphClass::Mark) => {
+				let mark_attachment_class = (self.lookup_flag & MARK_ATTACHMENT_CLASS_MASK) >> 8;
+				self.lookup_flag & IGNORE_MARKS_FLAG != 0
+					|| self.mark_filtering_set.is_some_and(|mark_glyph_set| !glyph_definition.in_mark_glyph_set(mark_glyph_set, glyph_id))
+					|| (mark_attachment_class != 0 && glyph_definition.mark_attachment_class(glyph_id) != mark_attachment_class)
+			},
+			Some(GlyphClass::Component) | None => false,
+		}
+	}
+
+	/// Whether a glyph is a mark, going by the glyph definitions or, for fonts without glyph classes, whether `marks` covers it.
+	pub fn is_mark(&self, glyph_id: u16, marks: &Coverage) -> bool {
+		match self.glyph_definition {
+			Some(glyph_definition) if glyph_definition.glyph_classes.is_some() => glyph_definition.glyph_class(glyph_id) == Some(GlyphClass::Mark),
+			_ => marks.contains(glyph_id),
+		}
+	}
+
+	/// The index of the next glyph after `index` that isn't skipped.
+	pub fn next(&self, glyph_ids: &[u16], index: usize) -> Option<usize> {
+		(index + 1..glyph_ids.len()).find(|&next_index| !self.skips(glyph_ids[next_index]))
+	}
+
+	/// The index of the closest glyph before `index` that isn't skipped.
+	pub fn previous(&self, glyph_ids: &[u16], index: usize) -> Option<usize> {
+		(0..index).rev().find(|&previous_index| !self.skips(glyph_ids[previous_index]))
+	}
+}
+
+impl Coverage {
+	pub fn index(&self, glyph_id: u16) -> Option<u16> {
+		match self {
+			Coverage::Glyphs(glyph_ids) => glyph_ids.binary_search(&glyph_id).ok().map(|index| index as u16),
+			Coverage::Ranges(ranges) => {
+				let range = ranges.iter().find(|range| range.start_glyph_id <= glyph_id && glyph_id <= range.end_glyph_id)?;
+				Some(range.value + (glyph_id - range.start_glyph_id))
+			},
+		}
+	}
+
+	pub fn contains(&self, glyph_id: u16) -> bool {
+		self.index(glyph_id).is_some()
+	}
+}
+
+impl ClassDefinition {
+	/// The class of a glyph, with glyphs that aren't given one in class 0.
+	pub fn class(&self, glyph_id: u16) -> u16 {
+		match self {
+			ClassDefinition::Array { start_glyph_id, classes } => {
+				glyph_id.checked_sub(*start_glyph_id).and_then(|index| classes.get(index as usize)).copied().unwrap_or(0)
+			},
+			ClassDefinition::Ranges(ranges) => {
+				ranges.iter().find(|range| range.start_glyph_id <= glyph_id && glyph_id <= range.end_glyph_id).map_or(0, |range| range.value)
+			},
+		}
+	}
+}
+
+impl SequenceContext {
+	/// If the context matches with its input starting at `index`, the indices of the input glyphs and the lookups to apply to them.
+	pub fn matches(&self, glyph_ids: &[u16], index: usize, filter: &GlyphFilter) -> Option<(Vec<usize>, &[SequenceLookupRecord])> {
+		let glyph_id = *glyph_ids.get(index)?;
+		match self {
+			SequenceContext::Glyphs { coverage, rule_sets } => {
+				let rule_set = rule_sets.get(coverage.index(glyph_id)? as usize)?;
+				let equal = |&value: &u16, glyph_id: u16| value == glyph_id;
+				rule_set.iter().find_map(|rule| {
+					let input_indices = sequence_matches(glyph_ids, index, filter, (&rule.backtrack, &rule.input, &rule.lookahead), (&equal, &equal, &equal))?;
+					Some((input_indices, rule.lookup_records.as_slice()))
+				})
+			},
+			SequenceContext::Classes { coverage, backtrack_classes, input_classes, lookahead_classes, rule_sets } => {
+				coverage.index(glyph_id)?;
+				let rule_set = rule_sets.get(input_classes.class(glyph_id) as usize)?;
+				rule_set.iter().find_map(|rule| {
+					let input_indices = sequence_matches(glyph_ids, index, filter, (&rule.backtrack, &rule.input, &rule.lookahead), (
+						|&class: &u16, glyph_id| backtrack_classes.class(glyph_id) == class,
+						|&class: &u16, glyph_id| input_classes.class(glyph_id) == class,
+						|&class: &u16, glyph_id| lookahead_classes.class(glyph_id) == class,
+					))?;
+					Some((input_indices, rule.lookup_records.as_slice()))
+				})
+			},
+			SequenceContext::Coverages { backtrack, input, lookahead, lookup_records } => {
+				let (first, input) = input.split_first()?;
+				first.index(glyph_id)?;
+				let covers = |coverage: &Coverage, glyph_id| coverage.contains(glyph_id);
+				let input_indices = sequence_matches(glyph_ids, index, filter, (backtrack, input, lookahead), (&covers, &covers, &covers))?;
+				Some((input_indices, lookup_records.as_slice()))
+			},
+		}
+	}
+}
+
+/// Matches the glyphs before `index` against the backtrack, and those after it against the rest of the input and then the lookahead,
+/// stepping over any the filter skips. Gives the indices of the input glyphs, starting with `index`.
+fn sequence_matches<T>(glyph_ids: &[u16], index: usize, filter: &GlyphFilter, (backtrack, input, lookahead): (&[T], &[T], &[T]), (backtrack_matches, input_matches, lookahead_matches): (impl Fn(&T, u16) -> bool, impl Fn(&T, u16) -> bool, impl Fn(&T, u16) -> bool)) -> Option<Vec<usize>> {
+	let mut input_indices = Vec::with_capacity(input.len() + 1);
+	input_indices.push(index);
+	let mut position = index;
+	for value in input {
+		position = filter.next(glyph_ids, position)?;
+		if !input_matches(value, glyph_ids[position]) {
+			return None;
+		}
+		input_indices.push(position);
+	}
+	for value in lookahead {
+		position = filter.next(glyph_ids, position)?;
+		if !lookahead_matches(value, glyph_ids[position]) {
+			return None;
+		}
+	}
+	let mut position = index;
+	for value in backtrack {
+		position = filter.previous(glyph_ids, position)?;
+		if !backtrack_matches(value, glyph_ids[position]) {
+			return None;
+		}
+	}
+	Some(input_indices)
+}
+
+impl<Subtable> LayoutTable<Subtable> {
+	/// The script to use, falling back to the default script and then to Latin when the font doesn't have the one asked for.
+	fn script(&self, script_tag: [u8; 4]) -> Option<&Script> {
+		[script_tag, DEFAULT_SCRIPT_TAG, *b"dflt", *b"latn"].iter().find_map(|tag| {
+			self.scripts.iter().find(|(script_tag, _)| script_tag == tag).map(|(_, script)| script)
+		})
+	}
+
+	/// The lookups for the given features, in the order they should be applied, for a script and optionally a language within it.
+	/// Any feature the language system requires is always included.
+	pub fn lookup_indices(&self, script_tag: [u8; 4], language_tag: Option<[u8; 4]>, feature_tags: &[[u8; 4]]) -> Vec<u16> {
+		let Some(script) = self.script(script_tag) else {
+			return Vec::new();
+		};
+		let language_system = language_tag
+			.and_then(|language_tag| script.language_systems.iter().find(|(tag, _)| *tag == language_tag).map(|(_, language_system)| language_system))
+			.or(script.default_language_system.as_ref());
+		let Some(language_system) = language_system else {
+			return Vec::new();
+		};
+
+		let mut lookup_indices: Vec<u16> = Vec::new();
+		let feature_indices = language_system.required_feature_index.iter().chain(language_system.feature_indices.iter());
+		for (position, &feature_index) in feature_indices.enumerate() {
+			let Some(feature) = self.features.get(feature_index as usize) else {
+				continue;
+			};
+			let required = position == 0 && language_system.required_feature_index.is_some();
+			if required || feature_tags.contains(&feature.feature_tag) {
+				lookup_indices.extend(feature.lookup_indices.iter().filter(|&&lookup_index| (lookup_index as usize) < self.lookups.len()));
+			}
+		}
+		// Lookups are applied in the order of the lookup list, whichever features they come from.
+		lookup_indices.sort_unstable();
+		lookup_indices.dedup();
+		lookup_indices
+	}
+}
+
+impl FromTTFReader for Coverage {
+	type Input = (u64, TableTag);
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (offset, table_tag): (u64, TableTag)) -> Result<Coverage, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+
+		let format: u16 = ttf_reader.read_bytes()?;
+		let count: u16 = ttf_reader.read_bytes()?;
+		match format {
+			1 => {
+				let mut glyph_ids = Vec::with_capacity(count as usize);
+				for _ in 0..count {
+					glyph_ids.push(ttf_reader.read_bytes()?);
+				}
+				Ok(Coverage::Glyphs(glyph_ids))
+			},
+			2 => Ok(Coverage::Ranges(read_range_records(ttf_reader, count)?)),
+			_ => Err(TrueTypeFontReaderError::InvalidData(table_tag, "coverage table should be format 1 or 2")),
+		}
+	}
+}
+
+impl FromTTFReader for ClassDefinition {
+	type Input = (u64, TableTag);
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (offset, table_tag): (u64, TableTag)) -> Result<ClassDefinition, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+
+		let format: u16 = ttf_reader.read_bytes()?;
+		match format {
+			1 => {
+				let start_glyph_id: u16 = ttf_reader.read_bytes()?;
+				let glyph_count: u16 = ttf_reader.read_bytes()?;
+				let mut classes = Vec::with_capacity(glyph_count as usize);
+				for _ in 0..glyph_count {
+					classes.push(ttf_reader.read_bytes()?);
+				}
+				Ok(ClassDefinition::Array { start_glyph_id, classes })
+			},
+			2 => {
+				let count: u16 = ttf_reader.read_bytes()?;
+				Ok(ClassDefinition::Ranges(read_range_records(ttf_reader, count)?))
+			},
+			_ => Err(TrueTypeFontReaderError::InvalidData(table_tag, "class definition table should be format 1 or 2")),
+		}
+	}
+}
+
+fn read_range_records<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, count: u16) -> Result<Vec<RangeRecord>, TrueTypeFontReaderError> {
+	let mut ranges = Vec::with_capacity(count as usize);
+	for _ in 0..count {
+		ranges.push(RangeRecord {
+			start_glyph_id: ttf_reader.read_bytes()?,
+			end_glyph_id: ttf_reader.read_bytes()?,
+			value: ttf_reader.read_bytes()?,
+		});
+	}
+	Ok(ranges)
+}
+
+/// Reads a class definition from an offset that may be 0, meaning every glyph is in class 0.
+pub fn read_optional_class_definition<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, base_offset: u64, offset: u16, table_tag: TableTag) -> Result<ClassDefinition, TrueTypeFontReaderError> {
+	match offset {
+		0 => Ok(ClassDefinition::Ranges(Vec::new())),
+		offset => ttf_reader.read((base_offset + offset as u64, table_tag)),
+	}
+}
+
+/// Reads a list of 16 bit values, such as glyph ids or offsets, preceded by how many there are.
+pub fn read_u16_array<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>) -> Result<Vec<u16>, TrueTypeFontReaderError> {
+	let count: u16 = ttf_reader.read_bytes()?;
+	read_u16s(ttf_reader, count as usize)
+}
+
+fn read_u16s<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, count: usize) -> Result<Vec<u16>, TrueTypeFontReaderError> {
+	let mut values = Vec::with_capacity(count);
+	for _ in 0..count {
+		values.push(ttf_reader.read_bytes()?);
+	}
+	Ok(values)
+}
+
+fn read_sequence_lookup_records<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, count: u16) -> Result<Vec<SequenceLookupRecord>, TrueTypeFontReaderError> {
+	let mut lookup_records = Vec::with_capacity(count as usize);
+	for _ in 0..count {
+		lookup_records.push(SequenceLookupRecord {
+			sequence_index: ttf_reader.read_bytes()?,
+			lookup_index: ttf_reader.read_bytes()?,
+		});
+	}
+	Ok(lookup_records)
+}
+
+fn read_sequence_rule<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64, chained: bool) -> Result<SequenceRule, TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	if chained {
+		let backtrack = read_u16_array(ttf_reader)?;
+		let input_count: u16 = ttf_reader.read_bytes()?;
+		let input = read_u16s(ttf_reader, input_count.saturating_sub(1) as usize)?;
+		let lookahead = read_u16_array(ttf_reader)?;
+		let lookup_count: u16 = ttf_reader.read_bytes()?;
+		Ok(SequenceRule { backtrack, input, lookahead, lookup_records: read_sequence_lookup_records(ttf_reader, lookup_count)? })
+	} else {
+		let input_count: u16 = ttf_reader.read_bytes()?;
+		let lookup_count: u16 = ttf_reader.read_bytes()?;
+		let input = read_u16s(ttf_reader, input_count.saturating_sub(1) as usize)?;
+		Ok(SequenceRule { backtrack: Vec::new(), input, lookahead: Vec::new(), lookup_records: read_sequence_lookup_records(ttf_reader, lookup_count)? })
+	}
+}
+
+/// Reads the rule set offsets that follow in a format 1 or 2 subtable, where a rule set offset of 0 means it has no rules.
+fn read_sequence_rule_sets<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64, chained: bool) -> Result<Vec<Vec<SequenceRule>>, TrueTypeFontReaderError> {
+	let rule_set_offsets = read_u16_array(ttf_reader)?;
+	let mut rule_sets = Vec::with_capacity(rule_set_offsets.len());
+	for rule_set_offset in rule_set_offsets {
+		if rule_set_offset == 0 {
+			rule_sets.push(Vec::new());
+			continue;
+		}
+		let rule_set_offset = offset + rule_set_offset as u64;
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(rule_set_offset))?;
+		let rule_offsets = read_u16_array(ttf_reader)?;
+		let mut rules = Vec::with_capacity(rule_offsets.len());
+		for rule_offset in rule_offsets {
+			rules.push(read_sequence_rule(ttf_reader, rule_set_offset + rule_offset as u64, chained)?);
+		}
+		rule_sets.push(rules);
+	}
+	Ok(rule_sets)
+}
+
+fn read_coverages<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64, coverage_offsets: Vec<u16>, table_tag: TableTag) -> Result<Vec<Coverage>, TrueTypeFontReaderError> {
+	let mut coverages = Vec::with_capacity(coverage_offsets.len());
+	for coverage_offset in coverage_offsets {
+		coverages.push(ttf_reader.read((offset + coverage_offset as u64, table_tag))?);
+	}
+	Ok(coverages)
+}
+
+/// Reads a contextual subtable, or a chained contextual one, of any format.
+pub fn read_sequence_context<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64, chained: bool, table_tag: TableTag) -> Result<SequenceContext, TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	let format: u16 = ttf_reader.read_bytes()?;
+	match format {
+		1 => {
+			let coverage_offset: u16 = ttf_reader.read_bytes()?;
+			let rule_sets = read_sequence_rule_sets(ttf_reader, offset, chained)?;
+			Ok(SequenceContext::Glyphs {
+				coverage: ttf_reader.read((offset + coverage_offset as u64, table_tag))?,
+				rule_sets,
+			})
+		},
+		2 => {
+			let coverage_offset: u16 = ttf_reader.read_bytes()?;
+			let (backtrack_class_definition_offset, input_class_definition_offset, lookahead_class_definition_offset) = if chained {
+				(ttf_reader.read_bytes()?, ttf_reader.read_bytes()?, ttf_reader.read_bytes()?)
+			} else {
+				(0, ttf_reader.read_bytes()?, 0)
+			};
+			let rule_sets = read_sequence_rule_sets(ttf_reader, offset, chained)?;
+			Ok(SequenceContext::Classes {
+				coverage: ttf_reader.read((offset + coverage_offset as u64, table_tag))?,
+				backtrack_classes: read_optional_class_definition(ttf_reader, offset, backtrack_class_definition_offset, table_tag)?,
+				input_classes: read_optional_class_definition(ttf_reader, offset, input_class_definition_offset, table_tag)?,
+				lookahead_classes: read_optional_class_definition(ttf_reader, offset, lookahead_class_definition_offset, table_tag)?,
+				rule_sets,
+			})
+		},
+		3 => {
+			let (backtrack_offsets, input_offsets, lookahead_offsets, lookup_count) = if chained {
+				let backtrack_offsets = read_u16_array(ttf_reader)?;
+				let input_offsets = read_u16_array(ttf_reader)?;
+				let lookahead_offsets = read_u16_array(ttf_reader)?;
+				(backtrack_offsets, input_offsets, lookahead_offsets, ttf_reader.read_bytes()?)
+			} else {
+				let input_count: u16 = ttf_reader.read_bytes()?;
+				let lookup_count: u16 = ttf_reader.read_bytes()?;
+				(Vec::new(), read_u16s(ttf_reader, input_count as usize)?, Vec::new(), lookup_count)
+			};
+			let lookup_records = read_sequence_lookup_records(ttf_reader, lookup_count)?;
+			Ok(SequenceContext::Coverages {
+				backtrack: read_coverages(ttf_reader, offset, backtrack_offsets, table_tag)?,
+				input: read_coverages(ttf_reader, offset, input_offsets, table_tag)?,
+				lookahead: read_coverages(ttf_reader, offset, lookahead_offsets, table_tag)?,
+				lookup_records,
+			})
+		},
+		_ => Err(TrueTypeFontReaderError::InvalidData(table_tag, "contextual subtable should be format 1, 2 or 3")),
+	}
+}
+
+fn read_tag<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>) -> Result<[u8; 4], TrueTypeFontReaderError> {
+	Ok(ttf_reader.read_bytes::<u32>()?.to_be_bytes())
+}
+
+fn read_language_system<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64) -> Result<LanguageSystem, TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	ttf_reader.skip(2)?; // lookup order offset, which is reserved
+	let required_feature_index: u16 = ttf_reader.read_bytes()?;
+	Ok(LanguageSystem {
+		required_feature_index: (required_feature_index != NO_REQUIRED_FEATURE).then_some(required_feature_index),
+		feature_indices: read_u16_array(ttf_reader)?,
+	})
+}
+
+fn read_script<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64) -> Result<Script, TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	let default_language_system_offset: u16 = ttf_reader.read_bytes()?;
+	let language_system_count: u16 = ttf_reader.read_bytes()?;
+	let mut language_system_records: Vec<([u8; 4], u16)> = Vec::with_capacity(language_system_count as usize);
+	for _ in 0..language_system_count {
+		language_system_records.push((read_tag(ttf_reader)?, ttf_reader.read_bytes()?));
+	}
+
+	let default_language_system = match default_language_system_offset {
+		0 => None,
+		default_language_system_offset => Some(read_language_system(ttf_reader, offset + default_language_system_offset as u64)?),
+	};
+	let mut language_systems = Vec::with_capacity(language_system_records.len());
+	for (language_system_tag, language_system_offset) in language_system_records {
+		language_systems.push((language_system_tag, read_language_system(ttf_reader, offset + language_system_offset as u64)?));
+	}
+
+	Ok(Script {
+		default_language_system,
+		language_systems,
+	})
+}
+
+/// Reads a list of tagged records, such as the script list or feature list, each with an offset from the start of the list.
+fn read_tagged_offsets<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64) -> Result<Vec<([u8; 4], u64)>, TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	let count: u16 = ttf_reader.read_bytes()?;
+	let mut records = Vec::with_capacity(count as usize);
+	for _ in 0..count {
+		records.push((read_tag(ttf_reader)?, offset + ttf_reader.read_bytes::<u16>()? as u64));
+	}
+	Ok(records)
+}
+
+fn read_lookup<Source: Read + Seek, Subtable: LookupSubtable>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64) -> Result<Lookup<Subtable>, TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	let mut lookup_type: u16 = ttf_reader.read_bytes()?;
+	let lookup_flag: u16 = ttf_reader.read_bytes()?;
+	let subtable_offsets = read_u16_array(ttf_reader)?;
+	let mark_filtering_set = match lookup_flag & USE_MARK_FILTERING_SET_FLAG {
+		0 => None,
+		_ => Some(ttf_reader.read_bytes()?),
+	};
+
+	let mut subtables = Vec::with_capacity(subtable_offsets.len());
+	for subtable_offset in subtable_offsets {
+		let mut subtable_offset = offset + subtable_offset as u64;
+		let mut subtable_lookup_type = lookup_type;
+		// Extension subtables only point at the real subtable, so that it can be further away than a 16 bit offset allows.
+		if lookup_type == Subtable::EXTENSION_LOOKUP_TYPE {
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(subtable_offset))?;
+			let format: u16 = ttf_reader.read_bytes()?;
+			if format != 1 {
+				return Err(TrueTypeFontReaderError::InvalidData(Subtable::TABLE_TAG, "extension subtable should be format 1"));
+			}
+			subtable_lookup_type = ttf_reader.read_bytes()?;
+			subtable_offset += ttf_reader.read_bytes::<u32>()? as u64;
+		}
+		subtables.push(Subtable::read_subtable(ttf_reader, subtable_lookup_type, subtable_offset)?);
+		if lookup_type == Subtable::EXTENSION_LOOKUP_TYPE && subtables.len() == 1 {
+			lookup_type = subtable_lookup_type;
+		}
+	}
+
+	Ok(Lookup {
+		lookup_type,
+		lookup_flag,
+		mark_filtering_set,
+		subtables,
+	})
+}
+
+impl<Subtable: LookupSubtable> FromTTFReader for LayoutTable<Subtable> {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<LayoutTable<Subtable>, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+
+		let major_version: u16 = ttf_reader.read_bytes()?;
+		let minor_version: u16 = ttf_reader.read_bytes()?;
+		if major_version != 1 || minor_version > 1 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(Subtable::TABLE_TAG, major_version, minor_version));
+		}
+		let script_list_offset = offset as u64 + ttf_reader.read_bytes::<u16>()? as u64;
+		let feature_list_offset = offset as u64 + ttf_reader.read_bytes::<u16>()? as u64;
+		let lookup_list_offset = offset as u64 + ttf_reader.read_bytes::<u16>()? as u64;
+		// Version 1.1 adds feature variations, which swap lookups in variable fonts and aren't applied yet.
+
+		let mut scripts = Vec::new();
+		for (script_tag, script_offset) in read_tagged_offsets(ttf_reader, script_list_offset)? {
+			scripts.push((script_tag, read_script(ttf_reader, script_offset)?));
+		}
+
+		let mut features = Vec::new();
+		for (feature_tag, feature_offset) in read_tagged_offsets(ttf_reader, feature_list_offset)? {
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(feature_offset))?;
+			ttf_reader.skip(2)?; // feature parameters offset
+			features.push(Feature {
+				feature_tag,
+				lookup_indices: read_u16_array(ttf_reader)?,
+			});
+		}
+
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(lookup_list_offset))?;
+		let lookup_offsets = read_u16_array(ttf_reader)?;
+		let mut lookups = Vec::with_capacity(lookup_offsets.len());
+		for lookup_offset in lookup_offsets {
+			lookups.push(read_lookup(ttf_reader, lookup_list_offset + lookup_offset as u64)?);
+		}
+
+		Ok(LayoutTable {
+			scripts,
+			features,
+			lookups,
+		})
+	}
+}
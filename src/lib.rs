@@ -1,98 +1,155 @@
 pub mod ttf_reader;
+pub mod cff_reader;
+pub mod variation_reader;
+pub mod naming_reader;
+pub mod postscript_reader;
+pub mod kerning_reader;
+pub mod layout_reader;
+pub mod glyph_definition_reader;
+pub mod glyph_positioning_reader;
+pub mod glyph_substitution_reader;
+pub mod colour_reader;
+pub mod bitmap_reader;
+pub mod hinting_reader;
+pub mod inflate;
+pub mod brotli;
+pub mod png;
+pub mod woff_reader;
+pub mod woff2_reader;
 pub mod ttf_parser;
 pub mod font;
 
 pub mod read {
-	use crate::font::{self, Font, LazyGlyph, ToTriangles};
-	use crate::ttf_reader::{self, CharacterToGlyphIndexTable, FontHeaderTable, GlyphTable, HorizontalHeaderTable, HorizontalMetricsTable, IndexToLocationTable, MaximumProfileTable, OS2AndWindowsMetricsTable, TableRecord, TableTag};
+	use crate::cff_reader::{CompactFontFormat2Table, CompactFontFormatTable};
+	use crate::font::{self, autohinter::{AutoHinter, AutoHintOutlines}, hinting::{Hinting, HintingTables}, Font, FontCollection, FontLoadError, FontVariations, GlyphLocations, LazyGlyph, RawGlyphs, SharedGlyphsKey, SharedTables, VariableOutlines, VerticalMetrics};
+	use crate::ttf_reader::{self, CharacterToGlyphIndexTable, CollectionHeader, FontHeaderTable, FromTTFReader, GlyphSource, GlyphTable, HorizontalHeaderTable, HorizontalMetricsTable, IndexToLocationTable, MaximumProfileTable, OS2AndWindowsMetricsTable, TableDirectory, TableRecord, TableTag, TrueTypeFontReader, VerticalHeaderTable, VerticalMetricsTable, VerticalOriginTable, COLLECTION_TAG};
 	use crate::ttf_parser::{Direction, GlyphDataIntermediate, GlyphIntermediate};
+	use crate::variation_reader::{AxisVariationsTable, FontVariationsTable, GlyphVariationsTable, HorizontalMetricsVariationsTable, InstanceRecord, MetricsVariationsTable, VariationAxisRecord};
+	use crate::naming_reader::NamingTable;
+	use crate::colour_reader::{ColourPaletteTable, ColourTable};
+	use crate::bitmap_reader::{BitmapGlyphs, ColourBitmapDataTable, ColourBitmapLocationTable};
+	use crate::hinting_reader::{ControlValueTable, ProgramTable};
+	use crate::postscript_reader::PostScriptTable;
+	use crate::kerning_reader::KerningTable;
+	use crate::glyph_definition_reader::GlyphDefinitionTable;
+	use crate::glyph_positioning_reader::GlyphPositioningTable;
+	use crate::glyph_substitution_reader::GlyphSubstitutionTable;
+	use crate::woff_reader::{self, WOFF_SIGNATURE};
+	use crate::woff2_reader::{self, WOFF2_SIGNATURE};
+	use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
+	use std::collections::HashMap;
 	use std::sync::{Arc, Mutex};
 use std::{fs::File, path::Path};
 
-	pub fn read_one_glyph(filename: &Path, glyph_index: usize) {
-		let file = File::open(filename).unwrap();
+	/// Reads a glyph straight from a font's glyf or CFF table, for debugging. Simple glyphs can be triangulated from it with
+	/// `ToTriangles::to_triangles(true)`. None if the font doesn't have that glyph.
+	pub fn read_one_glyph(filename: &Path, glyph_index: usize) -> Result<Option<GlyphDataIntermediate>, FontLoadError> {
+		let file = File::open(filename).map_err(|error| FontLoadError::ReaderError(error.into()))?;
 		let mut ttf_reader = ttf_reader::TrueTypeFontReader::new(file);
-		let sfnt_version: u32 = ttf_reader.read_bytes().unwrap();
-		let number_of_tables: u16 = ttf_reader.read_bytes().unwrap();
-		assert_eq!(sfnt_version, 0x00010000);
+		let sfnt_version: u32 = ttf_reader.read_bytes().map_err(|error| FontLoadError::from_table_error(error, TABLE_DIRECTORY))?;
+		let number_of_tables: u16 = ttf_reader.read_bytes().map_err(|error| FontLoadError::from_table_error(error, TABLE_DIRECTORY))?;
+		if sfnt_version != 0x00010000 && sfnt_version != 0x4F54544F /* OTTO */ {
+			return Err(FontLoadError::UnsupportedSfntVersion(sfnt_version));
+		}
 
-		ttf_reader.skip(6).unwrap();
+		ttf_reader.skip(6).map_err(|error| FontLoadError::from_table_error(error, TABLE_DIRECTORY))?;
 
 		let mut glyph_table_record: Option<TableRecord> = None;
+		let mut compact_font_format_table_record: Option<TableRecord> = None;
 		let mut maximum_profile_table_record: Option<TableRecord> = None;
 		let mut index_to_location_table_record: Option<TableRecord> = None;
 		let mut font_header_table_record: Option<TableRecord> = None;
-		let mut character_to_glyph_index_table_record: Option<TableRecord> = None;
-		let mut horizontal_header_table: Option<TableRecord> = None;
-		let mut horizontal_metrics_table: Option<TableRecord> = None;
-		let mut os2_and_windows_metrics_table_record: Option<TableRecord> = None;
-		let mut other_table_records = Vec::<TableRecord>::new();
 
 		for _ in 0..number_of_tables {
-			let table_record: TableRecord = ttf_reader.read_bytes().unwrap();
+			let table_record: TableRecord = ttf_reader.read_bytes().map_err(|error| FontLoadError::from_table_error(error, TABLE_DIRECTORY))?;
 			match table_record.table_tag {
 				TableTag::Glyph => glyph_table_record = Some(table_record),
+				TableTag::CompactFontFormat => compact_font_format_table_record = Some(table_record),
 				TableTag::MaximumProfile => maximum_profile_table_record = Some(table_record),
 				TableTag::IndexToLocation => index_to_location_table_record = Some(table_record),
 				TableTag::FontHeader => font_header_table_record = Some(table_record),
-				TableTag::CharacterToGlyphIndex => character_to_glyph_index_table_record = Some(table_record),
-				TableTag::HorizontalHeaderTable => horizontal_header_table = Some(table_record),
-				TableTag::HorizontalMetricsTable => horizontal_metrics_table = Some(table_record),
-				TableTag::OS2AndWindowsMetricsTable => os2_and_windows_metrics_table_record = Some(table_record),
-				TableTag::Other(_) =>other_table_records.push(table_record),
+				_ => {},
 			};
 		}
 
-		let maximum_profile_table: MaximumProfileTable = match maximum_profile_table_record {
-			Some(maximum_profile_table_record) =>  ttf_reader.read(maximum_profile_table_record.offset).unwrap(),
-			None => panic!("Font should have a maxp table."),
-		};
+		if glyph_table_record.is_none() && compact_font_format_table_record.is_some() {
+			let compact_font_format_table: CompactFontFormatTable = read_table(&mut ttf_reader, compact_font_format_table_record, TableTag::CompactFontFormat, |table_record| table_record.offset)?;
+			if glyph_index >= compact_font_format_table.char_strings.len() {
+				return Ok(None);
+			}
+			return Ok(Some(GlyphIntermediate::from(compact_font_format_table.outline(glyph_index)).glyph_data));
+		}
 
-		let font_header_table: FontHeaderTable = match font_header_table_record {
-			Some(font_header_table_record) => ttf_reader.read(font_header_table_record.offset).unwrap(),
-			None => panic!("Font should have a head table."),
-		};
+		let maximum_profile_table: MaximumProfileTable = read_table(&mut ttf_reader, maximum_profile_table_record, TableTag::MaximumProfile, |table_record| table_record.offset)?;
+		let font_header_table: FontHeaderTable = read_table(&mut ttf_reader, font_header_table_record, TableTag::FontHeader, |table_record| table_record.offset)?;
+		let index_to_location_table: IndexToLocationTable = read_table(&mut ttf_reader, index_to_location_table_record, TableTag::IndexToLocation, |table_record| (table_record.offset, font_header_table.index_to_location_format, maximum_profile_table.num_glyphs))?;
+		let glyph_table: GlyphTable = read_table(&mut ttf_reader, glyph_table_record, TableTag::Glyph, |table_record| (index_to_location_table.glyph_offsets, table_record.offset as u64))?;
 
-		let index_to_location_table: IndexToLocationTable = match index_to_location_table_record {
-			Some(index_to_location_table_record) => ttf_reader.read((index_to_location_table_record.offset, font_header_table.index_to_location_format, maximum_profile_table.num_glyphs)).unwrap(),
-			None => panic!("Font should have a loca table."),
-		};
+		Ok(glyph_table.glyphs.into_iter().nth(glyph_index).map(|glyph| GlyphIntermediate::from(glyph).glyph_data))
+	}
 
-		let glyph_table: GlyphTable = match glyph_table_record {
-			Some(glyph_table_record) => ttf_reader.read((index_to_location_table.glyph_offsets, glyph_table_record.offset as u64)).unwrap(),
-			None => panic!("Font should have a glyf table."),
-		};
+	impl Font {
+		pub fn new(filename: &Path) -> Result<Self, FontLoadError> {
+			let file = File::open(filename).map_err(|error| FontLoadError::ReaderError(error.into()))?;
+			Self::from_lazy_reader(BufReader::new(file))
+		}
 
-		let mut glyphs: Vec<GlyphIntermediate> = glyph_table.glyphs.into_iter().map(|v| v.into()).collect();
+		/// Loads a font from bytes already in memory, such as a memory-mapped file. The bytes are only borrowed while loading,
+		/// so glyf is copied out of them for glyph outlines to be read from as they're needed.
+		pub fn from_bytes(bytes: &[u8]) -> Result<Self, FontLoadError> {
+			Self::from_reader(Cursor::new(bytes))
+		}
 
-		let character_to_glyph_index_table: CharacterToGlyphIndexTable = match character_to_glyph_index_table_record {
-			Some(charachter_to_glyph_index_table_record) => ttf_reader.read(charachter_to_glyph_index_table_record.offset).unwrap(),
-			None => panic!("Font should have a cmap table."),
-		};
+		/// Loads a font from bytes that live as long as the program, such as `include_bytes!` data, reading glyph outlines
+		/// from them as they're needed without copying anything.
+		pub fn from_static_bytes(bytes: &'static [u8]) -> Result<Self, FontLoadError> {
+			Self::from_lazy_reader(Cursor::new(bytes))
+		}
 
-		let glyph = match glyphs.remove(glyph_index).glyph_data {
-			GlyphDataIntermediate::CompositeGlyph(_) => todo!(),
-			GlyphDataIntermediate::None => todo!(),
-			GlyphDataIntermediate::SimpleGlyph(simple_glyph) => {
-				simple_glyph.to_triangles(true)
-			},
-		};
+		/// Loads a font from any seekable source. For a collection, the first face is loaded, and a WOFF or WOFF2 file is unpacked first.
+		/// The source isn't kept, so glyf is copied out of it for glyph outlines to be read from as they're needed.
+		pub fn from_reader<Source: Read + Seek>(source: Source) -> Result<Self, FontLoadError> {
+			Self::from_ttf_reader(TrueTypeFontReader::from_source(source), |_| None)
+		}
 
-		match glyph {
-			Ok(_) => println!("Success"),
-			Err(_) => println!("Error"),
+		/// As `from_reader`, but the font keeps the source and reads glyph outlines from it as they're needed, so nothing is copied.
+		/// Shared bytes can be read this way through a `Cursor<Arc<[u8]>>`.
+		pub fn from_lazy_reader<Source: Read + Seek + Send + 'static>(source: Source) -> Result<Self, FontLoadError> {
+			Self::from_ttf_reader(TrueTypeFontReader::from_source(source), shared_glyph_source)
 		}
-	}
 
-	impl Font {
-		pub fn new(filename: &Path) -> Self {
-			let file = File::open(filename).unwrap();
-			let mut ttf_reader = ttf_reader::TrueTypeFontReader::new(file);
-			let sfnt_version: u32 = ttf_reader.read_bytes().unwrap();
-			let number_of_tables: u16 = ttf_reader.read_bytes().unwrap();
-			assert_eq!(sfnt_version, 0x00010000);
+		fn from_ttf_reader<Source: Read + Seek>(mut ttf_reader: TrueTypeFontReader<Source>, glyph_source: KeepSource<Source>) -> Result<Self, FontLoadError> {
+			let tag: u32 = ttf_reader.read_bytes().map_err(|error| FontLoadError::from_table_error(error, TABLE_DIRECTORY))?;
+			if tag == COLLECTION_TAG {
+				return FontCollection::from_ttf_reader(ttf_reader, glyph_source)?.face(0);
+			}
+			// Unpacked fonts are already in memory of their own, so they're always kept.
+			if tag == WOFF_SIGNATURE {
+				let sfnt = woff_reader::decode(&mut ttf_reader)?;
+				return Self::from_lazy_reader(Cursor::new(sfnt));
+			}
+			if tag == WOFF2_SIGNATURE {
+				let sfnt = woff2_reader::decode(&mut ttf_reader)?;
+				return Self::from_lazy_reader(Cursor::new(sfnt));
+			}
+
+			let table_directory: TableDirectory = ttf_reader.read(0).map_err(|error| FontLoadError::from_table_error(error, TABLE_DIRECTORY))?;
+			let source = Arc::new(Mutex::new(ttf_reader));
+			Self::from_table_directory(&source, glyph_source(&source).as_ref(), &table_directory, &mut SharedTables::default())
+		}
+
+		pub fn from_collection(filename: &Path, face_index: usize) -> Result<Self, FontLoadError> {
+			FontCollection::open(filename)?.face(face_index)
+		}
 
-			ttf_reader.skip(6).unwrap();
+		/// Loads a face, reading its glyf outlines from `glyph_source` as they're needed, or from a copy of glyf if there isn't one.
+		fn from_table_directory<Source: Read + Seek>(source: &Arc<Mutex<TrueTypeFontReader<Source>>>, glyph_source: Option<&Arc<Mutex<dyn GlyphSource>>>, table_directory: &TableDirectory, shared_tables: &mut SharedTables) -> Result<Self, FontLoadError> {
+			let mut source_lock = source.lock().unwrap();
+			let ttf_reader = &mut *source_lock;
+			let source_length = ttf_reader.length()?;
+			if table_directory.sfnt_version != 0x00010000 && table_directory.sfnt_version != 0x4F54544F /* OTTO */ {
+				return Err(FontLoadError::UnsupportedSfntVersion(table_directory.sfnt_version));
+			}
 
 			let mut glyph_table_record: Option<TableRecord> = None;
 			let mut maximum_profile_table_record: Option<TableRecord> = None;
@@ -102,10 +159,37 @@ use std::{fs::File, path::Path};
 			let mut horizontal_header_table_record: Option<TableRecord> = None;
 			let mut horizontal_metrics_table_record: Option<TableRecord> = None;
 			let mut os2_and_windows_metrics_table_record: Option<TableRecord> = None;
+			let mut compact_font_format_table_record: Option<TableRecord> = None;
+			let mut compact_font_format_2_table_record: Option<TableRecord> = None;
+			let mut font_variations_table_record: Option<TableRecord> = None;
+			let mut glyph_variations_table_record: Option<TableRecord> = None;
+			let mut axis_variations_table_record: Option<TableRecord> = None;
+			let mut horizontal_metrics_variations_table_record: Option<TableRecord> = None;
+			let mut metrics_variations_table_record: Option<TableRecord> = None;
+			let mut naming_table_record: Option<TableRecord> = None;
+			let mut postscript_table_record: Option<TableRecord> = None;
+			let mut kerning_table_record: Option<TableRecord> = None;
+			let mut glyph_positioning_table_record: Option<TableRecord> = None;
+			let mut glyph_substitution_table_record: Option<TableRecord> = None;
+			let mut glyph_definition_table_record: Option<TableRecord> = None;
+			let mut vertical_header_table_record: Option<TableRecord> = None;
+			let mut vertical_metrics_table_record: Option<TableRecord> = None;
+			let mut vertical_origin_table_record: Option<TableRecord> = None;
+			let mut colour_table_record: Option<TableRecord> = None;
+			let mut colour_palette_table_record: Option<TableRecord> = None;
+			let mut colour_bitmap_location_table_record: Option<TableRecord> = None;
+			let mut colour_bitmap_data_table_record: Option<TableRecord> = None;
+			let mut standard_bitmap_graphics_table_record: Option<TableRecord> = None;
+			let mut control_value_table_record: Option<TableRecord> = None;
+			let mut font_program_table_record: Option<TableRecord> = None;
+			let mut control_value_program_table_record: Option<TableRecord> = None;
+			let mut grid_fitting_table_record: Option<TableRecord> = None;
 			let mut other_table_records = Vec::<TableRecord>::new();
 
-			for _ in 0..number_of_tables {
-				let table_record: TableRecord = ttf_reader.read_bytes().unwrap();
+			for table_record in table_directory.table_records.iter().copied() {
+				if table_record.offset as u64 + table_record.length as u64 > source_length {
+					return Err(FontLoadError::OffsetOutOfRange(table_record.table_tag, table_record.offset as u64));
+				}
 				match table_record.table_tag {
 					TableTag::Glyph => glyph_table_record = Some(table_record),
 					TableTag::MaximumProfile => maximum_profile_table_record = Some(table_record),
@@ -115,57 +199,263 @@ use std::{fs::File, path::Path};
 					TableTag::HorizontalHeaderTable => horizontal_header_table_record = Some(table_record),
 					TableTag::HorizontalMetricsTable => horizontal_metrics_table_record = Some(table_record),
 					TableTag::OS2AndWindowsMetricsTable => os2_and_windows_metrics_table_record = Some(table_record),
+					TableTag::CompactFontFormat => compact_font_format_table_record = Some(table_record),
+					TableTag::CompactFontFormat2 => compact_font_format_2_table_record = Some(table_record),
+					TableTag::FontVariations => font_variations_table_record = Some(table_record),
+					TableTag::GlyphVariations => glyph_variations_table_record = Some(table_record),
+					TableTag::AxisVariations => axis_variations_table_record = Some(table_record),
+					TableTag::HorizontalMetricsVariations => horizontal_metrics_variations_table_record = Some(table_record),
+					TableTag::MetricsVariations => metrics_variations_table_record = Some(table_record),
+					TableTag::Naming => naming_table_record = Some(table_record),
+					TableTag::PostScript => postscript_table_record = Some(table_record),
+					TableTag::Kerning => kerning_table_record = Some(table_record),
+					TableTag::GlyphPositioning => glyph_positioning_table_record = Some(table_record),
+					TableTag::GlyphSubstitution => glyph_substitution_table_record = Some(table_record),
+					TableTag::GlyphDefinition => glyph_definition_table_record = Some(table_record),
+					TableTag::VerticalHeaderTable => vertical_header_table_record = Some(table_record),
+					TableTag::VerticalMetricsTable => vertical_metrics_table_record = Some(table_record),
+					TableTag::VerticalOrigin => vertical_origin_table_record = Some(table_record),
+					TableTag::Colour => colour_table_record = Some(table_record),
+					TableTag::ColourPalette => colour_palette_table_record = Some(table_record),
+					TableTag::ColourBitmapLocation => colour_bitmap_location_table_record = Some(table_record),
+					TableTag::ColourBitmapData => colour_bitmap_data_table_record = Some(table_record),
+					TableTag::StandardBitmapGraphics => standard_bitmap_graphics_table_record = Some(table_record),
+					TableTag::ControlValue => control_value_table_record = Some(table_record),
+					TableTag::FontProgram => font_program_table_record = Some(table_record),
+					TableTag::ControlValueProgram => control_value_program_table_record = Some(table_record),
+					TableTag::GridFitting => grid_fitting_table_record = Some(table_record),
 					TableTag::Other(_) => other_table_records.push(table_record),
 				};
 			}
 
-			let maximum_profile_table: MaximumProfileTable = match maximum_profile_table_record {
-				Some(maximum_profile_table_record) =>  ttf_reader.read(maximum_profile_table_record.offset).unwrap(),
-				None => panic!("Font should have a maxp table."),
+			let maximum_profile_table: MaximumProfileTable = read_table(ttf_reader, maximum_profile_table_record, TableTag::MaximumProfile, |record| record.offset)?;
+
+			let font_header_table: FontHeaderTable = read_table(ttf_reader, font_header_table_record, TableTag::FontHeader, |record| record.offset)?;
+
+			let horizontal_header_table: HorizontalHeaderTable = read_table(ttf_reader, horizontal_header_table_record, TableTag::HorizontalHeaderTable, |record| record.offset)?;
+
+			let horizontal_metrics_table: HorizontalMetricsTable = read_table(ttf_reader, horizontal_metrics_table_record, TableTag::HorizontalMetricsTable, |record| (horizontal_header_table.number_of_horizontal_metrics, maximum_profile_table.num_glyphs, record.offset as u64))?;
+
+			let os2_and_windows_metrics_table: OS2AndWindowsMetricsTable = read_table(ttf_reader, os2_and_windows_metrics_table_record, TableTag::OS2AndWindowsMetricsTable, |record| record.offset)?;
+
+			let postscript_table: Option<PostScriptTable> = read_optional_table(ttf_reader, postscript_table_record, TableTag::PostScript, |record| (record.offset, record.length));
+
+			// vmtx can't be read without the number of metrics in vhea, and VORG is only used alongside them.
+			let vertical_metrics: Option<Arc<VerticalMetrics>> = match vertical_header_table_record {
+				Some(_) => {
+					let vertical_header_table: VerticalHeaderTable = read_table(ttf_reader, vertical_header_table_record, TableTag::VerticalHeaderTable, |record| record.offset)?;
+					let vertical_metrics_table: Option<VerticalMetricsTable> = match vertical_metrics_table_record {
+						Some(_) => Some(read_table(ttf_reader, vertical_metrics_table_record, TableTag::VerticalMetricsTable, |record| (vertical_header_table.number_of_vertical_metrics, maximum_profile_table.num_glyphs, record.offset as u64))?),
+						None => None,
+					};
+					let vertical_origin_table: Option<VerticalOriginTable> = match vertical_origin_table_record {
+						Some(_) => Some(read_table(ttf_reader, vertical_origin_table_record, TableTag::VerticalOrigin, |record| record.offset)?),
+						None => None,
+					};
+					Some(Arc::new(VerticalMetrics {
+						ascender: vertical_header_table.ascender.into(),
+						descender: vertical_header_table.descender.into(),
+						line_gap: vertical_header_table.line_gap.into(),
+						metrics: vertical_metrics_table.map(|table| table.vertical_metrics).unwrap_or_default(),
+						vertical_origins: vertical_origin_table,
+					}))
+				},
+				None => None,
 			};
 
-			let font_header_table: FontHeaderTable = match font_header_table_record {
-				Some(font_header_table_record) => ttf_reader.read(font_header_table_record.offset).unwrap(),
-				None => panic!("Font should have a head table."),
+			let (axes, instances): (Vec<VariationAxisRecord>, Vec<InstanceRecord>) = match font_variations_table_record {
+				Some(_) => {
+					let font_variations_table: FontVariationsTable = read_table(ttf_reader, font_variations_table_record, TableTag::FontVariations, |record| record.offset)?;
+					(font_variations_table.axes, font_variations_table.instances)
+				},
+				None => (Vec::new(), Vec::new()),
 			};
+			let variation_coordinates = vec![0.0; axes.len()];
 
-			let index_to_location_table: IndexToLocationTable = match index_to_location_table_record {
-				Some(index_to_location_table_record) => ttf_reader.read((index_to_location_table_record.offset, font_header_table.index_to_location_format, maximum_profile_table.num_glyphs)).unwrap(),
-				None => panic!("Font should have a loca table."),
+			let axis_variations: Option<AxisVariationsTable> = match axis_variations_table_record {
+				Some(_) => Some(read_table(ttf_reader, axis_variations_table_record, TableTag::AxisVariations, |record| record.offset)?),
+				None => None,
 			};
 
-			let horizontal_header_table: HorizontalHeaderTable = match horizontal_header_table_record {
-				Some(horizontal_header_table_record) => ttf_reader.read(horizontal_header_table_record.offset).unwrap(),
-				None => panic!("Font should have a hhea table."),
+			let horizontal_metrics_variations: Option<HorizontalMetricsVariationsTable> = match horizontal_metrics_variations_table_record {
+				Some(_) => Some(read_table(ttf_reader, horizontal_metrics_variations_table_record, TableTag::HorizontalMetricsVariations, |record| record.offset)?),
+				None => None,
 			};
 
-			let horizontal_metrics_table: HorizontalMetricsTable = match horizontal_metrics_table_record {
-				Some(horizontal_metrics_table_record) => ttf_reader.read((horizontal_header_table.number_of_horizontal_metrics, maximum_profile_table.num_glyphs, horizontal_metrics_table_record.offset as u64)).unwrap(),
-				None => panic!("Font should have a hmtx table."),
+			let metrics_variations: Option<MetricsVariationsTable> = match metrics_variations_table_record {
+				Some(_) => Some(read_table(ttf_reader, metrics_variations_table_record, TableTag::MetricsVariations, |record| record.offset)?),
+				None => None,
 			};
 
-			let os2_and_windows_metrics_table: OS2AndWindowsMetricsTable = match os2_and_windows_metrics_table_record {
-				Some(os2_and_windows_metrics_table_record) => ttf_reader.read(os2_and_windows_metrics_table_record.offset).unwrap(),
-				None => panic!("Font should have a OS/2 table."),
+			// The values MVAR deltas are added to, by the tags it uses for them.
+			let mut default_metrics: HashMap<[u8; 4], i16> = HashMap::from([
+				(*b"hasc", os2_and_windows_metrics_table.s_typographic_ascender),
+				(*b"hdsc", os2_and_windows_metrics_table.s_typographic_descender),
+				(*b"hlgp", os2_and_windows_metrics_table.s_typographic_line_gap),
+				(*b"hcla", os2_and_windows_metrics_table.us_windows_ascent as i16),
+				(*b"hcld", os2_and_windows_metrics_table.us_windows_descend as i16),
+			]);
+			if let Some(postscript_table) = &postscript_table {
+				default_metrics.extend([(*b"undo", postscript_table.underline_position), (*b"unds", postscript_table.underline_thickness)]);
+			}
+			if let Some(vertical_metrics) = &vertical_metrics {
+				default_metrics.extend([(*b"vasc", vertical_metrics.ascender.value), (*b"vdsc", vertical_metrics.descender.value), (*b"vlgp", vertical_metrics.line_gap.value)]);
+			}
+
+			// CBLC can't be used without CBDT, which its offsets are into. sbix is only read for fonts that have neither.
+			let bitmap_glyphs: Option<Arc<BitmapGlyphs>> = match (colour_bitmap_location_table_record, colour_bitmap_data_table_record, standard_bitmap_graphics_table_record) {
+				(Some(_), Some(_), _) => {
+					let colour_bitmap_location_table: ColourBitmapLocationTable = read_table(ttf_reader, colour_bitmap_location_table_record, TableTag::ColourBitmapLocation, |record| record.offset)?;
+					let colour_bitmap_data_table: ColourBitmapDataTable = read_table(ttf_reader, colour_bitmap_data_table_record, TableTag::ColourBitmapData, |record| (record.offset, record.length))?;
+					Some(Arc::new(BitmapGlyphs::ColourBitmaps(colour_bitmap_location_table, colour_bitmap_data_table)))
+				},
+				(_, _, Some(_)) => Some(Arc::new(BitmapGlyphs::StandardBitmapGraphics(read_table(ttf_reader, standard_bitmap_graphics_table_record, TableTag::StandardBitmapGraphics, |record| (record.offset, record.length, maximum_profile_table.num_glyphs))?))),
+				_ => None,
 			};
 
-			let glyph_table: GlyphTable = match glyph_table_record {
-				Some(glyph_table_record) => ttf_reader.read((index_to_location_table.glyph_offsets, glyph_table_record.offset as u64)).unwrap(),
-				None => panic!("Font should have a glyf table."),
+			// Glyph outlines come from glyf when present, otherwise from CFF or CFF2.
+			let outline_table_record = glyph_table_record.or(compact_font_format_table_record).or(compact_font_format_2_table_record);
+
+			// glyf outlines are read from the font data as each glyph is first needed, so only where they are in it is read from loca here.
+			let glyph_locations = match glyph_table_record {
+				Some(table_record) => {
+					let index_to_location_table: IndexToLocationTable = read_table(ttf_reader, index_to_location_table_record, TableTag::IndexToLocation, |record| (record.offset, font_header_table.index_to_location_format, maximum_profile_table.num_glyphs))?;
+					Self::check_glyph_offsets(glyph_table_record, &index_to_location_table)?;
+					let (glyph_source, glyph_table_start) = match glyph_source {
+						Some(glyph_source) => (glyph_source.clone(), table_record.offset as u64),
+						None => (Self::copy_glyph_table(ttf_reader, table_record)?, 0),
+					};
+					Some(Arc::new(GlyphLocations::new(glyph_source, index_to_location_table.glyph_offsets, glyph_table_start)))
+				},
+				None => None,
 			};
 
-			let glyphs: Vec<GlyphIntermediate> = glyph_table.glyphs.into_iter().map(|v| v.into()).collect();
+			// CFF2 outlines, and glyf outlines with gvar deltas, are kept, so they can be varied again for other instances.
+			let variable_outlines = match (&glyph_locations, compact_font_format_table_record, compact_font_format_2_table_record, glyph_variations_table_record) {
+				(None, None, Some(_), _) => {
+					let compact_font_format_2_table: CompactFontFormat2Table = read_table(ttf_reader, compact_font_format_2_table_record, TableTag::CompactFontFormat2, |record| record.offset)?;
+					Some(VariableOutlines::CompactFontFormat2(compact_font_format_2_table))
+				},
+				(Some(glyph_locations), _, _, Some(_)) if !axes.is_empty() => {
+					let glyph_variations_table: GlyphVariationsTable = read_table(ttf_reader, glyph_variations_table_record, TableTag::GlyphVariations, |record| record.offset)?;
+					Some(VariableOutlines::TrueType(RawGlyphs::Unread(glyph_locations.clone()), glyph_variations_table))
+				},
+				_ => None,
+			};
+			let variations = variable_outlines.map(|outlines| Arc::new(FontVariations {
+				axes,
+				instances,
+				axis_variations,
+				outlines,
+				horizontal_metrics: horizontal_metrics_table.horizontal_metrics.clone(),
+				horizontal_metrics_variations,
+				metrics_variations,
+				default_metrics,
+			}));
+			let has_instructions = font_program_table_record.or(control_value_program_table_record).or(control_value_table_record).is_some();
+			let hinting: Option<Arc<Hinting>> = match glyph_locations.as_ref().filter(|_| has_instructions) {
+				Some(glyph_locations) => {
+					let read_program = |ttf_reader: &mut TrueTypeFontReader<Source>, table_record: Option<TableRecord>, table_tag: TableTag| -> Result<Vec<u8>, FontLoadError> {
+						match table_record {
+							Some(_) => Ok(read_table::<_, ProgramTable>(ttf_reader, table_record, table_tag, |record| (record.offset, record.length))?.instructions),
+							None => Ok(Vec::new()),
+						}
+					};
+					let tables = HintingTables {
+						font_program: read_program(ttf_reader, font_program_table_record, TableTag::FontProgram)?,
+						control_value_program: read_program(ttf_reader, control_value_program_table_record, TableTag::ControlValueProgram)?,
+						control_values: match control_value_table_record {
+							Some(_) => read_table::<_, ControlValueTable>(ttf_reader, control_value_table_record, TableTag::ControlValue, |record| (record.offset, record.length))?.control_values,
+							None => Vec::new(),
+						},
+						grid_fitting: match grid_fitting_table_record {
+							Some(_) => Some(read_table(ttf_reader, grid_fitting_table_record, TableTag::GridFitting, |record| record.offset)?),
+							None => None,
+						},
+					};
+					Some(Arc::new(Hinting::new(tables, RawGlyphs::Unread(glyph_locations.clone()), horizontal_metrics_table.horizontal_metrics.clone(), font_header_table.units_per_em, &maximum_profile_table)))
+				},
+				None => None,
+			};
 
+			let compact_font_format_table: Option<CompactFontFormatTable> = match (glyph_table_record, compact_font_format_table_record) {
+				(None, Some(_)) => Some(read_table(ttf_reader, compact_font_format_table_record, TableTag::CompactFontFormat, |record| record.offset)?),
+				_ => None,
+			};
 
-			let character_to_glyph_index_table: CharacterToGlyphIndexTable = match character_to_glyph_index_table_record {
-				Some(charachter_to_glyph_index_table_record) => ttf_reader.read(charachter_to_glyph_index_table_record.offset).unwrap(),
-				None => panic!("Font should have a cmap table."),
+			let character_to_glyph_index_table_record = character_to_glyph_index_table_record.ok_or(FontLoadError::MissingTable(TableTag::CharacterToGlyphIndex))?;
+			let mappings = match shared_tables.mappings.get(&character_to_glyph_index_table_record.offset) {
+				Some(mappings) => mappings.clone(),
+				None => {
+					let character_to_glyph_index_table: CharacterToGlyphIndexTable = read_table(ttf_reader, Some(character_to_glyph_index_table_record), TableTag::CharacterToGlyphIndex, |record| record.offset)?;
+					let mappings: Arc<Vec<font::Mapping>> = Arc::new(character_to_glyph_index_table.encoding_records.iter().zip(character_to_glyph_index_table.subtables).map(|v| v.into()).collect());
+					shared_tables.mappings.insert(character_to_glyph_index_table_record.offset, mappings.clone());
+					mappings
+				}
 			};
 
-			let mappings: Vec<font::Mapping> = character_to_glyph_index_table.subtables.into_iter().map(|v| v.into()).collect();
-			let glyphs: Vec<LazyGlyph> = glyphs.into_iter().zip(horizontal_metrics_table.horizontal_metrics).map(|(intermediate, horizontal_metric)| LazyGlyph::GlyphIncomplete(Arc::new(intermediate), Arc::new(horizontal_metric))).collect();
-			let number_of_glyphs = glyphs.len();
-			let glyphs = Mutex::new(glyphs);
+			// Names, kerning, layout and colour data can be done without, so any that can't be read are skipped rather than failing the font.
+			let names: Vec<font::Name> = read_optional_table::<_, NamingTable>(ttf_reader, naming_table_record, TableTag::Naming, |record| record.offset).map(Vec::from).unwrap_or_default();
+
+			let kerning: HashMap<(u16, u16), i16> = read_optional_table::<_, KerningTable>(ttf_reader, kerning_table_record, TableTag::Kerning, |record| record.offset).map(|table| font::kerning_pairs(&table)).unwrap_or_default();
+
+			let glyph_positioning: Option<Arc<GlyphPositioningTable>> = read_optional_table(ttf_reader, glyph_positioning_table_record, TableTag::GlyphPositioning, |record| record.offset).map(Arc::new);
+
+			let glyph_substitution: Option<Arc<GlyphSubstitutionTable>> = read_optional_table(ttf_reader, glyph_substitution_table_record, TableTag::GlyphSubstitution, |record| record.offset).map(Arc::new);
+
+			let glyph_definition: Option<Arc<GlyphDefinitionTable>> = read_optional_table(ttf_reader, glyph_definition_table_record, TableTag::GlyphDefinition, |record| record.offset).map(Arc::new);
+
+			let colour_glyphs: Option<Arc<ColourTable>> = read_optional_table(ttf_reader, colour_table_record, TableTag::Colour, |record| record.offset).map(Arc::new);
+
+			let colour_palettes: Option<Arc<ColourPaletteTable>> = read_optional_table(ttf_reader, colour_palette_table_record, TableTag::ColourPalette, |record| record.offset).map(Arc::new);
+
+			// Glyf outlines, varied or not, are read from the source as they're first asked for, so it's let go of first.
+			drop(source_lock);
+
+			let shared_glyphs_key = match (outline_table_record, horizontal_metrics_table_record) {
+				(Some(outline_table_record), Some(horizontal_metrics_table_record)) => Some(SharedGlyphsKey {
+					outlines: outline_table_record.offset,
+					horizontal_metrics: horizontal_metrics_table_record.offset,
+					vertical_metrics: vertical_metrics_table_record.map(|record| record.offset),
+					vertical_origins: vertical_origin_table_record.map(|record| record.offset),
+					colour_glyphs: colour_table_record.map(|record| record.offset),
+					bitmap_glyphs: [colour_bitmap_location_table_record, colour_bitmap_data_table_record, standard_bitmap_graphics_table_record].map(|record| record.map(|record| record.offset)),
+					em_box: (font_header_table.units_per_em, os2_and_windows_metrics_table.us_windows_ascent as i16, os2_and_windows_metrics_table.us_windows_descend as i16),
+				}),
+				_ => None,
+			};
+			let shared_glyphs = shared_glyphs_key.and_then(|key| shared_tables.glyphs.get(&key).cloned());
+
+			let glyphs = match shared_glyphs {
+				Some(glyphs) => glyphs,
+				None => {
+					let glyphs = match (glyph_table_record, &compact_font_format_table, &variations) {
+						(None, Some(compact_font_format_table), _) => Self::compact_font_format_glyphs(compact_font_format_table, maximum_profile_table.num_glyphs, horizontal_metrics_table),
+						(_, _, Some(variations)) => variations.glyphs(&variation_coordinates),
+						// Bitmap only fonts have no outlines, so their glyphs start empty and are given their images as they're loaded.
+						(None, None, None) if bitmap_glyphs.is_some() => Self::empty_glyphs(horizontal_metrics_table),
+						_ => match &glyph_locations {
+							Some(glyph_locations) => Self::unread_glyphs(glyph_locations, horizontal_metrics_table),
+							None => return Err(FontLoadError::MissingTable(TableTag::Glyph)),
+						},
+					};
+					let glyphs = Arc::new(Mutex::new(glyphs));
+					if let Some(key) = shared_glyphs_key {
+						shared_tables.glyphs.insert(key, glyphs.clone());
+					}
+					glyphs
+				}
+			};
+			let number_of_glyphs = glyphs.lock().unwrap().len();
+
+			// Outlines without instructions are hinted automatically, from blue zones measured on the characters the cmap maps.
+			// Variable fonts are only hinted by instructions.
+			let auto_hint_outlines = match (&hinting, glyph_locations.filter(|_| variations.is_none()), compact_font_format_table) {
+				(None, Some(glyph_locations), _) => Some(AutoHintOutlines::TrueType(RawGlyphs::Unread(glyph_locations))),
+				(None, None, Some(compact_font_format_table)) => Some(AutoHintOutlines::CompactFontFormat(compact_font_format_table)),
+				_ => None,
+			};
+			let auto_hinter = auto_hint_outlines.map(|outlines| Arc::new(AutoHinter::new(outlines, font_header_table.units_per_em, mappings.clone())));
 
 			/*for (glyph, horizontal_metric) in glyphs.iter_mut().zip(horizontal_metrics_table.horizontal_metrics) {
 				glyph.set_horizontal_metrics(horizontal_metric);
@@ -182,9 +472,26 @@ use std::{fs::File, path::Path};
 			println!("Units Per Em: {:?}", font_header_table.units_per_em); */
 
 
-			Font {
+			Ok(Font {
 				glyphs,
+				selected_mapping: font::preferred_mapping(&mappings),
 				mappings,
+				names: Arc::new(names),
+				italic_angle: postscript_table.as_ref().map_or(0.0, |table| table.italic_angle),
+				underline_position: postscript_table.as_ref().map_or(0, |table| table.underline_position).into(),
+				underline_thickness: postscript_table.as_ref().map_or(0, |table| table.underline_thickness).into(),
+				is_fixed_pitch: postscript_table.as_ref().is_some_and(|table| table.is_fixed_pitch),
+				kerning: Arc::new(kerning),
+				glyph_positioning,
+				glyph_substitution,
+				glyph_definition,
+				vertical_metrics,
+				colour_glyphs,
+				colour_palettes,
+				bitmap_glyphs,
+				hinting,
+				auto_hinter,
+				glyph_names: Arc::new(postscript_table.map(|table| table.glyph_names).unwrap_or_default()),
 				units_per_em: font_header_table.units_per_em.into(),
 				typographic_descender: (os2_and_windows_metrics_table.us_windows_descend as i16).into(),
 				typographic_ascender: (os2_and_windows_metrics_table.us_windows_ascent as i16).into(),
@@ -192,20 +499,295 @@ use std::{fs::File, path::Path};
 				//typographic_ascender: (os2_and_windows_metrics_table.s_typographic_ascender).into(),
 				line_spacing: (os2_and_windows_metrics_table.s_typographic_ascender - os2_and_windows_metrics_table.s_typographic_descender + os2_and_windows_metrics_table.s_typographic_line_gap).into(),
 				number_of_glyphs,
+				variations,
+				variation_coordinates,
+			})
+
+		}
+	}
+
+	impl Font {
+		fn unread_glyphs(glyph_locations: &Arc<GlyphLocations>, horizontal_metrics_table: HorizontalMetricsTable) -> Vec<LazyGlyph> {
+			horizontal_metrics_table.horizontal_metrics.into_iter().take(glyph_locations.len()).map(|horizontal_metric| LazyGlyph::GlyphUnread(glyph_locations.clone(), Arc::new(horizontal_metric))).collect()
+		}
+
+		/// glyf copied into memory, for fonts whose source can't be kept to read glyph outlines from.
+		fn copy_glyph_table<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, glyph_table_record: TableRecord) -> Result<Arc<Mutex<dyn GlyphSource>>, FontLoadError> {
+			let mut glyph_table = vec![0u8; glyph_table_record.length as usize];
+			ttf_reader.buffer_reader.seek(SeekFrom::Start(glyph_table_record.offset as u64)).and_then(|_| ttf_reader.buffer_reader.read_exact(&mut glyph_table)).map_err(|error| FontLoadError::from_table_error(error.into(), TableTag::Glyph))?;
+			Ok(Arc::new(Mutex::new(TrueTypeFontReader::from_source(Cursor::new(glyph_table)))))
+		}
+
+		/// Checks every glyph loca points to is inside glyf, so glyphs read later can't run past it.
+		fn check_glyph_offsets(glyph_table_record: Option<TableRecord>, index_to_location_table: &IndexToLocationTable) -> Result<(), FontLoadError> {
+			let glyph_table_length = glyph_table_record.ok_or(FontLoadError::MissingTable(TableTag::Glyph))?.length;
+			for glyph_offset in index_to_location_table.glyph_offsets.iter() {
+				if let (Some(offset), Some(length)) = (glyph_offset.glyph_offset, glyph_offset.glyph_length) && offset as u64 + length as u64 > glyph_table_length as u64 {
+					return Err(FontLoadError::OffsetOutOfRange(TableTag::Glyph, offset as u64));
+				}
 			}
+			Ok(())
+		}
+
+		fn empty_glyphs(horizontal_metrics_table: HorizontalMetricsTable) -> Vec<LazyGlyph> {
+			horizontal_metrics_table.horizontal_metrics.into_iter().map(|horizontal_metric| {
+				let intermediate = GlyphIntermediate { number_of_contours: None, bounds: [0; 4].into(), glyph_data: GlyphDataIntermediate::None };
+				LazyGlyph::GlyphIncomplete(Arc::new(intermediate), Arc::new(horizontal_metric))
+			}).collect()
+		}
+
+		fn compact_font_format_glyphs(compact_font_format_table: &CompactFontFormatTable, number_of_glyphs: u16, horizontal_metrics_table: HorizontalMetricsTable) -> Vec<LazyGlyph> {
+			let glyphs: Vec<GlyphIntermediate> = (0..(number_of_glyphs as usize).min(compact_font_format_table.char_strings.len())).map(|glyph_index| compact_font_format_table.outline(glyph_index).into()).collect();
+			glyphs.into_iter().zip(horizontal_metrics_table.horizontal_metrics).map(|(intermediate, horizontal_metric)| LazyGlyph::GlyphIncomplete(Arc::new(intermediate), Arc::new(horizontal_metric))).collect()
+		}
+	}
+
+	impl FontCollection<BufReader<File>> {
+		pub fn open(filename: &Path) -> Result<Self, FontLoadError> {
+			let file = File::open(filename).map_err(|error| FontLoadError::ReaderError(error.into()))?;
+			Self::from_lazy_reader(BufReader::new(file))
+		}
+	}
 
+	impl<Source: Read + Seek + Send + 'static> FontCollection<Source> {
+		/// As `from_reader`, but the faces keep the source and read glyph outlines from it as they're needed.
+		pub fn from_lazy_reader(source: Source) -> Result<Self, FontLoadError> {
+			Self::from_ttf_reader(TrueTypeFontReader::from_source(source), shared_glyph_source)
 		}
 	}
+
+	impl<Source: Read + Seek> FontCollection<Source> {
+		/// Reads a collection's table directories. The source isn't kept by its faces, so each copies its glyf out of it.
+		pub fn from_reader(source: Source) -> Result<Self, FontLoadError> {
+			Self::from_ttf_reader(TrueTypeFontReader::from_source(source), |_| None)
+		}
+
+		fn from_ttf_reader(mut ttf_reader: TrueTypeFontReader<Source>, glyph_source: KeepSource<Source>) -> Result<Self, FontLoadError> {
+			let header: CollectionHeader = ttf_reader.read(())?;
+			let mut faces: Vec<TableDirectory> = Vec::with_capacity(header.table_directory_offsets.len());
+			for table_directory_offset in header.table_directory_offsets.iter() {
+				faces.push(ttf_reader.read(*table_directory_offset).map_err(|error| FontLoadError::from_table_error(error, TABLE_DIRECTORY))?);
+			}
+
+			let ttf_reader = Arc::new(Mutex::new(ttf_reader));
+			Ok(FontCollection {
+				glyph_source: glyph_source(&ttf_reader),
+				ttf_reader,
+				header,
+				faces,
+				shared_tables: SharedTables::default(),
+			})
+		}
+
+		pub fn number_of_faces(&self) -> usize {
+			self.faces.len()
+		}
+
+		pub fn face(&mut self, face_index: usize) -> Result<Font, FontLoadError> {
+			let table_directory = match self.faces.get(face_index) {
+				Some(table_directory) => table_directory,
+				None => return Err(FontLoadError::FaceIndexOutOfRange(face_index, self.faces.len())),
+			};
+			Font::from_table_directory(&self.ttf_reader, self.glyph_source.as_ref(), table_directory, &mut self.shared_tables)
+		}
+	}
+
+	/// Stands for a face's table directory, which isn't a table of its own, in errors about it.
+	const TABLE_DIRECTORY: TableTag = TableTag::Other(['s', 'f', 'n', 't']);
+
+	/// Gives the font data for faces to keep, to read glyph outlines from as they're needed, or None if it can't be kept.
+	type KeepSource<Source> = fn(&Arc<Mutex<TrueTypeFontReader<Source>>>) -> Option<Arc<Mutex<dyn GlyphSource>>>;
+
+	/// The font data itself, for fonts that keep it to read glyph outlines from as they're needed.
+	fn shared_glyph_source<Source: Read + Seek + Send + 'static>(ttf_reader: &Arc<Mutex<TrueTypeFontReader<Source>>>) -> Option<Arc<Mutex<dyn GlyphSource>>> {
+		Some(ttf_reader.clone())
+	}
+
+	/// Reads a required table, attributing any failure to that table.
+	fn read_table<Source: Read + Seek, Type: FromTTFReader>(ttf_reader: &mut TrueTypeFontReader<Source>, table_record: Option<TableRecord>, table_tag: TableTag, input: impl FnOnce(TableRecord) -> Type::Input) -> Result<Type, FontLoadError> {
+		let table_record = table_record.ok_or(FontLoadError::MissingTable(table_tag))?;
+		ttf_reader.read(input(table_record)).map_err(|error| FontLoadError::from_table_error(error, table_tag))
+	}
+
+	/// Reads a table the font can be used without, giving None if it's missing or can't be read rather than failing the font.
+	fn read_optional_table<Source: Read + Seek, Type: FromTTFReader>(ttf_reader: &mut TrueTypeFontReader<Source>, table_record: Option<TableRecord>, table_tag: TableTag, input: impl FnOnce(TableRecord) -> Type::Input) -> Option<Type> {
+		table_record.and_then(|_| read_table(ttf_reader, table_record, table_tag, input).ok())
+	}
 }
 
 #[cfg(test)]
 mod tests {
-	use std::fs::File;
+	use std::{borrow::Cow, fs::File, io::Cursor, sync::{Arc, Mutex}};
 
-	use crate::{font::Vertex, ttf_parser::{Contour, EquivalentLineSegments, GetDirection, IntersectionPoint, Intersects, ToRightOf}, ttf_reader::{CharacterToGlyphIndexTable, FontHeaderTable, GlyphTable, IndexToLocationTable, MaximumProfileTable, TableRecord, TableTag}};
+	use crate::{bitmap_reader::{BitmapGlyphs, BitmapImage, ColourBitmapDataTable, ColourBitmapLocationTable, StandardBitmapGraphicsTable, PNG_GRAPHIC_TYPE}, brotli::{self, BrotliError}, cff_reader::{CharStringContour, CharStringError, CharStringInterpreter, PathSegment}, colour_reader::{Affine, Brush, ClipBox, ColourPaletteTable, ColourTable, Extend, Gradient, GradientStop, LayerRecord, PaintCommand, PaletteColour, FOREGROUND_PALETTE_ENTRY}, font::{autohinter::{AutoHintOutlines, AutoHinter, BlueEdge, BlueZone}, colour_rasterizer, font_renderer::VertexTarget, hinting::{Hinting, HintingError, HintingTables}, ComponentGlyph, Font, FontCollection, FontLoadError, GlyphData, GlyphLocations, GlyphLookupError, LazyGlyph, GlyphParseError, Bitmap, BitmapGlyph, Bounds, ColourPaint, Glyph, Vertex}, glyph_definition_reader::{CaretValue, GlyphClass, GlyphDefinitionTable}, hinting_reader::{GridFittingTable, DO_GREY, GRID_FIT}, glyph_positioning_reader::GlyphPositioningTable, glyph_substitution_reader::GlyphSubstitutionTable, inflate::{self, InflateError}, kerning_reader::KerningTable, naming_reader::NamingTable, png::{self, Image}, postscript_reader::PostScriptTable, variation_reader::{AxisVariationsTable, FontVariationsTable, GlyphVariationsTable, HorizontalMetricsVariationsTable, MetricsVariationsTable}, woff_reader, woff2_reader, layout_reader::GlyphFilter, ttf_parser::{Contour, CubicCurve, CubicCurveType, Direction, EquivalentLineSegments, GlyphComponentIntermediate, GlyphDataIntermediate, GlyphIntermediate, GetDirection, Point, IntersectionPoint, Intersects, ToRightOf}, ttf_reader::{CharacterToGlyphIndexSubtable, CharacterToGlyphIndexTable, ComponentGlyphRaw, CompositeGlyphRaw, FontHeaderTable, GlyphDataRaw, GlyphOffset, GlyphRaw, GlyphTable, HorizontalMetric, SimpleGlyphRaw, IndexToLocationTable, MaximumProfileTable, TableRecord, TableTag, TrueTypeFontReader, VerticalHeaderTable, VerticalMetric, VerticalMetricsTable, VerticalOriginTable}};
+
+	use mircalla_types::vectors::Colour;
 
 	use super::*;
 
+	/// The tables of a small TrueType font, with an empty .notdef and a square for 'A', so tests can change or break them.
+	fn test_font_tables() -> Vec<([u8; 4], Vec<u8>)> {
+		let mut font_header: Vec<u8> = vec![0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0x5F, 0x0F, 0x3C, 0xF5, 0, 0, 3, 232]; // 1000 units per em
+		font_header.extend([0; 16]); // created and modified
+		font_header.extend([0, 100, 0, 0, 1, 244, 1, 244, 0, 0, 0, 8, 0, 2, 0, 0, 0, 0]); // bounds, short loca offsets
+		let horizontal_header: Vec<u8> = vec![0, 1, 0, 0, 3, 32, 255, 56, 0, 0, 2, 88, 0, 0, 0, 0, 1, 244, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+		let maximum_profile: Vec<u8> = vec![0, 1, 0, 0, 0, 2, 0, 4, 0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+		let horizontal_metrics: Vec<u8> = vec![2, 88, 0, 0, 2, 88, 0, 100]; // both 600 wide
+		let mut os2: Vec<u8> = vec![0, 0, 2, 88, 1, 144, 0, 5, 0, 0];
+		os2.extend([0; 52]);
+		os2.extend([0, 0, 0, 65, 0, 65, 3, 32, 255, 56, 0, 0, 3, 232, 0, 200]); // 'A' only, ascender 800, descender -200
+		let character_to_glyph_index: Vec<u8> = vec![0, 0, 0, 1, 0, 3, 0, 1, 0, 0, 0, 12, 0, 6, 0, 12, 0, 0, 0, 65, 0, 1, 0, 1]; // 'A' is glyph 1
+		let index_to_location: Vec<u8> = vec![0, 0, 0, 0, 0, 17];
+		let glyph: Vec<u8> = vec![
+			0, 1, 0, 100, 0, 0, 1, 244, 1, 244, // one contour, bounds
+			0, 3, 0, 0, 1, 1, 1, 1, // end point, no instructions, four points on the curve
+			0, 100, 1, 144, 0, 0, 254, 112, // x deltas 100, 400, 0, -400
+			0, 0, 0, 0, 1, 244, 0, 0, // y deltas 0, 0, 500, 0
+		];
+		vec![
+			(*b"head", font_header), (*b"hhea", horizontal_header), (*b"maxp", maximum_profile), (*b"hmtx", horizontal_metrics),
+			(*b"OS/2", os2), (*b"cmap", character_to_glyph_index), (*b"loca", index_to_location), (*b"glyf", glyph),
+		]
+	}
+
+	/// Packs tables into a font, each four byte aligned after the table directory.
+	fn sfnt(sfnt_version: u32, tables: &[([u8; 4], Vec<u8>)]) -> Vec<u8> {
+		let mut data: Vec<u8> = sfnt_version.to_be_bytes().to_vec();
+		data.extend((tables.len() as u16).to_be_bytes());
+		data.extend([0; 6]);
+		let mut table_data: Vec<u8> = Vec::new();
+		for (tag, table) in tables {
+			let offset = 12 + 16 * tables.len() + table_data.len();
+			data.extend(tag);
+			data.extend([0; 4]);
+			data.extend((offset as u32).to_be_bytes());
+			data.extend((table.len() as u32).to_be_bytes());
+			table_data.extend(table);
+			table_data.resize(table_data.len().next_multiple_of(4), 0);
+		}
+		data.extend(table_data);
+		data
+	}
+
+	/// Packs faces into a collection, storing a table once where faces have the same one.
+	fn collection(faces: &[Vec<([u8; 4], Vec<u8>)>]) -> Vec<u8> {
+		let mut data: Vec<u8> = b"ttcf".to_vec();
+		data.extend([0, 1, 0, 0]);
+		data.extend((faces.len() as u32).to_be_bytes());
+		let table_directories_start = 12 + 4 * faces.len();
+		let table_data_start = table_directories_start + faces.iter().map(|tables| 12 + 16 * tables.len()).sum::<usize>();
+		let mut table_directories: Vec<u8> = Vec::new();
+		let mut table_data: Vec<u8> = Vec::new();
+		let mut table_offsets = Vec::new();
+		for tables in faces {
+			data.extend(((table_directories_start + table_directories.len()) as u32).to_be_bytes());
+			table_directories.extend(0x00010000u32.to_be_bytes());
+			table_directories.extend((tables.len() as u16).to_be_bytes());
+			table_directories.extend([0; 6]);
+			for table in tables {
+				let offset = match table_offsets.iter().find(|(stored, _)| *stored == table) {
+					Some((_, offset)) => *offset,
+					None => {
+						let offset = table_data_start + table_data.len();
+						table_data.extend(&table.1);
+						table_data.resize(table_data.len().next_multiple_of(4), 0);
+						table_offsets.push((table, offset));
+						offset
+					},
+				};
+				table_directories.extend(table.0);
+				table_directories.extend([0; 4]);
+				table_directories.extend((offset as u32).to_be_bytes());
+				table_directories.extend((table.1.len() as u32).to_be_bytes());
+			}
+		}
+		data.extend(table_directories);
+		data.extend(table_data);
+		data
+	}
+
+	#[test]
+	fn collection_faces_keep_their_own_vertical_metrics() {
+		let vertical_header: Vec<u8> = vec![0, 1, 0, 0, 3, 32, 255, 56, 0, 0, 3, 232, 0, 0, 0, 0, 1, 244, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+		let face = |vertical_metrics: Vec<u8>| -> Vec<([u8; 4], Vec<u8>)> {
+			let mut tables = test_font_tables();
+			tables.extend([(*b"vhea", vertical_header.clone()), (*b"vmtx", vertical_metrics)]);
+			tables
+		};
+		let data = collection(&[face(vec![3, 232, 0, 0, 3, 232, 1, 44]), face(vec![3, 32, 0, 0, 3, 32, 0, 100])]);
+		let mut font_collection = FontCollection::from_reader(Cursor::new(&data[..])).unwrap();
+		let first = font_collection.face(0).unwrap();
+		let second = font_collection.face(1).unwrap();
+
+		let first_glyph = first.get_glyph(1).unwrap();
+		let second_glyph = second.get_glyph(1).unwrap();
+		assert_eq!((first_glyph.advance_height.value, first_glyph.top_side_bearing.value), (1000, 300));
+		assert_eq!((second_glyph.advance_height.value, second_glyph.top_side_bearing.value), (800, 100));
+		// Only the tables both faces have are shared.
+		assert_eq!(font_collection.shared_tables.glyphs.len(), 2);
+		assert_eq!(font_collection.shared_tables.mappings.len(), 1);
+	}
+
+	#[test]
+	fn glyph_lookup_past_the_last_glyph() {
+		let font = Font::from_bytes(&sfnt(0x00010000, &test_font_tables())).unwrap();
+		assert_eq!(font.number_of_glyphs, 2);
+		let glyph = font.get_glyph(font.get_index('A').unwrap()).unwrap();
+		assert_eq!((glyph.advance_width.value, glyph.left_side_bearing.value), (600, 100));
+		assert!(matches!(glyph.data, GlyphData::SimpleGlyph(_)));
+		assert_eq!(font.get_glyph(2).err(), Some(GlyphLookupError::GlyphNotFound(2, 2)));
+	}
+
+	#[test]
+	fn read_one_glyph_from_glyf_and_cff() {
+		let compact_font_format: Vec<u8> = vec![
+			1, 0, 4, 1, // header
+			0, 1, 1, 1, 2, b'A', // name INDEX
+			0, 1, 1, 1, 3, 160, 17, // Top DICT INDEX, with CharStrings at 21
+			0, 0, 0, 0, // string and global subroutine INDEXes
+			0, 2, 1, 1, 2, 18, 14, // CharStrings INDEX, glyph 0 empty
+			239, 139, 21, 248, 36, 139, 5, 139, 248, 136, 5, 252, 36, 139, 5, 14, // glyph 1, a square
+		];
+		let tables: Vec<([u8; 4], Vec<u8>)> = test_font_tables().into_iter().filter(|(tag, _)| !matches!(tag, b"loca" | b"glyf")).chain([(*b"CFF ", compact_font_format)]).collect();
+		for (name, data) in [("glyf", sfnt(0x00010000, &test_font_tables())), ("cff", sfnt(0x4F54544F, &tables))] {
+			let path = std::env::temp_dir().join(format!("tapestry_read_one_glyph_{name}_{}.ttf", std::process::id()));
+			std::fs::write(&path, data).unwrap();
+			let glyph = crate::read::read_one_glyph(&path, 1);
+			let missing = crate::read::read_one_glyph(&path, 2);
+			std::fs::remove_file(&path).unwrap();
+			assert!(matches!(glyph, Ok(Some(GlyphDataIntermediate::SimpleGlyph(_)))), "{name}");
+			assert!(matches!(missing, Ok(None)), "{name}");
+		}
+	}
+
+	#[test]
+	fn font_from_bytes_and_from_reader_agree() {
+		let data = sfnt(0x00010000, &test_font_tables());
+		let fonts = [Font::from_bytes(&data).unwrap(), Font::from_reader(Cursor::new(&data)).unwrap(), Font::from_lazy_reader(Cursor::new(data.clone())).unwrap()];
+		for font in &fonts {
+			let glyph = font.get_glyph(1).unwrap();
+			assert_eq!(font.number_of_glyphs, 2);
+			assert_eq!((font.units_per_em.value, font.typographic_ascender.value, font.typographic_descender.value), (1000, 1000, 200));
+			assert_eq!((glyph.advance_width.value, glyph.left_side_bearing.value, glyph.advance_height.value, glyph.top_side_bearing.value), (600, 100, 1200, 500));
+		}
+	}
+
+	#[test]
+	fn malformed_table_directories_rejected() {
+		let data = sfnt(0x00010000, &test_font_tables());
+		// Cut off in the middle of the table records.
+		assert!(matches!(Font::from_bytes(&data[..40]), Err(FontLoadError::TruncatedTable(TableTag::Other(['s', 'f', 'n', 't'])))));
+		assert!(matches!(Font::from_bytes(&data[..2]), Err(FontLoadError::TruncatedTable(TableTag::Other(['s', 'f', 'n', 't'])))));
+
+		assert!(matches!(Font::from_bytes(&sfnt(0x74727565 /* true */, &test_font_tables())), Err(FontLoadError::UnsupportedSfntVersion(0x74727565))));
+
+		// glyf, the last table, moved past the end of the data.
+		let mut data = data;
+		let glyph_table_record = 12 + 16 * 7;
+		data[glyph_table_record + 8..glyph_table_record + 12].copy_from_slice(&0x10000u32.to_be_bytes());
+		assert!(matches!(Font::from_bytes(&data), Err(FontLoadError::OffsetOutOfRange(TableTag::Glyph, 0x10000))));
+	}
+
 	#[test]
 	fn point_to_right_of_line() {
 		let vertex_1: Vertex = (10, 0).into();
@@ -272,4 +854,1225 @@ mod tests {
 		println!("{:?}", contour.get_direction(&vertices));
 		panic!();
 	}
-}
\ No newline at end of file
+
+	#[test]
+	fn char_string_square_with_width_and_hints() {
+		// 100 0 20 hstem 10 10 rmoveto hintmask 50 hlineto 50 vlineto -50 hlineto endchar
+		let char_string: Vec<u8> = vec![239, 139, 159, 1, 149, 149, 21, 19, 0x80, 189, 6, 189, 7, 89, 6, 14];
+		let mut interpreter = CharStringInterpreter::new(&[], &[]);
+		interpreter.run(&char_string, 0).unwrap();
+		let outline = interpreter.finish();
+
+		assert_eq!(outline.width, Some(100.0));
+		assert_eq!(outline.contours, vec![CharStringContour {
+			start: (10.0, 10.0),
+			segments: vec![PathSegment::Line(60.0, 10.0), PathSegment::Line(60.0, 60.0), PathSegment::Line(10.0, 60.0)],
+		}]);
+	}
+
+	#[test]
+	fn char_string_calls_local_subroutine() {
+		// Subroutine 0: 50 0 0 50 -50 0 rrcurveto return
+		let local_subroutines: Vec<Vec<u8>> = vec![vec![189, 139, 139, 189, 89, 139, 8, 11]];
+		// 10 10 rmoveto -107 callsubr endchar
+		let char_string: Vec<u8> = vec![149, 149, 21, 32, 10, 14];
+		let mut interpreter = CharStringInterpreter::new(&[], &local_subroutines);
+		interpreter.run(&char_string, 0).unwrap();
+		let outline = interpreter.finish();
+
+		assert_eq!(outline.width, None);
+		assert_eq!(outline.contours[0].segments, vec![PathSegment::Cubic((60.0, 10.0), (60.0, 60.0), (10.0, 60.0))]);
+
+		let glyph: GlyphIntermediate = outline.into();
+		assert_eq!(glyph.number_of_contours, Some(1));
+		assert_eq!((glyph.bounds.x_min, glyph.bounds.x_max, glyph.bounds.y_min, glyph.bounds.y_max), (10, 60, 10, 60));
+	}
+
+	#[test]
+	fn char_string_hflex1_ends_at_start_height() {
+		// 0 0 rmoveto 10 20 10 10 10 10 10 -10 10 hflex1 endchar
+		let char_string: Vec<u8> = vec![139, 139, 21, 149, 159, 149, 149, 149, 149, 149, 129, 149, 12, 36, 14];
+		let mut interpreter = CharStringInterpreter::new(&[], &[]);
+		interpreter.run(&char_string, 0).unwrap();
+		let outline = interpreter.finish();
+
+		assert_eq!(outline.contours[0].segments, vec![
+			PathSegment::Cubic((10.0, 20.0), (20.0, 30.0), (30.0, 30.0)),
+			PathSegment::Cubic((40.0, 30.0), (50.0, 20.0), (60.0, 0.0)),
+		]);
+	}
+
+	#[test]
+	fn malformed_char_strings_rejected() {
+		// Subroutine 0 calls itself.
+		let local_subroutines: Vec<Vec<u8>> = vec![vec![32, 10]];
+		let run = |char_string: &[u8]| CharStringInterpreter::new(&[], &local_subroutines).run(char_string, 0);
+		assert_eq!(run(&[33, 10]), Err(CharStringError::SubroutineOutOfRange(1)));
+		assert_eq!(run(&[32, 10]), Err(CharStringError::SubroutinesNestedTooDeep));
+		assert_eq!(run(&[28, 1]), Err(CharStringError::UnexpectedEnd)); // a 16 bit number cut short
+		assert_eq!(run(&[149, 21]), Err(CharStringError::StackUnderflow)); // rmoveto with one operand
+		assert_eq!(run(&[139; 49]), Err(CharStringError::StackOverflow));
+		assert_eq!(run(&[12, 255]), Err(CharStringError::UnsupportedOperator(12 << 8 | 255)));
+		// 32767 32767 mul 32767 mul callsubr, whose operand is too big to add the bias to.
+		assert_eq!(run(&[255, 127, 255, 0, 0, 255, 127, 255, 0, 0, 12, 24, 255, 127, 255, 0, 0, 12, 24, 10]), Err(CharStringError::SubroutineOutOfRange(i32::MAX)));
+	}
+
+	#[test]
+	fn glyph_with_invalid_char_string_renders_empty() {
+		// 10 10 rmoveto 0 callsubr, with no subroutines to call.
+		let char_string: Vec<u8> = vec![149, 149, 21, 139, 10, 14];
+		let mut interpreter = CharStringInterpreter::new(&[], &[]);
+		let outline = interpreter.run(&char_string, 0).map(|_| interpreter.finish());
+		let glyph: Glyph = GlyphIntermediate::from(outline).into();
+		assert!(matches!(glyph.data, GlyphData::FailedParse(GlyphParseError::InvalidCharString(CharStringError::SubroutineOutOfRange(107)))));
+
+		let font = Font::from_bytes(&sfnt(0x00010000, &test_font_tables())).unwrap();
+		let target = VertexTarget { pixels_per_font_unit: 0.1, screen_size: (100, 100).into(), position: (0.0, 0.0).into(), bounds: ((0, 0).into(), (100, 100).into()) };
+		let (vertices, indices, convex_bezier_indices, concave_bezier_indices, cubic_bezier_indices) = glyph.to_raw(&font, &target, (0, 0).into(), 0, Colour { r: 0, g: 0, b: 0 });
+		assert!(vertices.is_empty() && indices.is_empty() && convex_bezier_indices.is_empty() && concave_bezier_indices.is_empty() && cubic_bezier_indices.is_empty());
+	}
+
+	#[test]
+	fn char_string_blends_between_regions() {
+		// 10 10 rmoveto 500 200 1 blend hlineto, at a point half way into the only region.
+		let char_string: Vec<u8> = vec![149, 149, 21, 248, 136, 247, 92, 140, 16, 6];
+		let variation_scalars = vec![vec![0.5]];
+		let mut interpreter = CharStringInterpreter::new_variable(&[], &[], &variation_scalars, 0);
+		interpreter.run(&char_string, 0).unwrap();
+		let outline = interpreter.finish();
+
+		assert_eq!(outline.width, None);
+		assert_eq!(outline.contours[0].segments, vec![PathSegment::Line(610.0, 10.0)]);
+
+		// 1 vsindex, when there's only the one variation store
+		let mut interpreter = CharStringInterpreter::new_variable(&[], &[], &variation_scalars, 0);
+		assert_eq!(interpreter.run(&[140, 15], 0), Err(CharStringError::VariationStoreIndexOutOfRange(1)));
+		// 500 200 2 blend, with too few operands for two blended values
+		let mut interpreter = CharStringInterpreter::new_variable(&[], &[], &variation_scalars, 0);
+		assert_eq!(interpreter.run(&[248, 136, 247, 92, 141, 16], 0), Err(CharStringError::StackUnderflow));
+		// 500 200 32767 32767 mul 32767 mul 32767 mul 32767 mul blend, asking for more blended values than can be counted
+		let mut interpreter = CharStringInterpreter::new_variable(&[], &[], &variation_scalars, 0);
+		let mut char_string: Vec<u8> = vec![248, 136, 247, 92, 255, 127, 255, 0, 0];
+		for _ in 0..4 {
+			char_string.extend([255, 127, 255, 0, 0, 12, 24]);
+		}
+		char_string.push(16);
+		assert_eq!(interpreter.run(&char_string, 0), Err(CharStringError::StackUnderflow));
+	}
+
+	#[test]
+	fn zlib_fixed_huffman_with_back_references() {
+		let data: Vec<u8> = vec![120, 218, 43, 73, 44, 72, 45, 46, 41, 170, 84, 40, 65, 103, 0, 0, 143, 189, 10, 181];
+		assert_eq!(inflate::zlib_decompress(&data, 100), Ok(b"tapestry tapestry tapestry".to_vec()));
+		assert_eq!(inflate::zlib_decompress(&data, 10), Err(InflateError::OutputTooLong));
+	}
+
+	#[test]
+	fn zlib_stored_block_checksum() {
+		let mut data: Vec<u8> = vec![120, 1, 1, 4, 0, 251, 255, 102, 111, 110, 116, 4, 57, 1, 184];
+		assert_eq!(inflate::zlib_decompress(&data, 100), Ok(b"font".to_vec()));
+		data[7] = b'g';
+		assert_eq!(inflate::zlib_decompress(&data, 100), Err(InflateError::ChecksumMismatch));
+	}
+
+	#[test]
+	fn woff2_with_transformed_glyf_open_sans() {
+		let data = include_bytes!("../tests/fonts/open-sans-regular.woff2");
+		let header: woff2_reader::WebOpenFontFormat2Header = TrueTypeFontReader::from_source(Cursor::new(&data[..])).read(()).unwrap();
+		assert!(header.table_directory_entries.iter().filter(|entry| matches!(&entry.tag, b"glyf" | b"loca")).all(|entry| entry.is_transformed()));
+
+		let font = Font::from_bytes(data).unwrap();
+		let glyphs = font.glyphs.lock().unwrap();
+		let outline = |character: char| {
+			let index = font.get_index(character).unwrap();
+			let LazyGlyph::GlyphUnread(glyph_locations, horizontal_metric) = &glyphs[index] else {
+				panic!("glyph should be unread");
+			};
+			let glyph = glyph_locations.read_glyph(index as u16).unwrap().unwrap();
+			let points = match &glyph.glyph_data {
+				GlyphDataRaw::SimpleGlyphRaw(simple_glyph) => (simple_glyph.x_coordinates.len(), simple_glyph.end_points_of_contours.clone()),
+				GlyphDataRaw::CompositeGlyphRaw(composite_glyph) => (composite_glyph.children.len(), Vec::new()),
+				GlyphDataRaw::None => (0, Vec::new()),
+			};
+			(points, horizontal_metric.advance_width, (glyph.x_min, glyph.y_max))
+		};
+		assert_eq!(outline('A'), ((15, vec![7, 14]), 1296, (0, 1468)));
+		assert_eq!(outline('H'), ((12, vec![11]), 1511, (201, 1462)));
+		assert_eq!(outline('o'), ((25, vec![12, 24]), 1237, (115, 1116)));
+		assert_eq!(outline('é').0, (2, Vec::new())); // e and an acute accent
+		assert_eq!(outline(' ').0, (0, Vec::new()));
+		drop(glyphs);
+		assert_eq!(font.get_glyph(font.get_index('é').unwrap()).unwrap().advance_width.value, 1149);
+	}
+
+	#[test]
+	fn malformed_deflate_streams_rejected() {
+		assert_eq!(inflate::zlib_decompress(&[120], 100), Err(InflateError::UnexpectedEnd));
+		assert_eq!(inflate::zlib_decompress(&[120, 0, 3, 0], 100), Err(InflateError::InvalidZlibHeader)); // header isn't a multiple of 31
+		assert_eq!(inflate::zlib_decompress(&[0x78, 0xBB, 3, 0], 100), Err(InflateError::PresetDictionaryNotSupported));
+		assert_eq!(inflate::inflate(&[0x07], 100), Err(InflateError::InvalidBlockType));
+		assert_eq!(inflate::inflate(&[0x01, 4, 0, 0, 0, b'f', b'o', b'n', b't'], 100), Err(InflateError::StoredLengthMismatch));
+		assert_eq!(inflate::inflate(&[0x01, 4, 0, 251, 255, b'f', b'o'], 100), Err(InflateError::UnexpectedEnd));
+		assert_eq!(inflate::inflate(&[0x03, 0x02, 0], 100), Err(InflateError::DistanceTooFarBack(1))); // a fixed code copy before any output
+		assert_eq!(inflate::inflate(&[0xFD, 0, 0], 100), Err(InflateError::InvalidCodeLengths)); // 288 literal codes in a dynamic block
+	}
+
+	#[test]
+	fn woff_with_compressed_and_stored_tables() {
+		let tables = test_font_tables();
+		// OS/2 is stored compressed, as it shrinks, and every other table as it is.
+		let compressed_os2: Vec<u8> = vec![120, 218, 99, 96, 96, 138, 96, 156, 192, 192, 202, 64, 46, 112, 100, 112, 100, 86, 248, 111, 193, 192, 192, 252, 130, 225, 4, 0, 89, 205, 4, 128];
+		let woff = |original_os2_length: u32| {
+			let mut directory: Vec<u8> = Vec::new();
+			let mut table_data: Vec<u8> = Vec::new();
+			for (tag, table) in tables.iter() {
+				let (stored, original_length) = match tag {
+					b"OS/2" => (&compressed_os2, original_os2_length),
+					_ => (table, table.len() as u32),
+				};
+				directory.extend(tag);
+				directory.extend(((44 + 20 * tables.len() + table_data.len()) as u32).to_be_bytes());
+				directory.extend((stored.len() as u32).to_be_bytes());
+				directory.extend(original_length.to_be_bytes());
+				directory.extend([0; 4]);
+				table_data.extend(stored);
+				table_data.resize(table_data.len().next_multiple_of(4), 0);
+			}
+			let mut data: Vec<u8> = b"wOFF".to_vec();
+			data.extend([0, 1, 0, 0]);
+			data.extend(((44 + directory.len() + table_data.len()) as u32).to_be_bytes());
+			data.extend((tables.len() as u16).to_be_bytes());
+			data.extend([0; 2]);
+			data.extend((sfnt(0x00010000, &tables).len() as u32).to_be_bytes());
+			data.extend([0; 24]);
+			data.extend(directory);
+			data.extend(table_data);
+			data
+		};
+
+		let data = woff(78);
+		let sfnt_data = woff_reader::decode(&mut TrueTypeFontReader::from_source(Cursor::new(&data))).unwrap();
+		let read_u32 = |offset: usize| u32::from_be_bytes(sfnt_data[offset..offset + 4].try_into().unwrap()) as usize;
+		assert_eq!((read_u32(0), sfnt_data[4..6].to_vec()), (0x00010000, vec![0, tables.len() as u8]));
+		for (index, (tag, table)) in tables.iter().enumerate() {
+			let record = 12 + 16 * index;
+			assert_eq!(&sfnt_data[record..record + 4], tag);
+			assert_eq!(&sfnt_data[read_u32(record + 8)..read_u32(record + 8) + read_u32(record + 12)], &table[..], "{}", String::from_utf8_lossy(tag));
+		}
+		assert_eq!(Font::from_bytes(&data).unwrap().get_index('A'), Some(1));
+
+		// An OS/2 that says it's longer than it inflates to.
+		let font = Font::from_bytes(&woff(80));
+		assert!(matches!(font, Err(FontLoadError::ReaderError(ttf_reader::TrueTypeFontReaderError::InvalidData(TableTag::OS2AndWindowsMetricsTable, _)))));
+	}
+
+	#[test]
+	fn brotli_compressed_meta_block_with_back_references() {
+		let data: Vec<u8> = vec![0x1B, 0x23, 0x00, 0xF8, 0xC5, 0x6D, 0x6C, 0x5D, 0xF7, 0x55, 0xF1, 0x47, 0x79, 0xAA, 0x5F, 0xB4, 0x29, 0x02, 0x30, 0x18, 0x65, 0xA2, 0x9C, 0x5C, 0xF7, 0x03];
+		assert_eq!(brotli::decompress(&data, 100), Ok(b"abcabcabcabcabcabc tapestry tapestry".to_vec()));
+		assert_eq!(brotli::decompress(&data, 10), Err(BrotliError::OutputTooLong));
+	}
+
+	#[test]
+	fn brotli_uncompressed_meta_block() {
+		let mut data: Vec<u8> = vec![0x8B, 0x17, 0x80];
+		data.extend(b"The Quick Brown Fox, the lazy dog and THE WORLD.");
+		data.push(0x03);
+		assert_eq!(brotli::decompress(&data, 100), Ok(b"The Quick Brown Fox, the lazy dog and THE WORLD.".to_vec()));
+		data.truncate(20);
+		assert_eq!(brotli::decompress(&data, 100), Err(BrotliError::UnexpectedEnd));
+	}
+
+	#[test]
+	fn malformed_brotli_streams_rejected() {
+		assert_eq!(brotli::decompress(&[], 100), Err(BrotliError::UnexpectedEnd));
+		assert_eq!(brotli::decompress(&[0x11], 100), Err(BrotliError::InvalidWindowSize));
+		assert_eq!(brotli::decompress(&[0x1C], 100), Err(BrotliError::InvalidMetaBlockHeader)); // metadata block with its reserved bit set
+
+		// A last meta-block of 10 bytes with one literal 'a', one command and one distance code, all simple prefix codes.
+		let mut data: Vec<u8> = vec![0x22, 0x01, 0x00, 0x00, 0x44, 0x58, 0x20, 0x10, 0x00];
+		// The command inserts 'a' then copies 2 bytes from the initial distance of 4, which is past the output, where no dictionary words are 2 long.
+		assert_eq!(brotli::decompress(&data, 100), Err(BrotliError::InvalidDictionaryReference(2, 2)));
+		data[6] = 0x40; // inserts 2 literals instead
+		data[..2].copy_from_slice(&[0x02, 0x00]); // into a 1 byte meta-block
+		assert_eq!(brotli::decompress(&data, 100), Err(BrotliError::MetaBlockLengthExceeded));
+		let data: Vec<u8> = vec![0x22, 0x01, 0x00, 0x00, 0x54, 0x58, 0x58, 0x20, 0x10, 0x00]; // 'a' listed twice in a two symbol literal code
+		assert_eq!(brotli::decompress(&data, 100), Err(BrotliError::InvalidPrefixCode));
+	}
+
+	#[test]
+	fn cmap_format_2_maps_one_and_two_byte_codes() {
+		let mut subtable: Vec<u8> = vec![0, 2, 2, 32, 0, 0];
+		let mut sub_header_keys = [0u16; 256];
+		sub_header_keys[0x81] = 8;
+		subtable.extend(sub_header_keys.iter().flat_map(|key| key.to_be_bytes()));
+		for value in [0x20u16, 3, 0, 10, 0x40, 2, 10, 8] {
+			subtable.extend(value.to_be_bytes());
+		}
+		for glyph_id in [5u16, 6, 0, 20, 21] {
+			subtable.extend(glyph_id.to_be_bytes());
+		}
+		let mut data: Vec<u8> = vec![0, 0, 0, 1, 0, 3, 0, 2, 0, 0, 0, 12];
+		data.extend(subtable);
+
+		let cmap: CharacterToGlyphIndexTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		let glyph_ids: Vec<Option<u16>> = [0x20, 0x21, 0x22, 0x23, 0x81, 0x8140, 0x8141, 0x8142, 0x8240].iter().map(|code| cmap.subtables[0].get_glyph_id(*code)).collect();
+		assert_eq!(glyph_ids, vec![Some(5), Some(6), None, None, None, Some(30), Some(31), None, None]);
+
+		// A glyph id array offset past the end of the array maps nothing, rather than reading out of bounds.
+		let mut past_glyphs = data.clone();
+		past_glyphs[544..546].copy_from_slice(&0x100u16.to_be_bytes());
+		let cmap: CharacterToGlyphIndexTable = TrueTypeFontReader::from_source(Cursor::new(&past_glyphs)).read(0).unwrap();
+		assert_eq!(cmap.subtables[0].get_glyph_id(0x8140), None);
+
+		// A sub header whose codes run past the last low byte is rejected, rather than overflowing when it's looked up.
+		let mut past_low_bytes = data.clone();
+		past_low_bytes[538..542].copy_from_slice(&[0xFF, 0x00, 0x02, 0x00]);
+		let cmap = TrueTypeFontReader::from_source(Cursor::new(&past_low_bytes)).read::<CharacterToGlyphIndexTable>(0);
+		assert!(matches!(cmap, Err(ttf_reader::TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, _))));
+
+		// A key for more sub headers than the subtable has room for is rejected.
+		data[278..280].copy_from_slice(&0xFFF8u16.to_be_bytes());
+		let cmap = TrueTypeFontReader::from_source(Cursor::new(&data)).read::<CharacterToGlyphIndexTable>(0);
+		assert!(matches!(cmap, Err(ttf_reader::TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, _))));
+	}
+
+	#[test]
+	fn cmap_format_14_variation_sequences() {
+		let data: Vec<u8> = vec![
+			0, 0, 0, 1, 0, 0, 0, 5, 0, 0, 0, 12,
+			0, 14, 0, 0, 0, 38, 0, 0, 0, 1, // format, length, number of records
+			0x00, 0xFE, 0x0F, 0, 0, 0, 21, 0, 0, 0, 29, // U+FE0F, default UVS offset, non-default UVS offset
+			0, 0, 0, 1, 0x00, 0x27, 0x64, 0, // U+2764 uses its default glyph
+			0, 0, 0, 1, 0x00, 0x26, 0x3A, 0, 42, // U+263A maps to glyph 42
+		];
+
+		let cmap: CharacterToGlyphIndexTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		let CharacterToGlyphIndexSubtable::Format14(subtable) = &cmap.subtables[0] else {
+			panic!("subtable should be format 14");
+		};
+		assert_eq!(subtable.variation_selector_records.len(), 1);
+		assert_eq!(subtable.variation_selector_records[0].variation_selector, 0xFE0F);
+		assert_eq!(subtable.variation_selector_records[0].default_uvs, vec![(0x2764, 0)]);
+		assert_eq!(subtable.variation_selector_records[0].non_default_uvs, vec![(0x263A, 42)]);
+		assert_eq!(cmap.subtables[0].get_glyph_id(0x263A), None);
+
+		// Record, range and mapping counts that run past the end of the subtable are rejected.
+		for count_offset in [18, 33, 41] {
+			let mut data = data.clone();
+			data[count_offset..count_offset + 4].copy_from_slice(&0x00FFFFFFu32.to_be_bytes());
+			let cmap = TrueTypeFontReader::from_source(Cursor::new(&data)).read::<CharacterToGlyphIndexTable>(0);
+			assert!(matches!(cmap, Err(ttf_reader::TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, _))));
+		}
+	}
+
+	#[test]
+	fn variation_selectors_pick_the_glyph_before_them() {
+		let mut tables = test_font_tables();
+		tables[5].1 = vec![
+			0, 0, 0, 2, 0, 3, 0, 1, 0, 0, 0, 20, 0, 0, 0, 5, 0, 0, 0, 32,
+			0, 6, 0, 12, 0, 0, 0, 65, 0, 1, 0, 1, // 'A' is glyph 1
+			0, 14, 0, 0, 0, 30, 0, 0, 0, 1,
+			0x00, 0xFE, 0x01, 0, 0, 0, 0, 0, 0, 0, 21, // U+FE01, with only a non-default UVS
+			0, 0, 0, 1, 0, 0, 65, 0, 0, // 'A' with U+FE01 is glyph 0
+		];
+		let font = Font::from_bytes(&sfnt(0x00010000, &tables)).unwrap();
+		assert_eq!(font.get_index('A'), Some(1));
+
+		// A sequence the font doesn't have uses the usual glyph, and selectors without a character before them are dropped.
+		let glyph_ids: Vec<u16> = font.layout_glyphs("A\u{FE01}A\u{FE0E}\u{FE0F}A", *b"latn", &[]).iter().map(|glyph| glyph.glyph_id).collect();
+		assert_eq!(glyph_ids, vec![0, 1, 1]);
+	}
+
+	#[test]
+	fn cmap_format_12_group_count_beyond_subtable() {
+		let mut data: Vec<u8> = vec![
+			0, 0, 0, 1, 0, 3, 0, 10, 0, 0, 0, 12,
+			0, 12, 0, 0, 0, 0, 0, 28, 0, 0, 0, 0, // format, reserved, length, language
+			0xFF, 0xFF, 0xFF, 0xFF, // number of groups
+			0, 0, 0, 0x20, 0, 0, 0, 0x7E, 0, 0, 0, 3,
+		];
+		let cmap = TrueTypeFontReader::from_source(Cursor::new(&data)).read::<CharacterToGlyphIndexTable>(0);
+		assert!(matches!(cmap, Err(ttf_reader::TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, _))));
+
+		data[24..28].copy_from_slice(&1u32.to_be_bytes());
+		let cmap: CharacterToGlyphIndexTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		assert_eq!(cmap.subtables[0].get_glyph_id(0x41), Some(36));
+	}
+
+	#[test]
+	fn cmap_format_4_ignores_glyph_id_array_offsets_outside_it() {
+		let mut tables = test_font_tables();
+		tables[5].1 = vec![
+			0, 0, 0, 1, 0, 3, 0, 1, 0, 0, 0, 12,
+			0, 4, 0, 34, 0, 0, 0, 4, 0, 4, 0, 1, 0, 0, // format, length, language, two segments
+			0, 66, 0xFF, 0xFF, 0, 0, // end codes, reserved pad
+			0, 65, 0xFF, 0xFF, // start codes
+			0, 0, 0, 1, // id deltas
+			0, 4, 0, 0, // id range offsets, 'A' to 'B' using the glyph id array
+			0, 1, // 'A' is glyph 1, and 'B' is past the end of the array
+		];
+		let font = Font::from_bytes(&sfnt(0x00010000, &tables)).unwrap();
+		assert_eq!((font.get_index('A'), font.get_index('B')), (Some(1), None));
+		assert_eq!(font.get_character_codes(1), vec!['A']);
+
+		// An id range offset pointing before the array.
+		tables[5].1[41] = 2;
+		let font = Font::from_bytes(&sfnt(0x00010000, &tables)).unwrap();
+		assert_eq!((font.get_index('A'), font.get_index('B')), (None, Some(1)));
+		let cmap: CharacterToGlyphIndexTable = TrueTypeFontReader::from_source(Cursor::new(&tables[5].1)).read(0).unwrap();
+		assert_eq!((cmap.subtables[0].get_glyph_id(65), cmap.subtables[0].get_glyph_id(66)), (None, Some(1)));
+	}
+
+	#[test]
+	fn cmap_format_13_maps_ranges_to_one_glyph() {
+		let mut data: Vec<u8> = vec![
+			0, 0, 0, 1, 0, 3, 0, 10, 0, 0, 0, 12,
+			0, 13, 0, 0, 0, 0, 0, 40, 0, 0, 0, 0, // format, reserved, length, language
+			0, 0, 0, 2, // number of groups
+			0, 0, 0, 0x20, 0, 0, 0, 0x7E, 0, 0, 0, 3,
+			0, 0x01, 0, 0, 0, 0x01, 0xFF, 0xFF, 0, 0, 0, 9,
+		];
+		let cmap: CharacterToGlyphIndexTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		let glyph_ids: Vec<Option<u16>> = [0x1F, 0x20, 0x7E, 0x7F, 0x10000, 0x1ABCD].iter().map(|code| cmap.subtables[0].get_glyph_id(*code)).collect();
+		assert_eq!(glyph_ids, vec![None, Some(3), Some(3), None, Some(9), Some(9)]);
+
+		// A group covering every character code gives only its first when looking characters up by glyph.
+		let mut tables = test_font_tables();
+		tables[5].1 = vec![
+			0, 0, 0, 1, 0, 3, 0, 10, 0, 0, 0, 12,
+			0, 13, 0, 0, 0, 0, 0, 28, 0, 0, 0, 0, 0, 0, 0, 1,
+			0, 0, 0, 0x20, 0, 0x10, 0xFF, 0xFF, 0, 0, 0, 1,
+		];
+		let font = Font::from_bytes(&sfnt(0x00010000, &tables)).unwrap();
+		assert_eq!((font.get_index('A'), font.get_character_codes(1)), (Some(1), vec![' ']));
+
+		data[24..28].copy_from_slice(&3u32.to_be_bytes());
+		let cmap = TrueTypeFontReader::from_source(Cursor::new(&data)).read::<CharacterToGlyphIndexTable>(0);
+		assert!(matches!(cmap, Err(ttf_reader::TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, _))));
+	}
+
+	#[test]
+	fn cmap_prefers_windows_unicode_subtable() {
+		let mut data: Vec<u8> = vec![
+			0, 0, 0, 2,
+			0, 1, 0, 0, 0, 0, 0, 20, // Mac Roman
+			0, 3, 0, 1, 0, 0, 1, 26, // Windows Unicode BMP
+			0, 0, 1, 6, 0, 0, // format 0, length, language
+		];
+		let mut mac_roman_glyph_ids = [0u8; 256];
+		mac_roman_glyph_ids[b'a' as usize] = 5;
+		data.extend(mac_roman_glyph_ids);
+		data.extend([0, 6, 0, 12, 0, 0, 0, 97, 0, 1, 0, 9]); // format 6 mapping 'a' to glyph 9
+
+		let cmap: CharacterToGlyphIndexTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		let mappings: Vec<font::Mapping> = cmap.encoding_records.iter().zip(cmap.subtables).map(|v| v.into()).collect();
+		assert_eq!((mappings[0].platform_id, mappings[0].encoding_id), (1, 0));
+		assert_eq!(font::preferred_mapping(&mappings), Some(1));
+		assert_eq!(font::preferred_mapping(&mappings[..1]), Some(0));
+	}
+
+	#[test]
+	fn naming_table_decodes_mac_roman_and_language_tags() {
+		let data: Vec<u8> = vec![
+			0, 1, 0, 3, 0, 48, // version, count, storage offset
+			0, 1, 0, 0, 0, 0, 0, 1, 0, 4, 0, 0, // Mac Roman, English, family name
+			0, 3, 0, 1, 0x04, 0x07, 0, 2, 0, 8, 0, 4, // Windows, German, subfamily name
+			0, 3, 0, 1, 0x80, 0x00, 0, 1, 0, 4, 0, 12, // Windows, first language tag, family name
+			0, 1, 0, 10, 0, 16, // one language tag
+			b'C', b'a', b'f', 0x8E,
+			0, b'F', 0, b'e', 0, b't', 0, b't',
+			0, b'A', 0, b'b',
+			0, b'd', 0, b'e', 0, b'-', 0, b'C', 0, b'H',
+		];
+
+		let naming_table: NamingTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		let names: Vec<_> = naming_table.name_records.iter().map(|name_record| (name_record.decode(), naming_table.language_tag(name_record))).collect();
+		assert_eq!(names[0], (Some("Caf\u{E9}".to_string()), Some("en".to_string())));
+		assert_eq!(names[1], (Some("Fett".to_string()), Some("de-DE".to_string())));
+		assert_eq!(names[2], (Some("Ab".to_string()), Some("de-CH".to_string())));
+	}
+
+	#[test]
+	fn font_loads_without_unreadable_optional_tables() {
+		let font_with = |tag: [u8; 4], table: Vec<u8>| {
+			let mut tables = test_font_tables();
+			tables.push((tag, table));
+			let font = Font::from_bytes(&sfnt(0x00010000, &tables)).unwrap();
+			assert_eq!(font.get_index('A'), Some(1));
+			font
+		};
+
+		// A name whose string runs past the end of the font.
+		let font = font_with(*b"name", vec![0, 0, 0, 1, 0, 18, 0, 3, 0, 1, 4, 9, 0, 1, 0, 100, 0, 0]);
+		assert!(font.names.is_empty());
+		// A kern version that doesn't exist.
+		let font = font_with(*b"kern", vec![0, 5, 0, 0]);
+		assert!(font.kerning.is_empty());
+		// GPOS and GSUB 1.2, which aren't defined.
+		let font = font_with(*b"GPOS", vec![0, 1, 0, 2, 0, 0, 0, 0, 0, 0]);
+		assert!(font.glyph_positioning.is_none());
+		let font = font_with(*b"GSUB", vec![0, 1, 0, 2, 0, 0, 0, 0, 0, 0]);
+		assert!(font.glyph_substitution.is_none());
+		// GDEF 1.1, which isn't defined.
+		let font = font_with(*b"GDEF", vec![0, 1, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+		assert!(font.glyph_definition.is_none());
+		// COLR base glyph records that aren't sorted by glyph id.
+		let font = font_with(*b"COLR", vec![0, 0, 0, 2, 0, 0, 0, 14, 0, 0, 0, 26, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0]);
+		assert!(font.colour_glyphs.is_none());
+		// A CPAL version that doesn't exist.
+		let font = font_with(*b"CPAL", vec![0, 5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+		assert!(font.colour_palettes.is_none());
+	}
+
+	#[test]
+	fn postscript_table_version_2_glyph_names() {
+		let mut data: Vec<u8> = vec![
+			0, 2, 0, 0, 0xFF, 0xF3, 0x80, 0, // version 2, italic angle -12.5
+			0xFF, 0x9C, 0, 50, 0, 0, 0, 1, // underline position and thickness, fixed pitch
+		];
+		data.extend([0; 16]);
+		data.extend([0, 3, 0, 0, 0, 68, 1, 2]); // three glyphs, the last with the first name in the table
+		data.extend([6, b'c', b'u', b's', b't', b'o', b'm']);
+
+		let postscript_table: PostScriptTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read((0, data.len() as u32)).unwrap();
+		assert_eq!(postscript_table.italic_angle, -12.5);
+		assert_eq!(postscript_table.underline_position, -100);
+		assert_eq!(postscript_table.underline_thickness, 50);
+		assert!(postscript_table.is_fixed_pitch);
+		assert_eq!(postscript_table.glyph_names, vec![".notdef", "a", "custom"]);
+	}
+
+	#[test]
+	fn postscript_table_without_glyph_names_or_unreadable() {
+		let postscript = |version: [u8; 4]| {
+			let mut data: Vec<u8> = version.to_vec();
+			data.extend([0xFF, 0xF3, 0x80, 0, 0xFF, 0x9C, 0, 50, 0, 0, 0, 0]); // italic angle -12.5, underline position and thickness
+			data.extend([0; 16]);
+			data
+		};
+		let font_with = |table: Vec<u8>| {
+			let mut tables = test_font_tables();
+			tables.push((*b"post", table));
+			Font::from_bytes(&sfnt(0x00010000, &tables)).unwrap()
+		};
+
+		// Apple's version 4, which doesn't name glyphs.
+		let font = font_with(postscript([0, 4, 0, 0]));
+		assert_eq!((font.italic_angle, font.underline_thickness.value), (-12.5, 50));
+		assert!(font.glyph_names.is_empty());
+
+		// Version 2.5 with an offset before the first Macintosh name leaves the glyphs unnamed.
+		let mut deprecated = postscript([0, 2, 0x50, 0]);
+		deprecated.extend([0, 2, 0xFF, 0]);
+		let font = font_with(deprecated);
+		assert_eq!(font.italic_angle, -12.5);
+		assert!(font.glyph_names.is_empty());
+
+		// A version that doesn't exist is skipped, rather than failing the font.
+		let font = font_with(postscript([0, 7, 0, 0]));
+		assert_eq!((font.italic_angle, font.underline_thickness.value), (0.0, 0));
+	}
+
+	#[test]
+	fn kerning_table_microsoft_and_apple_headers() {
+		let pair = [0, 1, 0, 6, 0, 0, 0, 0, 0, 36, 0, 57, 0xFF, 0x7F]; // one pair, glyphs 36 and 57 closer by 129
+		let mut microsoft: Vec<u8> = vec![0, 0, 0, 1, 0, 0, 0, 20, 0x00, 0x01];
+		microsoft.extend(pair);
+		let mut apple: Vec<u8> = vec![0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 22, 0x00, 0x00, 0, 0];
+		apple.extend(pair);
+
+		for data in [microsoft, apple] {
+			let kerning_table: KerningTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+			assert_eq!(kerning_table.subtables.len(), 1);
+			assert!(kerning_table.subtables[0].is_horizontal_kerning());
+			assert_eq!(font::kerning_pairs(&kerning_table).get(&(36, 57)), Some(&-129));
+		}
+	}
+
+	#[test]
+	fn glyph_positioning_pair_and_mark_to_base() {
+		let data: Vec<u8> = vec![
+			0, 1, 0, 0, 0, 10, 0, 32, 0, 58, // header
+			0, 1, b'D', b'F', b'L', b'T', 0, 8, 0, 4, 0, 0, 0, 0, 0xFF, 0xFF, 0, 2, 0, 0, 0, 1, // script list, default language using features 0 and 1
+			0, 2, b'k', b'e', b'r', b'n', 0, 14, b'm', b'a', b'r', b'k', 0, 20, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0, 1, // feature list, kern uses lookup 0 and mark lookup 1
+			0, 2, 0, 6, 0, 38, // lookup list
+			0, 2, 0, 0, 0, 1, 0, 8, // pair adjustment lookup
+			0, 1, 0, 18, 0, 4, 0, 0, 0, 1, 0, 12, 0, 1, 0, 2, 0xFF, 0xCE, 0, 1, 0, 1, 0, 1, // glyph 1 followed by glyph 2 advances 50 less
+			0, 4, 0, 0, 0, 1, 0, 8, // mark to base lookup
+			0, 1, 0, 12, 0, 18, 0, 1, 0, 24, 0, 36, 0, 1, 0, 1, 0, 3, 0, 1, 0, 1, 0, 1, // mark glyph 3 on base glyph 1
+			0, 1, 0, 0, 0, 6, 0, 1, 0, 100, 0, 0, // mark anchor
+			0, 1, 0, 4, 0, 1, 1, 44, 1, 244, // base anchor at (300, 500)
+		];
+		let glyph_positioning: GlyphPositioningTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+
+		let kerning = glyph_positioning.lookup_indices(*b"latn", None, &[*b"kern"]);
+		assert_eq!(kerning, vec![0]);
+		let positions = glyph_positioning.position(&[1, 2], &[600, 600], &kerning, None);
+		assert_eq!(positions[0].x_advance, -50);
+
+		let lookup_indices = glyph_positioning.lookup_indices(*b"latn", None, &[*b"kern", *b"mark"]);
+		let positions = glyph_positioning.position(&[1, 3], &[600, 200], &lookup_indices, None);
+		assert_eq!((positions[1].x_placement, positions[1].y_placement, positions[1].x_advance), (300 - 100 - 600, 500, -200));
+	}
+
+	#[test]
+	fn glyph_substitution_ligature_and_chained_context() {
+		let data: Vec<u8> = vec![
+			0, 1, 0, 0, 0, 10, 0, 32, 0, 58, // header
+			0, 1, b'D', b'F', b'L', b'T', 0, 8, 0, 4, 0, 0, 0, 0, 0xFF, 0xFF, 0, 2, 0, 0, 0, 1, // script list, default language using features 0 and 1
+			0, 2, b'l', b'i', b'g', b'a', 0, 14, b'c', b'a', b'l', b't', 0, 20, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1, 0, 1, // feature list, liga uses lookup 0 and calt lookup 1
+			0, 3, 0, 8, 0, 40, 0, 78, // lookup list
+			0, 4, 0, 0, 0, 1, 0, 8, // ligature lookup
+			0, 1, 0, 18, 0, 1, 0, 8, 0, 1, 0, 4, 0, 10, 0, 2, 0, 2, 0, 1, 0, 1, 0, 1, // glyphs 1 and 2 become glyph 10
+			0, 6, 0, 0, 0, 1, 0, 8, // chained context lookup
+			0, 3, 0, 1, 0, 18, 0, 1, 0, 24, 0, 0, 0, 1, 0, 0, 0, 2, 0, 1, 0, 1, 0, 10, 0, 1, 0, 1, 0, 3, // glyph 3 after glyph 10 goes through lookup 2
+			0, 1, 0, 0, 0, 1, 0, 8, // single substitution lookup, only used from the context
+			0, 2, 0, 8, 0, 1, 0, 20, 0, 1, 0, 1, 0, 3, // glyph 3 becomes glyph 20
+		];
+		let glyph_substitution: GlyphSubstitutionTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+
+		let lookup_indices = glyph_substitution.lookup_indices(*b"latn", None, &[*b"liga", *b"calt"]);
+		assert_eq!(lookup_indices, vec![0, 1]);
+		let mut glyph_ids = vec![1, 2, 3, 4, 3];
+		glyph_substitution.substitute(&mut glyph_ids, &lookup_indices, None);
+		assert_eq!(glyph_ids, vec![10, 20, 4, 3]);
+
+		let mut glyph_ids = vec![1, 2, 3];
+		glyph_substitution.substitute(&mut glyph_ids, &glyph_substitution.lookup_indices(*b"latn", None, &[*b"calt"]), None);
+		assert_eq!(glyph_ids, vec![1, 2, 3]);
+	}
+
+	#[test]
+	fn glyph_substitution_chained_context_edges() {
+		let mut data: Vec<u8> = vec![
+			0, 1, 0, 0, 0, 10, 0, 12, 0, 14, // header
+			0, 0, 0, 0, // no scripts or features
+			0, 3, 0, 8, 0, 62, 0, 82, // lookup list
+			0, 6, 0, 0, 0, 1, 0, 8, // chained context lookup
+			0, 3, 0, 1, 0, 28, 0, 1, 0, 34, 0, 1, 0, 40, 0, 3, 0, 0, 0, 1, 0, 5, 0, 1, 0, 0, 0, 9, // glyph 3 between glyphs 5 and 4 goes through lookup 1, then a sequence index and a lookup that don't exist
+			0, 1, 0, 1, 0, 5, 0, 1, 0, 1, 0, 3, 0, 1, 0, 1, 0, 4,
+			0, 1, 0, 0, 0, 1, 0, 8, // single substitution lookup
+			0, 1, 0, 6, 0, 17, 0, 1, 0, 1, 0, 3, // glyph 3 becomes glyph 20
+			0, 6, 0, 0, 0, 1, 0, 8, // chained context lookup that calls itself
+			0, 3, 0, 0, 0, 1, 0, 16, 0, 0, 0, 1, 0, 0, 0, 2, 0, 1, 0, 1, 0, 3,
+		];
+		let glyph_substitution: GlyphSubstitutionTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+
+		// Records past the input or naming missing lookups are skipped, and the context has to fit within the text.
+		for (glyph_ids, substituted) in [(vec![5, 3, 4], vec![5, 20, 4]), (vec![3, 4], vec![3, 4]), (vec![5, 3], vec![5, 3]), (vec![5, 3, 6], vec![5, 3, 6])] {
+			let mut glyph_ids = glyph_ids;
+			glyph_substitution.substitute(&mut glyph_ids, &[0], None);
+			assert_eq!(glyph_ids, substituted);
+		}
+
+		// Lookups that call themselves stop at the nesting limit.
+		let mut glyph_ids = vec![3, 3];
+		glyph_substitution.substitute(&mut glyph_ids, &[2], None);
+		assert_eq!(glyph_ids, vec![3, 3]);
+
+		data[31] = 4;
+		let glyph_substitution = TrueTypeFontReader::from_source(Cursor::new(&data)).read::<GlyphSubstitutionTable>(0);
+		assert!(matches!(glyph_substitution, Err(ttf_reader::TrueTypeFontReaderError::InvalidData(TableTag::GlyphSubstitution, _))));
+	}
+
+	#[test]
+	fn glyph_definition_classes_carets_and_mark_filtering() {
+		let data: Vec<u8> = vec![
+			0, 1, 0, 2, 0, 14, 0, 0, 0, 36, 0, 62, 0, 72, // header
+			0, 2, 0, 3, 0, 1, 0, 1, 0, 1, 0, 5, 0, 5, 0, 2, 0, 10, 0, 11, 0, 3, // glyph 1 is a base, 5 a ligature and 10 and 11 marks
+			0, 20, 0, 1, 0, 6, 0, 2, 0, 6, 0, 10, 0, 1, 1, 244, 0, 2, 0, 7, 0, 1, 0, 1, 0, 5, // glyph 5 has a caret at 500 and one on point 7
+			0, 1, 0, 10, 0, 2, 0, 1, 0, 2, // mark attachment classes
+			0, 1, 0, 1, 0, 0, 0, 8, 0, 1, 0, 1, 0, 11, // one mark glyph set, with glyph 11
+		];
+		let glyph_definition: GlyphDefinitionTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		assert_eq!(glyph_definition.glyph_class(1), Some(GlyphClass::Base));
+		assert_eq!(glyph_definition.glyph_class(5), Some(GlyphClass::Ligature));
+		assert_eq!(glyph_definition.glyph_class(10), Some(GlyphClass::Mark));
+		assert_eq!(glyph_definition.glyph_class(2), None);
+		assert_eq!(glyph_definition.ligature_carets(5), &[CaretValue::Coordinate(500), CaretValue::ContourPoint(7)]);
+		assert!(glyph_definition.ligature_carets(1).is_empty());
+
+		let ignore_marks = GlyphFilter { glyph_definition: Some(&glyph_definition), lookup_flag: 0x0008, mark_filtering_set: None };
+		assert!(ignore_marks.skips(10) && !ignore_marks.skips(1));
+		assert_eq!(ignore_marks.next(&[1, 10, 11, 2], 0), Some(3));
+		let mark_glyph_set = GlyphFilter { glyph_definition: Some(&glyph_definition), lookup_flag: 0x0010, mark_filtering_set: Some(0) };
+		assert!(mark_glyph_set.skips(10) && !mark_glyph_set.skips(11));
+		let mark_attachment_class = GlyphFilter { glyph_definition: Some(&glyph_definition), lookup_flag: 0x0200, mark_filtering_set: None };
+		assert!(mark_attachment_class.skips(10) && !mark_attachment_class.skips(11) && !mark_attachment_class.skips(5));
+	}
+
+	#[test]
+	fn vertical_header_metrics_and_origins() {
+		let data: Vec<u8> = vec![
+			0, 1, 16, 0, 1, 244, 254, 12, 0, 0, 3, 232, 0, 0, 0, 0, 3, 232, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, // vhea 1.1, ascender 500 and descender -500
+			3, 232, 0, 100, 3, 132, 0, 50, 0, 20, // vmtx, with the last glyph sharing the advance height before it
+			0, 1, 0, 0, 3, 112, 0, 1, 0, 2, 3, 132, // VORG, with glyph 2 at 900 and the rest at 880
+		];
+		let mut ttf_reader = TrueTypeFontReader::from_source(Cursor::new(&data));
+		let vertical_header: VerticalHeaderTable = ttf_reader.read(0).unwrap();
+		assert_eq!((vertical_header.ascender, vertical_header.descender, vertical_header.line_gap), (500, -500, 0));
+		assert_eq!(vertical_header.number_of_vertical_metrics, 2);
+		let vertical_metrics: VerticalMetricsTable = ttf_reader.read((vertical_header.number_of_vertical_metrics, 3, 36)).unwrap();
+		assert_eq!(vertical_metrics.vertical_metrics, vec![
+			VerticalMetric { advance_height: 1000, top_side_bearing: 100 },
+			VerticalMetric { advance_height: 900, top_side_bearing: 50 },
+			VerticalMetric { advance_height: 900, top_side_bearing: 20 },
+		]);
+		let vertical_origins: VerticalOriginTable = ttf_reader.read(46).unwrap();
+		assert_eq!(vertical_origins.vertical_origin_y(2), 900);
+		assert_eq!(vertical_origins.vertical_origin_y(1), 880);
+	}
+
+	#[test]
+	fn font_variations_named_instances() {
+		let data: Vec<u8> = vec![
+			0, 1, 0, 0, 0, 16, 0, 2, 0, 1, 0, 20, 0, 2, 0, 10, // header, with instance records big enough for PostScript names
+			b'w', b'g', b'h', b't', 0, 100, 0, 0, 1, 144, 0, 0, 3, 132, 0, 0, 0, 0, 1, 0, // weight from 100 to 900, defaulting to 400
+			1, 1, 0, 0, 2, 188, 0, 0, 1, 2, // instance at 700
+			1, 3, 0, 0, 1, 44, 0, 0, 255, 255, // instance at 300, without a PostScript name
+		];
+		let font_variations: FontVariationsTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		assert_eq!(font_variations.axes[0].default_value, 400.0);
+		assert_eq!(font_variations.instances.len(), 2);
+		assert_eq!((font_variations.instances[0].subfamily_name_id, font_variations.instances[0].postscript_name_id), (257, Some(258)));
+		assert_eq!(font_variations.instances[0].coordinates, vec![700.0]);
+		assert_eq!((font_variations.instances[1].coordinates[0], font_variations.instances[1].postscript_name_id), (300.0, None));
+	}
+
+	#[test]
+	fn glyph_variations_move_points_and_interpolate_untouched_ones() {
+		let data: Vec<u8> = vec![
+			0, 1, 0, 0, 0, 1, 0, 1, 0, 0, 0, 24, 0, 1, 0, 0, 0, 0, 0, 26, // header, with one axis and one shared tuple
+			0, 0, 0, 14, // glyph 0's data runs for 28 bytes
+			64, 0, // shared tuple peaking at 1
+			0, 2, 0, 14, // two tuple variations
+			0, 8, 32, 0, // the shared tuple, with its own points
+			0, 6, 160, 0, 192, 0, // peaking at -1, with its own points
+			2, 1, 0, 1, 1, 0, 50, 129, // points 0 and 1, moving point 1 right by 50
+			0, 133, 0, 236, 129, 136, // every point, moving the advance phantom point left by 20
+		];
+		let glyph_variations: GlyphVariationsTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		assert_eq!(glyph_variations.glyph_variations[0].len(), 2);
+
+		let glyph = GlyphRaw { number_of_contours: 1, x_min: 0, y_min: 0, x_max: 100, y_max: 100, glyph_data: GlyphDataRaw::SimpleGlyphRaw(SimpleGlyphRaw {
+			end_points_of_contours: vec![4],
+			instruction_length: 0,
+			instructions: Vec::new(),
+			flags: vec![1; 5],
+			x_coordinates: vec![0, 100, 100, 50, 0],
+			y_coordinates: vec![0, 0, 100, 100, 100],
+		})};
+		let horizontal_metric = HorizontalMetric { advance_width: 120, left_side_bearing: 0 };
+		let x_coordinates = |glyph: &GlyphRaw| match &glyph.glyph_data {
+			GlyphDataRaw::SimpleGlyphRaw(simple_glyph) => simple_glyph.x_coordinates.clone(),
+			_ => Vec::new(),
+		};
+
+		// Point 2 is beyond point 1, so moves with it, and point 3 is halfway between points 0 and 1, so moves half as far.
+		let (varied_glyph, varied_metric) = glyph_variations.vary_glyph(0, &glyph, horizontal_metric, &[1.0]);
+		assert_eq!(x_coordinates(&varied_glyph), vec![0, 150, 150, 75, 0]);
+		assert_eq!((varied_glyph.x_max, varied_metric.advance_width), (150, 120));
+		let (varied_glyph, _) = glyph_variations.vary_glyph(0, &glyph, horizontal_metric, &[0.5]);
+		assert_eq!(x_coordinates(&varied_glyph), vec![0, 125, 125, 63, 0]);
+		let (varied_glyph, varied_metric) = glyph_variations.vary_glyph(0, &glyph, horizontal_metric, &[-1.0]);
+		assert_eq!(x_coordinates(&varied_glyph), vec![0, 100, 100, 50, 0]);
+		assert_eq!(varied_metric.advance_width, 100);
+	}
+
+	#[test]
+	fn glyph_variations_interpolate_per_contour_and_reject_malformed_deltas() {
+		let data: Vec<u8> = vec![
+			0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 26, 0, 2, 0, 0, 0, 0, 0, 26, // header, with one axis and two glyphs
+			0, 0, 0, 10, 0, 19,
+			0, 1, 0, 10, 0, 10, 160, 0, 64, 0, // glyph 0's one tuple variation, peaking at 1, with its own points
+			3, 2, 1, 4, 15, 2, 10, 30, 99, 130, // points 1, 5 and 20, moving right by 10, 30 and 99
+			0, 1, 0, 10, 0, 8, 160, 0, 64, 0, // glyph 1's
+			2, 1, 0, 3, 1, 10, 20, 129, // points 0 and 3, moving right by 10 and 20
+		];
+		let glyph_variations: GlyphVariationsTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		let phantom_points = [(0, 0); 4];
+
+		// A contour with one moved point moves with it, other contours and the phantom points don't move, and points past the glyph are ignored.
+		let points: Vec<(i16, i16)> = [(0, 0), (100, 0), (100, 100), (0, 100), (200, 0), (300, 0), (250, 100)].into_iter().chain(phantom_points).collect();
+		let deltas = glyph_variations.point_deltas(0, &[1.0], &points, &[3, 6]);
+		assert_eq!(deltas, [(10.0, 0.0); 4].into_iter().chain([(30.0, 0.0); 3]).chain([(0.0, 0.0); 4]).collect::<Vec<(f32, f32)>>());
+		// As one contour, the points between the two moved ones are interpolated, or take the nearer one's delta outside them.
+		let deltas = glyph_variations.point_deltas(0, &[1.0], &points, &[6]);
+		assert_eq!(deltas[..7], [(10.0, 0.0), (10.0, 0.0), (10.0, 0.0), (10.0, 0.0), (20.0, 0.0), (30.0, 0.0), (25.0, 0.0)]);
+		assert_eq!(glyph_variations.point_deltas(0, &[0.5], &points, &[3, 6])[0], (5.0, 0.0));
+
+		// Points between two moved points at the same x, which move by different amounts, don't move along x.
+		let points: Vec<(i16, i16)> = [(0, 0), (100, 0), (100, 100), (0, 100)].into_iter().chain(phantom_points).collect();
+		let deltas = glyph_variations.point_deltas(1, &[1.0], &points, &[3]);
+		assert_eq!(deltas[..4], [(10.0, 0.0), (0.0, 0.0), (0.0, 0.0), (20.0, 0.0)]);
+
+		// An odd number of deltas, fewer deltas than points, a shared tuple that isn't there and glyph data offsets out of order.
+		for (index, value) in [(60, 2), (45, 128), (32, 32), (23, 20)] {
+			let mut data = data.clone();
+			data[index] = value;
+			let glyph_variations = TrueTypeFontReader::from_source(Cursor::new(&data)).read::<GlyphVariationsTable>(0);
+			assert!(matches!(glyph_variations, Err(ttf_reader::TrueTypeFontReaderError::InvalidData(TableTag::GlyphVariations, _))));
+		}
+	}
+
+	#[test]
+	fn variable_glyf_glyphs_are_varied_when_first_asked_for() {
+		let mut tables = test_font_tables();
+		tables.push((*b"fvar", vec![
+			0, 1, 0, 0, 0, 16, 0, 2, 0, 1, 0, 20, 0, 0, 0, 8,
+			b'w', b'g', b'h', b't', 0, 100, 0, 0, 1, 144, 0, 0, 3, 132, 0, 0, 0, 0, 1, 0, // weight from 100 to 900, defaulting to 400
+		]));
+		tables.push((*b"gvar", vec![
+			0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 26, 0, 2, 0, 0, 0, 0, 0, 26, // header, with one axis and two glyphs
+			0, 0, 0, 0, 0, 8,
+			0, 1, 0, 10, 0, 6, 160, 0, 64, 0, // glyph 1's one tuple variation, peaking at 1, with its own points
+			1, 0, 5, 0, 50, 128, // the advance phantom point, moving right by 50
+		]));
+		let font = Font::from_bytes(&sfnt(0x00010000, &tables)).unwrap();
+		let unread = |font: &Font| font.glyphs.lock().unwrap().iter().all(|glyph| matches!(glyph, LazyGlyph::GlyphUnread(..)));
+		assert!(unread(&font));
+
+		// Instances are made without reading any glyphs, which are varied as they're asked for.
+		let bold = font.with_variation(&[("wght", 900.0)]);
+		let medium = font.with_variation(&[("wght", 650.0)]);
+		assert!(unread(&bold) && unread(&medium));
+		assert_eq!(bold.get_glyph(1).unwrap().advance_width.value, 650);
+		assert_eq!(medium.get_glyph(1).unwrap().advance_width.value, 625);
+		assert_eq!(font.get_glyph(1).unwrap().advance_width.value, 600);
+		assert!(!unread(&medium) && matches!(medium.glyphs.lock().unwrap()[0], LazyGlyph::GlyphUnread(..)));
+	}
+
+	#[test]
+	fn axis_and_metrics_variations() {
+		let data: Vec<u8> = vec![
+			0, 1, 0, 0, 0, 0, 0, 1, // avar header, with one axis
+			0, 4, 192, 0, 192, 0, 0, 0, 0, 0, 32, 0, 48, 0, 64, 0, 64, 0, // -1 to -1, 0 to 0, 0.5 to 0.75 and 1 to 1
+		];
+		let axis_variations: AxisVariationsTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		assert_eq!(axis_variations.map_coordinate(0, 0.25), 0.375);
+		assert_eq!(axis_variations.map_coordinate(0, 0.75), 0.875);
+		assert_eq!(axis_variations.map_coordinate(0, -0.5), -0.5);
+
+		// One region peaking at 1 on the only axis, shared by both item variation stores.
+		let region_list: [u8; 10] = [0, 1, 0, 1, 0, 0, 64, 0, 64, 0];
+		let mut data: Vec<u8> = vec![
+			0, 1, 0, 0, 0, 0, 0, 20, 0, 0, 0, 52, 0, 0, 0, 0, 0, 0, 0, 0, // HVAR header, with only an advance width mapping
+			0, 1, 0, 0, 0, 12, 0, 1, 0, 0, 0, 22, // item variation store
+		];
+		data.extend(region_list);
+		data.extend([0, 2, 0, 0, 0, 1, 0, 0, 10, 236]); // two items, widening by 10 and narrowing by 20
+		data.extend([0, 0, 0, 3, 1, 0, 1]); // glyphs 0 and 2 use the second item, glyph 1 the first
+		let horizontal_metrics_variations: HorizontalMetricsVariationsTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		assert_eq!(horizontal_metrics_variations.advance_width_delta(0, &[1.0]), -20.0);
+		assert_eq!(horizontal_metrics_variations.advance_width_delta(1, &[0.5]), 5.0);
+		assert_eq!(horizontal_metrics_variations.advance_width_delta(5, &[1.0]), -20.0); // past the end of the map, so uses its last entry
+		assert_eq!(horizontal_metrics_variations.left_side_bearing_delta(0, &[1.0]), None);
+
+		let mut data: Vec<u8> = vec![
+			0, 1, 0, 0, 0, 0, 0, 8, 0, 1, 0, 20, // MVAR header, with one value record
+			104, 97, 115, 99, 0, 0, 0, 0, // hasc
+			0, 1, 0, 0, 0, 12, 0, 1, 0, 0, 0, 22, // item variation store
+		];
+		data.extend(region_list);
+		data.extend([0, 1, 0, 0, 0, 1, 0, 0, 50]); // one item, raising by 50
+		let metrics_variations: MetricsVariationsTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		assert_eq!(metrics_variations.delta(*b"hasc", &[0.5]), 25.0);
+		assert_eq!(metrics_variations.delta(*b"hdsc", &[0.5]), 0.0);
+	}
+
+	#[test]
+	fn colour_layers_and_palettes() {
+		let data: Vec<u8> = vec![
+			0, 0, 0, 2, 0, 0, 0, 14, 0, 0, 0, 26, 0, 3, // COLR header, with two base glyphs and three layers
+			0, 5, 0, 0, 0, 2, 0, 9, 0, 2, 0, 1, // glyph 5 from the first two layers, glyph 9 from the last
+			0, 3, 0, 1, 0, 4, 255, 255, 0, 6, 0, 0, // the second layer of glyph 5 is in the text colour
+		];
+		let colour_glyphs: ColourTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		assert_eq!(colour_glyphs.layers(5), &[LayerRecord { glyph_id: 3, palette_entry_index: 1 }, LayerRecord { glyph_id: 4, palette_entry_index: FOREGROUND_PALETTE_ENTRY }]);
+		assert_eq!(colour_glyphs.layers(9), &[LayerRecord { glyph_id: 6, palette_entry_index: 0 }]);
+		assert!(colour_glyphs.layers(7).is_empty());
+
+		let data: Vec<u8> = vec![
+			0, 1, 0, 2, 0, 2, 0, 3, 0, 0, 0, 28, 0, 0, 0, 1, // CPAL header, with two palettes of two entries sharing a colour
+			0, 0, 0, 40, 0, 0, 0, 48, 0, 0, 0, 0, // palette types and labels
+			0, 0, 255, 255, 0, 255, 0, 255, 255, 0, 0, 128, // red, green and a translucent blue, stored blue first
+			0, 0, 0, 1, 0, 0, 0, 2, // for light and dark backgrounds
+			255, 255, 1, 44, // only the second palette is named
+		];
+		let colour_palettes: ColourPaletteTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		let green = PaletteColour { red: 0, green: 255, blue: 0, alpha: 255 };
+		assert_eq!(colour_palettes.palettes[0], vec![PaletteColour { red: 255, green: 0, blue: 0, alpha: 255 }, green]);
+		assert_eq!(colour_palettes.palettes[1], vec![green, PaletteColour { red: 0, green: 0, blue: 255, alpha: 128 }]);
+		assert_eq!(colour_palettes.colour(1, 0), Some(green));
+		assert_eq!(colour_palettes.colour(0, FOREGROUND_PALETTE_ENTRY), None);
+		assert_eq!(colour_palettes.palette_for_background(true), Some(1));
+		assert_eq!(colour_palettes.palette_labels, vec![None, Some(300)]);
+	}
+
+	#[test]
+	fn colour_paint_graph_evaluated_and_rasterized() {
+		let data: Vec<u8> = vec![
+			0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, // COLR version 1 header, without any version 0 glyphs
+			0, 0, 0, 34, 0, 0, 0, 0, 0, 0, 0, 81, 0, 0, 0, 0, 0, 0, 0, 0, // a base glyph list and a clip list
+			0, 0, 0, 1, 0, 5, 0, 0, 0, 10, // glyph 5 is painted by
+			10, 0, 0, 6, 0, 2, // a linear gradient clipped to glyph 2
+			4, 0, 0, 16, 0, 0, 0, 0, 0, 100, 0, 0, 0, 0, 0, 100, // from x = 0 to x = 100
+			0, 0, 2, 0, 0, 0, 0, 64, 0, 64, 0, 0, 1, 64, 0, // padded, from the first palette entry to the second
+			1, 0, 0, 0, 1, 0, 5, 0, 5, 0, 0, 12, // glyph 5 is clipped to
+			1, 0, 0, 0, 0, 0, 100, 0, 100, // a box from (0, 0) to (100, 100)
+		];
+		let colour_glyphs: ColourTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		let red = PaletteColour { red: 255, green: 0, blue: 0, alpha: 255 };
+		let blue = PaletteColour { red: 0, green: 0, blue: 255, alpha: 255 };
+		let commands = colour_glyphs.paint(5, &[red, blue], red, &[]).unwrap();
+		let gradient = Gradient { extend: Extend::Pad, stops: vec![GradientStop { offset: 0.0, colour: [1.0, 0.0, 0.0, 1.0] }, GradientStop { offset: 1.0, colour: [0.0, 0.0, 1.0, 1.0] }] };
+		assert_eq!(commands, vec![PaintCommand::Fill { clips: vec![(2, Affine::IDENTITY)], brush: Brush::LinearGradient { start: (0.0, 0.0), end: (100.0, 0.0), gradient }, brush_transform: Affine::IDENTITY }]);
+		let clip_box = colour_glyphs.clip_box(5, &[]);
+		assert_eq!(clip_box, Some(ClipBox { x_min: 0.0, y_min: 0.0, x_max: 100.0, y_max: 100.0 }));
+		assert!(colour_glyphs.paint(6, &[red, blue], red, &[]).is_none());
+
+		// The clip glyph covers the left half of the box, in two anticlockwise triangles.
+		let vertices = vec![Vertex::new(0, 0), Vertex::new(50, 0), Vertex::new(50, 100), Vertex::new(0, 100)];
+		let half = Arc::new(Glyph::new_simple(vertices, vec![0, 1, 2, 0, 2, 3], Vec::new(), Vec::new(), Vec::new(), Bounds { x_min: 0, x_max: 50, y_min: 0, y_max: 100 }));
+		let image = colour_rasterizer::rasterize_paint(&ColourPaint { commands, clip_box }, &|index| (index == 2).then(|| half.clone()), 0.1);
+		assert_eq!((image.width, image.height), (10, 10));
+		assert_eq!(image.pixel(0, 3), [242, 0, 13, 255]);
+		assert_eq!(image.pixel(4, 9), [140, 0, 115, 255]);
+		assert_eq!(image.pixel(5, 0), [0, 0, 0, 0]);
+
+		// Record counts that run past the end of the table are rejected before anything is allocated for them.
+		for count_offset in [34, 82] {
+			let mut data = data.clone();
+			data[count_offset..count_offset + 4].copy_from_slice(&u32::MAX.to_be_bytes());
+			let colour_glyphs = TrueTypeFontReader::from_source(Cursor::new(&data)).read::<ColourTable>(0);
+			assert!(matches!(colour_glyphs, Err(ttf_reader::TrueTypeFontReaderError::InvalidData(TableTag::Colour, _))));
+		}
+	}
+
+	#[test]
+	fn png_filters_and_palettes_decoded() {
+		let data: Vec<u8> = vec![
+			137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 2, 0, 0, 0, 2, 8, 6, 0, 0, 0, 114, 182, 13, 36, // a 2 by 2 RGBA image
+			0, 0, 0, 24, 73, 68, 65, 84, 120, 218, 99, 252, 207, 192, 240, 159, 241, 63, 67, 35, 19, 35, 195, 127, 16, 108, 0, 0, 60, 158, 7, 1, 58, 150, 189, 27, // rows with the sub and up filters
+			0, 0, 0, 0, 73, 69, 78, 68, 174, 66, 96, 130,
+		];
+		assert_eq!(png::decode(&data), Ok(Image { width: 2, height: 2, pixels: vec![[255, 0, 0, 255], [0, 255, 0, 128], [0, 0, 255, 255], [255, 255, 255, 0]] }));
+
+		let data: Vec<u8> = vec![
+			137, 80, 78, 71, 13, 10, 26, 10, 0, 0, 0, 13, 73, 72, 68, 82, 0, 0, 0, 3, 0, 0, 0, 1, 2, 3, 0, 0, 0, 102, 142, 252, 39, // a 3 by 1 image with a 2 bit palette
+			0, 0, 0, 9, 80, 76, 84, 69, 255, 0, 0, 0, 255, 0, 0, 0, 255, 45, 74, 205, 138, // red, green and blue
+			0, 0, 0, 2, 116, 82, 78, 83, 255, 0, 229, 183, 48, 74, // green is see through
+			0, 0, 0, 10, 73, 68, 65, 84, 120, 218, 99, 144, 0, 0, 0, 26, 0, 25, 128, 0, 142, 187, // indices 0, 1 and 2
+			0, 0, 0, 0, 73, 69, 78, 68, 174, 66, 96, 130,
+		];
+		assert_eq!(png::decode(&data), Ok(Image { width: 3, height: 1, pixels: vec![[255, 0, 0, 255], [0, 255, 0, 0], [0, 0, 255, 255]] }));
+		assert_eq!(png::decode(&data[1..]), Err(png::PngError::InvalidSignature));
+	}
+
+	#[test]
+	fn png_average_and_paeth_filters_and_malformed_images() {
+		// Chunk CRCs aren't checked, so they are left as zero.
+		let png = |header: Option<[u8; 13]>, image_data: &[u8]| -> Vec<u8> {
+			let mut data: Vec<u8> = vec![137, 80, 78, 71, 13, 10, 26, 10];
+			let chunks = header.iter().map(|header| (b"IHDR", &header[..])).chain([(b"IDAT", image_data), (b"IEND", &[][..])]);
+			for (chunk_type, chunk) in chunks {
+				data.extend((chunk.len() as u32).to_be_bytes());
+				data.extend(chunk_type);
+				data.extend(chunk);
+				data.extend([0; 4]);
+			}
+			data
+		};
+		let grey = [0, 0, 0, 2, 0, 0, 0, 3, 8, 0, 0, 0, 0]; // a 2 by 3 greyscale image
+		let rows: [u8; 17] = [120, 218, 99, 224, 18, 97, 150, 228, 103, 17, 225, 2, 0, 1, 238, 0, 108]; // 10 20 unfiltered, 30 40 averaged, 50 60 Paeth predicted
+		let pixels: Vec<[u8; 4]> = [10, 20, 30, 40, 50, 60].iter().map(|&grey| [grey, grey, grey, 255]).collect();
+		assert_eq!(png::decode(&png(Some(grey), &rows)), Ok(Image { width: 2, height: 3, pixels }));
+
+		let bad_filter: [u8; 17] = [120, 218, 99, 224, 18, 97, 149, 228, 103, 17, 225, 2, 0, 1, 250, 0, 110]; // the middle row's filter is 5
+		assert_eq!(png::decode(&png(Some(grey), &bad_filter)), Err(png::PngError::InvalidFilter(5)));
+		let two_rows: [u8; 14] = [120, 218, 99, 224, 18, 97, 150, 228, 7, 0, 0, 210, 0, 74];
+		assert_eq!(png::decode(&png(Some(grey), &two_rows)), Err(png::PngError::UnexpectedEnd));
+		let mut corrupt = rows;
+		corrupt[16] ^= 1;
+		assert_eq!(png::decode(&png(Some(grey), &corrupt)), Err(png::PngError::DecompressionFailed(InflateError::ChecksumMismatch)));
+
+		assert_eq!(png::decode(&png(None, &rows)), Err(png::PngError::MissingHeader));
+		assert_eq!(png::decode(&png(Some([0, 0, 0, 2, 0, 0, 0, 3, 8, 3, 0, 0, 0]), &rows)), Err(png::PngError::MissingPalette));
+		assert_eq!(png::decode(&png(Some([0, 0, 0, 2, 0, 0, 0, 3, 4, 2, 0, 0, 0]), &rows)), Err(png::PngError::UnsupportedFormat(2, 4)));
+		assert_eq!(png::decode(&png(Some([0, 1, 0, 0, 0, 1, 0, 0, 8, 0, 0, 0, 0]), &rows)), Err(png::PngError::ImageTooLarge(65536, 65536)));
+		let data = png(Some(grey), &rows);
+		assert_eq!(png::decode(&data[..data.len() - 12]), Err(png::PngError::UnexpectedEnd)); // no IEND
+	}
+
+	#[test]
+	fn colour_bitmap_and_sbix_strikes() {
+		let mut data: Vec<u8> = vec![
+			0, 3, 0, 0, 0, 0, 0, 1, // CBLC header, with one strike
+			0, 0, 0, 56, 0, 0, 0, 20, 0, 0, 0, 1, 0, 0, 0, 0, // its index subtable array and how many subtables it has
+		];
+		data.extend([0; 24]); // line metrics
+		data.extend([
+			0, 4, 0, 5, 20, 20, 32, 1, // glyphs 4 to 5 at 20 pixels per em
+			0, 4, 0, 5, 0, 0, 0, 8, // one index subtable
+			0, 1, 0, 17, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 12, // format 1 with PNGs and small metrics, where glyph 4 has no image
+		]);
+		let location_table: ColourBitmapLocationTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		let data: Vec<u8> = vec![
+			0, 3, 0, 0, // CBDT header
+			2, 3, 1, 6, 4, 0, 0, 0, 3, 7, 8, 9, // a 3 by 2 image, 1 pixel right of the origin and reaching 6 above it
+		];
+		let data_table: ColourBitmapDataTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read((0, data.len() as u32)).unwrap();
+		let colour_bitmaps = BitmapGlyphs::ColourBitmaps(location_table, data_table);
+		assert_eq!(colour_bitmaps.images(5), vec![BitmapImage { pixels_per_em: 20, left: 1, bottom: 4, graphic_type: PNG_GRAPHIC_TYPE, data: &[7, 8, 9] }]);
+		assert!(colour_bitmaps.images(4).is_empty());
+		assert!(colour_bitmaps.images(6).is_empty());
+
+		let data: Vec<u8> = vec![
+			0, 1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 12, // sbix header, with one strike
+			0, 64, 0, 72, 0, 0, 0, 20, 0, 0, 0, 20, 0, 0, 0, 31, 0, 0, 0, 41, // at 64 pixels per em, without a graphic for glyph 0
+			0, 2, 255, 254, b'p', b'n', b'g', b' ', 1, 2, 3, // glyph 1
+			0, 0, 0, 0, b'd', b'u', b'p', b'e', 0, 1, // glyph 2 is the same as glyph 1
+		];
+		let table: StandardBitmapGraphicsTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read((0, data.len() as u32, 3)).unwrap();
+		let standard_bitmap_graphics = BitmapGlyphs::StandardBitmapGraphics(table);
+		let image = BitmapImage { pixels_per_em: 64, left: 2, bottom: -2, graphic_type: PNG_GRAPHIC_TYPE, data: &[1, 2, 3] };
+		assert_eq!(standard_bitmap_graphics.images(1), vec![image.clone()]);
+		assert_eq!(standard_bitmap_graphics.images(2), vec![image]);
+		assert!(standard_bitmap_graphics.images(0).is_empty());
+
+		let strike = |pixels_per_em: u16| Bitmap { pixels_per_em, left: 1, bottom: 4, image: Image { width: 3, height: 2, pixels: vec![[0; 4]; 6] } };
+		let bitmap_glyph = BitmapGlyph { strikes: vec![strike(20), strike(64), strike(40)] };
+		assert_eq!(bitmap_glyph.nearest_strike(12.0).map(|(index, _)| index), Some(0));
+		assert_eq!(bitmap_glyph.nearest_strike(30.0).map(|(index, _)| index), Some(2));
+		assert_eq!(bitmap_glyph.nearest_strike(100.0).map(|(index, _)| index), Some(1));
+		let bounds = bitmap_glyph.strikes[0].bounds(1000);
+		assert_eq!((bounds.x_min, bounds.x_max, bounds.y_min, bounds.y_max), (50, 200, 200, 300));
+	}
+
+	#[test]
+	fn composite_components_placed_by_matching_points() {
+		let square = GlyphRaw {
+			number_of_contours: 1, x_min: 0, y_min: 0, x_max: 100, y_max: 100,
+			glyph_data: GlyphDataRaw::SimpleGlyphRaw(SimpleGlyphRaw {
+				end_points_of_contours: vec![3],
+				instruction_length: 0,
+				instructions: Vec::new(),
+				flags: vec![1; 4],
+				x_coordinates: vec![0, 100, 100, 0],
+				y_coordinates: vec![0, 0, 100, 100],
+			}),
+		};
+		let component = |flag: u16, x_offset_point: i32, y_offset_point: i32| ComponentGlyphRaw { flag, glyph_index: 0, x_offset_point, y_offset_point, transform_0: None, transform_1: None, transform_2: None, transform_3: None };
+		let composite = |children: Vec<ComponentGlyphRaw>| GlyphRaw {
+			number_of_contours: -1, x_min: 0, y_min: 0, x_max: 300, y_max: 300,
+			glyph_data: GlyphDataRaw::CompositeGlyphRaw(CompositeGlyphRaw { children, instructions: Vec::new() }),
+		};
+		let glyphs = |glyph_id: u16| (glyph_id == 0).then_some(Cow::Borrowed(&square));
+
+		// The second square's first point is put on the first square's third, at (150, 100), then the third square's third point on the second's.
+		let matched = composite(vec![component(0x0002, 50, 0), component(0x0000, 2, 0), component(0x0000, 6, 2)]);
+		let GlyphDataIntermediate::CompositeGlyph(composite_glyph) = ttf_parser::match_component_points(&glyphs, matched).glyph_data else {
+			panic!("glyph should be a composite");
+		};
+		let offsets: Vec<(i32, i32)> = composite_glyph.children.iter().map(|child| (child.offset.x, child.offset.y)).collect();
+		assert_eq!(offsets, vec![(50, 0), (150, 100), (150, 100)]);
+
+		// Points past the end of the composite so far can't be matched, and neither can a composite nobody has matched.
+		let unmatched = composite(vec![component(0x0002, 0, 0), component(0x0000, 4, 0)]);
+		assert!(matches!(ttf_parser::match_component_points(&glyphs, unmatched.clone()).glyph_data, GlyphDataIntermediate::FailedParse(GlyphParseError::UnmatchedComponentPoint)));
+		assert!(matches!(GlyphIntermediate::from(unmatched).glyph_data, GlyphDataIntermediate::FailedParse(GlyphParseError::UnmatchedComponentPoint)));
+	}
+
+	#[test]
+	fn composite_components_keep_their_transforms() {
+		let component = |transform: [Option<u16>; 4]| ComponentGlyph::from(GlyphComponentIntermediate::from(ComponentGlyphRaw {
+			flag: 0x0002, glyph_index: 0, x_offset_point: 50, y_offset_point: 0,
+			transform_0: transform[0], transform_1: transform[1], transform_2: transform[2], transform_3: transform[3],
+		}));
+		let flipped = component([Some(0xC000), None, None, None]);
+		assert_eq!((flipped.transform, flipped.offset.x.value), (Affine::scale(-1.0, -1.0), 50));
+		assert_eq!(component([Some(0x2000), Some(0x4000), None, None]).transform, Affine::scale(0.5, 1.0));
+		// A quarter turn anticlockwise, taking x to y and y to -x.
+		assert_eq!(component([Some(0), Some(0x4000), Some(0xC000), Some(0)]).transform, Affine { xx: 0.0, yx: 1.0, xy: -1.0, yy: 0.0, dx: 0.0, dy: 0.0 });
+	}
+
+	#[test]
+	fn contours_without_points_have_a_direction() {
+		let points: Vec<Point> = [(0, 0), (100, 0), (100, 100)].into_iter().map(|(x, y)| Point { flag: 1, x, y }).collect();
+		assert!(matches!(vec![Some(0), Some(1), Some(2)].get_direction(&points), Direction::CounterClockwise));
+		for indices in [vec![], vec![None, None], vec![Some(0), Some(1), Some(5)]] {
+			assert!(matches!(indices.get_direction(&points), Direction::Clockwise));
+		}
+	}
+
+	#[test]
+	fn instructions_grid_fit_points_and_gasp_chooses_sizes() {
+		let maximum_profile_data: Vec<u8> = vec![
+			0, 1, 0, 0, 0, 3, 0, 5, 0, 1, 0, 5, 0, 1, 0, 2, // version 1.0, three glyphs, two zones
+			0, 4, 0, 4, 0, 1, 0, 0, 0, 16, 0, 16, 0, 1, 0, 1, // four twilight points, four storage locations, one function and a stack of 16
+		];
+		let maximum_profile: MaximumProfileTable = TrueTypeFontReader::from_source(Cursor::new(&maximum_profile_data)).read(0).unwrap();
+		let simple_glyph = |instructions: Vec<u8>| GlyphRaw {
+			number_of_contours: 1, x_min: 100, y_min: 0, x_max: 501, y_max: 701,
+			glyph_data: GlyphDataRaw::SimpleGlyphRaw(SimpleGlyphRaw {
+				end_points_of_contours: vec![4],
+				instruction_length: instructions.len() as u16,
+				instructions,
+				flags: vec![1; 5],
+				x_coordinates: vec![100, 301, 501, 501, 100],
+				y_coordinates: vec![0, 0, 0, 701, 701],
+			}),
+		};
+		let composite_glyph = GlyphRaw {
+			number_of_contours: -1, x_min: 200, y_min: 0, x_max: 601, y_max: 701,
+			glyph_data: GlyphDataRaw::CompositeGlyphRaw(CompositeGlyphRaw {
+				children: vec![ComponentGlyphRaw { flag: 0x0006, glyph_index: 0, x_offset_point: 100, y_offset_point: 0, transform_0: None, transform_1: None, transform_2: None, transform_3: None }],
+				instructions: Vec::new(),
+			}),
+		};
+		let glyphs = vec![
+			// SVTCA[x], call function 0 to round point 0, MIRP point 2 at control value 0, rounded and at least the minimum distance, then IUP[x]
+			simple_glyph(vec![0x01, 0xB1, 0, 0, 0x2B, 0xB1, 2, 0, 0xEC, 0x31]),
+			simple_glyph(vec![0x28]),
+			composite_glyph,
+			// The stack holds what maxp asks for plus some room for fonts that ask for too little, then overflows.
+			simple_glyph([0x40, 48].into_iter().chain([0; 48]).collect()),
+			simple_glyph([0x40, 49].into_iter().chain([0; 49]).collect()),
+			// POP and CINDEX past the bottom of the stack
+			simple_glyph(vec![0x21]),
+			simple_glyph(vec![0xB0, 5, 0x25]),
+			// Divide 1 by control value 0, then by control value 99 after writing it, and by control value -1, which don't exist so read as 0
+			simple_glyph(vec![0xB1, 1, 0, 0x45, 0x62]),
+			simple_glyph(vec![0xB3, 1, 99, 99, 5, 0x44, 0x45, 0x62]),
+			simple_glyph(vec![0xB0, 1, 0xB8, 0xFF, 0xFF, 0x45, 0x62]),
+			// Calling a function that isn't defined, jumping back forever and skipping a false IF that has no EIF
+			simple_glyph(vec![0xB0, 7, 0x2B]),
+			simple_glyph(vec![0xB8, 0xFF, 0xFD, 0x1C]),
+			simple_glyph(vec![0xB0, 0, 0x58]),
+			// SCFS moving every point to x 0, then the left side of the glyph past the right
+			simple_glyph(vec![0x01, 0x40, 10, 0, 0, 1, 0, 2, 0, 3, 0, 4, 0, 0x48, 0x48, 0x48, 0x48, 0x48]),
+			simple_glyph(vec![0x01, 0xB3, 0, 255, 4, 255, 0x48, 0x48]),
+		];
+		let horizontal_metrics = vec![HorizontalMetric { advance_width: 700, left_side_bearing: 100 }; glyphs.len()];
+		let font_program = vec![0xB0, 0, 0x2C, 0x2F, 0x2D]; // FDEF 0: MDAP[round]
+		let control_value_program = vec![0xB0, 70, 0x1D]; // SCVTCI
+		let tables = HintingTables { font_program, control_value_program, control_values: vec![400], grid_fitting: None };
+		let hinting = Hinting::new(tables, glyphs.into(), horizontal_metrics, 2048, &maximum_profile);
+
+		// At 16 pixels per em a font unit is half a 26.6 unit. The stem from point 0 to 2 is 201, rounded to 192 from the control value of 200.
+		let outline = hinting.hint_glyph(0, 16.0).unwrap().unwrap();
+		assert_eq!(outline.points, vec![(64, 0), (160, 0), (256, 0), (256, 351), (64, 351)]);
+		assert_eq!((outline.phantom_points[0], outline.phantom_points[1]), ((0, 0), (320, 0)));
+		assert_eq!(hinting.hint_glyph(1, 16.0), Err(HintingError::InvalidOpcode(0x28)));
+		// The component's offset of 50 is rounded to the grid, as its flags ask.
+		let composite = hinting.hint_glyph(2, 16.0).unwrap().unwrap();
+		assert_eq!(composite.points[0], (128, 0));
+		assert_eq!(composite.contour_ends, vec![4]);
+
+		let errors: Vec<Option<HintingError>> = (3..13).map(|glyph_id| hinting.hint_glyph(glyph_id, 16.0).err()).collect();
+		assert_eq!(errors, vec![
+			None, Some(HintingError::StackOverflow),
+			Some(HintingError::StackUnderflow), Some(HintingError::StackUnderflow),
+			None, Some(HintingError::DivideByZero), Some(HintingError::DivideByZero),
+			Some(HintingError::FunctionNotDefined(7)), Some(HintingError::TooManyInstructions), Some(HintingError::UnexpectedEnd),
+		]);
+		// Outlines the instructions flatten or turn inside out are given up on, leaving the glyph unhinted.
+		assert_eq!((hinting.hint_glyph(13, 16.0), hinting.hint_glyph(14, 16.0)), (Err(HintingError::DistortedOutline), Err(HintingError::DistortedOutline)));
+
+		let grid_fitting_data: Vec<u8> = vec![0, 1, 0, 2, 0, 8, 0, 2, 255, 255, 0, 3]; // smoothed only up to 8 pixels per em, then grid-fitted too
+		let grid_fitting: GridFittingTable = TrueTypeFontReader::from_source(Cursor::new(&grid_fitting_data)).read(0).unwrap();
+		assert_eq!((grid_fitting.behaviour(8), grid_fitting.behaviour(9)), (DO_GREY, GRID_FIT | DO_GREY));
+		assert!(!grid_fitting.grid_fits(7) && grid_fitting.grid_fits(20));
+	}
+
+	#[test]
+	fn autohinter_snaps_stems_and_blue_zones() {
+		let mut data: Vec<u8> = vec![
+			0, 0, 0, 1,
+			0, 3, 0, 1, 0, 0, 0, 12, // Windows Unicode BMP
+			0, 6, 0, 108, 0, 0, 0, 72, 0, 49, // format 6 from 'H' to 'x'
+		];
+		let mut glyph_ids = [0u8; 98];
+		glyph_ids[1] = 1; // 'H'
+		glyph_ids[97] = 2; // 'x'
+		data.extend(glyph_ids);
+		let cmap: CharacterToGlyphIndexTable = TrueTypeFontReader::from_source(Cursor::new(&data)).read(0).unwrap();
+		let mappings: Vec<font::Mapping> = cmap.encoding_records.iter().zip(cmap.subtables).map(|v| v.into()).collect();
+
+		let rectangle = |left: i16, bottom: i16, right: i16, top: i16| GlyphRaw {
+			number_of_contours: 1, x_min: left, y_min: bottom, x_max: right, y_max: top,
+			glyph_data: GlyphDataRaw::SimpleGlyphRaw(SimpleGlyphRaw {
+				end_points_of_contours: vec![3],
+				instruction_length: 0,
+				instructions: Vec::new(),
+				flags: vec![1; 4],
+				x_coordinates: vec![left, left, right, right],
+				y_coordinates: vec![bottom, top, top, bottom],
+			}),
+		};
+		let glyphs = vec![
+			GlyphRaw { number_of_contours: 0, x_min: 0, y_min: 0, x_max: 0, y_max: 0, glyph_data: GlyphDataRaw::None },
+			rectangle(100, 0, 301, 1401),
+			rectangle(100, 0, 301, 1001),
+			rectangle(130, -10, 330, 995),
+		];
+		let auto_hinter = AutoHinter::new(AutoHintOutlines::TrueType(glyphs.into()), 2048, Arc::new(mappings));
+		assert_eq!(auto_hinter.blue_zones()[0], BlueZone { reference: 1401.0, overshoot: 1401.0, edge: BlueEdge::Top });
+		assert_eq!(auto_hinter.blue_zones().len(), 4);
+
+		// At 16 pixels per em a pixel is 128 font units. The x-height of 7.8 pixels rounds to 8, the overshoot below the
+		// baseline is too small to keep, and the stem of 1.6 pixels becomes 2 about its centre.
+		let outline = auto_hinter.hint_glyph(3, 16.0).unwrap();
+		assert_eq!(outline.x_coordinates, vec![128, 128, 384, 384]);
+		assert_eq!(outline.y_coordinates, vec![0, 1024, 1024, 0]);
+		assert!(auto_hinter.hint_glyph(0, 16.0).is_none());
+	}
+
+	#[test]
+	fn glyphs_read_from_source_when_needed() {
+		let data: Vec<u8> = vec![
+			0, 0, 0, 0, // padding before glyf
+			0, 1, 0, 0, 0, 0, 0, 100, 0, 100, // one contour, bounds
+			0, 2, 0, 0, // end point of the contour, no instructions
+			1, 1, 1, // three points on the curve, with word coordinates
+			0, 0, 0, 100, 0xFF, 0x9C, // x deltas 0, 100, -100
+			0, 0, 0, 0, 0, 100, // y deltas 0, 0, 100
+		];
+		let glyph_offsets = vec![
+			GlyphOffset { id: 0, glyph_offset: None, glyph_length: None },
+			GlyphOffset { id: 1, glyph_offset: Some(0), glyph_length: Some(28) },
+			GlyphOffset { id: 2, glyph_offset: Some(100), glyph_length: Some(28) },
+		];
+		let glyph_locations = GlyphLocations::new(Arc::new(Mutex::new(TrueTypeFontReader::from_source(Cursor::new(data)))), glyph_offsets, 4);
+
+		assert!(matches!(glyph_locations.read_glyph(0), Some(Ok(GlyphRaw { glyph_data: GlyphDataRaw::None, .. }))));
+		let Some(Ok(GlyphRaw { glyph_data: GlyphDataRaw::SimpleGlyphRaw(glyph), .. })) = glyph_locations.read_glyph(1) else {
+			panic!("glyph 1 should be a simple glyph");
+		};
+		assert_eq!((glyph.x_coordinates, glyph.y_coordinates), (vec![0, 100, 0], vec![0, 0, 100]));
+		// A glyph past the end of the data only fails when it's read.
+		assert!(matches!(glyph_locations.read_glyph(2), Some(Err(_))));
+		assert!(glyph_locations.read_glyph(3).is_none());
+	}
+
+	#[test]
+	fn cubic_curves_classified() {
+		assert_eq!(CubicCurve::new((100.0, 100.0), (300.0, 900.0), (500.0, -300.0), (900.0, 500.0)).classify(), CubicCurveType::Serpentine);
+		assert_eq!(CubicCurve::new((100.0, 500.0), (100.0, 900.0), (900.0, 900.0), (500.0, 100.0)).classify(), CubicCurveType::Loop);
+		assert_eq!(CubicCurve::new((100.0, 100.0), (900.0, 900.0), (100.0, 900.0), (900.0, 100.0)).classify(), CubicCurveType::Cusp);
+		assert_eq!(CubicCurve::new((0.0, 0.0), (200.0, 400.0), (500.0, 400.0), (900.0, 0.0)).classify(), CubicCurveType::Quadratic);
+		assert_eq!(CubicCurve::new((100.0, 100.0), (300.0, 300.0), (600.0, 600.0), (900.0, 900.0)).classify(), CubicCurveType::Line);
+	}
+
+	#[test]
+	fn loop_split_at_double_point() {
+		let curve = CubicCurve::new((100.0, 100.0), (1000.0, 700.0), (-100.0, 700.0), (700.0, 100.0));
+		let parameters = curve.split_parameters();
+		assert_eq!(parameters.len(), 2);
+		assert!(parameters.iter().all(|t| *t > 0.0 && *t < 1.0));
+		// The curve passes through the same point at both, where it crosses itself.
+		let (first, second) = (curve.point_at(parameters[0]), curve.point_at(parameters[1]));
+		assert!((first.0 - second.0).abs() < 1e-6 && (first.1 - second.1).abs() < 1e-6);
+	}
+}
@@ -1,12 +1,18 @@
 use std::{path::Path, time::Instant};
 
-use tapestry::font::{Font, Mapping};
+use tapestry::font::{Font, MappingFormat};
 
 fn main() {
 	let filename = Path::new("./resources/fonts/Noto_Sans_JP/static/NotoSansJP-Regular.ttf");
 
 	let before = Instant::now();
-	let font = Font::new(filename);
+	let font = match Font::new(filename) {
+		Ok(font) => font,
+		Err(error) => {
+			println!("Failed to load font: {error:?}");
+			return;
+		}
+	};
 	let elapsed_time = before.elapsed();
 	println!("Loading Font took {} milliseconds", elapsed_time.as_millis());
 	println!("Font has {} glyphs", font.number_of_glyphs);
@@ -14,10 +20,16 @@ fn main() {
 	println!("Has Mappings:");
 	for mapping in font.mappings.iter() {
 		println!("	Mapping Format: {}",
-			match mapping {
-				Mapping::TrueTypeFormat12(_) => "12".to_string(),
-				Mapping::TrueTypeFormat4(_) => "4".to_string(),
-				Mapping::InvalidFormat(format) => format!("Invalid: {format}"),
+			match &mapping.format {
+				MappingFormat::TrueTypeFormat0(_) => "0".to_string(),
+				MappingFormat::TrueTypeFormat2(_) => "2".to_string(),
+				MappingFormat::TrueTypeFormat4(_) => "4".to_string(),
+				MappingFormat::TrueTypeFormat6(_) => "6".to_string(),
+				MappingFormat::TrueTypeFormat10(_) => "10".to_string(),
+				MappingFormat::TrueTypeFormat12(_) => "12".to_string(),
+				MappingFormat::TrueTypeFormat13(_) => "13".to_string(),
+				MappingFormat::TrueTypeFormat14(_) => "14".to_string(),
+				MappingFormat::InvalidFormat(format) => format!("Invalid: {format}"),
 			}
 		);
 	}
@@ -0,0 +1,204 @@
+use std::io::{self, Read, Seek};
+
+use crate::ttf_reader::{FromTTFReader, TableTag, TrueTypeFontReader, TrueTypeFontReaderError};
+
+pub const FAMILY_NAME_ID: u16 = 1;
+pub const SUBFAMILY_NAME_ID: u16 = 2;
+pub const FULL_NAME_ID: u16 = 4;
+pub const POSTSCRIPT_NAME_ID: u16 = 6;
+pub const TYPOGRAPHIC_FAMILY_NAME_ID: u16 = 16;
+pub const TYPOGRAPHIC_SUBFAMILY_NAME_ID: u16 = 17;
+
+const FIRST_LANGUAGE_TAG_ID: u16 = 0x8000; // language ids from here on index the language tags of a format 1 table
+
+#[derive(Debug, Clone)]
+pub struct NameRecord {
+	pub platform_id: u16,
+	pub encoding_id: u16,
+	pub language_id: u16,
+	pub name_id: u16,
+	pub string: Vec<u8>,
+}
+
+pub struct NamingTable {
+	pub name_records: Vec<NameRecord>,
+	pub language_tags: Vec<String>, // BCP 47 tags, only given by format 1 tables
+}
+
+/// The characters for bytes 0x80 to 0xFF in Mac Roman, below which it matches ASCII.
+const MAC_ROMAN: [char; 128] = [
+	'\u{00C4}', '\u{00C5}', '\u{00C7}', '\u{00C9}', '\u{00D1}', '\u{00D6}', '\u{00DC}', '\u{00E1}',
+	'\u{00E0}', '\u{00E2}', '\u{00E4}', '\u{00E3}', '\u{00E5}', '\u{00E7}', '\u{00E9}', '\u{00E8}',
+	'\u{00EA}', '\u{00EB}', '\u{00ED}', '\u{00EC}', '\u{00EE}', '\u{00EF}', '\u{00F1}', '\u{00F3}',
+	'\u{00F2}', '\u{00F4}', '\u{00F6}', '\u{00F5}', '\u{00FA}', '\u{00F9}', '\u{00FB}', '\u{00FC}',
+	'\u{2020}', '\u{00B0}', '\u{00A2}', '\u{00A3}', '\u{00A7}', '\u{2022}', '\u{00B6}', '\u{00DF}',
+	'\u{00AE}', '\u{00A9}', '\u{2122}', '\u{00B4}', '\u{00A8}', '\u{2260}', '\u{00C6}', '\u{00D8}',
+	'\u{221E}', '\u{00B1}', '\u{2264}', '\u{2265}', '\u{00A5}', '\u{00B5}', '\u{2202}', '\u{2211}',
+	'\u{220F}', '\u{03C0}', '\u{222B}', '\u{00AA}', '\u{00BA}', '\u{03A9}', '\u{00E6}', '\u{00F8}',
+	'\u{00BF}', '\u{00A1}', '\u{00AC}', '\u{221A}', '\u{0192}', '\u{2248}', '\u{2206}', '\u{00AB}',
+	'\u{00BB}', '\u{2026}', '\u{00A0}', '\u{00C0}', '\u{00C3}', '\u{00D5}', '\u{0152}', '\u{0153}',
+	'\u{2013}', '\u{2014}', '\u{201C}', '\u{201D}', '\u{2018}', '\u{2019}', '\u{00F7}', '\u{25CA}',
+	'\u{00FF}', '\u{0178}', '\u{2044}', '\u{20AC}', '\u{2039}', '\u{203A}', '\u{FB01}', '\u{FB02}',
+	'\u{2021}', '\u{00B7}', '\u{201A}', '\u{201E}', '\u{2030}', '\u{00C2}', '\u{00CA}', '\u{00C1}',
+	'\u{00CB}', '\u{00C8}', '\u{00CD}', '\u{00CE}', '\u{00CF}', '\u{00CC}', '\u{00D3}', '\u{00D4}',
+	'\u{F8FF}', '\u{00D2}', '\u{00DA}', '\u{00DB}', '\u{00D9}', '\u{0131}', '\u{02C6}', '\u{02DC}',
+	'\u{00AF}', '\u{02D8}', '\u{02D9}', '\u{02DA}', '\u{00B8}', '\u{02DD}', '\u{02DB}', '\u{02C7}',
+];
+
+/// BCP 47 tags for the Macintosh language ids 0 to 94.
+const MACINTOSH_LANGUAGES: [&str; 95] = [
+	"en", "fr", "de", "it", "nl", "sv", "es", "da", "pt", "nb", "he", "ja",
+	"ar", "fi", "el", "is", "mt", "tr", "hr", "zh-Hant", "ur", "hi", "th", "ko",
+	"lt", "pl", "hu", "et", "lv", "se", "fo", "fa", "ru", "zh-Hans", "nl-BE", "ga",
+	"sq", "ro", "cs", "sk", "sl", "yi", "sr", "mk", "bg", "uk", "be", "uz",
+	"kk", "az-Cyrl", "az-Arab", "hy", "ka", "ro-MD", "ky", "tg", "tk", "mn-Mong", "mn-Cyrl", "ps",
+	"ku", "ks", "sd", "bo", "ne", "sa", "mr", "bn", "as", "gu", "pa", "or",
+	"ml", "kn", "ta", "te", "si", "my", "km", "lo", "vi", "id", "tl", "ms",
+	"ms-Arab", "am", "ti", "om", "so", "sw", "rw", "rn", "ny", "mg", "eo",
+];
+
+/// BCP 47 tags for the Macintosh language ids 128 to 150.
+const MACINTOSH_LANGUAGES_FROM_128: [&str; 23] = [
+	"cy", "eu", "ca", "la", "qu", "gn", "ay", "tt", "ug", "dz", "jv", "su",
+	"gl", "af", "br", "iu", "gd", "gv", "ga", "to", "el-polytonic", "kl", "az-Latn",
+];
+
+/// BCP 47 tags for Windows language ids, sorted by id.
+const WINDOWS_LANGUAGES: [(u16, &str); 205] = [
+	(0x0401, "ar-SA"), (0x0402, "bg-BG"), (0x0403, "ca-ES"), (0x0404, "zh-TW"), (0x0405, "cs-CZ"), (0x0406, "da-DK"),
+	(0x0407, "de-DE"), (0x0408, "el-GR"), (0x0409, "en-US"), (0x040A, "es-ES"), (0x040B, "fi-FI"), (0x040C, "fr-FR"),
+	(0x040D, "he-IL"), (0x040E, "hu-HU"), (0x040F, "is-IS"), (0x0410, "it-IT"), (0x0411, "ja-JP"), (0x0412, "ko-KR"),
+	(0x0413, "nl-NL"), (0x0414, "nb-NO"), (0x0415, "pl-PL"), (0x0416, "pt-BR"), (0x0417, "rm-CH"), (0x0418, "ro-RO"),
+	(0x0419, "ru-RU"), (0x041A, "hr-HR"), (0x041B, "sk-SK"), (0x041C, "sq-AL"), (0x041D, "sv-SE"), (0x041E, "th-TH"),
+	(0x041F, "tr-TR"), (0x0420, "ur-PK"), (0x0421, "id-ID"), (0x0422, "uk-UA"), (0x0423, "be-BY"), (0x0424, "sl-SI"),
+	(0x0425, "et-EE"), (0x0426, "lv-LV"), (0x0427, "lt-LT"), (0x0428, "tg-Cyrl-TJ"), (0x042A, "vi-VN"), (0x042B, "hy-AM"),
+	(0x042C, "az-Latn-AZ"), (0x042D, "eu-ES"), (0x042E, "hsb-DE"), (0x042F, "mk-MK"), (0x0432, "tn-ZA"), (0x0434, "xh-ZA"),
+	(0x0435, "zu-ZA"), (0x0436, "af-ZA"), (0x0437, "ka-GE"), (0x0438, "fo-FO"), (0x0439, "hi-IN"), (0x043A, "mt-MT"),
+	(0x043B, "se-NO"), (0x043E, "ms-MY"), (0x043F, "kk-KZ"), (0x0440, "ky-KG"), (0x0441, "sw-KE"), (0x0442, "tk-TM"),
+	(0x0443, "uz-Latn-UZ"), (0x0444, "tt-RU"), (0x0445, "bn-IN"), (0x0446, "pa-IN"), (0x0447, "gu-IN"), (0x0448, "or-IN"),
+	(0x0449, "ta-IN"), (0x044A, "te-IN"), (0x044B, "kn-IN"), (0x044C, "ml-IN"), (0x044D, "as-IN"), (0x044E, "mr-IN"),
+	(0x044F, "sa-IN"), (0x0450, "mn-MN"), (0x0451, "bo-CN"), (0x0452, "cy-GB"), (0x0453, "km-KH"), (0x0454, "lo-LA"),
+	(0x0456, "gl-ES"), (0x0457, "kok-IN"), (0x045A, "syr-SY"), (0x045B, "si-LK"), (0x045D, "iu-Cans-CA"), (0x045E, "am-ET"),
+	(0x0461, "ne-NP"), (0x0462, "fy-NL"), (0x0463, "ps-AF"), (0x0464, "fil-PH"), (0x0465, "dv-MV"), (0x0468, "ha-Latn-NG"),
+	(0x046A, "yo-NG"), (0x046B, "quz-BO"), (0x046C, "nso-ZA"), (0x046D, "ba-RU"), (0x046E, "lb-LU"), (0x046F, "kl-GL"),
+	(0x0470, "ig-NG"), (0x0478, "ii-CN"), (0x047A, "arn-CL"), (0x047C, "moh-CA"), (0x047E, "br-FR"), (0x0480, "ug-CN"),
+	(0x0481, "mi-NZ"), (0x0482, "oc-FR"), (0x0483, "co-FR"), (0x0484, "gsw-FR"), (0x0485, "sah-RU"), (0x0486, "quc-Latn-GT"),
+	(0x0487, "rw-RW"), (0x0488, "wo-SN"), (0x048C, "prs-AF"), (0x0801, "ar-IQ"), (0x0804, "zh-CN"), (0x0807, "de-CH"),
+	(0x0809, "en-GB"), (0x080A, "es-MX"), (0x080C, "fr-BE"), (0x0810, "it-CH"), (0x0813, "nl-BE"), (0x0814, "nn-NO"),
+	(0x0816, "pt-PT"), (0x081A, "sr-Latn-RS"), (0x081D, "sv-FI"), (0x082C, "az-Cyrl-AZ"), (0x082E, "dsb-DE"), (0x083B, "se-SE"),
+	(0x083C, "ga-IE"), (0x083E, "ms-BN"), (0x0843, "uz-Cyrl-UZ"), (0x0845, "bn-BD"), (0x0850, "mn-Mong-CN"), (0x085D, "iu-Latn-CA"),
+	(0x085F, "tzm-Latn-DZ"), (0x086B, "quz-EC"), (0x0C01, "ar-EG"), (0x0C04, "zh-HK"), (0x0C07, "de-AT"), (0x0C09, "en-AU"),
+	(0x0C0A, "es-ES"), (0x0C0C, "fr-CA"), (0x0C1A, "sr-Cyrl-RS"), (0x0C3B, "se-FI"), (0x0C6B, "quz-PE"), (0x1001, "ar-LY"),
+	(0x1004, "zh-SG"), (0x1007, "de-LU"), (0x1009, "en-CA"), (0x100A, "es-GT"), (0x100C, "fr-CH"), (0x101A, "hr-BA"),
+	(0x103B, "smj-NO"), (0x1401, "ar-DZ"), (0x1404, "zh-MO"), (0x1407, "de-LI"), (0x1409, "en-NZ"), (0x140A, "es-CR"),
+	(0x140C, "fr-LU"), (0x141A, "bs-Latn-BA"), (0x143B, "smj-SE"), (0x1801, "ar-MA"), (0x1809, "en-IE"), (0x180A, "es-PA"),
+	(0x180C, "fr-MC"), (0x181A, "sr-Latn-BA"), (0x183B, "sma-NO"), (0x1C01, "ar-TN"), (0x1C09, "en-ZA"), (0x1C0A, "es-DO"),
+	(0x1C1A, "sr-Cyrl-BA"), (0x1C3B, "sma-SE"), (0x2001, "ar-OM"), (0x2009, "en-JM"), (0x200A, "es-VE"), (0x201A, "bs-Cyrl-BA"),
+	(0x203B, "sms-FI"), (0x2401, "ar-YE"), (0x2409, "en-029"), (0x240A, "es-CO"), (0x243B, "smn-FI"), (0x2801, "ar-SY"),
+	(0x2809, "en-BZ"), (0x280A, "es-PE"), (0x2C01, "ar-JO"), (0x2C09, "en-TT"), (0x2C0A, "es-AR"), (0x3001, "ar-LB"),
+	(0x3009, "en-ZW"), (0x300A, "es-EC"), (0x3401, "ar-KW"), (0x3409, "en-PH"), (0x340A, "es-CL"), (0x3801, "ar-AE"),
+	(0x380A, "es-UY"), (0x3C01, "ar-BH"), (0x3C0A, "es-PY"), (0x4001, "ar-QA"), (0x4009, "en-IN"), (0x400A, "es-BO"),
+	(0x4409, "en-MY"), (0x440A, "es-SV"), (0x4809, "en-SG"), (0x480A, "es-HN"), (0x4C0A, "es-NI"), (0x500A, "es-PR"),
+	(0x540A, "es-US"),
+];
+
+impl NameRecord {
+	/// The string as text, for the encodings that are used in practice: UTF-16BE for Unicode and Windows names, and Mac Roman.
+	pub fn decode(&self) -> Option<String> {
+		match (self.platform_id, self.encoding_id) {
+			(0, _) | (3, 0 | 1 | 10) => {
+				let code_units = self.string.chunks_exact(2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]));
+				char::decode_utf16(code_units).collect::<Result<String, _>>().ok()
+			},
+			(1, 0) => Some(self.string.iter().map(|&byte| match byte {
+				0x00..=0x7F => byte as char,
+				_ => MAC_ROMAN[byte as usize - 0x80],
+			}).collect()),
+			_ => None,
+		}
+	}
+}
+
+impl NamingTable {
+	/// The BCP 47 tag for a record's language, such as `en-US` or `zh-Hant`. Unicode platform names don't have a language.
+	pub fn language_tag(&self, name_record: &NameRecord) -> Option<String> {
+		if name_record.language_id >= FIRST_LANGUAGE_TAG_ID {
+			return self.language_tags.get((name_record.language_id - FIRST_LANGUAGE_TAG_ID) as usize).cloned();
+		}
+		let language_id = name_record.language_id;
+		let tag = match name_record.platform_id {
+			1 => match language_id {
+				0..=94 => MACINTOSH_LANGUAGES.get(language_id as usize).copied(),
+				_ => MACINTOSH_LANGUAGES_FROM_128.get((language_id as usize).wrapping_sub(128)).copied(),
+			},
+			3 => WINDOWS_LANGUAGES.binary_search_by_key(&language_id, |(id, _)| *id).ok().map(|index| WINDOWS_LANGUAGES[index].1),
+			_ => None,
+		};
+		tag.map(|tag| tag.to_string())
+	}
+}
+
+fn read_utf_16_string<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64, length: u16) -> Result<String, TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	let mut code_units = Vec::with_capacity(length as usize / 2);
+	for _ in 0..length / 2 {
+		code_units.push(ttf_reader.read_bytes::<u16>()?);
+	}
+	char::decode_utf16(code_units).collect::<Result<String, _>>()
+		.map_err(|_| TrueTypeFontReaderError::InvalidData(TableTag::Naming, "language tag should be valid UTF-16"))
+}
+
+impl FromTTFReader for NamingTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<NamingTable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+
+		let version: u16 = ttf_reader.read_bytes()?;
+		if version > 1 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::Naming, version, 0));
+		}
+		let count: u16 = ttf_reader.read_bytes()?;
+		let storage_offset = offset as u64 + ttf_reader.read_bytes::<u16>()? as u64;
+
+		let mut records: Vec<(NameRecord, u16, u16)> = Vec::with_capacity(count as usize); // record, length, offset into storage
+		for _ in 0..count {
+			let name_record = NameRecord {
+				platform_id: ttf_reader.read_bytes()?,
+				encoding_id: ttf_reader.read_bytes()?,
+				language_id: ttf_reader.read_bytes()?,
+				name_id: ttf_reader.read_bytes()?,
+				string: Vec::new(),
+			};
+			let length: u16 = ttf_reader.read_bytes()?;
+			let string_offset: u16 = ttf_reader.read_bytes()?;
+			records.push((name_record, length, string_offset));
+		}
+
+		let mut language_tag_records: Vec<(u16, u16)> = Vec::new();
+		if version == 1 {
+			let language_tag_count: u16 = ttf_reader.read_bytes()?;
+			for _ in 0..language_tag_count {
+				language_tag_records.push((ttf_reader.read_bytes()?, ttf_reader.read_bytes()?));
+			}
+		}
+
+		let mut name_records = Vec::with_capacity(records.len());
+		for (mut name_record, length, string_offset) in records {
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(storage_offset + string_offset as u64))?;
+			name_record.string = vec![0u8; length as usize];
+			ttf_reader.buffer_reader.read_exact(&mut name_record.string)?;
+			name_records.push(name_record);
+		}
+
+		let mut language_tags = Vec::with_capacity(language_tag_records.len());
+		for (length, string_offset) in language_tag_records {
+			language_tags.push(read_utf_16_string(ttf_reader, storage_offset + string_offset as u64, length)?);
+		}
+
+		Ok(NamingTable {
+			name_records,
+			language_tags,
+		})
+	}
+}
@@ -0,0 +1,230 @@
+//! Decoding of PNG (ISO/IEC 15948) images, as colour bitmap glyphs are stored in CBDT and sbix tables.
+
+use crate::inflate::{self, InflateError};
+
+const SIGNATURE: [u8; 8] = [137, b'P', b'N', b'G', b'\r', b'\n', 26, b'\n'];
+// Larger images are refused rather than decompressed, as glyphs are never anywhere near this big.
+const MAXIMUM_PIXELS: usize = 1 << 24;
+// Where each of the seven passes of an interlaced image starts, and how far apart its pixels are: x, y, across and down.
+const ADAM7_PASSES: [(usize, usize, usize, usize); 7] = [(0, 0, 8, 8), (4, 0, 8, 8), (0, 4, 4, 8), (2, 0, 4, 4), (0, 2, 2, 4), (1, 0, 2, 2), (0, 1, 1, 2)];
+
+#[derive(Debug, PartialEq)]
+pub enum PngError {
+	InvalidSignature,
+	UnexpectedEnd,
+	MissingHeader,
+	UnsupportedFormat(u8, u8), // colour type, bit depth
+	ImageTooLarge(usize, usize), // width, height
+	MissingPalette,
+	InvalidFilter(u8),
+	DecompressionFailed(InflateError),
+}
+
+impl From<InflateError> for PngError {
+	fn from(value: InflateError) -> Self {
+		PngError::DecompressionFailed(value)
+	}
+}
+
+/// A decoded image, in rows from the top, with RGBA pixels that aren't premultiplied.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+	pub width: usize,
+	pub height: usize,
+	pub pixels: Vec<[u8; 4]>,
+}
+
+/// The colours of an image that are see through, other than by an alpha channel.
+enum Transparency {
+	None,
+	Colour([u16; 3]), // A grey or RGB value, at the image's bit depth
+	Palette(Vec<u8>), // The alpha of each palette entry, with any past the end opaque
+}
+
+/// Decodes a PNG image of any colour type and bit depth, interlaced or not. Sixteen bit samples are cut down to eight.
+/// Chunk CRCs aren't checked, as the zlib checksum already covers the image data.
+pub fn decode(data: &[u8]) -> Result<Image, PngError> {
+	if data.get(..SIGNATURE.len()) != Some(&SIGNATURE[..]) {
+		return Err(PngError::InvalidSignature);
+	}
+	let mut header: Option<(usize, usize, u8, u8, bool)> = None; // width, height, bit depth, colour type, interlaced
+	let mut palette: Vec<[u8; 3]> = Vec::new();
+	let mut transparency = Transparency::None;
+	let mut image_data: Vec<u8> = Vec::new();
+
+	let mut position = SIGNATURE.len();
+	loop {
+		let length = read_u32(data, position)? as usize;
+		let chunk_type = data.get(position + 4..position + 8).ok_or(PngError::UnexpectedEnd)?;
+		let chunk = data.get(position + 8..position + 8 + length).ok_or(PngError::UnexpectedEnd)?;
+		position += 12 + length;
+		match chunk_type {
+			b"IHDR" => {
+				if chunk.len() < 13 {
+					return Err(PngError::UnexpectedEnd);
+				}
+				header = Some((read_u32(chunk, 0)? as usize, read_u32(chunk, 4)? as usize, chunk[8], chunk[9], chunk[12] == 1));
+			},
+			b"PLTE" => palette = chunk.chunks_exact(3).map(|entry| [entry[0], entry[1], entry[2]]).collect(),
+			b"tRNS" => transparency = match header {
+				Some((_, _, _, 3, _)) => Transparency::Palette(chunk.to_vec()),
+				Some((_, _, _, 0, _)) => {
+					let grey = read_u16(chunk, 0)?;
+					Transparency::Colour([grey; 3])
+				},
+				Some((_, _, _, 2, _)) => Transparency::Colour([read_u16(chunk, 0)?, read_u16(chunk, 2)?, read_u16(chunk, 4)?]),
+				_ => Transparency::None,
+			},
+			b"IDAT" => image_data.extend_from_slice(chunk),
+			b"IEND" => break,
+			_ => {}, // Ancillary chunks, such as gamma and text
+		}
+	}
+
+	let (width, height, bit_depth, colour_type, interlaced) = header.ok_or(PngError::MissingHeader)?;
+	let channels = match (colour_type, bit_depth) {
+		(0, 1 | 2 | 4 | 8 | 16) => 1,
+		(2, 8 | 16) => 3,
+		(3, 1 | 2 | 4 | 8) => 1,
+		(4, 8 | 16) => 2,
+		(6, 8 | 16) => 4,
+		_ => return Err(PngError::UnsupportedFormat(colour_type, bit_depth)),
+	};
+	if width.checked_mul(height).is_none_or(|pixels| pixels > MAXIMUM_PIXELS) {
+		return Err(PngError::ImageTooLarge(width, height));
+	}
+	if colour_type == 3 && palette.is_empty() {
+		return Err(PngError::MissingPalette);
+	}
+
+	let bits_per_pixel = channels * bit_depth as usize;
+	let passes: Vec<(usize, usize, usize, usize)> = match interlaced {
+		true => ADAM7_PASSES.to_vec(),
+		false => vec![(0, 0, 1, 1)],
+	};
+	// The width and height of each pass, which are empty for images too small to have pixels in them.
+	let pass_sizes: Vec<(usize, usize)> = passes.iter().map(|&(x, y, across, down)| (width.saturating_sub(x).div_ceil(across), height.saturating_sub(y).div_ceil(down))).collect();
+	let filtered_length = pass_sizes.iter().filter(|(pass_width, _)| *pass_width > 0).map(|(pass_width, pass_height)| pass_height * (1 + (pass_width * bits_per_pixel).div_ceil(8))).sum();
+	let mut filtered = inflate::zlib_decompress(&image_data, filtered_length)?;
+	if filtered.len() < filtered_length {
+		return Err(PngError::UnexpectedEnd);
+	}
+
+	let mut pixels = vec![[0, 0, 0, 0]; width * height];
+	let mut pass_start = 0;
+	for (&(x_start, y_start, across, down), &(pass_width, pass_height)) in passes.iter().zip(pass_sizes.iter()) {
+		if pass_width == 0 {
+			continue;
+		}
+		let row_length = (pass_width * bits_per_pixel).div_ceil(8);
+		let rows = &mut filtered[pass_start..pass_start + pass_height * (1 + row_length)];
+		unfilter(rows, row_length, bits_per_pixel.div_ceil(8))?;
+		for (row_index, row) in rows.chunks_exact(1 + row_length).enumerate() {
+			let row = &row[1..];
+			for column in 0..pass_width {
+				let sample = |channel: usize| read_sample(row, column * channels + channel, bit_depth);
+				let pixel = &mut pixels[(y_start + row_index * down) * width + x_start + column * across];
+				*pixel = match colour_type {
+					0 | 4 => {
+						let grey = sample(0);
+						let alpha = match (colour_type, &transparency) {
+							(4, _) => to_8_bit(sample(1), bit_depth),
+							(_, Transparency::Colour(colour)) if colour[0] == grey => 0,
+							_ => 255,
+						};
+						let grey = to_8_bit(grey, bit_depth);
+						[grey, grey, grey, alpha]
+					},
+					2 | 6 => {
+						let colour = [sample(0), sample(1), sample(2)];
+						let alpha = match (colour_type, &transparency) {
+							(6, _) => to_8_bit(sample(3), bit_depth),
+							(_, Transparency::Colour(transparent_colour)) if *transparent_colour == colour => 0,
+							_ => 255,
+						};
+						[to_8_bit(colour[0], bit_depth), to_8_bit(colour[1], bit_depth), to_8_bit(colour[2], bit_depth), alpha]
+					},
+					_ => {
+						let index = sample(0) as usize;
+						let [red, green, blue] = *palette.get(index).ok_or(PngError::MissingPalette)?;
+						let alpha = match &transparency {
+							Transparency::Palette(alphas) => alphas.get(index).copied().unwrap_or(255),
+							_ => 255,
+						};
+						[red, green, blue, alpha]
+					},
+				};
+			}
+		}
+		pass_start += pass_height * (1 + row_length);
+	}
+	Ok(Image { width, height, pixels })
+}
+
+fn read_u32(data: &[u8], position: usize) -> Result<u32, PngError> {
+	let bytes = data.get(position..position + 4).ok_or(PngError::UnexpectedEnd)?;
+	Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u16(data: &[u8], position: usize) -> Result<u16, PngError> {
+	let bytes = data.get(position..position + 2).ok_or(PngError::UnexpectedEnd)?;
+	Ok(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+/// Undoes the filter each row starts with, in place, leaving the filter bytes.
+/// Filters predict bytes from the one a pixel to the left, the one above, or both, so need how many bytes a pixel takes, rounded up.
+fn unfilter(rows: &mut [u8], row_length: usize, bytes_per_pixel: usize) -> Result<(), PngError> {
+	let stride = row_length + 1;
+	for row_start in (0..rows.len()).step_by(stride) {
+		let filter = rows[row_start];
+		for index in 1..stride {
+			let position = row_start + index;
+			let left = if index > bytes_per_pixel { rows[position - bytes_per_pixel] } else { 0 };
+			let above = if row_start > 0 { rows[position - stride] } else { 0 };
+			let above_left = if row_start > 0 && index > bytes_per_pixel { rows[position - stride - bytes_per_pixel] } else { 0 };
+			let prediction = match filter {
+				0 => 0,
+				1 => left,
+				2 => above,
+				3 => ((left as u16 + above as u16) / 2) as u8,
+				4 => paeth(left, above, above_left),
+				_ => return Err(PngError::InvalidFilter(filter)),
+			};
+			rows[position] = rows[position].wrapping_add(prediction);
+		}
+	}
+	Ok(())
+}
+
+/// Whichever of the three neighbours is closest to left + above - above left.
+fn paeth(left: u8, above: u8, above_left: u8) -> u8 {
+	let estimate = left as i16 + above as i16 - above_left as i16;
+	let (left_distance, above_distance, above_left_distance) = ((estimate - left as i16).abs(), (estimate - above as i16).abs(), (estimate - above_left as i16).abs());
+	if left_distance <= above_distance && left_distance <= above_left_distance {
+		left
+	} else if above_distance <= above_left_distance {
+		above
+	} else {
+		above_left
+	}
+}
+
+/// The sample at an index into a row, where samples smaller than a byte are packed from the most significant bit.
+fn read_sample(row: &[u8], index: usize, bit_depth: u8) -> u16 {
+	match bit_depth {
+		16 => u16::from_be_bytes([row[index * 2], row[index * 2 + 1]]),
+		8 => row[index] as u16,
+		_ => {
+			let bit = index * bit_depth as usize;
+			((row[bit / 8] >> (8 - bit_depth as usize - bit % 8)) & ((1 << bit_depth) - 1)) as u16
+		},
+	}
+}
+
+fn to_8_bit(sample: u16, bit_depth: u8) -> u8 {
+	match bit_depth {
+		16 => (sample >> 8) as u8,
+		8 => sample as u8,
+		_ => (sample as u32 * 255 / ((1 << bit_depth) - 1)) as u8,
+	}
+}
@@ -0,0 +1,122 @@
+use std::io::{self, Read, Seek};
+
+use crate::ttf_reader::{FromTTFReader, TableTag, TrueTypeFontReader, TrueTypeFontReaderError};
+
+const NUMBER_OF_MACINTOSH_GLYPH_NAMES: u16 = 258;
+
+/// The names of the glyphs in the standard Macintosh character set, which version 1 tables use as they are and version 2 tables refer to by index.
+pub const MACINTOSH_GLYPH_NAMES: [&str; NUMBER_OF_MACINTOSH_GLYPH_NAMES as usize] = [
+	".notdef", ".null", "nonmarkingreturn", "space", "exclam", "quotedbl", "numbersign", "dollar", "percent", "ampersand",
+	"quotesingle", "parenleft", "parenright", "asterisk", "plus", "comma", "hyphen", "period", "slash", "zero", "one",
+	"two", "three", "four", "five", "six", "seven", "eight", "nine", "colon", "semicolon", "less", "equal", "greater",
+	"question", "at", "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R", "S", "T",
+	"U", "V", "W", "X", "Y", "Z", "bracketleft", "backslash", "bracketright", "asciicircum", "underscore", "grave", "a",
+	"b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s", "t", "u", "v", "w", "x",
+	"y", "z", "braceleft", "bar", "braceright", "asciitilde", "Adieresis", "Aring", "Ccedilla", "Eacute", "Ntilde",
+	"Odieresis", "Udieresis", "aacute", "agrave", "acircumflex", "adieresis", "atilde", "aring", "ccedilla", "eacute",
+	"egrave", "ecircumflex", "edieresis", "iacute", "igrave", "icircumflex", "idieresis", "ntilde", "oacute", "ograve",
+	"ocircumflex", "odieresis", "otilde", "uacute", "ugrave", "ucircumflex", "udieresis", "dagger", "degree", "cent",
+	"sterling", "section", "bullet", "paragraph", "germandbls", "registered", "copyright", "trademark", "acute",
+	"dieresis", "notequal", "AE", "Oslash", "infinity", "plusminus", "lessequal", "greaterequal", "yen", "mu",
+	"partialdiff", "summation", "product", "pi", "integral", "ordfeminine", "ordmasculine", "Omega", "ae", "oslash",
+	"questiondown", "exclamdown", "logicalnot", "radical", "florin", "approxequal", "Delta", "guillemotleft",
+	"guillemotright", "ellipsis", "nonbreakingspace", "Agrave", "Atilde", "Otilde", "OE", "oe", "endash", "emdash",
+	"quotedblleft", "quotedblright", "quoteleft", "quoteright", "divide", "lozenge", "ydieresis", "Ydieresis", "fraction",
+	"currency", "guilsinglleft", "guilsinglright", "fi", "fl", "daggerdbl", "periodcentered", "quotesinglbase",
+	"quotedblbase", "perthousand", "Acircumflex", "Ecircumflex", "Aacute", "Edieresis", "Egrave", "Iacute", "Icircumflex",
+	"Idieresis", "Igrave", "Oacute", "Ocircumflex", "apple", "Ograve", "Uacute", "Ucircumflex", "Ugrave", "dotlessi",
+	"circumflex", "tilde", "macron", "breve", "dotaccent", "ring", "cedilla", "hungarumlaut", "ogonek", "caron", "Lslash",
+	"lslash", "Scaron", "scaron", "Zcaron", "zcaron", "brokenbar", "Eth", "eth", "Yacute", "yacute", "Thorn", "thorn",
+	"minus", "multiply", "onesuperior", "twosuperior", "threesuperior", "onehalf", "onequarter", "threequarters", "franc",
+	"Gbreve", "gbreve", "Idotaccent", "Scedilla", "scedilla", "Cacute", "cacute", "Ccaron", "ccaron", "dcroat",
+];
+
+pub struct PostScriptTable {
+	pub italic_angle: f32, // Degrees counter-clockwise from vertical, so negative for fonts that lean forwards
+	pub underline_position: i16,
+	pub underline_thickness: i16,
+	pub is_fixed_pitch: bool,
+	pub glyph_names: Vec<String>, // One per glyph, or empty for version 3 and 4 tables which don't name their glyphs
+}
+
+impl FromTTFReader for PostScriptTable {
+	type Input = (u32, u32); // offset, length
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (offset, length): (u32, u32)) -> Result<PostScriptTable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+
+		let major_version: u16 = ttf_reader.read_bytes()?;
+		let minor_version: u16 = ttf_reader.read_bytes()?;
+		let italic_angle = ttf_reader.read_bytes::<u32>()? as i32 as f32 / 65536.0;
+		let underline_position: i16 = ttf_reader.read_bytes()?;
+		let underline_thickness: i16 = ttf_reader.read_bytes()?;
+		let is_fixed_pitch = ttf_reader.read_bytes::<u32>()? != 0;
+		ttf_reader.skip(16)?; // memory usage hints for PostScript printers
+
+		let glyph_names = match (major_version, minor_version) {
+			(1, 0) => MACINTOSH_GLYPH_NAMES.iter().map(|name| name.to_string()).collect(),
+			(2, 0) => {
+				let number_of_glyphs: u16 = ttf_reader.read_bytes()?;
+				let mut glyph_name_indices: Vec<u16> = Vec::with_capacity(number_of_glyphs as usize);
+				for _ in 0..number_of_glyphs {
+					glyph_name_indices.push(ttf_reader.read_bytes()?);
+				}
+
+				// The names not in the Macintosh set follow as Pascal strings, up to the end of the table.
+				let names_start = 34 + 2 * number_of_glyphs as u32;
+				let mut names_data = vec![0u8; length.saturating_sub(names_start) as usize];
+				ttf_reader.buffer_reader.read_exact(&mut names_data)?;
+				let mut names: Vec<String> = Vec::new();
+				let mut position = 0;
+				while position < names_data.len() {
+					let name_length = names_data[position] as usize;
+					let Some(name) = names_data.get(position + 1..position + 1 + name_length) else {
+						return Err(TrueTypeFontReaderError::InvalidData(TableTag::PostScript, "glyph name shouldn't run past the end of the table"));
+					};
+					names.push(name.iter().map(|&byte| byte as char).collect());
+					position += 1 + name_length;
+				}
+
+				let mut glyph_names = Vec::with_capacity(glyph_name_indices.len());
+				for glyph_name_index in glyph_name_indices {
+					let name = match glyph_name_index.checked_sub(NUMBER_OF_MACINTOSH_GLYPH_NAMES) {
+						None => MACINTOSH_GLYPH_NAMES[glyph_name_index as usize].to_string(),
+						Some(index) => match names.get(index as usize) {
+							Some(name) => name.clone(),
+							None => return Err(TrueTypeFontReaderError::InvalidData(TableTag::PostScript, "glyph name index should refer to a name in the table")),
+						},
+					};
+					glyph_names.push(name);
+				}
+				glyph_names
+			},
+			(2, 0x5000) => {
+				// Deprecated, each glyph is named by its offset into the Macintosh set. Offsets outside it leave the glyphs unnamed.
+				let number_of_glyphs: u16 = ttf_reader.read_bytes()?;
+				let mut glyph_names = Vec::with_capacity(number_of_glyphs as usize);
+				for glyph_index in 0..number_of_glyphs {
+					let offset: i8 = ttf_reader.read_bytes()?;
+					match usize::try_from(glyph_index as i32 + offset as i32).ok().and_then(|index| MACINTOSH_GLYPH_NAMES.get(index)) {
+						Some(name) => glyph_names.push(name.to_string()),
+						None => break,
+					}
+				}
+				if glyph_names.len() < number_of_glyphs as usize {
+					glyph_names.clear();
+				}
+				glyph_names
+			},
+			// Apple's version 4 maps glyphs to character codes rather than names.
+			(3, 0) | (4, 0) => Vec::new(),
+			_ => return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::PostScript, major_version, minor_version)),
+		};
+
+		Ok(PostScriptTable {
+			italic_angle,
+			underline_position,
+			underline_thickness,
+			is_fixed_pitch,
+			glyph_names,
+		})
+	}
+}
@@ -1,13 +1,32 @@
 struct VertexInput {
 	@location(0) position: vec2<f32>,
-	@location(1) uv_coordinates: vec2<f32>,
+	@location(1) uv_coordinates: vec3<f32>,
 	@location(2) colour: vec3<f32>,
+	@location(3) paint_coordinates: vec2<f32>,
+	@location(4) paint_index: u32,
 };
 
 struct VertexOutput {
 	@builtin(position) clip_position: vec4<f32>,
-	@location(0) uv_coordinates: vec2<f32>,
+	@location(0) uv_coordinates: vec3<f32>,
 	@location(1) colour: vec3<f32>,
+	@location(2) paint_coordinates: vec2<f32>,
+	@location(3) @interpolate(flat) paint_index: u32,
+};
+
+// A colour glyph's brush. Kind 0 is a solid colour, kept as its only stop, then 1, 2 and 3 are linear, radial and sweep gradients.
+struct Paint {
+	kind: u32,
+	extend: u32,
+	first_stop: u32,
+	stop_count: u32,
+	geometry: vec4<f32>, // Start and end points, both centres, or the centre then the start and end angles
+	more_geometry: vec4<f32>, // The two radii of radial gradients
+};
+
+struct GradientStop {
+	colour: vec4<f32>, // Premultiplied
+	offset: f32,
 };
 
 @vertex
@@ -19,29 +38,144 @@ fn vs_main(
 	out.clip_position = vec4<f32>(position, 0.0, 1.0);
 	out.uv_coordinates = model.uv_coordinates;
 	out.colour = model.colour;
+	out.paint_coordinates = model.paint_coordinates;
+	out.paint_index = model.paint_index;
 	return out;
 }
 
 @group(0) @binding(0)
 var<uniform> mode: u32;
 
+@group(1) @binding(0)
+var<storage, read> paints: array<Paint>;
+
+@group(1) @binding(1)
+var<storage, read> gradient_stops: array<GradientStop>;
+
+const PI: f32 = 3.14159265;
+
+// The colour at a position along a gradient, with the stretch from its first stop to its last padded, repeated or reflected beyond them.
+fn gradient_colour(paint: Paint, position: f32) -> vec4<f32> {
+	let first = gradient_stops[paint.first_stop];
+	let last = gradient_stops[paint.first_stop + paint.stop_count - 1];
+	let span = last.offset - first.offset;
+	var t = position;
+	if span > 0.0 {
+		if paint.extend == 1 {
+			t = first.offset + (t - first.offset) - floor((t - first.offset) / span) * span;
+		} else if paint.extend == 2 {
+			let position_in_pair = (t - first.offset) - floor((t - first.offset) / (2.0 * span)) * 2.0 * span;
+			t = first.offset + select(position_in_pair, 2.0 * span - position_in_pair, position_in_pair > span);
+		}
+	}
+	if t <= first.offset {
+		return first.colour;
+	}
+	for (var index = paint.first_stop + 1; index < paint.first_stop + paint.stop_count; index++) {
+		let start = gradient_stops[index - 1];
+		let end = gradient_stops[index];
+		if t < end.offset {
+			return mix(start.colour, end.colour, (t - start.offset) / (end.offset - start.offset));
+		}
+	}
+	return last.colour;
+}
+
+// The premultiplied colour of a paint at a point in its own coordinates.
+fn paint_colour(paint: Paint, point: vec2<f32>) -> vec4<f32> {
+	if paint.kind == 1 {
+		let start = paint.geometry.xy;
+		let direction = paint.geometry.zw - start;
+		let length_squared = dot(direction, direction);
+		if length_squared == 0.0 {
+			return gradient_colour(paint, 0.0);
+		}
+		return gradient_colour(paint, dot(point - start, direction) / length_squared);
+	} else if paint.kind == 2 {
+		// The largest t where the circle interpolated between the two, with a radius that isn't negative, passes through the point.
+		let start_radius = paint.more_geometry.x;
+		let centre = paint.geometry.zw - paint.geometry.xy;
+		let radius = paint.more_geometry.y - start_radius;
+		let relative_point = point - paint.geometry.xy;
+		let a = dot(centre, centre) - radius * radius;
+		let b = dot(relative_point, centre) + start_radius * radius;
+		let c = dot(relative_point, relative_point) - start_radius * start_radius;
+		if abs(a) < 1e-6 {
+			if b == 0.0 {
+				return vec4<f32>(0.0);
+			}
+			let t = c / (2.0 * b);
+			if start_radius + t * radius < 0.0 {
+				return vec4<f32>(0.0);
+			}
+			return gradient_colour(paint, t);
+		}
+		let discriminant = b * b - a * c;
+		if discriminant < 0.0 {
+			return vec4<f32>(0.0);
+		}
+		let first = (b + sqrt(discriminant)) / a;
+		let second = (b - sqrt(discriminant)) / a;
+		let larger = max(first, second);
+		let smaller = min(first, second);
+		if start_radius + larger * radius >= 0.0 {
+			return gradient_colour(paint, larger);
+		} else if start_radius + smaller * radius >= 0.0 {
+			return gradient_colour(paint, smaller);
+		}
+		return vec4<f32>(0.0);
+	} else if paint.kind == 3 {
+		let relative_point = point - paint.geometry.xy;
+		var angle = atan2(relative_point.y, relative_point.x);
+		if angle < 0.0 {
+			angle += 2.0 * PI;
+		}
+		let sweep = paint.geometry.w - paint.geometry.z;
+		if abs(sweep) < 1e-6 {
+			return gradient_colour(paint, select(-1e30, 1e30, angle >= paint.geometry.z));
+		}
+		return gradient_colour(paint, (angle - paint.geometry.z) / sweep);
+	}
+	return gradient_stops[paint.first_stop].colour;
+}
+
+// The colour a covered fragment is filled with: the vertex colour, or its paint, which is premultiplied and so is divided back out for blending.
+fn fill_colour(in: VertexOutput) -> vec4<f32> {
+	if in.paint_index == 0 {
+		return vec4<f32>(in.colour, 1.0);
+	}
+	let colour = paint_colour(paints[in.paint_index], in.paint_coordinates);
+	if colour.a <= 0.0 {
+		return vec4<f32>(0, 0, 0, 0);
+	}
+	return vec4<f32>(colour.rgb / colour.a, colour.a);
+}
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
 	if mode == 0 {
-		return vec4<f32>(in.colour, 1.0);
+		return fill_colour(in);
 	} else if mode == 1 {
 		if pow(in.uv_coordinates[0], 2) <= in.uv_coordinates[1] {
-			return vec4<f32>(in.colour, 1.0);
+			return fill_colour(in);
 		} else {
 			return vec4<f32>(0, 0, 0, 0);
 		}
 	} else if mode == 2 {
 		if pow(in.uv_coordinates[0], 2) >= in.uv_coordinates[1] {
-			return vec4<f32>(in.colour, 1.0);
+			return fill_colour(in);
+		} else {
+			return vec4<f32>(0, 0, 0, 0);
+		}
+	} else if mode == 3 {
+		// Cubic curves use the k, l, m coordinates from the Loop-Blinn classification, filled where k³ - lm ≤ 0.
+		let klm = in.uv_coordinates;
+		if klm[0] * klm[0] * klm[0] - klm[1] * klm[2] <= 0 {
+			return fill_colour(in);
 		} else {
 			return vec4<f32>(0, 0, 0, 0);
 		}
 	} else {
 		return vec4<f32>(1, 0, 0, 1.0);
 	}
-}
\ No newline at end of file
+}
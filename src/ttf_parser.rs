@@ -1,14 +1,16 @@
+use std::borrow::Cow;
+
 use mircalla_types::vectors::Position;
 
-use crate::{font::{self, Bounds, FontUnits, GlyphParseError, ToTriangles, Vertex}, ttf_reader::{ComponentGlyphRaw, CompositeGlyphRaw, GlyphDataRaw, GlyphRaw, SimpleGlyphRaw}};
+use crate::{cff_reader::{CharStringError, CharStringOutline, PathSegment}, colour_reader::Affine, font::{self, Bounds, FontUnits, GlyphParseError, ToTriangles, Vertex}, ttf_reader::{ComponentGlyphRaw, CompositeGlyphRaw, GlyphDataRaw, GlyphRaw, SimpleGlyphRaw, ARGS_ARE_XY_VALUES}};
 
 impl From<GlyphIntermediate> for font::Glyph {
 	fn from(value: GlyphIntermediate) -> Self {
 		match value.glyph_data {
 			GlyphDataIntermediate::SimpleGlyph(glyph_data) => {
 				match glyph_data.to_triangles(false) {
-					Ok((vertices, indices, convex_bezier_indices, concave_bezier_indices)) => {
-						font::Glyph::new_simple(vertices, indices, convex_bezier_indices, concave_bezier_indices, value.bounds)
+					Ok((vertices, indices, convex_bezier_indices, concave_bezier_indices, cubic_bezier_indices)) => {
+						font::Glyph::new_simple(vertices, indices, convex_bezier_indices, concave_bezier_indices, cubic_bezier_indices, value.bounds)
 					},
 					Err(error) => {
 						println!("ERROR ==> A GLYPH HAS FAILED TO PARSE");
@@ -21,6 +23,7 @@ impl From<GlyphIntermediate> for font::Glyph {
 				let children: Vec<font::ComponentGlyph> = glyph_data.children.into_iter().map(|v| v.into()).collect();
 				font::Glyph::new_composite(children, value.bounds)
 			},
+			GlyphDataIntermediate::FailedParse(error) => font::Glyph::new_failed_parse(error, value.bounds),
 			GlyphDataIntermediate::None => { font::Glyph::new_empty(value.bounds)},
 		}
 	}
@@ -28,10 +31,10 @@ impl From<GlyphIntermediate> for font::Glyph {
 
 impl From<GlyphComponentIntermediate> for font::ComponentGlyph {
 	fn from(value: GlyphComponentIntermediate) -> Self {
-		assert!(value.transformation_matrix.is_identity());
 		font::ComponentGlyph {
 			child_index: value.glyph_index as usize,
 			offset: value.offset.into(),
+			transform: value.transformation_matrix.into(),
 		}
 	}
 }
@@ -142,7 +145,7 @@ impl IntersectionPoint<Vertex> for (&Vertex, &Vertex) {
 		let x = x.round() as i16;
 		let y = y.round() as i16;
 
-		Vertex { x: x.into(), y: y.into(), on_curve: true, uv_coords: [0.0, 0.0], }
+		Vertex { x: x.into(), y: y.into(), on_curve: true, cubic: false, uv_coords: [0.0, 0.0, 0.0], }
 	}
 }
 
@@ -296,7 +299,7 @@ impl Contour {
 }
 
 impl ToTriangles for GlyhpSimpleIntermediate {
-	fn to_triangles(self, debug_mode: bool) -> Result<(Vec<Vertex>, Vec<u32>, Vec<u32>, Vec<u32>), GlyphParseError> {
+	fn to_triangles(self, debug_mode: bool) -> Result<(Vec<Vertex>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u32>), GlyphParseError> {
 		let mut vertices: Vec<Vertex> = self.points.iter().map(|v| v.into()).collect();
 		//println!("virtices initially created ({})", vertices.len());
 
@@ -460,7 +463,9 @@ impl ToTriangles for GlyhpSimpleIntermediate {
 						let previous_index = contour.indices.previous(contour_indices_position).unwrap();
 						let vertex = &vertices[index];
 						let previous_vertex = &vertices[previous_index];
-						if ( !vertex.on_curve && !previous_vertex.on_curve ) {
+						// Cubic off curve points come in pairs, so only the second point of a pair is followed by an implied on curve point.
+						let first_of_cubic_pair = vertex.cubic && previous_vertex.cubic && contour.preceding_cubic_points(contour_indices_position, &vertices) % 2 == 1;
+						if ( !vertex.on_curve && !previous_vertex.on_curve && !first_of_cubic_pair ) {
 							let extra_vertex = Vertex::new((vertex.x + previous_vertex.x).value / 2, (vertex.y + previous_vertex.y).value / 2);
 							let extra_vertex_index = vertices.len();
 							vertices.push(extra_vertex);
@@ -487,6 +492,60 @@ impl ToTriangles for GlyhpSimpleIntermediate {
 			println!("\n\n");
 		}
 
+		// --- Split Cubic Curves and Add Cubic Bezier Triangles
+
+		let mut cubic_bezier_indices: Vec<u32> = Vec::new();
+		for contour in pre_processed_contours.iter_mut() {
+			let mut present_indices: Vec<usize> = contour.indices.iter().flatten().copied().collect();
+			let start_position = match present_indices.iter().position(|index| !vertices[*index].cubic) {
+				Some(start_position) => start_position,
+				None => continue,
+			};
+			if !present_indices.iter().any(|index| vertices[*index].cubic) {
+				continue;
+			}
+			present_indices.rotate_left(start_position);
+			let contour_edges: Vec<(usize, usize)> = (0..present_indices.len()).map(|position| (present_indices[position], present_indices[(position + 1) % present_indices.len()])).collect();
+
+			let mut new_indices: Vec<Option<usize>> = Vec::with_capacity(present_indices.len());
+			let mut position = 0;
+			while position < present_indices.len() {
+				let index = present_indices[position];
+				let next_index = present_indices.get(position + 1).copied();
+				match next_index {
+					Some(next_index) if vertices[index].cubic && vertices[next_index].cubic => {
+						let start_index = new_indices.last().copied().flatten().unwrap_or(present_indices[0]);
+						let end_index = present_indices[(position + 2) % present_indices.len()];
+						let (first_control, second_control) = (vertices[index].position(), vertices[next_index].position());
+						CubicContourBuilder {
+							vertices: &mut vertices,
+							contour_edges: &contour_edges,
+							contour_indices: &mut new_indices,
+							cubic_bezier_indices: &mut cubic_bezier_indices,
+						}.add_curve(start_index, first_control, second_control, end_index);
+						position += 3;
+						if debug_mode {
+							println!("Cubic Curve with control vertices ({index}, {next_index}) Added");
+						}
+					},
+					_ => {
+						if vertices[index].cubic {
+							// A lone cubic off curve point can only be drawn as a quadratic curve.
+							vertices[index].cubic = false;
+						}
+						new_indices.push(Some(index));
+						position += 1;
+					},
+				}
+			}
+			// The last curve may have ended back at the first point.
+			if new_indices.len() > 1 && new_indices.last() == new_indices.first() {
+				new_indices.pop();
+			}
+			contour.indices = new_indices;
+			contour.indices_removed = 0;
+		}
+
 		// --- Remove Off Curve Convex Points and Add Bezier Triangles
 
 		let mut convex_bezier_indices: Vec<u32> = Vec::new();
@@ -499,15 +558,15 @@ impl ToTriangles for GlyhpSimpleIntermediate {
 				let index = contour.indices[contour_indices_position];
 				match index {
 					Some(index) => {
-						let vertex = (&vertices[index]).with_changed_uv_coord([0.5, 0.0]);
+						let vertex = (&vertices[index]).with_changed_uv_coord([0.5, 0.0, 0.0]);
 						if vertex.on_curve {
 							continue;
 						}
 
 						let previous_index = contour.indices.previous(contour_indices_position).unwrap();
 						let next_index = contour.indices.next(contour_indices_position).unwrap();
-						let previous_vertex = (&vertices[previous_index]).with_changed_uv_coord([1.0, 1.0]);
-						let next_vertex = (&vertices[next_index]).with_changed_uv_coord([0.0, 0.0]);
+						let previous_vertex = (&vertices[previous_index]).with_changed_uv_coord([1.0, 1.0, 0.0]);
+						let next_vertex = (&vertices[next_index]).with_changed_uv_coord([0.0, 0.0, 0.0]);
 						if (&previous_vertex, &vertex).to_right_of(&next_vertex, true) {
 
 							let mut intersects = false;
@@ -530,7 +589,7 @@ impl ToTriangles for GlyhpSimpleIntermediate {
 								let new_vertex_x = new_vertex_x.round() as i16;
 								let new_vertex_y = new_vertex_y.round() as i16;
 
-								let new_vertex = Vertex { x: new_vertex_x.into(), y: new_vertex_y.into(), on_curve: true, uv_coords: [0.0, 0.0]};
+								let new_vertex = Vertex { x: new_vertex_x.into(), y: new_vertex_y.into(), on_curve: true, cubic: false, uv_coords: [0.0, 0.0, 0.0]};
 								vertices.push(new_vertex);
 								if debug_mode {
 									println!("Vertex {new_index} added so that contour fits line better");
@@ -977,7 +1036,332 @@ impl ToTriangles for GlyhpSimpleIntermediate {
 			}
 		}
 
-		Ok((vertices, indices, convex_bezier_indices, concave_bezier_indices))
+		Ok((vertices, indices, convex_bezier_indices, concave_bezier_indices, cubic_bezier_indices))
+	}
+}
+
+/// Flag bit marking an off curve point as a cubic control point, as used by `glyf` cubic outlines.
+pub const CUBIC_FLAG: u8 = 0x80;
+
+const MAXIMUM_CUBIC_SUBDIVISIONS: usize = 4;
+// Curves whose normalised inflection values are within this of a lower degree are treated as that degree.
+const DEGREE_EPSILON: f64 = 1e-3;
+const DISCRIMINANT_EPSILON: f64 = 1e-9;
+
+impl Contour {
+	fn preceding_cubic_points(&self, contour_indices_position: usize, vertices: &[Vertex]) -> usize {
+		let length = self.indices.len();
+		let mut count = 0;
+		let mut position = contour_indices_position;
+		for _ in 1..length {
+			position = (position as i64 - 1).rem_euclid(length as i64) as usize;
+			match self.indices[position] {
+				Some(index) if vertices[index].cubic => count += 1,
+				Some(_) => break,
+				None => {},
+			}
+		}
+		count
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CubicCurveType {
+	Serpentine,
+	Loop,
+	Cusp,
+	Quadratic,
+	Line,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CubicCurve {
+	pub points: [(f64, f64); 4], // start, first control, second control, end
+}
+
+impl CubicCurve {
+	pub fn new(start: (f64, f64), first_control: (f64, f64), second_control: (f64, f64), end: (f64, f64)) -> Self {
+		Self { points: [start, first_control, second_control, end] }
+	}
+
+	pub fn point_at(&self, t: f64) -> (f64, f64) {
+		let [p0, p1, p2, p3] = self.points;
+		let s = 1.0 - t;
+		let weights = [s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t];
+		(
+			weights[0] * p0.0 + weights[1] * p1.0 + weights[2] * p2.0 + weights[3] * p3.0,
+			weights[0] * p0.1 + weights[1] * p1.1 + weights[2] * p2.1 + weights[3] * p3.1,
+		)
+	}
+
+	/// Splits the curve at `t` with de Casteljau's algorithm.
+	pub fn split(&self, t: f64) -> (CubicCurve, CubicCurve) {
+		let lerp = |a: (f64, f64), b: (f64, f64)| (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+		let [p0, p1, p2, p3] = self.points;
+		let p01 = lerp(p0, p1);
+		let p12 = lerp(p1, p2);
+		let p23 = lerp(p2, p3);
+		let p012 = lerp(p01, p12);
+		let p123 = lerp(p12, p23);
+		let middle = lerp(p012, p123);
+		(CubicCurve::new(p0, p01, p012, middle), CubicCurve::new(middle, p123, p23, p3))
+	}
+
+	/// The d₁, d₂, d₃ values from Loop and Blinn, normalised so the classification doesn't depend on the size of the curve.
+	fn inflection_values(&self) -> [f64; 3] {
+		let determinant = |a: (f64, f64), b: (f64, f64), c: (f64, f64)| a.0 * (b.1 - c.1) - a.1 * (b.0 - c.0) + (b.0 * c.1 - b.1 * c.0);
+		let [b0, b1, b2, b3] = self.points;
+		let a1 = determinant(b0, b3, b2);
+		let a2 = determinant(b1, b0, b3);
+		let a3 = determinant(b2, b1, b0);
+		let d = [a1 - 2.0 * a2 + 3.0 * a3, -a2 + 3.0 * a3, 3.0 * a3];
+		let length = (d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt();
+		if length < f64::EPSILON {
+			[0.0, 0.0, 0.0]
+		} else {
+			[d[0] / length, d[1] / length, d[2] / length]
+		}
+	}
+
+	pub fn classify(&self) -> CubicCurveType {
+		let [d1, d2, d3] = self.inflection_values();
+		if d1.abs() < DEGREE_EPSILON && d2.abs() < DEGREE_EPSILON {
+			if d3.abs() < DEGREE_EPSILON {
+				return CubicCurveType::Line;
+			}
+			return CubicCurveType::Quadratic;
+		}
+		let discriminant = d1 * d1 * (3.0 * d2 * d2 - 4.0 * d1 * d3);
+		if discriminant > DISCRIMINANT_EPSILON {
+			CubicCurveType::Serpentine
+		} else if discriminant < -DISCRIMINANT_EPSILON {
+			CubicCurveType::Loop
+		} else {
+			CubicCurveType::Cusp
+		}
+	}
+
+	/// Parameters of the inflection points, cusp or loop double point strictly inside the curve, where it should be split.
+	pub fn split_parameters(&self) -> Vec<f64> {
+		let [d1, d2, d3] = self.inflection_values();
+		let mut parameters: Vec<f64> = match self.classify() {
+			CubicCurveType::Serpentine => {
+				let root = (9.0 * d2 * d2 - 12.0 * d1 * d3).max(0.0).sqrt();
+				vec![(3.0 * d2 - root) / (6.0 * d1), (3.0 * d2 + root) / (6.0 * d1)]
+			},
+			CubicCurveType::Loop => {
+				let root = (4.0 * d1 * d3 - 3.0 * d2 * d2).max(0.0).sqrt();
+				vec![(d2 - root) / (2.0 * d1), (d2 + root) / (2.0 * d1)]
+			},
+			_ => Vec::new(),
+		};
+		parameters.retain(|t| t.is_finite() && *t > 1e-3 && *t < 1.0 - 1e-3);
+		parameters.sort_by(|a, b| a.total_cmp(b));
+		parameters.dedup_by(|a, b| (*a - *b).abs() < 1e-3);
+		parameters
+	}
+
+	/// The k, l, m coordinates of each control point, oriented so that k³ - lm ≤ 0 on the right of the curve, which is the filled side of a glyph contour.
+	pub fn klm_coordinates(&self) -> Option<[[f32; 3]; 4]> {
+		let [d1, d2, d3] = self.inflection_values();
+		let klm: [[f64; 3]; 4] = match self.classify() {
+			CubicCurveType::Line => return None,
+			CubicCurveType::Quadratic => [
+				[0.0, 0.0, 0.0],
+				[1.0 / 3.0, 0.0, 1.0 / 3.0],
+				[2.0 / 3.0, 1.0 / 3.0, 2.0 / 3.0],
+				[1.0, 1.0, 1.0],
+			],
+			CubicCurveType::Cusp if d1.abs() < DEGREE_EPSILON => {
+				// Cusp at infinity
+				let (ls, lt) = (d3, 3.0 * d2);
+				[
+					[ls, ls * ls * ls, 1.0],
+					[ls - lt / 3.0, ls * ls * (ls - lt), 1.0],
+					[ls - 2.0 * lt / 3.0, (ls - lt) * (ls - lt) * ls, 1.0],
+					[ls - lt, (ls - lt) * (ls - lt) * (ls - lt), 1.0],
+				]
+			},
+			CubicCurveType::Serpentine | CubicCurveType::Cusp => {
+				let root = (9.0 * d2 * d2 - 12.0 * d1 * d3).max(0.0).sqrt();
+				let (ls, lt) = (3.0 * d2 - root, 6.0 * d1);
+				let (ms, mt) = (3.0 * d2 + root, 6.0 * d1);
+				[
+					[ls * ms, ls * ls * ls, ms * ms * ms],
+					[(3.0 * ls * ms - ls * mt - lt * ms) / 3.0, ls * ls * (ls - lt), ms * ms * (ms - mt)],
+					[(lt * (mt - 2.0 * ms) + ls * (3.0 * ms - 2.0 * mt)) / 3.0, (lt - ls) * (lt - ls) * ls, (mt - ms) * (mt - ms) * ms],
+					[(lt - ls) * (mt - ms), -(lt - ls) * (lt - ls) * (lt - ls), -(mt - ms) * (mt - ms) * (mt - ms)],
+				]
+			},
+			CubicCurveType::Loop => {
+				let root = (4.0 * d1 * d3 - 3.0 * d2 * d2).max(0.0).sqrt();
+				let (ls, lt) = (d2 - root, 2.0 * d1);
+				let (ms, mt) = (d2 + root, 2.0 * d1);
+				[
+					[ls * ms, ls * ls * ms, ls * ms * ms],
+					[(-ls * mt - lt * ms + 3.0 * ls * ms) / 3.0, -ls * (ls * (mt - 3.0 * ms) + 2.0 * lt * ms) / 3.0, -ms * (ls * (2.0 * mt - 3.0 * ms) + lt * ms) / 3.0],
+					[(lt * (mt - 2.0 * ms) + ls * (3.0 * ms - 2.0 * mt)) / 3.0, (lt - ls) * (ls * (2.0 * mt - 3.0 * ms) + lt * ms) / 3.0, (mt - ms) * (ls * (mt - 3.0 * ms) + 2.0 * lt * ms) / 3.0],
+					[(lt - ls) * (mt - ms), -(lt - ls) * (lt - ls) * (mt - ms), -(lt - ls) * (mt - ms) * (mt - ms)],
+				]
+			},
+		};
+
+		// k, l and m are linear over the plane, so they can be found anywhere from the three control points spanning the largest triangle.
+		let area = |a: (f64, f64), b: (f64, f64), c: (f64, f64)| (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+		let triangles = [[0, 1, 2], [0, 1, 3], [0, 2, 3], [1, 2, 3]];
+		let [a, b, c] = *triangles.iter().max_by(|first, second| {
+			area(self.points[first[0]], self.points[first[1]], self.points[first[2]]).abs().total_cmp(&area(self.points[second[0]], self.points[second[1]], self.points[second[2]]).abs())
+		}).unwrap();
+		let total_area = area(self.points[a], self.points[b], self.points[c]);
+		if total_area.abs() < 1e-6 {
+			return None;
+		}
+
+		// Test a point just to the right of the middle of the curve and flip the implicit function if it isn't filled there.
+		let middle = self.point_at(0.5);
+		let (before, after) = (self.point_at(0.49), self.point_at(0.51));
+		let tangent = (after.0 - before.0, after.1 - before.1);
+		let tangent_length = (tangent.0 * tangent.0 + tangent.1 * tangent.1).sqrt();
+		let mut needs_flip = false;
+		if tangent_length > f64::EPSILON {
+			let offset = 0.01 * tangent_length;
+			let test_point = (middle.0 + tangent.1 / tangent_length * offset, middle.1 - tangent.0 / tangent_length * offset);
+			let weights = [
+				area(test_point, self.points[b], self.points[c]) / total_area,
+				area(self.points[a], test_point, self.points[c]) / total_area,
+				area(self.points[a], self.points[b], test_point) / total_area,
+			];
+			let interpolated = |component: usize| weights[0] * klm[a][component] + weights[1] * klm[b][component] + weights[2] * klm[c][component];
+			let (k, l, m) = (interpolated(0), interpolated(1), interpolated(2));
+			needs_flip = k * k * k - l * m > 0.0;
+		}
+
+		// Scaling k by s and l, m by s^(3/2) scales k³ - lm by s³, keeping the values in a range f32 handles well.
+		let k_scale = klm.iter().map(|[k, _, _]| k.abs()).fold(0.0, f64::max);
+		let k_scale = if k_scale > f64::EPSILON { 1.0 / k_scale } else { 1.0 };
+		let lm_scale = k_scale.powf(1.5);
+		let sign = if needs_flip { -1.0 } else { 1.0 };
+		Some(klm.map(|[k, l, m]| [(sign * k * k_scale) as f32, (sign * l * lm_scale) as f32, (m * lm_scale) as f32]))
+	}
+
+	/// Triangles covering the convex hull of the control points, as indices into `points`, wound counter-clockwise like the other glyph triangles.
+	pub fn hull_triangles(&self) -> Vec<[usize; 3]> {
+		let area = |a: usize, b: usize, c: usize| {
+			let (pa, pb, pc) = (self.points[a], self.points[b], self.points[c]);
+			(pb.0 - pa.0) * (pc.1 - pa.1) - (pb.1 - pa.1) * (pc.0 - pa.0)
+		};
+
+		// Gift wrapping, starting from the lowest point.
+		let start = (0..4).min_by(|first, second| {
+			let (a, b) = (self.points[*first], self.points[*second]);
+			a.1.total_cmp(&b.1).then(a.0.total_cmp(&b.0))
+		}).unwrap();
+		let mut hull: Vec<usize> = vec![start];
+		let mut current = start;
+		loop {
+			let mut candidate = (current + 1) % 4;
+			for other in 0..4 {
+				if other == current {
+					continue;
+				}
+				let turn = area(current, candidate, other);
+				let further = {
+					let (pc, pa, pb) = (self.points[current], self.points[candidate], self.points[other]);
+					(pb.0 - pc.0).powi(2) + (pb.1 - pc.1).powi(2) > (pa.0 - pc.0).powi(2) + (pa.1 - pc.1).powi(2)
+				};
+				if turn < 0.0 || (turn == 0.0 && further) {
+					candidate = other;
+				}
+			}
+			if candidate == start || hull.len() == 4 {
+				break;
+			}
+			hull.push(candidate);
+			current = candidate;
+		}
+
+		(1..hull.len().saturating_sub(1)).map(|position| [hull[0], hull[position], hull[position + 1]]).filter(|[a, b, c]| area(*a, *b, *c) > 0.0).collect()
+	}
+}
+
+/// Rebuilds a contour around its cubic curves. The points the contour polygon should follow are pushed to `contour_indices`
+/// and the triangles covering each curve to `cubic_bezier_indices`.
+struct CubicContourBuilder<'a> {
+	vertices: &'a mut Vec<Vertex>,
+	contour_edges: &'a [(usize, usize)],
+	contour_indices: &'a mut Vec<Option<usize>>,
+	cubic_bezier_indices: &'a mut Vec<u32>,
+}
+
+impl CubicContourBuilder<'_> {
+	fn push_vertex(&mut self, (x, y): (f64, f64)) -> usize {
+		self.vertices.push(Vertex::new(x.round() as i16, y.round() as i16));
+		self.vertices.len() - 1
+	}
+
+	/// Adds a cubic curve from `start_index` to `end_index`, splitting it at inflection points and loop double points.
+	/// Cusps are left whole, as splitting at the cusp leaves pieces with a control point on their end point.
+	fn add_curve(&mut self, start_index: usize, first_control: (f64, f64), second_control: (f64, f64), end_index: usize) {
+		let curve = CubicCurve::new(self.vertices[start_index].position(), first_control, second_control, self.vertices[end_index].position());
+
+		let mut remaining = curve;
+		let mut consumed = 0.0;
+		let mut piece_start_index = start_index;
+		for t in curve.split_parameters() {
+			let (piece, rest) = remaining.split((t - consumed) / (1.0 - consumed));
+			let split_index = self.push_vertex(piece.points[3]);
+			self.add_piece(piece_start_index, piece.points[1], piece.points[2], split_index, 0);
+			piece_start_index = split_index;
+			remaining = rest;
+			consumed = t;
+		}
+		self.add_piece(piece_start_index, remaining.points[1], remaining.points[2], end_index, 0);
+	}
+
+	fn add_piece(&mut self, start_index: usize, first_control: (f64, f64), second_control: (f64, f64), end_index: usize, depth: usize) {
+		let first_control_vertex = Vertex::new(first_control.0.round() as i16, first_control.1.round() as i16);
+		let second_control_vertex = Vertex::new(second_control.0.round() as i16, second_control.1.round() as i16);
+		let curve = CubicCurve::new(self.vertices[start_index].position(), first_control_vertex.position(), second_control_vertex.position(), self.vertices[end_index].position());
+
+		// The fill is on the right of the contour, so control points on the right of the chord are inside the glyph.
+		let start = curve.points[0];
+		let chord = (curve.points[3].0 - start.0, curve.points[3].1 - start.1);
+		let side = |point: (f64, f64)| chord.0 * (point.1 - start.1) - chord.1 * (point.0 - start.0);
+		let (first_side, second_side) = (side(curve.points[1]), side(curve.points[2]));
+		let concave = first_side < 0.0 && second_side < 0.0;
+		let convex = first_side >= 0.0 && second_side >= 0.0;
+		let chord_intersects = convex && self.contour_edges.iter().any(|(first_index, second_index)| {
+			(&self.vertices[start_index], &self.vertices[end_index]).intersects((&self.vertices[*first_index], &self.vertices[*second_index]))
+		});
+
+		if (!(concave || convex) || chord_intersects) && depth < MAXIMUM_CUBIC_SUBDIVISIONS {
+			let (first_half, second_half) = CubicCurve::new(self.vertices[start_index].position(), first_control, second_control, self.vertices[end_index].position()).split(0.5);
+			let split_index = self.push_vertex(first_half.points[3]);
+			self.add_piece(start_index, first_half.points[1], first_half.points[2], split_index, depth + 1);
+			self.add_piece(split_index, second_half.points[1], second_half.points[2], end_index, depth + 1);
+			return;
+		}
+
+		if let Some(klm) = curve.klm_coordinates() {
+			let first_index = self.vertices.len();
+			for (position, klm) in klm.into_iter().enumerate() {
+				let (x, y) = curve.points[position];
+				self.vertices.push(Vertex { x: (x as i16).into(), y: (y as i16).into(), on_curve: position == 0 || position == 3, cubic: true, uv_coords: klm });
+			}
+			for triangle in curve.hull_triangles() {
+				self.cubic_bezier_indices.extend(triangle.map(|corner| (first_index + corner) as u32));
+			}
+		}
+
+		if concave {
+			let first_control_index = self.vertices.len();
+			self.vertices.push(first_control_vertex);
+			let second_control_index = self.vertices.len();
+			self.vertices.push(second_control_vertex);
+			self.contour_indices.push(Some(first_control_index));
+			self.contour_indices.push(Some(second_control_index));
+		}
+		self.contour_indices.push(Some(end_index));
 	}
 }
 
@@ -1037,7 +1421,8 @@ impl RemovableVector<usize> for Vec<Option<usize>> {
 impl From<&Point> for Vertex {
 	fn from(value: &Point) -> Self {
 		let on_curve = (value.flag & 0x01) == 1;
-		Vertex{ x: value.x.into(), y: value.y.into(), on_curve, uv_coords: [0.0, 0.0], }
+		let cubic = !on_curve && (value.flag & CUBIC_FLAG) != 0;
+		Vertex{ x: value.x.into(), y: value.y.into(), on_curve, cubic, uv_coords: [0.0, 0.0, 0.0], }
 	}
 }
 
@@ -1061,12 +1446,15 @@ impl From<[i16; 4]> for Bounds {
 pub enum GlyphDataIntermediate {
 	CompositeGlyph(GlyphCompositeIntermediate),
 	SimpleGlyph(GlyhpSimpleIntermediate),
+	FailedParse(GlyphParseError),
 	None,
 }
 
 impl From<GlyphDataRaw> for GlyphDataIntermediate {
 	fn from(value: GlyphDataRaw) -> Self {
 		match value {
+			// Components placed by matching points need the other glyphs to be put in place, by `match_component_points`.
+			GlyphDataRaw::CompositeGlyphRaw(value) if value.children.iter().any(|child| child.flag & ARGS_ARE_XY_VALUES == 0) => GlyphDataIntermediate::FailedParse(GlyphParseError::UnmatchedComponentPoint),
 			GlyphDataRaw::CompositeGlyphRaw(value) => GlyphDataIntermediate::CompositeGlyph(value.into()),
 			GlyphDataRaw::SimpleGlyphRaw(value) => GlyphDataIntermediate::SimpleGlyph(value.into()),
 			GlyphDataRaw::None => GlyphDataIntermediate::None,
@@ -1074,6 +1462,111 @@ impl From<GlyphDataRaw> for GlyphDataIntermediate {
 	}
 }
 
+const MAX_COMPONENT_DEPTH: usize = 8;
+
+/// A component's 2x2 transform, from the scale it's given if any.
+fn component_transform(child: &ComponentGlyphRaw) -> (f64, f64, f64, f64) {
+	let f2dot14 = |value: Option<u16>| value.map(|value| value as i16 as f64 / 16384.0);
+	match (f2dot14(child.transform_0), f2dot14(child.transform_1), f2dot14(child.transform_2), f2dot14(child.transform_3)) {
+		(Some(xx), Some(xy), Some(yx), Some(yy)) => (xx, xy, yx, yy),
+		(Some(x_scale), Some(y_scale), None, None) => (x_scale, 0.0, 0.0, y_scale),
+		(Some(scale), None, None, None) => (scale, 0.0, 0.0, scale),
+		_ => (1.0, 0.0, 0.0, 1.0),
+	}
+}
+
+/// How far to move a component placed by matching points so that its point lands on the composite's, both already in the outlines.
+fn matched_point_offset(outline: &SimpleGlyphRaw, composite_start: usize, component: &SimpleGlyphRaw, child: &ComponentGlyphRaw) -> Option<(i32, i32)> {
+	let parent_point = composite_start + child.x_offset_point as usize;
+	let (parent_x, parent_y) = (*outline.x_coordinates.get(parent_point)?, *outline.y_coordinates.get(parent_point)?);
+	let (child_x, child_y) = (*component.x_coordinates.get(child.y_offset_point as usize)?, *component.y_coordinates.get(child.y_offset_point as usize)?);
+	Some((parent_x as i32 - child_x as i32, parent_y as i32 - child_y as i32))
+}
+
+/// Adds a component's points to an outline, moved by an offset.
+fn append_moved(outline: &mut SimpleGlyphRaw, component: SimpleGlyphRaw, (offset_x, offset_y): (i32, i32)) {
+	let base = outline.x_coordinates.len() as u16;
+	outline.x_coordinates.extend(component.x_coordinates.iter().map(|x| (*x as i32 + offset_x) as i16));
+	outline.y_coordinates.extend(component.y_coordinates.iter().map(|y| (*y as i32 + offset_y) as i16));
+	outline.flags.extend(component.flags);
+	outline.end_points_of_contours.extend(component.end_points_of_contours.iter().map(|end| end + base));
+}
+
+/// Adds a glyph's points to an outline, with a composite's components transformed and then offset, or moved so the point they
+/// name lands on the one it's matched with in the composite's points so far. `glyphs` looks up components by glyph id.
+pub(crate) fn flatten_glyph<'a>(glyphs: &dyn Fn(u16) -> Option<Cow<'a, GlyphRaw>>, glyph: &GlyphRaw, outline: &mut SimpleGlyphRaw, (xx, xy, yx, yy): (f64, f64, f64, f64), (dx, dy): (f64, f64), depth: usize) -> Option<()> {
+	if depth > MAX_COMPONENT_DEPTH {
+		return None;
+	}
+	match &glyph.glyph_data {
+		GlyphDataRaw::SimpleGlyphRaw(simple) => {
+			let base = outline.x_coordinates.len() as u16;
+			for (x, y) in simple.x_coordinates.iter().zip(simple.y_coordinates.iter()) {
+				let (x, y) = (*x as f64, *y as f64);
+				outline.x_coordinates.push((x * xx + y * yx + dx).round() as i16);
+				outline.y_coordinates.push((x * xy + y * yy + dy).round() as i16);
+			}
+			outline.flags.extend(simple.flags.iter().map(|flag| flag & 0x01));
+			outline.end_points_of_contours.extend(simple.end_points_of_contours.iter().map(|end| end + base));
+		},
+		GlyphDataRaw::CompositeGlyphRaw(composite) => {
+			let composite_start = outline.x_coordinates.len();
+			for child in composite.children.iter() {
+				let (cxx, cxy, cyx, cyy) = component_transform(child);
+				let transform = (cxx * xx + cxy * yx, cxx * xy + cxy * yy, cyx * xx + cyy * yx, cyx * xy + cyy * yy);
+				let child_glyph = glyphs(child.glyph_index)?;
+				match child.flag & ARGS_ARE_XY_VALUES != 0 {
+					true => {
+						let (offset_x, offset_y) = (child.x_offset_point as f64, child.y_offset_point as f64);
+						let offset = (offset_x * xx + offset_y * yx + dx, offset_x * xy + offset_y * yy + dy);
+						flatten_glyph(glyphs, &child_glyph, outline, transform, offset, depth + 1)?;
+					},
+					false => {
+						let mut component = SimpleGlyphRaw::default();
+						flatten_glyph(glyphs, &child_glyph, &mut component, transform, (0.0, 0.0), depth + 1)?;
+						let offset = matched_point_offset(outline, composite_start, &component, child)?;
+						append_moved(outline, component, offset);
+					},
+				}
+			}
+		},
+		GlyphDataRaw::None => {},
+	}
+	Some(())
+}
+
+/// A glyph's intermediate form, with the components of a composite placed by matching points given the offsets that line those
+/// points up, so it's drawn like a composite placed by offsets. `glyphs` looks up components by glyph id.
+pub(crate) fn match_component_points<'a>(glyphs: &dyn Fn(u16) -> Option<Cow<'a, GlyphRaw>>, mut glyph: GlyphRaw) -> GlyphIntermediate {
+	if let GlyphDataRaw::CompositeGlyphRaw(composite) = &mut glyph.glyph_data
+		&& let Err(error) = match_composite_points(glyphs, composite) {
+		return GlyphIntermediate { number_of_contours: None, bounds: [glyph.x_min, glyph.x_max, glyph.y_min, glyph.y_max].into(), glyph_data: GlyphDataIntermediate::FailedParse(error) };
+	}
+	glyph.into()
+}
+
+fn match_composite_points<'a>(glyphs: &dyn Fn(u16) -> Option<Cow<'a, GlyphRaw>>, composite: &mut CompositeGlyphRaw) -> Result<(), GlyphParseError> {
+	if composite.children.iter().all(|child| child.flag & ARGS_ARE_XY_VALUES != 0) {
+		return Ok(());
+	}
+
+	let mut outline = SimpleGlyphRaw::default();
+	for child in composite.children.iter_mut() {
+		let child_glyph = glyphs(child.glyph_index).ok_or(GlyphParseError::UnmatchedComponentPoint)?;
+		let mut component = SimpleGlyphRaw::default();
+		flatten_glyph(glyphs, &child_glyph, &mut component, component_transform(child), (0.0, 0.0), 1).ok_or(GlyphParseError::UnmatchedComponentPoint)?;
+		let (offset_x, offset_y) = match child.flag & ARGS_ARE_XY_VALUES != 0 {
+			true => (child.x_offset_point, child.y_offset_point),
+			false => matched_point_offset(&outline, 0, &component, child).ok_or(GlyphParseError::UnmatchedComponentPoint)?,
+		};
+		child.flag |= ARGS_ARE_XY_VALUES;
+		child.x_offset_point = offset_x;
+		child.y_offset_point = offset_y;
+		append_moved(&mut outline, component, (offset_x, offset_y));
+	}
+	Ok(())
+}
+
 impl From<GlyphRaw> for GlyphIntermediate {
 	fn from(value: GlyphRaw) -> Self {
 		let number_of_contours = if value.number_of_contours > 0 {
@@ -1091,6 +1584,84 @@ impl From<GlyphRaw> for GlyphIntermediate {
 	}
 }
 
+impl From<CharStringOutline> for GlyphIntermediate {
+	fn from(value: CharStringOutline) -> Self {
+		let mut points: Vec<Point> = Vec::new();
+		let mut contours: Vec<Contour> = Vec::new();
+
+		for contour in value.contours.into_iter() {
+			let contour_start = points.len();
+			let mut contour_points: Vec<Point> = vec![Point::on_curve(contour.start)];
+			for segment in contour.segments.into_iter() {
+				match segment {
+					PathSegment::Line(x, y) => {
+						contour_points.push(Point::on_curve((x, y)));
+					},
+					PathSegment::Cubic(first_control, second_control, end) => {
+						contour_points.push(Point::cubic_off_curve(first_control));
+						contour_points.push(Point::cubic_off_curve(second_control));
+						contour_points.push(Point::on_curve(end));
+					},
+				}
+			}
+
+			// Charstring contours close implicitly, so a final point back at the start is redundant.
+			contour_points.dedup_by(|point, previous_point| point.same_position(previous_point) && point.flag & 0x01 == 1 && previous_point.flag & 0x01 == 1);
+			while contour_points.len() > 1 && contour_points.last().unwrap().same_position(&contour_points[0]) && contour_points.last().unwrap().flag & 0x01 == 1 {
+				contour_points.pop();
+			}
+			if contour_points.len() < 3 {
+				continue;
+			}
+
+			points.extend(contour_points);
+			let indices: Vec<Option<usize>> = (contour_start..points.len()).map(|v| Some(v)).collect();
+			let direction = indices.get_direction(&points);
+			contours.push(Contour {
+				indices,
+				indices_removed: 0,
+				direction,
+			});
+		}
+
+		let bounds = if points.is_empty() {
+			[0, 0, 0, 0].into()
+		} else {
+			[
+				points.iter().map(|point| point.x).min().unwrap(),
+				points.iter().map(|point| point.x).max().unwrap(),
+				points.iter().map(|point| point.y).min().unwrap(),
+				points.iter().map(|point| point.y).max().unwrap(),
+			].into()
+		};
+
+		let number_of_contours = if contours.is_empty() { None } else { Some(contours.len() as u16) };
+		let glyph_data = match number_of_contours {
+			Some(_) => GlyphDataIntermediate::SimpleGlyph(GlyhpSimpleIntermediate { contours, points }),
+			None => GlyphDataIntermediate::None,
+		};
+
+		GlyphIntermediate {
+			number_of_contours,
+			bounds,
+			glyph_data,
+		}
+	}
+}
+
+impl From<Result<CharStringOutline, CharStringError>> for GlyphIntermediate {
+	fn from(value: Result<CharStringOutline, CharStringError>) -> Self {
+		match value {
+			Ok(outline) => outline.into(),
+			Err(error) => GlyphIntermediate {
+				number_of_contours: None,
+				bounds: [0, 0, 0, 0].into(),
+				glyph_data: GlyphDataIntermediate::FailedParse(GlyphParseError::InvalidCharString(error)),
+			},
+		}
+	}
+}
+
 pub struct GlyphCompositeIntermediate {
 	pub children: Vec<GlyphComponentIntermediate>
 }
@@ -1141,10 +1712,17 @@ impl TransformationMatrix2x2 {
 	}
 
 	pub fn is_identity(&self) -> bool {
-		self.p11 - 1.0 < f32::EPSILON
-		&& self.p12 - 0.0 < f32::EPSILON
-		&& self.p21 - 0.0 < f32::EPSILON
-		&& self.p22 - 1.0 < f32::EPSILON
+		(self.p11 - 1.0).abs() < f32::EPSILON
+		&& self.p12.abs() < f32::EPSILON
+		&& self.p21.abs() < f32::EPSILON
+		&& (self.p22 - 1.0).abs() < f32::EPSILON
+	}
+}
+
+impl From<TransformationMatrix2x2> for Affine {
+	fn from(value: TransformationMatrix2x2) -> Self {
+		// p12 is the component's x scaled into y, and p21 its y scaled into x.
+		Affine { xx: value.p11, yx: value.p12, xy: value.p21, yy: value.p22, dx: 0.0, dy: 0.0 }
 	}
 }
 
@@ -1155,7 +1733,7 @@ impl From<[Option<Fixed2Dot14>; 4]> for TransformationMatrix2x2 {
 			[Some(scale), None, None, None] => TransformationMatrix2x2::identity_scaled(scale.into(), scale.into()),
 			[Some(x_scale), Some(y_scale), None, None] => TransformationMatrix2x2::identity_scaled(x_scale.into(), y_scale.into()),
 			[Some(p11), Some(p12), Some(p21), Some(p22)] => TransformationMatrix2x2 { p11: p11.into(), p12: p12.into(), p21: p21.into(), p22: p22.into(), },
-			_ => panic!("Transformation number should have one of the above patterns.")
+			_ => TransformationMatrix2x2::identity_scaled(1.0, 1.0),
 		}
 	}
 }
@@ -1186,10 +1764,7 @@ struct Fixed2Dot14(u16);
 
 impl From<Fixed2Dot14> for f32 {
 	fn from(input: Fixed2Dot14) -> Self {
-		let integer_part = (input.0 >> 14) as f32;
-		let float_part = (input.0 & 0b0011_1111_1111_1111) as f32 / 2u32.pow(14) as f32;
-
-		integer_part + float_part
+		input.0 as i16 as f32 / 2u32.pow(14) as f32
 	}
 }
 
@@ -1211,6 +1786,14 @@ pub struct Point {
 }
 
 impl Point {
+	fn on_curve((x, y): (f32, f32)) -> Self {
+		Point { flag: 0x01, x: x.round() as i16, y: y.round() as i16 }
+	}
+
+	fn cubic_off_curve((x, y): (f32, f32)) -> Self {
+		Point { flag: CUBIC_FLAG, x: x.round() as i16, y: y.round() as i16 }
+	}
+
 	pub fn same_position(&self, other_point: &Self) -> bool{
 		(self.x == other_point.x) && (self.y == other_point.y)
 	}
@@ -1285,6 +1868,10 @@ pub trait GetDirection<T> {
 
 impl GetDirection<Vertex> for Vec<Option<usize>> {
 	fn get_direction(&self, vertices: &Vec<Vertex>) -> Direction {
+		// Contours without points, or naming points the glyph doesn't have, have no direction to find, so are taken as outer ones.
+		if self.iter().all(Option::is_none) || self.iter().flatten().any(|index| *index >= vertices.len()) {
+			return Direction::Clockwise;
+		}
 		let mut lowest_y: FontUnits<i16> = i16::MAX.into();
 		let mut highest_x: FontUnits<i16> = i16::MIN.into();
 		let mut chosen_indices_position: usize = 0;
@@ -1361,6 +1948,10 @@ impl GetDirection<Vertex> for Vec<Option<usize>> {
 
 impl GetDirection<Point> for Vec<Option<usize>> {
 	fn get_direction(&self, vertices: &Vec<Point>) -> Direction {
+		// Contours without points, or naming points the glyph doesn't have, have no direction to find, so are taken as outer ones.
+		if self.iter().all(Option::is_none) || self.iter().flatten().any(|index| *index >= vertices.len()) {
+			return Direction::Clockwise;
+		}
 		let mut lowest_y = i16::MAX;
 		let mut highest_x = i16::MIN;
 		let mut chosen_indices_position: usize = 0;
@@ -4,6 +4,8 @@ use std::{fmt::{Debug, Display}, fs::File, io::{self, BufReader, Read, Seek}};
 pub enum TrueTypeFontReaderError {
 	NotEnoughBytesInBuffer(usize, usize),
 	IOError(io::Error),
+	UnsupportedTableVersion(TableTag, u16, u16), // table, major version, minor version
+	InvalidData(TableTag, &'static str),
 }
 
 impl From<io::Error> for TrueTypeFontReaderError {
@@ -12,17 +14,38 @@ impl From<io::Error> for TrueTypeFontReaderError {
 	}
 }
 
-pub struct TrueTypeFontReader {
-	pub buffer_reader: BufReader<File>,
+pub struct TrueTypeFontReader<Source: Read + Seek> {
+	pub buffer_reader: Source,
 }
 
-impl TrueTypeFontReader {
+impl TrueTypeFontReader<BufReader<File>> {
 	pub fn new(file: File) -> Self {
 		let buffer_reader = BufReader::new(file);
 		Self {
 			buffer_reader,
 		}
 	}
+}
+
+impl<Source: Read + Seek> TrueTypeFontReader<Source> {
+	/// Reads from any seekable source, such as a `Cursor` over bytes already in memory.
+	pub fn from_source(source: Source) -> Self {
+		Self {
+			buffer_reader: source,
+		}
+	}
+
+	pub fn length(&mut self) -> Result<u64, TrueTypeFontReaderError> {
+		let current_position = self.buffer_reader.stream_position()?;
+		let length = self.buffer_reader.seek(io::SeekFrom::End(0))?;
+		self.buffer_reader.seek(io::SeekFrom::Start(current_position))?;
+		Ok(length)
+	}
+
+	pub fn remaining(&mut self) -> Result<u64, TrueTypeFontReaderError> {
+		let current_position = self.buffer_reader.stream_position()?;
+		Ok(self.length()?.saturating_sub(current_position))
+	}
 
 	pub fn skip(&mut self, bytes: usize) -> Result<(), TrueTypeFontReaderError> {
 		self.buffer_reader.seek_relative(bytes as i64)?;
@@ -53,6 +76,31 @@ pub enum TableTag {
 	HorizontalHeaderTable,
 	HorizontalMetricsTable,
 	OS2AndWindowsMetricsTable,
+	CompactFontFormat,
+	CompactFontFormat2,
+	FontVariations,
+	GlyphVariations,
+	AxisVariations,
+	HorizontalMetricsVariations,
+	MetricsVariations,
+	Naming,
+	PostScript,
+	Kerning,
+	GlyphPositioning,
+	GlyphSubstitution,
+	GlyphDefinition,
+	VerticalHeaderTable,
+	VerticalMetricsTable,
+	VerticalOrigin,
+	Colour,
+	ColourPalette,
+	ColourBitmapLocation,
+	ColourBitmapData,
+	StandardBitmapGraphics,
+	ControlValue,
+	FontProgram,
+	ControlValueProgram,
+	GridFitting,
 }
 
 impl Display for TableTag {
@@ -67,6 +115,31 @@ impl Display for TableTag {
 			TableTag::HorizontalHeaderTable => write!(f, "hhea: Horizontal Header Table"),
 			TableTag::HorizontalMetricsTable => write!(f, "hmtx: Horizontal Metrics Table"),
 			TableTag::OS2AndWindowsMetricsTable => write!(f, "OS/2: OS/2 and Windows Metrics Table"),
+			TableTag::CompactFontFormat => write!(f, "CFF : Compact Font Format Table"),
+			TableTag::CompactFontFormat2 => write!(f, "CFF2: Compact Font Format 2 Table"),
+			TableTag::FontVariations => write!(f, "fvar: Font Variations Table"),
+			TableTag::GlyphVariations => write!(f, "gvar: Glyph Variations Table"),
+			TableTag::AxisVariations => write!(f, "avar: Axis Variations Table"),
+			TableTag::HorizontalMetricsVariations => write!(f, "HVAR: Horizontal Metrics Variations Table"),
+			TableTag::MetricsVariations => write!(f, "MVAR: Metrics Variations Table"),
+			TableTag::Naming => write!(f, "name: Naming Table"),
+			TableTag::PostScript => write!(f, "post: PostScript Table"),
+			TableTag::Kerning => write!(f, "kern: Kerning Table"),
+			TableTag::GlyphPositioning => write!(f, "GPOS: Glyph Positioning Table"),
+			TableTag::GlyphSubstitution => write!(f, "GSUB: Glyph Substitution Table"),
+			TableTag::GlyphDefinition => write!(f, "GDEF: Glyph Definition Table"),
+			TableTag::VerticalHeaderTable => write!(f, "vhea: Vertical Header Table"),
+			TableTag::VerticalMetricsTable => write!(f, "vmtx: Vertical Metrics Table"),
+			TableTag::VerticalOrigin => write!(f, "VORG: Vertical Origin Table"),
+			TableTag::Colour => write!(f, "COLR: Colour Table"),
+			TableTag::ColourPalette => write!(f, "CPAL: Colour Palette Table"),
+			TableTag::ColourBitmapLocation => write!(f, "CBLC: Colour Bitmap Location Table"),
+			TableTag::ColourBitmapData => write!(f, "CBDT: Colour Bitmap Data Table"),
+			TableTag::StandardBitmapGraphics => write!(f, "sbix: Standard Bitmap Graphics Table"),
+			TableTag::ControlValue => write!(f, "cvt : Control Value Table"),
+			TableTag::FontProgram => write!(f, "fpgm: Font Program"),
+			TableTag::ControlValueProgram => write!(f, "prep: Control Value Program"),
+			TableTag::GridFitting => write!(f, "gasp: Grid-fitting and Scan-conversion Procedure Table"),
 		}
 	}
 }
@@ -79,6 +152,25 @@ pub struct TableRecord {
 	pub length: u32,
 }
 
+#[derive(Debug, Clone)]
+pub struct TableDirectory {
+	pub offset: u32,
+	pub sfnt_version: u32,
+	pub table_records: Vec<TableRecord>,
+}
+
+pub const COLLECTION_TAG: u32 = 0x74746366; // 'ttcf'
+
+pub const ARGS_ARE_XY_VALUES: u16 = 0x0002; // A component's arguments are an offset, rather than points to match
+
+#[derive(Debug, Clone)]
+pub struct CollectionHeader {
+	pub major_version: u16,
+	pub minor_version: u16,
+	pub number_of_fonts: u32,
+	pub table_directory_offsets: Vec<u32>,
+}
+
 pub struct MaximumProfileTable {
 	major_version: u16,
 	minor_version: u16,
@@ -88,11 +180,11 @@ pub struct MaximumProfileTable {
 	max_composite_points: u16,
 	max_composite_contours: u16,
 	max_zones: u16,
-	max_twilight_points: u16,
-	max_storage: u16,
+	pub max_twilight_points: u16,
+	pub max_storage: u16,
 	max_function_defs: u16,
 	max_instruction_defs: u16,
-	max_stack_elements: u16,
+	pub max_stack_elements: u16,
 	max_size_of_instructions: u16,
 	max_component_elements: u16,
 	max_component_depth: u16,
@@ -106,7 +198,7 @@ pub struct IndexToLocationTable {
 pub struct GlyphOffset {
 	pub id: u16,
 	pub glyph_offset: Option<u32>,
-	glyph_length: Option<u32>,
+	pub glyph_length: Option<u32>,
 }
 
 pub struct FontHeaderTable {
@@ -130,7 +222,7 @@ pub struct FontHeaderTable {
 	glyph_data_format: i16, // 0 -> current format, don't think there should be any other values.
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct GlyphRaw {
 	pub number_of_contours: i16,
 	pub x_min: i16,
@@ -140,14 +232,14 @@ pub struct GlyphRaw {
 	pub glyph_data: GlyphDataRaw,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum GlyphDataRaw {
 	SimpleGlyphRaw(SimpleGlyphRaw),
 	CompositeGlyphRaw(CompositeGlyphRaw),
 	None,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
 pub struct SimpleGlyphRaw {
 	pub end_points_of_contours: Vec<u16>,
 	pub instruction_length: u16,
@@ -157,12 +249,13 @@ pub struct SimpleGlyphRaw {
 	pub y_coordinates: Vec<i16>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CompositeGlyphRaw {
 	pub children: Vec<ComponentGlyphRaw>,
+	pub instructions: Vec<u8>, // Run on the components' points together, after each component's own
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ComponentGlyphRaw {
 	pub flag: u16,
 	pub glyph_index: u16,
@@ -180,27 +273,101 @@ pub struct GlyphTable {
 
 #[derive(Debug)]
 pub struct EncodingRecord {
-	platform_id: u16,
-	encoding_id: u16,
+	pub platform_id: u16,
+	pub encoding_id: u16,
 	subtable_offset: u32,
 }
 
 #[derive(Debug)]
 pub enum CharacterToGlyphIndexSubtable {
+	Format0(CharacterToGlyphIndexSubtableFormat0),
+	Format2(CharacterToGlyphIndexSubtableFormat2),
 	Format4(CharacterToGlyphIndexSubtableFormat4),
+	Format6(CharacterToGlyphIndexSubtableFormat6),
+	Format10(CharacterToGlyphIndexSubtableFormat10),
 	Format12(CharacterToGlyphIndexSubtableFormat12),
+	Format13(CharacterToGlyphIndexSubtableFormat13),
+	Format14(CharacterToGlyphIndexSubtableFormat14),
 	InvalidFormat(usize),
 }
 
 impl CharacterToGlyphIndexSubtable {
 	pub fn get_glyph_id(&self, character_code: u64) -> Option<u16> {
 		match self {
+			CharacterToGlyphIndexSubtable::Format0(subtable) => {subtable.get_glyph_id(character_code)},
+			CharacterToGlyphIndexSubtable::Format2(subtable) => {subtable.get_glyph_id(character_code)},
 			CharacterToGlyphIndexSubtable::Format4(subtable) => {subtable.get_glyph_id(character_code)},
+			CharacterToGlyphIndexSubtable::Format6(subtable) => {subtable.get_glyph_id(character_code)},
+			CharacterToGlyphIndexSubtable::Format10(subtable) => {subtable.get_glyph_id(character_code)},
 			CharacterToGlyphIndexSubtable::Format12(subtable) => {subtable.get_glyph_id(character_code)},
-			CharacterToGlyphIndexSubtable::InvalidFormat(format) => {
-				println!("Error Attempting To index with invalid cmap subtable format: {format}");
-				None
-			}
+			CharacterToGlyphIndexSubtable::Format13(subtable) => {subtable.get_glyph_id(character_code)},
+			CharacterToGlyphIndexSubtable::Format14(_) => None, // only maps variation sequences, not single characters
+			CharacterToGlyphIndexSubtable::InvalidFormat(_) => None, // a format that isn't supported maps nothing
+		}
+	}
+}
+
+#[derive(Debug)]
+pub struct CharacterToGlyphIndexSubtableFormat0 {
+	pub length: u16,
+	pub language: u16,
+	pub glyph_id_array: Vec<u8>, // one glyph for each of the 256 single byte character codes
+}
+
+impl CharacterToGlyphIndexSubtableFormat0 {
+	fn get_glyph_id(&self, character_code: u64) -> Option<u16> {
+		match self.glyph_id_array.get(character_code as usize) {
+			Some(0) | None => None,
+			Some(glyph_id) => Some(*glyph_id as u16),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SubHeader {
+	pub first_code: u16,
+	pub entry_count: u16,
+	pub id_delta: i16,
+	pub id_range_offset: u16,
+}
+
+/// High byte mapping through table, for mixed one and two byte encodings such as Shift-JIS.
+#[derive(Debug)]
+pub struct CharacterToGlyphIndexSubtableFormat2 {
+	pub length: u16,
+	pub language: u16,
+	pub sub_header_keys: Vec<u16>, // sub header index times 8, for each possible high byte
+	pub sub_headers: Vec<SubHeader>,
+	pub glyph_id_array: Vec<u16>,
+}
+
+impl CharacterToGlyphIndexSubtableFormat2 {
+	fn get_glyph_id(&self, character_code: u64) -> Option<u16> {
+		if character_code > 0xFFFF {
+			return None;
+		}
+		let (high_byte, low_byte) = ((character_code >> 8) as usize, (character_code & 0xFF) as u16);
+		// Sub header 0 maps single byte characters, any other high byte key marks the first byte of a two byte character.
+		let sub_header_index = match high_byte {
+			0 if self.sub_header_keys[low_byte as usize] == 0 => 0,
+			0 => return None,
+			_ => match self.sub_header_keys[high_byte] / 8 {
+				0 => return None,
+				sub_header_index => sub_header_index as usize,
+			},
+		};
+		let sub_header = self.sub_headers.get(sub_header_index)?;
+		if low_byte < sub_header.first_code || low_byte as u32 >= sub_header.first_code as u32 + sub_header.entry_count as u32 {
+			return None;
+		}
+
+		// idRangeOffset counts bytes from itself, the last field of its sub header, to the sub header's first glyph.
+		let id_range_offset_position = sub_header_index * 8 + 6;
+		let glyph_id_array_position = self.sub_headers.len() * 8;
+		let glyph_id_index = (id_range_offset_position + sub_header.id_range_offset as usize).checked_sub(glyph_id_array_position)? / 2 + (low_byte - sub_header.first_code) as usize;
+		match self.glyph_id_array.get(glyph_id_index) {
+			Some(0) | None => None,
+			Some(glyph_id) => Some((*glyph_id as i32 + sub_header.id_delta as i32).rem_euclid(65536) as u16),
 		}
 	}
 }
@@ -227,8 +394,8 @@ impl CharacterToGlyphIndexSubtableFormat4 {
 				if self.id_range_offsets[i] == 0 {
 					return Some(((character_code as i128 + self.id_deltas[i] as i128) % 65536) as u16);
 				} else {
-					let glyph_id_index = (character_code - self.start_codes[i] as u64) + (self.id_range_offsets[i] as u64 / 2) + i as u64 - self.segment_count as u64;
-					let glyph_id = self.glyph_id_array[glyph_id_index as usize];
+					let glyph_id_index = ((character_code - self.start_codes[i] as u64) + (self.id_range_offsets[i] as u64 / 2) + i as u64).checked_sub(self.segment_count as u64)?;
+					let glyph_id = *self.glyph_id_array.get(glyph_id_index as usize)?;
 					if glyph_id == 0 {
 						return None;
 					} else {
@@ -261,11 +428,77 @@ impl CharacterToGlyphIndexSubtableFormat12 {
 	}
 }
 
+/// Trimmed table mapping, a dense run of 16 bit character codes.
+#[derive(Debug)]
+pub struct CharacterToGlyphIndexSubtableFormat6 {
+	pub length: u16,
+	pub language: u16,
+	pub first_code: u16,
+	pub glyph_id_array: Vec<u16>,
+}
+
+impl CharacterToGlyphIndexSubtableFormat6 {
+	fn get_glyph_id(&self, character_code: u64) -> Option<u16> {
+		let index = character_code.checked_sub(self.first_code as u64)?;
+		match self.glyph_id_array.get(index as usize) {
+			Some(0) | None => None,
+			Some(glyph_id) => Some(*glyph_id),
+		}
+	}
+}
+
+/// Trimmed array, a dense run of 32 bit character codes.
+#[derive(Debug)]
+pub struct CharacterToGlyphIndexSubtableFormat10 {
+	pub length: u32,
+	pub language: u32,
+	pub start_char_code: u32,
+	pub glyph_id_array: Vec<u16>,
+}
+
+impl CharacterToGlyphIndexSubtableFormat10 {
+	fn get_glyph_id(&self, character_code: u64) -> Option<u16> {
+		let index = character_code.checked_sub(self.start_char_code as u64)?;
+		match self.glyph_id_array.get(index as usize) {
+			Some(0) | None => None,
+			Some(glyph_id) => Some(*glyph_id),
+		}
+	}
+}
+
+/// Many to one range mappings, where every character in a group maps to the same glyph, as used by last resort fonts.
+#[derive(Debug)]
+pub struct CharacterToGlyphIndexSubtableFormat13 {
+	pub length: u32,
+	pub language: u32,
+	pub groups: Vec<(u32, u32, u32)>, // start character code, end character code, glyph id
+}
+
+impl CharacterToGlyphIndexSubtableFormat13 {
+	fn get_glyph_id(&self, character_code: u64) -> Option<u16> {
+		self.groups.iter().find(|(start_code, end_code, _)| character_code >= *start_code as u64 && character_code <= *end_code as u64).map(|(_, _, glyph_id)| *glyph_id as u16)
+	}
+}
+
+#[derive(Debug)]
+pub struct VariationSelectorRecord {
+	pub variation_selector: u32,
+	pub default_uvs: Vec<(u32, u8)>, // start unicode value, number of values after it, for sequences using the default glyph
+	pub non_default_uvs: Vec<(u32, u16)>, // unicode value, glyph id
+}
+
+/// Unicode Variation Sequences, mapping a base character followed by a variation selector to a glyph.
+#[derive(Debug)]
+pub struct CharacterToGlyphIndexSubtableFormat14 {
+	pub length: u32,
+	pub variation_selector_records: Vec<VariationSelectorRecord>,
+}
+
 #[derive(Debug)]
 pub struct CharacterToGlyphIndexTable {
 	version: u16,
 	number_of_subtables: u16,
-	encoding_records: Vec<EncodingRecord>,
+	pub encoding_records: Vec<EncodingRecord>,
 	pub subtables: Vec<CharacterToGlyphIndexSubtable>,
 }
 
@@ -327,6 +560,7 @@ pub struct OS2AndWindowsMetricsTable {
 
 }
 
+#[derive(Clone, Copy)]
 pub struct HorizontalMetric {
 	pub advance_width: u16,
 	pub left_side_bearing: i16,
@@ -336,6 +570,46 @@ pub struct HorizontalMetricsTable {
 	pub horizontal_metrics: Vec<HorizontalMetric>,
 }
 
+pub struct VerticalHeaderTable {
+	pub ascender: i16, // From the centre line of a column to its right edge
+	pub descender: i16, // From the centre line of a column to its left edge, usually negative
+	pub line_gap: i16, // Between columns
+	advance_height_max: u16,
+	minimum_top_side_bearing: i16,
+	minimum_bottom_side_bearing: i16,
+	y_max_extent: i16,
+	caret_slope_rise: i16,
+	caret_slope_run: i16,
+	caret_offset: i16,
+	pub number_of_vertical_metrics: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VerticalMetric {
+	pub advance_height: u16,
+	pub top_side_bearing: i16, // From the top of the glyph's em box to the top of its outline
+}
+
+pub struct VerticalMetricsTable {
+	pub vertical_metrics: Vec<VerticalMetric>,
+}
+
+/// Where the top of each glyph's em box is in vertical text, for CFF fonts, which don't have a glyf table to take it from.
+#[derive(Clone)]
+pub struct VerticalOriginTable {
+	pub default_vertical_origin_y: i16,
+	pub vertical_origins: Vec<(u16, i16)>, // Glyph id and the y coordinate of its origin, sorted by glyph id
+}
+
+impl VerticalOriginTable {
+	pub fn vertical_origin_y(&self, glyph_id: u16) -> i16 {
+		match self.vertical_origins.binary_search_by_key(&glyph_id, |&(glyph_id, _)| glyph_id) {
+			Ok(index) => self.vertical_origins[index].1,
+			Err(_) => self.default_vertical_origin_y,
+		}
+	}
+}
+
 pub trait FromBytes {
 	type Bytes: Default + AsMut<[u8]>;
 
@@ -411,6 +685,31 @@ impl FromBytes for TableTag {
 			[b'h', b'h', b'e', b'a'] => TableTag::HorizontalHeaderTable,
 			[b'h', b'm', b't', b'x'] => TableTag::HorizontalMetricsTable,
 			[b'O', b'S', b'/', b'2'] => TableTag::OS2AndWindowsMetricsTable,
+			[b'C', b'F', b'F', b' '] => TableTag::CompactFontFormat,
+			[b'C', b'F', b'F', b'2'] => TableTag::CompactFontFormat2,
+			[b'f', b'v', b'a', b'r'] => TableTag::FontVariations,
+			[b'g', b'v', b'a', b'r'] => TableTag::GlyphVariations,
+			[b'a', b'v', b'a', b'r'] => TableTag::AxisVariations,
+			[b'H', b'V', b'A', b'R'] => TableTag::HorizontalMetricsVariations,
+			[b'M', b'V', b'A', b'R'] => TableTag::MetricsVariations,
+			[b'n', b'a', b'm', b'e'] => TableTag::Naming,
+			[b'p', b'o', b's', b't'] => TableTag::PostScript,
+			[b'k', b'e', b'r', b'n'] => TableTag::Kerning,
+			[b'G', b'P', b'O', b'S'] => TableTag::GlyphPositioning,
+			[b'G', b'S', b'U', b'B'] => TableTag::GlyphSubstitution,
+			[b'G', b'D', b'E', b'F'] => TableTag::GlyphDefinition,
+			[b'v', b'h', b'e', b'a'] => TableTag::VerticalHeaderTable,
+			[b'v', b'm', b't', b'x'] => TableTag::VerticalMetricsTable,
+			[b'V', b'O', b'R', b'G'] => TableTag::VerticalOrigin,
+			[b'C', b'O', b'L', b'R'] => TableTag::Colour,
+			[b'C', b'P', b'A', b'L'] => TableTag::ColourPalette,
+			[b'C', b'B', b'L', b'C'] => TableTag::ColourBitmapLocation,
+			[b'C', b'B', b'D', b'T'] => TableTag::ColourBitmapData,
+			[b's', b'b', b'i', b'x'] => TableTag::StandardBitmapGraphics,
+			[b'c', b'v', b't', b' '] => TableTag::ControlValue,
+			[b'f', b'p', b'g', b'm'] => TableTag::FontProgram,
+			[b'p', b'r', b'e', b'p'] => TableTag::ControlValueProgram,
+			[b'g', b'a', b's', b'p'] => TableTag::GridFitting,
 			_ => TableTag::Other([bytes[0] as char, bytes[1] as char, bytes[2] as char, bytes[3] as char]),
 		}
 	}
@@ -446,23 +745,101 @@ where Self: Sized
 {
 	type Input;
 
-	fn read(ttf_reader: &mut TrueTypeFontReader, input: Self::Input) -> Result<Self, TrueTypeFontReaderError>;
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, input: Self::Input) -> Result<Self, TrueTypeFontReaderError>;
 }
 
-impl FromTTFReader for MaximumProfileTable {
+impl FromTTFReader for TableDirectory {
 	type Input = u32;
 
-	fn read(ttf_reader: &mut TrueTypeFontReader, offset: u32) -> Result<MaximumProfileTable, TrueTypeFontReaderError> {
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<TableDirectory, TrueTypeFontReaderError> {
 		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
 
+		let sfnt_version: u32 = ttf_reader.read_bytes()?;
+		let number_of_tables: u16 = ttf_reader.read_bytes()?;
+		ttf_reader.skip(6)?; // searchRange, entrySelector, rangeShift
+
+		let mut table_records: Vec<TableRecord> = Vec::with_capacity(number_of_tables as usize);
+		for _ in 0..number_of_tables {
+			table_records.push(ttf_reader.read_bytes()?);
+		}
+
+		Ok(TableDirectory {
+			offset,
+			sfnt_version,
+			table_records,
+		})
+	}
+}
+
+impl FromTTFReader for CollectionHeader {
+	type Input = ();
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, _: ()) -> Result<CollectionHeader, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(0))?;
+
+		let tag: u32 = ttf_reader.read_bytes()?;
+		if tag != COLLECTION_TAG {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::Other(['t', 't', 'c', 'f']), "collection should start with the ttcf tag"));
+		}
+
 		let major_version: u16 = ttf_reader.read_bytes()?;
 		let minor_version: u16 = ttf_reader.read_bytes()?;
+		if major_version != 1 && major_version != 2 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::Other(['t', 't', 'c', 'f']), major_version, minor_version));
+		}
 
-		if major_version != 1 || minor_version != 0 {
-			todo!("Version {major_version}.{minor_version} for maxp table is not currently supported");
+		let number_of_fonts: u32 = ttf_reader.read_bytes()?;
+		let mut table_directory_offsets: Vec<u32> = Vec::new();
+		for _ in 0..number_of_fonts {
+			table_directory_offsets.push(ttf_reader.read_bytes()?);
 		}
+		// Version 2 adds a DSIG record after the offsets, which isn't needed.
+
+		Ok(CollectionHeader {
+			major_version,
+			minor_version,
+			number_of_fonts,
+			table_directory_offsets,
+		})
+	}
+}
+
+impl FromTTFReader for MaximumProfileTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<MaximumProfileTable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+
+		let major_version: u16 = ttf_reader.read_bytes()?;
+		let minor_version: u16 = ttf_reader.read_bytes()?;
+
+		let num_glyphs: u16 = match (major_version, minor_version) {
+			(1, 0) => ttf_reader.read_bytes()?,
+			(0, 0x5000) => {
+				// Version 0.5 is used by fonts with CFF outlines and only has the number of glyphs.
+				let num_glyphs: u16 = ttf_reader.read_bytes()?;
+				return Ok(MaximumProfileTable {
+					major_version,
+					minor_version,
+					num_glyphs,
+					max_points: 0,
+					max_contours: 0,
+					max_composite_points: 0,
+					max_composite_contours: 0,
+					max_zones: 0,
+					max_twilight_points: 0,
+					max_storage: 0,
+					max_function_defs: 0,
+					max_instruction_defs: 0,
+					max_stack_elements: 0,
+					max_size_of_instructions: 0,
+					max_component_elements: 0,
+					max_component_depth: 0,
+				});
+			},
+			_ => return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::MaximumProfile, major_version, minor_version)),
+		};
 
-		let num_glyphs: u16 = ttf_reader.read_bytes()?;
 		let max_points: u16 = ttf_reader.read_bytes()?;
 		let max_contours: u16 = ttf_reader.read_bytes()?;
 		let max_composite_points: u16 = ttf_reader.read_bytes()?;
@@ -501,13 +878,13 @@ impl FromTTFReader for MaximumProfileTable {
 impl FromTTFReader for FontHeaderTable {
 	type Input = u32;
 
-	fn read(ttf_reader: &mut TrueTypeFontReader, offset: u32) -> Result<FontHeaderTable, TrueTypeFontReaderError> {
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<FontHeaderTable, TrueTypeFontReaderError> {
 		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
 		let major_version = ttf_reader.read_bytes()?;
 		let minor_version = ttf_reader.read_bytes()?;
 
 		if major_version != 1 || minor_version != 0 {
-			todo!("Version {major_version}.{minor_version} for head table is not currently supported");
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::FontHeader, major_version, minor_version));
 		}
 
 		Ok(FontHeaderTable {
@@ -536,13 +913,13 @@ impl FromTTFReader for FontHeaderTable {
 impl FromTTFReader for HorizontalHeaderTable {
 	type Input = u32;
 
-	fn read(ttf_reader: &mut TrueTypeFontReader, offset: u32) -> Result<HorizontalHeaderTable, TrueTypeFontReaderError> {
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<HorizontalHeaderTable, TrueTypeFontReaderError> {
 		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
 		let major_version = ttf_reader.read_bytes()?;
 		let minor_version = ttf_reader.read_bytes()?;
 
 		if major_version != 1 || minor_version != 0 {
-			todo!("Version {major_version}.{minor_version} for hhea table is not currently supported");
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::HorizontalHeaderTable, major_version, minor_version));
 		}
 
 		let ascender = ttf_reader.read_bytes()?;
@@ -561,7 +938,7 @@ impl FromTTFReader for HorizontalHeaderTable {
 		let reserved: i16 = ttf_reader.read_bytes()?;
 		let metric_data_format: i16 = ttf_reader.read_bytes()?;
 		if metric_data_format != 0 {
-			todo!("Metric Data Format {metric_data_format} from hhea is not currently supported");
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::HorizontalHeaderTable, "metric data format should be 0"));
 		}
 		let number_of_horizontal_metrics = ttf_reader.read_bytes()?;
 
@@ -587,7 +964,7 @@ impl FromTTFReader for HorizontalHeaderTable {
 impl FromTTFReader for GlyphOffset {
 	type Input = (i16, u16);
 
-	fn read(ttf_reader: &mut TrueTypeFontReader, (index_to_location_format, count): (i16, u16)) -> Result<GlyphOffset, TrueTypeFontReaderError> {
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (index_to_location_format, count): (i16, u16)) -> Result<GlyphOffset, TrueTypeFontReaderError> {
 		let glyph_offset: u32;
 		if index_to_location_format == 0 {
 			let half_glyph_offset: u16 = ttf_reader.read_bytes()?;
@@ -595,7 +972,7 @@ impl FromTTFReader for GlyphOffset {
 		} else if index_to_location_format == 1 {
 			glyph_offset = ttf_reader.read_bytes()?;
 		} else {
-			panic!("Only 0 and 1 are valid values for the index_to_location_format.")
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::FontHeader, "only 0 and 1 are valid values for the index_to_location_format"));
 		}
 
 		let result = Ok(GlyphOffset {
@@ -611,7 +988,7 @@ impl FromTTFReader for GlyphOffset {
 impl FromTTFReader for IndexToLocationTable {
 	type Input = (u32, i16, u16);
 
-	fn read(ttf_reader: &mut TrueTypeFontReader, (offset, index_to_location_format, num_glyphs): (u32, i16, u16)) -> Result<IndexToLocationTable, TrueTypeFontReaderError> {
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (offset, index_to_location_format, num_glyphs): (u32, i16, u16)) -> Result<IndexToLocationTable, TrueTypeFontReaderError> {
 		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
 
 		let mut previous: GlyphOffset = ttf_reader.read((index_to_location_format, 0))?;
@@ -620,7 +997,10 @@ impl FromTTFReader for IndexToLocationTable {
 		for count in 1..=num_glyphs {
 			let current: GlyphOffset = ttf_reader.read((index_to_location_format, count))?;
 			if current.glyph_offset == previous.glyph_offset { previous.glyph_offset = None; } else {
-				previous.glyph_length = Some(current.glyph_offset.unwrap() - previous.glyph_offset.unwrap());
+				match current.glyph_offset.unwrap().checked_sub(previous.glyph_offset.unwrap()) {
+					Some(glyph_length) => previous.glyph_length = Some(glyph_length),
+					None => return Err(TrueTypeFontReaderError::InvalidData(TableTag::IndexToLocation, "glyph offsets should be in ascending order")),
+				}
 			};
 			glyph_offsets.push(previous);
 			previous = current;
@@ -635,7 +1015,7 @@ impl FromTTFReader for IndexToLocationTable {
 impl FromTTFReader for GlyphRaw {
 	type Input = (GlyphOffset, u64);
 
-	fn read(ttf_reader: &mut TrueTypeFontReader, (glyph_offset, glyph_table_start): (GlyphOffset, u64)) -> Result<GlyphRaw, TrueTypeFontReaderError> {
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (glyph_offset, glyph_table_start): (GlyphOffset, u64)) -> Result<GlyphRaw, TrueTypeFontReaderError> {
 		match glyph_offset.glyph_offset {
 			Some(glyph_offset) => ttf_reader.buffer_reader.seek(io::SeekFrom::Start(glyph_table_start + glyph_offset as u64))?,
 			None => {
@@ -773,9 +1153,11 @@ impl FromTTFReader for GlyphRaw {
 		} else if number_of_contours < 0 {
 			// COMPOSITE GLYPH START
 			let mut more = true;
+			let mut have_instructions = false;
 			let mut children: Vec<ComponentGlyphRaw> = Vec::new();
 			while more {
 				let flag: u16 = ttf_reader.read_bytes()?;
+				have_instructions |= flag & 0x0100 > 0;
 				let glyph_index: u16 = ttf_reader.read_bytes()?;
 
 				let xy_long = flag & 0x0001 > 0;
@@ -791,11 +1173,11 @@ impl FromTTFReader for GlyphRaw {
 						y_offset_point = y_offset_point_long as i32;
 					},
 					(true, false) => {
+						// Without ARGS_ARE_XY_VALUES these are point numbers, the composite's then the component's, that should line up.
 						let x_offset_point_long: u16 = ttf_reader.read_bytes()?;
 						let y_offset_point_long: u16 = ttf_reader.read_bytes()?;
 						x_offset_point = x_offset_point_long as i32;
 						y_offset_point = y_offset_point_long as i32;
-						panic!("WHAT IS THIS INSANITY");
 					},
 					(false, true) => {
 						let x_offset_point_short: i8 = ttf_reader.read_bytes()?;
@@ -804,11 +1186,11 @@ impl FromTTFReader for GlyphRaw {
 						y_offset_point = y_offset_point_short as i32;
 					},
 					(false, false) => {
+						// Without ARGS_ARE_XY_VALUES these are point numbers, the composite's then the component's, that should line up.
 						let x_offset_point_short: u8 = ttf_reader.read_bytes()?;
 						let y_offset_point_short: u8 = ttf_reader.read_bytes()?;
 						x_offset_point = x_offset_point_short as i32;
 						y_offset_point = y_offset_point_short as i32;
-						panic!("WHAT IS THIS INSTANITY");
 					},
 				}
 
@@ -847,6 +1229,14 @@ impl FromTTFReader for GlyphRaw {
 				});
 			}
 
+			let mut instructions: Vec<u8> = Vec::new();
+			if have_instructions {
+				let instruction_length: u16 = ttf_reader.read_bytes()?;
+				for _ in 0..instruction_length {
+					instructions.push(ttf_reader.read_bytes()?);
+				}
+			}
+
 			Ok(GlyphRaw {
 				number_of_contours,
 				x_min,
@@ -855,6 +1245,7 @@ impl FromTTFReader for GlyphRaw {
 				y_max,
 				glyph_data: GlyphDataRaw::CompositeGlyphRaw(CompositeGlyphRaw {
 					children,
+					instructions,
 				}),
 			})
 			// COMPOSITE GLYPH END
@@ -874,7 +1265,7 @@ impl FromTTFReader for GlyphRaw {
 impl FromTTFReader for GlyphTable {
 	type Input = (Vec<GlyphOffset>, u64);
 
-	fn read(ttf_reader: &mut TrueTypeFontReader, (glyph_offsets, glyph_table_start): (Vec<GlyphOffset>, u64)) -> Result<GlyphTable, TrueTypeFontReaderError> {
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (glyph_offsets, glyph_table_start): (Vec<GlyphOffset>, u64)) -> Result<GlyphTable, TrueTypeFontReaderError> {
 		let mut glyphs: Vec<GlyphRaw> = Vec::new();
 		for glyph_offset in glyph_offsets {
 			let glyph: GlyphRaw = ttf_reader.read((glyph_offset.clone(), glyph_table_start))?;
@@ -886,10 +1277,95 @@ impl FromTTFReader for GlyphTable {
 	}
 }
 
+/// Font data that glyph outlines can go on being read from after the font is loaded, whatever it's read from.
+pub trait GlyphSource: Send {
+	fn read_glyph(&mut self, glyph_offset: GlyphOffset, glyph_table_start: u64) -> Result<GlyphRaw, TrueTypeFontReaderError>;
+}
+
+impl<Source: Read + Seek + Send> GlyphSource for TrueTypeFontReader<Source> {
+	fn read_glyph(&mut self, glyph_offset: GlyphOffset, glyph_table_start: u64) -> Result<GlyphRaw, TrueTypeFontReaderError> {
+		self.read((glyph_offset, glyph_table_start))
+	}
+}
+
+pub(crate) fn read_uint24<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>) -> Result<u32, TrueTypeFontReaderError> {
+	let high: u8 = ttf_reader.read_bytes()?;
+	let low: u16 = ttf_reader.read_bytes()?;
+	Ok((high as u32) << 16 | low as u32)
+}
+
+impl FromTTFReader for CharacterToGlyphIndexSubtableFormat0 {
+	type Input = ();
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, _: ()) -> Result<Self, TrueTypeFontReaderError> {
+		let length: u16 = ttf_reader.read_bytes()?;
+		let language: u16 = ttf_reader.read_bytes()?;
+
+		let mut glyph_id_array: Vec<u8> = Vec::with_capacity(256);
+		for _ in 0..256 {
+			glyph_id_array.push(ttf_reader.read_bytes()?);
+		}
+
+		Ok(CharacterToGlyphIndexSubtableFormat0 {
+			length,
+			language,
+			glyph_id_array,
+		})
+	}
+}
+
+impl FromTTFReader for CharacterToGlyphIndexSubtableFormat2 {
+	type Input = ();
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, _: ()) -> Result<Self, TrueTypeFontReaderError> {
+		let length: u16 = ttf_reader.read_bytes()?;
+		let language: u16 = ttf_reader.read_bytes()?;
+
+		let mut sub_header_keys: Vec<u16> = Vec::with_capacity(256);
+		for _ in 0..256 {
+			sub_header_keys.push(ttf_reader.read_bytes()?);
+		}
+
+		let number_of_sub_headers = sub_header_keys.iter().max().copied().unwrap_or(0) as usize / 8 + 1;
+		let header_length = 6 + 2 * 256 + 8 * number_of_sub_headers;
+		if header_length > length as usize {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, "format 2 sub headers should fit in the subtable"));
+		}
+		let mut sub_headers: Vec<SubHeader> = Vec::with_capacity(number_of_sub_headers);
+		for _ in 0..number_of_sub_headers {
+			let sub_header = SubHeader {
+				first_code: ttf_reader.read_bytes()?,
+				entry_count: ttf_reader.read_bytes()?,
+				id_delta: ttf_reader.read_bytes()?,
+				id_range_offset: ttf_reader.read_bytes()?,
+			};
+			if sub_header.first_code as u32 + sub_header.entry_count as u32 > 256 {
+				return Err(TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, "format 2 sub headers should only map low bytes"));
+			}
+			sub_headers.push(sub_header);
+		}
+
+		// The glyph id array fills whatever is left of the subtable.
+		let glyph_ids_to_read = (length as usize - header_length) / 2;
+		let mut glyph_id_array: Vec<u16> = Vec::with_capacity(glyph_ids_to_read);
+		for _ in 0..glyph_ids_to_read {
+			glyph_id_array.push(ttf_reader.read_bytes()?);
+		}
+
+		Ok(CharacterToGlyphIndexSubtableFormat2 {
+			length,
+			language,
+			sub_header_keys,
+			sub_headers,
+			glyph_id_array,
+		})
+	}
+}
+
 impl FromTTFReader for CharacterToGlyphIndexSubtableFormat4 {
 	type Input = (u32, u32);
 
-	fn read(ttf_reader: &mut TrueTypeFontReader, (subtable_offset, cmap_table_offset): (u32, u32)) -> Result<Self, TrueTypeFontReaderError> {
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (subtable_offset, cmap_table_offset): (u32, u32)) -> Result<Self, TrueTypeFontReaderError> {
 		let length: u16 = ttf_reader.read_bytes()?;
 		let language: u16 = ttf_reader.read_bytes()?;
 		let segment_count_x2: u16 = ttf_reader.read_bytes()?;
@@ -904,7 +1380,9 @@ impl FromTTFReader for CharacterToGlyphIndexSubtableFormat4 {
 		};
 
 		let reserved_pad: u16 = ttf_reader.read_bytes()?;
-		assert_eq!(reserved_pad, 0);
+		if reserved_pad != 0 {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, "format 4 reserved pad should be 0"));
+		}
 
 		let mut start_codes: Vec<u16> = Vec::new();
 		for _ in 0..segment_count {
@@ -922,8 +1400,8 @@ impl FromTTFReader for CharacterToGlyphIndexSubtableFormat4 {
 		}
 
 		let current_position = ttf_reader.buffer_reader.seek(io::SeekFrom::Current(0))?;
-		let current_position_from_table = current_position - (subtable_offset + cmap_table_offset) as u64;
-		let remaining_size = length as u64 - current_position_from_table;
+		let current_position_from_table = current_position.saturating_sub((subtable_offset + cmap_table_offset) as u64);
+		let remaining_size = (length as u64).saturating_sub(current_position_from_table);
 		let glyph_ids_to_read = remaining_size / 2;
 
 		let mut glyph_id_array: Vec<u16> = Vec::new();
@@ -950,12 +1428,17 @@ impl FromTTFReader for CharacterToGlyphIndexSubtableFormat4 {
 impl FromTTFReader for CharacterToGlyphIndexSubtableFormat12 {
 	type Input = ();
 
-	fn read(ttf_reader: &mut TrueTypeFontReader, _: ()) -> Result<Self, TrueTypeFontReaderError> {
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, _: ()) -> Result<Self, TrueTypeFontReaderError> {
 		let reserved: u16 = ttf_reader.read_bytes()?;
-		assert_eq!(reserved, 0);
+		if reserved != 0 {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, "format 12 reserved field should be 0"));
+		}
 		let length: u32 = ttf_reader.read_bytes()?;
 		let language: u32 = ttf_reader.read_bytes()?;
-		let num_groups: u32 =ttf_reader.read_bytes()?;
+		let num_groups: u32 = ttf_reader.read_bytes()?;
+		if num_groups as u64 * 12 + 16 > length as u64 || num_groups as u64 * 12 > ttf_reader.remaining()? {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, "format 12 groups should fit in the subtable"));
+		}
 
 		let mut groups: Vec<(u32, u32, u32)> = Vec::with_capacity(num_groups as usize);
 		for _ in 0..num_groups {
@@ -973,23 +1456,189 @@ impl FromTTFReader for CharacterToGlyphIndexSubtableFormat12 {
 	}
 }
 
+impl FromTTFReader for CharacterToGlyphIndexSubtableFormat6 {
+	type Input = ();
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, _: ()) -> Result<Self, TrueTypeFontReaderError> {
+		let length: u16 = ttf_reader.read_bytes()?;
+		let language: u16 = ttf_reader.read_bytes()?;
+		let first_code: u16 = ttf_reader.read_bytes()?;
+		let entry_count: u16 = ttf_reader.read_bytes()?;
+
+		let mut glyph_id_array: Vec<u16> = Vec::with_capacity(entry_count as usize);
+		for _ in 0..entry_count {
+			glyph_id_array.push(ttf_reader.read_bytes()?);
+		}
+
+		Ok(CharacterToGlyphIndexSubtableFormat6 {
+			length,
+			language,
+			first_code,
+			glyph_id_array,
+		})
+	}
+}
+
+impl FromTTFReader for CharacterToGlyphIndexSubtableFormat10 {
+	type Input = ();
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, _: ()) -> Result<Self, TrueTypeFontReaderError> {
+		let reserved: u16 = ttf_reader.read_bytes()?;
+		if reserved != 0 {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, "format 10 reserved field should be 0"));
+		}
+		let length: u32 = ttf_reader.read_bytes()?;
+		let language: u32 = ttf_reader.read_bytes()?;
+		let start_char_code: u32 = ttf_reader.read_bytes()?;
+		let number_of_chars: u32 = ttf_reader.read_bytes()?;
+		if number_of_chars as u64 * 2 + 20 > length as u64 {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, "format 10 glyphs should fit in the subtable"));
+		}
+
+		let mut glyph_id_array: Vec<u16> = Vec::with_capacity(number_of_chars as usize);
+		for _ in 0..number_of_chars {
+			glyph_id_array.push(ttf_reader.read_bytes()?);
+		}
+
+		Ok(CharacterToGlyphIndexSubtableFormat10 {
+			length,
+			language,
+			start_char_code,
+			glyph_id_array,
+		})
+	}
+}
+
+impl FromTTFReader for CharacterToGlyphIndexSubtableFormat13 {
+	type Input = ();
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, _: ()) -> Result<Self, TrueTypeFontReaderError> {
+		let reserved: u16 = ttf_reader.read_bytes()?;
+		if reserved != 0 {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, "format 13 reserved field should be 0"));
+		}
+		let length: u32 = ttf_reader.read_bytes()?;
+		let language: u32 = ttf_reader.read_bytes()?;
+		let num_groups: u32 = ttf_reader.read_bytes()?;
+		if num_groups as u64 * 12 + 16 > length as u64 || num_groups as u64 * 12 > ttf_reader.remaining()? {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, "format 13 groups should fit in the subtable"));
+		}
+
+		let mut groups: Vec<(u32, u32, u32)> = Vec::with_capacity(num_groups as usize);
+		for _ in 0..num_groups {
+			let start_code: u32 = ttf_reader.read_bytes()?;
+			let end_code: u32 = ttf_reader.read_bytes()?;
+			let glyph_id: u32 = ttf_reader.read_bytes()?;
+			groups.push((start_code, end_code, glyph_id));
+		}
+
+		Ok(CharacterToGlyphIndexSubtableFormat13 {
+			length,
+			language,
+			groups,
+		})
+	}
+}
+
+impl FromTTFReader for CharacterToGlyphIndexSubtableFormat14 {
+	type Input = u64; // offset of the subtable
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, subtable_offset: u64) -> Result<Self, TrueTypeFontReaderError> {
+		let length: u32 = ttf_reader.read_bytes()?;
+		let number_of_variation_selector_records: u32 = ttf_reader.read_bytes()?;
+		if number_of_variation_selector_records as u64 * 11 + 10 > length as u64 || number_of_variation_selector_records as u64 * 11 > ttf_reader.remaining()? {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, "format 14 variation selector records should fit in the subtable"));
+		}
+
+		let mut records: Vec<(u32, u32, u32)> = Vec::with_capacity(number_of_variation_selector_records as usize);
+		for _ in 0..number_of_variation_selector_records {
+			let variation_selector = read_uint24(ttf_reader)?;
+			let default_uvs_offset: u32 = ttf_reader.read_bytes()?;
+			let non_default_uvs_offset: u32 = ttf_reader.read_bytes()?;
+			records.push((variation_selector, default_uvs_offset, non_default_uvs_offset));
+		}
+
+		let mut variation_selector_records: Vec<VariationSelectorRecord> = Vec::with_capacity(records.len());
+		for (variation_selector, default_uvs_offset, non_default_uvs_offset) in records {
+			let mut default_uvs: Vec<(u32, u8)> = Vec::new();
+			if default_uvs_offset != 0 {
+				ttf_reader.buffer_reader.seek(io::SeekFrom::Start(subtable_offset + default_uvs_offset as u64))?;
+				let number_of_unicode_value_ranges: u32 = ttf_reader.read_bytes()?;
+				if default_uvs_offset as u64 + 4 + number_of_unicode_value_ranges as u64 * 4 > length as u64 {
+					return Err(TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, "format 14 default UVS ranges should fit in the subtable"));
+				}
+				for _ in 0..number_of_unicode_value_ranges {
+					default_uvs.push((read_uint24(ttf_reader)?, ttf_reader.read_bytes()?));
+				}
+			}
+
+			let mut non_default_uvs: Vec<(u32, u16)> = Vec::new();
+			if non_default_uvs_offset != 0 {
+				ttf_reader.buffer_reader.seek(io::SeekFrom::Start(subtable_offset + non_default_uvs_offset as u64))?;
+				let number_of_uvs_mappings: u32 = ttf_reader.read_bytes()?;
+				if non_default_uvs_offset as u64 + 4 + number_of_uvs_mappings as u64 * 5 > length as u64 {
+					return Err(TrueTypeFontReaderError::InvalidData(TableTag::CharacterToGlyphIndex, "format 14 non-default UVS mappings should fit in the subtable"));
+				}
+				for _ in 0..number_of_uvs_mappings {
+					non_default_uvs.push((read_uint24(ttf_reader)?, ttf_reader.read_bytes()?));
+				}
+			}
+
+			variation_selector_records.push(VariationSelectorRecord {
+				variation_selector,
+				default_uvs,
+				non_default_uvs,
+			});
+		}
+
+		Ok(CharacterToGlyphIndexSubtableFormat14 {
+			length,
+			variation_selector_records,
+		})
+	}
+}
+
 impl FromTTFReader for CharacterToGlyphIndexSubtable {
 	type Input = (u32, u32);
 
-	fn read(ttf_reader: &mut TrueTypeFontReader, (subtable_offset, cmap_table_offset): (u32, u32)) -> Result<CharacterToGlyphIndexSubtable, TrueTypeFontReaderError> {
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (subtable_offset, cmap_table_offset): (u32, u32)) -> Result<CharacterToGlyphIndexSubtable, TrueTypeFontReaderError> {
 		ttf_reader.buffer_reader.seek(io::SeekFrom::Start((subtable_offset + cmap_table_offset) as u64))?;
 		
 		let format: u16 = ttf_reader.read_bytes()?;
 
 		match format {
+			0 => {
+				let subtable: CharacterToGlyphIndexSubtableFormat0 = ttf_reader.read(())?;
+				Ok(CharacterToGlyphIndexSubtable::Format0(subtable))
+			},
+			2 => {
+				let subtable: CharacterToGlyphIndexSubtableFormat2 = ttf_reader.read(())?;
+				Ok(CharacterToGlyphIndexSubtable::Format2(subtable))
+			},
 			4 => {
 				let subtable: CharacterToGlyphIndexSubtableFormat4 = ttf_reader.read((subtable_offset, cmap_table_offset))?;
 				Ok(CharacterToGlyphIndexSubtable::Format4(subtable))
 			},
+			6 => {
+				let subtable: CharacterToGlyphIndexSubtableFormat6 = ttf_reader.read(())?;
+				Ok(CharacterToGlyphIndexSubtable::Format6(subtable))
+			},
+			10 => {
+				let subtable: CharacterToGlyphIndexSubtableFormat10 = ttf_reader.read(())?;
+				Ok(CharacterToGlyphIndexSubtable::Format10(subtable))
+			},
 			12 => {
 				let subtable: CharacterToGlyphIndexSubtableFormat12 = ttf_reader.read(())?;
 				Ok(CharacterToGlyphIndexSubtable::Format12(subtable))
 			},
+			13 => {
+				let subtable: CharacterToGlyphIndexSubtableFormat13 = ttf_reader.read(())?;
+				Ok(CharacterToGlyphIndexSubtable::Format13(subtable))
+			},
+			14 => {
+				let subtable: CharacterToGlyphIndexSubtableFormat14 = ttf_reader.read((subtable_offset + cmap_table_offset) as u64)?;
+				Ok(CharacterToGlyphIndexSubtable::Format14(subtable))
+			},
 			_ => {
 				Ok(CharacterToGlyphIndexSubtable::InvalidFormat(format as usize))
 			}
@@ -1000,12 +1649,12 @@ impl FromTTFReader for CharacterToGlyphIndexSubtable {
 impl FromTTFReader for CharacterToGlyphIndexTable {
 	type Input = u32;
 
-	fn read(ttf_reader: &mut TrueTypeFontReader, offset: u32) -> Result<CharacterToGlyphIndexTable, TrueTypeFontReaderError> {
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<CharacterToGlyphIndexTable, TrueTypeFontReaderError> {
 		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
 
 		let version: u16 = ttf_reader.read_bytes()?;
 		if version != 0 {
-			todo!("Version {version} for cmap table is not currently supported");
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::CharacterToGlyphIndex, version, 0));
 		}
 		let number_of_subtables: u16 = ttf_reader.read_bytes()?;
 
@@ -1031,7 +1680,7 @@ impl FromTTFReader for CharacterToGlyphIndexTable {
 impl FromTTFReader for HorizontalMetricsTable {
 	type Input = (u16, u16, u64);
 
-	fn read(ttf_reader: &mut TrueTypeFontReader, (number_of_horizontal_metrics, number_of_glyphs, offset): (u16, u16, u64)) -> Result<HorizontalMetricsTable, TrueTypeFontReaderError> {
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (number_of_horizontal_metrics, number_of_glyphs, offset): (u16, u16, u64)) -> Result<HorizontalMetricsTable, TrueTypeFontReaderError> {
 		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
 
 		let mut horizontal_metrics = Vec::with_capacity(number_of_glyphs as usize);
@@ -1043,7 +1692,7 @@ impl FromTTFReader for HorizontalMetricsTable {
 			horizontal_metrics.push(HorizontalMetric { advance_width, left_side_bearing });
 		}
 
-		for _ in 0..(number_of_glyphs - number_of_horizontal_metrics) {
+		for _ in 0..number_of_glyphs.saturating_sub(number_of_horizontal_metrics) {
 			let left_side_bearing = ttf_reader.read_bytes()?;
 			horizontal_metrics.push(HorizontalMetric { advance_width: most_recent_advance_width, left_side_bearing });
 		}
@@ -1054,21 +1703,116 @@ impl FromTTFReader for HorizontalMetricsTable {
 	}
 }
 
+impl FromTTFReader for VerticalHeaderTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<VerticalHeaderTable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+		let major_version: u16 = ttf_reader.read_bytes()?;
+		let minor_version: u16 = ttf_reader.read_bytes()?;
+
+		// Version 1.1 is stored as 0x00011000, and only renames the first three fields to make clear they are typographic.
+		if major_version != 1 || (minor_version != 0 && minor_version != 0x1000) {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::VerticalHeaderTable, major_version, minor_version));
+		}
+
+		let ascender = ttf_reader.read_bytes()?;
+		let descender = ttf_reader.read_bytes()?;
+		let line_gap = ttf_reader.read_bytes()?;
+		let advance_height_max = ttf_reader.read_bytes()?;
+		let minimum_top_side_bearing = ttf_reader.read_bytes()?;
+		let minimum_bottom_side_bearing = ttf_reader.read_bytes()?;
+		let y_max_extent = ttf_reader.read_bytes()?;
+		let caret_slope_rise = ttf_reader.read_bytes()?;
+		let caret_slope_run = ttf_reader.read_bytes()?;
+		let caret_offset = ttf_reader.read_bytes()?;
+		ttf_reader.skip(8)?; // reserved
+		let metric_data_format: i16 = ttf_reader.read_bytes()?;
+		if metric_data_format != 0 {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::VerticalHeaderTable, "metric data format should be 0"));
+		}
+		let number_of_vertical_metrics = ttf_reader.read_bytes()?;
+
+		Ok(VerticalHeaderTable {
+			ascender,
+			descender,
+			line_gap,
+			advance_height_max,
+			minimum_top_side_bearing,
+			minimum_bottom_side_bearing,
+			y_max_extent,
+			caret_slope_rise,
+			caret_slope_run,
+			caret_offset,
+			number_of_vertical_metrics,
+		})
+	}
+}
+
+impl FromTTFReader for VerticalMetricsTable {
+	type Input = (u16, u16, u64);
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (number_of_vertical_metrics, number_of_glyphs, offset): (u16, u16, u64)) -> Result<VerticalMetricsTable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+
+		let mut vertical_metrics = Vec::with_capacity(number_of_glyphs as usize);
+		let mut most_recent_advance_height = 0;
+		for _ in 0..number_of_vertical_metrics {
+			let advance_height = ttf_reader.read_bytes()?;
+			let top_side_bearing = ttf_reader.read_bytes()?;
+			most_recent_advance_height = advance_height;
+			vertical_metrics.push(VerticalMetric { advance_height, top_side_bearing });
+		}
+
+		for _ in 0..number_of_glyphs.saturating_sub(number_of_vertical_metrics) {
+			let top_side_bearing = ttf_reader.read_bytes()?;
+			vertical_metrics.push(VerticalMetric { advance_height: most_recent_advance_height, top_side_bearing });
+		}
+
+		Ok(VerticalMetricsTable {
+			vertical_metrics,
+		})
+	}
+}
+
+impl FromTTFReader for VerticalOriginTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<VerticalOriginTable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+		let major_version: u16 = ttf_reader.read_bytes()?;
+		let minor_version: u16 = ttf_reader.read_bytes()?;
+		if major_version != 1 || minor_version != 0 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::VerticalOrigin, major_version, minor_version));
+		}
+
+		let default_vertical_origin_y = ttf_reader.read_bytes()?;
+		let number_of_vertical_origins: u16 = ttf_reader.read_bytes()?;
+		let mut vertical_origins = Vec::with_capacity(number_of_vertical_origins as usize);
+		for _ in 0..number_of_vertical_origins {
+			vertical_origins.push((ttf_reader.read_bytes()?, ttf_reader.read_bytes()?));
+		}
+
+		Ok(VerticalOriginTable {
+			default_vertical_origin_y,
+			vertical_origins,
+		})
+	}
+}
+
 impl FromTTFReader for OS2AndWindowsMetricsTable {
 	type Input = u32;
 
-	fn read(ttf_reader: &mut TrueTypeFontReader, offset: u32) -> Result<OS2AndWindowsMetricsTable, TrueTypeFontReaderError> {
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<OS2AndWindowsMetricsTable, TrueTypeFontReaderError> {
 		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
 
 		let version: u16 = ttf_reader.read_bytes()?;
 
-		if version != 4 {
-			todo!("Version {version} for OS/2 table is not currently supported");
+		if version > 5 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::OS2AndWindowsMetricsTable, version, 0));
 		}
 
-		println!("Size of OS/2 table: {}", size_of::<OS2AndWindowsMetricsTable>());
-
-		Ok(OS2AndWindowsMetricsTable {
+		let mut table = OS2AndWindowsMetricsTable {
 			version,
 			x_average_character_width: ttf_reader.read_bytes()?,
 			us_weight_class: ttf_reader.read_bytes()?,
@@ -1099,13 +1843,29 @@ impl FromTTFReader for OS2AndWindowsMetricsTable {
 			s_typographic_line_gap: ttf_reader.read_bytes()?,
 			us_windows_ascent: ttf_reader.read_bytes()?,
 			us_windows_descend: ttf_reader.read_bytes()?,
-			ul_code_page_range_1: ttf_reader.read_bytes()?,
-			ul_code_page_range_2: ttf_reader.read_bytes()?,
-			sx_height: ttf_reader.read_bytes()?,
-			s_cap_height: ttf_reader.read_bytes()?,
-			us_default_character: ttf_reader.read_bytes()?,
-			us_break_character: ttf_reader.read_bytes()?,
-			us_max_context: ttf_reader.read_bytes()?,
-		})
+			ul_code_page_range_1: 0,
+			ul_code_page_range_2: 0,
+			sx_height: 0,
+			s_cap_height: 0,
+			us_default_character: 0,
+			us_break_character: 0,
+			us_max_context: 0,
+		};
+
+		// Version 0 stops at usWinDescent, version 1 adds the code page ranges and version 2 onwards add the rest.
+		if version >= 1 {
+			table.ul_code_page_range_1 = ttf_reader.read_bytes()?;
+			table.ul_code_page_range_2 = ttf_reader.read_bytes()?;
+		}
+
+		if version >= 2 {
+			table.sx_height = ttf_reader.read_bytes()?;
+			table.s_cap_height = ttf_reader.read_bytes()?;
+			table.us_default_character = ttf_reader.read_bytes()?;
+			table.us_break_character = ttf_reader.read_bytes()?;
+			table.us_max_context = ttf_reader.read_bytes()?;
+		}
+
+		Ok(table)
 	}
-}
\ No newline at end of file
+}
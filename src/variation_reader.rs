@@ -0,0 +1,825 @@
+use std::io::{self, Read, Seek};
+
+use crate::ttf_reader::{FromTTFReader, GlyphDataRaw, GlyphRaw, HorizontalMetric, TableTag, TrueTypeFontReader, TrueTypeFontReaderError};
+
+const LONG_WORDS_FLAG: u16 = 0x8000;
+const WORD_DELTA_COUNT_MASK: u16 = 0x7FFF;
+
+const LONG_OFFSETS_FLAG: u16 = 0x0001;
+const SHARED_POINT_NUMBERS_FLAG: u16 = 0x8000;
+const TUPLE_COUNT_MASK: u16 = 0x0FFF;
+const EMBEDDED_PEAK_TUPLE_FLAG: u16 = 0x8000;
+const INTERMEDIATE_REGION_FLAG: u16 = 0x4000;
+const PRIVATE_POINT_NUMBERS_FLAG: u16 = 0x2000;
+const TUPLE_INDEX_MASK: u16 = 0x0FFF;
+const POINTS_ARE_WORDS_FLAG: u8 = 0x80;
+const POINT_RUN_COUNT_MASK: u8 = 0x7F;
+const DELTAS_ARE_ZERO_FLAG: u8 = 0x80;
+const DELTAS_ARE_WORDS_FLAG: u8 = 0x40;
+const DELTA_RUN_COUNT_MASK: u8 = 0x3F;
+const ARGS_ARE_XY_VALUES_FLAG: u16 = 0x0002;
+const MAP_ENTRY_SIZE_MASK: u8 = 0x30;
+const INNER_INDEX_BIT_COUNT_MASK: u8 = 0x0F;
+
+#[derive(Debug, Clone)]
+pub struct VariationAxisRecord {
+	pub axis_tag: [u8; 4],
+	pub min_value: f32,
+	pub default_value: f32,
+	pub max_value: f32,
+	pub flags: u16,
+	pub axis_name_id: u16,
+}
+
+/// A named point in the design space, such as "Semibold Condensed".
+#[derive(Debug, Clone)]
+pub struct InstanceRecord {
+	pub subfamily_name_id: u16,
+	pub flags: u16,
+	pub coordinates: Vec<f32>, // User space value on each axis
+	pub postscript_name_id: Option<u16>,
+}
+
+pub struct FontVariationsTable {
+	pub axes: Vec<VariationAxisRecord>,
+	pub instances: Vec<InstanceRecord>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RegionAxisCoordinates {
+	pub start_coordinate: f32,
+	pub peak_coordinate: f32,
+	pub end_coordinate: f32,
+}
+
+pub struct ItemVariationData {
+	pub region_indices: Vec<u16>,
+	pub delta_sets: Vec<Vec<i32>>, // one delta per region index, for each item
+}
+
+/// Deltas for a glyph's points, which apply in one region of the design space.
+pub struct TupleVariation {
+	pub region: Vec<RegionAxisCoordinates>, // One per axis
+	pub point_numbers: Option<Vec<u16>>, // None when there's a delta for every point, including the phantom points
+	pub x_deltas: Vec<i32>,
+	pub y_deltas: Vec<i32>,
+}
+
+pub struct GlyphVariationsTable {
+	pub axis_count: u16,
+	pub glyph_variations: Vec<Vec<TupleVariation>>, // The tuple variations of each glyph
+}
+
+/// Maps each axis's normalised coordinates on to others, from avar, so an axis can change faster in some parts of its range.
+pub struct AxisVariationsTable {
+	pub segment_maps: Vec<Vec<(f32, f32)>>, // For each axis, pairs of coordinates to map from and to, in order
+}
+
+/// Maps glyph ids, or other items, to the outer and inner indices of their deltas in an item variation store.
+pub struct DeltaSetIndexMap {
+	pub entries: Vec<(u16, u16)>,
+}
+
+pub struct HorizontalMetricsVariationsTable {
+	pub item_variation_store: ItemVariationStore,
+	pub advance_width_mapping: Option<DeltaSetIndexMap>, // Without one, glyph ids are inner indices into the first item variation data
+	pub left_side_bearing_mapping: Option<DeltaSetIndexMap>,
+	pub right_side_bearing_mapping: Option<DeltaSetIndexMap>,
+}
+
+/// Deltas for font-wide metrics, such as the ascender, each identified by a tag like `*b"hasc"`.
+pub struct MetricsVariationsTable {
+	pub item_variation_store: Option<ItemVariationStore>,
+	pub value_records: Vec<([u8; 4], u16, u16)>, // Value tag, outer index, inner index
+}
+
+pub struct ItemVariationStore {
+	pub variation_regions: Vec<Vec<RegionAxisCoordinates>>, // one set of coordinates per axis, for each region
+	pub item_variation_data: Vec<ItemVariationData>,
+}
+
+fn fixed_to_f32(value: u32) -> f32 {
+	value as i32 as f32 / 65536.0
+}
+
+fn f2dot14_to_f32(value: i16) -> f32 {
+	value as f32 / 16384.0
+}
+
+impl VariationAxisRecord {
+	/// Maps a user space value on this axis to the normalised range -1 to 1, with the default value at 0.
+	pub fn normalise(&self, value: f32) -> f32 {
+		let value = value.clamp(self.min_value, self.max_value);
+		let normalised = if value < self.default_value {
+			-(self.default_value - value) / (self.default_value - self.min_value)
+		} else if value > self.default_value {
+			(value - self.default_value) / (self.max_value - self.default_value)
+		} else {
+			0.0
+		};
+		// Normalised coordinates are stored as F2DOT14 everywhere else, so round to that precision.
+		(normalised * 16384.0).round() / 16384.0
+	}
+}
+
+/// How much a region applies at the given normalised coordinates, between 0 and 1.
+fn region_scalar(region: &[RegionAxisCoordinates], coordinates: &[f32]) -> f32 {
+	let mut scalar = 1.0;
+	for (axis_index, axis) in region.iter().enumerate() {
+		let coordinate = coordinates.get(axis_index).copied().unwrap_or(0.0);
+		let (start, peak, end) = (axis.start_coordinate, axis.peak_coordinate, axis.end_coordinate);
+		if start > peak || peak > end || (start < 0.0 && end > 0.0) || peak == 0.0 || coordinate == peak {
+			continue;
+		}
+		if coordinate <= start || coordinate >= end {
+			return 0.0;
+		}
+		scalar *= if coordinate < peak {
+			(coordinate - start) / (peak - start)
+		} else {
+			(end - coordinate) / (end - peak)
+		};
+	}
+	scalar
+}
+
+impl ItemVariationStore {
+	/// How much a region applies at the given normalised coordinates, between 0 and 1.
+	pub fn region_scalar(&self, region_index: usize, coordinates: &[f32]) -> f32 {
+		match self.variation_regions.get(region_index) {
+			Some(region) => region_scalar(region, coordinates),
+			None => 0.0,
+		}
+	}
+
+	/// The scalars for each region referenced by one item variation data subtable, in the order its deltas are stored.
+	pub fn region_scalars(&self, data_index: usize, coordinates: &[f32]) -> Option<Vec<f32>> {
+		let data = self.item_variation_data.get(data_index)?;
+		Some(data.region_indices.iter().map(|region_index| self.region_scalar(*region_index as usize, coordinates)).collect())
+	}
+
+	/// The interpolated delta for an item, addressed by its outer (data) and inner (item) index.
+	pub fn delta(&self, outer_index: u16, inner_index: u16, coordinates: &[f32]) -> f32 {
+		let Some(data) = self.item_variation_data.get(outer_index as usize) else {
+			return 0.0;
+		};
+		let Some(deltas) = data.delta_sets.get(inner_index as usize) else {
+			return 0.0;
+		};
+		data.region_indices.iter().zip(deltas).map(|(region_index, delta)| *delta as f32 * self.region_scalar(*region_index as usize, coordinates)).sum()
+	}
+}
+
+impl AxisVariationsTable {
+	/// Maps a normalised coordinate on an axis through its segment map, interpolating between the pairs either side of it.
+	pub fn map_coordinate(&self, axis_index: usize, coordinate: f32) -> f32 {
+		let Some(segment_map) = self.segment_maps.get(axis_index).filter(|segment_map| !segment_map.is_empty()) else {
+			return coordinate;
+		};
+		if coordinate <= segment_map[0].0 {
+			return segment_map[0].1;
+		}
+		for segment in segment_map.windows(2) {
+			let ((from_start, to_start), (from_end, to_end)) = (segment[0], segment[1]);
+			if coordinate <= from_end {
+				if from_end == from_start {
+					return to_end;
+				}
+				let mapped = to_start + (coordinate - from_start) * (to_end - to_start) / (from_end - from_start);
+				return (mapped * 16384.0).round() / 16384.0;
+			}
+		}
+		segment_map[segment_map.len() - 1].1
+	}
+}
+
+impl DeltaSetIndexMap {
+	/// Items past the end of the map use its last entry.
+	pub fn index(&self, item: usize) -> Option<(u16, u16)> {
+		self.entries.get(item).or(self.entries.last()).copied()
+	}
+}
+
+impl HorizontalMetricsVariationsTable {
+	pub fn advance_width_delta(&self, glyph_id: usize, coordinates: &[f32]) -> f32 {
+		let (outer_index, inner_index) = match &self.advance_width_mapping {
+			Some(advance_width_mapping) => advance_width_mapping.index(glyph_id).unwrap_or((0, 0)),
+			None => (0, glyph_id as u16),
+		};
+		self.item_variation_store.delta(outer_index, inner_index, coordinates)
+	}
+
+	/// None when the font doesn't vary side bearings, which then move with the outline.
+	pub fn left_side_bearing_delta(&self, glyph_id: usize, coordinates: &[f32]) -> Option<f32> {
+		let (outer_index, inner_index) = self.left_side_bearing_mapping.as_ref()?.index(glyph_id)?;
+		Some(self.item_variation_store.delta(outer_index, inner_index, coordinates))
+	}
+}
+
+impl MetricsVariationsTable {
+	pub fn delta(&self, value_tag: [u8; 4], coordinates: &[f32]) -> f32 {
+		match (&self.item_variation_store, self.value_records.iter().find(|(tag, _, _)| *tag == value_tag)) {
+			(Some(item_variation_store), Some(&(_, outer_index, inner_index))) => item_variation_store.delta(outer_index, inner_index, coordinates),
+			_ => 0.0,
+		}
+	}
+}
+
+impl GlyphVariationsTable {
+	/// How far each of a glyph's points moves at the given normalised coordinates.
+	/// `points` are the glyph's points followed by its four phantom points, and untouched points in each contour are interpolated from their neighbours.
+	/// Composite glyphs, which have a point for each component's offset, have no contours and so no interpolation.
+	pub fn point_deltas(&self, glyph_id: usize, coordinates: &[f32], points: &[(i16, i16)], end_points_of_contours: &[u16]) -> Vec<(f32, f32)> {
+		let mut deltas = vec![(0.0, 0.0); points.len()];
+		let Some(tuple_variations) = self.glyph_variations.get(glyph_id) else {
+			return deltas;
+		};
+
+		for tuple_variation in tuple_variations {
+			let scalar = region_scalar(&tuple_variation.region, coordinates);
+			if scalar == 0.0 {
+				continue;
+			}
+			let tuple_deltas: Vec<Option<(f32, f32)>> = match &tuple_variation.point_numbers {
+				None => tuple_variation.x_deltas.iter().zip(&tuple_variation.y_deltas).map(|(&x, &y)| Some((x as f32, y as f32))).collect(),
+				Some(point_numbers) => {
+					let mut tuple_deltas = vec![None; points.len()];
+					for ((&point_number, &x), &y) in point_numbers.iter().zip(&tuple_variation.x_deltas).zip(&tuple_variation.y_deltas) {
+						if let Some(tuple_delta) = tuple_deltas.get_mut(point_number as usize) {
+							*tuple_delta = Some((x as f32, y as f32));
+						}
+					}
+					interpolate_untouched_points(&mut tuple_deltas, points, end_points_of_contours);
+					tuple_deltas
+				},
+			};
+			for (delta, tuple_delta) in deltas.iter_mut().zip(tuple_deltas) {
+				if let Some((x, y)) = tuple_delta {
+					delta.0 += x * scalar;
+					delta.1 += y * scalar;
+				}
+			}
+		}
+		deltas
+	}
+
+	/// A glyph with its points moved to the given normalised coordinates, along with its horizontal metrics, which move with its phantom points.
+	pub fn vary_glyph(&self, glyph_id: usize, glyph: &GlyphRaw, horizontal_metric: HorizontalMetric, coordinates: &[f32]) -> (GlyphRaw, HorizontalMetric) {
+		let mut glyph = glyph.clone();
+		let (mut points, end_points_of_contours): (Vec<(i16, i16)>, &[u16]) = match &glyph.glyph_data {
+			GlyphDataRaw::SimpleGlyphRaw(simple_glyph) => (simple_glyph.x_coordinates.iter().copied().zip(simple_glyph.y_coordinates.iter().copied()).collect(), &simple_glyph.end_points_of_contours),
+			GlyphDataRaw::CompositeGlyphRaw(composite_glyph) => (composite_glyph.children.iter().map(|child| (child.x_offset_point as i16, child.y_offset_point as i16)).collect(), &[]),
+			GlyphDataRaw::None => (Vec::new(), &[]),
+		};
+		let point_count = points.len();
+		// The phantom points are at the glyph's origin and advance, then its top and bottom origins, which aren't used.
+		let origin_x = glyph.x_min.saturating_sub(horizontal_metric.left_side_bearing);
+		points.extend([(origin_x, 0), (origin_x.saturating_add_unsigned(horizontal_metric.advance_width), 0), (0, 0), (0, 0)]);
+		let deltas = self.point_deltas(glyph_id, coordinates, &points, end_points_of_contours);
+		let moved = |point: (i16, i16), delta: (f32, f32)| ((point.0 as f32 + delta.0).round() as i16, (point.1 as f32 + delta.1).round() as i16);
+
+		match &mut glyph.glyph_data {
+			GlyphDataRaw::SimpleGlyphRaw(simple_glyph) => {
+				for (index, (x, y)) in points.iter().zip(&deltas).take(point_count).map(|(&point, &delta)| moved(point, delta)).enumerate() {
+					simple_glyph.x_coordinates[index] = x;
+					simple_glyph.y_coordinates[index] = y;
+				}
+				glyph.x_min = simple_glyph.x_coordinates.iter().copied().min().unwrap_or(0);
+				glyph.x_max = simple_glyph.x_coordinates.iter().copied().max().unwrap_or(0);
+				glyph.y_min = simple_glyph.y_coordinates.iter().copied().min().unwrap_or(0);
+				glyph.y_max = simple_glyph.y_coordinates.iter().copied().max().unwrap_or(0);
+			},
+			GlyphDataRaw::CompositeGlyphRaw(composite_glyph) => {
+				// Components placed by matching points don't have an offset to move.
+				// The bounds are left as they are, as they depend on the varied components.
+				for (child, (x, y)) in composite_glyph.children.iter_mut().zip(points.iter().zip(&deltas).map(|(&point, &delta)| moved(point, delta))) {
+					if child.flag & ARGS_ARE_XY_VALUES_FLAG != 0 {
+						child.x_offset_point = x as i32;
+						child.y_offset_point = y as i32;
+					}
+				}
+			},
+			GlyphDataRaw::None => {},
+		}
+
+		let origin_x = points[point_count].0 as f32 + deltas[point_count].0;
+		let advance_x = points[point_count + 1].0 as f32 + deltas[point_count + 1].0;
+		let horizontal_metric = HorizontalMetric {
+			advance_width: (advance_x - origin_x).round().max(0.0) as u16,
+			left_side_bearing: glyph.x_min.saturating_sub(origin_x.round() as i16),
+		};
+		(glyph, horizontal_metric)
+	}
+}
+
+/// Interpolates deltas for the points in each contour that a tuple variation doesn't give one, from the nearest points before and after that it does.
+/// Contours without any deltas are left without.
+fn interpolate_untouched_points(deltas: &mut [Option<(f32, f32)>], points: &[(i16, i16)], end_points_of_contours: &[u16]) {
+	let mut contour_start = 0;
+	for &contour_end in end_points_of_contours {
+		let contour_end = contour_end as usize;
+		if contour_end >= deltas.len() || contour_end < contour_start {
+			return;
+		}
+		let touched: Vec<usize> = (contour_start..=contour_end).filter(|&index| deltas[index].is_some()).collect();
+		if !touched.is_empty() {
+			let contour_length = contour_end + 1 - contour_start;
+			for (touched_index, &previous) in touched.iter().enumerate() {
+				let next = touched[(touched_index + 1) % touched.len()];
+				let (previous_delta, next_delta) = (deltas[previous].unwrap_or_default(), deltas[next].unwrap_or_default());
+				// The points between these two, wrapping round to the start of the contour.
+				let mut index = previous;
+				loop {
+					index = contour_start + (index + 1 - contour_start) % contour_length;
+					if index == next {
+						break;
+					}
+					deltas[index] = Some((
+						interpolate(points[index].0, points[previous].0, points[next].0, previous_delta.0, next_delta.0),
+						interpolate(points[index].1, points[previous].1, points[next].1, previous_delta.1, next_delta.1),
+					));
+				}
+			}
+		}
+		contour_start = contour_end + 1;
+	}
+}
+
+/// The delta for a coordinate, from two reference points' coordinates and deltas on the same axis.
+/// Between them it's interpolated, and outside them it takes the delta of the nearer one.
+fn interpolate(coordinate: i16, first: i16, second: i16, first_delta: f32, second_delta: f32) -> f32 {
+	let (low, high, low_delta, high_delta) = if first <= second {
+		(first, second, first_delta, second_delta)
+	} else {
+		(second, first, second_delta, first_delta)
+	};
+	if low == high {
+		if low_delta == high_delta { low_delta } else { 0.0 }
+	} else if coordinate <= low {
+		low_delta
+	} else if coordinate >= high {
+		high_delta
+	} else {
+		low_delta + (coordinate - low) as f32 * (high_delta - low_delta) / (high - low) as f32
+	}
+}
+
+/// Reads packed point numbers, or None when the count is 0, meaning every point.
+fn read_packed_point_numbers<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>) -> Result<Option<Vec<u16>>, TrueTypeFontReaderError> {
+	let first_byte: u8 = ttf_reader.read_bytes()?;
+	let count = match first_byte {
+		0 => return Ok(None),
+		first_byte if first_byte & POINTS_ARE_WORDS_FLAG != 0 => ((first_byte & POINT_RUN_COUNT_MASK) as u16) << 8 | ttf_reader.read_bytes::<u8>()? as u16,
+		first_byte => first_byte as u16,
+	};
+
+	// Each point number is stored as the difference from the one before.
+	let mut point_numbers: Vec<u16> = Vec::with_capacity(count as usize);
+	let mut point_number: u16 = 0;
+	while point_numbers.len() < count as usize {
+		let control: u8 = ttf_reader.read_bytes()?;
+		for _ in 0..(control & POINT_RUN_COUNT_MASK) as usize + 1 {
+			let difference = match control & POINTS_ARE_WORDS_FLAG {
+				0 => ttf_reader.read_bytes::<u8>()? as u16,
+				_ => ttf_reader.read_bytes::<u16>()?,
+			};
+			point_number = point_number.wrapping_add(difference);
+			point_numbers.push(point_number);
+		}
+	}
+	point_numbers.truncate(count as usize);
+	Ok(Some(point_numbers))
+}
+
+/// Reads runs of packed deltas until `end`. The x deltas come first, then the same number of y deltas.
+fn read_packed_deltas<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, end: u64) -> Result<Vec<i32>, TrueTypeFontReaderError> {
+	let mut deltas: Vec<i32> = Vec::new();
+	while ttf_reader.buffer_reader.stream_position()? < end {
+		let control: u8 = ttf_reader.read_bytes()?;
+		for _ in 0..(control & DELTA_RUN_COUNT_MASK) as usize + 1 {
+			deltas.push(match control & (DELTAS_ARE_ZERO_FLAG | DELTAS_ARE_WORDS_FLAG) {
+				DELTAS_ARE_ZERO_FLAG => 0,
+				DELTAS_ARE_WORDS_FLAG => ttf_reader.read_bytes::<i16>()? as i32,
+				0 => ttf_reader.read_bytes::<i8>()? as i32,
+				_ => ttf_reader.read_bytes::<u32>()? as i32, // Both flags mean 32 bit deltas
+			});
+		}
+	}
+	Ok(deltas)
+}
+
+fn read_f2dot14_tuple<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, axis_count: u16) -> Result<Vec<f32>, TrueTypeFontReaderError> {
+	let mut tuple: Vec<f32> = Vec::with_capacity(axis_count as usize);
+	for _ in 0..axis_count {
+		tuple.push(f2dot14_to_f32(ttf_reader.read_bytes()?));
+	}
+	Ok(tuple)
+}
+
+/// Reads the tuple variations of one glyph, from its glyph variation data at `offset`.
+fn read_glyph_variation_data<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u64, axis_count: u16, shared_tuples: &[Vec<f32>]) -> Result<Vec<TupleVariation>, TrueTypeFontReaderError> {
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+	let tuple_variation_count: u16 = ttf_reader.read_bytes()?;
+	let data_offset: u16 = ttf_reader.read_bytes()?;
+
+	let mut headers: Vec<(u16, Vec<RegionAxisCoordinates>, bool)> = Vec::with_capacity((tuple_variation_count & TUPLE_COUNT_MASK) as usize);
+	for _ in 0..tuple_variation_count & TUPLE_COUNT_MASK {
+		let variation_data_size: u16 = ttf_reader.read_bytes()?;
+		let tuple_index: u16 = ttf_reader.read_bytes()?;
+		let peak = match tuple_index & EMBEDDED_PEAK_TUPLE_FLAG {
+			0 => shared_tuples.get((tuple_index & TUPLE_INDEX_MASK) as usize).cloned().ok_or(TrueTypeFontReaderError::InvalidData(TableTag::GlyphVariations, "tuple index should be in the shared tuples"))?,
+			_ => read_f2dot14_tuple(ttf_reader, axis_count)?,
+		};
+		// Without an intermediate region, a tuple applies from the default to its peak, falling to nothing at the default.
+		let region = match tuple_index & INTERMEDIATE_REGION_FLAG {
+			0 => peak.iter().map(|&peak_coordinate| RegionAxisCoordinates { start_coordinate: peak_coordinate.min(0.0), peak_coordinate, end_coordinate: peak_coordinate.max(0.0) }).collect(),
+			_ => {
+				let start = read_f2dot14_tuple(ttf_reader, axis_count)?;
+				let end = read_f2dot14_tuple(ttf_reader, axis_count)?;
+				peak.iter().zip(start).zip(end).map(|((&peak_coordinate, start_coordinate), end_coordinate)| RegionAxisCoordinates { start_coordinate, peak_coordinate, end_coordinate }).collect()
+			},
+		};
+		headers.push((variation_data_size, region, tuple_index & PRIVATE_POINT_NUMBERS_FLAG != 0));
+	}
+
+	let mut data_start = offset + data_offset as u64;
+	ttf_reader.buffer_reader.seek(io::SeekFrom::Start(data_start))?;
+	let shared_point_numbers = match tuple_variation_count & SHARED_POINT_NUMBERS_FLAG {
+		0 => None,
+		_ => {
+			let shared_point_numbers = read_packed_point_numbers(ttf_reader)?;
+			data_start = ttf_reader.buffer_reader.stream_position()?;
+			shared_point_numbers
+		},
+	};
+
+	let mut tuple_variations: Vec<TupleVariation> = Vec::with_capacity(headers.len());
+	for (variation_data_size, region, private_point_numbers) in headers {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(data_start))?;
+		let data_end = data_start + variation_data_size as u64;
+		let point_numbers = match private_point_numbers {
+			true => read_packed_point_numbers(ttf_reader)?,
+			false => shared_point_numbers.clone(),
+		};
+		let mut x_deltas = read_packed_deltas(ttf_reader, data_end)?;
+		if x_deltas.len() % 2 != 0 {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::GlyphVariations, "tuple variation should have as many y deltas as x deltas"));
+		}
+		let y_deltas = x_deltas.split_off(x_deltas.len() / 2);
+		if point_numbers.as_ref().is_some_and(|point_numbers| point_numbers.len() != x_deltas.len()) {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::GlyphVariations, "tuple variation should have a delta for each of its points"));
+		}
+		tuple_variations.push(TupleVariation { region, point_numbers, x_deltas, y_deltas });
+		data_start = data_end;
+	}
+	Ok(tuple_variations)
+}
+
+impl FromTTFReader for AxisVariationsTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<AxisVariationsTable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+
+		let major_version: u16 = ttf_reader.read_bytes()?;
+		let minor_version: u16 = ttf_reader.read_bytes()?;
+		// Version 2 starts with the same segment maps, then adds an item variation store for mapping axes together, which isn't applied.
+		if major_version != 1 && major_version != 2 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::AxisVariations, major_version, minor_version));
+		}
+		ttf_reader.skip(2)?; // reserved
+		let axis_count: u16 = ttf_reader.read_bytes()?;
+
+		let mut segment_maps: Vec<Vec<(f32, f32)>> = Vec::with_capacity(axis_count as usize);
+		for _ in 0..axis_count {
+			let position_map_count: u16 = ttf_reader.read_bytes()?;
+			let mut segment_map: Vec<(f32, f32)> = Vec::with_capacity(position_map_count as usize);
+			for _ in 0..position_map_count {
+				segment_map.push((f2dot14_to_f32(ttf_reader.read_bytes()?), f2dot14_to_f32(ttf_reader.read_bytes()?)));
+			}
+			if segment_map.windows(2).any(|segment| segment[0].0 > segment[1].0) {
+				return Err(TrueTypeFontReaderError::InvalidData(TableTag::AxisVariations, "segment maps should be in order"));
+			}
+			segment_maps.push(segment_map);
+		}
+
+		Ok(AxisVariationsTable {
+			segment_maps,
+		})
+	}
+}
+
+impl FromTTFReader for DeltaSetIndexMap {
+	type Input = (u64, TableTag); // offset, table containing the map
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (offset, table_tag): (u64, TableTag)) -> Result<DeltaSetIndexMap, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+
+		let format: u8 = ttf_reader.read_bytes()?;
+		let entry_format: u8 = ttf_reader.read_bytes()?;
+		let map_count: u32 = match format {
+			0 => ttf_reader.read_bytes::<u16>()? as u32,
+			1 => ttf_reader.read_bytes()?,
+			_ => return Err(TrueTypeFontReaderError::InvalidData(table_tag, "delta set index map should be format 0 or 1")),
+		};
+
+		// Each entry packs the outer and inner indices together, the inner index in the low bits.
+		let entry_size = ((entry_format & MAP_ENTRY_SIZE_MASK) >> 4) + 1;
+		let inner_index_bit_count = (entry_format & INNER_INDEX_BIT_COUNT_MASK) + 1;
+		let mut entries: Vec<(u16, u16)> = Vec::with_capacity(map_count as usize);
+		for _ in 0..map_count {
+			let mut entry: u32 = 0;
+			for _ in 0..entry_size {
+				entry = entry << 8 | ttf_reader.read_bytes::<u8>()? as u32;
+			}
+			entries.push(((entry >> inner_index_bit_count) as u16, (entry & ((1 << inner_index_bit_count) - 1)) as u16));
+		}
+
+		Ok(DeltaSetIndexMap {
+			entries,
+		})
+	}
+}
+
+/// Reads a delta set index map at an offset from the start of its table, where an offset of 0 means there isn't one.
+fn read_optional_delta_set_index_map<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, table_offset: u64, offset: u32, table_tag: TableTag) -> Result<Option<DeltaSetIndexMap>, TrueTypeFontReaderError> {
+	match offset {
+		0 => Ok(None),
+		offset => Ok(Some(ttf_reader.read((table_offset + offset as u64, table_tag))?)),
+	}
+}
+
+impl FromTTFReader for HorizontalMetricsVariationsTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<HorizontalMetricsVariationsTable, TrueTypeFontReaderError> {
+		let offset = offset as u64;
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+
+		let major_version: u16 = ttf_reader.read_bytes()?;
+		let minor_version: u16 = ttf_reader.read_bytes()?;
+		if major_version != 1 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::HorizontalMetricsVariations, major_version, minor_version));
+		}
+		let item_variation_store_offset: u32 = ttf_reader.read_bytes()?;
+		let advance_width_mapping_offset: u32 = ttf_reader.read_bytes()?;
+		let left_side_bearing_mapping_offset: u32 = ttf_reader.read_bytes()?;
+		let right_side_bearing_mapping_offset: u32 = ttf_reader.read_bytes()?;
+
+		Ok(HorizontalMetricsVariationsTable {
+			item_variation_store: ttf_reader.read((offset + item_variation_store_offset as u64, TableTag::HorizontalMetricsVariations))?,
+			advance_width_mapping: read_optional_delta_set_index_map(ttf_reader, offset, advance_width_mapping_offset, TableTag::HorizontalMetricsVariations)?,
+			left_side_bearing_mapping: read_optional_delta_set_index_map(ttf_reader, offset, left_side_bearing_mapping_offset, TableTag::HorizontalMetricsVariations)?,
+			right_side_bearing_mapping: read_optional_delta_set_index_map(ttf_reader, offset, right_side_bearing_mapping_offset, TableTag::HorizontalMetricsVariations)?,
+		})
+	}
+}
+
+impl FromTTFReader for MetricsVariationsTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<MetricsVariationsTable, TrueTypeFontReaderError> {
+		let offset = offset as u64;
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+
+		let major_version: u16 = ttf_reader.read_bytes()?;
+		let minor_version: u16 = ttf_reader.read_bytes()?;
+		if major_version != 1 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::MetricsVariations, major_version, minor_version));
+		}
+		ttf_reader.skip(2)?; // reserved
+		let value_record_size: u16 = ttf_reader.read_bytes()?;
+		let value_record_count: u16 = ttf_reader.read_bytes()?;
+		let item_variation_store_offset: u16 = ttf_reader.read_bytes()?;
+		if value_record_count > 0 && value_record_size < 8 {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::MetricsVariations, "value records should be at least 8 bytes"));
+		}
+
+		let mut value_records: Vec<([u8; 4], u16, u16)> = Vec::with_capacity(value_record_count as usize);
+		for record_index in 0..value_record_count as u64 {
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset + 12 + record_index * value_record_size as u64))?;
+			let value_tag: u32 = ttf_reader.read_bytes()?;
+			value_records.push((value_tag.to_be_bytes(), ttf_reader.read_bytes()?, ttf_reader.read_bytes()?));
+		}
+
+		let item_variation_store = match item_variation_store_offset {
+			0 => None,
+			item_variation_store_offset => Some(ttf_reader.read((offset + item_variation_store_offset as u64, TableTag::MetricsVariations))?),
+		};
+
+		Ok(MetricsVariationsTable {
+			item_variation_store,
+			value_records,
+		})
+	}
+}
+
+impl FromTTFReader for GlyphVariationsTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<GlyphVariationsTable, TrueTypeFontReaderError> {
+		let offset = offset as u64;
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+
+		let major_version: u16 = ttf_reader.read_bytes()?;
+		let minor_version: u16 = ttf_reader.read_bytes()?;
+		if major_version != 1 || minor_version != 0 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::GlyphVariations, major_version, minor_version));
+		}
+		let axis_count: u16 = ttf_reader.read_bytes()?;
+		let shared_tuple_count: u16 = ttf_reader.read_bytes()?;
+		let shared_tuples_offset: u32 = ttf_reader.read_bytes()?;
+		let glyph_count: u16 = ttf_reader.read_bytes()?;
+		let flags: u16 = ttf_reader.read_bytes()?;
+		let glyph_variation_data_array_offset: u32 = ttf_reader.read_bytes()?;
+
+		// There is one more offset than glyphs, so the last glyph's data has an end.
+		let mut glyph_variation_data_offsets: Vec<u32> = Vec::with_capacity(glyph_count as usize + 1);
+		for _ in 0..=glyph_count {
+			glyph_variation_data_offsets.push(match flags & LONG_OFFSETS_FLAG {
+				0 => ttf_reader.read_bytes::<u16>()? as u32 * 2,
+				_ => ttf_reader.read_bytes()?,
+			});
+		}
+
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset + shared_tuples_offset as u64))?;
+		let mut shared_tuples: Vec<Vec<f32>> = Vec::with_capacity(shared_tuple_count as usize);
+		for _ in 0..shared_tuple_count {
+			shared_tuples.push(read_f2dot14_tuple(ttf_reader, axis_count)?);
+		}
+
+		let mut glyph_variations: Vec<Vec<TupleVariation>> = Vec::with_capacity(glyph_count as usize);
+		for glyph_offsets in glyph_variation_data_offsets.windows(2) {
+			glyph_variations.push(match glyph_offsets[1].checked_sub(glyph_offsets[0]) {
+				Some(0) => Vec::new(),
+				Some(_) => read_glyph_variation_data(ttf_reader, offset + glyph_variation_data_array_offset as u64 + glyph_offsets[0] as u64, axis_count, &shared_tuples)?,
+				None => return Err(TrueTypeFontReaderError::InvalidData(TableTag::GlyphVariations, "glyph variation data offsets should be in order")),
+			});
+		}
+
+		Ok(GlyphVariationsTable {
+			axis_count,
+			glyph_variations,
+		})
+	}
+}
+
+impl FromTTFReader for FontVariationsTable {
+	type Input = u32;
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, offset: u32) -> Result<FontVariationsTable, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64))?;
+
+		let major_version: u16 = ttf_reader.read_bytes()?;
+		let minor_version: u16 = ttf_reader.read_bytes()?;
+		if major_version != 1 {
+			return Err(TrueTypeFontReaderError::UnsupportedTableVersion(TableTag::FontVariations, major_version, minor_version));
+		}
+		let axes_array_offset: u16 = ttf_reader.read_bytes()?;
+		ttf_reader.skip(2)?;
+		let axis_count: u16 = ttf_reader.read_bytes()?;
+		let axis_size: u16 = ttf_reader.read_bytes()?;
+		if axis_size < 20 {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::FontVariations, "axis records should be at least 20 bytes"));
+		}
+		let instance_count: u16 = ttf_reader.read_bytes()?;
+		let instance_size: u16 = ttf_reader.read_bytes()?;
+		if instance_count > 0 && (instance_size as u32) < axis_count as u32 * 4 + 4 {
+			return Err(TrueTypeFontReaderError::InvalidData(TableTag::FontVariations, "instance records should have a coordinate for each axis"));
+		}
+
+		let mut axes: Vec<VariationAxisRecord> = Vec::with_capacity(axis_count as usize);
+		for axis_index in 0..axis_count as u64 {
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset as u64 + axes_array_offset as u64 + axis_index * axis_size as u64))?;
+			let axis_tag: u32 = ttf_reader.read_bytes()?;
+			let min_value = fixed_to_f32(ttf_reader.read_bytes()?);
+			let default_value = fixed_to_f32(ttf_reader.read_bytes()?);
+			let max_value = fixed_to_f32(ttf_reader.read_bytes()?);
+			if min_value > default_value || default_value > max_value {
+				return Err(TrueTypeFontReaderError::InvalidData(TableTag::FontVariations, "axis default value should be between its minimum and maximum"));
+			}
+			axes.push(VariationAxisRecord {
+				axis_tag: axis_tag.to_be_bytes(),
+				min_value,
+				default_value,
+				max_value,
+				flags: ttf_reader.read_bytes()?,
+				axis_name_id: ttf_reader.read_bytes()?,
+			});
+		}
+
+		// Instances follow the axes, and only have a PostScript name when their records are big enough for one.
+		let instances_offset = offset as u64 + axes_array_offset as u64 + axis_count as u64 * axis_size as u64;
+		let mut instances: Vec<InstanceRecord> = Vec::with_capacity(instance_count as usize);
+		for instance_index in 0..instance_count as u64 {
+			ttf_reader.buffer_reader.seek(io::SeekFrom::Start(instances_offset + instance_index * instance_size as u64))?;
+			let subfamily_name_id = ttf_reader.read_bytes()?;
+			let flags = ttf_reader.read_bytes()?;
+			let mut coordinates: Vec<f32> = Vec::with_capacity(axis_count as usize);
+			for _ in 0..axis_count {
+				coordinates.push(fixed_to_f32(ttf_reader.read_bytes()?));
+			}
+			let postscript_name_id = match instance_size as u32 >= axis_count as u32 * 4 + 6 {
+				true => Some(ttf_reader.read_bytes()?).filter(|&name_id| name_id != 0xFFFF),
+				false => None,
+			};
+			instances.push(InstanceRecord { subfamily_name_id, flags, coordinates, postscript_name_id });
+		}
+
+		Ok(FontVariationsTable {
+			axes,
+			instances,
+		})
+	}
+}
+
+impl FromTTFReader for ItemVariationData {
+	type Input = (u64, TableTag); // offset, table containing the store
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (offset, table_tag): (u64, TableTag)) -> Result<ItemVariationData, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+
+		let item_count: u16 = ttf_reader.read_bytes()?;
+		let word_delta_count: u16 = ttf_reader.read_bytes()?;
+		let region_index_count: u16 = ttf_reader.read_bytes()?;
+		let long_words = word_delta_count & LONG_WORDS_FLAG != 0;
+		let word_delta_count = word_delta_count & WORD_DELTA_COUNT_MASK;
+		if word_delta_count > region_index_count {
+			return Err(TrueTypeFontReaderError::InvalidData(table_tag, "item variation data has more word deltas than regions"));
+		}
+
+		let mut region_indices: Vec<u16> = Vec::with_capacity(region_index_count as usize);
+		for _ in 0..region_index_count {
+			region_indices.push(ttf_reader.read_bytes()?);
+		}
+
+		// Each delta set starts with the larger deltas: 32 bit and 16 bit with long words, otherwise 16 bit and 8 bit.
+		let mut delta_sets: Vec<Vec<i32>> = Vec::with_capacity(item_count as usize);
+		for _ in 0..item_count {
+			let mut deltas: Vec<i32> = Vec::with_capacity(region_index_count as usize);
+			for delta_index in 0..region_index_count {
+				let delta = match (long_words, delta_index < word_delta_count) {
+					(true, true) => ttf_reader.read_bytes::<u32>()? as i32,
+					(true, false) | (false, true) => ttf_reader.read_bytes::<i16>()? as i32,
+					(false, false) => ttf_reader.read_bytes::<i8>()? as i32,
+				};
+				deltas.push(delta);
+			}
+			delta_sets.push(deltas);
+		}
+
+		Ok(ItemVariationData {
+			region_indices,
+			delta_sets,
+		})
+	}
+}
+
+impl FromTTFReader for ItemVariationStore {
+	type Input = (u64, TableTag); // offset, table containing the store
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, (offset, table_tag): (u64, TableTag)) -> Result<ItemVariationStore, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset))?;
+
+		let format: u16 = ttf_reader.read_bytes()?;
+		if format != 1 {
+			return Err(TrueTypeFontReaderError::InvalidData(table_tag, "item variation store format should be 1"));
+		}
+		let variation_region_list_offset: u32 = ttf_reader.read_bytes()?;
+		let item_variation_data_count: u16 = ttf_reader.read_bytes()?;
+		let mut item_variation_data_offsets: Vec<u32> = Vec::with_capacity(item_variation_data_count as usize);
+		for _ in 0..item_variation_data_count {
+			item_variation_data_offsets.push(ttf_reader.read_bytes()?);
+		}
+
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(offset + variation_region_list_offset as u64))?;
+		let axis_count: u16 = ttf_reader.read_bytes()?;
+		let region_count: u16 = ttf_reader.read_bytes()?;
+		let mut variation_regions: Vec<Vec<RegionAxisCoordinates>> = Vec::with_capacity(region_count as usize);
+		for _ in 0..region_count {
+			let mut region: Vec<RegionAxisCoordinates> = Vec::with_capacity(axis_count as usize);
+			for _ in 0..axis_count {
+				region.push(RegionAxisCoordinates {
+					start_coordinate: f2dot14_to_f32(ttf_reader.read_bytes()?),
+					peak_coordinate: f2dot14_to_f32(ttf_reader.read_bytes()?),
+					end_coordinate: f2dot14_to_f32(ttf_reader.read_bytes()?),
+				});
+			}
+			variation_regions.push(region);
+		}
+
+		let mut item_variation_data: Vec<ItemVariationData> = Vec::with_capacity(item_variation_data_offsets.len());
+		for item_variation_data_offset in item_variation_data_offsets {
+			let data: ItemVariationData = ttf_reader.read((offset + item_variation_data_offset as u64, table_tag))?;
+			if data.region_indices.iter().any(|region_index| *region_index >= region_count) {
+				return Err(TrueTypeFontReaderError::InvalidData(table_tag, "item variation data should only reference regions in the region list"));
+			}
+			item_variation_data.push(data);
+		}
+
+		Ok(ItemVariationStore {
+			variation_regions,
+			item_variation_data,
+		})
+	}
+}
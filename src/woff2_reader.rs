@@ -0,0 +1,528 @@
+use std::io::{self, Cursor, Read, Seek};
+
+use crate::brotli;
+use crate::font::FontLoadError;
+use crate::ttf_reader::{FromBytes, FromTTFReader, TableTag, TrueTypeFontReader, TrueTypeFontReaderError, COLLECTION_TAG};
+use crate::woff_reader;
+
+pub const WOFF2_SIGNATURE: u32 = 0x774F4632; // 'wOF2'
+
+const WOFF2_TAG: TableTag = TableTag::Other(['w', 'O', 'F', '2']);
+
+// Tables with these tags only store their index in the table directory, any other tag is stored after index 63.
+const KNOWN_TAGS: [[u8; 4]; 63] = [
+	*b"cmap", *b"head", *b"hhea", *b"hmtx", *b"maxp", *b"name", *b"OS/2", *b"post", *b"cvt ", *b"fpgm", *b"glyf", *b"loca", *b"prep", *b"CFF ", *b"VORG", *b"EBDT",
+	*b"EBLC", *b"gasp", *b"hdmx", *b"kern", *b"LTSH", *b"PCLT", *b"VDMX", *b"vhea", *b"vmtx", *b"BASE", *b"GDEF", *b"GPOS", *b"GSUB", *b"EBSC", *b"JSTF", *b"MATH",
+	*b"CBDT", *b"CBLC", *b"COLR", *b"CPAL", *b"SVG ", *b"sbix", *b"acnt", *b"avar", *b"bdat", *b"bloc", *b"bsln", *b"cvar", *b"fdsc", *b"feat", *b"fmtx", *b"fvar",
+	*b"gvar", *b"hsty", *b"just", *b"lcar", *b"mort", *b"morx", *b"opbd", *b"prop", *b"trak", *b"Zapf", *b"Silf", *b"Glat", *b"Gloc", *b"Feat", *b"Sill",
+];
+const ARBITRARY_TAG_INDEX: u8 = 63;
+
+const GLYPH_TAG: [u8; 4] = *b"glyf";
+const INDEX_TO_LOCATION_TAG: [u8; 4] = *b"loca";
+const HORIZONTAL_METRICS_TAG: [u8; 4] = *b"hmtx";
+const HORIZONTAL_HEADER_TAG: [u8; 4] = *b"hhea";
+const FONT_HEADER_TAG: [u8; 4] = *b"head";
+
+// glyf and loca are transformed by default and use version 3 for no transform, every other table is the other way round.
+const GLYPH_NULL_TRANSFORM_VERSION: u8 = 3;
+const HORIZONTAL_METRICS_TRANSFORM_VERSION: u8 = 1;
+
+const GLYPH_TRANSFORM_HEADER_LENGTH: usize = 36;
+const OVERLAP_SIMPLE_BITMAP_FLAG: u16 = 0x0001;
+const PROPORTIONAL_LEFT_SIDE_BEARINGS_ABSENT_FLAG: u8 = 0x01;
+const MONOSPACED_LEFT_SIDE_BEARINGS_ABSENT_FLAG: u8 = 0x02;
+
+// Simple glyph point flags, as written to the rebuilt glyf table.
+const ON_CURVE_POINT: u8 = 0x01;
+const X_SHORT_VECTOR: u8 = 0x02;
+const Y_SHORT_VECTOR: u8 = 0x04;
+const REPEAT_FLAG: u8 = 0x08;
+const X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR: u8 = 0x10;
+const Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR: u8 = 0x20;
+const OVERLAP_SIMPLE: u8 = 0x40;
+
+// Composite glyph component flags.
+const ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const WE_HAVE_A_SCALE: u16 = 0x0008;
+const MORE_COMPONENTS: u16 = 0x0020;
+const WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+const WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+#[derive(Debug, Clone, Copy)]
+pub struct WebOpenFontFormat2TableDirectoryEntry {
+	pub tag: [u8; 4],
+	pub transform_version: u8,
+	pub original_length: u32,
+	pub transform_length: Option<u32>, // only present for transformed tables
+}
+
+pub struct WebOpenFontFormat2Header {
+	pub flavor: u32, // sfnt version of the font inside
+	pub length: u32,
+	pub total_sfnt_size: u32,
+	pub total_compressed_size: u32,
+	pub table_directory_entries: Vec<WebOpenFontFormat2TableDirectoryEntry>,
+}
+
+impl WebOpenFontFormat2TableDirectoryEntry {
+	pub fn table_tag(&self) -> TableTag {
+		TableTag::from_be_bytes(self.tag)
+	}
+
+	pub fn is_transformed(&self) -> bool {
+		match self.tag {
+			GLYPH_TAG | INDEX_TO_LOCATION_TAG => self.transform_version != GLYPH_NULL_TRANSFORM_VERSION,
+			_ => self.transform_version != 0,
+		}
+	}
+
+	/// How long the table is in the decompressed data, which is shorter than the table itself when it is transformed.
+	pub fn stored_length(&self) -> u32 {
+		self.transform_length.unwrap_or(self.original_length)
+	}
+}
+
+/// Reads a UIntBase128, a number of up to 32 bits stored seven bits to a byte, most significant first.
+fn read_base_128<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>) -> Result<u32, TrueTypeFontReaderError> {
+	let mut value: u32 = 0;
+	for byte_index in 0..5 {
+		let byte: u8 = ttf_reader.read_bytes()?;
+		if byte_index == 0 && byte == 0x80 {
+			return Err(TrueTypeFontReaderError::InvalidData(WOFF2_TAG, "UIntBase128 shouldn't have leading zeros"));
+		}
+		if value & 0xFE000000 != 0 {
+			return Err(TrueTypeFontReaderError::InvalidData(WOFF2_TAG, "UIntBase128 should fit in 32 bits"));
+		}
+		value = (value << 7) | (byte & 0x7F) as u32;
+		if byte & 0x80 == 0 {
+			return Ok(value);
+		}
+	}
+	Err(TrueTypeFontReaderError::InvalidData(WOFF2_TAG, "UIntBase128 should be at most 5 bytes"))
+}
+
+/// Reads a 255UInt16, where small values take a single byte.
+fn read_255_u16<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>) -> Result<u16, TrueTypeFontReaderError> {
+	let code: u8 = ttf_reader.read_bytes()?;
+	match code {
+		253 => ttf_reader.read_bytes(),
+		254 => Ok(ttf_reader.read_bytes::<u8>()? as u16 + 253 * 2),
+		255 => Ok(ttf_reader.read_bytes::<u8>()? as u16 + 253),
+		_ => Ok(code as u16),
+	}
+}
+
+impl FromTTFReader for WebOpenFontFormat2Header {
+	type Input = ();
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, _: ()) -> Result<WebOpenFontFormat2Header, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(0))?;
+
+		let signature: u32 = ttf_reader.read_bytes()?;
+		if signature != WOFF2_SIGNATURE {
+			return Err(TrueTypeFontReaderError::InvalidData(WOFF2_TAG, "WOFF2 file should start with the wOF2 signature"));
+		}
+		let flavor: u32 = ttf_reader.read_bytes()?;
+		let length: u32 = ttf_reader.read_bytes()?;
+		let number_of_tables: u16 = ttf_reader.read_bytes()?;
+		ttf_reader.skip(2)?; // reserved
+		let total_sfnt_size: u32 = ttf_reader.read_bytes()?;
+		let total_compressed_size: u32 = ttf_reader.read_bytes()?;
+		ttf_reader.skip(24)?; // the font's own version, then the metadata and private data blocks, none of which are needed to render
+
+		let mut table_directory_entries: Vec<WebOpenFontFormat2TableDirectoryEntry> = Vec::with_capacity(number_of_tables as usize);
+		for _ in 0..number_of_tables {
+			let flags: u8 = ttf_reader.read_bytes()?;
+			let tag = match flags & 0x3F {
+				ARBITRARY_TAG_INDEX => ttf_reader.read_bytes::<u32>()?.to_be_bytes(),
+				tag_index => KNOWN_TAGS[tag_index as usize],
+			};
+			let mut entry = WebOpenFontFormat2TableDirectoryEntry {
+				tag,
+				transform_version: flags >> 6,
+				original_length: read_base_128(ttf_reader)?,
+				transform_length: None,
+			};
+			if entry.is_transformed() {
+				entry.transform_length = Some(read_base_128(ttf_reader)?);
+			}
+			table_directory_entries.push(entry);
+		}
+
+		Ok(WebOpenFontFormat2Header {
+			flavor,
+			length,
+			total_sfnt_size,
+			total_compressed_size,
+			table_directory_entries,
+		})
+	}
+}
+
+/// Unpacks a WOFF2 file into the sfnt font it contains, decompressing its tables and undoing the glyf, loca and hmtx transforms.
+pub fn decode<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>) -> Result<Vec<u8>, FontLoadError> {
+	let source_length = ttf_reader.length()?;
+	let header: WebOpenFontFormat2Header = ttf_reader.read(())?;
+	if header.flavor == COLLECTION_TAG {
+		return Err(FontLoadError::UnsupportedSfntVersion(header.flavor));
+	}
+
+	// All the tables are compressed together, straight after the table directory.
+	let compressed_data_offset = ttf_reader.buffer_reader.stream_position().map_err(|error| FontLoadError::ReaderError(error.into()))?;
+	if compressed_data_offset + header.total_compressed_size as u64 > source_length {
+		return Err(FontLoadError::OffsetOutOfRange(WOFF2_TAG, compressed_data_offset));
+	}
+	let mut compressed_data = vec![0u8; header.total_compressed_size as usize];
+	ttf_reader.buffer_reader.read_exact(&mut compressed_data).map_err(|error| FontLoadError::from_table_error(error.into(), WOFF2_TAG))?;
+
+	let decompressed_length: u64 = header.table_directory_entries.iter().map(|entry| entry.stored_length() as u64).sum();
+	let data = brotli::decompress(&compressed_data, decompressed_length as usize).map_err(FontLoadError::BrotliDecompressionFailed)?;
+	if data.len() as u64 != decompressed_length {
+		return Err(FontLoadError::ReaderError(TrueTypeFontReaderError::InvalidData(WOFF2_TAG, "WOFF2 tables should decompress to the lengths in the table directory")));
+	}
+
+	let mut stored_tables: Vec<(WebOpenFontFormat2TableDirectoryEntry, &[u8])> = Vec::with_capacity(header.table_directory_entries.len());
+	let mut offset = 0;
+	for entry in header.table_directory_entries.iter().copied() {
+		let length = entry.stored_length() as usize;
+		stored_tables.push((entry, &data[offset..offset + length]));
+		offset += length;
+	}
+	let stored_table = |tag: [u8; 4]| stored_tables.iter().find(|(entry, _)| entry.tag == tag);
+
+	let reconstructed_glyphs = match stored_table(GLYPH_TAG) {
+		Some((entry, glyph_data)) if entry.is_transformed() => Some(reconstruct_glyph_table(glyph_data).map_err(|error| FontLoadError::from_table_error(error, TableTag::Glyph))?),
+		_ => None,
+	};
+
+	let mut tables: Vec<([u8; 4], u32, Vec<u8>)> = Vec::with_capacity(stored_tables.len());
+	for (entry, stored_data) in stored_tables.iter() {
+		let table_tag = entry.table_tag();
+		let table_data = match (entry.tag, entry.is_transformed(), &reconstructed_glyphs) {
+			(_, false, _) => stored_data.to_vec(),
+			(GLYPH_TAG, true, Some(reconstructed_glyphs)) => reconstructed_glyphs.glyph_table.clone(),
+			(INDEX_TO_LOCATION_TAG, true, Some(reconstructed_glyphs)) => {
+				if reconstructed_glyphs.index_to_location_table.len() != entry.original_length as usize {
+					return Err(FontLoadError::ReaderError(TrueTypeFontReaderError::InvalidData(table_tag, "rebuilt loca table should be the length in the table directory")));
+				}
+				reconstructed_glyphs.index_to_location_table.clone()
+			},
+			(HORIZONTAL_METRICS_TAG, true, Some(reconstructed_glyphs)) if entry.transform_version == HORIZONTAL_METRICS_TRANSFORM_VERSION => {
+				let (_, horizontal_header_data) = stored_table(HORIZONTAL_HEADER_TAG).ok_or(FontLoadError::MissingTable(TableTag::HorizontalHeaderTable))?;
+				let number_of_h_metrics = horizontal_header_data.get(34..36).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]])).ok_or(FontLoadError::TruncatedTable(TableTag::HorizontalHeaderTable))?;
+				reconstruct_horizontal_metrics_table(stored_data, number_of_h_metrics, &reconstructed_glyphs.x_mins).map_err(|error| FontLoadError::from_table_error(error, table_tag))?
+			},
+			_ => return Err(FontLoadError::ReaderError(TrueTypeFontReaderError::InvalidData(table_tag, "WOFF2 table uses a transform that isn't supported"))),
+		};
+		tables.push((entry.tag, 0, table_data));
+	}
+
+	// The sfnt table directory has to be sorted by tag, and the checksums were dropped when the font was packed.
+	tables.sort_by_key(|(tag, _, _)| *tag);
+	for (tag, checksum, table_data) in tables.iter_mut() {
+		if *tag == FONT_HEADER_TAG && table_data.len() >= 12 {
+			table_data[8..12].fill(0); // checksum adjustment, which is filled in once the whole font is built
+		}
+		*checksum = table_checksum(table_data);
+	}
+	let mut sfnt = woff_reader::build_sfnt(header.flavor, &tables);
+	if let Some(table_index) = tables.iter().position(|(tag, _, table_data)| *tag == FONT_HEADER_TAG && table_data.len() >= 12) {
+		let table_offset = u32::from_be_bytes(sfnt[12 + 16 * table_index + 8..12 + 16 * table_index + 12].try_into().unwrap()) as usize;
+		let checksum_adjustment = 0xB1B0AFBAu32.wrapping_sub(table_checksum(&sfnt));
+		sfnt[table_offset + 8..table_offset + 12].copy_from_slice(&checksum_adjustment.to_be_bytes());
+	}
+	Ok(sfnt)
+}
+
+fn table_checksum(data: &[u8]) -> u32 {
+	data.chunks(4).fold(0u32, |sum, chunk| {
+		let mut word = [0u8; 4];
+		word[..chunk.len()].copy_from_slice(chunk);
+		sum.wrapping_add(u32::from_be_bytes(word))
+	})
+}
+
+struct ReconstructedGlyphs {
+	glyph_table: Vec<u8>,
+	index_to_location_table: Vec<u8>,
+	x_mins: Vec<i16>, // for rebuilding left side bearings in hmtx
+}
+
+/// Rebuilds the glyf and loca tables from a transformed glyf table, which splits the glyph data into separate streams.
+fn reconstruct_glyph_table(data: &[u8]) -> Result<ReconstructedGlyphs, TrueTypeFontReaderError> {
+	let mut header = TrueTypeFontReader::from_source(Cursor::new(data));
+	header.skip(2)?; // reserved
+	let option_flags: u16 = header.read_bytes()?;
+	let number_of_glyphs: u16 = header.read_bytes()?;
+	let index_format: u16 = header.read_bytes()?;
+
+	let mut streams: Vec<&[u8]> = Vec::with_capacity(7);
+	let mut offset = GLYPH_TRANSFORM_HEADER_LENGTH;
+	for _ in 0..7 {
+		let stream_length: u32 = header.read_bytes()?;
+		let stream = data.get(offset..offset + stream_length as usize).ok_or(TrueTypeFontReaderError::InvalidData(TableTag::Glyph, "transformed glyf streams should fit in the table"))?;
+		streams.push(stream);
+		offset += stream_length as usize;
+	}
+	let overlap_simple_bitmap = match option_flags & OVERLAP_SIMPLE_BITMAP_FLAG {
+		0 => None,
+		_ => Some(data.get(offset..offset + (number_of_glyphs as usize).div_ceil(8)).ok_or(TrueTypeFontReaderError::InvalidData(TableTag::Glyph, "overlap simple bitmap should fit in the table"))?),
+	};
+	let bounding_box_bitmap_length = 4 * (number_of_glyphs as usize).div_ceil(32);
+	if streams[5].len() < bounding_box_bitmap_length {
+		return Err(TrueTypeFontReaderError::InvalidData(TableTag::Glyph, "bounding box stream should start with a bitmap of every glyph"));
+	}
+	let (bounding_box_bitmap, bounding_box_stream) = streams[5].split_at(bounding_box_bitmap_length);
+
+	let mut contour_counts = TrueTypeFontReader::from_source(Cursor::new(streams[0]));
+	let mut point_counts = TrueTypeFontReader::from_source(Cursor::new(streams[1]));
+	let mut flags = TrueTypeFontReader::from_source(Cursor::new(streams[2]));
+	let mut glyphs = TrueTypeFontReader::from_source(Cursor::new(streams[3]));
+	let mut composites = TrueTypeFontReader::from_source(Cursor::new(streams[4]));
+	let mut bounding_boxes = TrueTypeFontReader::from_source(Cursor::new(bounding_box_stream));
+	let mut instructions = TrueTypeFontReader::from_source(Cursor::new(streams[6]));
+
+	let mut glyph_table: Vec<u8> = Vec::new();
+	let mut glyph_offsets: Vec<u32> = Vec::with_capacity(number_of_glyphs as usize + 1);
+	let mut x_mins: Vec<i16> = Vec::with_capacity(number_of_glyphs as usize);
+	for glyph_index in 0..number_of_glyphs as usize {
+		glyph_offsets.push(glyph_table.len() as u32);
+		let has_bounding_box = bounding_box_bitmap[glyph_index >> 3] & (0x80 >> (glyph_index & 7)) != 0;
+		let number_of_contours: i16 = contour_counts.read_bytes()?;
+
+		let mut glyph: Vec<u8> = Vec::new();
+		let mut bounding_box: [i16; 4] = [0; 4];
+		let mut glyph_instructions_present = false;
+		let mut points: Vec<(i16, i16, bool)> = Vec::new(); // x, y, on curve
+		let mut end_points_of_contours: Vec<u16> = Vec::new();
+		match number_of_contours {
+			0 => {
+				if has_bounding_box {
+					return Err(TrueTypeFontReaderError::InvalidData(TableTag::Glyph, "empty glyph shouldn't have a bounding box"));
+				}
+				x_mins.push(0);
+				continue;
+			},
+			-1 => {
+				if !has_bounding_box {
+					return Err(TrueTypeFontReaderError::InvalidData(TableTag::Glyph, "composite glyph should have an explicit bounding box"));
+				}
+				loop {
+					let component_flags: u16 = composites.read_bytes()?;
+					let glyph_index: u16 = composites.read_bytes()?;
+					let argument_length = if component_flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+					let transform_length = if component_flags & WE_HAVE_A_SCALE != 0 {
+						2
+					} else if component_flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+						4
+					} else if component_flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+						8
+					} else {
+						0
+					};
+					let mut component_data = vec![0u8; argument_length + transform_length];
+					composites.buffer_reader.read_exact(&mut component_data)?;
+					glyph.extend(component_flags.to_be_bytes());
+					glyph.extend(glyph_index.to_be_bytes());
+					glyph.extend(component_data);
+					glyph_instructions_present |= component_flags & WE_HAVE_INSTRUCTIONS != 0;
+					if component_flags & MORE_COMPONENTS == 0 {
+						break;
+					}
+				}
+			},
+			1.. => {
+				let mut number_of_points: u16 = 0;
+				for _ in 0..number_of_contours {
+					number_of_points = number_of_points.checked_add(read_255_u16(&mut point_counts)?).ok_or(TrueTypeFontReaderError::InvalidData(TableTag::Glyph, "simple glyph should have at most 65535 points"))?;
+					end_points_of_contours.push(number_of_points.wrapping_sub(1));
+				}
+
+				let (mut x, mut y): (i16, i16) = (0, 0);
+				for _ in 0..number_of_points {
+					let flag: u8 = flags.read_bytes()?;
+					let (dx, dy) = read_triplet(&mut glyphs, flag & 0x7F)?;
+					x = x.wrapping_add(dx);
+					y = y.wrapping_add(dy);
+					points.push((x, y, flag & 0x80 == 0));
+				}
+				glyph_instructions_present = true;
+			},
+			_ => return Err(TrueTypeFontReaderError::InvalidData(TableTag::Glyph, "glyph should have -1 or more contours")),
+		}
+
+		if has_bounding_box {
+			for value in bounding_box.iter_mut() {
+				*value = bounding_boxes.read_bytes()?;
+			}
+		} else if !points.is_empty() {
+			bounding_box = [i16::MAX, i16::MAX, i16::MIN, i16::MIN];
+			for (x, y, _) in points.iter() {
+				bounding_box = [bounding_box[0].min(*x), bounding_box[1].min(*y), bounding_box[2].max(*x), bounding_box[3].max(*y)];
+			}
+		}
+		x_mins.push(bounding_box[0]);
+
+		glyph_table.extend(number_of_contours.to_be_bytes());
+		for value in bounding_box {
+			glyph_table.extend(value.to_be_bytes());
+		}
+		for end_point in end_points_of_contours {
+			glyph_table.extend(end_point.to_be_bytes());
+		}
+		glyph_table.extend(glyph);
+		if glyph_instructions_present {
+			let instruction_length = read_255_u16(&mut glyphs)?;
+			let mut glyph_instructions = vec![0u8; instruction_length as usize];
+			instructions.buffer_reader.read_exact(&mut glyph_instructions)?;
+			glyph_table.extend(instruction_length.to_be_bytes());
+			glyph_table.extend(glyph_instructions);
+		}
+		if !points.is_empty() {
+			let overlaps = overlap_simple_bitmap.is_some_and(|bitmap| bitmap[glyph_index >> 3] & (0x80 >> (glyph_index & 7)) != 0);
+			glyph_table.extend(encode_points(&points, overlaps));
+		}
+		glyph_table.resize(glyph_table.len().next_multiple_of(4), 0);
+	}
+	glyph_offsets.push(glyph_table.len() as u32);
+
+	let mut index_to_location_table: Vec<u8> = Vec::new();
+	for glyph_offset in glyph_offsets {
+		match index_format {
+			0 => {
+				let short_offset = u16::try_from(glyph_offset / 2).map_err(|_| TrueTypeFontReaderError::InvalidData(TableTag::IndexToLocation, "glyf table is too long for short loca offsets"))?;
+				index_to_location_table.extend(short_offset.to_be_bytes());
+			},
+			_ => index_to_location_table.extend(glyph_offset.to_be_bytes()),
+		}
+	}
+
+	Ok(ReconstructedGlyphs {
+		glyph_table,
+		index_to_location_table,
+		x_mins,
+	})
+}
+
+/// Reads the coordinate change to a point, packed into one to four bytes with a layout chosen by the point's flag.
+fn read_triplet<Source: Read + Seek>(glyphs: &mut TrueTypeFontReader<Source>, flag: u8) -> Result<(i16, i16), TrueTypeFontReaderError> {
+	let with_sign = |flag: u8, value: i32| if flag & 1 != 0 { value } else { -value };
+	let flag = flag as i32;
+	let (dx, dy) = match flag {
+		0..10 => {
+			let byte = glyphs.read_bytes::<u8>()? as i32;
+			(0, with_sign(flag as u8, ((flag & 14) << 7) + byte))
+		},
+		10..20 => {
+			let byte = glyphs.read_bytes::<u8>()? as i32;
+			(with_sign(flag as u8, (((flag - 10) & 14) << 7) + byte), 0)
+		},
+		20..84 => {
+			let base = flag - 20;
+			let byte = glyphs.read_bytes::<u8>()? as i32;
+			(with_sign(flag as u8, 1 + (base & 0x30) + (byte >> 4)), with_sign((flag >> 1) as u8, 1 + ((base & 0x0C) << 2) + (byte & 0x0F)))
+		},
+		84..120 => {
+			let base = flag - 84;
+			let x_byte = glyphs.read_bytes::<u8>()? as i32;
+			let y_byte = glyphs.read_bytes::<u8>()? as i32;
+			(with_sign(flag as u8, 1 + ((base / 12) << 8) + x_byte), with_sign((flag >> 1) as u8, 1 + (((base % 12) >> 2) << 8) + y_byte))
+		},
+		120..124 => {
+			let bytes: [u8; 3] = [glyphs.read_bytes()?, glyphs.read_bytes()?, glyphs.read_bytes()?];
+			let (first, second, third) = (bytes[0] as i32, bytes[1] as i32, bytes[2] as i32);
+			(with_sign(flag as u8, (first << 4) + (second >> 4)), with_sign((flag >> 1) as u8, ((second & 0x0F) << 8) + third))
+		},
+		_ => {
+			let dx: u16 = glyphs.read_bytes()?;
+			let dy: u16 = glyphs.read_bytes()?;
+			(with_sign(flag as u8, dx as i32), with_sign((flag >> 1) as u8, dy as i32))
+		},
+	};
+	Ok((dx as i16, dy as i16))
+}
+
+/// Encodes the points of a simple glyph as flags then x and y coordinates, the way the glyf table stores them.
+fn encode_points(points: &[(i16, i16, bool)], overlaps: bool) -> Vec<u8> {
+	let mut point_flags: Vec<u8> = Vec::with_capacity(points.len());
+	let mut x_coordinates: Vec<u8> = Vec::new();
+	let mut y_coordinates: Vec<u8> = Vec::new();
+	let (mut previous_x, mut previous_y) = (0i16, 0i16);
+	for (point_index, (x, y, on_curve)) in points.iter().enumerate() {
+		let mut flag = if *on_curve { ON_CURVE_POINT } else { 0 };
+		if point_index == 0 && overlaps {
+			flag |= OVERLAP_SIMPLE;
+		}
+		let (dx, dy) = (x.wrapping_sub(previous_x), y.wrapping_sub(previous_y));
+		(previous_x, previous_y) = (*x, *y);
+
+		if dx == 0 {
+			flag |= X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR;
+		} else if dx.unsigned_abs() < 256 {
+			flag |= X_SHORT_VECTOR | if dx > 0 { X_IS_SAME_OR_POSITIVE_X_SHORT_VECTOR } else { 0 };
+			x_coordinates.push(dx.unsigned_abs() as u8);
+		} else {
+			x_coordinates.extend(dx.to_be_bytes());
+		}
+		if dy == 0 {
+			flag |= Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR;
+		} else if dy.unsigned_abs() < 256 {
+			flag |= Y_SHORT_VECTOR | if dy > 0 { Y_IS_SAME_OR_POSITIVE_Y_SHORT_VECTOR } else { 0 };
+			y_coordinates.push(dy.unsigned_abs() as u8);
+		} else {
+			y_coordinates.extend(dy.to_be_bytes());
+		}
+		point_flags.push(flag);
+	}
+
+	// Runs of the same flag are stored once with a repeat count.
+	let mut encoded: Vec<u8> = Vec::with_capacity(point_flags.len() + x_coordinates.len() + y_coordinates.len());
+	let mut flag_index = 0;
+	while flag_index < point_flags.len() {
+		let flag = point_flags[flag_index];
+		let run_length = point_flags[flag_index..].iter().take(256).take_while(|other_flag| **other_flag == flag).count();
+		if run_length > 1 {
+			encoded.push(flag | REPEAT_FLAG);
+			encoded.push((run_length - 1) as u8);
+		} else {
+			encoded.push(flag);
+		}
+		flag_index += run_length;
+	}
+	encoded.extend(x_coordinates);
+	encoded.extend(y_coordinates);
+	encoded
+}
+
+/// Rebuilds the hmtx table, where the transform may leave out left side bearings that equal the glyph's xMin.
+fn reconstruct_horizontal_metrics_table(data: &[u8], number_of_h_metrics: u16, x_mins: &[i16]) -> Result<Vec<u8>, TrueTypeFontReaderError> {
+	let mut ttf_reader = TrueTypeFontReader::from_source(Cursor::new(data));
+	let flags: u8 = ttf_reader.read_bytes()?;
+	if flags & (PROPORTIONAL_LEFT_SIDE_BEARINGS_ABSENT_FLAG | MONOSPACED_LEFT_SIDE_BEARINGS_ABSENT_FLAG) == 0 {
+		return Err(TrueTypeFontReaderError::InvalidData(TableTag::HorizontalMetricsTable, "transformed hmtx table should leave out some left side bearings"));
+	}
+	if number_of_h_metrics == 0 || number_of_h_metrics as usize > x_mins.len() {
+		return Err(TrueTypeFontReaderError::InvalidData(TableTag::HorizontalMetricsTable, "number of horizontal metrics should be between 1 and the number of glyphs"));
+	}
+
+	let mut advance_widths: Vec<u16> = Vec::with_capacity(number_of_h_metrics as usize);
+	for _ in 0..number_of_h_metrics {
+		advance_widths.push(ttf_reader.read_bytes()?);
+	}
+
+	let mut horizontal_metrics_table: Vec<u8> = Vec::with_capacity(4 * number_of_h_metrics as usize + 2 * (x_mins.len() - number_of_h_metrics as usize));
+	for (glyph_index, x_min) in x_mins.iter().enumerate() {
+		let left_side_bearings_absent = match glyph_index < number_of_h_metrics as usize {
+			true => flags & PROPORTIONAL_LEFT_SIDE_BEARINGS_ABSENT_FLAG != 0,
+			false => flags & MONOSPACED_LEFT_SIDE_BEARINGS_ABSENT_FLAG != 0,
+		};
+		let left_side_bearing: i16 = if left_side_bearings_absent { *x_min } else { ttf_reader.read_bytes()? };
+		if let Some(advance_width) = advance_widths.get(glyph_index) {
+			horizontal_metrics_table.extend(advance_width.to_be_bytes());
+		}
+		horizontal_metrics_table.extend(left_side_bearing.to_be_bytes());
+	}
+	Ok(horizontal_metrics_table)
+}
@@ -0,0 +1,138 @@
+use std::io::{self, Read, Seek};
+
+use crate::font::FontLoadError;
+use crate::inflate;
+use crate::ttf_reader::{FromBytes, FromTTFReader, TableTag, TrueTypeFontReader, TrueTypeFontReaderError};
+
+pub const WOFF_SIGNATURE: u32 = 0x774F4646; // 'wOFF'
+
+const WOFF_TAG: TableTag = TableTag::Other(['w', 'O', 'F', 'F']);
+
+#[derive(Debug, Clone, Copy)]
+pub struct WebOpenFontFormatTableDirectoryEntry {
+	pub tag: [u8; 4],
+	pub offset: u32,
+	pub compressed_length: u32,
+	pub original_length: u32,
+	pub original_checksum: u32,
+}
+
+pub struct WebOpenFontFormatHeader {
+	pub flavor: u32, // sfnt version of the font inside
+	pub length: u32,
+	pub total_sfnt_size: u32,
+	pub table_directory_entries: Vec<WebOpenFontFormatTableDirectoryEntry>,
+}
+
+impl WebOpenFontFormatTableDirectoryEntry {
+	pub fn table_tag(&self) -> TableTag {
+		TableTag::from_be_bytes(self.tag)
+	}
+}
+
+impl FromBytes for WebOpenFontFormatTableDirectoryEntry {
+	type Bytes = [u8; 20];
+
+	fn from_be_bytes(bytes: Self::Bytes) -> Self {
+		WebOpenFontFormatTableDirectoryEntry {
+			tag: bytes[0..4].try_into().unwrap(),
+			offset: u32::from_be_bytes(bytes[4..8].try_into().unwrap()),
+			compressed_length: u32::from_be_bytes(bytes[8..12].try_into().unwrap()),
+			original_length: u32::from_be_bytes(bytes[12..16].try_into().unwrap()),
+			original_checksum: u32::from_be_bytes(bytes[16..20].try_into().unwrap()),
+		}
+	}
+}
+
+impl FromTTFReader for WebOpenFontFormatHeader {
+	type Input = ();
+
+	fn read<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>, _: ()) -> Result<WebOpenFontFormatHeader, TrueTypeFontReaderError> {
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(0))?;
+
+		let signature: u32 = ttf_reader.read_bytes()?;
+		if signature != WOFF_SIGNATURE {
+			return Err(TrueTypeFontReaderError::InvalidData(WOFF_TAG, "WOFF file should start with the wOFF signature"));
+		}
+		let flavor: u32 = ttf_reader.read_bytes()?;
+		let length: u32 = ttf_reader.read_bytes()?;
+		let number_of_tables: u16 = ttf_reader.read_bytes()?;
+		ttf_reader.skip(2)?; // reserved
+		let total_sfnt_size: u32 = ttf_reader.read_bytes()?;
+		ttf_reader.skip(24)?; // the font's own version, then the metadata and private data blocks, none of which are needed to render
+
+		let mut table_directory_entries: Vec<WebOpenFontFormatTableDirectoryEntry> = Vec::with_capacity(number_of_tables as usize);
+		for _ in 0..number_of_tables {
+			table_directory_entries.push(ttf_reader.read_bytes()?);
+		}
+
+		Ok(WebOpenFontFormatHeader {
+			flavor,
+			length,
+			total_sfnt_size,
+			table_directory_entries,
+		})
+	}
+}
+
+/// Unpacks a WOFF file into the sfnt font it contains, inflating any compressed tables.
+pub fn decode<Source: Read + Seek>(ttf_reader: &mut TrueTypeFontReader<Source>) -> Result<Vec<u8>, FontLoadError> {
+	let source_length = ttf_reader.length()?;
+	let header: WebOpenFontFormatHeader = ttf_reader.read(())?;
+
+	let mut tables: Vec<([u8; 4], u32, Vec<u8>)> = Vec::with_capacity(header.table_directory_entries.len());
+	for entry in header.table_directory_entries.iter().copied() {
+		let table_tag = entry.table_tag();
+		if entry.offset as u64 + entry.compressed_length as u64 > source_length {
+			return Err(FontLoadError::OffsetOutOfRange(table_tag, entry.offset as u64));
+		}
+		if entry.compressed_length > entry.original_length {
+			return Err(FontLoadError::ReaderError(TrueTypeFontReaderError::InvalidData(table_tag, "WOFF table shouldn't be longer compressed than uncompressed")));
+		}
+
+		ttf_reader.buffer_reader.seek(io::SeekFrom::Start(entry.offset as u64)).map_err(|error| FontLoadError::ReaderError(error.into()))?;
+		let mut data = vec![0u8; entry.compressed_length as usize];
+		ttf_reader.buffer_reader.read_exact(&mut data).map_err(|error| FontLoadError::from_table_error(error.into(), table_tag))?;
+
+		// Tables are only compressed when that makes them smaller, otherwise they are stored as they are.
+		if entry.compressed_length < entry.original_length {
+			data = inflate::zlib_decompress(&data, entry.original_length as usize).map_err(|error| FontLoadError::DecompressionFailed(table_tag, error))?;
+			if data.len() != entry.original_length as usize {
+				return Err(FontLoadError::ReaderError(TrueTypeFontReaderError::InvalidData(table_tag, "WOFF table should inflate to its original length")));
+			}
+		}
+		tables.push((entry.tag, entry.original_checksum, data));
+	}
+
+	Ok(build_sfnt(header.flavor, &tables))
+}
+
+/// Lays tables, given as their tag, checksum and data, out as an sfnt font, each on a four byte boundary and in the order given.
+pub(crate) fn build_sfnt(sfnt_version: u32, tables: &[([u8; 4], u32, Vec<u8>)]) -> Vec<u8> {
+	let number_of_tables = tables.len() as u16;
+	let entry_selector = if number_of_tables == 0 { 0 } else { 15 - number_of_tables.leading_zeros() as u16 };
+	let search_range = (1u16 << entry_selector).wrapping_mul(16);
+	let range_shift = number_of_tables.wrapping_mul(16).wrapping_sub(search_range);
+
+	let mut sfnt: Vec<u8> = Vec::new();
+	sfnt.extend(sfnt_version.to_be_bytes());
+	sfnt.extend(number_of_tables.to_be_bytes());
+	sfnt.extend(search_range.to_be_bytes());
+	sfnt.extend(entry_selector.to_be_bytes());
+	sfnt.extend(range_shift.to_be_bytes());
+
+	let mut offset = 12 + 16 * tables.len() as u32;
+	for (tag, checksum, data) in tables {
+		sfnt.extend(tag);
+		sfnt.extend(checksum.to_be_bytes());
+		sfnt.extend(offset.to_be_bytes());
+		sfnt.extend((data.len() as u32).to_be_bytes());
+		offset += (data.len() as u32).next_multiple_of(4);
+	}
+
+	for (_, _, data) in tables {
+		sfnt.extend(data);
+		sfnt.resize(sfnt.len().next_multiple_of(4), 0);
+	}
+	sfnt
+}